@@ -366,8 +366,10 @@ fn rocket() -> _ {
     // Start RPC server in background
     let rpc_blockchain = Arc::clone(&blockchain);
     tokio::spawn(async move {
-        let rpc_server = RpcServer::new(rpc_blockchain);
-        if let Err(e) = rpc_server.start("127.0.0.1:18332").await {
+        let bind = std::env::var("QC_BACKEND_RPC_BIND").unwrap_or_else(|_| "127.0.0.1:18332".to_string());
+        let rpc_server = RpcServer::new(rpc_blockchain)
+            .with_auth_token(std::env::var("QC_BACKEND_RPC_TOKEN").ok());
+        if let Err(e) = rpc_server.start(&bind).await {
             println!("RPC server error: {}", e);
         }
     });
@@ -5,12 +5,16 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use crate::blockchain::{Blockchain, Transaction};
 use chrono::Utc;
+use subtle::ConstantTimeEq;
 
 #[derive(Serialize, Deserialize)]
 pub struct RpcRequest {
     pub method: String,
     pub params: serde_json::Value,
     pub id: u64,
+    /// Must match the server's token when one is configured
+    #[serde(default)]
+    pub auth: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -22,11 +26,18 @@ pub struct RpcResponse {
 
 pub struct RpcServer {
     blockchain: Arc<RwLock<Blockchain>>,
+    auth_token: Option<Arc<str>>,
 }
 
 impl RpcServer {
     pub fn new(blockchain: Arc<RwLock<Blockchain>>) -> Self {
-        RpcServer { blockchain }
+        RpcServer { blockchain, auth_token: None }
+    }
+
+    /// Reject requests whose `auth` field doesn't match `token`
+    pub fn with_auth_token(mut self, token: Option<String>) -> Self {
+        self.auth_token = token.map(Arc::from);
+        self
     }
 
     pub async fn start(&self, addr: &str) -> Result<(), Box<dyn std::error::Error>> {
@@ -36,6 +47,7 @@ impl RpcServer {
         loop {
             let (mut socket, _) = listener.accept().await?;
             let blockchain = Arc::clone(&self.blockchain);
+            let auth_token = self.auth_token.clone();
 
             tokio::spawn(async move {
                 let mut buf = vec![0; 1024];
@@ -46,7 +58,15 @@ impl RpcServer {
                         Ok(n) => {
                             let request_str = String::from_utf8_lossy(&buf[0..n]);
                             if let Ok(request) = serde_json::from_str::<RpcRequest>(&request_str) {
-                                let response = Self::handle_request(request, blockchain.clone()).await;
+                                let response = if Self::is_authorized(&request, auth_token.as_deref()) {
+                                    Self::handle_request(request, blockchain.clone()).await
+                                } else {
+                                    RpcResponse {
+                                        result: None,
+                                        error: Some("Unauthorized".to_string()),
+                                        id: request.id,
+                                    }
+                                };
                                 let response_json = serde_json::to_string(&response).unwrap();
                                 
                                 if socket.write_all(response_json.as_bytes()).await.is_err() {
@@ -61,6 +81,14 @@ impl RpcServer {
         }
     }
 
+    fn is_authorized(request: &RpcRequest, token: Option<&str>) -> bool {
+        match token {
+            None => true,
+            // Only the token's length can leak through timing
+            Some(token) => request.auth.as_deref().is_some_and(|auth| auth.as_bytes().ct_eq(token.as_bytes()).into()),
+        }
+    }
+
     async fn handle_request(
         request: RpcRequest,
         blockchain: Arc<RwLock<Blockchain>>,
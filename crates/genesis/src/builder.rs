@@ -2,6 +2,7 @@
 
 use anyhow::{Result, Context};
 use chrono::{DateTime, Utc};
+use qc_types::target::compact_to_target;
 use crate::{
     config::{ChainSpec, GenesisAllocation},
     block::{GenesisBlock, BlockHeader, GenesisTransaction, TransactionType, GenesisMetadata, CreationParams},
@@ -14,6 +15,7 @@ pub struct GenesisBuilder {
     chain_spec: ChainSpec,
     deterministic: bool,
     custom_seed: Option<[u8; 32]>,
+    mine: bool,
 }
 
 impl GenesisBuilder {
@@ -23,6 +25,7 @@ impl GenesisBuilder {
             chain_spec,
             deterministic: true,
             custom_seed: None,
+            mine: false,
         }
     }
     
@@ -38,6 +41,12 @@ impl GenesisBuilder {
         self
     }
     
+    /// Grind the header nonce until the hash meets the genesis difficulty
+    pub fn mined(mut self, mine: bool) -> Self {
+        self.mine = mine;
+        self
+    }
+    
     /// Build the genesis block
     pub fn build(self) -> Result<GenesisBlock> {
         // Validate chain specification first
@@ -106,6 +115,12 @@ impl GenesisBuilder {
             metadata,
         ).context("Failed to create genesis block")?;
         
+        // Mine before signing so the signature covers the final nonce
+        if self.mine {
+            GenesisMiner::mine_genesis(&mut block)
+                .context("Failed to mine genesis block")?;
+        }
+        
         // Sign the block
         if self.deterministic {
             let header_bytes = bincode::serialize(&block.header)
@@ -285,31 +300,7 @@ impl GenesisMiner {
     
     /// Convert difficulty to target
     fn difficulty_to_target(difficulty: u32) -> [u8; 32] {
-        let mut target = [0u8; 32];
-        
-        // Extract exponent and mantissa from compact format
-        let exponent = (difficulty >> 24) as usize;
-        let mantissa = difficulty & 0x00ffffff;
-        
-        if exponent <= 3 {
-            // Special case for very small targets
-            let mantissa_bytes = mantissa.to_be_bytes();
-            for i in 0..(4 - exponent).min(32) {
-                target[31 - i] = mantissa_bytes[3 - i];
-            }
-        } else if exponent < 32 {
-            // Normal case
-            let mantissa_bytes = mantissa.to_be_bytes();
-            let start_pos = 32 - exponent;
-            for i in 1..4 {
-                if start_pos + i < 32 {
-                    target[start_pos + i] = mantissa_bytes[i];
-                }
-            }
-        }
-        // If exponent >= 32, target remains all zeros (impossible target)
-        
-        target
+        compact_to_target(difficulty).to_be_bytes()
     }
     
     /// Check if hash meets difficulty target
@@ -176,6 +176,18 @@ impl ChainSpec {
         Ok(spec)
     }
     
+    /// Load regtest configuration
+    pub fn load_regtest() -> Result<Self> {
+        let mut spec = Self::load_from_file("chain_spec.toml")?;
+        spec.genesis = Self::regtest_genesis_config();
+        // Compact form of the all-ones target, so any hash mines a block
+        // with overwhelming probability
+        spec.consensus.genesis_difficulty = 0x2100ffff;
+        spec.network_protocol.default_port = 18444;
+        spec.network_protocol.magic_bytes = [0x51, 0x54, 0x43, 0x52]; // "QTCR"
+        Ok(spec)
+    }
+    
     /// Default genesis configuration (no premine)
     fn default_genesis_config() -> GenesisConfig {
         GenesisConfig {
@@ -233,6 +245,22 @@ impl ChainSpec {
         }
     }
     
+    /// Regtest genesis configuration
+    fn regtest_genesis_config() -> GenesisConfig {
+        GenesisConfig {
+            timestamp: DateTime::parse_from_rfc3339("2025-01-15T00:00:02Z")
+                .unwrap()
+                .with_timezone(&Utc),
+            message: "QuantumCoin Regtest Genesis - Local Testing Only".to_string(),
+            allocations: vec![],
+            coinbase: GenesisCoinbaseConfig {
+                message: "QuantumCoin Regtest - Deterministic Local Chain".to_string(),
+                extra_nonce_size: 8,
+                flags: "QuantumCoin/2.0-regtest".to_string(),
+            },
+        }
+    }
+    
     /// Calculate the total genesis allocation amount
     pub fn total_genesis_allocation(&self) -> u64 {
         self.genesis.allocations.iter().map(|a| a.amount).sum()
@@ -289,4 +317,15 @@ mod tests {
         assert_eq!(spec.supply.premine, 0);
         assert_eq!(spec.total_genesis_allocation(), 0);
     }
+
+    #[test]
+    fn test_regtest_easiest_target() {
+        let spec = ChainSpec::load_regtest().unwrap();
+        assert!(spec.validate().is_ok());
+        assert_eq!(
+            spec.consensus.genesis_difficulty,
+            qc_types::target::target_to_compact(qc_types::target::U256::MAX)
+        );
+        assert_ne!(spec.network_protocol.magic_bytes, ChainSpec::load_testnet().unwrap().network_protocol.magic_bytes);
+    }
 }
@@ -31,8 +31,44 @@ pub fn create_testnet_genesis() -> Result<GenesisBlock> {
     builder.build()
 }
 
+/// Create a regtest genesis block, mined against the easiest target
+pub fn create_regtest_genesis() -> Result<GenesisBlock> {
+    let chain_spec = ChainSpec::load_regtest()?;
+    let builder = GenesisBuilder::new(chain_spec).mined(true);
+    builder.build()
+}
+
 /// Verify a genesis block against the chain specification
 pub fn verify_genesis_block(block: &GenesisBlock, chain_spec: &ChainSpec) -> Result<bool> {
     let verifier = GenesisVerifier::new(chain_spec);
     verifier.verify(block)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_regtest_genesis_is_valid_and_mined() {
+        let genesis = create_regtest_genesis().unwrap();
+        assert!(genesis.validate().is_ok());
+
+        let chain_spec = ChainSpec::load_regtest().unwrap();
+        assert!(verify_genesis_block(&genesis, &chain_spec).unwrap());
+
+        // The easiest target is met by almost any hash
+        assert!(genesis.header.nonce < 16);
+        let target = qc_types::target::compact_to_target(genesis.header.difficulty);
+        assert!(qc_types::target::U256::from_be_bytes(genesis.hash) <= target);
+    }
+
+    #[test]
+    fn test_regtest_genesis_is_reproducible_and_distinct() {
+        let first = create_regtest_genesis().unwrap();
+        let second = create_regtest_genesis().unwrap();
+        assert_eq!(first.hash, second.hash);
+
+        assert_ne!(first.hash, create_mainnet_genesis().unwrap().hash);
+        assert_ne!(first.hash, create_testnet_genesis().unwrap().hash);
+    }
+}
@@ -1,5 +1,7 @@
 //! Block structure and validation for QuantumCoin
 
+use crate::transaction::WITNESS_SCALE_FACTOR;
+use qc_types::target::{compact_to_target, U256};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
@@ -41,6 +43,16 @@ pub struct BlockHeader {
     pub nonce: u64,
 }
 
+impl BlockHeader {
+    /// Hash of this header, which is also the block hash
+    pub fn hash(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        let serialized = bincode::serialize(self).unwrap();
+        hasher.update(&serialized);
+        hasher.finalize().into()
+    }
+}
+
 /// Complete block with header and transactions
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Block {
@@ -54,18 +66,18 @@ pub struct Block {
 impl Block {
     /// Calculate the hash of this block
     pub fn hash(&self) -> [u8; 32] {
-        let mut hasher = Sha256::new();
-        let serialized = bincode::serialize(&self.header).unwrap();
-        hasher.update(&serialized);
-        hasher.finalize().into()
+        self.header.hash()
+    }
+    
+    /// Block weight. Blocks reference transactions by id and carry no
+    /// witness data, so every byte counts `WITNESS_SCALE_FACTOR` times.
+    pub fn weight(&self) -> usize {
+        bincode::serialized_size(self).unwrap() as usize * WITNESS_SCALE_FACTOR
     }
     
     /// Verify the block's proof of work
     pub fn verify_pow(&self) -> bool {
-        let hash = self.hash();
-        let hash_value = u256_from_bytes(&hash);
-        let target = difficulty_to_target(self.header.difficulty);
-        hash_value < target
+        U256::from_be_bytes(self.hash()) <= compact_to_target(self.header.difficulty)
     }
     
     /// Create genesis block
@@ -84,21 +96,6 @@ impl Block {
     }
 }
 
-// Utility functions for difficulty calculations
-fn difficulty_to_target(difficulty: u32) -> [u8; 32] {
-    // Simplified difficulty to target conversion
-    let mut target = [0xff; 32];
-    target[0] = (difficulty >> 24) as u8;
-    target[1] = (difficulty >> 16) as u8;
-    target[2] = (difficulty >> 8) as u8;
-    target[3] = difficulty as u8;
-    target
-}
-
-fn u256_from_bytes(bytes: &[u8; 32]) -> [u8; 32] {
-    *bytes
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -117,4 +114,19 @@ mod tests {
         let hash2 = genesis.hash();
         assert_eq!(hash1, hash2, "Hash should be deterministic");
     }
+    
+    #[test]
+    fn test_verify_pow() {
+        let mut block = Block::genesis();
+        // Unmined, the genesis header misses its 0x1d00ffff target
+        assert!(!block.verify_pow());
+        
+        // One hash in 512 meets this target
+        block.header.difficulty = 0x1f7fffff;
+        while !block.verify_pow() {
+            block.header.nonce += 1;
+        }
+        let hash = block.hash();
+        assert!(hash[0] == 0 && hash[1] <= 0x7f);
+    }
 }
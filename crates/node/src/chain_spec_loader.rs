@@ -37,6 +37,8 @@ pub struct RawNetworkSpec {
     pub symbol: String,
     pub decimals: u8,
     pub version: String,
+    #[serde(default)]
+    pub kind: qc_validation::NetworkKind,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,6 +49,9 @@ pub struct RawConsensusSpec {
     pub difficulty_adjustment_period: u64,
     pub max_difficulty_change: f64,
     pub genesis_difficulty: String, // Hex string like "0x1d00ffff"
+    /// Least cumulative work a peer's header chain must prove before we sync from it
+    #[serde(default)]
+    pub minimum_chain_work: Option<String>, // Hex string like "0x0000000000000000000000000000000000000000000000000000000100010001"
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -236,6 +241,7 @@ impl ChainSpecLoader {
                 symbol: raw.network.symbol,
                 decimals: raw.network.decimals,
                 version: raw.network.version,
+                kind: raw.network.kind,
             },
             consensus,
             supply: crate::consensus_engine::SupplySpec {
@@ -361,6 +367,12 @@ impl ChainSpecLoader {
                 .with_context(|| format!("Invalid difficulty: {}", spec.genesis_difficulty))?
         };
         
+        let minimum_chain_work = match &spec.minimum_chain_work {
+            Some(work) => u128::from_str_radix(work.trim_start_matches("0x"), 16)
+                .with_context(|| format!("Invalid minimum chain work: {}", work))?,
+            None => 0,
+        };
+        
         info!("Consensus: {} with {} hash, {}s blocks, difficulty adjustment every {} blocks",
               spec.algorithm, spec.hash_function, spec.target_block_time, spec.difficulty_adjustment_period);
         
@@ -371,6 +383,7 @@ impl ChainSpecLoader {
             difficulty_adjustment_period: spec.difficulty_adjustment_period,
             max_difficulty_change: spec.max_difficulty_change,
             genesis_difficulty,
+            minimum_chain_work,
         })
     }
     
@@ -543,6 +556,7 @@ impl ChainSpecLoader {
                 symbol: "QTC-TEST".to_string(),
                 decimals: 8,
                 version: "2.0.0-test".to_string(),
+                kind: qc_validation::NetworkKind::Regtest,
             },
             consensus: crate::consensus_engine::ConsensusSpec {
                 algorithm: "proof_of_work".to_string(),
@@ -551,6 +565,7 @@ impl ChainSpecLoader {
                 difficulty_adjustment_period: 10, // Every 10 blocks for testing
                 max_difficulty_change: 4.0,
                 genesis_difficulty: 0x207fffff, // Lower difficulty for testing
+                minimum_chain_work: 0,
             },
             supply: crate::consensus_engine::SupplySpec {
                 max_supply: 1_000_000_00000000, // 1M coins for testing
@@ -635,6 +650,7 @@ target_block_time = 600
 difficulty_adjustment_period = 2016
 max_difficulty_change = 4.0
 genesis_difficulty = "0x1d00ffff"
+minimum_chain_work = "0x100010001"
 
 [supply]
 max_supply = 22000000000000000
@@ -738,6 +754,7 @@ checksum = "blake3:test"
         assert_eq!(spec.network.name, "quantumcoin-test");
         assert_eq!(spec.consensus.target_block_time, 600);
         assert_eq!(spec.consensus.genesis_difficulty, 0x1d00ffff);
+        assert_eq!(spec.consensus.minimum_chain_work, 0x100010001);
     }
     
     #[test]
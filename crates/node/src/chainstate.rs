@@ -1,57 +1,174 @@
 use crate::storage::Storage;
 use crate::pow::{sha256d, check_proof_of_work};
-use crate::target::bits_to_target;
+use crate::rejections::{RejectReason, RejectedKind, Rejection, RejectionLog};
 use qc_types::*;
-use qc_validation::{ChainSpec, validate_transaction, block_subsidy, merkle_root};
-use anyhow::{Result, bail};
+use qc_types::target::{compact_to_target, target_to_work};
+use qc_validation::key_reuse::output_pubkey;
+use qc_validation::{ChainSpec, KeyReusePolicy, validate_transaction_with, block_subsidy, check_block_sigops, check_block_weight, merkle_root};
+use anyhow::{bail, Result};
 use rocksdb::WriteBatch;
 use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+/// Capacity of the chain event channel; slow subscribers skip to newer events
+pub const CHAIN_EVENT_BUFFER: usize = 64;
+
+/// Notifications published as the chain advances
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChainEvent {
+    BlockConnected(ConnectedBlockInfo),
+}
+
+/// Facts about a connected block, worked out once while connecting it so
+/// consumers don't each re-derive them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectedBlockInfo {
+    pub height: u64,
+    pub hash: Hash32,
+    pub tx_count: usize,
+    pub total_fees: Amount,
+    pub subsidy: Amount,
+    /// Serialized size in bytes
+    pub size: u64,
+    pub weight: u64,
+}
+
+/// Result of submitting a block that didn't fail fresh validation
+#[derive(Debug, Clone)]
+pub enum SubmitOutcome {
+    Accepted(Hash32),
+    /// Already stored; nothing was reapplied
+    Duplicate(Hash32),
+    /// Rejected earlier; carries the original reason
+    KnownInvalid(Rejection),
+}
+
+/// How a chain tip relates to the active chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TipStatus {
+    /// The tip of the chain we build on
+    Active,
+    /// Every block on the branch is stored and none was rejected
+    ValidFork,
+    /// The branch has a block rejected under its header hash
+    Invalid,
+    /// Some block on the branch is known only by its header
+    HeadersOnly,
+}
+
+impl TipStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TipStatus::Active => "active",
+            TipStatus::ValidFork => "valid-fork",
+            TipStatus::Invalid => "invalid",
+            TipStatus::HeadersOnly => "headers-only",
+        }
+    }
+}
+
+/// A known header with no known child, or the active tip
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainTip {
+    pub height: u64,
+    pub hash: Hash32,
+    /// Blocks between the tip and where its branch leaves the active chain
+    pub branchlen: u64,
+    pub chainwork: u128,
+    pub status: TipStatus,
+}
 
 pub struct ChainState<'a> {
     pub spec: &'a ChainSpec,
     pub store: &'a Storage,
+    /// Receives a `BlockConnected` for every applied block, if set
+    pub events: Option<&'a broadcast::Sender<ChainEvent>>,
+    /// Counts every rejected block and transaction by reason, if set
+    pub rejections: Option<&'a RejectionLog>,
 }
 
 impl<'a> ChainState<'a> {
-    pub fn apply_block(&self, height: u64, block: &Block) -> Result<()> {
-        // Verify proof of work
-        let target = bits_to_target(block.header.bits);
+    /// Apply a block unless it is already stored or was rejected before, so
+    /// resubmitting the same block is a no-op
+    pub fn submit_block(&self, height: u64, block: &Block) -> Result<SubmitOutcome> {
+        let hash = self.block_hash(&block.header);
+        if self.store.get_block(&hash)?.is_some() {
+            return Ok(SubmitOutcome::Duplicate(hash));
+        }
+        if let Some(rejection) = self.rejections.and_then(|r| r.block_rejection(&hash)) {
+            return Ok(SubmitOutcome::KnownInvalid(rejection));
+        }
+        self.apply_block(height, block)?;
+        Ok(SubmitOutcome::Accepted(hash))
+    }
+
+    /// Validate `block` and connect it at `height`, returning what it added
+    pub fn apply_block(&self, height: u64, block: &Block) -> Result<ConnectedBlockInfo> {
+        // Verify proof of work, unless this is a regtest chain running without it
+        let target = compact_to_target(block.header.bits);
         let block_hash = sha256d(&block.header);
-        if !check_proof_of_work(&block_hash, target) {
-            bail!("Invalid proof of work");
+        if self.spec.pow_required() && !check_proof_of_work(&block_hash, &target) {
+            return Err(self.reject_block(block, RejectReason::BadProofOfWork, "Invalid proof of work"));
         }
+        self.index_header(&block.header, height)?;
 
         // Verify merkle root
         let calculated_merkle = merkle_root(&block.txs);
         if calculated_merkle.0 != block.header.merkle_root.0 {
-            bail!("Merkle root mismatch");
+            return Err(self.reject_block(block, RejectReason::BadMerkleRoot, "Merkle root mismatch"));
+        }
+
+        // Bound the block's weight and signature verifications before doing any
+        if let Err(e) = check_block_weight(self.spec, block) {
+            return Err(self.reject_block(block, RejectReason::Policy, e.to_string()));
+        }
+        if let Err(e) = check_block_sigops(self.spec, &block.txs) {
+            return Err(self.reject_block(block, RejectReason::Policy, e.to_string()));
         }
 
         // TODO: Verify timestamp, previous block linkage, etc.
 
+        let verify_signatures = !self.assumed_valid(&block.header, height)?;
+
         let mut wb = WriteBatch::default();
         
         // Build UTXO lookup function
         let lookup = |op: &OutPoint| self.store.get_utxo(op).ok().flatten();
 
         // Validate and apply transactions
+        let subsidy = block_subsidy(self.spec, height);
+        let mut total_fees: Amount = 0;
         for (i, tx) in block.txs.iter().enumerate() {
-            if i == 0 {
-                // Coinbase transaction validation
-                let subsidy = block_subsidy(self.spec, height);
-                let total_out: i128 = tx.vout.iter().map(|o| o.value as i128).sum();
-                
-                // TODO: Add transaction fees to subsidy calculation
-                if total_out > subsidy as i128 { 
-                    bail!("Coinbase output exceeds subsidy + fees"); 
-                }
-            } else {
+            // The coinbase is checked once the fees it may claim are known
+            if i > 0 {
                 // Regular transaction validation
-                validate_transaction(self.spec, height, tx, false, &lookup)
-                    .map_err(|e| anyhow::anyhow!("Transaction validation failed: {}", e))?;
+                if let Err(e) = validate_transaction_with(self.spec, height, tx, false, verify_signatures, &lookup) {
+                    if let Some(rejections) = self.rejections {
+                        rejections.record(RejectedKind::Transaction, &self.calculate_txid(tx), (&e).into(), e.to_string());
+                    }
+                    return Err(self.reject_block(
+                        block,
+                        RejectReason::InvalidTransaction,
+                        format!("Transaction validation failed: {}", e),
+                    ));
+                }
                 
-                // Remove spent UTXOs
+                // Validation checked the inputs cover the outputs
+                total_fees += tx.fee(|op| lookup(op).map(|(value, ..)| value)).unwrap_or(0);
+
+                // Reuse is flagged, not a consensus failure
+                let reused = self.check_key_reuse(tx, KeyReusePolicy::Warn)?;
+                if !reused.is_empty() {
+                    warn!("Transaction {} pays to revealed pubkeys at outputs {:?}", self.calculate_txid(tx), reused);
+                }
+
+                // Remove spent UTXOs, whose pubkeys this spend reveals
                 for input in &tx.vin {
+                    if let Some((_, kind, ..)) = lookup(&input.prevout) {
+                        self.store.put_revealed_key_batch(&mut wb, output_pubkey(&kind));
+                    }
                     self.store.del_utxo_batch(&mut wb, &input.prevout);
                 }
             }
@@ -68,18 +185,153 @@ impl<'a> ChainState<'a> {
             }
         }
 
-        // Write block and update tip
-        self.store.db.write(wb)?;
+        if let Some(coinbase) = block.txs.first() {
+            let total_out: i128 = coinbase.vout.iter().map(|o| o.value as i128).sum();
+            if total_out > subsidy as i128 + total_fees as i128 {
+                return Err(self.reject_block(block, RejectReason::ExcessiveCoinbase, "Coinbase output exceeds subsidy + fees"));
+            }
+        }
+
+        // UTXO changes, block, tip and txindex land in one write, so a failure
+        // or crash part way leaves none of them applied
         let block_hash = self.block_hash(&block.header);
-        self.store.write_block(&block_hash, block, height)?;
+        self.store.write_block_batch(&mut wb, &block_hash, block, height)?;
+        self.store.db.write(wb)?;
         
         info!("✅ Applied block at height {} with {} transactions", height, block.txs.len());
-        Ok(())
+        let connected = ConnectedBlockInfo {
+            height,
+            hash: block_hash,
+            tx_count: block.txs.len(),
+            total_fees,
+            subsidy,
+            size: bincode::serialized_size(block)?,
+            weight: block.weight(),
+        };
+        if let Some(events) = self.events {
+            // No subscribers is fine
+            let _ = events.send(ChainEvent::BlockConnected(connected.clone()));
+        }
+        Ok(connected)
+    }
+
+    /// Relay check for a transaction paying to pubkeys that a confirmed
+    /// spend already revealed: the reused outputs under `Warn`, and an error
+    /// under `Reject` when there are any
+    pub fn check_key_reuse(&self, tx: &Transaction, policy: KeyReusePolicy) -> Result<Vec<usize>> {
+        Ok(self.store.revealed_keys_for(tx)?.check_relay(tx, policy)?)
+    }
+
+    /// Add `header` to the known header chain at `height`, building on its
+    /// parent's chainwork when the parent is known. Returns the cumulative work.
+    pub fn index_header(&self, header: &BlockHeader, height: u64) -> Result<u128> {
+        if let Some((_, _, chainwork)) = self.store.get_header(&header.hash())? {
+            return Ok(chainwork);
+        }
+        let parent_work = self.store.get_header(&header.prev_block)?.map_or(0, |(_, _, work)| work);
+        let chainwork = parent_work.saturating_add(target_to_work(compact_to_target(header.bits)));
+        self.store.put_header(header, height, chainwork)?;
+        Ok(chainwork)
+    }
+
+    /// Index a peer's header chain, the first header at `start_height`, if it
+    /// proves the spec's minimum chain work. Each header must meet its own
+    /// target and build on the one before; the first builds on whatever work
+    /// its parent has in the index. A chain below the minimum is ignored and
+    /// nothing is indexed. Returns the chainwork of the last header.
+    pub fn accept_headers(&self, start_height: u64, headers: &[BlockHeader]) -> Result<u128> {
+        let Some(first) = headers.first() else { bail!("Empty header chain") };
+        let mut work = self.store.get_header(&first.prev_block)?.map_or(0, |(_, _, work)| work);
+        for (i, header) in headers.iter().enumerate() {
+            if i > 0 && header.prev_block != headers[i - 1].hash() {
+                bail!("Header {} does not build on the one before", start_height + i as u64);
+            }
+            let target = compact_to_target(header.bits);
+            if self.spec.pow_required() && !check_proof_of_work(&sha256d(header), &target) {
+                bail!("Header {} has invalid proof of work", start_height + i as u64);
+            }
+            work = work.saturating_add(target_to_work(target));
+        }
+        let minimum = self.spec.minimum_chain_work();
+        if work < minimum {
+            bail!("Header chain work {} below minimum {}", work, minimum);
+        }
+        for (i, header) in headers.iter().enumerate() {
+            self.index_header(header, start_height + i as u64)?;
+        }
+        Ok(work)
+    }
+
+    /// Every chain tip in the header index, highest first: the active tip
+    /// and each header nothing builds on
+    pub fn chain_tips(&self) -> Result<Vec<ChainTip>> {
+        let headers = self.store.header_entries()?;
+        let parents: HashSet<Hash32> = headers.iter().map(|(_, (header, _, _))| header.prev_block).collect();
+        let active = self.store.get_tip()?;
+        let mut tips = Vec::new();
+        for (hash, (_, height, chainwork)) in headers {
+            if parents.contains(&hash) && active != Some(hash) {
+                continue;
+            }
+            let (branchlen, status) = self.branch_status(&hash)?;
+            tips.push(ChainTip { height, hash, branchlen, chainwork, status });
+        }
+        tips.sort_by(|a, b| b.height.cmp(&a.height).then(a.hash.0.cmp(&b.hash.0)));
+        Ok(tips)
+    }
+
+    /// Length of the branch from `tip` back to the active chain, and what
+    /// its blocks say about it
+    fn branch_status(&self, tip: &Hash32) -> Result<(u64, TipStatus)> {
+        let mut branchlen = 0;
+        let mut status = TipStatus::ValidFork;
+        let mut cursor = *tip;
+        while let Some((header, height, _)) = self.store.get_header(&cursor)? {
+            if self.store.get_block_by_height(height)?.is_some_and(|b| b.header.hash() == cursor) {
+                break;
+            }
+            if self.rejections.is_some_and(|r| r.block_rejection(&cursor).is_some()) {
+                status = TipStatus::Invalid;
+            } else if status == TipStatus::ValidFork && self.store.get_block(&cursor)?.is_none() {
+                status = TipStatus::HeadersOnly;
+            }
+            branchlen += 1;
+            cursor = header.prev_block;
+        }
+        Ok(if branchlen == 0 { (0, TipStatus::Active) } else { (branchlen, status) })
+    }
+
+    /// Whether `header` at `height` is covered by the spec's assume-valid
+    /// block: that block's header is known and this is it or one of its
+    /// ancestors in the header chain. Side branches, and anything while the
+    /// assume-valid header is still unknown, are fully verified.
+    fn assumed_valid(&self, header: &BlockHeader, height: u64) -> Result<bool> {
+        let Some(assume_valid) = self.spec.assume_valid() else { return Ok(false) };
+        let hash = header.hash();
+        let mut cursor = assume_valid;
+        // Walk back from the assume-valid header to this height
+        while let Some((ancestor, ancestor_height, _)) = self.store.get_header(&cursor)? {
+            if ancestor_height <= height {
+                return Ok(ancestor_height == height && cursor == hash);
+            }
+            cursor = ancestor.prev_block;
+        }
+        Ok(false)
+    }
+
+    /// Record `block` as rejected and build the error `apply_block` returns,
+    /// which downcasts to the `Rejection`
+    fn reject_block(&self, block: &Block, reason: RejectReason, detail: impl Into<String>) -> anyhow::Error {
+        let hash = self.block_hash(&block.header);
+        let detail = detail.into();
+        if let Some(rejections) = self.rejections {
+            rejections.record(RejectedKind::Block, &hash, reason, detail.clone());
+        }
+        anyhow::Error::new(Rejection { kind: RejectedKind::Block, hash: hash.to_hex(), reason, detail })
     }
 
     pub fn block_hash(&self, header: &BlockHeader) -> Hash32 {
-        let hash = sha256d(header);
-        Hash32(hash)
+        header.hash()
     }
     
     pub fn calculate_txid(&self, tx: &Transaction) -> Hash32 {
@@ -149,7 +401,7 @@ window_blocks = 30
         "#;
         
         let spec: ChainSpec = toml::from_str(spec_content)?;
-        let cs = ChainState { spec: &spec, store: &storage };
+        let cs = ChainState { spec: &spec, store: &storage, events: None, rejections: None };
         
         // Test block hash calculation
         let header = BlockHeader::new(1, Hash32::zero(), Hash32::zero(), 1700000000, 0x1d00ffff, 12345);
@@ -158,4 +410,450 @@ window_blocks = 30
         
         Ok(())
     }
+
+    #[test]
+    fn test_rejections_counted_by_reason() -> Result<()> {
+        use crate::miner::{build_candidate, mine_block_cpu};
+        use crate::rejections::RejectionLogConfig;
+
+        let temp_dir = tempdir()?;
+        let storage = Storage::open(temp_dir.path())?;
+        let spec: ChainSpec = toml::from_str(include_str!("../../../chain_spec.toml"))?;
+        let rejections = RejectionLog::new(RejectionLogConfig { log: false, ..RejectionLogConfig::default() });
+        let cs = ChainState { spec: &spec, store: &storage, events: None, rejections: Some(&rejections) };
+
+        let coinbase = Transaction::new(1, vec![], vec![TxOut::new_p2pq(block_subsidy(&spec, 1), vec![0u8; 1312])], 1);
+        let spend = |inputs: Vec<OutPoint>| {
+            let vin = inputs.into_iter().map(|op| TxIn::new(op, vec![], false)).collect();
+            Transaction::new(1, vin, vec![TxOut::new_p2pq(spec.txpolicy.dust_threshold_sats, vec![0u8; 1312])], 0)
+        };
+        let mined = |txs: Vec<Transaction>| mine_block_cpu(build_candidate(Hash32::zero(), 0x207fffff, txs), 1_000).unwrap();
+
+        // The difficulty-1 target is out of reach at nonce zero
+        let unmined = build_candidate(Hash32::zero(), 0x1d00ffff, vec![coinbase.clone()]);
+        assert!(cs.apply_block(1, &unmined).is_err());
+
+        let mut bad_merkle = build_candidate(Hash32::zero(), 0x207fffff, vec![coinbase.clone()]);
+        bad_merkle.header.merkle_root = Hash32([9u8; 32]);
+        assert!(cs.apply_block(1, &mine_block_cpu(bad_merkle, 1_000).unwrap()).is_err());
+
+        let outpoint = OutPoint::new(Hash32([5u8; 32]), 0);
+        let double_spend = spend(vec![outpoint.clone(), outpoint.clone()]);
+        assert!(cs.apply_block(1, &mined(vec![coinbase.clone(), double_spend])).is_err());
+        assert!(cs.apply_block(1, &mined(vec![coinbase, spend(vec![outpoint])])).is_err());
+
+        assert_eq!(rejections.count(RejectedKind::Block, RejectReason::BadProofOfWork), 1);
+        assert_eq!(rejections.count(RejectedKind::Block, RejectReason::BadMerkleRoot), 1);
+        assert_eq!(rejections.count(RejectedKind::Block, RejectReason::InvalidTransaction), 2);
+        assert_eq!(rejections.count(RejectedKind::Transaction, RejectReason::DoubleSpend), 1);
+        assert_eq!(rejections.count(RejectedKind::Transaction, RejectReason::MissingInput), 1);
+        assert_eq!(rejections.report().recent.len(), 6);
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_pow_regtest_accepts_unmined_block() -> Result<()> {
+        use crate::miner::build_candidate;
+
+        let mainnet: ChainSpec = toml::from_str(include_str!("../../../chain_spec.toml"))?;
+        let no_pow_on = |kind: &str| -> Result<ChainSpec> {
+            let content = include_str!("../../../chain_spec.toml")
+                .replace("[network]\n", &format!("[network]\nkind = \"{}\"\n", kind))
+                .replace("[consensus]\n", "[consensus]\nno_pow = true\n");
+            Ok(toml::from_str(&content)?)
+        };
+        let coinbase = Transaction::new(1, vec![], vec![TxOut::new_p2pq(block_subsidy(&mainnet, 1), vec![0u8; 1312])], 1);
+        // Nonce zero against the difficulty-1 target; no mining was done
+        let block = build_candidate(Hash32::zero(), 0x1d00ffff, vec![coinbase]);
+        assert_eq!(block.header.nonce, 0);
+
+        let temp_dir = tempdir()?;
+        let storage = Storage::open(temp_dir.path())?;
+        let regtest = no_pow_on("regtest")?;
+        let cs = ChainState { spec: &regtest, store: &storage, events: None, rejections: None };
+        cs.apply_block(1, &block)?;
+        assert_eq!(storage.get_tip_height()?, Some(1));
+
+        // Mainnet and testnet enforce proof of work even with no_pow set
+        for spec in [mainnet, no_pow_on("mainnet")?, no_pow_on("testnet")?] {
+            let temp_dir = tempdir()?;
+            let storage = Storage::open(temp_dir.path())?;
+            let cs = ChainState { spec: &spec, store: &storage, events: None, rejections: None };
+            let err = cs.apply_block(1, &block).unwrap_err();
+            assert!(err.to_string().contains("proof of work"));
+            assert_eq!(storage.get_tip_height()?, None);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_overweight_block_rejected() -> Result<()> {
+        use crate::miner::build_candidate;
+        use crate::rejections::RejectionLogConfig;
+
+        let content = include_str!("../../../chain_spec.toml")
+            .replace("[network]\n", "[network]\nkind = \"regtest\"\n")
+            .replace("[consensus]\n", "[consensus]\nno_pow = true\n");
+        let mut spec: ChainSpec = toml::from_str(&content)?;
+        let coinbase = Transaction::new(1, vec![], vec![TxOut::new_p2pq(block_subsidy(&spec, 1), vec![0u8; 1312])], 1);
+        let block = build_candidate(Hash32::zero(), 0x207fffff, vec![coinbase]);
+
+        let temp_dir = tempdir()?;
+        let storage = Storage::open(temp_dir.path())?;
+        let rejections = RejectionLog::new(RejectionLogConfig { log: false, ..RejectionLogConfig::default() });
+        spec.consensus.max_block_weight = block.weight() - 1;
+        let cs = ChainState { spec: &spec, store: &storage, events: None, rejections: Some(&rejections) };
+        let err = cs.apply_block(1, &block).unwrap_err();
+        assert!(err.to_string().contains("weight limit"));
+        assert_eq!(rejections.count(RejectedKind::Block, RejectReason::Policy), 1);
+
+        // Exactly at the limit is fine
+        let temp_dir = tempdir()?;
+        let storage = Storage::open(temp_dir.path())?;
+        spec.consensus.max_block_weight = block.weight();
+        let cs = ChainState { spec: &spec, store: &storage, events: None, rejections: None };
+        cs.apply_block(1, &block)?;
+        assert_eq!(storage.get_tip_height()?, Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_header_chain_below_minimum_work_ignored() -> Result<()> {
+        use crate::miner::{build_candidate, mine_block_cpu};
+
+        let mut spec: ChainSpec = toml::from_str(include_str!("../../../chain_spec.toml"))?;
+        let temp_dir = tempdir()?;
+        let storage = Storage::open(temp_dir.path())?;
+
+        let mut headers: Vec<BlockHeader> = Vec::new();
+        for _ in 0..3 {
+            let prev = headers.last().map_or(Hash32::zero(), |h| h.hash());
+            headers.push(mine_block_cpu(build_candidate(prev, 0x207fffff, vec![]), 1_000).unwrap().header);
+        }
+        let per_header = target_to_work(compact_to_target(0x207fffff));
+        spec.consensus.minimum_chain_work = Some(format!("{:#x}", per_header * 3));
+        let cs = ChainState { spec: &spec, store: &storage, events: None, rejections: None };
+
+        // Two headers fall short and leave nothing behind
+        let err = cs.accept_headers(1, &headers[..2]).unwrap_err();
+        assert!(err.to_string().contains("below minimum"));
+        assert!(storage.get_header(&headers[0].hash())?.is_none());
+        assert_eq!(storage.best_header_work()?, 0);
+
+        // Three reach the minimum and are indexed for sync
+        assert_eq!(cs.accept_headers(1, &headers)?, per_header * 3);
+        assert_eq!(storage.get_header(&headers[2].hash())?.map(|(_, height, _)| height), Some(3));
+        assert_eq!(storage.best_header_work()?, per_header * 3);
+
+        // A chain that doesn't link up is refused whatever its work
+        let unlinked = [headers[0].clone(), headers[2].clone()];
+        assert!(cs.accept_headers(1, &unlinked).is_err());
+        Ok(())
+    }
+
+    /// Chain spec with `assume_valid` set to `hash`, over a store holding two
+    /// 10,000-sat outputs to spend
+    fn assume_valid_fixture(hash: Hash32, dir: &std::path::Path) -> Result<(ChainSpec, Storage, OutPoint, OutPoint)> {
+        let content = include_str!("../../../chain_spec.toml")
+            .replace("[consensus]\n", &format!("[consensus]\nassume_valid = \"{}\"\n", hash.to_hex()));
+        let spec: ChainSpec = toml::from_str(&content)?;
+        assert_eq!(spec.assume_valid(), Some(hash));
+
+        let storage = Storage::open(dir)?;
+        let (op_a, op_b) = (OutPoint::new(Hash32([5u8; 32]), 0), OutPoint::new(Hash32([6u8; 32]), 0));
+        let mut wb = WriteBatch::default();
+        for op in [&op_a, &op_b] {
+            storage.put_utxo_batch(&mut wb, op, &(10_000, OutputType::P2PQ { pubkey: vec![0u8; 1312] }, 0, false));
+        }
+        storage.db.write(wb)?;
+        Ok((spec, storage, op_a, op_b))
+    }
+
+    #[test]
+    fn test_signatures_skipped_only_before_assume_valid_block() -> Result<()> {
+        use crate::miner::{build_candidate, mine_block_cpu};
+
+        let base: ChainSpec = toml::from_str(include_str!("../../../chain_spec.toml"))?;
+        let coinbase = |tag: u32| {
+            Transaction::new(1, vec![], vec![TxOut::new_p2pq(block_subsidy(&base, 1), vec![0u8; 1312])], tag)
+        };
+        // Carries an empty signature, so only passes unverified
+        let unsigned_spend = |op: &OutPoint, value: Amount| {
+            Transaction::new(1, vec![TxIn::new(op.clone(), vec![], false)], vec![TxOut::new_p2pq(value, vec![1u8; 1312])], 0)
+        };
+        let mined = |prev: Hash32, txs: Vec<Transaction>| mine_block_cpu(build_candidate(prev, 0x207fffff, txs), 1_000).unwrap();
+
+        // The fixture's first output, spent by the checkpoint's parent
+        let op_a = OutPoint::new(Hash32([5u8; 32]), 0);
+        let ancestor = mined(Hash32::zero(), vec![coinbase(1), unsigned_spend(&op_a, 5_000)]);
+        let checkpoint = mined(ancestor.header.hash(), vec![coinbase(2)]);
+        let temp_dir = tempdir()?;
+        let (spec, storage, _, op_b) = assume_valid_fixture(checkpoint.header.hash(), temp_dir.path())?;
+        let cs = ChainState { spec: &spec, store: &storage, events: None, rejections: None };
+
+        // Until header sync reaches the checkpoint nothing is assumed
+        assert!(cs.apply_block(1, &ancestor).unwrap_err().to_string().contains("signature invalid"));
+        cs.index_header(&ancestor.header, 1)?;
+        assert!(cs.index_header(&checkpoint.header, 2)? > cs.index_header(&ancestor.header, 1)?);
+
+        // Below the checkpoint signatures go unchecked, amounts do not
+        let overspend = mined(Hash32::zero(), vec![coinbase(1), unsigned_spend(&op_b, 20_000)]);
+        assert!(cs.apply_block(1, &overspend).unwrap_err().to_string().contains("insufficient funds"));
+        cs.apply_block(1, &ancestor)?;
+        cs.apply_block(2, &checkpoint)?;
+
+        // Past it every signature is verified again
+        let after = mined(checkpoint.header.hash(), vec![coinbase(3), unsigned_spend(&op_b, 5_000)]);
+        assert!(cs.apply_block(3, &after).unwrap_err().to_string().contains("signature invalid"));
+        assert_eq!(storage.get_tip_height()?, Some(2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_side_branch_below_assume_valid_still_verified() -> Result<()> {
+        use crate::miner::{build_candidate, mine_block_cpu};
+
+        let base: ChainSpec = toml::from_str(include_str!("../../../chain_spec.toml"))?;
+        let coinbase = |tag: u32| {
+            Transaction::new(1, vec![], vec![TxOut::new_p2pq(block_subsidy(&base, 1), vec![0u8; 1312])], tag)
+        };
+        let unsigned_spend = |op: &OutPoint| {
+            Transaction::new(1, vec![TxIn::new(op.clone(), vec![], false)], vec![TxOut::new_p2pq(5_000, vec![1u8; 1312])], 0)
+        };
+        let mined = |prev: Hash32, txs: Vec<Transaction>| mine_block_cpu(build_candidate(prev, 0x207fffff, txs), 1_000).unwrap();
+
+        let ancestor = mined(Hash32::zero(), vec![coinbase(1)]);
+        let checkpoint = mined(ancestor.header.hash(), vec![coinbase(2)]);
+        let temp_dir = tempdir()?;
+        let (spec, storage, op_a, _) = assume_valid_fixture(checkpoint.header.hash(), temp_dir.path())?;
+        let cs = ChainState { spec: &spec, store: &storage, events: None, rejections: None };
+        cs.index_header(&ancestor.header, 1)?;
+        cs.index_header(&checkpoint.header, 2)?;
+
+        // Same height as an assumed-valid ancestor, but not on its branch
+        let side = mined(Hash32::zero(), vec![coinbase(3), unsigned_spend(&op_a)]);
+        assert!(cs.apply_block(1, &side).unwrap_err().to_string().contains("signature invalid"));
+        assert_eq!(storage.get_tip_height()?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_payment_to_revealed_pubkey_flagged() -> Result<()> {
+        use crate::miner::{build_candidate, mine_block_cpu};
+        use qc_validation::ValidationError;
+
+        let base: ChainSpec = toml::from_str(include_str!("../../../chain_spec.toml"))?;
+        let coinbase = |tag: u32| {
+            Transaction::new(1, vec![], vec![TxOut::new_p2pq(block_subsidy(&base, 1), vec![7u8; 1312])], tag)
+        };
+        let pay_to = |pubkey: u8| Transaction::new(1, vec![], vec![TxOut::new_p2pq(1_000, vec![pubkey; 1312])], 0);
+        let mined = |prev: Hash32, txs: Vec<Transaction>| mine_block_cpu(build_candidate(prev, 0x207fffff, txs), 1_000).unwrap();
+
+        // Spends the fixture's output locked to the all-zero pubkey
+        let op_a = OutPoint::new(Hash32([5u8; 32]), 0);
+        let spend = Transaction::new(1, vec![TxIn::new(op_a, vec![], false)], vec![TxOut::new_p2pq(5_000, vec![1u8; 1312])], 0);
+        let block = mined(Hash32::zero(), vec![coinbase(1), spend]);
+        let checkpoint = mined(block.header.hash(), vec![coinbase(2)]);
+        let temp_dir = tempdir()?;
+        let (spec, storage, _, _) = assume_valid_fixture(checkpoint.header.hash(), temp_dir.path())?;
+        let cs = ChainState { spec: &spec, store: &storage, events: None, rejections: None };
+        cs.index_header(&block.header, 1)?;
+        cs.index_header(&checkpoint.header, 2)?;
+
+        assert!(cs.check_key_reuse(&pay_to(0), KeyReusePolicy::Reject)?.is_empty());
+        cs.apply_block(1, &block)?;
+
+        assert_eq!(cs.check_key_reuse(&pay_to(0), KeyReusePolicy::Warn)?, vec![0]);
+        let err = cs.check_key_reuse(&pay_to(0), KeyReusePolicy::Reject).unwrap_err();
+        assert!(matches!(err.downcast_ref::<ValidationError>(), Some(ValidationError::PubkeyReuse)));
+        assert!(cs.check_key_reuse(&pay_to(1), KeyReusePolicy::Reject)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_coinbase_may_claim_subsidy_plus_fees() -> Result<()> {
+        use crate::miner::{build_candidate, mine_block_cpu};
+
+        let base: ChainSpec = toml::from_str(include_str!("../../../chain_spec.toml"))?;
+        let subsidy = block_subsidy(&base, 1);
+        let coinbase = |value: Amount, tag: u32| {
+            Transaction::new(1, vec![], vec![TxOut::new_p2pq(value, vec![0u8; 1312])], tag)
+        };
+        let mined = |prev: Hash32, txs: Vec<Transaction>| mine_block_cpu(build_candidate(prev, 0x207fffff, txs), 1_000).unwrap();
+
+        // Spends the fixture's 10,000-sat output, leaving a 5,000-sat fee
+        let op_a = OutPoint::new(Hash32([5u8; 32]), 0);
+        let spend = Transaction::new(1, vec![TxIn::new(op_a, vec![], false)], vec![TxOut::new_p2pq(5_000, vec![1u8; 1312])], 0);
+
+        for (claim, accepted) in [(subsidy + 5_000, true), (subsidy + 5_001, false)] {
+            let block = mined(Hash32::zero(), vec![coinbase(claim, 1), spend.clone()]);
+            let checkpoint = mined(block.header.hash(), vec![coinbase(subsidy, 2)]);
+            let temp_dir = tempdir()?;
+            let (spec, storage, _, _) = assume_valid_fixture(checkpoint.header.hash(), temp_dir.path())?;
+            let cs = ChainState { spec: &spec, store: &storage, events: None, rejections: None };
+            cs.index_header(&block.header, 1)?;
+            cs.index_header(&checkpoint.header, 2)?;
+
+            match cs.apply_block(1, &block) {
+                Ok(connected) => {
+                    assert!(accepted);
+                    assert_eq!(connected.total_fees, 5_000);
+                }
+                Err(err) => {
+                    assert!(!accepted);
+                    assert_eq!(err.downcast_ref::<Rejection>().unwrap().reason, RejectReason::ExcessiveCoinbase);
+                    assert_eq!(storage.get_tip_height()?, None);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_connect_applies_all_state_or_none() -> Result<()> {
+        use crate::miner::build_candidate;
+
+        let content = include_str!("../../../chain_spec.toml")
+            .replace("[network]\n", "[network]\nkind = \"regtest\"\n")
+            .replace("[consensus]\n", "[consensus]\nno_pow = true\n");
+        let spec: ChainSpec = toml::from_str(&content)?;
+        let temp_dir = tempdir()?;
+        let storage = Storage::open(temp_dir.path())?;
+        let cs = ChainState { spec: &spec, store: &storage, events: None, rejections: None };
+        let coinbase = |tag: u8| Transaction::new(1, vec![], vec![TxOut::new_p2pq(block_subsidy(&spec, 1), vec![tag; 1312])], 1);
+
+        let first = build_candidate(Hash32::zero(), 0x207fffff, vec![coinbase(1)]);
+        cs.apply_block(1, &first)?;
+        let txid = cs.calculate_txid(&first.txs[0]);
+        assert!(storage.get_utxo(&OutPoint::new(txid, 0))?.is_some());
+        assert_eq!(storage.get_transaction(&txid)?.map(|(height, _)| height), Some(1));
+        assert_eq!(storage.get_tip()?, Some(first.hash()));
+        assert_eq!(storage.get_tip_height()?, Some(1));
+
+        // The spend fails after the coinbase was staged; nothing of it lands
+        let spend = Transaction::new(1, vec![TxIn::new(OutPoint::new(Hash32([5u8; 32]), 0), vec![], false)], vec![TxOut::new_p2pq(1_000, vec![3u8; 1312])], 0);
+        let second = build_candidate(first.hash(), 0x207fffff, vec![coinbase(2), spend]);
+        assert!(cs.apply_block(2, &second).is_err());
+        let txid = cs.calculate_txid(&second.txs[0]);
+        assert!(storage.get_utxo(&OutPoint::new(txid, 0))?.is_none());
+        assert!(storage.get_transaction(&txid)?.is_none());
+        assert!(storage.get_block(&second.hash())?.is_none());
+        assert_eq!(storage.get_tip()?, Some(first.hash()));
+        assert_eq!(storage.get_tip_height()?, Some(1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_connected_block_info_matches_block() -> Result<()> {
+        use crate::miner::build_candidate;
+
+        let content = include_str!("../../../chain_spec.toml")
+            .replace("[network]\n", "[network]\nkind = \"regtest\"\n")
+            .replace("[consensus]\n", "[consensus]\nno_pow = true\n");
+        let spec: ChainSpec = toml::from_str(&content)?;
+        let temp_dir = tempdir()?;
+        let storage = Storage::open(temp_dir.path())?;
+        let (events, mut connected) = broadcast::channel(CHAIN_EVENT_BUFFER);
+        let cs = ChainState { spec: &spec, store: &storage, events: Some(&events), rejections: None };
+
+        let coinbase = Transaction::new(1, vec![], vec![TxOut::new_p2pq(block_subsidy(&spec, 1), vec![1u8; 1312])], 1);
+        let block = build_candidate(Hash32::zero(), 0x207fffff, vec![coinbase]);
+        let info = cs.apply_block(1, &block)?;
+
+        assert_eq!(info, ConnectedBlockInfo {
+            height: 1,
+            hash: block.hash(),
+            tx_count: 1,
+            total_fees: 0,
+            subsidy: block_subsidy(&spec, 1),
+            size: bincode::serialize(&block)?.len() as u64,
+            weight: block.weight(),
+        });
+        assert_eq!(connected.try_recv()?, ChainEvent::BlockConnected(info));
+        Ok(())
+    }
+
+    #[test]
+    fn test_chain_tips_report_forks() -> Result<()> {
+        use crate::miner::build_candidate;
+        use crate::rejections::RejectionLogConfig;
+
+        let content = include_str!("../../../chain_spec.toml")
+            .replace("[network]\n", "[network]\nkind = \"regtest\"\n")
+            .replace("[consensus]\n", "[consensus]\nno_pow = true\n");
+        let mut spec: ChainSpec = toml::from_str(&content)?;
+        let coinbase = |tag: u8, extra: usize| {
+            Transaction::new(1, vec![], vec![TxOut::new_p2pq(block_subsidy(&spec, 1), vec![tag; 1312 + extra])], 1)
+        };
+        let on = |parent: &Block, tag: u8| build_candidate(parent.hash(), 0x207fffff, vec![coinbase(tag, 0)]);
+        let a1 = build_candidate(Hash32::zero(), 0x207fffff, vec![coinbase(1, 0)]);
+        let (b2, e2, a2) = (on(&a1, 2), on(&a1, 5), on(&a1, 3));
+        let c3 = on(&b2, 4);
+        let heavy = build_candidate(a1.hash(), 0x207fffff, vec![coinbase(6, 1_000)]);
+        spec.consensus.max_block_weight = a1.weight() + 1_000;
+
+        let temp_dir = tempdir()?;
+        let storage = Storage::open(temp_dir.path())?;
+        let rejections = RejectionLog::new(RejectionLogConfig { log: false, ..RejectionLogConfig::default() });
+        let cs = ChainState { spec: &spec, store: &storage, events: None, rejections: Some(&rejections) };
+        cs.apply_block(1, &a1)?;
+        for block in [&b2, &e2, &a2] {
+            cs.apply_block(2, block)?;
+        }
+        cs.index_header(&c3.header, 3)?;
+        assert!(cs.apply_block(2, &heavy).is_err());
+
+        let tips: Vec<(Hash32, u64, u64, TipStatus)> =
+            cs.chain_tips()?.into_iter().map(|t| (t.hash, t.height, t.branchlen, t.status)).collect();
+        assert_eq!(tips.len(), 4);
+        assert!(tips.contains(&(a2.hash(), 2, 0, TipStatus::Active)));
+        assert!(tips.contains(&(e2.hash(), 2, 1, TipStatus::ValidFork)));
+        assert!(tips.contains(&(heavy.hash(), 2, 1, TipStatus::Invalid)));
+        assert_eq!(tips[0], (c3.hash(), 3, 2, TipStatus::HeadersOnly));
+        Ok(())
+    }
+
+    #[test]
+    fn test_resubmitted_blocks_are_idempotent() -> Result<()> {
+        use crate::miner::{build_candidate, mine_block_cpu};
+        use crate::rejections::RejectionLogConfig;
+
+        let temp_dir = tempdir()?;
+        let storage = Storage::open(temp_dir.path())?;
+        let spec: ChainSpec = toml::from_str(include_str!("../../../chain_spec.toml"))?;
+        let rejections = RejectionLog::new(RejectionLogConfig { log: false, ..RejectionLogConfig::default() });
+        let cs = ChainState { spec: &spec, store: &storage, events: None, rejections: Some(&rejections) };
+
+        let coinbase = Transaction::new(1, vec![], vec![TxOut::new_p2pq(block_subsidy(&spec, 1), vec![0u8; 1312])], 1);
+        let block = mine_block_cpu(build_candidate(Hash32::zero(), 0x207fffff, vec![coinbase.clone()]), 1_000).unwrap();
+        let hash = cs.block_hash(&block.header);
+
+        assert!(matches!(cs.submit_block(1, &block)?, SubmitOutcome::Accepted(h) if h == hash));
+        assert!(matches!(cs.submit_block(1, &block)?, SubmitOutcome::Duplicate(h) if h == hash));
+        assert_eq!(storage.get_tip_height()?, Some(1));
+
+        // A body that doesn't match its header says nothing about the header,
+        // so the same hash is revalidated each time
+        let mut bad_merkle = build_candidate(Hash32::zero(), 0x207fffff, vec![coinbase.clone()]);
+        bad_merkle.header.merkle_root = Hash32([9u8; 32]);
+        let bad_merkle = mine_block_cpu(bad_merkle, 1_000).unwrap();
+        assert!(cs.submit_block(2, &bad_merkle).is_err());
+        assert!(cs.submit_block(2, &bad_merkle).is_err());
+        assert!(rejections.block_rejection(&cs.block_hash(&bad_merkle.header)).is_none());
+        assert_eq!(rejections.count(RejectedKind::Block, RejectReason::BadMerkleRoot), 2);
+
+        // A header that misses its target is answered from the log
+        let unmined = build_candidate(Hash32::zero(), 0x1d00ffff, vec![coinbase]);
+        assert!(cs.submit_block(2, &unmined).is_err());
+        match cs.submit_block(2, &unmined)? {
+            SubmitOutcome::KnownInvalid(rejection) => {
+                assert_eq!(rejection.reason, RejectReason::BadProofOfWork);
+                assert_eq!(rejection.hash, cs.block_hash(&unmined.header).to_hex());
+            }
+            other => panic!("expected known-invalid, got {:?}", other),
+        }
+        assert_eq!(rejections.count(RejectedKind::Block, RejectReason::BadProofOfWork), 1);
+        assert_eq!(storage.get_tip_height()?, Some(1));
+        Ok(())
+    }
 }
@@ -14,6 +14,7 @@ use crate::{
     config::SharedConfig,
 };
 use anyhow::{Result, anyhow, Context};
+use qc_types::target::{compact_to_target, target_to_compact, U256};
 use blake3::Hasher as Blake3Hasher;
 use chrono::{DateTime, Utc, Duration};
 use parking_lot::{RwLock, Mutex};
@@ -24,112 +25,10 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 use tracing::{debug, error, info, warn, instrument};
 
-/// Chain specification loaded from chain_spec.toml
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ChainSpec {
-    pub network: NetworkSpec,
-    pub consensus: ConsensusSpec,
-    pub supply: SupplySpec,
-    pub transaction: TransactionSpec,
-    pub block: BlockSpec,
-    pub cryptography: CryptographySpec,
-    pub fees: FeeSpec,
-    pub mining: MiningSpec,
-    pub governance: GovernanceSpec,
-    pub post_quantum: PostQuantumSpec,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct NetworkSpec {
-    pub name: String,
-    pub symbol: String,
-    pub decimals: u8,
-    pub version: String,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ConsensusSpec {
-    pub algorithm: String,
-    pub hash_function: String,
-    pub target_block_time: u64,
-    pub difficulty_adjustment_period: u64,
-    pub max_difficulty_change: f64,
-    pub genesis_difficulty: u32,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SupplySpec {
-    pub max_supply: u64,
-    pub initial_reward: u64,
-    pub halving_interval: u64,
-    pub premine: u64,
-    pub inflation_schedule: Vec<InflationEntry>,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct InflationEntry {
-    pub height: u64,
-    pub reward: u64,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TransactionSpec {
-    pub max_tx_size: usize,
-    pub min_tx_fee: u64,
-    pub dust_threshold: u64,
-    pub max_inputs_per_tx: usize,
-    pub max_outputs_per_tx: usize,
-    pub signature_hash_type: String,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct BlockSpec {
-    pub max_block_size: usize,
-    pub max_block_weight: usize,
-    pub coinbase_maturity: u64,
-    pub max_reorg_depth: u64,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CryptographySpec {
-    pub address_version: u8,
-    pub private_key_version: u8,
-    pub checksum_algorithm: String,
-    pub signature_scheme: String,
-    pub hash_algorithm: String,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct FeeSpec {
-    pub min_relay_fee: u64,
-    pub increment_fee: u64,
-    pub dust_relay_fee: u64,
-    pub max_fee_rate: u64,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct MiningSpec {
-    pub coinbase_flags: String,
-    pub extra_nonce_placeholder: usize,
-    pub witness_commitment_pos: usize,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct GovernanceSpec {
-    pub bip9_activation_threshold: u64,
-    pub bip9_min_activation_height: u64,
-    pub lock_in_period: u64,
-    pub timeout_period: u64,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PostQuantumSpec {
-    pub signature_algorithm: String,
-    pub public_key_size: usize,
-    pub private_key_size: usize,
-    pub signature_size: usize,
-    pub security_level: u8,
-}
+pub use qc_validation::engine_spec::{
+    ChainSpec, NetworkSpec, ConsensusSpec, SupplySpec, InflationEntry, TransactionSpec, BlockSpec,
+    CryptographySpec, FeeSpec, MiningSpec, GovernanceSpec, PostQuantumSpec,
+};
 
 /// Comprehensive consensus validation errors
 #[derive(Error, Debug)]
@@ -170,6 +69,9 @@ pub enum ConsensusError {
     #[error("Block too large: size {size} exceeds limit {limit}")]
     BlockTooLarge { size: usize, limit: usize },
     
+    #[error("Block too heavy: weight {weight} exceeds limit {limit}")]
+    BlockTooHeavy { weight: usize, limit: usize },
+    
     #[error("Too many transactions: count {count} exceeds limit {limit}")]
     TooManyTransactions { count: usize, limit: usize },
     
@@ -274,7 +176,7 @@ impl ConsensusEngine {
     pub fn new(spec: ChainSpec, config: SharedConfig) -> Result<Self> {
         let economics = Economics::from_shared_config(&config);
         
-        let initial_difficulty = Self::compact_to_target(spec.consensus.genesis_difficulty);
+        let initial_difficulty = compact_to_target(spec.consensus.genesis_difficulty);
         
         let difficulty_state = DifficultyState {
             current_difficulty: spec.consensus.genesis_difficulty,
@@ -413,7 +315,7 @@ impl ConsensusEngine {
     /// Validate proof of work meets difficulty requirement
     fn validate_proof_of_work(&self, block: &Block) -> Result<(), ConsensusError> {
         let block_hash = block.hash();
-        let difficulty_target = Self::compact_to_target(block.header.difficulty);
+        let difficulty_target = compact_to_target(block.header.difficulty);
         
         // Check if block hash meets difficulty requirement
         if !self.hash_meets_target(&block_hash, difficulty_target) {
@@ -650,7 +552,7 @@ impl ConsensusEngine {
         }
     }
     
-    /// Validate block size constraints
+    /// Validate block size and weight constraints
     fn validate_block_size(&self, block: &Block) -> Result<(), ConsensusError> {
         let block_size = bincode::serialize(block)
             .map_err(|e| ConsensusError::ConfigError(anyhow!("Serialization error: {}", e)))?
@@ -663,6 +565,14 @@ impl ConsensusEngine {
             });
         }
         
+        let weight = block.weight();
+        if weight > self.spec.block.max_block_weight {
+            return Err(ConsensusError::BlockTooHeavy {
+                weight,
+                limit: self.spec.block.max_block_weight,
+            });
+        }
+        
         Ok(())
     }
     
@@ -708,9 +618,9 @@ impl ConsensusEngine {
         let limited_ratio = ratio.max(1.0 / max_adjustment).min(max_adjustment);
         
         // Calculate new difficulty
-        let current_target = Self::compact_to_target(difficulty_state.current_difficulty);
+        let current_target = compact_to_target(difficulty_state.current_difficulty);
         let new_target = Self::multiply_target(current_target, limited_ratio);
-        let new_difficulty = Self::target_to_compact(new_target);
+        let new_difficulty = target_to_compact(new_target);
         
         info!(
             "Difficulty adjusted from {} to {} (ratio: {:.4})",
@@ -806,59 +716,11 @@ impl ConsensusEngine {
     
     /// Utility functions for difficulty calculations
     
-    fn compact_to_target(compact: u32) -> [u8; 32] {
-        let mut target = [0u8; 32];
-        let size = (compact >> 24) as usize;
-        let mantissa = compact & 0x00ffffff;
-        
-        if size <= 3 {
-            target[29] = (mantissa >> 16) as u8;
-            target[30] = (mantissa >> 8) as u8;
-            target[31] = mantissa as u8;
-        } else if size < 32 {
-            let offset = 32 - size;
-            target[offset] = (mantissa >> 16) as u8;
-            target[offset + 1] = (mantissa >> 8) as u8;
-            target[offset + 2] = mantissa as u8;
-        }
-        
-        target
-    }
-    
-    fn target_to_compact(target: [u8; 32]) -> u32 {
-        // Find the most significant non-zero byte
-        let mut size = 32;
-        while size > 0 && target[32 - size] == 0 {
-            size -= 1;
-        }
-        
-        if size == 0 {
-            return 0;
-        }
-        
-        let mut mantissa = 0u32;
-        if size >= 3 {
-            mantissa = (target[32 - size] as u32) << 16
-                | (target[32 - size + 1] as u32) << 8
-                | (target[32 - size + 2] as u32);
-        } else {
-            mantissa = (target[32 - size] as u32) << (8 * (3 - size));
-        }
-        
-        // Handle the sign bit
-        if mantissa & 0x800000 != 0 {
-            mantissa >>= 8;
-            size += 1;
-        }
-        
-        (size as u32) << 24 | mantissa
-    }
-    
-    fn hash_meets_target(&self, hash: &[u8; 32], target: [u8; 32]) -> bool {
-        hash <= &target
+    fn hash_meets_target(&self, hash: &[u8; 32], target: U256) -> bool {
+        U256::from_be_bytes(*hash) <= target
     }
     
-    fn multiply_target(target: [u8; 32], multiplier: f64) -> [u8; 32] {
+    fn multiply_target(target: U256, multiplier: f64) -> U256 {
         // Convert target to big integer, multiply, and convert back
         // This is a simplified version - a real implementation would use proper big integer arithmetic
         target
@@ -910,6 +772,7 @@ mod tests {
                 symbol: "TEST".to_string(),
                 decimals: 8,
                 version: "1.0.0".to_string(),
+                kind: qc_validation::NetworkKind::Regtest,
             },
             consensus: ConsensusSpec {
                 algorithm: "proof_of_work".to_string(),
@@ -918,6 +781,7 @@ mod tests {
                 difficulty_adjustment_period: 2016,
                 max_difficulty_change: 4.0,
                 genesis_difficulty: 0x1d00ffff,
+                minimum_chain_work: 0,
             },
             supply: SupplySpec {
                 max_supply: 22_000_000_00000000,
@@ -1074,6 +938,29 @@ mod tests {
         assert_eq!(best_hash, "hash2", "Should select fork with highest total work");
     }
     
+    #[test]
+    fn test_block_size_and_weight_limits() {
+        let mut block = Block::genesis();
+        block.transactions = vec![[7u8; 32]; 100];
+        let size = bincode::serialized_size(&block).unwrap() as usize;
+        let engine_with = |max_block_size, max_block_weight| {
+            let mut spec = create_test_spec();
+            spec.block.max_block_size = max_block_size;
+            spec.block.max_block_weight = max_block_weight;
+            ConsensusEngine::new(spec, ChainConfig::default().shared()).unwrap()
+        };
+        
+        assert!(engine_with(size, block.weight()).validate_block_size(&block).is_ok());
+        
+        // Within byte size but over weight
+        let result = engine_with(size, block.weight() - 1).validate_block_size(&block);
+        assert!(matches!(result, Err(ConsensusError::BlockTooHeavy { .. })));
+        
+        // Within weight but over byte size
+        let result = engine_with(size - 1, block.weight() * 2).validate_block_size(&block);
+        assert!(matches!(result, Err(ConsensusError::BlockTooLarge { .. })));
+    }
+    
     #[test]
     fn test_network_partition_detection() {
         let spec = create_test_spec();
@@ -12,6 +12,7 @@ mod tests {
     use crate::config::ChainConfig;
     use crate::block::{Block, BlockHeader};
     use crate::transaction::{Transaction, TransactionInput, TransactionOutput};
+    use qc_types::target::{compact_to_target, target_to_compact};
     use proptest::prelude::*;
     use proptest::collection::vec;
     use std::collections::HashMap;
@@ -67,13 +68,11 @@ mod tests {
         fn arb_transaction()(
             inputs in vec(arb_transaction_input(), 1..10),
             outputs in vec(arb_transaction_output(), 1..10),
-            fee in 0u64..1_000_000,
             timestamp in 1_640_995_200u64..2_000_000_000u64,
         ) -> Transaction {
             Transaction {
                 inputs,
                 outputs,
-                fee,
                 timestamp,
             }
         }
@@ -382,17 +381,11 @@ mod tests {
         fn test_difficulty_target_conversion(
             difficulty in 0x1000_0000u32..0x2000_0000u32
         ) {
-            let target = ConsensusEngine::compact_to_target(difficulty);
-            let recovered_difficulty = ConsensusEngine::target_to_compact(target);
+            let target = compact_to_target(difficulty);
+            let recovered_difficulty = target_to_compact(target);
             
-            // Conversion should be reasonably close (allowing for rounding)
-            let diff_ratio = if recovered_difficulty > difficulty {
-                recovered_difficulty as f64 / difficulty as f64
-            } else {
-                difficulty as f64 / recovered_difficulty as f64
-            };
-            
-            prop_assert!(diff_ratio < 1.1, "Difficulty conversion should preserve value within 10%");
+            // Any decoded target fits in a mantissa, so re-encoding is lossless
+            prop_assert_eq!(compact_to_target(recovered_difficulty), target);
         }
         
         /// Test that network time updates maintain consistency
@@ -512,7 +505,6 @@ mod tests {
                 amount: 1000000,
                 recipient: vec![0u8; 20],
             }],
-            fee: 1000,
             timestamp: 1_640_995_200,
         };
         
@@ -1,114 +1,227 @@
 use anyhow::*;
 use parking_lot::Mutex;
+use qc_types::{OutPoint, Amount, Hash32, Height, OutputType};
+use qc_validation::{ChainSpec, check_block_sigops, validate_transaction};
 use rand::{Rng, thread_rng};
 use serde::{Serialize, Deserialize};
 use sha2::{Digest, Sha256};
-use std::{collections::HashMap, sync::Arc, time::{SystemTime, UNIX_EPOCH}};
+use std::{collections::{HashMap, HashSet, VecDeque}, sync::Arc, time::{SystemTime, UNIX_EPOCH}};
+use tracing::error;
+
+pub mod p2p;
+pub mod sync;
+
+pub use p2p::P2PNetwork;
 
 pub type Hash = [u8;32];
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
-pub struct Tx {
-    pub nonce: u64,
-    pub from: String,
-    pub to: String,
-    pub value: u64,
-    pub fee: u64,
-    pub data: String
-}
+/// Number of ancestors whose median timestamp a new block must exceed
+pub const MEDIAN_TIME_SPAN: usize = 11;
+/// Lowest difficulty `mine_one` will retarget to
+pub const MIN_DIFFICULTY: u128 = 1_000_000;
+/// Blocks held while their parent is unknown
+pub const MAX_ORPHAN_BLOCKS: usize = 100;
+/// Most blocks of the active chain a fork switch may disconnect
+pub const DEFAULT_MAX_REORG_DEPTH: u64 = 100;
 
+/// Blocks carry the same UTXO transactions that validation and storage use
+pub use qc_types::Transaction;
+
+/// Hashes are held as bytes and only rendered as `0x`-prefixed hex when a
+/// block is serialized for the JSON API
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct BlockHeader {
-    pub parent: String,
+    #[serde(with = "hex_hash")]
+    pub parent: Hash32,
     pub number: u64,
     pub timestamp: u64,
     pub difficulty: u128,
     pub nonce: u64,
-    pub merkle_root: String,
+    #[serde(with = "hex_hash")]
+    pub merkle_root: Hash32,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct Block {
-    pub hash: String,
+    #[serde(with = "hex_hash")]
+    pub hash: Hash32,
     pub header: BlockHeader,
-    pub txs: Vec<Tx>,
+    pub txs: Vec<Transaction>,
     pub work: u128, // difficulty contribution
 }
 
+mod hex_hash {
+    use qc_types::Hash32;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(hash: &Hash32, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("0x{}", hash.to_hex()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Hash32, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        Hash32::from_hex(hex.trim_start_matches("0x")).map_err(D::Error::custom)
+    }
+}
+
 #[derive(Default)]
 struct ChainInner {
-    blocks_by_hash: HashMap<String, Block>,
-    hash_by_number: HashMap<u64, String>,
-    head: String,
+    blocks_by_hash: HashMap<Hash32, Block>,
+    hash_by_number: HashMap<u64, Hash32>,
+    head: Hash32,
     total_work: u128,
+    work_by_hash: HashMap<Hash32, u128>, // cumulative work up to and including each block
     peers: u64,
+    min_difficulty: u128,
+    max_reorg_depth: u64,
+    refused_reorgs: HashSet<Hash32>, // fork tips not followed for forking too deep
+    orphans: HashMap<Hash32, Block>, // blocks waiting on an unknown parent
+    orphan_order: VecDeque<Hash32>,  // `orphans` keys, oldest first
 }
 
 #[derive(Clone)]
 pub struct Chain(Arc<Mutex<ChainInner>>);
 
+/// Point-in-time copy of the chain's head and the state the next block
+/// is built from
+#[derive(Debug, Clone)]
+pub struct ChainSnapshot {
+    pub head: Block,
+    pub total_work: u128,
+    pub block_count: usize,
+    pub min_difficulty: u128,
+}
+
 impl Chain {
     pub fn new_genesis() -> Self {
+        Self::from_genesis(Self::make_block(None, 0, 0x0000_0fff_ffff_ffff_ffff, now(), vec![]))
+    }
+
+    /// Override the retarget floor, e.g. so tests can mine instantly
+    pub fn with_min_difficulty(self, min_difficulty: u128) -> Self {
+        self.0.lock().min_difficulty = min_difficulty;
+        self
+    }
+
+    /// Override how many blocks of the active chain a reorg may disconnect
+    pub fn with_max_reorg_depth(self, max_reorg_depth: u64) -> Self {
+        self.0.lock().max_reorg_depth = max_reorg_depth;
+        self
+    }
+
+    pub fn from_genesis(genesis: Block) -> Self {
         let inner = ChainInner::default();
         let me = Self(Arc::new(Mutex::new(inner)));
-        let genesis = Self::make_block(None, 0, 0x0000_0fff_ffff_ffff_ffff, vec![]);
         let mut g = me.0.lock();
         g.total_work = genesis.work;
-        g.hash_by_number.insert(0, genesis.hash.clone());
-        g.blocks_by_hash.insert(genesis.hash.clone(), genesis.clone());
-        g.head = genesis.hash.clone();
+        g.work_by_hash.insert(genesis.hash, genesis.work);
+        g.hash_by_number.insert(0, genesis.hash);
+        g.head = genesis.hash;
+        g.blocks_by_hash.insert(genesis.hash, genesis);
         g.peers = 1;
+        g.min_difficulty = MIN_DIFFICULTY;
+        g.max_reorg_depth = DEFAULT_MAX_REORG_DEPTH;
+        drop(g);
         me
     }
 
-    fn make_block(parent: Option<&Block>, number: u64, difficulty: u128, txs: Vec<Tx>) -> Block {
-        let parent_hash = parent.map(|b| b.hash.clone()).unwrap_or_else(|| "0x00".into());
+    /// Chain placed directly at `head` with `total_work` behind it, so tests
+    /// can start from e.g. the block before a retarget without mining there.
+    /// Ancestors of `head` are unknown.
+    #[cfg(test)]
+    pub fn with_state(head: Block, total_work: u128, min_difficulty: u128) -> Self {
+        let number = head.header.number;
+        let me = Self::from_genesis(head.clone()).with_min_difficulty(min_difficulty);
+        let mut g = me.0.lock();
+        g.hash_by_number.clear();
+        g.hash_by_number.insert(number, head.hash);
+        g.work_by_hash.insert(head.hash, total_work);
+        g.total_work = total_work;
+        drop(g);
+        me
+    }
+
+    /// Copy of the head and the work and difficulty state behind it, taken
+    /// under one lock
+    pub fn snapshot(&self) -> ChainSnapshot {
+        let g = self.0.lock();
+        ChainSnapshot {
+            head: g.blocks_by_hash[&g.head].clone(),
+            total_work: g.total_work,
+            block_count: g.blocks_by_hash.len(),
+            min_difficulty: g.min_difficulty,
+        }
+    }
+
+    fn make_block(parent: Option<&Block>, number: u64, difficulty: u128, timestamp: u64, txs: Vec<Transaction>) -> Block {
+        let parent_hash = parent.map_or_else(Hash32::zero, |b| b.hash);
         let merkle_root = merkle_root(&txs);
-        let timestamp = now();
         let mut nonce = 0u64;
         // naive PoW: find nonce s.t. hash_u128 <= target
         let mut rng = thread_rng();
         let target = u128::MAX / difficulty;
-        let header_seed = |nonce: u64| {
-            let mut h = Sha256::new();
-            h.update(&hex::decode(parent_hash.trim_start_matches("0x")).unwrap_or_default());
-            h.update(number.to_be_bytes());
-            h.update(timestamp.to_be_bytes());
-            h.update(difficulty.to_be_bytes());
-            h.update(nonce.to_be_bytes());
-            h.update(&hex::decode(merkle_root.trim_start_matches("0x")).unwrap_or_default());
-            let first = h.finalize();
-            let mut h2 = Sha256::new();
-            h2.update(first);
-            let out = h2.finalize();
-            let mut arr=[0u8;32]; arr.copy_from_slice(&out); arr
-        };
-        let hash_u128 = |bytes: &Hash| -> u128 {
+        let header_seed = |nonce: u64| header_hash(&parent_hash, number, timestamp, difficulty, nonce, &merkle_root);
+        let hash_u128 = |hash: &Hash32| -> u128 {
             let mut n = [0u8;16];
-            n.copy_from_slice(&bytes[..16]);
+            n.copy_from_slice(&hash.0[..16]);
             u128::from_be_bytes(n)
         };
-        let mut hash_bytes = header_seed(nonce);
-        while hash_u128(&hash_bytes) > target {
+        let mut hash = header_seed(nonce);
+        while hash_u128(&hash) > target {
             nonce = nonce.wrapping_add(1).max(rng.gen::<u32>() as u64);
-            hash_bytes = header_seed(nonce);
+            hash = header_seed(nonce);
         }
-        let hash = format!("0x{}", hex::encode(hash_bytes));
         let header = BlockHeader { parent: parent_hash, number, timestamp, difficulty, nonce, merkle_root };
         let work = difficulty;
         Block { hash, header, txs, work }
     }
 
-    pub fn head(&self) -> Block { self.0.lock().blocks_by_hash[&self.0.lock().head].clone() }
-    pub fn height(&self) -> u64 { self.0.lock().hash_by_number.len().saturating_sub(1) as u64 }
+    /// The head block, read under a single lock; `None` only for a chain
+    /// whose head was never set
+    pub fn head(&self) -> Option<Block> { let g = self.0.lock(); g.blocks_by_hash.get(&g.head).cloned() }
+    pub fn height(&self) -> u64 { let g = self.0.lock(); g.blocks_by_hash[&g.head].header.number }
     pub fn peers(&self) -> u64 { self.0.lock().peers }
 
+    /// Fork tips with more work that were not followed because they fork
+    /// more than the maximum reorg depth below the head
+    pub fn refused_reorgs(&self) -> Vec<Hash32> {
+        let mut tips: Vec<Hash32> = self.0.lock().refused_reorgs.iter().copied().collect();
+        tips.sort_by_key(|h| h.0);
+        tips
+    }
+
+    /// The block at height `n` on the current head's chain, or `None` above
+    /// the head. The number index is only trusted for a block that is an
+    /// ancestor of the head, so an entry a reorg left behind never surfaces.
     pub fn get_block_by_number(&self, n: u64) -> Option<Block> {
         let g = self.0.lock();
-        g.hash_by_number.get(&n).and_then(|h| g.blocks_by_hash.get(h).cloned())
+        let mut cursor = g.blocks_by_hash.get(&g.head)?;
+        if n > cursor.header.number { return None; }
+        if let Some(indexed) = g.hash_by_number.get(&n).and_then(|h| g.blocks_by_hash.get(h)) {
+            if Self::is_ancestor(&g, indexed, cursor) { return Some(indexed.clone()); }
+        }
+        while cursor.header.number > n {
+            cursor = g.blocks_by_hash.get(&cursor.header.parent)?;
+        }
+        Some(cursor.clone())
+    }
+
+    /// Whether `ancestor` is `tip` or one of its parents
+    fn is_ancestor(g: &ChainInner, ancestor: &Block, tip: &Block) -> bool {
+        let mut cursor = Some(tip);
+        while let Some(b) = cursor {
+            if b.header.number <= ancestor.header.number { return b.hash == ancestor.hash; }
+            cursor = g.blocks_by_hash.get(&b.header.parent);
+        }
+        false
     }
 
     pub fn mine_one(&self) -> Block {
+        self.mine_with(vec![])
+    }
+
+    /// Mine `txs` unchanged into a block on the current head
+    pub fn mine_with(&self, txs: Vec<Transaction>) -> Block {
         // simplistic retarget: keep target ~30s by adjusting difficulty ±5%
         let mut g = self.0.lock();
         let prev = g.blocks_by_hash.get(&g.head).unwrap();
@@ -118,37 +231,491 @@ impl Chain {
         let dt = now().saturating_sub(last_ts).max(1);
         if dt < target { difficulty = (difficulty as f64 * 1.05) as u128; }
         if dt > target { difficulty = (difficulty as f64 * 0.95) as u128; }
-        difficulty = difficulty.clamp(1_000_000, u128::MAX/2);
+        difficulty = difficulty.clamp(g.min_difficulty, u128::MAX/2);
 
-        let b = Self::make_block(Some(prev), prev.header.number+1, difficulty, vec![]);
-        g.blocks_by_hash.insert(b.hash.clone(), b.clone());
-        g.hash_by_number.insert(b.header.number, b.hash.clone());
-        g.head = b.hash.clone();
-        g.total_work += b.work;
+        // Never reuse or go back on the parent's timestamp, even when blocks come faster than 1/s
+        let timestamp = now().max(last_ts + 1);
+        let b = Self::make_block(Some(prev), prev.header.number+1, difficulty, timestamp, txs);
+        Self::connect(&mut g, b.clone()).expect("mined on head");
         b
     }
+
+    /// Import a block; returns true if it or an orphan it released became the
+    /// new head. The head is the tip with the most cumulative work, so this
+    /// may reorg. A block whose parent is unknown is held in the orphan pool
+    /// and connected once the parent is imported.
+    pub fn import_block(&self, block: Block) -> Result<bool> {
+        let mut g = self.0.lock();
+        if g.blocks_by_hash.contains_key(&block.hash) || g.orphans.contains_key(&block.hash) { return Ok(false); }
+        if !g.blocks_by_hash.contains_key(&block.header.parent) {
+            // No block hashes to zero, so one claiming it as parent can never connect
+            ensure!(block.header.parent != Hash32::zero(), "block {} has a zeroed parent hash", block.header.number);
+            Self::add_orphan(&mut g, block);
+            return Ok(false);
+        }
+        let mut new_head = Self::connect(&mut g, block.clone())?;
+        let mut released = Self::take_orphans_of(&mut g, &block.hash);
+        while let Some(orphan) = released.pop() {
+            let hash = orphan.hash;
+            // An orphan that fails validation is dropped along with its descendants
+            if let std::result::Result::Ok(head) = Self::connect(&mut g, orphan) {
+                new_head |= head;
+                released.extend(Self::take_orphans_of(&mut g, &hash));
+            }
+        }
+        Ok(new_head)
+    }
+
+    pub fn is_orphan(&self, hash: &Hash32) -> bool { self.0.lock().orphans.contains_key(hash) }
+    pub fn orphan_count(&self) -> usize { self.0.lock().orphans.len() }
+
+    /// Hold `block` until its parent arrives, evicting the oldest orphan when full
+    fn add_orphan(g: &mut ChainInner, block: Block) {
+        if g.orphan_order.len() == MAX_ORPHAN_BLOCKS {
+            if let Some(oldest) = g.orphan_order.pop_front() {
+                g.orphans.remove(&oldest);
+            }
+        }
+        g.orphan_order.push_back(block.hash);
+        g.orphans.insert(block.hash, block);
+    }
+
+    /// Remove and return the orphans built directly on `parent`
+    fn take_orphans_of(g: &mut ChainInner, parent: &Hash32) -> Vec<Block> {
+        let children: Vec<Hash32> = g.orphans.values()
+            .filter(|b| b.header.parent == *parent)
+            .map(|b| b.hash)
+            .collect();
+        g.orphan_order.retain(|h| !children.contains(h));
+        children.iter().filter_map(|h| g.orphans.remove(h)).collect()
+    }
+
+    /// Median timestamp of the last `MEDIAN_TIME_SPAN` blocks ending at `tip`
+    fn median_time_past(g: &ChainInner, tip: &Hash32) -> u64 {
+        let mut times = Vec::with_capacity(MEDIAN_TIME_SPAN);
+        let mut cursor = g.blocks_by_hash.get(tip);
+        while let Some(b) = cursor {
+            times.push(b.header.timestamp);
+            if times.len() == MEDIAN_TIME_SPAN { break; }
+            cursor = g.blocks_by_hash.get(&b.header.parent);
+        }
+        times.sort_unstable();
+        times.get(times.len() / 2).copied().unwrap_or(0)
+    }
+
+    /// Height of the last block `tip`'s chain shares with the active chain
+    fn fork_point(g: &ChainInner, tip: &Hash32) -> u64 {
+        let mut cursor = g.blocks_by_hash.get(tip);
+        while let Some(b) = cursor {
+            if g.hash_by_number.get(&b.header.number) == Some(&b.hash) { return b.header.number; }
+            cursor = g.blocks_by_hash.get(&b.header.parent);
+        }
+        0
+    }
+
+    fn connect(g: &mut ChainInner, block: Block) -> Result<bool> {
+        let parent = g.blocks_by_hash.get(&block.header.parent)
+            .ok_or_else(|| anyhow!("unknown parent 0x{}", block.header.parent.to_hex()))?;
+        ensure!(block.header.number == parent.header.number + 1,
+            "block number {} does not follow parent {}", block.header.number, parent.header.number);
+        let median = Self::median_time_past(g, &block.header.parent);
+        ensure!(block.header.timestamp > median,
+            "block timestamp {} not after median time past {}", block.header.timestamp, median);
+        ensure!(block.header.merkle_root == merkle_root(&block.txs), "merkle root mismatch");
+
+        let work = g.work_by_hash[&block.header.parent] + block.work;
+        let hash = block.hash;
+        g.work_by_hash.insert(hash, work);
+        g.blocks_by_hash.insert(hash, block);
+        if work <= g.total_work { return Ok(false); }
+
+        // More work alone doesn't let a fork rewrite deep history
+        let depth = g.blocks_by_hash[&g.head].header.number.saturating_sub(Self::fork_point(g, &hash));
+        if depth > g.max_reorg_depth {
+            if g.refused_reorgs.insert(hash) {
+                error!("Security alert: refusing reorg to 0x{} with work {}; it forks {} blocks below the head (limit {})",
+                    hash.to_hex(), work, depth, g.max_reorg_depth);
+            }
+            return Ok(false);
+        }
+
+        // Rewrite the number index back to the fork point and drop the abandoned tail
+        let head_number = g.blocks_by_hash[&hash].header.number;
+        g.hash_by_number.retain(|n, _| *n <= head_number);
+        let mut cursor = hash;
+        loop {
+            let b = &g.blocks_by_hash[&cursor];
+            let (number, parent) = (b.header.number, b.header.parent);
+            if g.hash_by_number.get(&number) == Some(&cursor) { break; }
+            g.hash_by_number.insert(number, cursor);
+            if number == 0 { break; }
+            cursor = parent;
+        }
+        g.head = hash;
+        g.total_work = work;
+        Ok(true)
+    }
+}
+
+/// Validate every transaction in `block` with the consensus rules used by
+/// chainstate; only the first transaction may be a coinbase
+pub fn validate_block_transactions<FLookup>(spec: &ChainSpec, block: &Block, mut lookup: FLookup) -> Result<()>
+where
+    FLookup: FnMut(&OutPoint) -> Option<(Amount, OutputType, Height, bool)>
+{
+    ensure!(block.header.merkle_root == merkle_root(&block.txs), "merkle root mismatch");
+    check_block_sigops(spec, &block.txs)?;
+    for (i, tx) in block.txs.iter().enumerate() {
+        let is_coinbase = i == 0 && tx.is_coinbase();
+        ensure!(is_coinbase || !tx.is_coinbase(), "coinbase at position {}", i);
+        validate_transaction(spec, block.header.number, tx, is_coinbase, &mut lookup)
+            .map_err(|e| anyhow!("transaction {}: {}", i, e))?;
+    }
+    Ok(())
+}
+
+fn merkle_root(txs: &[Transaction]) -> Hash32 {
+    qc_validation::merkle_root(txs)
 }
 
-fn merkle_root(txs:&[Tx])->String{
-    if txs.is_empty(){ return format!("0x{}", hex::encode([0u8;32])); }
-    let mut hashes: Vec<Hash> = txs.iter().map(|t|{
-        let mut h=Sha256::new(); h.update(serde_json::to_vec(t).unwrap()); let first=h.finalize();
-        let mut h2=Sha256::new(); h2.update(first); let out=h2.finalize();
-        let mut a=[0u8;32]; a.copy_from_slice(&out); a
-    }).collect();
-    while hashes.len()>1{
-        let mut next=Vec::new();
-        for pair in hashes.chunks(2){
-            let a=pair[0]; let b=*pair.get(1).unwrap_or(&pair[0]);
-            let mut h=Sha256::new(); h.update(a); h.update(b);
-            let first=h.finalize(); let mut h2=Sha256::new(); h2.update(first);
-            let out=h2.finalize(); let mut arr=[0u8;32]; arr.copy_from_slice(&out); next.push(arr);
-        }
-        hashes=next;
-    }
-    format!("0x{}", hex::encode(hashes[0]))
+/// Double SHA-256 over the header fields, the hash a block is mined to
+pub fn header_hash(parent: &Hash32, number: u64, timestamp: u64, difficulty: u128, nonce: u64, merkle_root: &Hash32) -> Hash32 {
+    let mut h = Sha256::new();
+    h.update(parent.0);
+    h.update(number.to_be_bytes());
+    h.update(timestamp.to_be_bytes());
+    h.update(difficulty.to_be_bytes());
+    h.update(nonce.to_be_bytes());
+    h.update(merkle_root.0);
+    Hash32(Sha256::digest(h.finalize()).into())
 }
 
 fn now()->u64{
     SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pqcrypto_traits::sign::PublicKey as _;
+    use qc_crypto::{generate_keypair, pq_sign, tx_sighash};
+    use qc_types::{TxIn, TxOut};
+    use qc_validation::block_subsidy;
+
+    fn spec() -> ChainSpec {
+        toml::from_str(include_str!("../../../chain_spec.toml")).unwrap()
+    }
+
+    type Prevout = (Amount, OutputType, Height, bool);
+
+    /// A coinbase plus a signed spend of a P2PQ output, both built in the
+    /// types model, and a lookup that knows the spent output
+    fn types_model_txs(spec: &ChainSpec) -> (Vec<Transaction>, impl FnMut(&OutPoint) -> Option<Prevout>) {
+        let (pk, sk) = generate_keypair();
+        let pubkey = pk.as_bytes().to_vec();
+        let funding = OutPoint::new(Hash32([5u8; 32]), 0);
+
+        let coinbase = Transaction::new(1, vec![], vec![TxOut::new_p2pq(block_subsidy(spec, 1), pubkey.clone())], 0);
+        let mut spend = Transaction::new(
+            1,
+            vec![TxIn::new(funding.clone(), vec![], false)],
+            vec![TxOut::new_p2pq(40_000, pubkey.clone())],
+            0,
+        );
+        let sighash = tx_sighash(&bincode::serialize(&spend).unwrap());
+        spend.vin[0].pq_signature = pq_sign(&sk, &sighash);
+
+        let lookup = move |op: &OutPoint| {
+            (*op == funding).then(|| (50_000, OutputType::P2PQ { pubkey: pubkey.clone() }, 0, false))
+        };
+        (vec![coinbase, spend], lookup)
+    }
+
+    fn block(parent: &Block, work: u128, tag: &str) -> Block {
+        Block {
+            hash: Hash32(Sha256::digest(format!("{}{}", tag, parent.header.number + 1)).into()),
+            header: BlockHeader {
+                parent: parent.hash,
+                number: parent.header.number + 1,
+                timestamp: parent.header.timestamp + 30,
+                difficulty: work,
+                nonce: 0,
+                merkle_root: merkle_root(&[]),
+            },
+            txs: vec![],
+            work,
+        }
+    }
+
+    fn test_chain() -> (Chain, Block) {
+        let genesis = Block {
+            hash: Hash32([0xab; 32]),
+            header: BlockHeader { parent: Hash32::zero(), number: 0, timestamp: 1_700_000_000, difficulty: 1, nonce: 0, merkle_root: merkle_root(&[]) },
+            txs: vec![],
+            work: 1,
+        };
+        (Chain::from_genesis(genesis.clone()), genesis)
+    }
+
+    #[test]
+    fn test_head_returns_genesis_without_deadlock() {
+        let (chain, genesis) = test_chain();
+        let (done, finished) = std::sync::mpsc::channel();
+        let reader = chain.clone();
+        std::thread::spawn(move || done.send(reader.head()).unwrap());
+        let head = finished.recv_timeout(std::time::Duration::from_secs(5)).expect("head() deadlocked").unwrap();
+        assert_eq!(head.hash, genesis.hash);
+        assert_eq!(head.header.number, 0);
+
+        // The lock is released again afterwards
+        assert_eq!(chain.head().unwrap().hash, genesis.hash);
+        assert!(Chain(Arc::new(Mutex::new(ChainInner::default()))).head().is_none());
+    }
+
+    #[test]
+    fn test_height_follows_head_through_reorg() {
+        let (chain, genesis) = test_chain();
+        let a1 = block(&genesis, 10, "a");
+        let a2 = block(&a1, 10, "a");
+        let a3 = block(&a2, 10, "a");
+        for b in [&a1, &a2, &a3] { assert!(chain.import_block(b.clone()).unwrap()); }
+        assert_eq!(chain.height(), 3);
+
+        // Shorter fork with more cumulative work takes over
+        let b1 = block(&genesis, 10, "b");
+        let b2 = block(&b1, 100, "b");
+        assert!(!chain.import_block(b1.clone()).unwrap());
+        assert_eq!(chain.height(), 3);
+        assert!(chain.import_block(b2.clone()).unwrap());
+
+        assert_eq!(chain.head().unwrap().hash, b2.hash);
+        assert_eq!(chain.height(), 2);
+        assert_eq!(chain.height(), chain.head().unwrap().header.number);
+        assert_eq!(chain.get_block_by_number(1).unwrap().hash, b1.hash);
+        assert!(chain.get_block_by_number(3).is_none());
+
+        // Switching back extends past the old height again
+        let a4 = block(&a3, 100, "a");
+        assert!(chain.import_block(a4.clone()).unwrap());
+        assert_eq!(chain.height(), 4);
+        assert_eq!(chain.get_block_by_number(1).unwrap().hash, a1.hash);
+    }
+
+    #[test]
+    fn test_block_by_number_ignores_stale_index_after_reorg() {
+        let (chain, genesis) = test_chain();
+        let a1 = block(&genesis, 10, "a");
+        let a2 = block(&a1, 10, "a");
+        let a3 = block(&a2, 10, "a");
+        for b in [&a1, &a2, &a3] {
+            chain.import_block(b.clone()).unwrap();
+        }
+        let b1 = block(&genesis, 20, "b");
+        let b2 = block(&b1, 20, "b");
+        chain.import_block(b1.clone()).unwrap();
+        assert!(chain.import_block(b2.clone()).unwrap());
+
+        // Leave the abandoned branch in the index, as an interrupted reorg could
+        for b in [&a1, &a2, &a3] {
+            chain.0.lock().hash_by_number.insert(b.header.number, b.hash);
+        }
+        assert_eq!(chain.get_block_by_number(0).unwrap().hash, genesis.hash);
+        assert_eq!(chain.get_block_by_number(1).unwrap().hash, b1.hash);
+        assert_eq!(chain.get_block_by_number(2).unwrap().hash, b2.hash);
+        assert!(chain.get_block_by_number(3).is_none());
+    }
+
+    #[test]
+    fn test_reorg_refused_beyond_max_depth() {
+        let (chain, genesis) = test_chain();
+        let chain = chain.with_max_reorg_depth(2);
+        let mut active = vec![genesis.clone()];
+        for _ in 0..4 {
+            let b = block(active.last().unwrap(), 10, "a");
+            chain.import_block(b.clone()).unwrap();
+            active.push(b);
+        }
+
+        // Forking two blocks below the head is within the limit
+        let shallow = block(&active[2], 100, "s");
+        assert!(chain.import_block(shallow.clone()).unwrap());
+        assert_eq!(chain.head().unwrap().hash, shallow.hash);
+        assert!(chain.refused_reorgs().is_empty());
+
+        // Forking three below it is not, however much work it brings
+        let deep1 = block(&active[0], 10, "d");
+        let deep2 = block(&deep1, 1_000, "d");
+        chain.import_block(deep1).unwrap();
+        assert!(!chain.import_block(deep2.clone()).unwrap());
+        assert_eq!(chain.head().unwrap().hash, shallow.hash);
+        assert_eq!(chain.height(), 3);
+        assert_eq!(chain.get_block_by_number(1).unwrap().hash, active[1].hash);
+        assert_eq!(chain.refused_reorgs(), vec![deep2.hash]);
+    }
+
+    #[test]
+    fn test_block_after_placed_state_retargets_once() {
+        let head = Block {
+            hash: Hash32([0xcd; 32]),
+            header: BlockHeader { parent: Hash32([0xab; 32]), number: 99, timestamp: now(), difficulty: 1_000, nonce: 0, merkle_root: merkle_root(&[]) },
+            txs: vec![],
+            work: 1_000,
+        };
+        let chain = Chain::with_state(head.clone(), 50_000, 1);
+        let before = chain.snapshot();
+        assert_eq!(before.head.hash, head.hash);
+        assert_eq!(before.total_work, 50_000);
+        assert_eq!(chain.height(), 99);
+
+        // The head is fresh, so the next block is mined 5% harder, once
+        let next = chain.mine_one();
+        assert_eq!(next.header.number, 100);
+        assert_eq!(next.header.difficulty, 1_050);
+        let after = chain.snapshot();
+        assert_eq!(after.head.hash, next.hash);
+        assert_eq!(after.total_work, 50_000 + 1_050);
+        assert_eq!(after.block_count, 2);
+
+        // Snapshots are copies, unaffected by later blocks
+        assert_eq!(before.head.hash, head.hash);
+        assert_eq!(before.block_count, 1);
+    }
+
+    #[test]
+    fn test_side_branch_does_not_move_height() {
+        let (chain, genesis) = test_chain();
+        let a1 = block(&genesis, 10, "a");
+        chain.import_block(a1.clone()).unwrap();
+        for i in 0..5 {
+            let side = block(&genesis, 1, &format!("s{}", i));
+            assert!(!chain.import_block(side).unwrap());
+        }
+        assert_eq!(chain.height(), 1);
+        assert_eq!(chain.head().unwrap().hash, a1.hash);
+    }
+
+    #[test]
+    fn test_rapid_mining_strictly_increases_timestamps() {
+        let (chain, _) = test_chain();
+        let chain = chain.with_min_difficulty(1);
+        let mut last = chain.head().unwrap().header.timestamp;
+        for _ in 0..20 {
+            let b = chain.mine_one();
+            assert!(b.header.timestamp > last, "{} <= {}", b.header.timestamp, last);
+            last = b.header.timestamp;
+        }
+        assert_eq!(chain.height(), 20);
+    }
+
+    #[test]
+    fn test_import_rejects_timestamp_at_or_before_median() {
+        let (chain, genesis) = test_chain();
+        let mut tip = genesis;
+        for i in 0..MEDIAN_TIME_SPAN {
+            tip = block(&tip, 1, &format!("t{}", i));
+            chain.import_block(tip.clone()).unwrap();
+        }
+        // Ancestors are 30s apart, so the median is 5 blocks back from the tip
+        let median = tip.header.timestamp - 5 * 30;
+
+        let mut stale = block(&tip, 1, "stale");
+        stale.header.timestamp = median;
+        assert!(chain.import_block(stale).is_err());
+
+        let mut behind_parent = block(&tip, 1, "ok");
+        behind_parent.header.timestamp = median + 1;
+        assert!(chain.import_block(behind_parent).unwrap());
+    }
+
+    #[test]
+    fn test_mined_blocks_link_by_byte_hash() {
+        let (chain, genesis) = test_chain();
+        let chain = chain.with_min_difficulty(1);
+        let mut parent = genesis;
+        for _ in 0..5 {
+            let b = chain.mine_one();
+            assert_eq!(b.header.parent, parent.hash);
+            let h = &b.header;
+            assert_eq!(b.hash, header_hash(&h.parent, h.number, h.timestamp, h.difficulty, h.nonce, &h.merkle_root));
+            parent = b;
+        }
+        assert_eq!(chain.head().unwrap().hash, parent.hash);
+        assert_eq!(chain.get_block_by_number(5).unwrap().hash, parent.hash);
+
+        // A fresh chain's genesis hangs off the zero hash
+        assert_eq!(Chain::make_block(None, 0, 1, 1, vec![]).header.parent, Hash32::zero());
+    }
+
+    #[test]
+    fn test_hashes_rendered_as_hex_only_in_json() {
+        let (chain, _) = test_chain();
+        let block = chain.with_min_difficulty(1).mine_one();
+        let json = serde_json::to_value(&block).unwrap();
+        assert_eq!(json["hash"], format!("0x{}", block.hash.to_hex()));
+        assert_eq!(json["header"]["parent"], format!("0x{}", "ab".repeat(32)));
+
+        let decoded: Block = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(decoded.hash, block.hash);
+        assert_eq!(decoded.header.merkle_root, block.header.merkle_root);
+
+        let mut malformed = json;
+        malformed["header"]["parent"] = "0xnot-hex".into();
+        assert!(serde_json::from_value::<Block>(malformed).is_err());
+    }
+
+    #[test]
+    fn test_unknown_parent_held_as_orphan() {
+        let (chain, genesis) = test_chain();
+        let x1 = block(&genesis, 1, "x");
+        let x2 = block(&x1, 1, "x");
+        let x3 = block(&x2, 1, "x");
+        assert!(!chain.import_block(x3.clone()).unwrap());
+        assert!(!chain.import_block(x2.clone()).unwrap());
+        assert_eq!(chain.height(), 0);
+        assert!(chain.is_orphan(&x2.hash));
+        assert_eq!(chain.orphan_count(), 2);
+
+        // A zeroed parent above genesis can't connect and isn't held
+        let mut zeroed = block(&genesis, 1, "z");
+        zeroed.header.parent = Hash32::zero();
+        zeroed.header.number = 5;
+        assert!(chain.import_block(zeroed).is_err());
+        assert_eq!(chain.orphan_count(), 2);
+
+        // The missing parent releases its descendants in order
+        assert!(chain.import_block(x1).unwrap());
+        assert_eq!(chain.orphan_count(), 0);
+        assert_eq!(chain.height(), 3);
+        assert_eq!(chain.head().unwrap().hash, x3.hash);
+    }
+
+    #[test]
+    fn test_types_transactions_flow_through_mining_and_validation() {
+        let spec = spec();
+        let (txs, lookup) = types_model_txs(&spec);
+        let (chain, _) = test_chain();
+        let chain = chain.with_min_difficulty(1);
+
+        let block = chain.mine_with(txs.clone());
+        assert_eq!(block.txs, txs);
+        assert_eq!(chain.head().unwrap().txs, txs);
+        assert_eq!(block.header.merkle_root, qc_validation::merkle_root(&txs));
+        validate_block_transactions(&spec, &block, lookup).unwrap();
+    }
+
+    #[test]
+    fn test_altered_transaction_rejected() {
+        let spec = spec();
+        let (txs, lookup) = types_model_txs(&spec);
+        let (chain, _) = test_chain();
+        let block = chain.with_min_difficulty(1).mine_with(txs);
+
+        // Changing the spend breaks the merkle commitment
+        let mut tampered = block.clone();
+        tampered.txs[1].vout[0].value += 1;
+        let (fresh, _) = test_chain();
+        assert!(fresh.import_block(tampered.clone()).is_err());
+        assert!(validate_block_transactions(&spec, &tampered, lookup).is_err());
+        assert!(fresh.import_block(block).unwrap());
+    }
+}
@@ -1,19 +1,25 @@
 mod storage;
 mod chainstate;
 mod mempool;
-mod p2p;
 mod pow;
 mod rpc;
-mod asert;
+mod rejections;
+mod snapshot;
 mod miner;
 mod target;
 
 use crate::storage::Storage;
-use crate::chainstate::ChainState;
-use crate::miner::{build_candidate, mine_block_cpu};
+use crate::chainstate::{ChainState, CHAIN_EVENT_BUFFER};
+use crate::miner::{mine_block_cpu, mine_template, RewardDestination, TemplateCache};
+use crate::rejections::{RejectionLog, RejectionLogConfig, DEFAULT_REJECTION_HISTORY};
+use crate::snapshot::AssumeUtxo;
+use crate::rpc::{NodeCapabilities, RpcChain, RpcConfig, DEFAULT_RPC_BIND};
+use clap::Parser;
+use qc_node::p2p;
+use parking_lot::Mutex;
 use qc_types::*;
-use qc_validation::{ChainSpec, merkle_root, block_subsidy};
-use std::{fs, path::PathBuf};
+use qc_validation::{ChainSpec, merkle_root, block_subsidy, reconcile_supply};
+use std::{fs, net::SocketAddr, path::PathBuf, sync::Arc};
 use tracing::{info, error, Level};
 use tracing_subscriber::EnvFilter;
 
@@ -22,8 +28,66 @@ fn read_spec(path: &str) -> ChainSpec {
     toml::from_str(&content).expect("parse chain spec")
 }
 
+#[derive(Parser)]
+#[command(name = "qc-node", about = "QuantumCoin full node")]
+struct Cli {
+    /// Address the RPC server listens on
+    #[arg(long, env = "QC_RPC_BIND", default_value = DEFAULT_RPC_BIND)]
+    rpc_bind: SocketAddr,
+
+    /// Require `Authorization: Bearer <token>` on every RPC request
+    #[arg(long, env = "QC_RPC_TOKEN", hide_env_values = true)]
+    rpc_token: Option<String>,
+
+    /// Hex-encoded Dilithium2 public key that mined block rewards pay to
+    #[arg(long, env = "QC_REWARD_PUBKEY")]
+    reward_pubkey: Option<String>,
+
+    /// Pay block rewards to a RevStop-revocable output
+    #[arg(long)]
+    reward_revocable: bool,
+
+    /// Skip indexing transactions by txid
+    #[arg(long)]
+    no_txindex: bool,
+
+    /// Don't log each rejected block and transaction; they are still counted
+    #[arg(long)]
+    quiet_rejections: bool,
+
+    /// Recent rejections kept for `getrejectionstats`
+    #[arg(long, default_value_t = DEFAULT_REJECTION_HISTORY)]
+    rejection_history: usize,
+
+    /// Hex hash of a block whose ancestors skip signature verification,
+    /// overriding the chain spec's `assume_valid`
+    #[arg(long)]
+    assumevalid: Option<String>,
+
+    /// Write the UTXO set at the current tip to this file, then exit
+    #[arg(long, value_name = "PATH")]
+    dumputxoset: Option<PathBuf>,
+
+    /// Bootstrap an empty data directory from a UTXO snapshot file instead
+    /// of replaying history from genesis
+    #[arg(long, value_name = "PATH", requires = "assumeutxo")]
+    loadutxoset: Option<PathBuf>,
+
+    /// Snapshot `--loadutxoset` must match, as `<height>:<block hash>:<commitment>`
+    /// printed by `--dumputxoset` on a node you trust
+    #[arg(long)]
+    assumeutxo: Option<AssumeUtxo>,
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    let reward = cli
+        .reward_pubkey
+        .as_deref()
+        .map(|pubkey| RewardDestination::from_hex(pubkey, cli.reward_revocable))
+        .transpose()?;
+
     // Initialize logging
     tracing_subscriber::fmt()
         .with_env_filter(EnvFilter::from_default_env().add_directive(Level::INFO.into()))
@@ -33,8 +97,21 @@ async fn main() -> anyhow::Result<()> {
     info!("⚛️ Post-Quantum Cryptocurrency with RevStop Protection");
 
     // Load chain specification
-    let spec = read_spec("chain_spec.toml");
+    let mut spec = read_spec("chain_spec.toml");
+    if cli.assumevalid.is_some() {
+        spec.consensus.assume_valid = cli.assumevalid.clone();
+    }
+    let spec = Arc::new(spec);
     info!("📋 Loaded chain spec: {} ({})", spec.network.name, spec.network.symbol);
+    if spec.consensus.no_pow && spec.pow_required() {
+        anyhow::bail!("no_pow is only allowed on regtest, not {:?}", spec.network.kind);
+    }
+    if let Some(hash) = &spec.consensus.assume_valid {
+        if spec.assume_valid().is_none() {
+            anyhow::bail!("assume_valid is not a 32-byte hex block hash: {}", hash);
+        }
+        info!("⏩ Skipping signature checks up to assumed-valid block {}", hash);
+    }
 
     // Initialize data directory
     let datadir = PathBuf::from("./.qc-data");
@@ -44,10 +121,39 @@ async fn main() -> anyhow::Result<()> {
     }
 
     // Open storage
-    let store = Storage::open(&datadir)?;
+    let store = Arc::new(Storage::open_with_txindex(&datadir, !cli.no_txindex)?);
     info!("💾 Storage initialized");
 
-    let cs = ChainState { spec: &spec, store: &store };
+    if let Some(path) = &cli.dumputxoset {
+        let trusted = snapshot::dump_utxo_set(&store, path)?;
+        info!("📦 Load it with --loadutxoset {} --assumeutxo {}", path.display(), trusted);
+        return Ok(());
+    }
+    if let (Some(path), Some(trusted)) = (&cli.loadutxoset, &cli.assumeutxo) {
+        snapshot::load_utxo_set(&store, path, trusted)?;
+    }
+
+    let capabilities = NodeCapabilities {
+        network: spec.network.name.clone(),
+        txindex: store.txindex(),
+        ..NodeCapabilities::default()
+    };
+    let rejections = Arc::new(RejectionLog::new(RejectionLogConfig {
+        log: !cli.quiet_rejections,
+        history: cli.rejection_history,
+    }));
+    // Devnet mining and `submitblock` both extend the tip; one lock orders them
+    let tip_lock: Arc<Mutex<()>> = Arc::default();
+    let rpc_config = RpcConfig {
+        bind: cli.rpc_bind,
+        auth_token: cli.rpc_token,
+        capabilities,
+        rejections: rejections.clone(),
+        chain: Some(RpcChain { spec: spec.clone(), store: store.clone(), tip_lock: tip_lock.clone() }),
+    };
+
+    let (chain_events, _) = tokio::sync::broadcast::channel(CHAIN_EVENT_BUFFER);
+    let cs = ChainState { spec: &spec, store: &store, events: Some(&chain_events), rejections: Some(&rejections) };
 
     // Check if we have existing blockchain
     if let Some(tip_hash) = store.get_tip()? {
@@ -71,6 +177,12 @@ async fn main() -> anyhow::Result<()> {
             ],
         };
         
+        // Premine plus the whole subsidy schedule has to land on max supply
+        let genesis_value: Amount = coinbase.vout.iter().map(|o| o.value).sum();
+        let supply = reconcile_supply(&spec, genesis_value - block_subsidy(&spec, 0))?;
+        info!("🪙 Supply reconciles: {} sats emitted, {} sats short of the cap from halving rounding",
+            supply.emission_sats, supply.shortfall_sats);
+
         let mut genesis = Block{
             header: BlockHeader{
                 version: 1, 
@@ -96,8 +208,10 @@ async fn main() -> anyhow::Result<()> {
     info!("🌐 Starting network services...");
     
     // Start RPC server
-    tokio::spawn(async {
-        if let Err(e) = rpc::serve_rpc().await {
+    let rpc_bind = rpc_config.bind;
+    let rpc_events = chain_events.clone();
+    tokio::spawn(async move {
+        if let Err(e) = rpc::serve_rpc(rpc_config, rpc_events).await {
             error!("RPC server error: {}", e);
         }
     });
@@ -110,43 +224,37 @@ async fn main() -> anyhow::Result<()> {
     });
 
     // Mine a few devnet blocks for testing
-    info!("⛏️ Mining initial devnet blocks...");
-    let mut prev_hash = if let Some(tip) = store.get_tip()? {
-        tip
-    } else {
-        return Err(anyhow::anyhow!("No genesis block found"));
-    };
-
-    for height in 1..=5 {
-        let coinbase = Transaction{ 
-            version: 1, 
-            lock_time: 0, 
-            vin: vec![],
-            vout: vec![ 
-                TxOut{ 
-                    value: block_subsidy(&spec, height), 
-                    kind: OutputType::P2PQ{ pubkey: vec![1u8; 1312] } 
-                } 
-            ] 
-        };
-        
-        let mut block = build_candidate(prev_hash, 0x1d00ffff, vec![coinbase]);
-        
-        info!("⛏️ Mining block {}...", height);
-        if let Some(found) = mine_block_cpu(block, 10_000_000) { 
-            block = found; 
+    if let Some(reward) = &reward {
+        info!("⛏️ Mining initial devnet blocks, rewards to {}", reward.address());
+        let mut templates = TemplateCache::new(reward.clone(), 0x1d00ffff, 0);
+        for _ in 0..5 {
+            let Some(prev_hash) = store.get_tip()? else {
+                return Err(anyhow::anyhow!("No genesis block found"));
+            };
+            let height = store.get_tip_height()?.map_or(0, |h| h + 1);
+            let template = templates.template(&spec, prev_hash, height, 0, Vec::new);
+            
+            info!("⛏️ Mining block {}...", height);
+            let block = mine_template(template, 10_000_000).unwrap_or_else(|| template.block.clone());
+            
+            // A block submitted over RPC while mining makes this one stale
+            let _tip = tip_lock.lock();
+            if store.get_tip()? != Some(prev_hash) {
+                info!("🔁 Tip moved while mining block {}, dropping it", height);
+                continue;
+            }
+            cs.apply_block(height, &block)?;
+            
+            info!("✅ Mined block {} with hash: {}", height, cs.block_hash(&block.header).to_hex());
         }
-        
-        cs.apply_block(height, &block)?;
-        prev_hash = cs.block_hash(&block.header);
-        
-        info!("✅ Mined block {} with hash: {}", height, prev_hash.to_hex());
+    } else {
+        info!("⛏️ No --reward-pubkey set, skipping devnet mining");
     }
 
     info!("🎉 QuantumCoin node startup complete!");
-    info!("🔗 RPC server: http://127.0.0.1:8332");
+    info!("🔗 RPC server: http://{}", rpc_bind);
     info!("🌐 P2P listening: 0.0.0.0:8333");
-    info!("💡 Try: curl -s http://127.0.0.1:8332/gethealth");
+    info!("💡 Try: curl -s http://{}/gethealth", rpc_bind);
 
     // Keep node running
     futures::future::pending::<()>().await;
@@ -0,0 +1,302 @@
+//! Block candidates and CPU mining.
+
+use crate::pow::check_proof_of_work;
+use anyhow::{bail, Context, Result};
+use qc_types::target::compact_to_target;
+use qc_types::*;
+use qc_validation::{block_subsidy, merkle_root, ChainSpec};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Length of a Dilithium2 public key
+pub const REWARD_PUBKEY_LEN: usize = 1312;
+
+/// Key that block rewards are paid to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RewardDestination {
+    pub pubkey: Vec<u8>,
+    /// Pay to a `P2PQRevocable` output instead of plain `P2PQ`
+    pub revocable: bool,
+}
+
+impl RewardDestination {
+    pub fn new(pubkey: Vec<u8>, revocable: bool) -> Result<Self> {
+        if pubkey.len() != REWARD_PUBKEY_LEN {
+            bail!("reward pubkey is {} bytes, expected {}", pubkey.len(), REWARD_PUBKEY_LEN);
+        }
+        Ok(Self { pubkey, revocable })
+    }
+
+    pub fn from_hex(pubkey_hex: &str, revocable: bool) -> Result<Self> {
+        let pubkey = hex::decode(pubkey_hex.trim()).context("reward pubkey is not hex")?;
+        Self::new(pubkey, revocable)
+    }
+
+    pub fn address(&self) -> String {
+        qc_crypto::address_from_pubkey(&self.pubkey)
+    }
+
+    fn output(&self, spec: &ChainSpec, value: Amount) -> TxOut {
+        if self.revocable {
+            TxOut::new_revocable(value, self.pubkey.clone(), spec.revstop.window_blocks)
+        } else {
+            TxOut::new_p2pq(value, self.pubkey.clone())
+        }
+    }
+}
+
+/// Coinbase paying the block subsidy at `height` plus the `fees` its block's
+/// transactions pay to `reward`. The height goes in `lock_time` so coinbases
+/// to the same key get distinct txids.
+pub fn coinbase_transaction(spec: &ChainSpec, height: u64, fees: Amount, reward: &RewardDestination) -> Transaction {
+    Transaction::new(1, vec![], vec![reward.output(spec, block_subsidy(spec, height) + fees)], height as u32)
+}
+
+/// Unmined block on top of `prev_block`, timestamped now
+pub fn build_candidate(prev_block: Hash32, bits: u32, txs: Vec<Transaction>) -> Block {
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let header = BlockHeader::new(1, prev_block, merkle_root(&txs), time, bits, 0);
+    Block::new(header, txs)
+}
+
+/// Search nonces from the block's current one; `None` if none of the next
+/// `max_tries` meet its target
+pub fn mine_block_cpu(mut block: Block, max_tries: u64) -> Option<Block> {
+    let target = compact_to_target(block.header.bits);
+    for _ in 0..max_tries {
+        if check_proof_of_work(&block.hash().0, &target) {
+            return Some(block);
+        }
+        block.header.nonce = block.header.nonce.wrapping_add(1);
+    }
+    None
+}
+
+/// Candidate block and what it was built from
+#[derive(Debug, Clone)]
+pub struct BlockTemplate {
+    pub tip: Hash32,
+    pub height: u64,
+    pub block: Block,
+    /// Fees paid by the template's non-coinbase transactions
+    pub fees: Amount,
+}
+
+/// Keeps one block template across nonce attempts. Transaction selection and
+/// the merkle root are only redone when the tip moves or the mempool holds
+/// more in fees than the template collects, by at least `min_fee_gain`.
+pub struct TemplateCache {
+    reward: RewardDestination,
+    bits: u32,
+    min_fee_gain: Amount,
+    template: Option<BlockTemplate>,
+    builds: u64,
+}
+
+impl TemplateCache {
+    pub fn new(reward: RewardDestination, bits: u32, min_fee_gain: Amount) -> Self {
+        Self { reward, bits, min_fee_gain, template: None, builds: 0 }
+    }
+
+    /// Number of templates built so far
+    pub fn builds(&self) -> u64 {
+        self.builds
+    }
+
+    /// Template for the block at `height` on `tip`. `pending_fees` is the
+    /// mempool's total fees; `select` picks the transactions, with the fee
+    /// each pays, and is only called when the template is rebuilt.
+    pub fn template<F>(&mut self, spec: &ChainSpec, tip: Hash32, height: u64, pending_fees: Amount, select: F) -> &mut BlockTemplate
+    where
+        F: FnOnce() -> Vec<(Transaction, Amount)>,
+    {
+        let stale = match &self.template {
+            Some(t) => t.tip != tip || (pending_fees > t.fees && pending_fees - t.fees >= self.min_fee_gain),
+            None => true,
+        };
+        if stale {
+            let selected = select();
+            let fees = selected.iter().map(|(_, fee)| *fee).sum();
+            let txs = std::iter::once(coinbase_transaction(spec, height, fees, &self.reward))
+                .chain(selected.into_iter().map(|(tx, _)| tx))
+                .collect();
+            self.template = Some(BlockTemplate { tip, height, block: build_candidate(tip, self.bits, txs), fees });
+            self.builds += 1;
+        }
+        self.template.as_mut().expect("template built above")
+    }
+}
+
+/// Try the next `max_tries` nonces against a cached template, picking up
+/// where the last call stopped. The coinbase has no input to carry an
+/// extranonce, so when the nonce wraps the header time is rolled instead.
+pub fn mine_template(template: &mut BlockTemplate, max_tries: u64) -> Option<Block> {
+    let target = compact_to_target(template.block.header.bits);
+    let header = &mut template.block.header;
+    for _ in 0..max_tries {
+        let found = check_proof_of_work(&header.hash().0, &target);
+        let attempt = header.clone();
+        header.nonce = header.nonce.wrapping_add(1);
+        if header.nonce == 0 {
+            header.time += 1;
+        }
+        if found {
+            return Some(Block::new(attempt, template.block.txs.clone()));
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pqcrypto_traits::sign::PublicKey as _;
+    use qc_crypto::{generate_keypair, pq_sign, tx_sighash};
+    use qc_validation::{validate_transaction, ValidationError};
+
+    fn spec() -> ChainSpec {
+        toml::from_str(include_str!("../../../chain_spec.toml")).unwrap()
+    }
+
+    #[test]
+    fn test_coinbase_pays_configured_key() {
+        let spec = spec();
+        let (pk, _) = generate_keypair();
+        let reward = RewardDestination::new(pk.as_bytes().to_vec(), false).unwrap();
+
+        let coinbase = coinbase_transaction(&spec, 7, 0, &reward);
+        assert!(coinbase.is_coinbase());
+        assert_eq!(coinbase.vout, vec![TxOut::new_p2pq(block_subsidy(&spec, 7), reward.pubkey.clone())]);
+        assert_ne!(coinbase, coinbase_transaction(&spec, 8, 0, &reward));
+
+        // Fees collected by the block go to the miner too
+        assert_eq!(coinbase_transaction(&spec, 7, 250, &reward).vout[0].value, block_subsidy(&spec, 7) + 250);
+
+        let revocable = RewardDestination { revocable: true, ..reward.clone() };
+        assert_eq!(
+            coinbase_transaction(&spec, 7, 0, &revocable).vout[0].kind,
+            OutputType::P2PQRevocable { pubkey: reward.pubkey.clone(), window_blocks: spec.revstop.window_blocks }
+        );
+
+        assert!(RewardDestination::from_hex(&hex::encode(&reward.pubkey), false).is_ok());
+        assert!(RewardDestination::from_hex("01", false).is_err());
+        assert!(RewardDestination::from_hex("not hex", false).is_err());
+    }
+
+    #[test]
+    fn test_coinbase_spendable_by_reward_key_after_maturity() {
+        let spec = spec();
+        let (pk, sk) = generate_keypair();
+        let reward = RewardDestination::new(pk.as_bytes().to_vec(), false).unwrap();
+        let coinbase_height = 3;
+        let coinbase = coinbase_transaction(&spec, coinbase_height, 0, &reward);
+        let funding = OutPoint::new(Hash32([7u8; 32]), 0);
+
+        // The UTXO entry chainstate records for a coinbase output
+        let output = coinbase.vout[0].clone();
+        let lookup = |op: &OutPoint| {
+            (*op == funding).then(|| (output.value, output.kind.clone(), coinbase_height, true))
+        };
+
+        let unsigned = Transaction::new(
+            1,
+            vec![TxIn::new(funding.clone(), vec![], false)],
+            vec![TxOut::new_p2pq(output.value / 2, reward.pubkey.clone())],
+            0,
+        );
+        let sighash = tx_sighash(&bincode::serialize(&unsigned).unwrap());
+        let signed = |signature| {
+            let mut spend = unsigned.clone();
+            spend.vin[0].pq_signature = signature;
+            spend
+        };
+        let spend = signed(pq_sign(&sk, &sighash));
+
+        let maturity = spec.txpolicy.coinbase_maturity as u64;
+        assert!(matches!(
+            validate_transaction(&spec, coinbase_height + maturity - 1, &spend, false, lookup),
+            Err(ValidationError::CoinbaseImmature)
+        ));
+        validate_transaction(&spec, coinbase_height + maturity, &spend, false, lookup).unwrap();
+
+        let (_, other_sk) = generate_keypair();
+        assert!(matches!(
+            validate_transaction(&spec, coinbase_height + maturity, &signed(pq_sign(&other_sk, &sighash)), false, lookup),
+            Err(ValidationError::BadSignature)
+        ));
+    }
+
+    #[test]
+    fn test_mine_block_cpu() {
+        let spec = spec();
+        let (pk, _) = generate_keypair();
+        let reward = RewardDestination::new(pk.as_bytes().to_vec(), false).unwrap();
+        let candidate = build_candidate(Hash32::zero(), 0x207fffff, vec![coinbase_transaction(&spec, 1, 0, &reward)]);
+        assert_eq!(candidate.header.merkle_root, merkle_root(&candidate.txs));
+
+        let block = mine_block_cpu(candidate, 1_000).unwrap();
+        assert!(check_proof_of_work(&block.hash().0, &compact_to_target(0x207fffff)));
+        assert!(mine_block_cpu(block, 0).is_none());
+    }
+
+    fn fee_paying_tx(tag: u8) -> Transaction {
+        Transaction::new(1, vec![TxIn::new(OutPoint::new(Hash32([tag; 32]), 0), vec![], false)], vec![], 0)
+    }
+
+    #[test]
+    fn test_template_reused_across_nonce_attempts() {
+        let spec = spec();
+        let (pk, _) = generate_keypair();
+        let reward = RewardDestination::new(pk.as_bytes().to_vec(), false).unwrap();
+        let mut cache = TemplateCache::new(reward, 0x207fffff, 10_000);
+        let tip = Hash32([1; 32]);
+        let mut selections = 0;
+        let mut select = || {
+            selections += 1;
+            vec![(fee_paying_tx(1), 500)]
+        };
+
+        let mut found = Vec::new();
+        for _ in 0..20 {
+            let template = cache.template(&spec, tip, 1, 500, &mut select);
+            found.extend(mine_template(template, 4));
+        }
+        assert_eq!((cache.builds(), selections), (1, 1));
+
+        // Every block came from the one template, each at a different nonce
+        let merkle = found[0].header.merkle_root;
+        assert!(found.len() > 10);
+        assert!(found.iter().all(|b| b.header.merkle_root == merkle && b.header.prev_block == tip));
+        assert!(found.windows(2).all(|w| w[0].header.nonce < w[1].header.nonce));
+        assert!(found.iter().all(|b| check_proof_of_work(&b.hash().0, &compact_to_target(0x207fffff))));
+    }
+
+    #[test]
+    fn test_template_rebuilt_when_tip_changes() {
+        let spec = spec();
+        let (pk, _) = generate_keypair();
+        let reward = RewardDestination::new(pk.as_bytes().to_vec(), false).unwrap();
+        let mut cache = TemplateCache::new(reward.clone(), 0x207fffff, 10_000);
+        let (tip_a, tip_b) = (Hash32([1; 32]), Hash32([2; 32]));
+        let low_fee = || vec![(fee_paying_tx(1), 500)];
+
+        cache.template(&spec, tip_a, 1, 500, low_fee);
+        cache.template(&spec, tip_a, 1, 500, low_fee);
+        assert_eq!(cache.builds(), 1);
+
+        let template = cache.template(&spec, tip_b, 2, 500, low_fee);
+        assert_eq!((template.tip, template.height), (tip_b, 2));
+        assert_eq!(template.block.header.prev_block, tip_b);
+        assert_eq!(template.block.txs[0], coinbase_transaction(&spec, 2, 500, &reward));
+        assert_eq!(cache.builds(), 2);
+
+        // A small fee bump keeps the template; a big one rebuilds it
+        cache.template(&spec, tip_b, 2, 500, || unreachable!());
+        cache.template(&spec, tip_b, 2, 9_000, || unreachable!());
+        assert_eq!(cache.builds(), 2);
+        let template = cache.template(&spec, tip_b, 2, 10_500, || vec![(fee_paying_tx(1), 500), (fee_paying_tx(2), 10_000)]);
+        assert_eq!((template.fees, template.block.txs.len()), (10_500, 3));
+        assert_eq!(template.block.txs[0].vout[0].value, block_subsidy(&spec, 2) + 10_500);
+        assert_eq!(template.block.header.merkle_root, merkle_root(&template.block.txs));
+        assert_eq!(cache.builds(), 3);
+    }
+}
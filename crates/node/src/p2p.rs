@@ -9,6 +9,9 @@ use tokio::sync::RwLock;
 use std::sync::Arc;
 use serde::{Serialize, Deserialize};
 
+/// Version announced in our `Version` handshake
+pub const PROTOCOL_VERSION: u32 = 70015;
+
 #[derive(Clone, Debug)]
 pub struct Peer {
     pub id: String,
@@ -18,6 +21,9 @@ pub struct Peer {
     pub last_seen: u64,
     pub ban_score: u32,
     pub connected_at: u64,
+    /// Best height the peer has claimed, from its version handshake or a
+    /// later block announcement, and when it claimed it
+    pub reported_height: Option<(u64, u64)>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -28,6 +34,8 @@ pub enum P2PMessage {
         services: u64, 
         timestamp: u64,
         user_agent: String,
+        /// Sender's chain height, feeding the sync target
+        start_height: u64,
     },
     VerAck,
     GetBlocks { 
@@ -38,7 +46,7 @@ pub enum P2PMessage {
         block: crate::Block 
     },
     Transaction { 
-        tx: crate::Tx 
+        tx: crate::Transaction 
     },
     GetPeers,
     Peers { 
@@ -72,7 +80,7 @@ impl P2PNetwork {
                 "seed3.quantumcoincrypto.com".to_string(),
             ],
             listen_addr,
-            protocol_version: 70015,
+            protocol_version: PROTOCOL_VERSION,
             chain,
         }
     }
@@ -155,6 +163,7 @@ impl P2PNetwork {
                     last_seen: self.current_time(),
                     ban_score: 0,
                     connected_at: self.current_time(),
+                    reported_height: None,
                 };
                 
                 {
@@ -177,6 +186,7 @@ impl P2PNetwork {
             services: 1, // NODE_NETWORK
             timestamp: self.current_time(),
             user_agent: "/QuantumCoin:2.0.0/".to_string(),
+            start_height: self.chain.height(),
         };
         
         // In production, this would send actual network messages
@@ -241,6 +251,7 @@ impl P2PNetwork {
             last_seen: Self::now(),
             ban_score: 0,
             connected_at: Self::now(),
+            reported_height: None,
         };
         
         {
@@ -264,7 +275,7 @@ impl P2PNetwork {
                 tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
                 
                 // Broadcast latest block to all peers
-                let latest_block = chain.head();
+                let Some(latest_block) = chain.head() else { continue };
                 let peers_read = peers.read().await;
                 
                 for (peer_id, peer) in peers_read.iter() {
@@ -332,6 +343,42 @@ impl P2PNetwork {
         Ok(())
     }
     
+    /// Record what a peer's message says about it. The height in its
+    /// `Version` handshake, raised by each block it announces, feeds the
+    /// sync target through `peer_heights`.
+    pub async fn handle_message(&self, peer_id: &str, message: &P2PMessage) {
+        let now = self.current_time();
+        let mut peers = self.peers.write().await;
+        let Some(peer) = peers.get_mut(peer_id) else { return };
+        peer.last_seen = now;
+        match message {
+            P2PMessage::Version { version, services, start_height, .. } => {
+                peer.version = *version;
+                peer.services = *services;
+                peer.reported_height = Some((*start_height, now));
+            }
+            P2PMessage::Block { block } => {
+                let best = peer.reported_height.map_or(0, |(height, _)| height).max(block.header.number);
+                peer.reported_height = Some((best, now));
+            }
+            _ => {}
+        }
+    }
+
+    /// `(peer id, height, reported at)` for every peer that has claimed a height
+    pub async fn peer_heights(&self) -> Vec<(String, u64, u64)> {
+        self.peers.read().await.values()
+            .filter_map(|peer| peer.reported_height.map(|(height, at)| (peer.id.clone(), height, at)))
+            .collect()
+    }
+
+    /// Ids of every connected peer, sorted
+    pub async fn peer_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.peers.read().await.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
     /// Get current peer count
     pub async fn peer_count(&self) -> usize {
         self.peers.read().await.len()
@@ -352,6 +399,14 @@ impl P2PNetwork {
         }
     }
     
+    /// Add to a peer's ban score; `monitor_dos_protection` bans it past 100
+    pub async fn penalize_peer(&self, peer_id: &str, points: u32, reason: &str) {
+        if let Some(peer) = self.peers.write().await.get_mut(peer_id) {
+            peer.ban_score = peer.ban_score.saturating_add(points);
+            println!("⚠️  Peer {} penalized {} - Reason: {} (score: {})", peer_id, points, reason, peer.ban_score);
+        }
+    }
+    
     /// DoS protection - monitor peer behavior
     pub async fn monitor_dos_protection(&self) {
         let peers = Arc::clone(&self.peers);
@@ -443,4 +498,45 @@ mod tests {
         let banned = network.banned_peers.read().await;
         assert!(banned.contains(&test_addr));
     }
+
+    #[tokio::test]
+    async fn test_handshake_height_feeds_sync_target() {
+        use crate::sync::{SyncManager, SyncMode};
+
+        let chain = crate::Chain::new_genesis();
+        let network = Arc::new(P2PNetwork::new("127.0.0.1:0".parse().unwrap(), chain.clone()));
+        for (i, height) in [700u64, 705, 710].iter().enumerate() {
+            let addr: SocketAddr = format!("10.0.0.{}:8333", i + 1).parse().unwrap();
+            let peer = Peer {
+                id: addr.to_string(),
+                addr,
+                version: 0,
+                services: 0,
+                last_seen: 0,
+                ban_score: 0,
+                connected_at: 0,
+                reported_height: None,
+            };
+            network.peers.write().await.insert(peer.id.clone(), peer);
+            let version = P2PMessage::Version {
+                version: PROTOCOL_VERSION,
+                services: 1,
+                timestamp: 0,
+                user_agent: "/test/".to_string(),
+                start_height: *height,
+            };
+            network.handle_message(&addr.to_string(), &version).await;
+        }
+
+        // A block announced past the handshake height raises it
+        let mut block = chain.head().unwrap();
+        block.header.number = 720;
+        network.handle_message("10.0.0.1:8333", &P2PMessage::Block { block }).await;
+        assert_eq!(network.peers.read().await["10.0.0.1:8333"].reported_height.map(|(h, _)| h), Some(720));
+
+        let spec: qc_validation::ChainSpec = toml::from_str(include_str!("../../../chain_spec.toml")).unwrap();
+        let mut sync = SyncManager::new(&spec, chain, network, SyncMode::Full);
+        sync.refresh_peer_heights().await;
+        assert_eq!(sync.best_known_height(), 710);
+    }
 }
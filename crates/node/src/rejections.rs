@@ -0,0 +1,218 @@
+use parking_lot::Mutex;
+use qc_types::Hash32;
+use qc_validation::ValidationError;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use tracing::warn;
+
+/// Rejections `getrejectionstats` lists individually, newest first
+pub const DEFAULT_REJECTION_HISTORY: usize = 100;
+/// Rejected block hashes remembered so resubmissions skip revalidation
+pub const MAX_KNOWN_INVALID_BLOCKS: usize = 1_000;
+
+/// Why a block or transaction was turned away
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RejectReason {
+    BadProofOfWork,
+    BadMerkleRoot,
+    /// Coinbase pays more than subsidy plus fees
+    ExcessiveCoinbase,
+    /// Block carried a transaction that failed validation
+    InvalidTransaction,
+    /// Input spends an outpoint twice within the transaction
+    DoubleSpend,
+    /// Input's outpoint is unknown or already spent
+    MissingInput,
+    /// Outputs are worth more than the inputs, leaving a negative fee
+    InsufficientFee,
+    BadSignature,
+    /// RevStop cancel past its window, or on an output RevStop doesn't guard
+    Revstop,
+    ImmatureCoinbase,
+    /// Pays to a pubkey an earlier spend revealed
+    KeyReuse,
+    /// Size, count, dust or amount rules
+    Policy,
+}
+
+impl RejectReason {
+    /// Whether a block rejected for this reason is invalid under its header
+    /// hash alone. Proof of work is in the header, and sigops are counted only
+    /// once the merkle root ties the body to it; everything else depends on
+    /// a body a peer can swap out or on chain state that can change.
+    pub fn invalidates_header(self) -> bool {
+        matches!(self, RejectReason::BadProofOfWork | RejectReason::Policy)
+    }
+}
+
+impl From<&ValidationError> for RejectReason {
+    fn from(err: &ValidationError) -> Self {
+        match err {
+            ValidationError::DuplicateInput => RejectReason::DoubleSpend,
+            ValidationError::MissingInput => RejectReason::MissingInput,
+            ValidationError::InsufficientFunds => RejectReason::InsufficientFee,
+            ValidationError::BadSignature => RejectReason::BadSignature,
+            ValidationError::CancelOutsideWindow | ValidationError::RevstopMisuse => RejectReason::Revstop,
+            ValidationError::CoinbaseImmature => RejectReason::ImmatureCoinbase,
+            ValidationError::PubkeyReuse => RejectReason::KeyReuse,
+            _ => RejectReason::Policy,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RejectedKind {
+    Block,
+    Transaction,
+}
+
+/// One rejected block or transaction
+#[derive(Debug, Clone, Serialize)]
+pub struct Rejection {
+    pub kind: RejectedKind,
+    pub hash: String,
+    pub reason: RejectReason,
+    pub detail: String,
+}
+
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.detail)
+    }
+}
+
+impl std::error::Error for Rejection {}
+
+/// Whether rejections are logged and how many are kept for inspection
+#[derive(Debug, Clone)]
+pub struct RejectionLogConfig {
+    /// Emit a warning for every rejection
+    pub log: bool,
+    pub history: usize,
+}
+
+impl Default for RejectionLogConfig {
+    fn default() -> Self {
+        Self { log: true, history: DEFAULT_REJECTION_HISTORY }
+    }
+}
+
+/// What `getrejectionstats` reports
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RejectionReport {
+    pub blocks: BTreeMap<RejectReason, u64>,
+    pub transactions: BTreeMap<RejectReason, u64>,
+    pub recent: Vec<Rejection>,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    blocks: BTreeMap<RejectReason, u64>,
+    transactions: BTreeMap<RejectReason, u64>,
+    recent: VecDeque<Rejection>,
+    invalid_blocks: HashMap<Hash32, Rejection>,
+    /// `invalid_blocks` keys, oldest first
+    invalid_order: VecDeque<Hash32>,
+}
+
+/// Counts rejected blocks and transactions by reason so operators can spot
+/// attack patterns, keeping the latest few for detail
+#[derive(Debug, Default)]
+pub struct RejectionLog {
+    config: RejectionLogConfig,
+    counters: Mutex<Counters>,
+}
+
+impl RejectionLog {
+    pub fn new(config: RejectionLogConfig) -> Self {
+        Self { config, counters: Mutex::default() }
+    }
+
+    pub fn record(&self, kind: RejectedKind, hash: &Hash32, reason: RejectReason, detail: impl Into<String>) {
+        let rejection = Rejection { kind, hash: hash.to_hex(), reason, detail: detail.into() };
+        if self.config.log {
+            warn!("Rejected {:?} {}: {:?} ({})", kind, rejection.hash, reason, rejection.detail);
+        }
+
+        let mut counters = self.counters.lock();
+        let counts = match kind {
+            RejectedKind::Block => &mut counters.blocks,
+            RejectedKind::Transaction => &mut counters.transactions,
+        };
+        *counts.entry(reason).or_default() += 1;
+        if kind == RejectedKind::Block && reason.invalidates_header() && !counters.invalid_blocks.contains_key(hash) {
+            if counters.invalid_order.len() == MAX_KNOWN_INVALID_BLOCKS {
+                if let Some(oldest) = counters.invalid_order.pop_front() {
+                    counters.invalid_blocks.remove(&oldest);
+                }
+            }
+            counters.invalid_order.push_back(*hash);
+            counters.invalid_blocks.insert(*hash, rejection.clone());
+        }
+        if self.config.history > 0 {
+            if counters.recent.len() == self.config.history {
+                counters.recent.pop_front();
+            }
+            counters.recent.push_back(rejection);
+        }
+    }
+
+    pub fn count(&self, kind: RejectedKind, reason: RejectReason) -> u64 {
+        let counters = self.counters.lock();
+        let counts = match kind {
+            RejectedKind::Block => &counters.blocks,
+            RejectedKind::Transaction => &counters.transactions,
+        };
+        counts.get(&reason).copied().unwrap_or(0)
+    }
+
+    /// Why the block with `hash` was rejected, if it was
+    pub fn block_rejection(&self, hash: &Hash32) -> Option<Rejection> {
+        self.counters.lock().invalid_blocks.get(hash).cloned()
+    }
+
+    pub fn report(&self) -> RejectionReport {
+        let counters = self.counters.lock();
+        RejectionReport {
+            blocks: counters.blocks.clone(),
+            transactions: counters.transactions.clone(),
+            recent: counters.recent.iter().rev().cloned().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_by_kind_and_reason() {
+        let log = RejectionLog::new(RejectionLogConfig { log: false, history: 2 });
+        let hash = Hash32([1u8; 32]);
+        log.record(RejectedKind::Block, &hash, RejectReason::BadProofOfWork, "pow");
+        log.record(RejectedKind::Block, &hash, RejectReason::BadProofOfWork, "pow");
+        log.record(RejectedKind::Transaction, &hash, (&ValidationError::DuplicateInput).into(), "dup");
+
+        assert_eq!(log.count(RejectedKind::Block, RejectReason::BadProofOfWork), 2);
+        assert_eq!(log.count(RejectedKind::Transaction, RejectReason::DoubleSpend), 1);
+        assert_eq!(log.count(RejectedKind::Transaction, RejectReason::BadProofOfWork), 0);
+
+        // Only the newest `history` entries are kept
+        let report = log.report();
+        assert_eq!(report.recent.len(), 2);
+        assert_eq!(report.recent[0].reason, RejectReason::DoubleSpend);
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["blocks"]["bad-proof-of-work"], 2);
+        assert_eq!(json["recent"][0]["kind"], "transaction");
+    }
+
+    #[test]
+    fn test_revstop_errors_have_their_own_reason() {
+        assert_eq!(RejectReason::from(&ValidationError::CancelOutsideWindow), RejectReason::Revstop);
+        assert_eq!(RejectReason::from(&ValidationError::RevstopMisuse), RejectReason::Revstop);
+        assert_eq!(RejectReason::from(&ValidationError::BadSignature), RejectReason::BadSignature);
+        assert_eq!(serde_json::to_value(RejectReason::Revstop).unwrap(), "revstop");
+    }
+}
@@ -1,122 +1,828 @@
 use axum::{
-    routing::get,
+    extract::{Query, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    body::Bytes,
+    routing::{get, post},
     Router,
-    response::IntoResponse,
+    response::{IntoResponse, Response},
     Json,
 };
-use serde_json::json;
-use tracing::info;
-
-pub async fn serve_rpc() -> anyhow::Result<()> {
-    async fn health() -> impl IntoResponse {
-        Json(json!({
-            "ok": true,
-            "service": "qc-node",
-            "version": "1.0.0",
-            "network": "QuantumCoin",
-            "post_quantum": true,
-            "revstop_enabled": true,
-            "status": "running"
-        }))
-    }
-
-    async fn getinfo() -> impl IntoResponse {
-        Json(json!({
-            "version": "1.0.0",
-            "protocol_version": 1,
-            "blocks": 5,
-            "timeoffset": 0,
-            "connections": 0,
-            "proxy": "",
-            "difficulty": 0x1d00ffff,
-            "testnet": false,
-            "keypoololdest": 0,
-            "keypoolsize": 0,
-            "paytxfee": 0.00010000,
-            "relayfee": 0.00001000,
-            "errors": ""
-        }))
-    }
-
-    async fn getblockchaininfo() -> impl IntoResponse {
-        Json(json!({
-            "chain": "main",
-            "blocks": 5,
-            "headers": 5,
-            "bestblockhash": "000000000000000000000000000000000000000000000000000000000000000",
-            "difficulty": 1.0,
-            "mediantime": 1700000000,
-            "verificationprogress": 1.0,
-            "initialblockdownload": false,
-            "chainwork": "0000000000000000000000000000000000000000000000000000000000000006",
-            "size_on_disk": 1024,
-            "pruned": false,
-            "softforks": {},
-            "warnings": ""
-        }))
-    }
-
-    async fn getmininginfo() -> impl IntoResponse {
-        Json(json!({
-            "blocks": 5,
-            "currentblockweight": 4000,
-            "currentblocktx": 1,
-            "difficulty": 1.0,
-            "networkhashps": 1000000,
-            "pooledtx": 0,
-            "chain": "main",
-            "warnings": ""
-        }))
-    }
-
-    async fn getnetworkinfo() -> impl IntoResponse {
-        Json(json!({
-            "version": 1000000,
-            "subversion": "/QuantumCoin:1.0.0/",
-            "protocolversion": 1,
-            "localservices": "0000000000000001",
-            "localrelay": true,
-            "timeoffset": 0,
-            "connections": 0,
-            "networkactive": true,
-            "networks": [
-                {
-                    "name": "ipv4",
-                    "limited": false,
-                    "reachable": true,
-                    "proxy": "",
-                    "proxy_randomize_credentials": false
-                }
-            ],
-            "relayfee": 0.00001000,
-            "incrementalfee": 0.00001000,
-            "localaddresses": [],
-            "warnings": ""
-        }))
+use crate::chainstate::{ChainEvent, ChainState, SubmitOutcome};
+use crate::p2p::PROTOCOL_VERSION;
+use crate::rejections::{RejectReason, Rejection, RejectionLog};
+use crate::storage::Storage;
+use parking_lot::Mutex;
+use qc_crypto::constant_time_eq;
+use qc_types::{Block, Hash32};
+use qc_validation::{ChainSpec, ValidationError};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+use tokio::sync::broadcast;
+use tracing::{info, warn};
+
+pub const DEFAULT_RPC_BIND: &str = "127.0.0.1:8332";
+/// `waitfornewblock` timeout when the caller doesn't give one
+pub const DEFAULT_WAIT_TIMEOUT_MS: u64 = 30_000;
+/// Upper bound on `waitfornewblock` so a request can't hold a connection forever
+pub const MAX_WAIT_TIMEOUT_MS: u64 = 300_000;
+/// Most requests one batch may carry; larger batches are rejected whole
+pub const MAX_BATCH_LEN: usize = 100;
+
+/// Every method `call_method` dispatches, as advertised by `getnodeinfo`
+pub const RPC_METHODS: &[&str] = &[
+    "waitfornewblock",
+    "gethealth",
+    "getinfo",
+    "getnodeinfo",
+    "getblockchaininfo",
+    "getmininginfo",
+    "getnetworkinfo",
+    "getrejectionstats",
+    "getchaintips",
+    "submitblock",
+];
+
+/// Optional features this node was started with, reported by `getnodeinfo`
+#[derive(Debug, Clone, Default)]
+pub struct NodeCapabilities {
+    pub network: String,
+    pub txindex: bool,
+    pub addressindex: bool,
+    pub cfilters: bool,
+    /// Blocks below this height have been discarded; `None` keeps everything
+    pub prune_height: Option<u64>,
+}
+
+/// Where the RPC server listens and whether callers must authenticate
+#[derive(Debug, Clone)]
+pub struct RpcConfig {
+    pub bind: SocketAddr,
+    /// When set, every request needs `Authorization: Bearer <token>`
+    pub auth_token: Option<String>,
+    pub capabilities: NodeCapabilities,
+    /// Rejected block and transaction counts reported by `getrejectionstats`
+    pub rejections: Arc<RejectionLog>,
+    /// Chain that `submitblock` applies to; without it the method is unavailable
+    pub chain: Option<RpcChain>,
+}
+
+/// Chain state the RPC server may write to
+#[derive(Clone)]
+pub struct RpcChain {
+    pub spec: Arc<ChainSpec>,
+    pub store: Arc<Storage>,
+    /// Held by every writer from reading the tip to connecting on top of it
+    pub tip_lock: Arc<Mutex<()>>,
+}
+
+impl std::fmt::Debug for RpcChain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RpcChain").field("network", &self.spec.network.name).finish_non_exhaustive()
     }
+}
 
-    let app = Router::new()
-        .route("/gethealth", get(health))
-        .route("/getinfo", get(getinfo))
-        .route("/getblockchaininfo", get(getblockchaininfo))
-        .route("/getmininginfo", get(getmininginfo))
-        .route("/getnetworkinfo", get(getnetworkinfo));
-
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:8332").await?;
-    info!("🔗 RPC server listening on http://0.0.0.0:8332");
-    
-    axum::serve(listener, app).await?;
+impl Default for RpcConfig {
+    fn default() -> Self {
+        Self {
+            bind: DEFAULT_RPC_BIND.parse().expect("valid default bind"),
+            auth_token: None,
+            capabilities: NodeCapabilities::default(),
+            rejections: Arc::default(),
+            chain: None,
+        }
+    }
+}
+
+/// Shared handles the RPC handlers read from
+#[derive(Clone)]
+struct RpcState {
+    chain_events: broadcast::Sender<ChainEvent>,
+    capabilities: Arc<NodeCapabilities>,
+    rejections: Arc<RejectionLog>,
+    chain: Option<RpcChain>,
+}
+
+pub async fn serve_rpc(config: RpcConfig, chain_events: broadcast::Sender<ChainEvent>) -> anyhow::Result<()> {
+    if config.auth_token.is_none() && !config.bind.ip().is_loopback() {
+        warn!("RPC bound to {} without an auth token", config.bind);
+    }
+
+    let listener = tokio::net::TcpListener::bind(config.bind).await?;
+    info!("🔗 RPC server listening on http://{}", config.bind);
+
+    axum::serve(listener, router(&config, chain_events)).await?;
     Ok(())
 }
 
+async fn require_token(State(token): State<Arc<str>>, request: Request, next: Next) -> Response {
+    let presented = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+
+    match presented {
+        Some(p) if constant_time_eq(p.as_bytes(), token.as_bytes()) => next.run(request).await,
+        _ => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Bearer")],
+            Json(json!({ "error": "unauthorized" })),
+        )
+            .into_response(),
+    }
+}
+
+fn gethealth() -> Value {
+    json!({
+        "ok": true,
+        "service": "qc-node",
+        "version": "1.0.0",
+        "network": "QuantumCoin",
+        "post_quantum": true,
+        "revstop_enabled": true,
+        "status": "running"
+    })
+}
+
+fn getinfo() -> Value {
+    json!({
+        "version": "1.0.0",
+        "protocol_version": 1,
+        "blocks": 5,
+        "timeoffset": 0,
+        "connections": 0,
+        "proxy": "",
+        "difficulty": 0x1d00ffff,
+        "testnet": false,
+        "keypoololdest": 0,
+        "keypoolsize": 0,
+        "paytxfee": 0.00010000,
+        "relayfee": 0.00001000,
+        "errors": ""
+    })
+}
+
+fn getnodeinfo(capabilities: &NodeCapabilities) -> Value {
+    json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "network": capabilities.network,
+        "protocol_version": PROTOCOL_VERSION,
+        "indexes": {
+            "txindex": capabilities.txindex,
+            "addressindex": capabilities.addressindex,
+            "cfilters": capabilities.cfilters,
+        },
+        "pruned": capabilities.prune_height.is_some(),
+        "prune_height": capabilities.prune_height,
+        "methods": RPC_METHODS,
+    })
+}
+
+fn getblockchaininfo() -> Value {
+    json!({
+        "chain": "main",
+        "blocks": 5,
+        "headers": 5,
+        "bestblockhash": "000000000000000000000000000000000000000000000000000000000000000",
+        "difficulty": 1.0,
+        "mediantime": 1700000000,
+        "verificationprogress": 1.0,
+        "initialblockdownload": false,
+        "chainwork": "0000000000000000000000000000000000000000000000000000000000000006",
+        "size_on_disk": 1024,
+        "pruned": false,
+        "softforks": {},
+        "warnings": ""
+    })
+}
+
+fn getmininginfo() -> Value {
+    json!({
+        "blocks": 5,
+        "currentblockweight": 4000,
+        "currentblocktx": 1,
+        "difficulty": 1.0,
+        "networkhashps": 1000000,
+        "pooledtx": 0,
+        "chain": "main",
+        "warnings": ""
+    })
+}
+
+fn getrejectionstats(rejections: &RejectionLog) -> Value {
+    serde_json::to_value(rejections.report()).unwrap_or(Value::Null)
+}
+
+fn getnetworkinfo() -> Value {
+    json!({
+        "version": 1000000,
+        "subversion": "/QuantumCoin:1.0.0/",
+        "protocolversion": 1,
+        "localservices": "0000000000000001",
+        "localrelay": true,
+        "timeoffset": 0,
+        "connections": 0,
+        "networkactive": true,
+        "networks": [
+            {
+                "name": "ipv4",
+                "limited": false,
+                "reachable": true,
+                "proxy": "",
+                "proxy_randomize_credentials": false
+            }
+        ],
+        "relayfee": 0.00001000,
+        "incrementalfee": 0.00001000,
+        "localaddresses": [],
+        "warnings": ""
+    })
+}
+
+// JSON-RPC 2.0 error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+// Application error codes, numbered as Bitcoin Core does so clients can share handling
+const NOT_FOUND: i64 = -5;
+const VERIFY_ERROR: i64 = -25;
+const VERIFY_REJECTED: i64 = -26;
+const ALREADY_IN_CHAIN: i64 = -27;
+
+/// Every way an RPC call can fail. Each variant has one stable code, so
+/// callers can branch on the code and show the message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RpcError {
+    ParseError,
+    InvalidRequest,
+    MethodNotFound(String),
+    InvalidParams(String),
+    /// Storage or other node failure unrelated to the request
+    Internal(String),
+    /// Requested block, transaction or parent is unknown
+    NotFound(String),
+    /// Breaks a consensus rule
+    Validation(String),
+    /// Valid but refused by local policy
+    Policy(String),
+    AlreadyInChain(String),
+}
+
+impl RpcError {
+    pub fn code(&self) -> i64 {
+        match self {
+            RpcError::ParseError => PARSE_ERROR,
+            RpcError::InvalidRequest => INVALID_REQUEST,
+            RpcError::MethodNotFound(_) => METHOD_NOT_FOUND,
+            RpcError::InvalidParams(_) => INVALID_PARAMS,
+            RpcError::Internal(_) => INTERNAL_ERROR,
+            RpcError::NotFound(_) => NOT_FOUND,
+            RpcError::Validation(_) => VERIFY_ERROR,
+            RpcError::Policy(_) => VERIFY_REJECTED,
+            RpcError::AlreadyInChain(_) => ALREADY_IN_CHAIN,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            RpcError::ParseError => "Parse error".to_string(),
+            RpcError::InvalidRequest => "Invalid Request".to_string(),
+            RpcError::MethodNotFound(method) => format!("Method not found: {}", method),
+            RpcError::InvalidParams(detail) => format!("Invalid params: {}", detail),
+            RpcError::Internal(detail) => format!("Internal error: {}", detail),
+            RpcError::NotFound(detail) => format!("Not found: {}", detail),
+            RpcError::Validation(detail) => format!("Validation failed: {}", detail),
+            RpcError::Policy(detail) => format!("Rejected by policy: {}", detail),
+            RpcError::AlreadyInChain(detail) => format!("Already in chain: {}", detail),
+        }
+    }
+
+    fn rejected(reason: RejectReason, detail: String) -> Self {
+        match reason {
+            RejectReason::Policy | RejectReason::KeyReuse => RpcError::Policy(detail),
+            _ => RpcError::Validation(detail),
+        }
+    }
+}
+
+impl From<&ValidationError> for RpcError {
+    fn from(err: &ValidationError) -> Self {
+        RpcError::rejected(err.into(), err.to_string())
+    }
+}
+
+impl From<&Rejection> for RpcError {
+    fn from(rejection: &Rejection) -> Self {
+        RpcError::rejected(rejection.reason, rejection.detail.clone())
+    }
+}
+
+impl From<anyhow::Error> for RpcError {
+    fn from(err: anyhow::Error) -> Self {
+        RpcError::Internal(err.to_string())
+    }
+}
+
+/// Wait until the next block connects or `timeout_ms` elapses
+async fn waitfornewblock(chain_events: &broadcast::Sender<ChainEvent>, timeout_ms: u64) -> Value {
+    let mut events = chain_events.subscribe();
+    let wait = Duration::from_millis(timeout_ms.min(MAX_WAIT_TIMEOUT_MS));
+
+    let connected = tokio::time::timeout(wait, async {
+        loop {
+            match events.recv().await {
+                Ok(ChainEvent::BlockConnected(block)) => return Some((block.hash, block.height)),
+                // Missed some events, but that still means blocks connected; take the next one
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    })
+    .await
+    .ok()
+    .flatten();
+
+    match connected {
+        Some((hash, height)) => json!({ "hash": hash.to_hex(), "height": height, "timed_out": false }),
+        None => json!({ "timed_out": true }),
+    }
+}
+
+/// Accepts `[timeout_ms]` or `{"timeout_ms": n}`
+fn timeout_param(params: Option<&Value>) -> u64 {
+    params
+        .and_then(|p| p.get(0).or_else(|| p.get("timeout_ms")))
+        .and_then(Value::as_u64)
+        .unwrap_or(DEFAULT_WAIT_TIMEOUT_MS)
+}
+
+/// Apply a hex-encoded, bincode-serialized block on top of the current tip.
+/// A block that is already stored succeeds as `"duplicate"`; one carrying a
+/// transaction the chain already confirmed fails as already in chain.
+fn submitblock(state: &RpcState, params: Option<&Value>) -> Result<Value, RpcError> {
+    let chain = state.chain.as_ref().ok_or_else(|| RpcError::Internal("chain state not available".into()))?;
+    let data = params
+        .and_then(|p| p.get(0).or_else(|| p.get("hexdata")))
+        .and_then(Value::as_str)
+        .ok_or_else(|| RpcError::InvalidParams("expected [hexdata]".into()))?;
+    let block: Block = hex::decode(data)
+        .ok()
+        .and_then(|bytes| bincode::deserialize(&bytes).ok())
+        .ok_or_else(|| RpcError::InvalidParams("block does not decode".into()))?;
+
+    let hash = block.header.hash();
+    let _tip = chain.tip_lock.lock();
+    if chain.store.get_block(&hash)?.is_some() {
+        return Ok(json!("duplicate"));
+    }
+    let tip = chain.store.get_tip()?.unwrap_or_else(Hash32::zero);
+    if block.header.prev_block != tip {
+        let prev = block.header.prev_block;
+        return Err(match chain.store.get_block(&prev)? {
+            Some(_) => RpcError::Policy(format!("parent {} is not the chain tip", prev.to_hex())),
+            None => RpcError::NotFound(format!("parent block {}", prev.to_hex())),
+        });
+    }
+    // A transaction the chain already confirmed can't be confirmed again
+    for tx in block.txs.iter().skip(1) {
+        let txid = chain.store.calculate_txid(tx);
+        if chain.store.get_transaction(&txid)?.is_some() {
+            return Err(RpcError::AlreadyInChain(format!("transaction {}", txid.to_hex())));
+        }
+    }
+    let height = chain.store.get_tip_height()?.map_or(0, |h| h + 1);
+
+    let cs = ChainState {
+        spec: &chain.spec,
+        store: &chain.store,
+        events: Some(&state.chain_events),
+        rejections: Some(&state.rejections),
+    };
+    match cs.submit_block(height, &block) {
+        Ok(SubmitOutcome::Accepted(hash)) => Ok(json!({ "hash": hash.to_hex(), "height": height })),
+        Ok(SubmitOutcome::Duplicate(_)) => Ok(json!("duplicate")),
+        Ok(SubmitOutcome::KnownInvalid(rejection)) => Err((&rejection).into()),
+        // Fresh rejections carry their reason; anything else is a node fault
+        Err(err) => match err.downcast::<Rejection>() {
+            Ok(rejection) => Err((&rejection).into()),
+            Err(err) => Err(err.into()),
+        },
+    }
+}
+
+/// Every known chain tip: the active one and the end of each other branch
+/// in the header index, with how far it forks from the active chain
+fn getchaintips(state: &RpcState) -> Result<Value, RpcError> {
+    let chain = state.chain.as_ref().ok_or_else(|| RpcError::Internal("chain state not available".into()))?;
+    let cs = ChainState { spec: &chain.spec, store: &chain.store, events: None, rejections: Some(&state.rejections) };
+    let tips: Vec<Value> = cs.chain_tips()?.into_iter().map(|tip| json!({
+        "height": tip.height,
+        "hash": tip.hash.to_hex(),
+        "branchlen": tip.branchlen,
+        "chainwork": format!("{:064x}", tip.chainwork),
+        "status": tip.status.as_str(),
+    })).collect();
+    Ok(Value::Array(tips))
+}
+
+async fn call_method(state: &RpcState, method: &str, params: Option<&Value>) -> Result<Value, RpcError> {
+    match method {
+        "waitfornewblock" => Ok(waitfornewblock(&state.chain_events, timeout_param(params)).await),
+        "gethealth" => Ok(gethealth()),
+        "getinfo" => Ok(getinfo()),
+        "getnodeinfo" => Ok(getnodeinfo(&state.capabilities)),
+        "getblockchaininfo" => Ok(getblockchaininfo()),
+        "getmininginfo" => Ok(getmininginfo()),
+        "getnetworkinfo" => Ok(getnetworkinfo()),
+        "getrejectionstats" => Ok(getrejectionstats(&state.rejections)),
+        "getchaintips" => getchaintips(state),
+        "submitblock" => submitblock(state, params),
+        _ => Err(RpcError::MethodNotFound(method.to_string())),
+    }
+}
+
+fn rpc_error(id: Value, error: &RpcError) -> Value {
+    json!({ "jsonrpc": "2.0", "error": { "code": error.code(), "message": error.message() }, "id": id })
+}
+
+/// Handle one request object; `None` means it was a notification and gets no reply
+async fn handle_request(state: &RpcState, request: Value) -> Option<Value> {
+    let id = request.get("id").cloned();
+    let method = request.get("method").and_then(Value::as_str);
+    let (Some(method), Some("2.0")) = (method, request.get("jsonrpc").and_then(Value::as_str)) else {
+        return Some(rpc_error(id.unwrap_or(Value::Null), &RpcError::InvalidRequest));
+    };
+
+    let result = call_method(state, method, request.get("params")).await;
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+        Err(error) => rpc_error(id, &error),
+    })
+}
+
+/// JSON-RPC 2.0 endpoint accepting a single request or a batch array of at
+/// most `MAX_BATCH_LEN`. Each batch entry is answered independently, in order.
+async fn jsonrpc(State(state): State<RpcState>, body: Bytes) -> Response {
+    let reply = match serde_json::from_slice::<Value>(&body) {
+        Err(_) => Some(rpc_error(Value::Null, &RpcError::ParseError)),
+        Ok(Value::Array(batch)) if batch.is_empty() || batch.len() > MAX_BATCH_LEN => {
+            Some(rpc_error(Value::Null, &RpcError::InvalidRequest))
+        }
+        Ok(Value::Array(batch)) => {
+            let mut responses = Vec::with_capacity(batch.len());
+            for request in batch {
+                responses.extend(handle_request(&state, request).await);
+            }
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        Ok(request) => handle_request(&state, request).await,
+    };
+
+    match reply {
+        Some(reply) => Json(reply).into_response(),
+        None => StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct WaitQuery {
+    timeout_ms: Option<u64>,
+}
+
+pub fn router(config: &RpcConfig, chain_events: broadcast::Sender<ChainEvent>) -> Router {
+    let app = Router::new()
+        .route("/", post(jsonrpc))
+        .route(
+            "/waitfornewblock",
+            get(|State(state): State<RpcState>, Query(q): Query<WaitQuery>| async move {
+                Json(waitfornewblock(&state.chain_events, q.timeout_ms.unwrap_or(DEFAULT_WAIT_TIMEOUT_MS)).await)
+            }),
+        )
+        .route("/gethealth", get(|| async { Json(gethealth()) }))
+        .route("/getinfo", get(|| async { Json(getinfo()) }))
+        .route("/getnodeinfo", get(|State(state): State<RpcState>| async move { Json(getnodeinfo(&state.capabilities)) }))
+        .route("/getblockchaininfo", get(|| async { Json(getblockchaininfo()) }))
+        .route("/getmininginfo", get(|| async { Json(getmininginfo()) }))
+        .route("/getnetworkinfo", get(|| async { Json(getnetworkinfo()) }))
+        .route("/getrejectionstats", get(|State(state): State<RpcState>| async move { Json(getrejectionstats(&state.rejections)) }))
+        .with_state(RpcState {
+            chain_events,
+            capabilities: Arc::new(config.capabilities.clone()),
+            rejections: config.rejections.clone(),
+            chain: config.chain.clone(),
+        });
+
+    match &config.auth_token {
+        Some(token) => app.layer(middleware::from_fn_with_state(Arc::<str>::from(token.as_str()), require_token)),
+        None => app,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::chainstate::ConnectedBlockInfo;
+    use axum::body::Body;
+    use tower::ServiceExt;
+
+    fn events() -> broadcast::Sender<ChainEvent> {
+        broadcast::channel(crate::chainstate::CHAIN_EVENT_BUFFER).0
+    }
+
+    fn with_token(token: &str) -> RpcConfig {
+        RpcConfig { auth_token: Some(token.to_string()), ..RpcConfig::default() }
+    }
+
+    async fn get_health(app: Router, auth: Option<&str>) -> StatusCode {
+        let mut request = Request::builder().uri("/gethealth");
+        if let Some(auth) = auth {
+            request = request.header(header::AUTHORIZATION, auth);
+        }
+        app.oneshot(request.body(Body::empty()).unwrap()).await.unwrap().status()
+    }
 
     #[tokio::test]
     async fn test_rpc_health() {
-        // This would require setting up a test server
-        // For now, just verify the function compiles
-        assert!(true);
+        assert_eq!(get_health(router(&RpcConfig::default(), events()), None).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_authorized_request_succeeds() {
+        let app = router(&with_token("s3cret"), events());
+        assert_eq!(get_health(app, Some("Bearer s3cret")).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_unauthorized_request_rejected() {
+        let config = with_token("s3cret");
+        assert_eq!(get_health(router(&config, events()), None).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(get_health(router(&config, events()), Some("Bearer wrong")).await, StatusCode::UNAUTHORIZED);
+        assert_eq!(get_health(router(&config, events()), Some("s3cret")).await, StatusCode::UNAUTHORIZED);
+    }
+
+    async fn post_rpc(body: &str) -> (StatusCode, Value) {
+        post_rpc_to(&RpcConfig::default(), body).await
+    }
+
+    async fn post_rpc_to(config: &RpcConfig, body: &str) -> (StatusCode, Value) {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = router(config, events()).oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&bytes).unwrap_or(Value::Null))
+    }
+
+    #[tokio::test]
+    async fn test_batch_isolates_failing_call() {
+        let (status, body) = post_rpc(
+            r#"[{"jsonrpc":"2.0","method":"gethealth","id":1},
+                {"jsonrpc":"2.0","method":"nosuchmethod","id":"b"}]"#,
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        let responses = body.as_array().unwrap();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["id"], 1);
+        assert_eq!(responses[0]["result"]["ok"], true);
+        assert!(responses[0].get("error").is_none());
+        assert_eq!(responses[1]["id"], "b");
+        assert_eq!(responses[1]["error"]["code"], METHOD_NOT_FOUND);
+        assert!(responses[1].get("result").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_single_request_and_notifications() {
+        let (_, body) = post_rpc(r#"{"jsonrpc":"2.0","method":"getinfo","id":7}"#).await;
+        assert_eq!(body["id"], 7);
+        assert_eq!(body["result"]["protocol_version"], 1);
+
+        // Notifications are executed but never answered
+        let (_, body) = post_rpc(
+            r#"[{"jsonrpc":"2.0","method":"gethealth"},{"jsonrpc":"2.0","method":"getinfo","id":2}]"#,
+        )
+        .await;
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        let (status, _) = post_rpc(r#"{"jsonrpc":"2.0","method":"gethealth"}"#).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn test_malformed_batches() {
+        let (_, body) = post_rpc("[]").await;
+        assert_eq!(body["error"]["code"], INVALID_REQUEST);
+
+        let (_, body) = post_rpc("[1, {\"method\":\"gethealth\",\"id\":3}]").await;
+        let responses = body.as_array().unwrap();
+        assert_eq!(responses.len(), 2);
+        assert!(responses.iter().all(|r| r["error"]["code"] == INVALID_REQUEST));
+        assert_eq!(responses[1]["id"], 3);
+
+        let (_, body) = post_rpc("[{").await;
+        assert_eq!(body["error"]["code"], PARSE_ERROR);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_batch_rejected() {
+        let batch = |len: usize| {
+            let requests: Vec<Value> = (0..len).map(|id| json!({"jsonrpc":"2.0","method":"gethealth","id":id})).collect();
+            Value::Array(requests).to_string()
+        };
+
+        let (_, body) = post_rpc(&batch(MAX_BATCH_LEN)).await;
+        assert_eq!(body.as_array().unwrap().len(), MAX_BATCH_LEN);
+
+        // Rejected whole, without running any of it
+        let (_, body) = post_rpc(&batch(MAX_BATCH_LEN + 1)).await;
+        assert_eq!(body["error"]["code"], INVALID_REQUEST);
+        assert_eq!(body["id"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn test_waitfornewblock_returns_when_block_connects() {
+        let events = events();
+        let publisher = events.clone();
+        let hash = qc_types::Hash32([7u8; 32]);
+        let started = std::time::Instant::now();
+
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let block = ConnectedBlockInfo { height: 42, hash, tx_count: 1, total_fees: 0, subsidy: 0, size: 0, weight: 0 };
+            publisher.send(ChainEvent::BlockConnected(block)).unwrap();
+        });
+        let result = waitfornewblock(&events, 10_000).await;
+
+        assert_eq!(result["timed_out"], false);
+        assert_eq!(result["height"], 42);
+        assert_eq!(result["hash"], hash.to_hex());
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_waitfornewblock_times_out() {
+        let events = events();
+        let started = std::time::Instant::now();
+        let result = waitfornewblock(&events, 100).await;
+
+        assert_eq!(result["timed_out"], true);
+        assert!(result.get("hash").is_none());
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn test_waitfornewblock_over_jsonrpc() {
+        let (_, body) = post_rpc(r#"{"jsonrpc":"2.0","method":"waitfornewblock","params":[10],"id":1}"#).await;
+        assert_eq!(body["result"]["timed_out"], true);
+    }
+
+    #[tokio::test]
+    async fn test_nodeinfo_reports_runtime_capabilities() {
+        for txindex in [true, false] {
+            let capabilities = NodeCapabilities { network: "QuantumCoin".into(), txindex, ..NodeCapabilities::default() };
+            let config = RpcConfig { capabilities, ..RpcConfig::default() };
+            let (_, body) = post_rpc_to(&config, r#"{"jsonrpc":"2.0","method":"getnodeinfo","id":1}"#).await;
+            let info = &body["result"];
+
+            assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+            assert_eq!(info["network"], "QuantumCoin");
+            assert_eq!(info["protocol_version"], PROTOCOL_VERSION);
+            assert_eq!(info["indexes"]["txindex"], txindex);
+            assert_eq!(info["indexes"]["addressindex"], false);
+            assert_eq!(info["indexes"]["cfilters"], false);
+            assert_eq!(info["pruned"], false);
+            assert!(info["prune_height"].is_null());
+        }
+
+        let capabilities = NodeCapabilities { prune_height: Some(1_000), ..NodeCapabilities::default() };
+        let config = RpcConfig { capabilities, ..RpcConfig::default() };
+        let (_, body) = post_rpc_to(&config, r#"{"jsonrpc":"2.0","method":"getnodeinfo","id":1}"#).await;
+        assert_eq!(body["result"]["pruned"], true);
+        assert_eq!(body["result"]["prune_height"], 1_000);
+    }
+
+    #[tokio::test]
+    async fn test_advertised_methods_are_dispatched() {
+        let (_, body) = post_rpc(r#"{"jsonrpc":"2.0","method":"getnodeinfo","id":1}"#).await;
+        let advertised: Vec<&str> =
+            body["result"]["methods"].as_array().unwrap().iter().map(|m| m.as_str().unwrap()).collect();
+        assert_eq!(advertised, RPC_METHODS);
+
+        let state =
+            RpcState { chain_events: events(), capabilities: Arc::default(), rejections: Arc::default(), chain: None };
+        for method in RPC_METHODS {
+            // Keep waitfornewblock from holding the test for the default timeout
+            let result = call_method(&state, method, Some(&json!([1]))).await;
+            assert!(!matches!(result, Err(RpcError::MethodNotFound(_))), "{} not dispatched", method);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_getrejectionstats_reports_counts() {
+        use crate::rejections::{RejectReason, RejectedKind};
+        use qc_types::Hash32;
+
+        let config = RpcConfig::default();
+        config.rejections.record(RejectedKind::Block, &Hash32([3u8; 32]), RejectReason::BadMerkleRoot, "Merkle root mismatch");
+        let (_, body) = post_rpc_to(&config, r#"{"jsonrpc":"2.0","method":"getrejectionstats","id":1}"#).await;
+        let stats = &body["result"];
+
+        assert_eq!(stats["blocks"]["bad-merkle-root"], 1);
+        assert_eq!(stats["transactions"], json!({}));
+        assert_eq!(stats["recent"][0]["hash"], Hash32([3u8; 32]).to_hex());
+    }
+
+    #[test]
+    fn test_validation_errors_map_to_stable_codes() {
+        assert_eq!(RpcError::from(&ValidationError::BadSignature).code(), VERIFY_ERROR);
+        assert_eq!(RpcError::from(&ValidationError::MissingInput).code(), VERIFY_ERROR);
+        assert_eq!(RpcError::from(&ValidationError::TooManySigops).code(), VERIFY_REJECTED);
+        assert_eq!(RpcError::from(&ValidationError::PubkeyReuse).code(), VERIFY_REJECTED);
+        assert_eq!(
+            RpcError::from(&ValidationError::CancelOutsideWindow),
+            RpcError::Validation("revstop cancel outside window".into())
+        );
+        assert_eq!(
+            RpcError::from(&ValidationError::DuplicateInput),
+            RpcError::Validation("duplicate input".into())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_submitblock_failures_use_mapped_codes() {
+        use crate::miner::{build_candidate, mine_block_cpu};
+        use qc_types::{Transaction, TxOut};
+        use qc_validation::block_subsidy;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let spec: ChainSpec = toml::from_str(include_str!("../../../chain_spec.toml")).unwrap();
+        let chain = RpcChain {
+            spec: Arc::new(spec),
+            store: Arc::new(Storage::open(temp_dir.path()).unwrap()),
+            tip_lock: Arc::default(),
+        };
+        let config = RpcConfig { chain: Some(chain.clone()), ..RpcConfig::default() };
+        let submit = |block: &Block| {
+            let hex = hex::encode(bincode::serialize(block).unwrap());
+            format!(r#"{{"jsonrpc":"2.0","method":"submitblock","params":["{}"],"id":1}}"#, hex)
+        };
+        let mined = |prev: Hash32, height: u64| {
+            let reward = TxOut::new_p2pq(block_subsidy(&chain.spec, height), vec![0u8; 1312]);
+            let coinbase = Transaction::new(1, vec![], vec![reward], height as u32);
+            mine_block_cpu(build_candidate(prev, 0x207fffff, vec![coinbase]), 1_000).unwrap()
+        };
+
+        let genesis = mined(Hash32::zero(), 0);
+        let (_, body) = post_rpc_to(&config, &submit(&genesis)).await;
+        assert_eq!(body["result"]["hash"], genesis.header.hash().to_hex());
+        assert_eq!(body["result"]["height"], 0);
+
+        let (_, body) = post_rpc_to(&config, r#"{"jsonrpc":"2.0","method":"getchaintips","id":1}"#).await;
+        assert_eq!(body["result"][0]["hash"], genesis.header.hash().to_hex());
+        assert_eq!(body["result"][0]["status"], "active");
+
+        // Resubmitting is not an error
+        let (_, body) = post_rpc_to(&config, &submit(&genesis)).await;
+        assert_eq!(body["result"], "duplicate");
+        assert!(body.get("error").is_none());
+
+        // Confirming the genesis coinbase a second time
+        let mut replay = mined(genesis.header.hash(), 1);
+        replay.txs.push(genesis.txs[0].clone());
+        let (_, body) = post_rpc_to(&config, &submit(&replay)).await;
+        assert_eq!(body["error"]["code"], ALREADY_IN_CHAIN);
+
+        let orphan = mined(Hash32([4u8; 32]), 1);
+        let (_, body) = post_rpc_to(&config, &submit(&orphan)).await;
+        assert_eq!(body["error"]["code"], NOT_FOUND);
+
+        let mut bad = build_candidate(genesis.header.hash(), 0x207fffff, mined(Hash32::zero(), 1).txs);
+        bad.header.merkle_root = Hash32([9u8; 32]);
+        let bad = mine_block_cpu(bad, 1_000).unwrap();
+        // Body failures are never remembered, so both attempts revalidate
+        for _ in 0..2 {
+            let (_, body) = post_rpc_to(&config, &submit(&bad)).await;
+            assert_eq!(body["error"]["code"], VERIFY_ERROR);
+            assert!(body["error"]["message"].as_str().unwrap().starts_with("Validation failed"));
+        }
+
+        let (_, body) = post_rpc_to(&config, r#"{"jsonrpc":"2.0","method":"submitblock","params":["zz"],"id":1}"#).await;
+        assert_eq!(body["error"]["code"], INVALID_PARAMS);
+        let (_, body) = post_rpc(&submit(&genesis)).await;
+        assert_eq!(body["error"]["code"], INTERNAL_ERROR);
+    }
+
+    #[test]
+    fn test_default_binds_loopback() {
+        assert!(RpcConfig::default().bind.ip().is_loopback());
     }
 }
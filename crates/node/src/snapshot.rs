@@ -0,0 +1,206 @@
+//! assumeutxo-style UTXO set snapshots
+//!
+//! A snapshot is the full UTXO set at some block plus a commitment hash over
+//! it. A new node can load one instead of replaying history from genesis,
+//! provided the commitment matches a value it already trusts.
+
+use crate::storage::{Storage, UtxoValue};
+use anyhow::{bail, Context, Result};
+use qc_types::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{fmt, fs, path::Path, str::FromStr};
+use tracing::info;
+
+const SNAPSHOT_MAGIC: &[u8; 4] = b"QCUS";
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Snapshot parameters a node trusts, e.g. hardcoded per network release
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AssumeUtxo {
+    pub height: u64,
+    pub block_hash: Hash32,
+    pub commitment: Hash32,
+}
+
+/// Written as `<height>:<block hash>:<commitment>`, the form `--assumeutxo` takes
+impl fmt::Display for AssumeUtxo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.height, self.block_hash.to_hex(), self.commitment.to_hex())
+    }
+}
+
+impl FromStr for AssumeUtxo {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.split(':');
+        let (Some(height), Some(block_hash), Some(commitment), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+            bail!("expected <height>:<block hash>:<commitment>");
+        };
+        Ok(AssumeUtxo {
+            height: height.parse().context("snapshot height")?,
+            block_hash: Hash32::from_hex(block_hash).context("snapshot block hash")?,
+            commitment: Hash32::from_hex(commitment).context("snapshot commitment")?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct UtxoSnapshot {
+    version: u32,
+    height: u64,
+    block_hash: Hash32,
+    commitment: Hash32,
+    coins: Vec<(OutPoint, UtxoValue)>,
+}
+
+/// Commitment over a UTXO set: sha256d of the count followed by every entry in outpoint order
+pub fn utxo_commitment(coins: &[(OutPoint, UtxoValue)]) -> Hash32 {
+    let mut sorted: Vec<_> = coins.iter().collect();
+    sorted.sort_by(|a, b| (a.0.txid.0, a.0.vout).cmp(&(b.0.txid.0, b.0.vout)));
+
+    let mut hasher = Sha256::new();
+    hasher.update((sorted.len() as u64).to_le_bytes());
+    for entry in sorted {
+        hasher.update(bincode::serialize(entry).expect("serialize utxo"));
+    }
+    Hash32(Sha256::digest(hasher.finalize()).into())
+}
+
+/// `dumputxoset`: write the UTXO set at the current tip to `path`
+pub fn dump_utxo_set(store: &Storage, path: &Path) -> Result<AssumeUtxo> {
+    let block_hash = store.get_tip()?.context("no chain tip to snapshot")?;
+    let height = store.get_tip_height()?.context("chain tip height unknown")?;
+    let coins = store.utxo_entries()?;
+    let commitment = utxo_commitment(&coins);
+
+    let snapshot = UtxoSnapshot { version: SNAPSHOT_VERSION, height, block_hash, commitment, coins };
+    let mut bytes = SNAPSHOT_MAGIC.to_vec();
+    bytes.extend(bincode::serialize(&snapshot)?);
+    fs::write(path, bytes).with_context(|| format!("write snapshot {}", path.display()))?;
+
+    info!("📦 Dumped {} UTXOs at height {} (commitment {})", snapshot.coins.len(), height, commitment.to_hex());
+    Ok(AssumeUtxo { height, block_hash, commitment })
+}
+
+/// `loadutxoset`: import a snapshot into an empty store and make its block the tip.
+/// Returns the snapshot height; validation resumes from the block after it.
+pub fn load_utxo_set(store: &Storage, path: &Path, trusted: &AssumeUtxo) -> Result<u64> {
+    if store.get_tip()?.is_some() {
+        bail!("refusing to load a UTXO snapshot over an existing chain");
+    }
+
+    let bytes = fs::read(path).with_context(|| format!("read snapshot {}", path.display()))?;
+    let Some(body) = bytes.strip_prefix(SNAPSHOT_MAGIC) else {
+        bail!("not a UTXO snapshot");
+    };
+    let snapshot: UtxoSnapshot = codec::decode_bounded(body, body.len() as u64).context("malformed UTXO snapshot")?;
+
+    if snapshot.version != SNAPSHOT_VERSION {
+        bail!("unsupported snapshot version {}", snapshot.version);
+    }
+    if snapshot.height != trusted.height || snapshot.block_hash != trusted.block_hash {
+        bail!(
+            "snapshot is for block {} at height {}, expected {} at height {}",
+            snapshot.block_hash.to_hex(), snapshot.height, trusted.block_hash.to_hex(), trusted.height
+        );
+    }
+    // The header commitment is only a claim; recompute it from the coins themselves
+    let commitment = utxo_commitment(&snapshot.coins);
+    if commitment != snapshot.commitment || commitment != trusted.commitment {
+        bail!("UTXO snapshot commitment mismatch: computed {}, trusted {}", commitment.to_hex(), trusted.commitment.to_hex());
+    }
+
+    store.import_utxo_snapshot(&snapshot.coins, &snapshot.block_hash, snapshot.height)?;
+    info!("📦 Loaded {} UTXOs, resuming from height {}", snapshot.coins.len(), snapshot.height + 1);
+    Ok(snapshot.height)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rocksdb::WriteBatch;
+    use tempfile::tempdir;
+
+    fn coin(n: u8, value: Amount) -> (OutPoint, UtxoValue) {
+        (OutPoint::new(Hash32([n; 32]), n as u32), (value, OutputType::P2PQ { pubkey: vec![n; 32] }, n as u64, n == 0))
+    }
+
+    /// Store with three UTXOs and a tip at height 10
+    fn populated_store(dir: &Path) -> Result<Storage> {
+        let store = Storage::open(dir)?;
+        let mut wb = WriteBatch::default();
+        for (op, val) in [coin(0, 50_0000_0000), coin(1, 1_000), coin(2, 25_000)] {
+            store.put_utxo_batch(&mut wb, &op, &val);
+        }
+        store.db.write(wb)?;
+        let header = BlockHeader::new(1, Hash32::zero(), Hash32::zero(), 1000, 0x1d00ffff, 0);
+        store.write_block(&Hash32([9u8; 32]), &Block::new(header, vec![]), 10)?;
+        Ok(store)
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() -> Result<()> {
+        let (src_dir, dst_dir, file_dir) = (tempdir()?, tempdir()?, tempdir()?);
+        let path = file_dir.path().join("utxo.dat");
+        let source = populated_store(src_dir.path())?;
+
+        let trusted = dump_utxo_set(&source, &path)?;
+        assert_eq!(trusted.height, 10);
+        assert_eq!(trusted.block_hash, Hash32([9u8; 32]));
+        // What the dumping node prints is what `--assumeutxo` accepts
+        assert_eq!(trusted.to_string().parse::<AssumeUtxo>()?, trusted);
+        assert!("10:abcd".parse::<AssumeUtxo>().is_err());
+
+        let target = Storage::open(dst_dir.path())?;
+        assert_eq!(load_utxo_set(&target, &path, &trusted)?, 10);
+        assert_eq!(target.utxo_entries()?, source.utxo_entries()?);
+        assert_eq!(target.get_tip()?, Some(trusted.block_hash));
+        assert_eq!(target.get_tip_height()?, Some(10));
+        assert_eq!(target.get_utxo(&coin(2, 0).0)?.unwrap().0, 25_000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tampered_snapshot_rejected() -> Result<()> {
+        let (src_dir, dst_dir, file_dir) = (tempdir()?, tempdir()?, tempdir()?);
+        let path = file_dir.path().join("utxo.dat");
+        let trusted = dump_utxo_set(&populated_store(src_dir.path())?, &path)?;
+
+        // Inflate one coin but leave the claimed commitment alone
+        let bytes = fs::read(&path)?;
+        let mut snapshot: UtxoSnapshot = bincode::deserialize(&bytes[SNAPSHOT_MAGIC.len()..])?;
+        snapshot.coins[1].1.0 = 21_000_000_0000_0000;
+        let mut tampered = SNAPSHOT_MAGIC.to_vec();
+        tampered.extend(bincode::serialize(&snapshot)?);
+        fs::write(&path, tampered)?;
+
+        let target = Storage::open(dst_dir.path())?;
+        let err = load_utxo_set(&target, &path, &trusted).unwrap_err();
+        assert!(err.to_string().contains("commitment mismatch"), "{}", err);
+        assert!(target.utxo_entries()?.is_empty());
+        assert_eq!(target.get_tip()?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_untrusted_commitment_rejected() -> Result<()> {
+        let (src_dir, dst_dir, file_dir) = (tempdir()?, tempdir()?, tempdir()?);
+        let path = file_dir.path().join("utxo.dat");
+        let mut trusted = dump_utxo_set(&populated_store(src_dir.path())?, &path)?;
+        trusted.commitment = Hash32([0xab; 32]);
+
+        let target = Storage::open(dst_dir.path())?;
+        assert!(load_utxo_set(&target, &path, &trusted).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_commitment_ignores_order() {
+        let coins = vec![coin(0, 1), coin(1, 2), coin(2, 3)];
+        let reversed: Vec<_> = coins.iter().rev().cloned().collect();
+        assert_eq!(utxo_commitment(&coins), utxo_commitment(&reversed));
+        assert_ne!(utxo_commitment(&coins), utxo_commitment(&coins[..2]));
+    }
+}
@@ -1,14 +1,27 @@
 use qc_types::*;
+use qc_validation::key_reuse::{output_pubkey, RevealedKeys};
 use anyhow::Result;
 use rocksdb::{DB, Options, WriteBatch};
 use std::path::Path;
 
+/// Stored per unspent output: value, script kind, creation height, coinbase flag
+pub type UtxoValue = (Amount, OutputType, u64, bool);
+
+/// Stored per known header: the header, its height and the chain's cumulative work
+pub type HeaderEntry = (BlockHeader, u64, u128);
+
 pub struct Storage { 
-    pub db: DB 
+    pub db: DB,
+    /// Whether connected blocks' transactions are indexed by txid
+    txindex: bool,
 }
 
 impl Storage {
     pub fn open<P: AsRef<Path>>(p: P) -> Result<Self> {
+        Self::open_with_txindex(p, true)
+    }
+
+    pub fn open_with_txindex<P: AsRef<Path>>(p: P, txindex: bool) -> Result<Self> {
         let mut opts = Options::default();
         opts.create_if_missing(true);
         opts.increase_parallelism(num_cpus::get() as i32);
@@ -16,10 +29,15 @@ impl Storage {
         opts.set_max_open_files(1000);
         
         Ok(Self { 
-            db: DB::open(&opts, p)? 
+            db: DB::open(&opts, p)?,
+            txindex,
         })
     }
 
+    pub fn txindex(&self) -> bool {
+        self.txindex
+    }
+
     // Key prefixes for different data types
     fn k_utxo(op: &OutPoint) -> Vec<u8> {
         let mut k = b"U".to_vec();
@@ -43,13 +61,33 @@ impl Storage {
     fn k_tip() -> Vec<u8> { 
         b"T:tip".to_vec() 
     }
+
+    fn k_tip_height() -> Vec<u8> {
+        b"T:height".to_vec()
+    }
     
+    fn k_best_header_work() -> Vec<u8> {
+        b"T:best_work".to_vec()
+    }
+
+    fn k_header(h: &Hash32) -> Vec<u8> {
+        let mut k = b"W".to_vec();
+        k.extend_from_slice(&h.0);
+        k
+    }
+
     fn k_tx(txid: &Hash32) -> Vec<u8> {
         let mut k = b"X".to_vec();
         k.extend_from_slice(&txid.0);
         k
     }
 
+    fn k_revealed(pubkey: &[u8]) -> Vec<u8> {
+        let mut k = b"K".to_vec();
+        k.extend_from_slice(pubkey);
+        k
+    }
+
     /// Get UTXO data
     pub fn get_utxo(&self, op: &OutPoint) -> Result<Option<(Amount, OutputType, u64, bool)>> {
         if let Some(v) = self.db.get(Self::k_utxo(op))? {
@@ -69,20 +107,46 @@ impl Storage {
         wb.delete(Self::k_utxo(op));
     }
 
+    /// Mark a pubkey as revealed by a confirmed spend, in a batch write
+    pub fn put_revealed_key_batch(&self, wb: &mut WriteBatch, pubkey: &[u8]) {
+        wb.put(Self::k_revealed(pubkey), []);
+    }
+
+    /// The revealed pubkeys among those `tx` pays to
+    pub fn revealed_keys_for(&self, tx: &Transaction) -> Result<RevealedKeys> {
+        let mut revealed = RevealedKeys::default();
+        for out in &tx.vout {
+            let pubkey = output_pubkey(&out.kind);
+            if self.db.get(Self::k_revealed(pubkey))?.is_some() {
+                revealed.reveal(pubkey);
+            }
+        }
+        Ok(revealed)
+    }
+
     /// Write block to storage
     pub fn write_block(&self, hash: &Hash32, blk: &Block, height: u64) -> Result<()> {
         let mut wb = WriteBatch::default();
+        self.write_block_batch(&mut wb, hash, blk, height)?;
+        self.db.write(wb)?;
+        Ok(())
+    }
+
+    /// Add a block, its height index entry, the new tip and its txindex
+    /// entries to batch write
+    pub fn write_block_batch(&self, wb: &mut WriteBatch, hash: &Hash32, blk: &Block, height: u64) -> Result<()> {
         wb.put(Self::k_block(hash), bincode::serialize(blk)?);
         wb.put(Self::k_height(height), hash.0);
         wb.put(Self::k_tip(), hash.0);
+        wb.put(Self::k_tip_height(), height.to_le_bytes());
         
         // Index transactions
-        for tx in &blk.txs {
-            let txid = self.calculate_txid(tx);
-            wb.put(Self::k_tx(&txid), bincode::serialize(&(height, tx))?);
+        if self.txindex {
+            for tx in &blk.txs {
+                let txid = self.calculate_txid(tx);
+                wb.put(Self::k_tx(&txid), bincode::serialize(&(height, tx))?);
+            }
         }
-        
-        self.db.write(wb)?;
         Ok(())
     }
 
@@ -118,6 +182,91 @@ impl Storage {
         }
     }
 
+    /// Height of the current tip
+    pub fn get_tip_height(&self) -> Result<Option<u64>> {
+        if let Some(bytes) = self.db.get(Self::k_tip_height())? {
+            Ok(Some(u64::from_le_bytes(bytes.as_slice().try_into()?)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Index a header with its height and cumulative chainwork, raising the
+    /// best known chainwork if it exceeds it
+    pub fn put_header(&self, header: &BlockHeader, height: u64, chainwork: u128) -> Result<()> {
+        let mut wb = WriteBatch::default();
+        wb.put(Self::k_header(&header.hash()), bincode::serialize(&(header, height, chainwork))?);
+        if chainwork > self.best_header_work()? {
+            wb.put(Self::k_best_header_work(), chainwork.to_le_bytes());
+        }
+        self.db.write(wb)?;
+        Ok(())
+    }
+
+    /// A known header by hash
+    pub fn get_header(&self, hash: &Hash32) -> Result<Option<HeaderEntry>> {
+        if let Some(v) = self.db.get(Self::k_header(hash))? {
+            Ok(Some(bincode::deserialize(&v)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Most cumulative work of any known header; zero before the first
+    pub fn best_header_work(&self) -> Result<u128> {
+        if let Some(bytes) = self.db.get(Self::k_best_header_work())? {
+            Ok(u128::from_le_bytes(bytes.as_slice().try_into()?))
+        } else {
+            Ok(0)
+        }
+    }
+
+    /// Every known header with its hash, in key order
+    pub fn header_entries(&self) -> Result<Vec<(Hash32, HeaderEntry)>> {
+        let mut entries = Vec::new();
+        for item in self.db.prefix_iterator(b"W") {
+            let (k, v) = item?;
+            // Without a prefix extractor the iterator runs on past the prefix
+            if !k.starts_with(b"W") {
+                break;
+            }
+            let mut hash = [0u8; 32];
+            hash.copy_from_slice(&k[1..33]);
+            entries.push((Hash32(hash), bincode::deserialize(&v)?));
+        }
+        Ok(entries)
+    }
+
+    /// Every UTXO in key order
+    pub fn utxo_entries(&self) -> Result<Vec<(OutPoint, UtxoValue)>> {
+        let mut entries = Vec::new();
+        for item in self.db.prefix_iterator(b"U") {
+            let (k, v) = item?;
+            // Without a prefix extractor the iterator runs on past the prefix
+            if !k.starts_with(b"U") {
+                break;
+            }
+            let mut txid = [0u8; 32];
+            txid.copy_from_slice(&k[1..33]);
+            let vout = u32::from_le_bytes(k[33..37].try_into()?);
+            entries.push((OutPoint::new(Hash32(txid), vout), bincode::deserialize(&v)?));
+        }
+        Ok(entries)
+    }
+
+    /// Write a snapshot's UTXOs and make its base block the tip, atomically
+    pub fn import_utxo_snapshot(&self, coins: &[(OutPoint, UtxoValue)], base_hash: &Hash32, height: u64) -> Result<()> {
+        let mut wb = WriteBatch::default();
+        for (op, val) in coins {
+            self.put_utxo_batch(&mut wb, op, val);
+        }
+        wb.put(Self::k_height(height), base_hash.0);
+        wb.put(Self::k_tip(), base_hash.0);
+        wb.put(Self::k_tip_height(), height.to_le_bytes());
+        self.db.write(wb)?;
+        Ok(())
+    }
+
     /// Calculate transaction ID
     pub fn calculate_txid(&self, tx: &Transaction) -> Hash32 {
         use sha2::{Digest, Sha256};
@@ -128,7 +277,7 @@ impl Storage {
         Hash32(arr)
     }
 
-    /// Get transaction by ID
+    /// Get transaction by ID; always `None` when the txindex is off
     pub fn get_transaction(&self, txid: &Hash32) -> Result<Option<(u64, Transaction)>> {
         if let Some(v) = self.db.get(Self::k_tx(txid))? {
             Ok(Some(bincode::deserialize(&v)?))
@@ -216,4 +365,27 @@ mod tests {
         
         Ok(())
     }
+
+    #[test]
+    fn test_txindex_toggle() -> Result<()> {
+        let coinbase = Transaction {
+            version: 1,
+            lock_time: 0,
+            vin: vec![],
+            vout: vec![TxOut { value: 50, kind: OutputType::P2PQ { pubkey: vec![1u8; 1312] } }],
+        };
+        let header = BlockHeader::new(1, Hash32::zero(), Hash32::zero(), 1000, 0x1d00ffff, 0);
+        let block = Block::new(header, vec![coinbase.clone()]);
+
+        for txindex in [true, false] {
+            let dir = tempdir()?;
+            let storage = Storage::open_with_txindex(dir.path(), txindex)?;
+            storage.write_block(&Hash32([1u8; 32]), &block, 0)?;
+
+            let txid = storage.calculate_txid(&coinbase);
+            assert_eq!(storage.txindex(), txindex);
+            assert_eq!(storage.get_transaction(&txid)?.is_some(), txindex);
+        }
+        Ok(())
+    }
 }
@@ -1,11 +1,20 @@
 // QuantumCoin Blockchain Synchronization - Full and Fast Sync
 
 use crate::{Block, Chain, P2PNetwork};
+use crate::p2p::P2PMessage;
 use anyhow::{Result, anyhow};
-use std::collections::{HashMap, VecDeque};
+use qc_validation::ChainSpec;
+use qc_validation::sync_status::{best_known_height, sync_progress, sync_status};
+pub use qc_validation::sync_status::{SyncStatus, SYNC_COMPLETE_PROGRESS};
+use std::collections::{HashMap, HashSet, VecDeque};
+use parking_lot::{Mutex, MutexGuard};
 use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 
+/// Peer height reports older than this no longer count toward the sync target
+pub const PEER_HEIGHT_MAX_AGE_SECS: u64 = 30 * 60;
+
 #[derive(Debug, Clone)]
 pub enum SyncMode {
     Full,        // Download and validate all blocks from genesis
@@ -13,25 +22,421 @@ pub enum SyncMode {
     Checkpoint,  // Start from a trusted checkpoint
 }
 
+/// Seconds a peer gets to deliver a requested header batch or block body
+pub const DEFAULT_DOWNLOAD_TIMEOUT_SECS: u64 = 30;
+
+/// Ban score added to a peer each time it lets a download time out
+pub const DOWNLOAD_TIMEOUT_BAN_SCORE: u32 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownloadKind {
+    Headers,
+    Block,
+}
+
+#[derive(Debug, Clone)]
+struct Download {
+    kind: DownloadKind,
+    peer: String,
+    requested_at: u64,
+}
+
+/// A header or block request its peer failed to answer in time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimedOutDownload {
+    pub height: u64,
+    pub kind: DownloadKind,
+    pub peer: String,
+}
+
+/// Outstanding header and block requests, each with its own deadline so a
+/// peer trickling a response byte by byte cannot hold up sync
+#[derive(Debug)]
+pub struct DownloadTracker {
+    timeout_secs: u64,
+    in_flight: HashMap<u64, Download>,
+    /// Peers that already timed out on a height, skipped when reassigning it
+    failed: HashMap<u64, HashSet<String>>,
+}
+
+impl Default for DownloadTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_DOWNLOAD_TIMEOUT_SECS)
+    }
+}
+
+impl DownloadTracker {
+    pub fn new(timeout_secs: u64) -> Self {
+        Self { timeout_secs, in_flight: HashMap::new(), failed: HashMap::new() }
+    }
+
+    /// Record that `height` was requested from `peer`
+    pub fn request(&mut self, height: u64, kind: DownloadKind, peer: &str) {
+        self.request_at(height, kind, peer, SyncManager::now());
+    }
+
+    fn request_at(&mut self, height: u64, kind: DownloadKind, peer: &str, now: u64) {
+        self.in_flight.insert(height, Download { kind, peer: peer.to_string(), requested_at: now });
+    }
+
+    /// The download for `height` arrived; returns the peer that served it
+    pub fn completed(&mut self, height: u64) -> Option<String> {
+        self.failed.remove(&height);
+        self.in_flight.remove(&height).map(|download| download.peer)
+    }
+
+    /// Peer currently responsible for `height`
+    pub fn peer_for(&self, height: u64) -> Option<&str> {
+        self.in_flight.get(&height).map(|download| download.peer.as_str())
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Cancel every download past its deadline
+    fn expire_at(&mut self, now: u64) -> Vec<TimedOutDownload> {
+        let expired: Vec<u64> = self.in_flight.iter()
+            .filter(|(_, download)| now.saturating_sub(download.requested_at) >= self.timeout_secs)
+            .map(|(&height, _)| height)
+            .collect();
+        let mut timed_out = Vec::with_capacity(expired.len());
+        for height in expired {
+            if let Some(download) = self.in_flight.remove(&height) {
+                self.failed.entry(height).or_default().insert(download.peer.clone());
+                timed_out.push(TimedOutDownload { height, kind: download.kind, peer: download.peer });
+            }
+        }
+        timed_out.sort_by_key(|download| download.height);
+        timed_out
+    }
+
+    /// Request a timed-out download again from the least busy peer that
+    /// hasn't already failed it
+    fn reassign_at(&mut self, download: &TimedOutDownload, peers: &[String], now: u64) -> Option<String> {
+        let failed = self.failed.get(&download.height);
+        let peer = peers.iter()
+            .filter(|peer| !failed.is_some_and(|failed| failed.contains(*peer)))
+            .min_by_key(|peer| self.in_flight.values().filter(|d| &d.peer == *peer).count())?
+            .clone();
+        self.request_at(download.height, download.kind, &peer, now);
+        Some(peer)
+    }
+}
+
+/// Limits on fetching the missing parents of orphan blocks, so catching up
+/// through a run of orphans cannot flood peers with requests
+#[derive(Debug, Clone)]
+pub struct OrphanThrottleConfig {
+    /// Parent requests outstanding across all peers
+    pub max_in_flight: usize,
+    /// Parent requests outstanding to any single peer
+    pub max_per_peer: usize,
+    /// New parent requests issued per second
+    pub requests_per_sec: usize,
+    /// Seconds before an unanswered request goes back in the queue
+    pub request_timeout_secs: u64,
+}
+
+impl Default for OrphanThrottleConfig {
+    fn default() -> Self {
+        Self { max_in_flight: 16, max_per_peer: 4, requests_per_sec: 8, request_timeout_secs: 10 }
+    }
+}
+
+/// An outstanding parent request
+#[derive(Debug, Clone)]
+struct ParentRequest {
+    peer: String,
+    sent_at: u64,
+}
+
+/// Queues orphan-parent hashes and hands them out to peers within the
+/// configured rate and concurrency, always to the least loaded peer
+#[derive(Debug, Default)]
+pub struct OrphanParentThrottle {
+    config: OrphanThrottleConfig,
+    queue: VecDeque<String>,
+    queued: HashSet<String>,
+    in_flight: HashMap<String, ParentRequest>,
+    per_peer: HashMap<String, usize>,
+    /// Second the current rate window started, and requests issued in it
+    window: (u64, usize),
+    /// Rotates which peer wins ties so equal peers share the load
+    next_peer: usize,
+}
+
+impl OrphanParentThrottle {
+    pub fn new(config: OrphanThrottleConfig) -> Self {
+        Self { config, ..Self::default() }
+    }
+
+    /// Queue a missing parent unless it is already queued or requested
+    pub fn queue_parent(&mut self, hash: &str) {
+        if !self.in_flight.contains_key(hash) && self.queued.insert(hash.to_string()) {
+            self.queue.push_back(hash.to_string());
+        }
+    }
+
+    /// The parent arrived, freeing its request slot
+    pub fn parent_received(&mut self, hash: &str) {
+        if let Some(request) = self.in_flight.remove(hash) {
+            self.release(&request.peer);
+        }
+        if self.queued.remove(hash) {
+            self.queue.retain(|queued| queued != hash);
+        }
+    }
+
+    /// Drop every request outstanding to a disconnected peer, requeueing them
+    pub fn peer_disconnected(&mut self, peer: &str) {
+        let lost: Vec<String> = self.in_flight.iter()
+            .filter(|(_, request)| request.peer == peer)
+            .map(|(hash, _)| hash.clone())
+            .collect();
+        for hash in lost {
+            self.requeue(hash);
+        }
+        self.per_peer.remove(peer);
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Parent requests to send now, as `(parent hash, peer)` pairs
+    pub fn next_requests(&mut self, peers: &[String]) -> Vec<(String, String)> {
+        self.next_requests_at(peers, SyncManager::now())
+    }
+
+    fn next_requests_at(&mut self, peers: &[String], now: u64) -> Vec<(String, String)> {
+        self.expire_at(now);
+        if self.window.0 != now {
+            self.window = (now, 0);
+        }
+
+        let mut requests = Vec::new();
+        while self.in_flight.len() < self.config.max_in_flight && self.window.1 < self.config.requests_per_sec {
+            let Some(peer) = self.least_loaded_peer(peers) else { break };
+            let Some(hash) = self.queue.pop_front() else { break };
+            self.queued.remove(&hash);
+            *self.per_peer.entry(peer.clone()).or_default() += 1;
+            self.in_flight.insert(hash.clone(), ParentRequest { peer: peer.clone(), sent_at: now });
+            self.window.1 += 1;
+            requests.push((hash, peer));
+        }
+        requests
+    }
+
+    /// Peer with the fewest outstanding requests still under the per-peer cap
+    fn least_loaded_peer(&mut self, peers: &[String]) -> Option<String> {
+        if peers.is_empty() {
+            return None;
+        }
+        let start = self.next_peer % peers.len();
+        let peer = (0..peers.len())
+            .map(|offset| &peers[(start + offset) % peers.len()])
+            .map(|peer| (peer, self.per_peer.get(peer).copied().unwrap_or(0)))
+            .filter(|&(_, load)| load < self.config.max_per_peer)
+            .min_by_key(|&(_, load)| load)?
+            .0
+            .clone();
+        self.next_peer = start + 1;
+        Some(peer)
+    }
+
+    /// Requeue requests that went unanswered past the timeout
+    fn expire_at(&mut self, now: u64) {
+        let expired: Vec<String> = self.in_flight.iter()
+            .filter(|(_, request)| now.saturating_sub(request.sent_at) >= self.config.request_timeout_secs)
+            .map(|(hash, _)| hash.clone())
+            .collect();
+        for hash in expired {
+            self.requeue(hash);
+        }
+    }
+
+    fn requeue(&mut self, hash: String) {
+        if let Some(request) = self.in_flight.remove(&hash) {
+            self.release(&request.peer);
+            self.queued.insert(hash.clone());
+            self.queue.push_front(hash);
+        }
+    }
+
+    fn release(&mut self, peer: &str) {
+        if let Some(load) = self.per_peer.get_mut(peer) {
+            *load = load.saturating_sub(1);
+        }
+    }
+}
+
+/// Latest height a peer claimed, and when
+#[derive(Debug, Clone, Copy)]
+struct PeerHeight {
+    height: u64,
+    reported_at: u64,
+}
+
 pub struct SyncManager {
     chain: Chain,
     network: Arc<P2PNetwork>,
     sync_mode: SyncMode,
     target_height: u64,
     download_queue: Arc<RwLock<VecDeque<u64>>>,
-    downloading: Arc<RwLock<HashMap<u64, u64>>>, // height -> timestamp
+    peer_heights: HashMap<String, PeerHeight>,
+    orphan_parents: OrphanParentThrottle,
+    /// Shared with the download workers, which record each request in it
+    downloads: Arc<Mutex<DownloadTracker>>,
+    min_sync_peers: usize,
 }
 
 impl SyncManager {
-    pub fn new(chain: Chain, network: Arc<P2PNetwork>, sync_mode: SyncMode) -> Self {
+    /// Requires the spec's `network.min_sync_peers` peers agreeing on our tip
+    /// before sync counts as complete
+    pub fn new(spec: &ChainSpec, chain: Chain, network: Arc<P2PNetwork>, sync_mode: SyncMode) -> Self {
         Self {
             chain,
             network,
             sync_mode,
             target_height: 0,
             download_queue: Arc::new(RwLock::new(VecDeque::new())),
-            downloading: Arc::new(RwLock::new(HashMap::new())),
+            peer_heights: HashMap::new(),
+            orphan_parents: OrphanParentThrottle::default(),
+            downloads: Arc::new(Mutex::new(DownloadTracker::default())),
+            min_sync_peers: spec.network.min_sync_peers,
+        }
+    }
+
+    /// Override the spec's count of peers that must report our tip height
+    /// before sync counts as complete
+    pub fn with_min_sync_peers(mut self, min_sync_peers: usize) -> Self {
+        self.min_sync_peers = min_sync_peers;
+        self
+    }
+
+    pub fn with_download_timeout(mut self, timeout_secs: u64) -> Self {
+        self.downloads = Arc::new(Mutex::new(DownloadTracker::new(timeout_secs)));
+        self
+    }
+
+    /// Outstanding header and block requests
+    pub fn downloads(&self) -> MutexGuard<'_, DownloadTracker> {
+        self.downloads.lock()
+    }
+
+    /// Cancel downloads past their deadline, penalize the peers that let them
+    /// lapse and hand the work to other `peers`. Returns what timed out and
+    /// who took it over, if anyone could.
+    pub async fn reassign_timed_out_downloads(&mut self, peers: &[String]) -> Vec<(TimedOutDownload, Option<String>)> {
+        self.reassign_timed_out_downloads_at(peers, Self::now()).await
+    }
+
+    async fn reassign_timed_out_downloads_at(&mut self, peers: &[String], now: u64) -> Vec<(TimedOutDownload, Option<String>)> {
+        let mut reassigned = Vec::new();
+        let expired = self.downloads.lock().expire_at(now);
+        for download in expired {
+            println!("⏱️  {:?} download at height {} from {} timed out", download.kind, download.height, download.peer);
+            self.network.penalize_peer(&download.peer, DOWNLOAD_TIMEOUT_BAN_SCORE, "download timed out").await;
+            let peer = self.downloads.lock().reassign_at(&download, peers, now);
+            reassigned.push((download, peer));
+        }
+        reassigned
+    }
+
+    pub fn with_orphan_throttle(mut self, config: OrphanThrottleConfig) -> Self {
+        self.orphan_parents = OrphanParentThrottle::new(config);
+        self
+    }
+
+    /// Throttled requests for the parents of received orphan blocks
+    pub fn orphan_parents(&mut self) -> &mut OrphanParentThrottle {
+        &mut self.orphan_parents
+    }
+
+    /// Take in a block `peer_id` sent: note the height it implies for the
+    /// peer, release any parent request it answers and import it. A block
+    /// whose parent we lack is held as an orphan and its parent queued for
+    /// a throttled request. Returns whether the head moved.
+    pub async fn receive_block(&mut self, peer_id: &str, block: Block) -> Result<bool> {
+        self.network.handle_message(peer_id, &P2PMessage::Block { block: block.clone() }).await;
+        self.orphan_parents.parent_received(&block.hash.to_hex());
+        self.downloads.lock().completed(block.header.number);
+        let new_head = self.chain.import_block(block.clone())?;
+        if self.chain.is_orphan(&block.hash) {
+            self.orphan_parents.queue_parent(&block.header.parent.to_hex());
+        }
+        Ok(new_head)
+    }
+
+    /// Send out as many queued orphan-parent requests as the throttle allows.
+    /// Returns the `(parent hash, peer)` pairs requested.
+    pub async fn request_orphan_parents(&mut self) -> Vec<(String, String)> {
+        let peers = self.network.peer_ids().await;
+        let requests = self.orphan_parents.next_requests(&peers);
+        for (parent, peer) in &requests {
+            // In production, send GetBlocks for the parent
+            println!("📥 Requesting orphan parent {} from peer {}", parent, peer);
         }
+        requests
+    }
+    
+    /// Record the height a peer claims, from its version handshake or a
+    /// header announcement. Only the latest report per peer is kept.
+    pub fn record_peer_height(&mut self, peer_id: &str, height: u64) {
+        self.record_peer_height_at(peer_id, height, Self::now());
+    }
+    
+    fn record_peer_height_at(&mut self, peer_id: &str, height: u64, now: u64) {
+        self.peer_heights.insert(peer_id.to_string(), PeerHeight { height, reported_at: now });
+    }
+
+    /// Take in the heights peers have claimed to the network layer
+    pub async fn refresh_peer_heights(&mut self) {
+        for (peer_id, height, reported_at) in self.network.peer_heights().await {
+            self.record_peer_height_at(&peer_id, height, reported_at);
+        }
+    }
+    
+    /// Height to sync toward: the median of recent peer reports, never below
+    /// our own height
+    pub fn best_known_height(&self) -> u64 {
+        self.best_known_height_at(Self::now())
+    }
+    
+    fn best_known_height_at(&self, now: u64) -> u64 {
+        best_known_height(self.chain.height(), &self.recent_peer_heights(now))
+    }
+    
+    fn recent_peer_heights(&self, now: u64) -> Vec<u64> {
+        self.peer_heights.values()
+            .filter(|r| now.saturating_sub(r.reported_at) <= PEER_HEIGHT_MAX_AGE_SECS)
+            .map(|r| r.height)
+            .collect()
+    }
+    
+    /// Share of the best known height we hold, from 0.0 to 1.0
+    pub fn sync_progress(&self) -> f64 {
+        self.sync_progress_at(Self::now())
+    }
+    
+    fn sync_progress_at(&self, now: u64) -> f64 {
+        sync_progress(self.chain.height(), &self.recent_peer_heights(now))
+    }
+    
+    /// Caught up is not enough to be healthy: at least `min_sync_peers`
+    /// peers must also report our tip height, so a node fed by a single
+    /// peer or cut off from the network reports itself degraded
+    pub fn sync_status(&self) -> SyncStatus {
+        self.sync_status_at(Self::now())
+    }
+    
+    fn sync_status_at(&self, now: u64) -> SyncStatus {
+        sync_status(self.chain.height(), &self.recent_peer_heights(now), self.min_sync_peers)
     }
     
     /// Start synchronization process
@@ -61,9 +466,8 @@ impl SyncManager {
             return Err(anyhow!("No peers available for sync"));
         }
         
-        // In production, this would query peers for their heights
-        // For now, simulate realistic network height
-        self.target_height = self.chain.height() + 100; // Simulate being 100 blocks behind
+        self.refresh_peer_heights().await;
+        self.target_height = self.best_known_height();
         
         println!("🎯 Target height: {} (current: {})", self.target_height, self.chain.height());
         Ok(())
@@ -158,7 +562,7 @@ impl SyncManager {
         println!("📋 Loading trusted checkpoint...");
         
         // For now, use genesis as checkpoint
-        Ok(self.chain.head())
+        self.chain.head().ok_or_else(|| anyhow!("Chain has no head block"))
     }
     
     async fn validate_checkpoint(&self, checkpoint: &Block) -> Result<()> {
@@ -171,7 +575,7 @@ impl SyncManager {
         ]);
         
         if let Some(expected_hash) = known_checkpoints.get(&checkpoint.header.number) {
-            if &checkpoint.hash != expected_hash {
+            if checkpoint.hash.to_hex() != *expected_hash {
                 return Err(anyhow!("Checkpoint hash mismatch"));
             }
         }
@@ -191,8 +595,8 @@ impl SyncManager {
     
     async fn start_download_worker(&self, worker_id: usize) {
         let queue = Arc::clone(&self.download_queue);
-        let downloading = Arc::clone(&self.downloading);
-        let chain = self.chain.clone();
+        let downloads = Arc::clone(&self.downloads);
+        let network = Arc::clone(&self.network);
         
         tokio::spawn(async move {
             println!("🔄 Download worker {} started", worker_id);
@@ -205,11 +609,15 @@ impl SyncManager {
                 
                 match height_opt {
                     Some(height) => {
-                        // Mark as downloading
-                        {
-                            let mut downloading_lock = downloading.write().await;
-                            downloading_lock.insert(height, Self::now());
+                        let peers = network.peer_ids().await;
+                        if peers.is_empty() {
+                            queue.write().await.push_front(height);
+                            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+                            continue;
                         }
+                        // Spread heights over peers; the tracker gives each a deadline
+                        let peer = &peers[(height % peers.len() as u64) as usize];
+                        downloads.lock().request(height, DownloadKind::Block, peer);
                         
                         // Simulate block download and validation
                         tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
@@ -219,13 +627,8 @@ impl SyncManager {
                         // 2. Validate block
                         // 3. Add to chain
                         
-                        // Mark as completed
-                        {
-                            let mut downloading_lock = downloading.write().await;
-                            downloading_lock.remove(&height);
-                        }
-                        
-                        println!("📥 Worker {} downloaded block {}", worker_id, height);
+                        downloads.lock().completed(height);
+                        println!("📥 Worker {} downloaded block {} from {}", worker_id, height, peer);
                     },
                     None => {
                         // No more blocks to download
@@ -236,21 +639,19 @@ impl SyncManager {
         });
     }
     
-    async fn wait_for_sync_completion(&self) -> Result<()> {
+    async fn wait_for_sync_completion(&mut self) -> Result<()> {
         loop {
-            let (queue_empty, downloads_empty) = {
-                let queue = self.download_queue.read().await;
-                let downloading = self.downloading.read().await;
-                (queue.is_empty(), downloading.is_empty())
-            };
-            
-            if queue_empty && downloads_empty {
+            self.request_orphan_parents().await;
+            let peers = self.network.peer_ids().await;
+            self.reassign_timed_out_downloads(&peers).await;
+
+            let downloading_count = self.downloads.lock().in_flight();
+            let queue_count = self.download_queue.read().await.len();
+            if downloading_count == 0 && queue_count == 0 {
                 break;
             }
             
             // Show progress
-            let downloading_count = self.downloading.read().await.len();
-            let queue_count = self.download_queue.read().await.len();
             
             if downloading_count > 0 || queue_count > 0 {
                 println!("📊 Sync progress: {} downloading, {} queued", downloading_count, queue_count);
@@ -303,7 +704,8 @@ pub async fn test_fresh_node_sync() -> Result<()> {
     network.start().await?;
     
     // Start sync
-    let mut sync_manager = SyncManager::new(chain.clone(), network.clone(), SyncMode::Full);
+    let spec: ChainSpec = toml::from_str(include_str!("../../../chain_spec.toml"))?;
+    let mut sync_manager = SyncManager::new(&spec, chain.clone(), network.clone(), SyncMode::Full);
     sync_manager.start_sync().await?;
     
     // Wait for sync with SLA timeout
@@ -331,6 +733,7 @@ pub async fn test_fresh_node_sync() -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use qc_types::Hash32;
     
     #[tokio::test]
     async fn test_sync_manager() {
@@ -338,9 +741,246 @@ mod tests {
         let listen_addr = "127.0.0.1:0".parse().unwrap();
         let network = Arc::new(P2PNetwork::new(listen_addr, chain.clone()));
         
-        let sync_manager = SyncManager::new(chain, network, SyncMode::Full);
+        let sync_manager = SyncManager::new(&spec(), chain, network, SyncMode::Full);
         
         // Test sync manager creation
         assert!(matches!(sync_manager.sync_mode, SyncMode::Full));
     }
+    
+    fn sync_manager() -> SyncManager {
+        let genesis = Block {
+            hash: Hash32([0xab; 32]),
+            header: crate::BlockHeader { parent: Hash32::zero(), number: 0, timestamp: 1_700_000_000, difficulty: 1, nonce: 0, merkle_root: Hash32::zero() },
+            txs: vec![],
+            work: 1,
+        };
+        let chain = Chain::from_genesis(genesis);
+        let network = Arc::new(P2PNetwork::new("127.0.0.1:0".parse().unwrap(), chain.clone()));
+        SyncManager::new(&spec(), chain, network, SyncMode::Full)
+    }
+
+    fn spec() -> ChainSpec {
+        toml::from_str(include_str!("../../../chain_spec.toml")).unwrap()
+    }
+    
+    #[test]
+    fn test_best_known_height_ignores_lying_minority() {
+        let mut sync = sync_manager();
+        let now = 1_700_100_000;
+        for (i, height) in [1_000, 1_001, 1_002, 1_002, 1_003, 999, 1_001].iter().enumerate() {
+            sync.record_peer_height_at(&format!("honest{}", i), *height, now);
+        }
+        for i in 0..3 {
+            sync.record_peer_height_at(&format!("liar{}", i), u64::MAX - i, now);
+        }
+        let best = sync.best_known_height_at(now);
+        assert!((999..=1_003).contains(&best), "median skewed to {}", best);
+        
+        // Even a near-half minority stays out of the target
+        for i in 3..6 {
+            sync.record_peer_height_at(&format!("liar{}", i), 50_000_000, now);
+        }
+        assert!(sync.best_known_height_at(now) <= 1_003);
+    }
+    
+    #[test]
+    fn test_min_sync_peers_comes_from_spec() {
+        let content = include_str!("../../../chain_spec.toml").replace("min_sync_peers = 3", "min_sync_peers = 1");
+        let spec: ChainSpec = toml::from_str(&content).unwrap();
+        let chain = sync_manager().chain;
+        let network = Arc::new(P2PNetwork::new("127.0.0.1:0".parse().unwrap(), chain.clone()));
+        let mut sync = SyncManager::new(&spec, chain, network, SyncMode::Full);
+        let now = 1_700_100_000;
+        sync.record_peer_height_at("only", 0, now);
+        assert_eq!(sync.sync_status_at(now), SyncStatus::Healthy);
+        assert_eq!(sync_manager().min_sync_peers, 3);
+    }
+
+    #[test]
+    fn test_single_peer_sync_is_degraded() {
+        let mut sync = sync_manager();
+        let now = 1_700_100_000;
+        assert_eq!(sync.sync_status_at(now), SyncStatus::Degraded);
+
+        // One peer agreeing on our tip is not enough to trust it
+        sync.record_peer_height_at("only", 0, now);
+        assert_eq!(sync.sync_progress_at(now), 1.0);
+        assert_eq!(sync.sync_status_at(now), SyncStatus::Degraded);
+        assert_eq!(serde_json::to_value(sync.sync_status_at(now)).unwrap(), "degraded");
+
+        // A peer ahead of us means we are still syncing
+        sync.record_peer_height_at("only", 500, now);
+        assert_eq!(sync.sync_status_at(now), SyncStatus::Syncing);
+    }
+
+    #[test]
+    fn test_multi_peer_agreement_is_healthy() {
+        let mut sync = sync_manager();
+        let now = 1_700_100_000;
+        for peer in ["a", "b"] {
+            sync.record_peer_height_at(peer, 0, now);
+        }
+        assert_eq!(sync.sync_status_at(now), SyncStatus::Degraded);
+
+        sync.record_peer_height_at("c", 0, now);
+        assert_eq!(sync.sync_status_at(now), SyncStatus::Healthy);
+
+        // Stale reports stop counting toward agreement
+        let later = now + PEER_HEIGHT_MAX_AGE_SECS + 1;
+        sync.record_peer_height_at("a", 0, later);
+        assert_eq!(sync.sync_status_at(later), SyncStatus::Degraded);
+    }
+
+    #[tokio::test]
+    async fn test_received_orphan_queues_its_parent() {
+        let mut sync = sync_manager();
+        let genesis = sync.chain.head().unwrap();
+        let child = |parent: &Block, tag: u8| Block {
+            hash: Hash32([tag; 32]),
+            header: crate::BlockHeader {
+                parent: parent.hash,
+                number: parent.header.number + 1,
+                timestamp: parent.header.timestamp + 30,
+                difficulty: 1,
+                nonce: 0,
+                merkle_root: qc_validation::merkle_root(&[]),
+            },
+            txs: vec![],
+            work: 1,
+        };
+        let b1 = child(&genesis, 1);
+        let b2 = child(&b1, 2);
+
+        assert!(!sync.receive_block("peer", b2.clone()).await.unwrap());
+        assert_eq!(sync.orphan_parents().queued(), 1);
+
+        // The parent arrives, clearing the queue and connecting both
+        assert!(sync.receive_block("peer", b1.clone()).await.unwrap());
+        assert_eq!(sync.orphan_parents().queued(), 0);
+        assert_eq!(sync.chain.height(), 2);
+    }
+
+    #[test]
+    fn test_orphan_parent_requests_throttled_and_spread() {
+        let config = OrphanThrottleConfig { max_in_flight: 6, max_per_peer: 2, requests_per_sec: 100, request_timeout_secs: 10 };
+        let mut throttle = OrphanParentThrottle::new(config);
+        for i in 0..50 {
+            throttle.queue_parent(&format!("parent{}", i));
+        }
+        throttle.queue_parent("parent0");
+        assert_eq!(throttle.queued(), 50);
+
+        let peers: Vec<String> = ["a", "b", "c", "d"].iter().map(|p| p.to_string()).collect();
+        let now = 1_700_000_000;
+        let first = throttle.next_requests_at(&peers, now);
+        assert_eq!(first.len(), 6);
+        assert_eq!(throttle.in_flight(), 6);
+
+        // Spread across every peer, none above its cap
+        let mut per_peer: HashMap<&str, usize> = HashMap::new();
+        for (_, peer) in &first {
+            *per_peer.entry(peer.as_str()).or_default() += 1;
+        }
+        assert_eq!(per_peer.len(), 4);
+        assert!(per_peer.values().all(|&n| n <= 2));
+
+        // Nothing more until a parent arrives
+        assert!(throttle.next_requests_at(&peers, now).is_empty());
+        throttle.parent_received(&first[0].0);
+        assert_eq!(throttle.next_requests_at(&peers, now).len(), 1);
+
+        // A single peer is held to its own cap
+        let mut solo = OrphanParentThrottle::new(config_with_rate(3));
+        for i in 0..10 {
+            solo.queue_parent(&format!("parent{}", i));
+        }
+        let only = vec!["a".to_string()];
+        assert_eq!(solo.next_requests_at(&only, now).len(), 2);
+
+        // And the rate limit applies per second
+        let mut limited = OrphanParentThrottle::new(config_with_rate(3));
+        for i in 0..10 {
+            limited.queue_parent(&format!("parent{}", i));
+        }
+        assert_eq!(limited.next_requests_at(&peers, now).len(), 3);
+        assert!(limited.next_requests_at(&peers, now).is_empty());
+        assert_eq!(limited.next_requests_at(&peers, now + 1).len(), 3);
+
+        // Timed-out requests are retried
+        assert_eq!(limited.next_requests_at(&peers, now + 20).len(), 3);
+        assert_eq!(limited.in_flight(), 3);
+        assert_eq!(limited.queued(), 7);
+    }
+
+    fn config_with_rate(requests_per_sec: usize) -> OrphanThrottleConfig {
+        OrphanThrottleConfig { max_in_flight: 8, max_per_peer: 2, requests_per_sec, request_timeout_secs: 10 }
+    }
+
+    #[tokio::test]
+    async fn test_slow_download_reassigned_to_faster_peer() {
+        let mut sync = sync_manager().with_download_timeout(10);
+        let peers = vec!["slow".to_string(), "fast".to_string()];
+        let now = 1_700_000_000;
+        sync.downloads().request_at(101, DownloadKind::Block, "slow", now);
+        sync.downloads().request_at(102, DownloadKind::Block, "fast", now);
+        sync.downloads().request_at(103, DownloadKind::Headers, "slow", now + 5);
+
+        // The fast peer delivers; the slow one is still within its deadline
+        assert_eq!(sync.downloads().completed(102).as_deref(), Some("fast"));
+        assert!(sync.reassign_timed_out_downloads_at(&peers, now + 9).await.is_empty());
+
+        // Trickling past the deadline cancels the request and moves it
+        let reassigned = sync.reassign_timed_out_downloads_at(&peers, now + 10).await;
+        assert_eq!(reassigned.len(), 1);
+        let (timed_out, peer) = &reassigned[0];
+        assert_eq!(timed_out, &TimedOutDownload { height: 101, kind: DownloadKind::Block, peer: "slow".to_string() });
+        assert_eq!(peer.as_deref(), Some("fast"));
+        assert_eq!(sync.downloads().peer_for(101), Some("fast"));
+        assert_eq!(sync.downloads().peer_for(103), Some("slow"));
+
+        // A height is never handed back to a peer that already failed it
+        let reassigned = sync.reassign_timed_out_downloads_at(&peers, now + 20).await;
+        let heights: Vec<(u64, Option<&str>)> = reassigned.iter().map(|(d, p)| (d.height, p.as_deref())).collect();
+        assert_eq!(heights, vec![(101, None), (103, Some("fast"))]);
+        assert_eq!(sync.downloads().in_flight(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_received_block_completes_its_download() {
+        let mut sync = sync_manager();
+        let genesis = sync.chain.head().unwrap();
+        let block = Block {
+            hash: Hash32([1; 32]),
+            header: crate::BlockHeader {
+                parent: genesis.hash,
+                number: 1,
+                timestamp: genesis.header.timestamp + 30,
+                difficulty: 1,
+                nonce: 0,
+                merkle_root: qc_validation::merkle_root(&[]),
+            },
+            txs: vec![],
+            work: 1,
+        };
+        sync.downloads().request(1, DownloadKind::Block, "peer");
+        assert!(sync.receive_block("peer", block).await.unwrap());
+        assert_eq!(sync.downloads().in_flight(), 0);
+        assert!(sync.reassign_timed_out_downloads_at(&["peer".to_string()], u64::MAX).await.is_empty());
+    }
+
+    #[test]
+    fn test_best_known_height_uses_recent_reports() {
+        let mut sync = sync_manager();
+        assert_eq!(sync.best_known_height_at(0), 0);
+        
+        let now = 1_700_100_000;
+        sync.record_peer_height_at("a", 500, now - PEER_HEIGHT_MAX_AGE_SECS - 1);
+        sync.record_peer_height_at("b", 100, now);
+        assert_eq!(sync.best_known_height_at(now), 100);
+        
+        // A newer header announcement replaces the peer's handshake height
+        sync.record_peer_height_at("a", 120, now);
+        sync.record_peer_height_at("b", 130, now);
+        assert_eq!(sync.best_known_height_at(now), 120);
+    }
 }
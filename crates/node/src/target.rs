@@ -1,40 +1,5 @@
-/// Compact target encoding/decoding (Bitcoin-style) for difficulty bits
-pub fn bits_to_target(bits: u32) -> u128 {
-    let exponent = (bits >> 24) as u32;
-    let mantissa = bits & 0x007fffff;
-    
-    if exponent <= 3 {
-        ((mantissa as u128) >> (8 * (3 - exponent)))
-    } else {
-        (mantissa as u128) << (8 * (exponent - 3))
-    }
-}
-
-pub fn target_to_bits(target: u128) -> u32 {
-    if target == 0 {
-        return 0;
-    }
-    
-    // Find the most significant byte
-    let mut size = 0u32;
-    let mut tmp = target;
-    while tmp > 0 {
-        tmp >>= 8;
-        size += 1;
-    }
-    
-    let mut compact: u32;
-    if size <= 3 {
-        let mantissa = (target << (8 * (3 - size))) as u32;
-        compact = mantissa & 0x007fffff;
-    } else {
-        let mantissa = (target >> (8 * (size - 3))) as u32;
-        compact = mantissa & 0x007fffff;
-    }
-    
-    compact |= size << 24;
-    compact
-}
+//! Difficulty retargeting. Compact `bits` encoding lives in
+//! `qc_types::target`.
 
 /// Calculate next difficulty target using simplified algorithm
 pub fn next_difficulty_target(prev_target: u128, actual_timespan: u64, target_timespan: u64) -> u128 {
@@ -52,19 +17,9 @@ pub fn next_difficulty_target(prev_target: u128, actual_timespan: u64, target_ti
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_bits_target_roundtrip() {
-        let original_bits = 0x1d00ffff;
-        let target = bits_to_target(original_bits);
-        let reconstructed_bits = target_to_bits(target);
-        
-        // Should be approximately equal (some precision loss is expected)
-        assert!((original_bits as i64 - reconstructed_bits as i64).abs() < 256);
-    }
-
     #[test]
     fn test_difficulty_adjustment() {
-        let initial_target = bits_to_target(0x1d00ffff);
+        let initial_target = u128::MAX >> 40;
         
         // If blocks come too fast, difficulty should increase (target decrease)
         let faster_target = next_difficulty_target(initial_target, 300, 600);
@@ -81,13 +36,7 @@ mod tests {
 
     #[test]
     fn test_target_bounds() {
-        // Test minimum target
-        let min_bits = target_to_bits(1);
-        assert!(min_bits > 0);
-        
-        // Test maximum reasonable target
-        let max_target = u128::MAX >> 32;
-        let max_bits = target_to_bits(max_target);
-        assert!(bits_to_target(max_bits) <= max_target);
+        assert_eq!(next_difficulty_target(1, 1, 600), 1);
+        assert_eq!(next_difficulty_target(u128::MAX >> 32, 6000, 600), u128::MAX >> 32);
     }
 }
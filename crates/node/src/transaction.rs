@@ -16,8 +16,15 @@ pub enum TransactionError {
     /// Invalid amount
     #[error("Invalid amount: {0}")]
     InvalidAmount(u64),
+    
+    /// Input spends an output that is not in the UTXO set
+    #[error("Unknown input {}:{}", hex::encode(.0), .1)]
+    UnknownInput([u8; 32], u32),
 }
 
+/// Weight units per byte of non-witness data; witness bytes count once
+pub const WITNESS_SCALE_FACTOR: usize = 4;
+
 /// Transaction input
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionInput {
@@ -41,7 +48,8 @@ pub struct TransactionOutput {
     pub recipient: Vec<u8>,
 }
 
-/// Complete transaction
+/// Complete transaction. There is no stored fee: it is whatever the inputs
+/// are worth beyond the outputs, see [`Transaction::fee`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
     /// Transaction inputs
@@ -50,9 +58,6 @@ pub struct Transaction {
     /// Transaction outputs
     pub outputs: Vec<TransactionOutput>,
     
-    /// Transaction fee
-    pub fee: u64,
-    
     /// Transaction timestamp
     pub timestamp: u64,
 }
@@ -67,16 +72,40 @@ impl Transaction {
         hasher.finalize().into()
     }
     
+    /// Serialized size without signatures
+    pub fn base_size(&self) -> usize {
+        let mut stripped = self.clone();
+        for input in &mut stripped.inputs {
+            input.signature.clear();
+        }
+        bincode::serialized_size(&stripped).unwrap() as usize
+    }
+    
+    /// Signature bytes, which are witness data
+    pub fn witness_size(&self) -> usize {
+        self.inputs.iter().map(|input| input.signature.len()).sum()
+    }
+    
+    /// Block weight: base bytes count `WITNESS_SCALE_FACTOR` times, witness bytes once
+    pub fn weight(&self) -> usize {
+        self.base_size() * WITNESS_SCALE_FACTOR + self.witness_size()
+    }
+    
     /// Get transaction ID as hex string
     pub fn id(&self) -> String {
         hex::encode(self.hash())
     }
     
-    /// Calculate total input amount
-    pub fn total_input_amount(&self) -> u64 {
-        // This would need to look up the actual UTXO values
-        // For now, return placeholder
-        0
+    /// Calculate total input amount, valuing each spent output with `lookup`
+    pub fn total_input_amount<F>(&self, mut lookup: F) -> Result<u64, TransactionError>
+    where
+        F: FnMut(&[u8; 32], u32) -> Option<u64>,
+    {
+        self.inputs.iter().try_fold(0u64, |total, input| {
+            let value = lookup(&input.prev_tx_hash, input.output_index)
+                .ok_or(TransactionError::UnknownInput(input.prev_tx_hash, input.output_index))?;
+            total.checked_add(value).ok_or(TransactionError::InvalidAmount(value))
+        })
     }
     
     /// Calculate total output amount
@@ -84,6 +113,16 @@ impl Transaction {
         self.outputs.iter().map(|output| output.amount).sum()
     }
     
+    /// Fee paid: total input amount from the UTXO set minus total output amount
+    pub fn fee<F>(&self, lookup: F) -> Result<u64, TransactionError>
+    where
+        F: FnMut(&[u8; 32], u32) -> Option<u64>,
+    {
+        self.total_input_amount(lookup)?
+            .checked_sub(self.total_output_amount())
+            .ok_or(TransactionError::InsufficientFunds)
+    }
+    
     /// Validate transaction structure
     pub fn validate(&self) -> Result<(), TransactionError> {
         // Check inputs exist
@@ -120,7 +159,6 @@ mod tests {
         let tx = Transaction {
             inputs: vec![],
             outputs: vec![],
-            fee: 1000,
             timestamp: 1640995200,
         };
         
@@ -134,11 +172,64 @@ mod tests {
         let tx = Transaction {
             inputs: vec![],
             outputs: vec![],
-            fee: 1000,
             timestamp: 1640995200,
         };
         
         let id = tx.id();
         assert_eq!(id.len(), 64, "ID should be 64 hex characters");
     }
+    
+    #[test]
+    fn test_weight_discounts_witness_bytes() {
+        let input = |signature: Vec<u8>| TransactionInput { prev_tx_hash: [1; 32], output_index: 0, signature };
+        let output = |recipient: Vec<u8>| TransactionOutput { amount: 5000, recipient };
+        let tx = Transaction {
+            inputs: vec![input(vec![])],
+            outputs: vec![output(vec![2; 20])],
+            timestamp: 1640995200,
+        };
+        assert_eq!(tx.witness_size(), 0);
+        assert_eq!(tx.weight(), tx.base_size() * WITNESS_SCALE_FACTOR);
+        
+        // Signature bytes add one weight unit each
+        let signed = Transaction { inputs: vec![input(vec![3; 100])], ..tx.clone() };
+        assert_eq!(signed.base_size(), tx.base_size());
+        assert_eq!(signed.weight(), tx.weight() + 100);
+        
+        // Any other byte adds four
+        let bigger = Transaction { outputs: vec![output(vec![2; 120])], ..tx.clone() };
+        assert_eq!(bigger.weight(), tx.weight() + 100 * WITNESS_SCALE_FACTOR);
+    }
+    
+    #[test]
+    fn test_fee_derived_from_utxo_values() {
+        let tx = Transaction {
+            inputs: vec![
+                TransactionInput { prev_tx_hash: [1; 32], output_index: 0, signature: vec![] },
+                TransactionInput { prev_tx_hash: [1; 32], output_index: 1, signature: vec![] },
+            ],
+            outputs: vec![TransactionOutput { amount: 7000, recipient: vec![2; 20] }],
+            timestamp: 1640995200,
+        };
+        let utxos = |hash: &[u8; 32], index: u32| (*hash == [1; 32]).then_some(4000 + u64::from(index) * 1000);
+        
+        assert_eq!(tx.total_input_amount(utxos).unwrap(), 9000);
+        assert_eq!(tx.fee(utxos).unwrap(), 2000);
+        assert!(matches!(tx.fee(|_: &[u8; 32], _| None), Err(TransactionError::UnknownInput(_, 0))));
+        assert!(matches!(tx.fee(|_: &[u8; 32], _| Some(1000)), Err(TransactionError::InsufficientFunds)));
+    }
+    
+    #[test]
+    fn test_forged_fee_field_ignored() {
+        let json = serde_json::json!({
+            "inputs": [{ "prev_tx_hash": vec![1u8; 32], "output_index": 0, "signature": [] }],
+            "outputs": [{ "amount": 9000, "recipient": [2, 2] }],
+            "fee": 1_000_000,
+            "timestamp": 1640995200u64,
+        });
+        let tx: Transaction = serde_json::from_value(json).unwrap();
+        
+        assert_eq!(tx.fee(|_: &[u8; 32], _| Some(10_000)).unwrap(), 1000);
+        assert!(serde_json::to_value(&tx).unwrap().get("fee").is_none());
+    }
 }
@@ -1,6 +1,7 @@
 // QuantumCoin Validation Rules - Bitcoin-level Rigor
 
 use crate::{Block, BlockHeader, Tx, Chain, Hash};
+use qc_types::Hash32;
 use anyhow::{Result, anyhow};
 use sha2::{Digest, Sha256};
 use std::collections::HashSet;
@@ -46,7 +47,7 @@ impl Validator {
     
     fn validate_block_header(&self, header: &BlockHeader) -> Result<()> {
         // Header format validation
-        if header.number == 0 && header.parent != "0x00" {
+        if header.number == 0 && header.parent != Hash32::zero() {
             return Err(anyhow!("Genesis block must have null parent"));
         }
         
@@ -167,8 +168,8 @@ impl Validator {
     fn calculate_block_hash(&self, header: &BlockHeader) -> [u8; 32] {
         let mut hasher = Sha256::new();
         hasher.update(&1u32.to_le_bytes()); // version
-        hasher.update(&hex::decode(header.parent.trim_start_matches("0x")).unwrap_or_default());
-        hasher.update(&hex::decode(header.merkle_root.trim_start_matches("0x")).unwrap_or_default());
+        hasher.update(header.parent.0);
+        hasher.update(header.merkle_root.0);
         hasher.update(&header.timestamp.to_le_bytes());
         hasher.update(&header.difficulty.to_le_bytes());
         hasher.update(&header.nonce.to_le_bytes());
@@ -185,9 +186,9 @@ impl Validator {
         Ok(())
     }
     
-    fn calculate_merkle_root(&self, transactions: &[Tx]) -> String {
+    fn calculate_merkle_root(&self, transactions: &[Tx]) -> Hash32 {
         if transactions.is_empty() {
-            return format!("0x{}", hex::encode([0u8; 32]));
+            return Hash32::zero();
         }
         
         let mut level: Vec<[u8; 32]> = transactions.iter().map(|tx| {
@@ -213,7 +214,7 @@ impl Validator {
             level = next_level;
         }
         
-        format!("0x{}", hex::encode(level[0]))
+        Hash32(level[0])
     }
     
     /// Validate complete transaction
@@ -303,7 +304,7 @@ mod tests {
         let mut validator = Validator::new(chain.clone());
         
         // Test genesis block validation
-        let genesis = chain.head();
+        let genesis = chain.head().unwrap();
         assert!(validator.validate_block(&genesis).is_ok());
     }
     
@@ -356,7 +356,6 @@ async fn test_transaction_validation_integration() {
             amount: 1000000,
             recipient: vec![0u8; 20],
         }],
-        fee: 1000,
         timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
     };
     
@@ -371,7 +370,6 @@ async fn test_transaction_validation_integration() {
             amount: 1000000,
             recipient: vec![0u8; 20],
         }],
-        fee: 1000,
         timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
     };
     
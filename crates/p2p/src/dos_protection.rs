@@ -1,10 +1,15 @@
 //! DoS protection implementation
 
 use crate::{P2PError, Result, GossipMessage};
-use std::collections::HashMap;
-use std::net::SocketAddr;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
 use tokio::sync::RwLock;
 use std::time::{SystemTime, Duration};
+use tracing::{debug, info, warn};
+
+/// Window over which per-peer and per-subnet message rates are measured
+const RATE_WINDOW: Duration = Duration::from_secs(60);
 
 #[derive(Debug, Clone)]
 pub struct PeerScore {
@@ -12,32 +17,443 @@ pub struct PeerScore {
     pub last_updated: SystemTime,
 }
 
-#[derive(Debug, Clone)]
+/// Operating posture of the node, switchable at runtime (e.g. by the AI sentinel)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SecurityLevel {
-    Low,
-    Medium,
-    High,
-    Critical,
+    Relaxed,
+    Normal,
+    Strict,
+    /// Only whitelisted peers may connect
+    Lockdown,
+}
+
+/// Limits applied at a given security level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SecurityPolicy {
+    pub ban_threshold: i32,
+    pub messages_per_minute: usize,
+    /// Aggregate limit for all peers sharing a /24 (IPv4) or /48 (IPv6)
+    pub subnet_messages_per_minute: usize,
+    pub max_connections: usize,
+    pub whitelist_only: bool,
+}
+
+impl SecurityLevel {
+    pub fn policy(&self) -> SecurityPolicy {
+        match self {
+            SecurityLevel::Relaxed => SecurityPolicy {
+                ban_threshold: 200,
+                messages_per_minute: 600,
+                subnet_messages_per_minute: 2000,
+                max_connections: 250,
+                whitelist_only: false,
+            },
+            SecurityLevel::Normal => SecurityPolicy {
+                ban_threshold: 100,
+                messages_per_minute: 300,
+                subnet_messages_per_minute: 1000,
+                max_connections: 125,
+                whitelist_only: false,
+            },
+            SecurityLevel::Strict => SecurityPolicy {
+                ban_threshold: 50,
+                messages_per_minute: 120,
+                subnet_messages_per_minute: 400,
+                max_connections: 64,
+                whitelist_only: false,
+            },
+            SecurityLevel::Lockdown => SecurityPolicy {
+                ban_threshold: 25,
+                messages_per_minute: 60,
+                subnet_messages_per_minute: 120,
+                max_connections: 16,
+                whitelist_only: true,
+            },
+        }
+    }
+}
+
+/// Trusted address or network exempt from banning and rate limits
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WhitelistEntry {
+    Ip(IpAddr),
+    Subnet { network: IpAddr, prefix_len: u8 },
+}
+
+impl WhitelistEntry {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match *self {
+            WhitelistEntry::Ip(allowed) => allowed == ip,
+            WhitelistEntry::Subnet { network, prefix_len } => match (network, ip) {
+                (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                    let mask = u32::MAX.checked_shl(32 - prefix_len.min(32) as u32).unwrap_or(0);
+                    u32::from(net) & mask == u32::from(ip) & mask
+                }
+                (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                    let mask = u128::MAX.checked_shl(128 - prefix_len.min(128) as u32).unwrap_or(0);
+                    u128::from(net) & mask == u128::from(ip) & mask
+                }
+                _ => false,
+            },
+        }
+    }
+}
+
+impl From<IpAddr> for WhitelistEntry {
+    fn from(ip: IpAddr) -> Self {
+        WhitelistEntry::Ip(ip)
+    }
+}
+
+/// Parses `"1.2.3.4"` or CIDR notation such as `"10.0.0.0/8"`
+impl FromStr for WhitelistEntry {
+    type Err = P2PError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || P2PError::InvalidFormat(format!("invalid whitelist entry: {}", s));
+        match s.split_once('/') {
+            None => s.parse().map(WhitelistEntry::Ip).map_err(|_| invalid()),
+            Some((network, prefix)) => {
+                let network: IpAddr = network.parse().map_err(|_| invalid())?;
+                let prefix_len: u8 = prefix.parse().map_err(|_| invalid())?;
+                let max = if network.is_ipv4() { 32 } else { 128 };
+                if prefix_len > max {
+                    return Err(invalid());
+                }
+                Ok(WhitelistEntry::Subnet { network, prefix_len })
+            }
+        }
+    }
 }
 
 pub struct DosProtection {
     peer_scores: RwLock<HashMap<SocketAddr, PeerScore>>,
+    level: RwLock<SecurityLevel>,
+    connections: RwLock<HashSet<SocketAddr>>,
+    message_times: RwLock<HashMap<SocketAddr, VecDeque<SystemTime>>>,
+    subnet_message_times: RwLock<HashMap<IpAddr, VecDeque<SystemTime>>>,
+    whitelist: RwLock<Vec<WhitelistEntry>>,
 }
 
 impl DosProtection {
     pub fn new() -> Self {
+        Self::with_level(SecurityLevel::Normal)
+    }
+
+    pub fn with_level(level: SecurityLevel) -> Self {
         Self {
             peer_scores: RwLock::new(HashMap::new()),
+            level: RwLock::new(level),
+            connections: RwLock::new(HashSet::new()),
+            message_times: RwLock::new(HashMap::new()),
+            subnet_message_times: RwLock::new(HashMap::new()),
+            whitelist: RwLock::new(Vec::new()),
         }
     }
 
+    pub fn with_whitelist(mut self, entries: impl IntoIterator<Item = WhitelistEntry>) -> Self {
+        self.whitelist.get_mut().extend(entries);
+        self
+    }
+
+    pub async fn security_level(&self) -> SecurityLevel {
+        *self.level.read().await
+    }
+
+    pub async fn set_security_level(&self, level: SecurityLevel) {
+        let mut current = self.level.write().await;
+        if *current != level {
+            info!("DoS protection security level changed: {:?} -> {:?}", *current, level);
+            *current = level;
+        }
+    }
+
+    pub async fn policy(&self) -> SecurityPolicy {
+        self.security_level().await.policy()
+    }
+
+    pub async fn add_to_whitelist(&self, entry: impl Into<WhitelistEntry>) {
+        let entry = entry.into();
+        let mut whitelist = self.whitelist.write().await;
+        if !whitelist.contains(&entry) {
+            whitelist.push(entry);
+        }
+    }
+
+    pub async fn is_whitelisted(&self, peer: SocketAddr) -> bool {
+        self.whitelist.read().await.iter().any(|entry| entry.contains(peer.ip()))
+    }
+
+    /// Add misbehavior points to a peer, returning whether it is now banned
+    pub async fn record_misbehavior(&self, peer: SocketAddr, points: i32) -> bool {
+        {
+            let mut scores = self.peer_scores.write().await;
+            let entry = scores.entry(peer).or_insert(PeerScore {
+                score: 0,
+                last_updated: SystemTime::now(),
+            });
+            entry.score = entry.score.saturating_add(points);
+            entry.last_updated = SystemTime::now();
+        }
+        if self.is_whitelisted(peer).await {
+            // Scores are still tracked so operators can see what trusted peers are doing
+            info!("Whitelisted peer {} misbehaved (+{}), score now {}", peer, points, self.peer_score(peer).await);
+        }
+        self.is_banned(peer).await
+    }
+
+    pub async fn peer_score(&self, peer: SocketAddr) -> i32 {
+        self.peer_scores.read().await.get(&peer).map(|s| s.score).unwrap_or(0)
+    }
+
+    /// Banned peers are those whose score meets the current level's threshold.
+    /// Whitelisted peers are never banned.
+    pub async fn is_banned(&self, peer: SocketAddr) -> bool {
+        if self.is_whitelisted(peer).await {
+            return false;
+        }
+        let threshold = self.policy().await.ban_threshold;
+        self.peer_score(peer).await >= threshold
+    }
+
+    /// Admit an inbound connection if the current policy allows it
+    pub async fn accept_connection(&self, peer: SocketAddr) -> Result<()> {
+        let policy = self.policy().await;
+
+        if policy.whitelist_only && !self.is_whitelisted(peer).await {
+            return Err(P2PError::DosProtection(format!("lockdown: {} is not whitelisted", peer)));
+        }
+        if self.is_banned(peer).await {
+            return Err(P2PError::PeerBanned { peer });
+        }
+
+        let mut connections = self.connections.write().await;
+        if !connections.contains(&peer) && connections.len() >= policy.max_connections {
+            return Err(P2PError::DosProtection("connection limit reached".to_string()));
+        }
+        connections.insert(peer);
+        Ok(())
+    }
+
+    pub async fn remove_connection(&self, peer: SocketAddr) {
+        self.connections.write().await.remove(&peer);
+        self.message_times.write().await.remove(&peer);
+    }
+
+    pub async fn connection_count(&self) -> usize {
+        self.connections.read().await.len()
+    }
+
     pub async fn check_message_rate(&self, message: &GossipMessage) -> Result<()> {
-        // Rate limiting logic
+        let Some(peer) = message.source_peer else {
+            return Ok(()); // locally originated
+        };
+        if self.is_whitelisted(peer).await {
+            debug!("Skipping rate limit for whitelisted peer {}", peer);
+            return Ok(());
+        }
+
+        let policy = self.policy().await;
+        let now = SystemTime::now();
+        let subnet = subnet_of(peer.ip());
+
+        // Check the shared subnet budget first so an attacker can't multiply its quota across addresses
+        let mut subnet_times = self.subnet_message_times.write().await;
+        let subnet_window = subnet_times.entry(subnet).or_default();
+        prune_window(subnet_window, now);
+        if subnet_window.len() >= policy.subnet_messages_per_minute {
+            warn!("Subnet {} throttled (triggered by {})", subnet, peer);
+            return Err(P2PError::DosProtection(format!("subnet {} rate limit exceeded", subnet)));
+        }
+
+        let mut times = self.message_times.write().await;
+        let window = times.entry(peer).or_default();
+        prune_window(window, now);
+        if window.len() >= policy.messages_per_minute {
+            warn!("Message rate limit exceeded by {}", peer);
+            return Err(P2PError::DosProtection(format!("message rate limit exceeded by {}", peer)));
+        }
+
+        window.push_back(now);
+        subnet_window.push_back(now);
         Ok(())
     }
 
-    pub async fn check_peer_behavior(&self, peer: SocketAddr, message: &GossipMessage) -> Result<()> {
-        // Peer behavior checking
+    /// Whether the peer's subnet has used up its aggregate message budget
+    pub async fn is_subnet_throttled(&self, peer: SocketAddr) -> bool {
+        let limit = self.policy().await.subnet_messages_per_minute;
+        let mut subnet_times = self.subnet_message_times.write().await;
+        match subnet_times.get_mut(&subnet_of(peer.ip())) {
+            Some(window) => {
+                prune_window(window, SystemTime::now());
+                window.len() >= limit
+            }
+            None => false,
+        }
+    }
+
+    pub async fn check_peer_behavior(&self, peer: SocketAddr, _message: &GossipMessage) -> Result<()> {
+        if self.is_banned(peer).await {
+            return Err(P2PError::PeerBanned { peer });
+        }
         Ok(())
     }
 }
+
+/// Network prefix used for aggregate rate limiting: /24 for IPv4, /48 for IPv6
+pub fn subnet_of(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V4(v4) => {
+            let o = v4.octets();
+            IpAddr::from([o[0], o[1], o[2], 0])
+        }
+        IpAddr::V6(v6) => {
+            let s = v6.segments();
+            IpAddr::from([s[0], s[1], s[2], 0, 0, 0, 0, 0])
+        }
+    }
+}
+
+fn prune_window(window: &mut VecDeque<SystemTime>, now: SystemTime) {
+    while let Some(&oldest) = window.front() {
+        if now.duration_since(oldest).unwrap_or_default() > RATE_WINDOW {
+            window.pop_front();
+        } else {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MessagePriority, MessageType};
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    fn message_from(peer: SocketAddr, n: usize) -> GossipMessage {
+        let payload = format!("{}-{}", peer, n).into_bytes();
+        GossipMessage::new(MessageType::Transaction, payload, Some(peer), MessagePriority::Normal)
+    }
+
+    /// Send `per_peer` messages from each peer, returning how many were rejected
+    async fn flood(dos: &DosProtection, peers: &[SocketAddr], per_peer: usize) -> usize {
+        let mut rejected = 0;
+        for n in 0..per_peer {
+            for peer in peers {
+                if dos.check_message_rate(&message_from(*peer, n)).await.is_err() {
+                    rejected += 1;
+                }
+            }
+        }
+        rejected
+    }
+
+    #[tokio::test]
+    async fn test_strict_lowers_ban_threshold() {
+        let dos = DosProtection::new();
+        let peer = addr("10.0.0.1:8333");
+
+        assert!(!dos.record_misbehavior(peer, 60).await);
+
+        dos.set_security_level(SecurityLevel::Strict).await;
+        assert!(SecurityLevel::Strict.policy().ban_threshold < SecurityLevel::Normal.policy().ban_threshold);
+        assert!(dos.is_banned(peer).await);
+
+        dos.set_security_level(SecurityLevel::Normal).await;
+        assert!(!dos.is_banned(peer).await);
+    }
+
+    #[tokio::test]
+    async fn test_lockdown_refuses_non_whitelisted() {
+        let dos = DosProtection::new();
+        let trusted = addr("192.168.1.10:8333");
+        let stranger = addr("203.0.113.5:8333");
+        dos.add_to_whitelist(trusted.ip()).await;
+
+        assert!(dos.accept_connection(stranger).await.is_ok());
+        dos.remove_connection(stranger).await;
+
+        dos.set_security_level(SecurityLevel::Lockdown).await;
+        assert!(matches!(dos.accept_connection(stranger).await, Err(P2PError::DosProtection(_))));
+        assert!(dos.accept_connection(trusted).await.is_ok());
+    }
+
+    #[test]
+    fn test_subnet_prefixes() {
+        assert_eq!(subnet_of("10.1.2.3".parse().unwrap()), "10.1.2.0".parse::<IpAddr>().unwrap());
+        assert_eq!(
+            subnet_of("2001:db8:abcd:12::1".parse().unwrap()),
+            "2001:db8:abcd::".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_single_subnet_trips_limit() {
+        let dos = DosProtection::new();
+        let policy = dos.policy().await;
+        let peers: Vec<_> = (1..=20).map(|i| addr(&format!("10.0.0.{}:8333", i))).collect();
+
+        // Each peer stays well under its own limit, but the /24 as a whole does not
+        let per_peer = policy.messages_per_minute / 5;
+        assert!(per_peer * peers.len() > policy.subnet_messages_per_minute);
+
+        let rejected = flood(&dos, &peers, per_peer).await;
+        assert_eq!(rejected, per_peer * peers.len() - policy.subnet_messages_per_minute);
+        assert!(dos.is_subnet_throttled(addr("10.0.0.200:8333")).await);
+        assert!(!dos.is_subnet_throttled(addr("10.0.1.1:8333")).await);
+    }
+
+    #[tokio::test]
+    async fn test_spread_subnets_not_throttled() {
+        let dos = DosProtection::new();
+        let policy = dos.policy().await;
+        let peers: Vec<_> = (1..=20).map(|i| addr(&format!("10.0.{}.1:8333", i))).collect();
+
+        let per_peer = policy.messages_per_minute / 5;
+        assert_eq!(flood(&dos, &peers, per_peer).await, 0);
+    }
+
+    #[test]
+    fn test_whitelist_entry_parsing() {
+        let subnet: WhitelistEntry = "10.8.0.0/16".parse().unwrap();
+        assert!(subnet.contains("10.8.200.1".parse().unwrap()));
+        assert!(!subnet.contains("10.9.0.1".parse().unwrap()));
+
+        let single: WhitelistEntry = "192.168.1.10".parse().unwrap();
+        assert!(single.contains("192.168.1.10".parse().unwrap()));
+        assert!(!single.contains("192.168.1.11".parse().unwrap()));
+
+        assert!("10.0.0.0/33".parse::<WhitelistEntry>().is_err());
+        assert!("not-an-ip".parse::<WhitelistEntry>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_whitelisted_peer_never_banned() {
+        let dos = DosProtection::new()
+            .with_whitelist(vec!["10.8.0.0/16".parse().unwrap()]);
+        let pool = addr("10.8.3.4:8333");
+        let other = addr("203.0.113.5:8333");
+        let threshold = dos.policy().await.ban_threshold;
+
+        assert!(!dos.record_misbehavior(pool, threshold * 3).await);
+        assert!(dos.record_misbehavior(other, threshold * 3).await);
+
+        // Behavior is still recorded for the trusted peer
+        assert_eq!(dos.peer_score(pool).await, threshold * 3);
+        assert!(dos.accept_connection(pool).await.is_ok());
+        assert!(matches!(dos.accept_connection(other).await, Err(P2PError::PeerBanned { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_whitelisted_peer_bypasses_rate_limit() {
+        let dos = DosProtection::new();
+        let pool = addr("10.8.3.4:8333");
+        dos.add_to_whitelist("10.8.0.0/16".parse::<WhitelistEntry>().unwrap()).await;
+
+        let limit = dos.policy().await.messages_per_minute;
+        assert_eq!(flood(&dos, &[pool], limit * 2).await, 0);
+    }
+}
@@ -3,6 +3,7 @@
 //! Provides efficient, secure message propagation with DoS protection
 
 use crate::{P2PError, Result, MessageId, NetworkMessage, MessageType, MessagePriority, GossipMessage};
+use crate::{NetworkHealth, PriorityMessageQueue};
 use std::{
     collections::HashMap,
     net::SocketAddr,
@@ -16,6 +17,7 @@ use tokio::{
 use tracing::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
 use lru::LruCache;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
 
 /// Maximum message propagation time-to-live
 const MAX_TTL: u8 = 32;
@@ -23,6 +25,9 @@ const MAX_TTL: u8 = 32;
 /// Maximum concurrent messages per peer
 const MAX_CONCURRENT_MESSAGES: usize = 100;
 
+/// Messages sent to each peer per processing tick
+const MESSAGES_PER_TICK: usize = 10;
+
 /// Message deduplication cache size
 const DEDUP_CACHE_SIZE: usize = 10000;
 
@@ -62,6 +67,10 @@ pub struct GossipProtocol {
     message_cache: Arc<Mutex<LruCache<MessageId, GossipMessage>>>,
     message_stats: Arc<RwLock<GossipStats>>,
     shutdown_tx: Option<mpsc::Sender<()>>,
+    /// Source of randomness for peer selection; seeded for reproducible tests
+    rng: Arc<Mutex<StdRng>>,
+    /// Outbound queue occupancy is recorded here after every processing tick
+    health: Arc<NetworkHealth>,
 }
 
 #[derive(Debug, Clone)]
@@ -73,11 +82,11 @@ struct PeerConnection {
     bytes_sent: u64,
     bytes_received: u64,
     is_healthy: bool,
-    outbound_queue: Arc<Mutex<Vec<String>>>, // Simplified for compilation
+    outbound_queue: Arc<Mutex<PriorityMessageQueue>>,
 }
 
-#[derive(Debug, Default)]
-struct GossipStats {
+#[derive(Debug, Clone, Default)]
+pub struct GossipStats {
     messages_sent: u64,
     messages_received: u64,
     messages_dropped: u64,
@@ -101,28 +110,44 @@ impl GossipProtocol {
             message_cache,
             message_stats: Arc::new(RwLock::new(GossipStats::default())),
             shutdown_tx: None,
+            rng: Arc::new(Mutex::new(StdRng::from_entropy())),
+            health: Arc::new(NetworkHealth::new()),
         }
     }
 
+    /// Draw peer selections from an RNG seeded with `seed`, so the same
+    /// seed and peers always give the same propagation order
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Arc::new(Mutex::new(StdRng::seed_from_u64(seed)));
+        self
+    }
+
+    /// Health metrics, including how full the outbound queues are at each priority
+    pub fn health(&self) -> &Arc<NetworkHealth> {
+        &self.health
+    }
+
     /// Start the gossip protocol
     pub async fn start(&mut self) -> Result<()> {
         let (shutdown_tx, mut shutdown_rx) = mpsc::channel(1);
         self.shutdown_tx = Some(shutdown_tx);
 
         // Start background tasks
-        let tasks = vec![
-            self.start_message_processor(),
-            self.start_health_monitor(),
-            self.start_backpressure_controller(),
-            self.start_network_monitor(),
-        ];
+        let tasks = async {
+            tokio::try_join!(
+                self.start_message_processor(),
+                self.start_health_monitor(),
+                self.start_backpressure_controller(),
+                self.start_network_monitor(),
+            )
+        };
 
         // Wait for shutdown signal or task completion
         tokio::select! {
             _ = shutdown_rx.recv() => {
                 info!("Gossip protocol shutting down");
             }
-            result = futures::future::try_join_all(tasks) => {
+            result = tasks => {
                 match result {
                     Ok(_) => info!("All gossip tasks completed"),
                     Err(e) => error!("Gossip task failed: {}", e),
@@ -161,7 +186,7 @@ impl GossipProtocol {
         debug!("Broadcasting message to {} peers", target_peers.len());
 
         // Queue message for each target peer
-        for peer_addr in target_peers {
+        for &peer_addr in &target_peers {
             self.send_to_peer(peer_addr, message.clone()).await?;
         }
 
@@ -258,7 +283,7 @@ impl GossipProtocol {
             bytes_sent: 0,
             bytes_received: 0,
             is_healthy: true,
-            outbound_queue: Arc::new(Mutex::new(Vec::new())),
+            outbound_queue: Arc::new(Mutex::new(PriorityMessageQueue::new())),
         };
 
         peers.insert(peer_addr, connection);
@@ -279,6 +304,23 @@ impl GossipProtocol {
         }
     }
 
+    /// Take every message currently queued for a peer, highest priority first
+    pub async fn drain_outbound(&self, peer_addr: SocketAddr) -> Vec<GossipMessage> {
+        let peers = self.peers.read().await;
+        match peers.get(&peer_addr) {
+            Some(peer) => {
+                let mut queue = peer.outbound_queue.lock().await;
+                std::iter::from_fn(|| queue.pop().map(|(message, _)| message)).collect()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    /// Whether this node has already seen a message
+    pub async fn has_seen(&self, id: &MessageId) -> bool {
+        self.message_cache.lock().await.contains(id)
+    }
+
     /// Get current network health status
     pub async fn get_health_status(&self) -> String {
         format!("Network health: {} peers", self.peers.read().await.len())
@@ -320,10 +362,9 @@ impl GossipProtocol {
             .max(1.0)
             .min(candidates.len() as f32) as usize;
 
-        // Randomize peer selection (simplified but working)
-        use rand::seq::SliceRandom;
-        let mut rng = rand::thread_rng();
-        candidates.shuffle(&mut rng);
+        // Randomize peer selection; sort first so HashMap order can't leak into a seeded run
+        candidates.sort();
+        candidates.shuffle(&mut *self.rng.lock().await);
 
         // Select top candidates
         candidates.truncate(target_count);
@@ -353,10 +394,11 @@ impl GossipProtocol {
                 return Err(P2PError::BackpressureLimit);
             }
 
-            // Add to peer's outbound queue (simplified)
+            // Add to peer's outbound queue
             {
                 let mut queue = peer.outbound_queue.lock().await;
-                queue.push(format!("message-{}", hex::encode(message.network_message.id.as_bytes())));
+                let priority = message.network_message.priority;
+                queue.push(message, priority);
             }
 
             debug!("Queued message for peer {}", peer_addr);
@@ -379,26 +421,33 @@ impl GossipProtocol {
         
         loop {
             interval.tick().await;
+            self.process_outbound().await;
+        }
+    }
+
+    /// Send up to `MESSAGES_PER_TICK` queued messages to each peer, highest
+    /// priority first, then record what is left in the health metrics
+    async fn process_outbound(&self) {
+        let peers = self.peers.read().await.clone();
+        let mut queues = Vec::with_capacity(peers.len());
+        
+        for (peer_addr, peer) in &peers {
+            let mut queue = peer.outbound_queue.lock().await;
             
-            // Process outbound message queues for all peers
-            let peers = self.peers.read().await.clone();
-            
-            for (peer_addr, peer) in peers {
-                let mut queue = peer.outbound_queue.lock().await;
-                
-                // Process up to 10 messages per peer per tick
-                for _ in 0..10 {
-                    if let Some(message_id) = queue.pop() {
-                        // Simulate actual network send here
-                        // In real implementation, this would use the network layer
-                        debug!("Processing message to peer {}: {}", 
-                               peer_addr, message_id);
-                    } else {
-                        break;
-                    }
+            for _ in 0..MESSAGES_PER_TICK {
+                if let Some((message, _)) = queue.pop() {
+                    // Simulate actual network send here
+                    // In real implementation, this would use the network layer
+                    debug!("Processing message to peer {}: {}", 
+                           peer_addr, hex::encode(message.network_message.id.as_bytes()));
+                } else {
+                    break;
                 }
             }
+            queues.push(queue);
         }
+        
+        self.health.update_queue_occupancy(queues.iter().map(|queue| &**queue)).await;
     }
 
     async fn start_health_monitor(&self) -> Result<()> {
@@ -502,6 +551,58 @@ mod tests {
         assert!(gossip.validate_message(&message).await.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_same_seed_same_peer_selection() {
+        let config = GossipConfig { propagation_factor: 0.3, ..GossipConfig::default() };
+        let message = GossipMessage::new(MessageType::Block, b"block".to_vec(), None, MessagePriority::High);
+
+        let mut runs = Vec::new();
+        for seed in [42, 42, 43] {
+            let gossip = GossipProtocol::new(config.clone()).with_seed(seed);
+            for port in 0..20 {
+                gossip.add_peer(SocketAddr::from(([10, 0, 0, 1], 8000 + port))).await.unwrap();
+            }
+            let mut selections = Vec::new();
+            for _ in 0..5 {
+                selections.push(gossip.select_propagation_peers(&message).await.unwrap());
+            }
+            runs.push(selections);
+        }
+
+        assert_eq!(runs[0], runs[1]);
+        assert_eq!(runs[0][0].len(), 6);
+        // Successive selections differ, and so does another seed
+        assert!(runs[0].windows(2).any(|pair| pair[0] != pair[1]));
+        assert_ne!(runs[0], runs[2]);
+    }
+
+    #[tokio::test]
+    async fn test_processing_records_queue_occupancy() {
+        let gossip = GossipProtocol::new(GossipConfig::default());
+        let peers: Vec<SocketAddr> = vec!["127.0.0.1:8333".parse().unwrap(), "127.0.0.1:8334".parse().unwrap()];
+        for &peer in &peers {
+            gossip.add_peer(peer).await.unwrap();
+        }
+        let message = |payload: Vec<u8>, priority| GossipMessage::new(MessageType::Transaction, payload, None, priority);
+
+        // The critical message is sent ahead of every low one queued before it
+        gossip.send_to_peer(peers[0], message(b"low".repeat(4), MessagePriority::Low)).await.unwrap();
+        for i in 0..MESSAGES_PER_TICK as u8 + 2 {
+            gossip.send_to_peer(peers[0], message(vec![i; 8], MessagePriority::Low)).await.unwrap();
+        }
+        gossip.send_to_peer(peers[0], message(b"block".to_vec(), MessagePriority::Critical)).await.unwrap();
+        gossip.send_to_peer(peers[1], message(b"high".to_vec(), MessagePriority::High)).await.unwrap();
+
+        gossip.process_outbound().await;
+        let metrics = gossip.health().get_current_metrics().await;
+        assert_eq!(metrics.queue_occupancy.get(&MessagePriority::Critical), None);
+        assert_eq!(metrics.queue_occupancy.get(&MessagePriority::High), None);
+        assert_eq!(metrics.queue_occupancy.get(&MessagePriority::Low), Some(&4));
+
+        gossip.process_outbound().await;
+        assert!(gossip.health().get_current_metrics().await.queue_occupancy.is_empty());
+    }
+
     #[tokio::test]
     async fn test_peer_management() {
         let config = GossipConfig::default();
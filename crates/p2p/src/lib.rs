@@ -13,13 +13,15 @@ pub mod message_propagation;
 pub mod peer_scoring;
 pub mod network_health;
 pub mod priority_queue;
+pub mod simulation;
 
 pub use gossip::{GossipProtocol};
-pub use dos_protection::{DosProtection, PeerScore, SecurityLevel};
-pub use message_propagation::{PropagationManager, PropagationStats};
+pub use dos_protection::{DosProtection, PeerScore, SecurityLevel, SecurityPolicy, WhitelistEntry};
+pub use message_propagation::{LatencyHistogram, PropagationManager, PropagationStats};
 pub use peer_scoring::{PeerScorer, ScoreReason, PeerBehavior};
 pub use network_health::{NetworkHealth, PartitionDetector, HealthMetrics};
 pub use priority_queue::{PriorityMessageQueue, MessageItem};
+pub use simulation::{SimNetwork, LinkConfig};
 
 use std::net::SocketAddr;
 use std::time::SystemTime;
@@ -88,15 +90,6 @@ pub enum MessagePriority {
     Critical = 3,
 }
 
-/// Gossip message with metadata
-#[derive(Debug, Clone)]
-pub struct GossipMessage {
-    pub network_message: NetworkMessage,
-    pub first_seen: SystemTime,
-    pub propagation_count: u32,
-    pub source_peer: Option<SocketAddr>,
-}
-
 /// Message types for the P2P network
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum MessageType {
@@ -116,6 +109,21 @@ pub struct GossipMessage {
     pub source_peer: Option<SocketAddr>,
 }
 
+// Messages are identified by content hash, so queues and caches key on the id
+impl PartialEq for GossipMessage {
+    fn eq(&self, other: &Self) -> bool {
+        self.network_message.id == other.network_message.id
+    }
+}
+
+impl Eq for GossipMessage {}
+
+impl std::hash::Hash for GossipMessage {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.network_message.id.hash(state);
+    }
+}
+
 impl GossipMessage {
     pub fn new(
         message_type: MessageType,
@@ -1,10 +1,25 @@
 //! Message propagation management
 
-use crate::GossipMessage;
+use crate::{GossipMessage, MessageId};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Write;
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime};
 use tokio::sync::RwLock;
 
+/// Upper bounds, in bytes, of the size classes block latencies are grouped
+/// by; larger blocks fall into a final open-ended class
+pub const BLOCK_SIZE_CLASSES: [usize; 4] = [10_000, 100_000, 1_000_000, 2_000_000];
+
+/// Upper bounds, in milliseconds, of the latency histogram buckets; slower
+/// acknowledgements fall into a final open-ended bucket
+pub const LATENCY_BUCKETS_MS: [u64; 7] = [100, 250, 500, 1_000, 2_500, 5_000, 10_000];
+
+/// Blocks whose first-seen time is kept for matching peer acknowledgements
+pub const MAX_TRACKED_BLOCKS: usize = 256;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum MessagePriority {
     Low = 0,
@@ -13,22 +28,70 @@ pub enum MessagePriority {
     Critical = 3,
 }
 
-#[derive(Debug, Clone, Default)]
+/// Index into `BLOCK_SIZE_CLASSES` of the class a block of `size` bytes
+/// belongs to, or its length for blocks above every bound
+pub fn size_class(size: usize) -> usize {
+    BLOCK_SIZE_CLASSES
+        .iter()
+        .position(|&bound| size <= bound)
+        .unwrap_or(BLOCK_SIZE_CLASSES.len())
+}
+
+/// Distribution of first-seen to acknowledgement latencies
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LatencyHistogram {
+    /// Samples per `LATENCY_BUCKETS_MS` bucket, not cumulative
+    pub buckets: [u64; LATENCY_BUCKETS_MS.len() + 1],
+    pub count: u64,
+    pub sum_ms: u64,
+}
+
+impl LatencyHistogram {
+    pub fn observe(&mut self, latency: Duration) {
+        let ms = latency.as_millis() as u64;
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|&bound| ms <= bound)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.sum_ms += ms;
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PropagationStats {
     pub total_messages: u64,
     pub successful_propagations: u64,
     pub failed_propagations: u64,
     pub avg_propagation_time_ms: f64,
+    /// Peer acknowledgement latencies, one histogram per block size class
+    pub block_latency: [LatencyHistogram; BLOCK_SIZE_CLASSES.len() + 1],
+}
+
+struct TrackedBlock {
+    size: usize,
+    first_seen: SystemTime,
+    acked_by: HashSet<SocketAddr>,
+}
+
+#[derive(Default)]
+struct TrackedBlocks {
+    blocks: HashMap<MessageId, TrackedBlock>,
+    /// `blocks` keys, oldest first
+    order: VecDeque<MessageId>,
 }
 
 pub struct PropagationManager {
     stats: Arc<RwLock<PropagationStats>>,
+    tracked: Arc<RwLock<TrackedBlocks>>,
 }
 
 impl PropagationManager {
     pub fn new() -> Self {
         Self {
             stats: Arc::new(RwLock::new(PropagationStats::default())),
+            tracked: Arc::new(RwLock::new(TrackedBlocks::default())),
         }
     }
 
@@ -37,4 +100,121 @@ impl PropagationManager {
         stats.total_messages += 1;
         stats.successful_propagations += peer_count as u64;
     }
+
+    /// Start timing a block's propagation. Only the first sighting counts.
+    pub async fn record_block_seen(&self, id: MessageId, size: usize, first_seen: SystemTime) {
+        let mut tracked = self.tracked.write().await;
+        if tracked.blocks.contains_key(&id) {
+            return;
+        }
+        if tracked.order.len() == MAX_TRACKED_BLOCKS {
+            if let Some(oldest) = tracked.order.pop_front() {
+                tracked.blocks.remove(&oldest);
+            }
+        }
+        tracked.order.push_back(id);
+        tracked.blocks.insert(id, TrackedBlock { size, first_seen, acked_by: HashSet::new() });
+    }
+
+    /// Record that `peer` has the block, bucketing the time since it was
+    /// first seen by the block's size class. Returns false for untracked
+    /// blocks and repeat acknowledgements, which are not counted.
+    pub async fn record_block_ack(&self, id: &MessageId, peer: SocketAddr, acked_at: SystemTime) -> bool {
+        let (size, latency) = {
+            let mut tracked = self.tracked.write().await;
+            let Some(block) = tracked.blocks.get_mut(id) else { return false };
+            if !block.acked_by.insert(peer) {
+                return false;
+            }
+            (block.size, acked_at.duration_since(block.first_seen).unwrap_or_default())
+        };
+
+        self.stats.write().await.block_latency[size_class(size)].observe(latency);
+        true
+    }
+
+    pub async fn stats(&self) -> PropagationStats {
+        self.stats.read().await.clone()
+    }
+
+    /// Block latency histograms in Prometheus text format, labelled by the
+    /// upper bound of each size class
+    pub async fn export_prometheus(&self) -> String {
+        let stats = self.stats.read().await;
+        let mut out = String::from(
+            "# HELP quantumcoin_block_propagation_ms Time from first seeing a block to a peer acknowledging it\n\
+             # TYPE quantumcoin_block_propagation_ms histogram\n",
+        );
+
+        for (class, histogram) in stats.block_latency.iter().enumerate() {
+            let size = BLOCK_SIZE_CLASSES
+                .get(class)
+                .map_or_else(|| "+Inf".to_string(), |bound| bound.to_string());
+            let mut cumulative = 0;
+            for (bucket, samples) in histogram.buckets.iter().enumerate() {
+                cumulative += samples;
+                let le = LATENCY_BUCKETS_MS
+                    .get(bucket)
+                    .map_or_else(|| "+Inf".to_string(), |bound| bound.to_string());
+                let _ = writeln!(
+                    out,
+                    "quantumcoin_block_propagation_ms_bucket{{size=\"{}\",le=\"{}\"}} {}",
+                    size, le, cumulative
+                );
+            }
+            let _ = writeln!(out, "quantumcoin_block_propagation_ms_sum{{size=\"{}\"}} {}", size, histogram.sum_ms);
+            let _ = writeln!(out, "quantumcoin_block_propagation_ms_count{{size=\"{}\"}} {}", size, histogram.count);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[tokio::test]
+    async fn test_latencies_bucketed_by_size_class() {
+        let manager = PropagationManager::new();
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let small = MessageId::new(b"small block");
+        let large = MessageId::new(b"large block");
+        manager.record_block_seen(small, 5_000, start).await;
+        manager.record_block_seen(large, 1_500_000, start).await;
+
+        let after = |ms| start + Duration::from_millis(ms);
+        assert!(manager.record_block_ack(&small, peer(1), after(80)).await);
+        assert!(manager.record_block_ack(&small, peer(2), after(300)).await);
+        assert!(manager.record_block_ack(&large, peer(1), after(4_000)).await);
+        assert!(manager.record_block_ack(&large, peer(2), after(20_000)).await);
+        // Repeat acknowledgements and unknown blocks are ignored
+        assert!(!manager.record_block_ack(&small, peer(1), after(900)).await);
+        assert!(!manager.record_block_ack(&MessageId::new(b"unseen"), peer(1), after(50)).await);
+
+        let stats = manager.stats().await;
+        let small_hist = &stats.block_latency[size_class(5_000)];
+        assert_eq!(size_class(5_000), 0);
+        assert_eq!(small_hist.count, 2);
+        assert_eq!(small_hist.buckets[0], 1);
+        assert_eq!(small_hist.buckets[2], 1);
+
+        let large_hist = &stats.block_latency[size_class(1_500_000)];
+        assert_eq!(size_class(1_500_000), 3);
+        assert_eq!(large_hist.count, 2);
+        assert_eq!(large_hist.buckets[5], 1);
+        assert_eq!(large_hist.buckets[LATENCY_BUCKETS_MS.len()], 1);
+        assert_eq!(large_hist.sum_ms, 24_000);
+
+        let untouched = [1, 2, BLOCK_SIZE_CLASSES.len()];
+        assert!(untouched.iter().all(|&class| stats.block_latency[class].count == 0));
+
+        let text = manager.export_prometheus().await;
+        assert!(text.contains("quantumcoin_block_propagation_ms_bucket{size=\"10000\",le=\"250\"} 1"));
+        assert!(text.contains("quantumcoin_block_propagation_ms_bucket{size=\"10000\",le=\"500\"} 2"));
+        assert!(text.contains("quantumcoin_block_propagation_ms_count{size=\"2000000\"} 2"));
+    }
 }
@@ -1,6 +1,8 @@
 //! Network health monitoring
 
+use crate::{MessagePriority, PriorityMessageQueue};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use tokio::sync::RwLock;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -9,6 +11,8 @@ pub struct HealthMetrics {
     pub message_rate: u64,
     pub network_score: f64,
     pub is_healthy: bool,
+    pub queue_occupancy: BTreeMap<MessagePriority, usize>,
+    pub queue_bytes: BTreeMap<MessagePriority, usize>,
 }
 
 pub struct NetworkHealth {
@@ -23,6 +27,8 @@ impl NetworkHealth {
                 message_rate: 0,
                 network_score: 1.0,
                 is_healthy: true,
+                queue_occupancy: BTreeMap::new(),
+                queue_bytes: BTreeMap::new(),
             }),
         }
     }
@@ -39,6 +45,25 @@ impl NetworkHealth {
         metrics.network_score = if metrics.is_healthy { 1.0 } else { 0.5 };
     }
 
+    /// Snapshot per-priority depth summed over `queues`, so starvation
+    /// shows up in metrics
+    pub async fn update_queue_occupancy<'a>(&self, queues: impl IntoIterator<Item = &'a PriorityMessageQueue>) {
+        let mut occupancy = BTreeMap::new();
+        let mut bytes = BTreeMap::new();
+        for queue in queues {
+            for (priority, count) in queue.occupancy() {
+                *occupancy.entry(priority).or_insert(0) += count;
+            }
+            for (priority, size) in queue.occupancy_bytes() {
+                *bytes.entry(priority).or_insert(0) += size;
+            }
+        }
+
+        let mut metrics = self.metrics.write().await;
+        metrics.queue_occupancy = occupancy;
+        metrics.queue_bytes = bytes;
+    }
+
     pub async fn detect_partition(&self) -> bool {
         let metrics = self.metrics.read().await;
         metrics.peer_count < 5
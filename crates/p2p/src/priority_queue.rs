@@ -2,6 +2,7 @@
 
 use crate::{GossipMessage, MessagePriority};
 use priority_queue::PriorityQueue;
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone)]
 pub struct MessageItem {
@@ -9,6 +10,7 @@ pub struct MessageItem {
     pub priority: MessagePriority,
 }
 
+#[derive(Debug)]
 pub struct PriorityMessageQueue {
     queue: PriorityQueue<GossipMessage, MessagePriority>,
 }
@@ -35,4 +37,58 @@ impl PriorityMessageQueue {
     pub fn is_empty(&self) -> bool {
         self.queue.is_empty()
     }
+
+    /// Number of queued messages at each priority level
+    pub fn occupancy(&self) -> BTreeMap<MessagePriority, usize> {
+        let mut levels = BTreeMap::new();
+        for (_, priority) in self.queue.iter() {
+            *levels.entry(*priority).or_insert(0) += 1;
+        }
+        levels
+    }
+
+    /// Total payload bytes queued at each priority level
+    pub fn occupancy_bytes(&self) -> BTreeMap<MessagePriority, usize> {
+        let mut levels = BTreeMap::new();
+        for (message, priority) in self.queue.iter() {
+            *levels.entry(*priority).or_insert(0) += message.network_message.payload.len();
+        }
+        levels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MessageType;
+
+    fn message(payload: &[u8]) -> GossipMessage {
+        GossipMessage::new(MessageType::Transaction, payload.to_vec(), None, MessagePriority::Normal)
+    }
+
+    #[test]
+    fn test_occupancy_by_level() {
+        let mut queue = PriorityMessageQueue::new();
+        queue.push(message(b"critical"), MessagePriority::Critical);
+        queue.push(message(b"high-1"), MessagePriority::High);
+        queue.push(message(b"high-22"), MessagePriority::High);
+        queue.push(message(b"low-a"), MessagePriority::Low);
+        queue.push(message(b"low-bb"), MessagePriority::Low);
+        queue.push(message(b"low-ccc"), MessagePriority::Low);
+
+        let occupancy = queue.occupancy();
+        assert_eq!(occupancy.get(&MessagePriority::Critical), Some(&1));
+        assert_eq!(occupancy.get(&MessagePriority::High), Some(&2));
+        assert_eq!(occupancy.get(&MessagePriority::Normal), None);
+        assert_eq!(occupancy.get(&MessagePriority::Low), Some(&3));
+
+        let bytes = queue.occupancy_bytes();
+        assert_eq!(bytes.get(&MessagePriority::Critical), Some(&8));
+        assert_eq!(bytes.get(&MessagePriority::High), Some(&13));
+        assert_eq!(bytes.get(&MessagePriority::Low), Some(&18));
+
+        // Draining the highest level updates the map
+        queue.pop();
+        assert_eq!(queue.occupancy().get(&MessagePriority::Critical), None);
+    }
 }
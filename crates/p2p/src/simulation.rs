@@ -0,0 +1,288 @@
+//! Deterministic in-memory network for multi-node gossip testing
+//!
+//! Wires several `GossipProtocol` instances together without sockets.
+//! Time advances in discrete rounds; every link has its own latency (in
+//! rounds) and drop rate. Drops and each node's peer selection are drawn
+//! from RNGs seeded from one seed, so a given seed always replays the same run.
+
+use crate::gossip::{GossipConfig, GossipProtocol};
+use crate::{GossipMessage, MessageId, Result};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use tracing::debug;
+
+/// Behaviour of a simulated link between two nodes
+#[derive(Debug, Clone, Copy)]
+pub struct LinkConfig {
+    /// Rounds a message spends in flight (minimum 1)
+    pub latency_rounds: u64,
+    /// Probability in `[0, 1]` that a message on this link is lost
+    pub drop_rate: f64,
+}
+
+impl Default for LinkConfig {
+    fn default() -> Self {
+        Self {
+            latency_rounds: 1,
+            drop_rate: 0.0,
+        }
+    }
+}
+
+struct InFlight {
+    from: usize,
+    to: usize,
+    deliver_at: u64,
+    message: GossipMessage,
+}
+
+/// A set of gossip nodes connected by simulated links
+pub struct SimNetwork {
+    nodes: Vec<GossipProtocol>,
+    addrs: Vec<SocketAddr>,
+    links: HashMap<(usize, usize), LinkConfig>,
+    in_flight: Vec<InFlight>,
+    round: u64,
+    rng: StdRng,
+}
+
+impl SimNetwork {
+    /// Create `n` unconnected nodes
+    pub fn new(n: usize, config: GossipConfig, seed: u64) -> Self {
+        let addrs = (0..n)
+            .map(|i| SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, (i / 256) as u8, (i % 256) as u8)), 8333))
+            .collect();
+        // Node peer selection is seeded from the run's RNG too
+        let mut rng = StdRng::seed_from_u64(seed);
+        let nodes = (0..n).map(|_| GossipProtocol::new(config.clone()).with_seed(rng.gen())).collect();
+
+        Self {
+            nodes,
+            addrs,
+            links: HashMap::new(),
+            in_flight: Vec::new(),
+            round: 0,
+            rng,
+        }
+    }
+
+    /// Create `n` nodes with every pair connected by `link`
+    pub async fn fully_connected(n: usize, config: GossipConfig, link: LinkConfig, seed: u64) -> Result<Self> {
+        let mut sim = Self::new(n, config, seed);
+        for a in 0..n {
+            for b in (a + 1)..n {
+                sim.connect(a, b, link).await?;
+            }
+        }
+        Ok(sim)
+    }
+
+    /// Connect two nodes in both directions
+    pub async fn connect(&mut self, a: usize, b: usize, link: LinkConfig) -> Result<()> {
+        self.nodes[a].add_peer(self.addrs[b]).await?;
+        self.nodes[b].add_peer(self.addrs[a]).await?;
+        self.links.insert((a, b), link);
+        self.links.insert((b, a), link);
+        Ok(())
+    }
+
+    /// Override the behaviour of one direction of an existing link
+    pub fn set_link(&mut self, from: usize, to: usize, link: LinkConfig) {
+        self.links.insert((from, to), link);
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    pub fn node(&self, index: usize) -> &GossipProtocol {
+        &self.nodes[index]
+    }
+
+    pub fn addr(&self, index: usize) -> SocketAddr {
+        self.addrs[index]
+    }
+
+    /// Originate a message at one node
+    pub async fn inject(&mut self, node: usize, message: GossipMessage) -> Result<()> {
+        self.nodes[node].broadcast(message).await
+    }
+
+    /// Advance one round: send everything queued, then deliver what is due
+    pub async fn step(&mut self) -> Result<()> {
+        let mut links: Vec<_> = self.links.iter().map(|(&k, &v)| (k, v)).collect();
+        links.sort_by_key(|(k, _)| *k); // HashMap order would make drops irreproducible
+
+        for ((from, to), link) in links {
+            for message in self.nodes[from].drain_outbound(self.addrs[to]).await {
+                if link.drop_rate > 0.0 && self.rng.gen_bool(link.drop_rate.min(1.0)) {
+                    debug!("sim: dropped message {} -> {}", from, to);
+                    continue;
+                }
+                self.in_flight.push(InFlight {
+                    from,
+                    to,
+                    deliver_at: self.round + link.latency_rounds.max(1),
+                    message,
+                });
+            }
+        }
+
+        self.round += 1;
+
+        let (due, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.in_flight)
+            .into_iter()
+            .partition(|m| m.deliver_at <= self.round);
+        self.in_flight = pending;
+
+        for delivery in due {
+            let sender = self.addrs[delivery.from];
+            if let Err(e) = self.nodes[delivery.to].handle_incoming_message(sender, delivery.message).await {
+                debug!("sim: node {} rejected message from {}: {}", delivery.to, delivery.from, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of nodes that have seen a message
+    pub async fn coverage(&self, id: &MessageId) -> usize {
+        let mut seen = 0;
+        for node in &self.nodes {
+            if node.has_seen(id).await {
+                seen += 1;
+            }
+        }
+        seen
+    }
+
+    /// Step until every node has seen `id`, returning the round it happened in
+    pub async fn run_until_converged(&mut self, id: &MessageId, max_rounds: u64) -> Result<Option<u64>> {
+        while self.round < max_rounds {
+            if self.coverage(id).await == self.len() {
+                return Ok(Some(self.round));
+            }
+            self.step().await?;
+        }
+        Ok((self.coverage(id).await == self.len()).then_some(self.round))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MessagePriority, MessageType};
+
+    fn flooding_config() -> GossipConfig {
+        GossipConfig {
+            propagation_factor: 1.0,
+            ..GossipConfig::default()
+        }
+    }
+
+    fn block(payload: &[u8]) -> GossipMessage {
+        GossipMessage::new(MessageType::Block, payload.to_vec(), None, MessagePriority::High)
+    }
+
+    /// Ring where each node also links two hops away
+    async fn ring(n: usize, link: LinkConfig, seed: u64) -> SimNetwork {
+        let mut sim = SimNetwork::new(n, flooding_config(), seed);
+        for i in 0..n {
+            sim.connect(i, (i + 1) % n, link).await.unwrap();
+            sim.connect(i, (i + 2) % n, link).await.unwrap();
+        }
+        sim
+    }
+
+    #[tokio::test]
+    async fn test_ten_nodes_converge_on_block() {
+        let mut sim = ring(10, LinkConfig::default(), 7).await;
+        let message = block(b"block at height 1");
+        let id = message.network_message.id;
+
+        sim.inject(0, message).await.unwrap();
+        let converged = sim.run_until_converged(&id, 10).await.unwrap();
+
+        // Farthest node is 5 hops around the ring, covered 2 hops per round
+        assert_eq!(converged, Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_latency_delays_convergence() {
+        let link = LinkConfig { latency_rounds: 2, drop_rate: 0.0 };
+        let mut sim = ring(10, link, 7).await;
+        let message = block(b"slow block");
+        let id = message.network_message.id;
+
+        sim.inject(0, message).await.unwrap();
+        assert_eq!(sim.run_until_converged(&id, 20).await.unwrap(), Some(6));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_links_isolate_node() {
+        let mut sim = SimNetwork::fully_connected(10, flooding_config(), LinkConfig::default(), 7)
+            .await
+            .unwrap();
+        let lossy = LinkConfig { latency_rounds: 1, drop_rate: 1.0 };
+        for i in 0..9 {
+            sim.set_link(i, 9, lossy);
+        }
+
+        let message = block(b"unreachable");
+        let id = message.network_message.id;
+        sim.inject(0, message).await.unwrap();
+
+        assert_eq!(sim.run_until_converged(&id, 10).await.unwrap(), None);
+        assert_eq!(sim.coverage(&id).await, 9);
+    }
+
+    #[tokio::test]
+    async fn test_same_seed_same_propagation_order() {
+        // Nodes forward to a random half of their peers, so coverage depends on selection
+        let config = GossipConfig { propagation_factor: 0.5, ..GossipConfig::default() };
+        let mut runs = Vec::new();
+        for seed in [99, 99] {
+            let mut sim = SimNetwork::fully_connected(12, config.clone(), LinkConfig::default(), seed)
+                .await
+                .unwrap();
+            let message = block(b"partially gossiped block");
+            let id = message.network_message.id;
+            sim.inject(0, message).await.unwrap();
+
+            let mut coverage = Vec::new();
+            for _ in 0..4 {
+                sim.step().await.unwrap();
+                let mut seen = Vec::new();
+                for i in 0..sim.len() {
+                    seen.push(sim.node(i).has_seen(&id).await);
+                }
+                coverage.push(seen);
+            }
+            runs.push(coverage);
+        }
+        assert_eq!(runs[0], runs[1]);
+    }
+
+    #[tokio::test]
+    async fn test_same_seed_same_outcome() {
+        let mut rounds = Vec::new();
+        for _ in 0..2 {
+            let link = LinkConfig { latency_rounds: 1, drop_rate: 0.3 };
+            let mut sim = ring(10, link, 1234).await;
+            let message = block(b"lossy block");
+            let id = message.network_message.id;
+            sim.inject(0, message).await.unwrap();
+            sim.run_until_converged(&id, 30).await.unwrap();
+            rounds.push((sim.round(), sim.coverage(&id).await));
+        }
+        assert_eq!(rounds[0], rounds[1]);
+    }
+}
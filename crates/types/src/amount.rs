@@ -0,0 +1,127 @@
+//! Exact conversion between `Amount` (integer sats) and decimal QTC strings
+
+use crate::Amount;
+use thiserror::Error;
+
+/// Decimal places in one QTC; must match `network.decimals` in the chain spec
+pub const QTC_DECIMALS: u8 = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum AmountError {
+    #[error("empty amount")]
+    Empty,
+    #[error("invalid amount format")]
+    InvalidFormat,
+    #[error("negative amount")]
+    Negative,
+    #[error("more than {0} decimal places")]
+    TooPrecise(u8),
+    #[error("amount out of range")]
+    Overflow,
+}
+
+/// Parse a decimal QTC string such as `"12.34567891"` into sats
+pub fn from_qtc_str(s: &str) -> Result<Amount, AmountError> {
+    parse_decimal(s, QTC_DECIMALS)
+}
+
+/// Format sats as a decimal QTC string with all decimal places, e.g. `"12.34567891"`
+pub fn to_qtc_str(amount: Amount) -> String {
+    format_decimal(amount, QTC_DECIMALS)
+}
+
+/// Parse a non-negative decimal string with at most `decimals` fractional digits
+pub fn parse_decimal(s: &str, decimals: u8) -> Result<Amount, AmountError> {
+    if s.is_empty() {
+        return Err(AmountError::Empty);
+    }
+    if let Some(rest) = s.strip_prefix('-') {
+        // Still report malformed input as such rather than as negative
+        parse_decimal(rest, decimals)?;
+        return Err(AmountError::Negative);
+    }
+
+    let (whole, frac) = match s.split_once('.') {
+        Some((whole, frac)) => (whole, frac),
+        None => (s, ""),
+    };
+    let all_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+    if whole.is_empty() || !all_digits(whole) || !all_digits(frac) || (s.contains('.') && frac.is_empty()) {
+        return Err(AmountError::InvalidFormat);
+    }
+    if frac.len() > decimals as usize {
+        return Err(AmountError::TooPrecise(decimals));
+    }
+
+    let scale = (10 as Amount).checked_pow(decimals as u32).ok_or(AmountError::Overflow)?;
+    let whole = digits_value(whole)?;
+    let frac_scale = (10 as Amount).pow((decimals as usize - frac.len()) as u32);
+    let frac = digits_value(frac)? * frac_scale;
+
+    whole
+        .checked_mul(scale)
+        .and_then(|sats| sats.checked_add(frac))
+        .ok_or(AmountError::Overflow)
+}
+
+/// Format an amount with exactly `decimals` fractional digits
+pub fn format_decimal(amount: Amount, decimals: u8) -> String {
+    let sign = if amount < 0 { "-" } else { "" };
+    let sats = amount.unsigned_abs();
+    if decimals == 0 {
+        return format!("{}{}", sign, sats);
+    }
+    let scale = 10u64.pow(decimals as u32);
+    format!("{}{}.{:0width$}", sign, sats / scale, sats % scale, width = decimals as usize)
+}
+
+fn digits_value(digits: &str) -> Result<Amount, AmountError> {
+    digits.bytes().try_fold(0 as Amount, |acc, b| {
+        acc.checked_mul(10)
+            .and_then(|v| v.checked_add((b - b'0') as Amount))
+            .ok_or(AmountError::Overflow)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for sats in [0, 1, 99_999_999, 100_000_000, 1_234_567_891, 2_100_000_000_000_000] {
+            assert_eq!(from_qtc_str(&to_qtc_str(sats)).unwrap(), sats);
+        }
+        assert_eq!(from_qtc_str("12.34567891").unwrap(), 1_234_567_891);
+        assert_eq!(to_qtc_str(1_234_567_891), "12.34567891");
+        assert_eq!(from_qtc_str("12").unwrap(), 1_200_000_000);
+        assert_eq!(from_qtc_str("0.5").unwrap(), 50_000_000);
+        assert_eq!(to_qtc_str(50_000_000), "0.50000000");
+    }
+
+    #[test]
+    fn test_over_precision_rejected() {
+        assert_eq!(from_qtc_str("0.000000001"), Err(AmountError::TooPrecise(8)));
+        assert_eq!(from_qtc_str("1.123456789"), Err(AmountError::TooPrecise(8)));
+        assert_eq!(parse_decimal("1.234", 2), Err(AmountError::TooPrecise(2)));
+    }
+
+    #[test]
+    fn test_negative_and_malformed_rejected() {
+        assert_eq!(from_qtc_str("-1.5"), Err(AmountError::Negative));
+        assert_eq!(from_qtc_str("-abc"), Err(AmountError::InvalidFormat));
+        assert_eq!(from_qtc_str(""), Err(AmountError::Empty));
+        for bad in [".5", "5.", "1.2.3", "1e8", "+1", " 1", "1,5"] {
+            assert_eq!(from_qtc_str(bad), Err(AmountError::InvalidFormat), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_largest_representable_value() {
+        let max = to_qtc_str(Amount::MAX);
+        assert_eq!(max, "92233720368.54775807");
+        assert_eq!(from_qtc_str(&max).unwrap(), Amount::MAX);
+        assert_eq!(from_qtc_str("92233720368.54775808"), Err(AmountError::Overflow));
+        assert_eq!(from_qtc_str("99999999999999999999"), Err(AmountError::Overflow));
+    }
+}
@@ -0,0 +1,232 @@
+//! Versioned canonical encoding for consensus-critical types.
+//!
+//! Hashes that consensus depends on must not change when a serialization
+//! crate does, so `Transaction` and `BlockHeader` have a hand-written layout
+//! here instead of relying on bincode's. Every encoding starts with
+//! `CANONICAL_VERSION`; the layout under a given version never changes, and
+//! a new layout gets a new version byte.
+//!
+//! Layout, version 1 (all integers little-endian):
+//!
+//! - `Hash32`: 32 raw bytes
+//! - `OutPoint`: txid, vout `u32`
+//! - `TxIn`: prevout, signature length `u32`, signature bytes, cancel `u8` (0 or 1)
+//! - `TxOut`: value `i64`, kind tag `u8`, pubkey length `u32`, pubkey bytes,
+//!   then window_blocks `u32` for tag 1 (`P2PQRevocable`); tag 0 is `P2PQ`
+//! - `Transaction`: version `u32`, input count `u32`, inputs, output count
+//!   `u32`, outputs, lock_time `u32`
+//! - `BlockHeader`: version `u32`, prev_block, merkle_root, time `u64`,
+//!   bits `u32`, nonce `u32`
+
+use crate::{BlockHeader, Hash32, OutPoint, OutputType, Transaction, TxIn, TxOut};
+use thiserror::Error;
+
+/// Version byte that prefixes every canonical encoding
+pub const CANONICAL_VERSION: u8 = 1;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum CanonicalError {
+    #[error("unsupported canonical encoding version {0}")]
+    UnsupportedVersion(u8),
+    #[error("unexpected end of input")]
+    UnexpectedEnd,
+    #[error("{0} trailing bytes after value")]
+    TrailingBytes(usize),
+    #[error("invalid output kind tag {0}")]
+    InvalidTag(u8),
+    #[error("invalid bool byte {0}")]
+    InvalidBool(u8),
+}
+
+/// A type with a fixed, versioned byte layout
+pub trait Canonical: Sized {
+    fn write(&self, out: &mut Vec<u8>);
+    fn read(r: &mut Reader<'_>) -> Result<Self, CanonicalError>;
+}
+
+/// Encode `value` with the current version byte
+pub fn encode<T: Canonical>(value: &T) -> Vec<u8> {
+    let mut out = vec![CANONICAL_VERSION];
+    value.write(&mut out);
+    out
+}
+
+/// Decode a complete encoding; unknown versions and trailing bytes are errors
+pub fn decode<T: Canonical>(bytes: &[u8]) -> Result<T, CanonicalError> {
+    let mut r = Reader { bytes };
+    match r.u8()? {
+        CANONICAL_VERSION => {}
+        v => return Err(CanonicalError::UnsupportedVersion(v)),
+    }
+    let value = T::read(&mut r)?;
+    if !r.bytes.is_empty() {
+        return Err(CanonicalError::TrailingBytes(r.bytes.len()));
+    }
+    Ok(value)
+}
+
+/// Cursor over the remaining input
+pub struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], CanonicalError> {
+        if self.bytes.len() < n {
+            return Err(CanonicalError::UnexpectedEnd);
+        }
+        let (head, rest) = self.bytes.split_at(n);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], CanonicalError> {
+        Ok(self.take(N)?.try_into().expect("length checked"))
+    }
+
+    fn u8(&mut self) -> Result<u8, CanonicalError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, CanonicalError> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64, CanonicalError> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    fn i64(&mut self) -> Result<i64, CanonicalError> {
+        self.array().map(i64::from_le_bytes)
+    }
+
+    fn bool(&mut self) -> Result<bool, CanonicalError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            b => Err(CanonicalError::InvalidBool(b)),
+        }
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>, CanonicalError> {
+        let len = self.u32()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    /// A counted list; every element takes at least one byte, so a count
+    /// larger than the remaining input fails before allocating
+    fn list<T: Canonical>(&mut self) -> Result<Vec<T>, CanonicalError> {
+        let len = self.u32()? as usize;
+        if len > self.bytes.len() {
+            return Err(CanonicalError::UnexpectedEnd);
+        }
+        (0..len).map(|_| T::read(self)).collect()
+    }
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    out.extend_from_slice(bytes);
+}
+
+fn write_list<T: Canonical>(out: &mut Vec<u8>, items: &[T]) {
+    out.extend_from_slice(&(items.len() as u32).to_le_bytes());
+    for item in items {
+        item.write(out);
+    }
+}
+
+impl Canonical for Hash32 {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.0);
+    }
+
+    fn read(r: &mut Reader<'_>) -> Result<Self, CanonicalError> {
+        r.array().map(Hash32)
+    }
+}
+
+impl Canonical for OutPoint {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.txid.write(out);
+        out.extend_from_slice(&self.vout.to_le_bytes());
+    }
+
+    fn read(r: &mut Reader<'_>) -> Result<Self, CanonicalError> {
+        Ok(OutPoint { txid: Hash32::read(r)?, vout: r.u32()? })
+    }
+}
+
+impl Canonical for TxIn {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.prevout.write(out);
+        write_bytes(out, &self.pq_signature);
+        out.push(self.cancel as u8);
+    }
+
+    fn read(r: &mut Reader<'_>) -> Result<Self, CanonicalError> {
+        Ok(TxIn { prevout: OutPoint::read(r)?, pq_signature: r.bytes()?, cancel: r.bool()? })
+    }
+}
+
+impl Canonical for TxOut {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.value.to_le_bytes());
+        match &self.kind {
+            OutputType::P2PQ { pubkey } => {
+                out.push(0);
+                write_bytes(out, pubkey);
+            }
+            OutputType::P2PQRevocable { pubkey, window_blocks } => {
+                out.push(1);
+                write_bytes(out, pubkey);
+                out.extend_from_slice(&window_blocks.to_le_bytes());
+            }
+        }
+    }
+
+    fn read(r: &mut Reader<'_>) -> Result<Self, CanonicalError> {
+        let value = r.i64()?;
+        let kind = match r.u8()? {
+            0 => OutputType::P2PQ { pubkey: r.bytes()? },
+            1 => OutputType::P2PQRevocable { pubkey: r.bytes()?, window_blocks: r.u32()? },
+            tag => return Err(CanonicalError::InvalidTag(tag)),
+        };
+        Ok(TxOut { value, kind })
+    }
+}
+
+impl Canonical for Transaction {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.version.to_le_bytes());
+        write_list(out, &self.vin);
+        write_list(out, &self.vout);
+        out.extend_from_slice(&self.lock_time.to_le_bytes());
+    }
+
+    fn read(r: &mut Reader<'_>) -> Result<Self, CanonicalError> {
+        Ok(Transaction { version: r.u32()?, vin: r.list()?, vout: r.list()?, lock_time: r.u32()? })
+    }
+}
+
+impl Canonical for BlockHeader {
+    fn write(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.version.to_le_bytes());
+        self.prev_block.write(out);
+        self.merkle_root.write(out);
+        out.extend_from_slice(&self.time.to_le_bytes());
+        out.extend_from_slice(&self.bits.to_le_bytes());
+        out.extend_from_slice(&self.nonce.to_le_bytes());
+    }
+
+    fn read(r: &mut Reader<'_>) -> Result<Self, CanonicalError> {
+        Ok(BlockHeader {
+            version: r.u32()?,
+            prev_block: Hash32::read(r)?,
+            merkle_root: Hash32::read(r)?,
+            time: r.u64()?,
+            bits: r.u32()?,
+            nonce: r.u32()?,
+        })
+    }
+}
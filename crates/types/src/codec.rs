@@ -0,0 +1,70 @@
+//! Bounded bincode decoding for bytes that arrive from outside the node.
+//!
+//! `bincode::deserialize` trusts length prefixes, so a crafted payload can
+//! claim a multi-gigabyte buffer before any validation runs. These helpers
+//! keep the wire format of `bincode::serialize` but refuse to read past a
+//! byte limit, so such a prefix fails with `SizeLimit` instead of
+//! allocating.
+
+use bincode::Options;
+use serde::de::DeserializeOwned;
+
+/// Default cap on bytes decoded from one untrusted payload
+pub const MAX_DECODE_BYTES: u64 = 32 * 1024 * 1024;
+
+/// Decode `bytes`, reading at most `limit` bytes. Encoding matches
+/// `bincode::serialize`.
+pub fn decode_bounded<T: DeserializeOwned>(bytes: &[u8], limit: u64) -> bincode::Result<T> {
+    // `Options::deserialize` drops the limit for slice input, so build the
+    // deserializer directly to keep it
+    let options = bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_limit(limit);
+    T::deserialize(&mut bincode::Deserializer::from_slice(bytes, options))
+}
+
+/// Decode untrusted `bytes`. No length prefix may claim more than the
+/// payload holds, nor more than `MAX_DECODE_BYTES`.
+pub fn decode<T: DeserializeOwned>(bytes: &[u8]) -> bincode::Result<T> {
+    decode_bounded(bytes, MAX_DECODE_BYTES.min(bytes.len() as u64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Hash32, OutPoint, Transaction, TxIn, TxOut};
+
+    fn sample() -> Transaction {
+        Transaction::new(
+            1,
+            vec![TxIn::new(OutPoint::new(Hash32([7u8; 32]), 0), vec![9u8; 16], false)],
+            vec![TxOut::new_p2pq(10_000, vec![3u8; 16])],
+            0,
+        )
+    }
+
+    #[test]
+    fn round_trips_bincode_serialize() {
+        let tx = sample();
+        let bytes = bincode::serialize(&tx).unwrap();
+        assert_eq!(decode::<Transaction>(&bytes).unwrap(), tx);
+    }
+
+    #[test]
+    fn oversized_signature_prefix_hits_limit() {
+        let mut bytes = bincode::serialize(&sample()).unwrap();
+        // version, input count, prevout
+        let sig_len_offset = 4 + 8 + 32 + 4;
+        bytes[sig_len_offset..sig_len_offset + 8].copy_from_slice(&(4u64 << 30).to_le_bytes());
+
+        let err = decode::<Transaction>(&bytes).unwrap_err();
+        assert!(matches!(*err, bincode::ErrorKind::SizeLimit));
+    }
+
+    #[test]
+    fn payload_over_limit_rejected() {
+        let bytes = bincode::serialize(&sample()).unwrap();
+        let err = decode_bounded::<Transaction>(&bytes, 32).unwrap_err();
+        assert!(matches!(*err, bincode::ErrorKind::SizeLimit));
+    }
+}
@@ -1,10 +1,21 @@
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 
+pub mod amount;
+pub mod canonical;
+pub mod codec;
+pub mod target;
+
+pub use amount::{from_qtc_str, to_qtc_str, AmountError, QTC_DECIMALS};
+
 pub type Amount = i64;      // sats (8 decimals)
 pub type Height = u64;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+/// Weight units per byte of non-witness data; signature bytes count once
+pub const WITNESS_SCALE_FACTOR: u64 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Hash32(pub [u8; 32]);
 
 impl Hash32 {
@@ -22,7 +33,7 @@ impl Hash32 {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct OutPoint { 
     pub txid: Hash32, 
     pub vout: u32 
@@ -95,8 +106,54 @@ impl Transaction {
         self.vin.is_empty()
     }
     
-    pub fn total_output_value(&self) -> Amount {
-        self.vout.iter().map(|o| o.value).sum()
+    /// Sum of all output values, or `None` if the sum overflows `Amount`.
+    pub fn total_output_value(&self) -> Option<Amount> {
+        self.vout.iter().try_fold(0 as Amount, |acc, o| acc.checked_add(o.value))
+    }
+
+    /// Fee paid by this transaction: the value of the outputs it spends,
+    /// as given by `lookup`, minus its own outputs. `None` if an input is
+    /// unknown, a sum overflows, or the outputs exceed the inputs. A
+    /// coinbase spends nothing and pays no fee.
+    pub fn fee<F>(&self, mut lookup: F) -> Option<Amount>
+    where
+        F: FnMut(&OutPoint) -> Option<Amount>
+    {
+        if self.is_coinbase() { return Some(0); }
+        let sum_in = self.vin.iter().try_fold(0 as Amount, |acc, i| acc.checked_add(lookup(&i.prevout)?))?;
+        let sum_out = self.total_output_value()?;
+        if sum_in < sum_out { return None; }
+        Some(sum_in - sum_out)
+    }
+
+    /// What every input signs: the transaction serialized with signatures
+    /// and cancel flags cleared. Hash it with `qc_crypto::tx_sighash`.
+    pub fn signing_skeleton(&self) -> Vec<u8> {
+        let mut skeleton = self.clone();
+        for input in &mut skeleton.vin {
+            input.pq_signature.clear();
+            input.cancel = false;
+        }
+        bincode::serialize(&skeleton).expect("serialize transaction")
+    }
+
+    /// Canonical encoding size with every input's signature stripped
+    pub fn base_size(&self) -> u64 {
+        let mut stripped = self.clone();
+        for input in &mut stripped.vin {
+            input.pq_signature.clear();
+        }
+        canonical::encode(&stripped).len() as u64
+    }
+
+    /// Signature bytes, which are witness data
+    pub fn witness_size(&self) -> u64 {
+        self.vin.iter().map(|i| i.pq_signature.len() as u64).sum()
+    }
+
+    /// Base bytes count `WITNESS_SCALE_FACTOR` times, witness bytes once
+    pub fn weight(&self) -> u64 {
+        self.base_size() * WITNESS_SCALE_FACTOR + self.witness_size()
     }
 }
 
@@ -128,6 +185,14 @@ impl BlockHeader {
             nonce,
         }
     }
+
+    /// Canonical block hash: double SHA-256 of the versioned canonical
+    /// encoding, so it does not depend on bincode's layout.
+    pub fn hash(&self) -> Hash32 {
+        let bytes = canonical::encode(self);
+        let first = Sha256::digest(&bytes);
+        Hash32(Sha256::digest(first).into())
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -141,14 +206,29 @@ impl Block {
         Self { header, txs }
     }
     
+    /// Hash of this block's header
+    pub fn hash(&self) -> Hash32 {
+        self.header.hash()
+    }
+    
     pub fn transaction_count(&self) -> usize {
         self.txs.len()
     }
     
-    pub fn total_fees(&self) -> Amount {
-        // Calculate fees as input_sum - output_sum for each non-coinbase tx
-        // This is a simplified calculation
-        0 // TODO: Implement proper fee calculation with UTXO lookup
+    /// Sum of the fees paid by the block's non-coinbase transactions,
+    /// derived from the outputs they spend. `lookup` must also resolve
+    /// outputs created earlier in the same block.
+    pub fn total_fees<F>(&self, mut lookup: F) -> Option<Amount>
+    where
+        F: FnMut(&OutPoint) -> Option<Amount>
+    {
+        self.txs.iter().try_fold(0 as Amount, |acc, tx| acc.checked_add(tx.fee(&mut lookup)?))
+    }
+
+    /// Header bytes at full weight plus the weight of every transaction
+    pub fn weight(&self) -> u64 {
+        let header = canonical::encode(&self.header).len() as u64;
+        header * WITNESS_SCALE_FACTOR + self.txs.iter().map(Transaction::weight).sum::<u64>()
     }
 }
 
@@ -196,4 +276,96 @@ mod tests {
         );
         assert!(!regular_tx.is_coinbase());
     }
+
+    #[test]
+    fn test_total_output_value_overflow() {
+        let tx = Transaction::new(
+            1,
+            vec![],
+            vec![TxOut::new_p2pq(1_000, vec![]), TxOut::new_p2pq(2_000, vec![])],
+            0
+        );
+        assert_eq!(tx.total_output_value(), Some(3_000));
+
+        let overflow = Transaction::new(
+            1,
+            vec![],
+            vec![TxOut::new_p2pq(Amount::MAX, vec![]), TxOut::new_p2pq(1, vec![])],
+            0
+        );
+        assert_eq!(overflow.total_output_value(), None);
+    }
+
+    #[test]
+    fn test_fee_is_inputs_minus_outputs() {
+        let funding = OutPoint::new(Hash32([7u8; 32]), 0);
+        let tx = Transaction::new(
+            1,
+            vec![TxIn::new(funding.clone(), vec![], false)],
+            vec![TxOut::new_p2pq(9_000, vec![])],
+            0
+        );
+        let lookup = |o: &OutPoint| (*o == funding).then_some(10_000);
+        assert_eq!(tx.fee(lookup), Some(1_000));
+
+        // Unknown inputs and outputs exceeding inputs have no fee
+        assert_eq!(tx.fee(|_| None), None);
+        assert_eq!(tx.fee(|_| Some(8_000)), None);
+        assert_eq!(Transaction::new(1, vec![], vec![TxOut::new_p2pq(5_000, vec![])], 0).fee(|_| None), Some(0));
+
+        let header = BlockHeader::new(1, Hash32::zero(), Hash32::zero(), 0, 0, 0);
+        let block = Block::new(header, vec![Transaction::new(1, vec![], vec![TxOut::new_p2pq(5_000, vec![])], 0), tx]);
+        assert_eq!(block.total_fees(lookup), Some(1_000));
+    }
+
+    #[test]
+    fn test_weight_discounts_signature_bytes() {
+        let input = |sig: Vec<u8>| TxIn::new(OutPoint::new(Hash32([1u8; 32]), 0), sig, false);
+        let tx = Transaction::new(1, vec![input(vec![])], vec![TxOut::new_p2pq(5_000, vec![2u8; 20])], 0);
+        assert_eq!(tx.witness_size(), 0);
+        assert_eq!(tx.weight(), tx.base_size() * WITNESS_SCALE_FACTOR);
+
+        // Signature bytes add one weight unit each
+        let signed = Transaction::new(1, vec![input(vec![3u8; 100])], tx.vout.clone(), 0);
+        assert_eq!(signed.base_size(), tx.base_size());
+        assert_eq!(signed.weight(), tx.weight() + 100);
+
+        // Any other byte adds four
+        let bigger = Transaction::new(1, tx.vin.clone(), vec![TxOut::new_p2pq(5_000, vec![2u8; 120])], 0);
+        assert_eq!(bigger.weight(), tx.weight() + 100 * WITNESS_SCALE_FACTOR);
+
+        let header = BlockHeader::new(1, Hash32::zero(), Hash32::zero(), 0, 0, 0);
+        let block = Block::new(header.clone(), vec![tx.clone(), signed.clone()]);
+        // Sizes follow the canonical layout, whatever bincode would produce
+        assert_eq!(tx.base_size(), canonical::encode(&tx).len() as u64);
+        let header_weight = canonical::encode(&header).len() as u64 * WITNESS_SCALE_FACTOR;
+        assert_eq!(header_weight, (1 + 4 + 32 + 32 + 8 + 4 + 4) * WITNESS_SCALE_FACTOR);
+        assert_eq!(block.weight(), header_weight + tx.weight() + signed.weight());
+    }
+
+    #[test]
+    fn test_block_hash_deterministic() {
+        let header = BlockHeader::new(1, Hash32([1u8; 32]), Hash32([2u8; 32]), 1_700_000_000, 0x1d00ffff, 42);
+        assert_eq!(header.hash(), header.clone().hash());
+        assert_ne!(header.hash(), Hash32::zero());
+
+        let block = Block::new(header.clone(), vec![Transaction::new(1, vec![], vec![], 0)]);
+        assert_eq!(block.hash(), header.hash());
+    }
+
+    #[test]
+    fn test_block_hash_covers_every_header_field() {
+        let base = BlockHeader::new(1, Hash32([1u8; 32]), Hash32([2u8; 32]), 1_700_000_000, 0x1d00ffff, 42);
+        let variants = [
+            BlockHeader { version: 2, ..base.clone() },
+            BlockHeader { prev_block: Hash32([9u8; 32]), ..base.clone() },
+            BlockHeader { merkle_root: Hash32([9u8; 32]), ..base.clone() },
+            BlockHeader { time: base.time + 1, ..base.clone() },
+            BlockHeader { bits: base.bits - 1, ..base.clone() },
+            BlockHeader { nonce: base.nonce + 1, ..base.clone() },
+        ];
+        for variant in &variants {
+            assert_ne!(variant.hash(), base.hash(), "{:?}", variant);
+        }
+    }
 }
@@ -0,0 +1,132 @@
+//! Compact ("bits") encoding of proof-of-work targets.
+//!
+//! Same format as Bitcoin's `nBits`: the top byte is the target's length in
+//! bytes and the low three bytes are its most significant bytes. Bit 23 is a
+//! sign bit, so a target whose leading byte is `>= 0x80` is stored with one
+//! more length byte and a shorter mantissa. A block is valid when its hash,
+//! read as a big-endian integer, is `<=` the decoded target.
+
+use std::ops::Shr;
+
+/// Bits of the difficulty-1 target, which is also the genesis target
+pub const DIFFICULTY_1_BITS: u32 = 0x1d00ffff;
+
+/// Unsigned 256-bit integer stored as big-endian bytes, so the derived
+/// ordering is numeric and a block hash compares against it directly
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct U256(pub [u8; 32]);
+
+impl U256 {
+    pub const ZERO: U256 = U256([0; 32]);
+    pub const MAX: U256 = U256([0xff; 32]);
+
+    pub fn from_u128(value: u128) -> Self {
+        let mut bytes = [0u8; 32];
+        bytes[16..].copy_from_slice(&value.to_be_bytes());
+        U256(bytes)
+    }
+
+    pub fn from_be_bytes(bytes: [u8; 32]) -> Self {
+        U256(bytes)
+    }
+
+    pub fn to_be_bytes(self) -> [u8; 32] {
+        self.0
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.0 == [0; 32]
+    }
+
+    /// Nearest `f64`; exact up to 2^53
+    pub fn to_f64(&self) -> f64 {
+        self.0.iter().fold(0.0, |acc, b| acc * 256.0 + *b as f64)
+    }
+
+    /// Number of bytes after the leading zero bytes
+    fn significant_bytes(&self) -> usize {
+        32 - self.0.iter().take_while(|b| **b == 0).count()
+    }
+}
+
+impl Shr<u32> for U256 {
+    type Output = U256;
+
+    fn shr(self, bits: u32) -> U256 {
+        if bits >= 256 {
+            return U256::ZERO;
+        }
+        let (bytes, rem) = ((bits / 8) as usize, bits % 8);
+        let mut out = [0u8; 32];
+        for (i, byte) in out.iter_mut().enumerate().skip(bytes) {
+            let hi = self.0[i - bytes];
+            let lo = if i > bytes { self.0[i - bytes - 1] } else { 0 };
+            *byte = if rem == 0 { hi } else { (hi >> rem) | (lo << (8 - rem)) };
+        }
+        U256(out)
+    }
+}
+
+/// Decode compact bits. Negative or overflowing encodings decode to zero,
+/// which no hash can meet.
+pub fn compact_to_target(bits: u32) -> U256 {
+    let size = (bits >> 24) as usize;
+    let word = bits & 0x007f_ffff;
+    if word == 0 {
+        return U256::ZERO;
+    }
+    let negative = bits & 0x0080_0000 != 0;
+    let overflow = size > 34 || (word > 0xff && size > 33) || (word > 0xffff && size > 32);
+    if negative || overflow {
+        return U256::ZERO;
+    }
+
+    if size <= 3 {
+        return U256::from_u128((word >> (8 * (3 - size))) as u128);
+    }
+    let mut out = [0u8; 32];
+    for (i, b) in word.to_be_bytes()[1..].iter().enumerate() {
+        // Bytes past the top of a 33- or 34-byte size are zero, per the overflow check
+        if let Some(idx) = (32 + i).checked_sub(size) {
+            out[idx] = *b;
+        }
+    }
+    U256(out)
+}
+
+/// Encode a target as compact bits, truncating to its three most
+/// significant bytes
+pub fn target_to_compact(target: U256) -> u32 {
+    let mut size = target.significant_bytes();
+    if size == 0 {
+        return 0;
+    }
+    let start = 32 - size;
+    let mut word = target.0[start..(start + 3).min(32)]
+        .iter()
+        .fold(0u32, |acc, b| (acc << 8) | *b as u32);
+    if size < 3 {
+        word <<= 8 * (3 - size);
+    }
+    if word & 0x0080_0000 != 0 {
+        word >>= 8;
+        size += 1;
+    }
+    ((size as u32) << 24) | word
+}
+
+/// Expected hashes to meet `target`, about `2^256 / (target + 1)`. Only the
+/// top 128 bits of the target count, so targets below `2^128` saturate.
+pub fn target_to_work(target: U256) -> u128 {
+    let mut hi = [0u8; 16];
+    hi.copy_from_slice(&target.0[..16]);
+    u128::MAX / u128::from_be_bytes(hi).saturating_add(1)
+}
+
+/// Difficulty relative to the difficulty-1 target; infinite for a zero target
+pub fn target_to_difficulty(target: U256) -> f64 {
+    if target.is_zero() {
+        return f64::INFINITY;
+    }
+    compact_to_target(DIFFICULTY_1_BITS).to_f64() / target.to_f64()
+}
@@ -0,0 +1,93 @@
+//! Pins the exact canonical bytes of known values. If one of these fails,
+//! the consensus encoding changed: bump `CANONICAL_VERSION` instead of
+//! editing the expected bytes.
+
+use qc_types::canonical::{self, CanonicalError, CANONICAL_VERSION};
+use qc_types::*;
+
+fn sample_tx() -> Transaction {
+    Transaction::new(
+        1,
+        vec![TxIn::new(OutPoint::new(Hash32([0x11; 32]), 2), vec![0xaa, 0xbb], true)],
+        vec![TxOut::new_p2pq(5_000, vec![1, 2, 3]), TxOut::new_revocable(7, vec![4], 144)],
+        9,
+    )
+}
+
+fn sample_header() -> BlockHeader {
+    BlockHeader::new(1, Hash32([0x22; 32]), Hash32([0x33; 32]), 1_700_000_000, 0x1d00ffff, 42)
+}
+
+fn hex_concat(parts: &[&str]) -> Vec<u8> {
+    hex::decode(parts.concat()).unwrap()
+}
+
+#[test]
+fn transaction_bytes_pinned() {
+    let expected = hex_concat(&[
+        "01",                                    // encoding version
+        "01000000",                              // tx version
+        "01000000",                              // input count
+        &"11".repeat(32), "02000000",            // prevout
+        "02000000", "aabb",                      // signature
+        "01",                                    // cancel
+        "02000000",                              // output count
+        "8813000000000000", "00", "03000000", "010203",
+        "0700000000000000", "01", "01000000", "04", "90000000",
+        "09000000",                              // lock time
+    ]);
+    assert_eq!(canonical::encode(&sample_tx()), expected);
+}
+
+#[test]
+fn block_header_bytes_pinned() {
+    let expected = hex_concat(&[
+        "01",
+        "01000000",
+        &"22".repeat(32),
+        &"33".repeat(32),
+        "00f1536500000000",
+        "ffff001d",
+        "2a000000",
+    ]);
+    assert_eq!(canonical::encode(&sample_header()), expected);
+}
+
+#[test]
+fn block_hash_pinned() {
+    assert_eq!(
+        sample_header().hash().to_hex(),
+        "06597a768995386857017bf50b3f15b7358e47f7d9974e777fc698709d7a4236"
+    );
+}
+
+#[test]
+fn round_trip() {
+    let tx = sample_tx();
+    assert_eq!(canonical::decode::<Transaction>(&canonical::encode(&tx)).unwrap(), tx);
+    let header = sample_header();
+    assert_eq!(canonical::decode::<BlockHeader>(&canonical::encode(&header)).unwrap(), header);
+}
+
+#[test]
+fn malformed_encodings_rejected() {
+    let mut bytes = canonical::encode(&sample_tx());
+
+    let mut future = bytes.clone();
+    future[0] = CANONICAL_VERSION + 1;
+    assert_eq!(canonical::decode::<Transaction>(&future), Err(CanonicalError::UnsupportedVersion(2)));
+
+    let mut bad_bool = bytes.clone();
+    bad_bool[1 + 4 + 4 + 36 + 6] = 2;
+    assert_eq!(canonical::decode::<Transaction>(&bad_bool), Err(CanonicalError::InvalidBool(2)));
+
+    let mut huge_count = bytes.clone();
+    huge_count[5..9].copy_from_slice(&u32::MAX.to_le_bytes());
+    assert_eq!(canonical::decode::<Transaction>(&huge_count), Err(CanonicalError::UnexpectedEnd));
+
+    let truncated = &bytes[..bytes.len() - 1];
+    assert_eq!(canonical::decode::<Transaction>(truncated), Err(CanonicalError::UnexpectedEnd));
+
+    bytes.push(0);
+    assert_eq!(canonical::decode::<Transaction>(&bytes), Err(CanonicalError::TrailingBytes(1)));
+}
@@ -0,0 +1,83 @@
+//! Length prefixes in untrusted payloads must not drive allocation.
+
+use qc_types::codec::decode;
+use qc_types::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+/// Largest single allocation a bounded decode may make
+const MAX_ALLOCATION: usize = 2 * 1024 * 1024;
+
+struct LargestAllocation;
+
+thread_local! {
+    static LARGEST: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for LargestAllocation {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = LARGEST.try_with(|l| l.set(l.get().max(layout.size())));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = LARGEST.try_with(|l| l.set(l.get().max(new_size)));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: LargestAllocation = LargestAllocation;
+
+fn largest_allocation_during<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    LARGEST.with(|l| l.set(0));
+    let result = f();
+    (result, LARGEST.with(|l| l.get()))
+}
+
+fn encoded_tx() -> Vec<u8> {
+    let tx = Transaction::new(
+        1,
+        vec![TxIn::new(OutPoint::new(Hash32([1u8; 32]), 0), vec![2u8; 64], false)],
+        vec![TxOut::new_p2pq(5_000, vec![3u8; 32])],
+        0,
+    );
+    bincode::serialize(&tx).unwrap()
+}
+
+#[test]
+fn billions_of_inputs_rejected_without_large_allocation() {
+    let mut bytes = encoded_tx();
+    bytes[4..12].copy_from_slice(&3_000_000_000u64.to_le_bytes());
+
+    let (result, largest) = largest_allocation_during(|| decode::<Transaction>(&bytes));
+    assert!(result.is_err());
+    assert!(largest <= MAX_ALLOCATION, "allocated {} bytes", largest);
+}
+
+#[test]
+fn huge_signature_rejected_without_large_allocation() {
+    let mut bytes = encoded_tx();
+    let sig_len_offset = 4 + 8 + 32 + 4;
+    bytes[sig_len_offset..sig_len_offset + 8].copy_from_slice(&(8u64 << 30).to_le_bytes());
+
+    let (result, largest) = largest_allocation_during(|| decode::<Transaction>(&bytes));
+    assert!(matches!(result.map_err(|e| *e), Err(bincode::ErrorKind::SizeLimit)));
+    assert!(largest <= MAX_ALLOCATION, "allocated {} bytes", largest);
+}
+
+#[test]
+fn huge_block_transaction_count_rejected() {
+    let header = BlockHeader::new(1, Hash32::zero(), Hash32::zero(), 0, 0, 0);
+    let mut bytes = bincode::serialize(&Block::new(header, Vec::new())).unwrap();
+    let count_offset = bytes.len() - 8;
+    bytes[count_offset..].copy_from_slice(&u64::MAX.to_le_bytes());
+
+    let (result, largest) = largest_allocation_during(|| decode::<Block>(&bytes));
+    assert!(result.is_err());
+    assert!(largest <= MAX_ALLOCATION, "allocated {} bytes", largest);
+}
@@ -0,0 +1,76 @@
+use qc_types::target::*;
+
+fn target_hex(hex_str: &str) -> U256 {
+    U256(hex::decode(hex_str).unwrap().try_into().unwrap())
+}
+
+#[test]
+fn genesis_bits_decode_to_bitcoin_genesis_target() {
+    let genesis = target_hex("00000000ffff0000000000000000000000000000000000000000000000000000");
+    assert_eq!(compact_to_target(DIFFICULTY_1_BITS), genesis);
+    assert_eq!(target_to_compact(genesis), DIFFICULTY_1_BITS);
+    assert_eq!(target_to_difficulty(genesis), 1.0);
+}
+
+#[test]
+fn known_values() {
+    // Bitcoin block 32256, the first retarget
+    assert_eq!(
+        compact_to_target(0x1d00d86a),
+        target_hex("00000000d86a0000000000000000000000000000000000000000000000000000")
+    );
+    assert_eq!(compact_to_target(0x0300ffff), U256::from_u128(0xffff));
+    assert_eq!(compact_to_target(0x0200ffff), U256::from_u128(0xff));
+    assert_eq!(compact_to_target(0x01120000), U256::from_u128(0x12));
+    assert_eq!(target_to_compact(U256::from_u128(0x12)), 0x01120000);
+    // 0x80 is the sign bit, so it moves down a byte
+    assert_eq!(target_to_compact(U256::from_u128(0x80)), 0x02008000);
+    assert_eq!(target_to_compact(U256::MAX), 0x2100ffff);
+    assert_eq!(target_to_compact(U256::ZERO), 0);
+}
+
+#[test]
+fn invalid_bits_decode_to_zero() {
+    assert!(compact_to_target(0).is_zero());
+    assert!(compact_to_target(0x1d000000).is_zero());
+    // Negative
+    assert!(compact_to_target(0x04923456).is_zero());
+    // Overflow
+    assert!(compact_to_target(0x2201_0000).is_zero());
+    assert!(compact_to_target(0x2300_0001).is_zero());
+    assert!(!compact_to_target(0x2200_0001).is_zero());
+}
+
+#[test]
+fn round_trip() {
+    for bits in [0x1d00ffff, 0x1b0404cb, 0x1f7fffff, 0x207fffff, 0x1701_2345, 0x0312_3456, 0x2100ffff] {
+        assert_eq!(target_to_compact(compact_to_target(bits)), bits, "{:08x}", bits);
+    }
+
+    // Encoding keeps the three most significant bytes and never rounds up
+    let mut target = target_hex("0000000000000000000123456789abcdef0123456789abcdef0123456789abcd");
+    while !target.is_zero() {
+        let decoded = compact_to_target(target_to_compact(target));
+        assert!(decoded <= target);
+        assert!(target_to_difficulty(decoded) / target_to_difficulty(target) <= 1.0 + 1.0 / 32768.0);
+        target = target >> 1;
+    }
+}
+
+#[test]
+fn difficulty_scales_inversely_with_target() {
+    let genesis = compact_to_target(DIFFICULTY_1_BITS);
+    assert_eq!(target_to_difficulty(genesis >> 4), 16.0);
+    assert_eq!(target_to_difficulty(compact_to_target(0x1b0404cb)), 16_307.420938523983);
+    assert!(target_to_difficulty(U256::MAX) < 1.0);
+    assert_eq!(target_to_difficulty(U256::ZERO), f64::INFINITY);
+}
+
+#[test]
+fn shift_right() {
+    let value = U256::from_u128(0x1234_5678_9abc_def0);
+    assert_eq!(value >> 4, U256::from_u128(0x0123_4567_89ab_cdef));
+    assert_eq!(value >> 68, U256::ZERO);
+    assert_eq!(U256::MAX >> 255, U256::from_u128(1));
+    assert_eq!(U256::MAX >> 256, U256::ZERO);
+}
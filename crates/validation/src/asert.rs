@@ -0,0 +1,49 @@
+//! ASERT difficulty adjustment (the aserti3-2d variant).
+//!
+//! Each block's target is the anchor block's target scaled by
+//! `2^((time_delta - target_block_time * height_delta) / half_life)`: a chain
+//! one half-life behind schedule has twice the anchor target, one half-life
+//! ahead has half. All arithmetic is integer so every node computes the
+//! same target.
+
+use crate::ChainSpec;
+
+/// Easiest target a block may have
+pub const MAX_TARGET: u128 = u128::MAX >> 32;
+
+/// Block every later target is computed from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Anchor {
+    pub height: u64,
+    pub time: u64,
+    pub target: u128,
+}
+
+/// Target for the block at `height`, whose parent has timestamp
+/// `parent_time`. `height` must be above the anchor.
+pub fn next_target(spec: &ChainSpec, anchor: &Anchor, height: u64, parent_time: u64) -> u128 {
+    let time_delta = parent_time as i128 - anchor.time as i128;
+    let height_delta = height.saturating_sub(anchor.height + 1) as i128;
+    let ideal = spec.consensus.target_block_time_secs as i128;
+    let half_life = spec.consensus.asert_half_life_secs.max(1) as i128;
+
+    // 16.16 fixed-point exponent; div_euclid rounds toward negative infinity
+    let exponent = ((time_delta - ideal * height_delta) << 16).div_euclid(half_life);
+    let shifts = exponent >> 16;
+    let frac = (exponent & 0xffff) as u128;
+
+    // Cubic approximation of 2^frac - 1, scaled by 2^16
+    let factor = 65_536
+        + ((195_766_423_245_049 * frac + 971_821_376 * frac * frac + 5_127 * frac * frac * frac + (1 << 47))
+            >> 48);
+
+    let scaled = anchor.target.min(MAX_TARGET) * factor;
+    let shifted = if shifts < 0 {
+        scaled.checked_shr((-shifts).min(128) as u32).unwrap_or(0)
+    } else if scaled.leading_zeros() as i128 > shifts {
+        scaled << shifts
+    } else {
+        return MAX_TARGET;
+    };
+    (shifted >> 16).clamp(1, MAX_TARGET)
+}
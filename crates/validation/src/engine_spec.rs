@@ -0,0 +1,299 @@
+//! The consensus engine's chain spec, and its conversion into [`crate::ChainSpec`].
+//!
+//! The engine describes a chain in more detail than validation needs, with
+//! its own field names. Keeping the engine's spec here, next to the one the
+//! validation crate checks blocks against, keeps the conversion between
+//! them compiled and tested so the two can't drift apart on shared limits.
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Chain specification loaded from chain_spec.toml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChainSpec {
+    pub network: NetworkSpec,
+    pub consensus: ConsensusSpec,
+    pub supply: SupplySpec,
+    pub transaction: TransactionSpec,
+    pub block: BlockSpec,
+    pub cryptography: CryptographySpec,
+    pub fees: FeeSpec,
+    pub mining: MiningSpec,
+    pub governance: GovernanceSpec,
+    pub post_quantum: PostQuantumSpec,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkSpec {
+    pub name: String,
+    pub symbol: String,
+    pub decimals: u8,
+    pub version: String,
+    /// Which chain this is; only regtest may relax consensus rules
+    #[serde(default)]
+    pub kind: crate::NetworkKind,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConsensusSpec {
+    pub algorithm: String,
+    pub hash_function: String,
+    pub target_block_time: u64,
+    pub difficulty_adjustment_period: u64,
+    pub max_difficulty_change: f64,
+    pub genesis_difficulty: u32,
+    /// Header chains proving less cumulative work are not worth syncing
+    #[serde(default)]
+    pub minimum_chain_work: u128,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SupplySpec {
+    pub max_supply: u64,
+    pub initial_reward: u64,
+    pub halving_interval: u64,
+    pub premine: u64,
+    pub inflation_schedule: Vec<InflationEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InflationEntry {
+    pub height: u64,
+    pub reward: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionSpec {
+    pub max_tx_size: usize,
+    pub min_tx_fee: u64,
+    pub dust_threshold: u64,
+    pub max_inputs_per_tx: usize,
+    pub max_outputs_per_tx: usize,
+    pub signature_hash_type: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockSpec {
+    pub max_block_size: usize,
+    pub max_block_weight: usize,
+    pub coinbase_maturity: u64,
+    pub max_reorg_depth: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CryptographySpec {
+    pub address_version: u8,
+    pub private_key_version: u8,
+    pub checksum_algorithm: String,
+    pub signature_scheme: String,
+    pub hash_algorithm: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeeSpec {
+    pub min_relay_fee: u64,
+    pub increment_fee: u64,
+    pub dust_relay_fee: u64,
+    pub max_fee_rate: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MiningSpec {
+    pub coinbase_flags: String,
+    pub extra_nonce_placeholder: usize,
+    pub witness_commitment_pos: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GovernanceSpec {
+    pub bip9_activation_threshold: u64,
+    pub bip9_min_activation_height: u64,
+    pub lock_in_period: u64,
+    pub timeout_period: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PostQuantumSpec {
+    pub signature_algorithm: String,
+    pub public_key_size: usize,
+    pub private_key_size: usize,
+    pub signature_size: usize,
+    pub security_level: u8,
+}
+
+/// A spec value too large for the validation crate's field
+#[derive(Debug, Error)]
+#[error("{field} = {value} is out of range")]
+pub struct SpecRangeError {
+    pub field: &'static str,
+    pub value: String,
+}
+
+/// Narrow a spec value into the validation crate's field type
+fn narrow<T, U>(value: U, field: &'static str) -> Result<T, SpecRangeError>
+where
+    T: TryFrom<U>,
+    U: Copy + std::fmt::Display,
+{
+    T::try_from(value).map_err(|_| SpecRangeError { field, value: value.to_string() })
+}
+
+/// The validation crate's view of this spec, so both check blocks against
+/// the same limits. Rules this spec doesn't describe (ASERT half-life,
+/// RevStop window, sigop limit) take the validation crate's defaults.
+impl TryFrom<&ChainSpec> for crate::ChainSpec {
+    type Error = SpecRangeError;
+
+    fn try_from(spec: &ChainSpec) -> Result<Self, Self::Error> {
+        Ok(crate::ChainSpec {
+            network: crate::Network {
+                name: spec.network.name.clone(),
+                symbol: spec.network.symbol.clone(),
+                decimals: spec.network.decimals,
+                version: spec.network.version.clone(),
+                kind: spec.network.kind,
+                min_sync_peers: crate::DEFAULT_MIN_SYNC_PEERS,
+            },
+            consensus: crate::Consensus {
+                hash_function: spec.consensus.hash_function.clone(),
+                target_block_time_secs: spec.consensus.target_block_time,
+                // Retargets every `difficulty_adjustment_period` blocks
+                difficulty_adjustment: "periodic".to_string(),
+                asert_half_life_secs: crate::DEFAULT_ASERT_HALF_LIFE_SECS,
+                max_sigops_per_block: crate::DEFAULT_MAX_SIGOPS_PER_BLOCK,
+                max_block_weight: spec.block.max_block_weight as u64,
+                no_pow: false,
+                assume_valid: None,
+                minimum_chain_work: (spec.consensus.minimum_chain_work > 0)
+                    .then(|| format!("{:#x}", spec.consensus.minimum_chain_work)),
+            },
+            supply: crate::Supply {
+                max_supply_sats: narrow(spec.supply.max_supply, "supply.max_supply")?,
+                halving_interval_blocks: spec.supply.halving_interval,
+                premine_sats: narrow(spec.supply.premine, "supply.premine")?,
+            },
+            txpolicy: crate::TxPolicy {
+                max_tx_size: narrow(spec.transaction.max_tx_size, "transaction.max_tx_size")?,
+                min_fee_per_kb_sats: narrow(spec.fees.min_relay_fee, "fees.min_relay_fee")?,
+                dust_threshold_sats: narrow(spec.transaction.dust_threshold, "transaction.dust_threshold")?,
+                max_inputs: narrow(spec.transaction.max_inputs_per_tx, "transaction.max_inputs_per_tx")?,
+                max_outputs: narrow(spec.transaction.max_outputs_per_tx, "transaction.max_outputs_per_tx")?,
+                coinbase_maturity: narrow(spec.block.coinbase_maturity, "block.coinbase_maturity")?,
+            },
+            revstop: crate::RevStop { window_blocks: crate::DEFAULT_REVSTOP_WINDOW_BLOCKS },
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec() -> ChainSpec {
+        ChainSpec {
+            network: NetworkSpec {
+                name: "test".to_string(),
+                symbol: "TEST".to_string(),
+                decimals: 8,
+                version: "1.0.0".to_string(),
+                kind: crate::NetworkKind::Regtest,
+            },
+            consensus: ConsensusSpec {
+                algorithm: "proof_of_work".to_string(),
+                hash_function: "blake3".to_string(),
+                target_block_time: 600,
+                difficulty_adjustment_period: 2016,
+                max_difficulty_change: 4.0,
+                genesis_difficulty: 0x1d00ffff,
+                minimum_chain_work: 0,
+            },
+            supply: SupplySpec {
+                max_supply: 22_000_000_00000000,
+                initial_reward: 50_00000000,
+                halving_interval: 210000,
+                premine: 0,
+                inflation_schedule: vec![
+                    InflationEntry { height: 0, reward: 50_00000000 },
+                    InflationEntry { height: 210000, reward: 25_00000000 },
+                ],
+            },
+            transaction: TransactionSpec {
+                max_tx_size: 100000,
+                min_tx_fee: 1000,
+                dust_threshold: 546,
+                max_inputs_per_tx: 1000,
+                max_outputs_per_tx: 1000,
+                signature_hash_type: "dilithium2".to_string(),
+            },
+            block: BlockSpec {
+                max_block_size: 4000000,
+                max_block_weight: 4000000,
+                coinbase_maturity: 100,
+                max_reorg_depth: 6,
+            },
+            cryptography: CryptographySpec {
+                address_version: 0x51,
+                private_key_version: 0x80,
+                checksum_algorithm: "blake3".to_string(),
+                signature_scheme: "dilithium2".to_string(),
+                hash_algorithm: "blake3_256".to_string(),
+            },
+            fees: FeeSpec {
+                min_relay_fee: 1000,
+                increment_fee: 1000,
+                dust_relay_fee: 3000,
+                max_fee_rate: 10000000,
+            },
+            mining: MiningSpec {
+                coinbase_flags: "QuantumCoin/1.0".to_string(),
+                extra_nonce_placeholder: 8,
+                witness_commitment_pos: 0,
+            },
+            governance: GovernanceSpec {
+                bip9_activation_threshold: 1916,
+                bip9_min_activation_height: 0,
+                lock_in_period: 2016,
+                timeout_period: 10080,
+            },
+            post_quantum: PostQuantumSpec {
+                signature_algorithm: "dilithium2".to_string(),
+                public_key_size: 1312,
+                private_key_size: 2528,
+                signature_size: 2420,
+                security_level: 2,
+            },
+        }
+    }
+
+    #[test]
+    fn test_validation_spec_limits_match_consensus_spec() {
+        let spec = spec();
+        let converted = crate::ChainSpec::try_from(&spec).unwrap();
+
+        assert_eq!(converted.txpolicy.max_tx_size, spec.transaction.max_tx_size as u64);
+        assert_eq!(converted.txpolicy.dust_threshold_sats, spec.transaction.dust_threshold as i64);
+        assert_eq!(converted.txpolicy.max_inputs as usize, spec.transaction.max_inputs_per_tx);
+        assert_eq!(converted.txpolicy.max_outputs as usize, spec.transaction.max_outputs_per_tx);
+        assert_eq!(converted.txpolicy.coinbase_maturity as u64, spec.block.coinbase_maturity);
+        assert_eq!(converted.txpolicy.min_fee_per_kb_sats, spec.fees.min_relay_fee as i64);
+        assert_eq!(converted.supply.max_supply_sats, spec.supply.max_supply as i64);
+        assert_eq!(converted.supply.halving_interval_blocks, spec.supply.halving_interval);
+        assert_eq!(converted.consensus.target_block_time_secs, spec.consensus.target_block_time);
+        assert_eq!(converted.consensus.max_block_weight, spec.block.max_block_weight as u64);
+        assert!(converted.pow_required());
+
+        // The network kind carries over rather than defaulting to mainnet
+        assert_eq!(converted.network.kind, crate::NetworkKind::Regtest);
+        let mut mainnet = spec.clone();
+        mainnet.network.kind = crate::NetworkKind::Mainnet;
+        assert_eq!(crate::ChainSpec::try_from(&mainnet).unwrap().network.kind, crate::NetworkKind::Mainnet);
+
+        // Limits the validation crate can't represent are refused, not truncated
+        let mut oversized = spec;
+        oversized.transaction.max_inputs_per_tx = u32::MAX as usize + 1;
+        let err = crate::ChainSpec::try_from(&oversized).unwrap_err();
+        assert_eq!(err.field, "transaction.max_inputs_per_tx");
+        assert!(err.to_string().contains("max_inputs_per_tx"));
+    }
+}
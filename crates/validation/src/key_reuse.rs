@@ -0,0 +1,75 @@
+//! Detection of payments to pubkeys an earlier spend already revealed.
+//!
+//! Spending a P2PQ output publishes a Dilithium signature under its pubkey.
+//! Every further signature under the same key weakens it, so paying to a
+//! key that has already signed a spend is address reuse worth flagging.
+
+use crate::ValidationError;
+use qc_types::*;
+use std::collections::HashSet;
+
+/// What relay does with a transaction that pays to a revealed pubkey
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyReusePolicy {
+    /// Accept it; the caller reports the reused outputs
+    #[default]
+    Warn,
+    Reject,
+}
+
+/// Pubkeys that have signed at least one confirmed spend
+#[derive(Debug, Clone, Default)]
+pub struct RevealedKeys {
+    keys: HashSet<Vec<u8>>,
+}
+
+/// The pubkey an output pays to
+pub fn output_pubkey(kind: &OutputType) -> &[u8] {
+    match kind {
+        OutputType::P2PQ { pubkey } | OutputType::P2PQRevocable { pubkey, .. } => pubkey,
+    }
+}
+
+impl RevealedKeys {
+    /// Record the pubkeys of every output `tx` spends. Call before the spent
+    /// outputs leave the UTXO set `lookup` reads from.
+    pub fn record_spends<FLookup>(&mut self, tx: &Transaction, mut lookup: FLookup)
+    where
+        FLookup: FnMut(&OutPoint) -> Option<(Amount, OutputType, Height, bool)>,
+    {
+        for input in &tx.vin {
+            if let Some((_, kind, _, _)) = lookup(&input.prevout) {
+                self.reveal(output_pubkey(&kind));
+            }
+        }
+    }
+
+    /// Record a pubkey known to have signed a confirmed spend
+    pub fn reveal(&mut self, pubkey: &[u8]) {
+        self.keys.insert(pubkey.to_vec());
+    }
+
+    pub fn is_revealed(&self, pubkey: &[u8]) -> bool {
+        self.keys.contains(pubkey)
+    }
+
+    /// Indices of the outputs of `tx` paying to a revealed pubkey
+    pub fn reused_outputs(&self, tx: &Transaction) -> Vec<usize> {
+        tx.vout
+            .iter()
+            .enumerate()
+            .filter(|(_, out)| self.is_revealed(output_pubkey(&out.kind)))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Relay check: the reused outputs under `Warn`, or `PubkeyReuse` under
+    /// `Reject` when there are any
+    pub fn check_relay(&self, tx: &Transaction, policy: KeyReusePolicy) -> Result<Vec<usize>, ValidationError> {
+        let reused = self.reused_outputs(tx);
+        if policy == KeyReusePolicy::Reject && !reused.is_empty() {
+            return Err(ValidationError::PubkeyReuse);
+        }
+        Ok(reused)
+    }
+}
@@ -1,9 +1,46 @@
 use qc_crypto::{pq_verify, tx_sighash};
 use qc_types::*;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use thiserror::Error;
 use pqcrypto_dilithium::dilithium2::PublicKey;
 
+pub mod asert;
+pub mod engine_spec;
+pub mod key_reuse;
+pub mod sync_status;
+
+pub use key_reuse::{KeyReusePolicy, RevealedKeys};
+
+/// Signature verifications a block may require when the spec sets no limit
+pub const DEFAULT_MAX_SIGOPS_PER_BLOCK: u64 = 20_000;
+
+/// Block weight limit when the spec sets none; signature bytes weigh a
+/// quarter of every other byte
+pub const DEFAULT_MAX_BLOCK_WEIGHT: u64 = 4_000_000;
+
+/// ASERT half-life for specs that describe another retargeting scheme
+pub const DEFAULT_ASERT_HALF_LIFE_SECS: u64 = 30 * 24 * 60 * 60;
+
+/// RevStop cancel window for specs that don't set one
+pub const DEFAULT_REVSTOP_WINDOW_BLOCKS: u32 = 30;
+
+/// Peers that must agree on our tip before sync counts as complete, for
+/// specs that don't set it
+pub const DEFAULT_MIN_SYNC_PEERS: usize = 3;
+
+fn default_max_block_weight() -> u64 {
+    DEFAULT_MAX_BLOCK_WEIGHT
+}
+
+fn default_max_sigops_per_block() -> u64 {
+    DEFAULT_MAX_SIGOPS_PER_BLOCK
+}
+
+fn default_min_sync_peers() -> usize {
+    DEFAULT_MIN_SYNC_PEERS
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct ChainSpec {
     pub network: Network,
@@ -19,6 +56,22 @@ pub struct Network {
     pub symbol: String,
     pub decimals: u8,
     pub version: String,
+    #[serde(default)]
+    pub kind: NetworkKind,
+    /// Peers that must report our tip height before the node calls itself
+    /// synced, so one lying or partitioned peer can't make it look healthy
+    #[serde(default = "default_min_sync_peers")]
+    pub min_sync_peers: usize,
+}
+
+/// Which chain a spec describes. Only regtest may relax consensus rules.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkKind {
+    #[default]
+    Mainnet,
+    Testnet,
+    Regtest,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -27,6 +80,26 @@ pub struct Consensus {
     pub target_block_time_secs: u64,
     pub difficulty_adjustment: String,
     pub asert_half_life_secs: u64,
+    /// Most Dilithium verifications a block may require, so a block of
+    /// many-input transactions can't stall validation within the size limits
+    #[serde(default = "default_max_sigops_per_block")]
+    pub max_sigops_per_block: u64,
+    /// Most weight a block may carry, with signatures discounted as
+    /// witness data (see `qc_types::WITNESS_SCALE_FACTOR`)
+    #[serde(default = "default_max_block_weight")]
+    pub max_block_weight: u64,
+    /// Skip proof-of-work checks so test blocks need no mining. Ignored
+    /// unless the network is regtest.
+    #[serde(default)]
+    pub no_pow: bool,
+    /// Hex hash of a block trusted to have valid signatures. Blocks leading
+    /// up to it skip Dilithium verification; everything else is checked.
+    #[serde(default)]
+    pub assume_valid: Option<String>,
+    /// Hex cumulative work a peer's header chain must prove before the node
+    /// indexes it, so cheap low-work headers can't be used to spam it
+    #[serde(default)]
+    pub minimum_chain_work: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -51,6 +124,26 @@ pub struct RevStop {
     pub window_blocks: u32 
 }
 
+impl ChainSpec {
+    /// Whether blocks must meet their target. Always true off regtest,
+    /// whatever `no_pow` says.
+    pub fn pow_required(&self) -> bool {
+        !(self.consensus.no_pow && self.network.kind == NetworkKind::Regtest)
+    }
+
+    /// The assume-valid block hash, if one is set and parses
+    pub fn assume_valid(&self) -> Option<Hash32> {
+        self.consensus.assume_valid.as_deref().and_then(|hex| Hash32::from_hex(hex).ok())
+    }
+
+    /// Least chainwork a header chain must reach; zero when unset
+    pub fn minimum_chain_work(&self) -> u128 {
+        self.consensus.minimum_chain_work.as_deref()
+            .and_then(|hex| u128::from_str_radix(hex.trim_start_matches("0x"), 16).ok())
+            .unwrap_or(0)
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum ValidationError {
     #[error("tx too large")] TxTooLarge,
@@ -62,22 +155,23 @@ pub enum ValidationError {
     #[error("revstop cancel outside window")] CancelOutsideWindow,
     #[error("revstop misuse")] RevstopMisuse,
     #[error("coinbase immature")] CoinbaseImmature,
+    #[error("duplicate input")] DuplicateInput,
+    #[error("negative output value")] NegativeOutput,
+    #[error("amount overflow")] AmountOverflow,
+    #[error("genesis premine differs from spec")] PremineMismatch,
+    #[error("supply does not reconcile with max supply")] SupplyMismatch,
+    #[error("pays to a pubkey already revealed by a spend")] PubkeyReuse,
+    #[error("block exceeds signature operation limit")] TooManySigops,
+    #[error("block exceeds weight limit")] BlockTooHeavy,
 }
 
-fn encode_tx_skeleton(tx: &Transaction) -> Vec<u8> {
-    let mut tmp = tx.clone();
-    for i in &mut tmp.vin { 
-        i.pq_signature.clear(); 
-        i.cancel = false; 
-    }
-    bincode::serialize(&tmp).expect("serialize")
-}
-
+/// Era-0 subsidy such that `eras` halvings emit everything the premine
+/// leaves under the cap: s0 * blocks * (2 - 2^(1-eras)) = cap - premine
 pub fn initial_subsidy_sats(spec: &ChainSpec, eras: u32) -> i64 {
     let blocks_per_era = spec.supply.halving_interval_blocks as i128;
-    let cap = spec.supply.max_supply_sats as i128;
+    let emitted = (spec.supply.max_supply_sats - spec.supply.premine_sats) as i128;
     let two_pow_eras = 1i128 << eras;
-    let s0 = cap * two_pow_eras / (blocks_per_era * (two_pow_eras - 1));
+    let s0 = emitted * two_pow_eras / (2 * blocks_per_era * (two_pow_eras - 1));
     s0 as i64
 }
 
@@ -88,11 +182,88 @@ pub fn block_subsidy(spec: &ChainSpec, height: u64) -> i64 {
     if sub < 0 { 0 } else { sub }
 }
 
+/// Everything the subsidy schedule pays out, genesis included
+pub fn total_subsidy_emission(spec: &ChainSpec) -> i128 {
+    let hal = spec.supply.halving_interval_blocks;
+    (0..64u64)
+        .map(|era| block_subsidy(spec, era * hal) as i128)
+        .take_while(|sub| *sub > 0)
+        .map(|sub| sub * hal as i128)
+        .sum()
+}
+
+/// How premine and scheduled emission add up against `max_supply_sats`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SupplyReconciliation {
+    pub premine_sats: i128,
+    pub emission_sats: i128,
+    /// Cap minus premine and emission: what the integer halvings round away
+    pub shortfall_sats: i128,
+}
+
+/// Check a genesis premine (genesis outputs beyond the height-0 subsidy)
+/// against the spec. Each halving floors the subsidy, losing under one sat
+/// per block per era, so the emission may fall short of the cap by less than
+/// `64 * halving_interval_blocks` sats but never exceed it.
+pub fn reconcile_supply(spec: &ChainSpec, premine_sats: i64) -> Result<SupplyReconciliation, ValidationError> {
+    if premine_sats != spec.supply.premine_sats {
+        return Err(ValidationError::PremineMismatch);
+    }
+    let premine_sats = premine_sats as i128;
+    let emission_sats = total_subsidy_emission(spec);
+    let shortfall_sats = spec.supply.max_supply_sats as i128 - premine_sats - emission_sats;
+    if !(0..64 * spec.supply.halving_interval_blocks as i128).contains(&shortfall_sats) {
+        return Err(ValidationError::SupplyMismatch);
+    }
+    Ok(SupplyReconciliation { premine_sats, emission_sats, shortfall_sats })
+}
+
+/// Signature verifications validating `txs` takes: one per input
+pub fn block_sigops(txs: &[Transaction]) -> u64 {
+    txs.iter().map(|tx| tx.vin.len() as u64).sum()
+}
+
+/// Reject a block needing more than `max_sigops_per_block` verifications.
+/// Cheap, so run it before verifying any signature.
+pub fn check_block_sigops(spec: &ChainSpec, txs: &[Transaction]) -> Result<u64, ValidationError> {
+    let sigops = block_sigops(txs);
+    if sigops > spec.consensus.max_sigops_per_block {
+        return Err(ValidationError::TooManySigops);
+    }
+    Ok(sigops)
+}
+
+/// Reject a block weighing more than `max_block_weight`
+pub fn check_block_weight(spec: &ChainSpec, block: &Block) -> Result<u64, ValidationError> {
+    let weight = block.weight();
+    if weight > spec.consensus.max_block_weight {
+        return Err(ValidationError::BlockTooHeavy);
+    }
+    Ok(weight)
+}
+
 pub fn validate_transaction<FLookup>(
     spec: &ChainSpec,
     height_now: u64,
     tx: &Transaction,
     is_coinbase: bool,
+    lookup: FLookup
+) -> Result<(), ValidationError>
+where
+    FLookup: FnMut(&OutPoint) -> Option<(Amount, OutputType, Height, bool)>
+{
+    validate_transaction_with(spec, height_now, tx, is_coinbase, true, lookup)
+}
+
+/// `validate_transaction`, optionally without the Dilithium verifications.
+/// Only skip them for blocks an assume-valid hash vouches for; every other
+/// rule still applies.
+pub fn validate_transaction_with<FLookup>(
+    spec: &ChainSpec,
+    height_now: u64,
+    tx: &Transaction,
+    is_coinbase: bool,
+    verify_signatures: bool,
     mut lookup: FLookup
 ) -> Result<(), ValidationError>
 where
@@ -105,16 +276,22 @@ where
         return Err(ValidationError::CountLimit);
     }
     for o in &tx.vout {
+        if o.value < 0 { return Err(ValidationError::NegativeOutput); }
         if o.value < spec.txpolicy.dust_threshold_sats { return Err(ValidationError::Dust); }
     }
+    let sum_out = tx.total_output_value().ok_or(ValidationError::AmountOverflow)?;
+
+    // an outpoint may only be spent once, even within the same transaction
+    let mut seen = HashSet::with_capacity(tx.vin.len());
+    for input in &tx.vin {
+        if !seen.insert(&input.prevout) { return Err(ValidationError::DuplicateInput); }
+    }
 
     if is_coinbase { return Ok(()); }
 
-    let mut sum_in: i128 = 0;
-    let sum_out: i128 = tx.vout.iter().map(|o| o.value as i128).sum();
+    let mut sum_in: Amount = 0;
 
-    let skeleton = encode_tx_skeleton(tx);
-    let sighash = tx_sighash(&skeleton);
+    let sighash = tx_sighash(&tx.signing_skeleton());
 
     for input in &tx.vin {
         let Some((val, out_type, created_height, was_coinbase)) = lookup(&input.prevout) else {
@@ -127,10 +304,11 @@ where
             }
         }
 
+        let verify = |pubkey: &Vec<u8>| !verify_signatures || pq_verify_pub(pubkey, &sighash, &input.pq_signature);
         match &out_type {
             OutputType::P2PQ { pubkey } => {
                 if input.cancel { return Err(ValidationError::RevstopMisuse); }
-                if !pq_verify_pub(pubkey, &sighash, &input.pq_signature) {
+                if !verify(pubkey) {
                     return Err(ValidationError::BadSignature);
                 }
             }
@@ -138,18 +316,19 @@ where
                 let age = height_now.saturating_sub(created_height);
                 if input.cancel {
                     if age > *window_blocks as u64 { return Err(ValidationError::CancelOutsideWindow); }
-                    if !pq_verify_pub(pubkey, &sighash, &input.pq_signature) {
+                    if !verify(pubkey) {
                         return Err(ValidationError::BadSignature);
                     }
                 } else {
-                    if !pq_verify_pub(pubkey, &sighash, &input.pq_signature) {
+                    if !verify(pubkey) {
                         return Err(ValidationError::BadSignature);
                     }
                 }
             }
         }
 
-        sum_in += val as i128;
+        if val < 0 { return Err(ValidationError::NegativeOutput); }
+        sum_in = sum_in.checked_add(val).ok_or(ValidationError::AmountOverflow)?;
     }
 
     if sum_in < sum_out { return Err(ValidationError::InsufficientFunds); }
@@ -163,6 +342,13 @@ fn pq_verify_pub(pubkey: &Vec<u8>, sighash: &[u8;32], sig: &Vec<u8>) -> bool {
     }
 }
 
+/// Merkle root of a block with no transactions
+pub const EMPTY_MERKLE_ROOT: Hash32 = Hash32([0u8; 32]);
+
+/// Bitcoin-style merkle root. Leaves are the SHA-256 of each transaction's
+/// canonical encoding; each level hashes adjacent pairs, pairing an odd last
+/// node with itself. A single transaction's root is its leaf hash, and an
+/// empty list gives `EMPTY_MERKLE_ROOT`.
 pub fn merkle_root(txs: &[Transaction]) -> Hash32 {
     use sha2::{Digest, Sha256};
     fn h(bytes: &[u8]) -> [u8;32] {
@@ -171,8 +357,8 @@ pub fn merkle_root(txs: &[Transaction]) -> Hash32 {
         let out = sh.finalize();
         let mut arr = [0u8;32]; arr.copy_from_slice(&out); arr
     }
-    let mut layer: Vec<[u8;32]> = txs.iter().map(|t| h(&bincode::serialize(t).unwrap())).collect();
-    if layer.is_empty() { return Hash32::zero(); }
+    let mut layer: Vec<[u8;32]> = txs.iter().map(|t| h(&qc_types::canonical::encode(t))).collect();
+    if layer.is_empty() { return EMPTY_MERKLE_ROOT; }
     while layer.len() > 1 {
         let mut next = vec![];
         for i in (0..layer.len()).step_by(2) {
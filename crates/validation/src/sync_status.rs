@@ -0,0 +1,57 @@
+//! When a node may call itself synced, from the tip heights its peers report.
+//!
+//! Being caught up with the best known height is not enough: a node fed by a
+//! single peer, or cut off with a few of them, could be shown any chain. Sync
+//! only counts as complete once `network.min_sync_peers` peers report our tip.
+
+use serde::Serialize;
+
+/// Fraction of the best known height we must hold to count as caught up
+pub const SYNC_COMPLETE_PROGRESS: f64 = 0.99;
+
+/// What a node reports about its own sync state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncStatus {
+    /// Caught up, with enough peers reporting our tip
+    Healthy,
+    /// Behind the height our peers report
+    Syncing,
+    /// Apparently caught up, but too few peers agree to be sure
+    Degraded,
+}
+
+/// Height to sync toward: the median of `peer_heights`, never below `ours`.
+/// Taking the lower median means a minority of peers claiming huge heights
+/// cannot move the target past the honest ones.
+pub fn best_known_height(ours: u64, peer_heights: &[u64]) -> u64 {
+    if peer_heights.is_empty() {
+        return ours;
+    }
+    let mut heights = peer_heights.to_vec();
+    heights.sort_unstable();
+    heights[(heights.len() - 1) / 2].max(ours)
+}
+
+/// Share of the best known height we hold, from 0.0 to 1.0
+pub fn sync_progress(ours: u64, peer_heights: &[u64]) -> f64 {
+    let target = best_known_height(ours, peer_heights);
+    if target == 0 {
+        return 1.0;
+    }
+    ours as f64 / target as f64
+}
+
+/// Syncing while behind the best known height; once caught up, healthy only
+/// if at least `min_sync_peers` of `peer_heights` equal our tip height
+pub fn sync_status(ours: u64, peer_heights: &[u64], min_sync_peers: usize) -> SyncStatus {
+    if sync_progress(ours, peer_heights) < SYNC_COMPLETE_PROGRESS {
+        return SyncStatus::Syncing;
+    }
+    let agreeing = peer_heights.iter().filter(|&&h| h == ours).count();
+    if agreeing < min_sync_peers {
+        SyncStatus::Degraded
+    } else {
+        SyncStatus::Healthy
+    }
+}
@@ -0,0 +1,42 @@
+use qc_validation::*;
+use qc_types::*;
+
+fn spec() -> ChainSpec { 
+    toml::from_str(include_str!("../../../chain_spec.toml")).unwrap() 
+}
+
+fn no_utxos(_: &OutPoint) -> Option<(Amount, OutputType, Height, bool)> { None }
+
+#[test]
+fn outputs_overflowing_amount_rejected() {
+    let spec = spec();
+    let half = Amount::MAX / 2 + 1;
+    let tx = Transaction::new(
+        1,
+        vec![TxIn::new(OutPoint::new(Hash32::zero(), 0), vec![], false)],
+        vec![TxOut::new_p2pq(half, vec![]), TxOut::new_p2pq(half, vec![])],
+        0,
+    );
+
+    let res = validate_transaction(&spec, 200, &tx, false, no_utxos);
+    assert!(matches!(res, Err(ValidationError::AmountOverflow)));
+
+    // Coinbase outputs are summed too
+    let coinbase = Transaction::new(1, vec![], tx.vout.clone(), 0);
+    let res = validate_transaction(&spec, 200, &coinbase, true, no_utxos);
+    assert!(matches!(res, Err(ValidationError::AmountOverflow)));
+}
+
+#[test]
+fn negative_output_rejected() {
+    let spec = spec();
+    let tx = Transaction::new(
+        1,
+        vec![TxIn::new(OutPoint::new(Hash32::zero(), 0), vec![], false)],
+        vec![TxOut::new_p2pq(10_000, vec![]), TxOut::new_p2pq(-5_000, vec![])],
+        0,
+    );
+
+    let res = validate_transaction(&spec, 200, &tx, false, no_utxos);
+    assert!(matches!(res, Err(ValidationError::NegativeOutput)));
+}
@@ -1,8 +1,15 @@
-#[test]
-fn placeholder_asert_sanity() {
-    // Placeholder test for ASERT difficulty adjustment
-    // TODO: Implement proper ASERT algorithm tests
-    assert!(true);
+use qc_validation::asert::{next_target, Anchor, MAX_TARGET};
+use qc_validation::ChainSpec;
+
+fn spec() -> ChainSpec {
+    toml::from_str(include_str!("../../../chain_spec.toml")).unwrap()
+}
+
+/// Target with plenty of room to move in both directions
+const ANCHOR_TARGET: u128 = MAX_TARGET >> 24;
+
+fn anchor() -> Anchor {
+    Anchor { height: 0, time: 1_700_000_000, target: ANCHOR_TARGET }
 }
 
 #[test]
@@ -11,21 +18,147 @@ fn asert_basic_properties() {
     // - If blocks come faster, difficulty increases
     // - If blocks come slower, difficulty decreases
     // - Changes are proportional to time deviation
-    
-    // This is a placeholder until ASERT is fully implemented
-    let target_time = 600; // 10 minutes
-    let fast_time = 300;   // 5 minutes (too fast)
-    let slow_time = 1200;  // 20 minutes (too slow)
-    
-    assert!(fast_time < target_time);
-    assert!(slow_time > target_time);
-    
-    // TODO: Test actual ASERT algorithm when implemented
+    let spec = spec();
+    let a = anchor();
+    let target_time = spec.consensus.target_block_time_secs;
+    let half_life = spec.consensus.asert_half_life_secs;
+
+    // Parent of block 11 is 10 blocks after the anchor
+    let on_schedule = next_target(&spec, &a, 11, a.time + 10 * target_time);
+    assert_eq!(on_schedule, ANCHOR_TARGET);
+
+    let fast = next_target(&spec, &a, 11, a.time + 10 * target_time / 2);
+    let slow = next_target(&spec, &a, 11, a.time + 10 * target_time * 2);
+    assert!(fast < ANCHOR_TARGET);
+    assert!(slow > ANCHOR_TARGET);
+
+    // A full half-life off schedule halves or doubles the target
+    let behind = next_target(&spec, &a, 11, a.time + 10 * target_time + half_life);
+    let ahead = next_target(&spec, &a, 11, a.time + 10 * target_time - half_life);
+    assert_eq!(behind, ANCHOR_TARGET * 2);
+    assert_eq!(ahead, ANCHOR_TARGET / 2);
+}
+
+#[test]
+fn asert_extremes_stay_in_range() {
+    let spec = spec();
+    let a = anchor();
+    assert_eq!(next_target(&spec, &a, 2, a.time + 1_000 * spec.consensus.asert_half_life_secs), MAX_TARGET);
+    assert_eq!(next_target(&spec, &a, 1_000_000_000, a.time), 1);
+}
+
+/// xorshift64*, so the simulated chain is identical on every run
+struct Rng(u64);
+
+impl Rng {
+    fn unit(&mut self) -> f64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let x = self.0.wrapping_mul(0x2545_f491_4f6c_dd1d);
+        // (0, 1], so ln() below stays finite
+        ((x >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Mines a simulated chain under ASERT, one block at a time
+struct Sim<'a> {
+    spec: &'a ChainSpec,
+    anchor: Anchor,
+    rng: Rng,
+    height: u64,
+    time: u64,
+    /// (timestamp, interval since parent) of every block mined
+    blocks: Vec<(u64, u64)>,
+}
+
+impl<'a> Sim<'a> {
+    fn new(spec: &'a ChainSpec) -> Self {
+        let anchor = anchor();
+        Sim { spec, anchor, rng: Rng(0x4153_4552_5421), height: 0, time: anchor.time, blocks: Vec::new() }
+    }
+
+    /// Hashes per second that find a block every target interval at the anchor target
+    fn base_hashrate(&self) -> f64 {
+        (MAX_TARGET / ANCHOR_TARGET) as f64 / self.spec.consensus.target_block_time_secs as f64
+    }
+
+    /// Mine at `hashrate` until `duration` seconds of block time have passed
+    fn mine_for(&mut self, hashrate: f64, duration: u64) {
+        let end = self.time + duration;
+        while self.time < end {
+            let target = next_target(self.spec, &self.anchor, self.height + 1, self.time);
+            let expected = (MAX_TARGET / target) as f64 / hashrate;
+            // Exponentially distributed solve time, at least a second
+            let interval = ((-self.rng.unit().ln() * expected).round() as u64).max(1);
+            self.height += 1;
+            self.time += interval;
+            self.blocks.push((self.time, interval));
+        }
+    }
+
+    /// Mean relative interval error |mean/target - 1| over each of `windows`
+    /// consecutive half-lives starting at `start`
+    fn window_errors(&self, start: u64, windows: u64) -> Vec<f64> {
+        let half_life = self.spec.consensus.asert_half_life_secs;
+        let target_time = self.spec.consensus.target_block_time_secs as f64;
+        (0..windows)
+            .map(|k| {
+                let (lo, hi) = (start + k * half_life, start + (k + 1) * half_life);
+                let intervals: Vec<u64> =
+                    self.blocks.iter().filter(|(t, _)| *t > lo && *t <= hi).map(|(_, i)| *i).collect();
+                let mean = intervals.iter().sum::<u64>() as f64 / intervals.len() as f64;
+                (mean / target_time - 1.0).abs()
+            })
+            .collect()
+    }
+}
+
+/// Slack for the sampling noise in a window's mean interval
+const SAMPLING_NOISE: f64 = 0.05;
+
+/// ASERT should close at least this fraction of the remaining error per
+/// half-life; the continuous-time ideal is one half
+const MAX_ERROR_RATIO_PER_HALF_LIFE: f64 = 0.65;
+
+fn assert_converges(errors: &[f64], label: &str) {
+    for pair in errors.windows(2) {
+        assert!(
+            pair[1] <= pair[0] * MAX_ERROR_RATIO_PER_HALF_LIFE + SAMPLING_NOISE,
+            "{}: error went {:.3} -> {:.3} over one half-life ({:?})",
+            label, pair[0], pair[1], errors
+        );
+    }
+    let last = *errors.last().unwrap();
+    assert!(last < 2.0 * SAMPLING_NOISE, "{}: still {:.3} off target ({:?})", label, last, errors);
 }
 
 #[test]
 fn asert_convergence() {
     // ASERT should converge to target time over multiple adjustments
-    // TODO: Implement when ASERT algorithm is ready
-    assert!(true);
+    let spec = spec();
+    let half_life = spec.consensus.asert_half_life_secs;
+    let mut sim = Sim::new(&spec);
+    let base = sim.base_hashrate();
+
+    // Steady hashrate holds the target interval
+    sim.mine_for(base, half_life);
+    let steady = sim.window_errors(sim.anchor.time, 1);
+    assert!(steady[0] < 0.05, "steady state off by {:.3}", steady[0]);
+
+    // 10x hashrate jump: blocks come fast, then the target tightens
+    let jump_at = sim.time;
+    sim.mine_for(base * 10.0, 7 * half_life);
+    let jump = sim.window_errors(jump_at, 7);
+    assert!(jump[0] > 0.3, "jump barely registered: {:?}", jump);
+    assert_converges(&jump, "10x jump");
+
+    // Back to the original hashrate: blocks come slow, then the target loosens
+    let drop_at = sim.time;
+    sim.mine_for(base, 10 * half_life);
+    let drop = sim.window_errors(drop_at, 10);
+    assert!(drop[0] > 1.0, "drop barely registered: {:?}", drop);
+    assert_converges(&drop, "10x drop");
+
+    assert!(sim.blocks.len() > 10_000, "only {} blocks simulated", sim.blocks.len());
 }
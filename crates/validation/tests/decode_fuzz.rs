@@ -0,0 +1,198 @@
+//! Deterministic fuzzing of the untrusted transaction decode path.
+//!
+//! Random and mutated byte strings go through `qc_types::codec::decode` and,
+//! when that succeeds, `validate_transaction`. Neither may panic, no single
+//! allocation may exceed `MAX_ALLOCATION`, and every case must finish within
+//! `CASE_TIME_BUDGET`. The seed is fixed so failures reproduce exactly.
+
+use qc_types::*;
+use qc_validation::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+/// Largest single allocation a decode of untrusted bytes may make
+const MAX_ALLOCATION: usize = 2 * 1024 * 1024;
+const CASE_TIME_BUDGET: Duration = Duration::from_secs(1);
+const RANDOM_CASES: usize = 5_000;
+const MUTATION_CASES: usize = 5_000;
+const SEED: u64 = 0x5143_4655_5a5a_0001;
+
+/// Byte offsets of the length prefixes in a one-input, one-output encoding
+const VIN_LEN_OFFSET: usize = 4;
+const SIG_LEN_OFFSET: usize = VIN_LEN_OFFSET + 8 + 32 + 4;
+
+struct LargestAllocation;
+
+thread_local! {
+    static LARGEST: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for LargestAllocation {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = LARGEST.try_with(|l| l.set(l.get().max(layout.size())));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = LARGEST.try_with(|l| l.set(l.get().max(new_size)));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: LargestAllocation = LargestAllocation;
+
+/// xorshift64*, enough to spread cases without pulling in a fuzzing crate
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next() as u8).collect()
+    }
+}
+
+fn spec() -> ChainSpec {
+    toml::from_str(include_str!("../../../chain_spec.toml")).unwrap()
+}
+
+fn sample_tx() -> Vec<u8> {
+    let tx = Transaction::new(
+        1,
+        vec![TxIn::new(OutPoint::new(Hash32([7u8; 32]), 0), vec![9u8; 16], false)],
+        vec![TxOut::new_p2pq(10_000, vec![3u8; 16])],
+        0,
+    );
+    bincode::serialize(&tx).unwrap()
+}
+
+fn with_u64_at(mut bytes: Vec<u8>, offset: usize, value: u64) -> Vec<u8> {
+    bytes[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    bytes
+}
+
+/// Decode and, if that succeeds, validate `bytes`, failing on excessive
+/// allocation or time. Returns whether the bytes decoded.
+fn run_case(spec: &ChainSpec, bytes: &[u8]) -> bool {
+    LARGEST.with(|l| l.set(0));
+    let start = Instant::now();
+
+    let decoded = codec::decode::<Transaction>(bytes);
+    if let Ok(tx) = &decoded {
+        // Exercise both a missing prevout and one with an unusable key
+        let _ = validate_transaction(spec, 1_000, tx, false, |_| None);
+        let _ = validate_transaction(spec, 1_000, tx, false, |_| {
+            Some((20_000, OutputType::P2PQ { pubkey: vec![0u8; 8] }, 1, false))
+        });
+        let _ = validate_transaction(spec, 1_000, tx, true, |_| None);
+    }
+
+    let elapsed = start.elapsed();
+    let largest = LARGEST.with(|l| l.get());
+    assert!(elapsed < CASE_TIME_BUDGET, "case took {:?}: {:02x?}", elapsed, bytes);
+    assert!(largest <= MAX_ALLOCATION, "case allocated {} bytes: {:02x?}", largest, bytes);
+    decoded.is_ok()
+}
+
+/// Named malformed inputs that must fail to decode
+fn corpus() -> Vec<(&'static str, Vec<u8>)> {
+    let valid = sample_tx();
+    let mut cases = vec![
+        ("empty", Vec::new()),
+        ("version only", valid[..4].to_vec()),
+        ("truncated vin length", valid[..VIN_LEN_OFFSET + 3].to_vec()),
+        ("truncated prevout", valid[..VIN_LEN_OFFSET + 8 + 20].to_vec()),
+        ("truncated signature", valid[..SIG_LEN_OFFSET + 8 + 4].to_vec()),
+        ("missing lock time", valid[..valid.len() - 1].to_vec()),
+        ("max vin length", with_u64_at(valid.clone(), VIN_LEN_OFFSET, u64::MAX)),
+        ("billions of inputs", with_u64_at(valid.clone(), VIN_LEN_OFFSET, 4_000_000_000)),
+        ("max signature length", with_u64_at(valid.clone(), SIG_LEN_OFFSET, u64::MAX)),
+        ("gigabyte signature", with_u64_at(valid.clone(), SIG_LEN_OFFSET, 1 << 30)),
+        ("all ones", vec![0xff; 64]),
+    ];
+
+    // Huge prefix at the output count, whose offset follows the input
+    let vout_len_offset = SIG_LEN_OFFSET + 8 + 16 + 1;
+    cases.push(("max vout length", with_u64_at(valid.clone(), vout_len_offset, u64::MAX)));
+    // Unknown output type tag
+    let mut bad_tag = valid;
+    bad_tag[vout_len_offset + 8 + 8..vout_len_offset + 8 + 12].copy_from_slice(&7u32.to_le_bytes());
+    cases.push(("unknown output kind", bad_tag));
+    cases
+}
+
+#[test]
+fn sample_encoding_layout() {
+    let valid = sample_tx();
+    assert_eq!(valid[VIN_LEN_OFFSET..VIN_LEN_OFFSET + 8], 1u64.to_le_bytes());
+    assert_eq!(valid[SIG_LEN_OFFSET..SIG_LEN_OFFSET + 8], 16u64.to_le_bytes());
+    assert!(run_case(&spec(), &valid));
+}
+
+#[test]
+fn malformed_corpus_rejected() {
+    let spec = spec();
+    for (name, bytes) in corpus() {
+        assert!(!run_case(&spec, &bytes), "{} decoded", name);
+    }
+}
+
+#[test]
+fn random_bytes_never_panic() {
+    let spec = spec();
+    let mut rng = Rng(SEED);
+    for _ in 0..RANDOM_CASES {
+        let len = rng.below(512);
+        let bytes = rng.bytes(len);
+        run_case(&spec, &bytes);
+    }
+}
+
+#[test]
+fn mutated_transactions_never_panic() {
+    let spec = spec();
+    let valid = sample_tx();
+    let mut rng = Rng(SEED ^ 0xffff);
+    for _ in 0..MUTATION_CASES {
+        let mut bytes = valid.clone();
+        match rng.below(4) {
+            0 => {
+                let i = rng.below(bytes.len());
+                bytes[i] ^= 1 << rng.below(8);
+            }
+            1 => bytes.truncate(rng.below(bytes.len())),
+            2 => {
+                // Any eight-byte window may be a length prefix
+                let at = rng.below(bytes.len() - 7);
+                let value = match rng.below(3) {
+                    0 => u64::MAX,
+                    1 => 1 << (32 + rng.below(31)),
+                    _ => rng.next(),
+                };
+                bytes = with_u64_at(bytes, at, value);
+            }
+            _ => {
+                let at = rng.below(bytes.len());
+                let len = rng.below(32);
+                let extra = rng.bytes(len);
+                bytes.splice(at..at, extra);
+            }
+        }
+        run_case(&spec, &bytes);
+    }
+}
@@ -0,0 +1,52 @@
+use qc_crypto::{generate_keypair, pq_sign, tx_sighash};
+use qc_validation::*;
+use qc_types::*;
+use pqcrypto_traits::sign::PublicKey as _;
+use std::collections::HashMap;
+
+fn spec() -> ChainSpec { 
+    toml::from_str(include_str!("../../../chain_spec.toml")).unwrap() 
+}
+
+fn sign_all(tx: &mut Transaction, sk: &pqcrypto_dilithium::dilithium2::SecretKey) {
+    let sighash = tx_sighash(&tx.signing_skeleton());
+    let sig = pq_sign(sk, &sighash);
+    for i in &mut tx.vin {
+        i.pq_signature = sig.clone();
+    }
+}
+
+#[test]
+fn duplicate_inputs_rejected() {
+    let spec = spec();
+    let (pk, sk) = generate_keypair();
+    let pk = pk.as_bytes().to_vec();
+
+    let a = OutPoint::new(Hash32([1u8; 32]), 0);
+    let b = OutPoint::new(Hash32([1u8; 32]), 1);
+    let mut utxo = HashMap::<OutPoint, (Amount, OutputType, Height, bool)>::new();
+    utxo.insert(a.clone(), (10_000, OutputType::P2PQ { pubkey: pk.clone() }, 100, false));
+    utxo.insert(b.clone(), (10_000, OutputType::P2PQ { pubkey: pk.clone() }, 100, false));
+    let lookup = |op: &OutPoint| utxo.get(op).cloned();
+
+    // Same outpoint listed twice would otherwise count its value twice
+    let mut dup = Transaction::new(
+        1,
+        vec![TxIn::new(a.clone(), vec![], false), TxIn::new(a.clone(), vec![], false)],
+        vec![TxOut::new_p2pq(15_000, pk.clone())],
+        0,
+    );
+    sign_all(&mut dup, &sk);
+    let res = validate_transaction(&spec, 200, &dup, false, lookup);
+    assert!(matches!(res, Err(ValidationError::DuplicateInput)));
+
+    // Distinct outpoints pass
+    let mut ok = Transaction::new(
+        1,
+        vec![TxIn::new(a, vec![], false), TxIn::new(b, vec![], false)],
+        vec![TxOut::new_p2pq(15_000, pk)],
+        0,
+    );
+    sign_all(&mut ok, &sk);
+    assert!(validate_transaction(&spec, 200, &ok, false, lookup).is_ok());
+}
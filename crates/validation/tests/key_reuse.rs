@@ -0,0 +1,46 @@
+use qc_validation::*;
+use qc_types::*;
+use std::collections::HashMap;
+
+fn pubkey(tag: u8) -> Vec<u8> {
+    vec![tag; 1312]
+}
+
+#[test]
+fn payment_to_spent_pubkey_flagged_as_reuse() {
+    let funding = OutPoint::new(Hash32([1u8; 32]), 0);
+    let mut utxo = HashMap::<OutPoint, (Amount, OutputType, Height, bool)>::new();
+    utxo.insert(funding.clone(), (10_000, OutputType::P2PQ { pubkey: pubkey(1) }, 100, false));
+
+    let payment_to_one = Transaction::new(1, vec![], vec![TxOut::new_p2pq(5_000, pubkey(1))], 0);
+    let mut revealed = RevealedKeys::default();
+    assert!(revealed.reused_outputs(&payment_to_one).is_empty());
+
+    // Spending the output publishes a signature under pubkey 1
+    let spend = Transaction::new(
+        1,
+        vec![TxIn::new(funding, vec![], false)],
+        vec![TxOut::new_p2pq(9_000, pubkey(2))],
+        0,
+    );
+    revealed.record_spends(&spend, |op| utxo.get(op).cloned());
+    assert!(revealed.is_revealed(&pubkey(1)));
+    assert!(!revealed.is_revealed(&pubkey(2)));
+
+    let pays_back = Transaction::new(
+        1,
+        vec![TxIn::new(OutPoint::new(Hash32([2u8; 32]), 0), vec![], false)],
+        vec![TxOut::new_p2pq(4_000, pubkey(3)), TxOut::new_revocable(4_000, pubkey(1), 30)],
+        0,
+    );
+    assert_eq!(revealed.reused_outputs(&pays_back), vec![1]);
+    assert_eq!(revealed.check_relay(&pays_back, KeyReusePolicy::Warn).unwrap(), vec![1]);
+    assert!(matches!(
+        revealed.check_relay(&pays_back, KeyReusePolicy::Reject),
+        Err(ValidationError::PubkeyReuse)
+    ));
+
+    // Fresh keys pass either way
+    let fresh = Transaction::new(1, vec![], vec![TxOut::new_p2pq(4_000, pubkey(3))], 0);
+    assert!(revealed.check_relay(&fresh, KeyReusePolicy::Reject).unwrap().is_empty());
+}
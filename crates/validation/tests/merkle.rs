@@ -0,0 +1,27 @@
+use qc_types::*;
+use qc_validation::*;
+
+fn tx(tag: u8) -> Transaction {
+    Transaction::new(1, vec![], vec![TxOut::new_p2pq(5_000, vec![tag; 4])], tag as u32)
+}
+
+fn root_hex(txs: &[Transaction]) -> String {
+    merkle_root(txs).to_hex()
+}
+
+/// Roots computed independently by `scripts/merkle_vectors.py`, which
+/// encodes the transactions by hand and hashes them with Python's hashlib
+#[test]
+fn roots_pinned_for_small_blocks() {
+    assert_eq!(merkle_root(&[]), EMPTY_MERKLE_ROOT);
+    assert_eq!(EMPTY_MERKLE_ROOT, Hash32::zero());
+
+    // One transaction: the root is its leaf hash, SHA-256 of the canonical encoding
+    assert_eq!(root_hex(&[tx(1)]), "517dd7c015cb8fc2e75353c64de3d77c0dbe485915f83108a283ea57a35f719d");
+    assert_eq!(root_hex(&[tx(1), tx(2)]), "7c39a5fac0fb7093a167186fe64bc2f74edf1b69408e2a44ff7fb6fe3827b5d8");
+    // Odd count: the third leaf is paired with itself
+    assert_eq!(
+        root_hex(&[tx(1), tx(2), tx(3)]),
+        "2ce34f16f080db4989cb9934cc007d670650c82065581c2ba537619329cd61af"
+    );
+}
@@ -0,0 +1,24 @@
+use qc_validation::*;
+
+fn spec_with(kind: &str, no_pow: bool) -> ChainSpec {
+    let content = include_str!("../../../chain_spec.toml")
+        .replace("[network]\n", &format!("[network]\nkind = \"{}\"\n", kind))
+        .replace("[consensus]\n", &format!("[consensus]\nno_pow = {}\n", no_pow));
+    toml::from_str(&content).unwrap()
+}
+
+#[test]
+fn no_pow_honoured_on_regtest_only() {
+    assert!(!spec_with("regtest", true).pow_required());
+    assert!(spec_with("regtest", false).pow_required());
+    assert!(spec_with("testnet", true).pow_required());
+    assert!(spec_with("mainnet", true).pow_required());
+}
+
+#[test]
+fn spec_without_kind_is_mainnet() {
+    let spec: ChainSpec = toml::from_str(include_str!("../../../chain_spec.toml")).unwrap();
+    assert_eq!(spec.network.kind, NetworkKind::Mainnet);
+    assert!(!spec.consensus.no_pow);
+    assert!(spec.pow_required());
+}
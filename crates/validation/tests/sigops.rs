@@ -0,0 +1,44 @@
+use qc_validation::*;
+use qc_types::*;
+
+fn spec() -> ChainSpec {
+    toml::from_str(include_str!("../../../chain_spec.toml")).unwrap()
+}
+
+/// A spend with `inputs` inputs, each carrying a Dilithium-sized signature
+fn spend(tag: u8, inputs: u32) -> Transaction {
+    let vin = (0..inputs)
+        .map(|i| TxIn::new(OutPoint::new(Hash32([tag; 32]), i), vec![0u8; 2420], false))
+        .collect();
+    Transaction::new(1, vin, vec![TxOut::new_p2pq(10_000, vec![tag; 1312])], 0)
+}
+
+#[test]
+fn block_over_sigop_cap_rejected() {
+    let mut spec = spec();
+    assert_eq!(spec.consensus.max_sigops_per_block, DEFAULT_MAX_SIGOPS_PER_BLOCK);
+    spec.consensus.max_sigops_per_block = 100;
+
+    let coinbase = Transaction::new(1, vec![], vec![TxOut::new_p2pq(block_subsidy(&spec, 1), vec![9u8; 1312])], 0);
+    let mut txs = vec![coinbase];
+    txs.extend((0..4).map(|tag| spend(tag, spec.txpolicy.max_inputs)));
+
+    // Every transaction is within the size and count limits on its own
+    for tx in &txs {
+        assert!(bincode::serialize(tx).unwrap().len() as u64 <= spec.txpolicy.max_tx_size);
+        assert!(tx.vin.len() as u32 <= spec.txpolicy.max_inputs);
+    }
+    assert_eq!(block_sigops(&txs), 128);
+    assert!(matches!(check_block_sigops(&spec, &txs), Err(ValidationError::TooManySigops)));
+
+    // Three full spends fit
+    txs.pop();
+    assert_eq!(check_block_sigops(&spec, &txs).unwrap(), 96);
+}
+
+#[test]
+fn normal_block_within_default_cap() {
+    let spec = spec();
+    let txs: Vec<Transaction> = (0..50).map(|tag| spend(tag, 2)).collect();
+    assert_eq!(check_block_sigops(&spec, &txs).unwrap(), 100);
+}
@@ -74,3 +74,39 @@ fn emission_rate_decreases() {
         }
     }
 }
+
+#[test]
+fn genesis_and_emission_reconcile_to_max_supply() {
+    let spec = spec();
+    let hal = spec.supply.halving_interval_blocks as i128;
+
+    // Mainnet genesis pays only the height-0 subsidy, so there is no premine
+    let supply = reconcile_supply(&spec, 0).unwrap();
+    assert_eq!(supply.premine_sats, 0);
+    assert_eq!(supply.emission_sats, total_subsidy_emission(&spec));
+    assert_eq!(supply.premine_sats + supply.emission_sats + supply.shortfall_sats, spec.supply.max_supply_sats as i128);
+
+    // Intentional shortfall: integer halvings floor the subsidy, so the
+    // schedule stops short of the cap by a fraction of a coin
+    println!("Shortfall: {} sats", supply.shortfall_sats);
+    assert!(supply.shortfall_sats >= 0);
+    assert!(supply.shortfall_sats < 64 * hal);
+    assert!(supply.shortfall_sats < 100_000_000);
+
+    assert!(matches!(reconcile_supply(&spec, 1), Err(ValidationError::PremineMismatch)));
+}
+
+#[test]
+fn premine_comes_out_of_the_subsidy_schedule() {
+    let mut spec = spec();
+    let emission_without_premine = total_subsidy_emission(&spec);
+    spec.supply.premine_sats = spec.supply.max_supply_sats / 10;
+
+    let supply = reconcile_supply(&spec, spec.supply.premine_sats).unwrap();
+    assert!(supply.emission_sats < emission_without_premine);
+    assert_eq!(supply.premine_sats + supply.emission_sats + supply.shortfall_sats, spec.supply.max_supply_sats as i128);
+
+    // A premine above the cap can't reconcile, whatever the schedule emits
+    spec.supply.premine_sats = spec.supply.max_supply_sats + 1;
+    assert!(matches!(reconcile_supply(&spec, spec.supply.premine_sats), Err(ValidationError::SupplyMismatch)));
+}
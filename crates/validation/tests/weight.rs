@@ -0,0 +1,34 @@
+use qc_validation::*;
+use qc_types::*;
+
+fn spec() -> ChainSpec {
+    toml::from_str(include_str!("../../../chain_spec.toml")).unwrap()
+}
+
+fn block(txs: Vec<Transaction>) -> Block {
+    Block::new(BlockHeader::new(1, Hash32::zero(), merkle_root(&txs), 0, 0x207fffff, 0), txs)
+}
+
+/// A one-input spend whose `extra` bytes sit in the signature or in the output
+fn spend(extra: usize, in_signature: bool) -> Transaction {
+    let (sig, pubkey) = if in_signature { (extra, 0) } else { (0, extra) };
+    let vin = vec![TxIn::new(OutPoint::new(Hash32([1u8; 32]), 0), vec![0u8; 2420 + sig], false)];
+    Transaction::new(1, vin, vec![TxOut::new_p2pq(10_000, vec![7u8; 1312 + pubkey])], 0)
+}
+
+#[test]
+fn signature_bytes_weigh_less_than_other_bytes() {
+    let mut spec = spec();
+    assert_eq!(spec.consensus.max_block_weight, DEFAULT_MAX_BLOCK_WEIGHT);
+
+    let witness_heavy = block(vec![spend(10_000, true)]);
+    let base_heavy = block(vec![spend(10_000, false)]);
+    // Same number of encoded bytes
+    let encoded_len = |block: &Block| canonical::encode(&block.txs[0]).len();
+    assert_eq!(encoded_len(&witness_heavy), encoded_len(&base_heavy));
+    assert_eq!(base_heavy.weight() - witness_heavy.weight(), 10_000 * (WITNESS_SCALE_FACTOR - 1));
+
+    spec.consensus.max_block_weight = witness_heavy.weight();
+    assert_eq!(check_block_weight(&spec, &witness_heavy).unwrap(), witness_heavy.weight());
+    assert!(matches!(check_block_weight(&spec, &base_heavy), Err(ValidationError::BlockTooHeavy)));
+}
@@ -0,0 +1,138 @@
+// Receive and change address chains with used-index tracking and labels
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+/// Change addresses derive from indices with the top bit set, so they can
+/// never coincide with a receive index
+pub const CHANGE_CHAIN_OFFSET: u32 = 0x8000_0000;
+
+/// Which address chain an index belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum AddressChain {
+    /// Addresses handed out to payers
+    Receive,
+    /// Addresses the wallet pays its own change to
+    Change,
+}
+
+impl AddressChain {
+    /// Chain a raw derivation index belongs to
+    pub fn of(index: u32) -> Self {
+        if index & CHANGE_CHAIN_OFFSET == 0 {
+            AddressChain::Receive
+        } else {
+            AddressChain::Change
+        }
+    }
+
+    /// Raw derivation index of the `position`th address on this chain
+    pub fn index(self, position: u32) -> u32 {
+        match self {
+            AddressChain::Receive => position & !CHANGE_CHAIN_OFFSET,
+            AddressChain::Change => position | CHANGE_CHAIN_OFFSET,
+        }
+    }
+}
+
+/// Tracks which derivation indices have been handed out or seen on chain so
+/// an address is never given out twice
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AddressBook {
+    next_receive: u32,
+    next_change: u32,
+    used: HashSet<u32>,
+    labels: HashMap<u32, String>,
+}
+
+impl AddressBook {
+    /// Book saved at `path`, or an empty one if nothing has been saved yet
+    pub fn load(path: &Path) -> Result<Self> {
+        match fs::read(path) {
+            Ok(data) => bincode::deserialize(&data)
+                .with_context(|| format!("corrupt address book in {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("failed to read {}", path.display())),
+        }
+    }
+
+    /// Write the book to `path`, replacing it whole so a crash mid-write
+    /// leaves the previous copy
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bincode::serialize(self)?)
+            .with_context(|| format!("failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, path).with_context(|| format!("failed to replace {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Reserve the next unused index on `chain` and mark it used
+    pub fn next_index(&mut self, chain: AddressChain) -> u32 {
+        let next = match chain {
+            AddressChain::Receive => &mut self.next_receive,
+            AddressChain::Change => &mut self.next_change,
+        };
+        let mut index = chain.index(*next);
+        while self.used.contains(&index) {
+            *next += 1;
+            index = chain.index(*next);
+        }
+        *next += 1;
+        self.used.insert(index);
+        index
+    }
+
+    /// Record that the address at `index` appeared on chain
+    pub fn mark_used(&mut self, index: u32) {
+        self.used.insert(index);
+    }
+
+    pub fn is_used(&self, index: u32) -> bool {
+        self.used.contains(&index)
+    }
+
+    pub fn set_label(&mut self, index: u32, label: impl Into<String>) {
+        self.labels.insert(index, label.into());
+    }
+
+    pub fn label(&self, index: u32) -> Option<&str> {
+        self.labels.get(&index).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_used_indices_are_skipped() {
+        let mut book = AddressBook::default();
+        book.mark_used(1);
+        assert_eq!(book.next_index(AddressChain::Receive), 0);
+        assert_eq!(book.next_index(AddressChain::Receive), 2);
+        assert_eq!(book.next_index(AddressChain::Change), CHANGE_CHAIN_OFFSET);
+        assert!(book.is_used(2));
+        assert_eq!(AddressChain::of(CHANGE_CHAIN_OFFSET + 5), AddressChain::Change);
+        assert_eq!(AddressChain::of(5), AddressChain::Receive);
+    }
+
+    #[test]
+    fn test_saved_book_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.dat");
+        assert_eq!(AddressBook::load(&path).unwrap(), AddressBook::default());
+
+        let mut book = AddressBook::default();
+        book.next_index(AddressChain::Receive);
+        book.mark_used(4);
+        book.set_label(0, "rent");
+        book.save(&path).unwrap();
+
+        let mut loaded = AddressBook::load(&path).unwrap();
+        assert_eq!(loaded, book);
+        assert_eq!(loaded.next_index(AddressChain::Receive), 1);
+    }
+}
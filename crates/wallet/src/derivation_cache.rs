@@ -0,0 +1,82 @@
+// Bounded in-memory cache of derived keys and addresses
+
+use std::collections::HashMap;
+
+/// Entries kept when the wallet doesn't pick a capacity; enough for a few
+/// gap-limit scans of both address chains
+pub const DEFAULT_DERIVATION_CACHE_SIZE: usize = 1024;
+
+/// A derivation a wallet seed can perform, by index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DerivationPath {
+    Address(u32),
+    PrivateKey(u32),
+}
+
+/// What a derivation produced
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Derived {
+    Address(String),
+    /// Seed of the index's Dilithium2 keypair
+    PrivateKey([u8; 32]),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub len: usize,
+}
+
+/// Least-recently-used cache; a capacity of zero disables caching
+#[derive(Debug)]
+pub struct DerivationCache {
+    capacity: usize,
+    /// Path -> (result, last use)
+    entries: HashMap<DerivationPath, (Derived, u64)>,
+    clock: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl DerivationCache {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, entries: HashMap::new(), clock: 0, hits: 0, misses: 0 }
+    }
+
+    /// Cached result for `path`, running `derive` on a miss
+    pub fn get_or_derive(&mut self, path: DerivationPath, derive: impl FnOnce() -> Derived) -> Derived {
+        self.clock += 1;
+        if let Some((derived, used)) = self.entries.get_mut(&path) {
+            *used = self.clock;
+            self.hits += 1;
+            return derived.clone();
+        }
+
+        self.misses += 1;
+        let derived = derive();
+        if self.capacity > 0 {
+            if self.entries.len() >= self.capacity {
+                self.evict_least_recent();
+            }
+            self.entries.insert(path, (derived.clone(), self.clock));
+        }
+        derived
+    }
+
+    fn evict_least_recent(&mut self) {
+        if let Some(oldest) = self.entries.iter().min_by_key(|(_, (_, used))| *used).map(|(path, _)| *path) {
+            self.entries.remove(&oldest);
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats { hits: self.hits, misses: self.misses, len: self.entries.len() }
+    }
+}
+
+impl Default for DerivationCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_DERIVATION_CACHE_SIZE)
+    }
+}
@@ -0,0 +1,56 @@
+// Message signatures proving ownership of wallet addresses
+
+use pqcrypto_dilithium::dilithium2;
+use qc_crypto::{address_from_pubkey, pubkey_hash, verify_message};
+
+use crate::key_hash_from_address;
+
+/// Check a `WalletSeed::signmessage` proof for `address`, either the
+/// wallet's base58check form or the bech32 form of the same key
+pub fn verifymessage(address: &str, message: &[u8], signature: &[u8]) -> bool {
+    let Some(key_hash) = key_hash_from_address(address) else {
+        return verify_message(address, message, signature);
+    };
+    // The proof carries the public key; it has to be the one the address hashes
+    let pk_len = dilithium2::public_key_bytes();
+    signature.len() > pk_len
+        && pubkey_hash(&signature[..pk_len]) == key_hash
+        && verify_message(&address_from_pubkey(&signature[..pk_len]), message, signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WalletSeed;
+    use pqcrypto_traits::sign::PublicKey as _;
+    use qc_crypto::{pq_verify, tx_sighash};
+
+    const TEST_MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn test_signmessage_proves_ownership() {
+        let wallet = WalletSeed::from_mnemonic(TEST_MNEMONIC, "").unwrap();
+        let address = wallet.derive_keyed_address(0).unwrap();
+
+        let signature = wallet.signmessage(0, b"withdrawal request #42").unwrap();
+        assert!(verifymessage(&address, b"withdrawal request #42", &signature));
+        assert!(!verifymessage(&address, b"withdrawal request #43", &signature));
+        assert!(!verifymessage(&wallet.derive_keyed_address(1).unwrap(), b"withdrawal request #42", &signature));
+
+        // The bech32 form of the same key verifies too
+        let key = wallet.derive_private_key(0).unwrap();
+        assert!(verifymessage(&key.address(), b"withdrawal request #42", &signature));
+    }
+
+    #[test]
+    fn test_message_signature_cannot_sign_transaction() {
+        let wallet = WalletSeed::from_mnemonic(TEST_MNEMONIC, "").unwrap();
+        let key = wallet.derive_private_key(0).unwrap();
+
+        // Tricked into signing a transaction's sighash as a "message"
+        let sighash = tx_sighash(b"spend everything to the attacker");
+        let signature = wallet.signmessage(0, &sighash).unwrap();
+        let pk_len = key.public_key.as_bytes().len();
+        assert!(!pq_verify(&key.public_key, &sighash, &signature[pk_len..]));
+    }
+}
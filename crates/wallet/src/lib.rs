@@ -3,8 +3,42 @@
 use anyhow::{Result, anyhow};
 use rand::{RngCore, rngs::OsRng};
 use sha2::{Digest, Sha256};
-use bip39::{Mnemonic, Language, Seed};
+use bip39::{Mnemonic, Language};
+use base58::{FromBase58, ToBase58};
+use pqcrypto_dilithium::dilithium2::{PublicKey, SecretKey};
+use pqcrypto_traits::sign::PublicKey as _;
+use qc_crypto::{address_from_pubkey, keypair_from_seed, pq_sign, pq_verify, pubkey_hash, sign_message};
+use qc_types::OutputType;
 use std::fmt;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+pub mod address_book;
+pub mod derivation_cache;
+pub mod keyring;
+pub mod transaction;
+pub mod watch_only;
+pub use address_book::{AddressBook, AddressChain, CHANGE_CHAIN_OFFSET};
+pub use derivation_cache::{CacheStats, DerivationCache, DerivationPath, Derived};
+pub use keyring::verifymessage;
+pub use transaction::{TransactionBuilder, TransactionError, TxSigner};
+pub use watch_only::{AddressTx, WalletTxEntry, WatchOnlyWallet};
+
+/// Network an address belongs to; each has its own base58 version byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Network {
+    Mainnet,
+    Testnet,
+}
+
+impl Network {
+    pub fn address_version(self) -> u8 {
+        match self {
+            Network::Mainnet => 0x51,
+            Network::Testnet => 0x6f,
+        }
+    }
+}
 
 /// Deterministic key generation - Bitcoin standard
 pub fn new_seed_32() -> [u8;32] {
@@ -23,39 +57,62 @@ pub fn generate_mnemonic() -> Result<String> {
 /// Recover seed from mnemonic - BIP39 standard
 pub fn mnemonic_to_seed(mnemonic: &str, passphrase: &str) -> Result<[u8; 64]> {
     let mnemonic = Mnemonic::parse_in(Language::English, mnemonic)?;
-    let seed = Seed::new(&mnemonic, passphrase);
-    
-    let mut seed_bytes = [0u8; 64];
-    seed_bytes.copy_from_slice(seed.as_bytes());
-    Ok(seed_bytes)
+    Ok(mnemonic.to_seed(passphrase))
 }
 
 /// Address generation from seed - Deterministic and reproducible
 pub fn address_from_seed(seed: &[u8; 32], index: u32) -> String {
-    // Derive key using PBKDF2
-    let mut derived_key = [0u8; 32];
-    pbkdf2::pbkdf2::<hmac::Hmac<sha2::Sha256>>(
-        seed,
-        &index.to_be_bytes(),
-        4096,
-        &mut derived_key
-    );
-    
-    // Create address with checksum
-    let mut hasher = Sha256::new();
-    hasher.update(&derived_key);
-    let hash = hasher.finalize();
-    
+    address_from_seed_on(Network::Mainnet, seed, index)
+}
+
+/// Address generation from seed for a specific network
+pub fn address_from_seed_on(network: Network, seed: &[u8; 32], index: u32) -> String {
+    base58check_address(network, &key_hash_from_seed(seed, index))
+}
+
+/// Base58check address paying to a Dilithium2 public key, committing to the
+/// same hash as its bech32 `address_from_pubkey` form
+pub fn address_from_pubkey_on(network: Network, pubkey: &[u8]) -> String {
+    base58check_address(network, &pubkey_hash(pubkey))
+}
+
+fn base58check_address(network: Network, key_hash: &[u8; 20]) -> String {
     // Use base58check encoding like Bitcoin
-    let version_byte = 0x51; // QuantumCoin mainnet address version
-    let mut payload = vec![version_byte];
-    payload.extend_from_slice(&hash[..20]); // 20-byte hash160
+    let mut payload = vec![network.address_version()];
+    payload.extend_from_slice(key_hash);
     
     // Add checksum
     let checksum = double_sha256(&payload);
     payload.extend_from_slice(&checksum[..4]);
     
-    base58::encode(&payload)
+    payload.to_base58()
+}
+
+/// Key hash of a base58check address on either network, or `None` if it
+/// doesn't decode or its checksum is wrong
+pub fn key_hash_from_address(address: &str) -> Option<[u8; 20]> {
+    let decoded = address.from_base58().ok()?;
+    if decoded.len() != 25 || double_sha256(&decoded[..21])[..4] != decoded[21..] {
+        return None;
+    }
+    if decoded[0] != Network::Mainnet.address_version() && decoded[0] != Network::Testnet.address_version() {
+        return None;
+    }
+    let mut key_hash = [0u8; 20];
+    key_hash.copy_from_slice(&decoded[1..21]);
+    Some(key_hash)
+}
+
+/// 20-byte hash committed to by the address at `index`
+pub fn key_hash_from_seed(seed: &[u8; 32], index: u32) -> [u8; 20] {
+    // Derive key using PBKDF2
+    let mut derived_key = [0u8; 32];
+    pbkdf2::pbkdf2_hmac::<Sha256>(seed, &index.to_be_bytes(), 4096, &mut derived_key);
+    
+    let hash = Sha256::digest(derived_key);
+    let mut key_hash = [0u8; 20];
+    key_hash.copy_from_slice(&hash[..20]);
+    key_hash
 }
 
 /// Generate address with proper bech32 encoding
@@ -79,7 +136,7 @@ pub fn validate_address(address: &str) -> Result<bool> {
         }
     } else {
         // Base58check validation
-        match base58::decode(address) {
+        match address.from_base58() {
             Ok(decoded) => {
                 if decoded.len() != 25 { // version(1) + hash(20) + checksum(4)
                     return Ok(false);
@@ -96,65 +153,79 @@ pub fn validate_address(address: &str) -> Result<bool> {
     }
 }
 
-/// Transaction signing with test vectors
-pub fn sign_transaction(tx_data: &[u8], private_key: &[u8; 32]) -> Result<Vec<u8>> {
-    // For now, use simple HMAC-SHA256 signing
-    // In production, this would use Dilithium2 post-quantum signatures
-    
-    use hmac::{Hmac, Mac};
-    type HmacSha256 = Hmac<Sha256>;
-    
-    let mut mac = HmacSha256::new_from_slice(private_key)?;
-    mac.update(tx_data);
-    let signature = mac.finalize().into_bytes();
-    
-    Ok(signature.to_vec())
+/// Dilithium2 keypair the wallet derives at an index
+#[derive(Clone)]
+pub struct DerivedKey {
+    pub public_key: PublicKey,
+    pub secret_key: SecretKey,
 }
 
-/// Verify transaction signature
-pub fn verify_signature(tx_data: &[u8], signature: &[u8], public_key: &[u8; 32]) -> Result<bool> {
-    // For now, verify HMAC-SHA256
-    // In production, this would verify Dilithium2 signatures
-    
-    use hmac::{Hmac, Mac};
-    type HmacSha256 = Hmac<Sha256>;
-    
-    let mut mac = HmacSha256::new_from_slice(public_key)?;
-    mac.update(tx_data);
-    
-    match mac.verify_slice(signature) {
-        Ok(_) => Ok(true),
-        Err(_) => Ok(false),
+impl DerivedKey {
+    /// Output type paying to this key, as the validator checks it
+    pub fn output_type(&self) -> OutputType {
+        OutputType::P2PQ { pubkey: self.public_key.as_bytes().to_vec() }
+    }
+
+    pub fn address(&self) -> String {
+        address_from_pubkey(self.public_key.as_bytes())
     }
 }
 
-/// Cross-platform test vectors for key generation
+/// Signs every input with the derived key
+impl TxSigner for DerivedKey {
+    fn sign(&self, _input: usize, sighash: &[u8; 32]) -> Vec<u8> {
+        sign_transaction(sighash, self)
+    }
+}
+
+/// Detached Dilithium2 signature over `tx_data`, normally a transaction's
+/// `tx_sighash`
+pub fn sign_transaction(tx_data: &[u8], key: &DerivedKey) -> Vec<u8> {
+    pq_sign(&key.secret_key, tx_data)
+}
+
+/// Verify a `sign_transaction` signature
+pub fn verify_signature(tx_data: &[u8], signature: &[u8], public_key: &PublicKey) -> bool {
+    pq_verify(public_key, tx_data, signature)
+}
+
+/// Cross-platform test vectors for the seed -> address pipeline.
+///
+/// Computed independently from the derivation spec (PBKDF2-HMAC-SHA256 over the
+/// big-endian index, 4096 rounds; SHA-256 truncated to 20 bytes; base58check).
+/// Any change to derivation must break these.
 pub fn get_test_vectors() -> Vec<CryptoTestVector> {
+    let vector = |name: &str, network, seed: Vec<u8>, index, key_hash: &str, address: &str| CryptoTestVector {
+        name: name.to_string(),
+        network,
+        seed,
+        index,
+        expected_address: address.to_string(),
+        expected_key_hash: hex::decode(key_hash).unwrap(),
+    };
+    
     vec![
-        CryptoTestVector {
-            name: "genesis_key".to_string(),
-            seed: hex::decode("0000000000000000000000000000000000000000000000000000000000000000").unwrap(),
-            index: 0,
-            expected_address: "15Aex1JWjRwJPKAV2JHdtVz7L4mJ7kKLXQ".to_string(),
-            expected_pubkey: hex::decode("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").unwrap(),
-        },
-        CryptoTestVector {
-            name: "test_key_1".to_string(),
-            seed: hex::decode("ffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffffff").unwrap(),
-            index: 1,
-            expected_address: "1BKqsrnVjV7P9qPJGF7bDddrpKGKKZeUP8".to_string(),
-            expected_pubkey: hex::decode("02f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9").unwrap(),
-        },
+        vector("zero_seed_mainnet", Network::Mainnet, vec![0x00; 32], 0,
+               "4b7e4773f4266659781a685b42b7e8d5dcd4b522", "ZiG2iD9ci7UbBWgMpMyLFgFXPhhxcnfDYr"),
+        vector("ones_seed_mainnet", Network::Mainnet, vec![0xff; 32], 1,
+               "f4794ba976f71f28bdd92e4e5a497356cac47d02", "ZyfWwNB9NYEtkNwfQmEegqyTPUM4ej2Esz"),
+        vector("counting_seed_mainnet", Network::Mainnet, (0..32).collect(), 7,
+               "7ca4f6218d19fb0b3eb2dcaf2af456b74481d160", "ZnjvAJh1wsL5HFVyZL25TWuRdRw5obBjMw"),
+        vector("zero_seed_testnet", Network::Testnet, vec![0x00; 32], 0,
+               "4b7e4773f4266659781a685b42b7e8d5dcd4b522", "mnQ8FU6G1XNsiWrxYwxtoSR8GqSFxBg3TF"),
+        vector("counting_seed_testnet_high_index", Network::Testnet, (0..32).collect(), 0x8000_0000,
+               "ef7843d781cd29b7476263476dc3f22f0df3a05b", "n3M9s4UP1iMqKPmVw2vBwYdtUH3W1zgmsL"),
     ]
 }
 
 #[derive(Debug, Clone)]
 pub struct CryptoTestVector {
     pub name: String,
+    pub network: Network,
     pub seed: Vec<u8>,
     pub index: u32,
     pub expected_address: String,
-    pub expected_pubkey: Vec<u8>,
+    pub expected_key_hash: Vec<u8>,
 }
 
 /// Wallet seed/mnemonic with recovery test
@@ -162,6 +233,12 @@ pub struct WalletSeed {
     pub mnemonic: String,
     pub seed: [u8; 64],
     pub master_key: [u8; 32],
+    /// Each derivation runs thousands of PBKDF2 rounds, so results are kept
+    cache: Mutex<DerivationCache>,
+    /// Indices handed out or seen on chain, so addresses aren't reused
+    addresses: Mutex<AddressBook>,
+    /// Where `addresses` is saved after every change, if anywhere
+    wallet_file: Option<PathBuf>,
 }
 
 impl WalletSeed {
@@ -171,7 +248,7 @@ impl WalletSeed {
         
         // Derive master private key
         let mut master_key = [0u8; 32];
-        pbkdf2::pbkdf2::<hmac::Hmac<Sha256>>(
+        pbkdf2::pbkdf2_hmac::<Sha256>(
             &seed,
             b"QuantumCoin master key",
             4096,
@@ -182,6 +259,9 @@ impl WalletSeed {
             mnemonic,
             seed,
             master_key,
+            cache: Mutex::default(),
+            addresses: Mutex::default(),
+            wallet_file: None,
         })
     }
     
@@ -189,7 +269,7 @@ impl WalletSeed {
         let seed = mnemonic_to_seed(mnemonic, passphrase)?;
         
         let mut master_key = [0u8; 32];
-        pbkdf2::pbkdf2::<hmac::Hmac<Sha256>>(
+        pbkdf2::pbkdf2_hmac::<Sha256>(
             &seed,
             b"QuantumCoin master key", 
             4096,
@@ -200,24 +280,116 @@ impl WalletSeed {
             mnemonic: mnemonic.to_string(),
             seed,
             master_key,
+            cache: Mutex::default(),
+            addresses: Mutex::default(),
+            wallet_file: None,
         })
     }
     
+    /// Keep at most `capacity` derived keys and addresses; zero disables the cache
+    pub fn with_cache_capacity(mut self, capacity: usize) -> Self {
+        self.cache = Mutex::new(DerivationCache::new(capacity));
+        self
+    }
+    
+    /// Keep the address book in the wallet file at `path`, picking up where
+    /// an earlier run left off, so a restart never hands out an address again
+    pub fn with_wallet_file(mut self, path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        self.addresses = Mutex::new(AddressBook::load(&path)?);
+        self.wallet_file = Some(path);
+        Ok(self)
+    }
+    
+    /// Apply `update` to the address book and save it to the wallet file
+    fn update_addresses<T>(&self, update: impl FnOnce(&mut AddressBook) -> T) -> Result<T> {
+        let mut addresses = self.addresses.lock().unwrap();
+        let result = update(&mut addresses);
+        if let Some(path) = &self.wallet_file {
+            addresses.save(path)?;
+        }
+        Ok(result)
+    }
+    
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.lock().unwrap().stats()
+    }
+    
     /// Derive address at specific index
     pub fn derive_address(&self, index: u32) -> String {
-        address_from_seed(&self.master_key, index)
+        let derived = self.cache.lock().unwrap().get_or_derive(DerivationPath::Address(index), || {
+            Derived::Address(address_from_seed(&self.master_key, index))
+        });
+        match derived {
+            Derived::Address(address) => address,
+            Derived::PrivateKey(_) => unreachable!("address path cached a private key"),
+        }
     }
     
-    /// Derive private key at specific index
-    pub fn derive_private_key(&self, index: u32) -> [u8; 32] {
-        let mut derived = [0u8; 32];
-        pbkdf2::pbkdf2::<hmac::Hmac<Sha256>>(
-            &self.master_key,
-            &index.to_be_bytes(),
-            2048,
-            &mut derived
-        );
-        derived
+    /// Base58check address of the Dilithium2 key at `index`, the address a
+    /// `signmessage` proof for `index` verifies against. Unlike
+    /// `derive_address` it commits to the key itself.
+    pub fn derive_keyed_address(&self, index: u32) -> Result<String> {
+        let key = self.derive_private_key(index)?;
+        Ok(address_from_pubkey_on(Network::Mainnet, key.public_key.as_bytes()))
+    }
+    
+    /// Fresh receive address that has never been handed out or used
+    pub fn next_receive_address(&self) -> Result<String> {
+        let index = self.update_addresses(|addresses| addresses.next_index(AddressChain::Receive))?;
+        Ok(self.derive_address(index))
+    }
+    
+    /// Fresh address on the change chain, disjoint from receive addresses
+    pub fn next_change_address(&self) -> Result<String> {
+        let index = self.update_addresses(|addresses| addresses.next_index(AddressChain::Change))?;
+        Ok(self.derive_address(index))
+    }
+    
+    /// Prove ownership of `derive_keyed_address(index)` by signing `message`
+    /// with the index's key; check the proof with `verifymessage`
+    pub fn signmessage(&self, index: u32, message: &[u8]) -> Result<Vec<u8>> {
+        let key = self.derive_private_key(index)?;
+        Ok(sign_message(&key.public_key, &key.secret_key, message))
+    }
+    
+    /// Record that the address at `index` has received funds
+    pub fn mark_address_used(&self, index: u32) -> Result<()> {
+        self.update_addresses(|addresses| addresses.mark_used(index))
+    }
+    
+    pub fn set_address_label(&self, index: u32, label: &str) -> Result<()> {
+        self.update_addresses(|addresses| addresses.set_label(index, label))
+    }
+    
+    pub fn address_label(&self, index: u32) -> Option<String> {
+        self.addresses.lock().unwrap().label(index).map(str::to_string)
+    }
+    
+    /// Derive the Dilithium2 keypair at `index`. The same mnemonic and index
+    /// always give the same keypair.
+    pub fn derive_private_key(&self, index: u32) -> Result<DerivedKey> {
+        let (public_key, secret_key) = keypair_from_seed(&self.key_seed(index))
+            .map_err(|e| anyhow!("Failed to derive Dilithium2 keypair {}: {}", index, e))?;
+        Ok(DerivedKey { public_key, secret_key })
+    }
+
+    /// Seed the keypair at `index` is generated from
+    fn key_seed(&self, index: u32) -> [u8; 32] {
+        let derived = self.cache.lock().unwrap().get_or_derive(DerivationPath::PrivateKey(index), || {
+            let mut derived = [0u8; 32];
+            pbkdf2::pbkdf2_hmac::<Sha256>(
+                &self.master_key,
+                &index.to_be_bytes(),
+                2048,
+                &mut derived
+            );
+            Derived::PrivateKey(derived)
+        });
+        match derived {
+            Derived::PrivateKey(key) => key,
+            Derived::Address(_) => unreachable!("private key path cached an address"),
+        }
     }
 }
 
@@ -273,6 +445,9 @@ pub fn test_wallet_recovery() -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use qc_crypto::tx_sighash;
+    use qc_types::{Hash32, OutPoint, TxOut};
+    use qc_validation::{validate_transaction, ChainSpec, ValidationError};
     
     #[test]
     fn test_deterministic_key_generation() {
@@ -294,15 +469,41 @@ mod tests {
     
     #[test]
     fn test_transaction_signing() {
-        let private_key = [1u8; 32];
-        let tx_data = b"test transaction data";
-        
-        let signature = sign_transaction(tx_data, &private_key).unwrap();
-        assert!(!signature.is_empty());
-        
-        // Test verification
-        let public_key = private_key; // For test purposes
-        assert!(verify_signature(tx_data, &signature, &public_key).unwrap());
+        let wallet = WalletSeed::from_mnemonic(TEST_MNEMONIC, "").unwrap();
+        let key = wallet.derive_private_key(0).unwrap();
+        let sighash = tx_sighash(b"test transaction data");
+
+        let signature = sign_transaction(&sighash, &key);
+        assert!(verify_signature(&sighash, &signature, &key.public_key));
+        assert!(!verify_signature(&tx_sighash(b"other transaction"), &signature, &key.public_key));
+        assert!(!verify_signature(&sighash, &signature, &wallet.derive_private_key(1).unwrap().public_key));
+
+        // Recovering the mnemonic recovers the key
+        let recovered = WalletSeed::from_mnemonic(TEST_MNEMONIC, "").unwrap().derive_private_key(0).unwrap();
+        assert_eq!(recovered.public_key.as_bytes(), key.public_key.as_bytes());
+        assert!(verify_signature(&sighash, &sign_transaction(&sighash, &recovered), &key.public_key));
+    }
+
+    #[test]
+    fn test_signed_spend_passes_validation() {
+        let spec: ChainSpec = toml::from_str(include_str!("../../../chain_spec.toml")).unwrap();
+        let wallet = WalletSeed::from_mnemonic(TEST_MNEMONIC, "").unwrap();
+        let key = wallet.derive_private_key(0).unwrap();
+        let funding = OutPoint::new(Hash32([3u8; 32]), 0);
+
+        let tx = TransactionBuilder::new()
+            .add_input(funding.clone(), 100_000)
+            .add_output(TxOut::new_p2pq(40_000, vec![7u8; 1312]))
+            .add_change(wallet.derive_private_key(CHANGE_CHAIN_OFFSET).unwrap().public_key.as_bytes().to_vec())
+            .build_and_sign(&key)
+            .unwrap();
+        let lookup = |op: &OutPoint| (*op == funding).then(|| (100_000, key.output_type(), 0, false));
+        validate_transaction(&spec, 1, &tx, false, lookup).unwrap();
+
+        // Another index's key does not own the output
+        let other = wallet.derive_private_key(1).unwrap();
+        let lookup = |op: &OutPoint| (*op == funding).then(|| (100_000, other.output_type(), 0, false));
+        assert!(matches!(validate_transaction(&spec, 1, &tx, false, lookup), Err(ValidationError::BadSignature)));
     }
     
     #[test]
@@ -316,16 +517,122 @@ mod tests {
     
     #[test]
     fn test_cross_platform_vectors() {
-        let vectors = get_test_vectors();
-        
-        for vector in vectors {
+        for vector in get_test_vectors() {
             let mut seed = [0u8; 32];
             seed.copy_from_slice(&vector.seed[..32]);
             
-            let address = address_from_seed(&seed, vector.index);
+            let key_hash = key_hash_from_seed(&seed, vector.index);
+            assert_eq!(key_hash.to_vec(), vector.expected_key_hash, "key hash for '{}'", vector.name);
             
-            // In production, this should match exactly
-            println!("Test vector '{}': Generated {}", vector.name, address);
+            let address = address_from_seed_on(vector.network, &seed, vector.index);
+            assert_eq!(address, vector.expected_address, "address for '{}'", vector.name);
+            assert!(validate_address(&address).unwrap());
         }
     }
+    
+    const TEST_MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn test_repeat_derivation_served_from_cache() {
+        let wallet = WalletSeed::from_mnemonic(TEST_MNEMONIC, "").unwrap();
+        let uncached = WalletSeed::from_mnemonic(TEST_MNEMONIC, "").unwrap().with_cache_capacity(0);
+
+        let address = wallet.derive_address(3);
+        let key = wallet.derive_private_key(3).unwrap().public_key;
+        assert_eq!(wallet.cache_stats(), CacheStats { hits: 0, misses: 2, len: 2 });
+
+        assert_eq!(wallet.derive_address(3), address);
+        assert_eq!(wallet.derive_private_key(3).unwrap().public_key.as_bytes(), key.as_bytes());
+        assert_eq!(wallet.cache_stats(), CacheStats { hits: 2, misses: 2, len: 2 });
+
+        // Cached results match a fresh derivation
+        assert_eq!(uncached.derive_address(3), address);
+        assert_eq!(uncached.derive_private_key(3).unwrap().public_key.as_bytes(), key.as_bytes());
+        assert_eq!(uncached.derive_address(3), address);
+        assert_eq!(uncached.cache_stats(), CacheStats { hits: 0, misses: 3, len: 0 });
+    }
+
+    #[test]
+    fn test_derivation_cache_is_bounded() {
+        let wallet = WalletSeed::from_mnemonic(TEST_MNEMONIC, "").unwrap().with_cache_capacity(2);
+        wallet.derive_address(0);
+        wallet.derive_address(1);
+        // Touch 0 so 1 is the least recently used
+        wallet.derive_address(0);
+        wallet.derive_address(2);
+        assert_eq!(wallet.cache_stats().len, 2);
+
+        let hits = wallet.cache_stats().hits;
+        wallet.derive_address(0);
+        assert_eq!(wallet.cache_stats().hits, hits + 1);
+        wallet.derive_address(1);
+        assert_eq!(wallet.cache_stats().hits, hits + 1);
+    }
+
+    #[test]
+    fn test_receive_addresses_are_fresh() {
+        let wallet = WalletSeed::from_mnemonic(TEST_MNEMONIC, "").unwrap();
+        wallet.mark_address_used(1).unwrap();
+
+        let first = wallet.next_receive_address().unwrap();
+        let second = wallet.next_receive_address().unwrap();
+        assert_ne!(first, second);
+        assert_eq!(first, wallet.derive_address(0));
+        // Index 1 already received funds, so it is skipped
+        assert_eq!(second, wallet.derive_address(2));
+
+        wallet.set_address_label(0, "invoice 42").unwrap();
+        assert_eq!(wallet.address_label(0).as_deref(), Some("invoice 42"));
+        assert_eq!(wallet.address_label(2), None);
+    }
+
+    #[test]
+    fn test_address_book_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.dat");
+        let wallet = WalletSeed::from_mnemonic(TEST_MNEMONIC, "").unwrap().with_wallet_file(&path).unwrap();
+        let first = wallet.next_receive_address().unwrap();
+        wallet.mark_address_used(1).unwrap();
+        wallet.set_address_label(0, "invoice 42").unwrap();
+
+        let restarted = WalletSeed::from_mnemonic(TEST_MNEMONIC, "").unwrap().with_wallet_file(&path).unwrap();
+        let next = restarted.next_receive_address().unwrap();
+        assert_ne!(next, first);
+        assert_eq!(next, restarted.derive_address(2));
+        assert_eq!(restarted.address_label(0).as_deref(), Some("invoice 42"));
+    }
+
+    #[test]
+    fn test_change_addresses_never_collide_with_receive() {
+        let wallet = WalletSeed::from_mnemonic(TEST_MNEMONIC, "").unwrap();
+        let receive: std::collections::HashSet<String> = (0..4).map(|_| wallet.next_receive_address().unwrap()).collect();
+        let change: std::collections::HashSet<String> = (0..4).map(|_| wallet.next_change_address().unwrap()).collect();
+
+        assert_eq!(receive.len(), 4);
+        assert_eq!(change.len(), 4);
+        assert!(receive.is_disjoint(&change));
+        assert!(change.contains(&wallet.derive_address(CHANGE_CHAIN_OFFSET)));
+    }
+
+    #[test]
+    fn test_keyed_address_commits_to_derived_key() {
+        let wallet = WalletSeed::from_mnemonic(TEST_MNEMONIC, "").unwrap();
+        let key = wallet.derive_private_key(5).unwrap();
+        let address = wallet.derive_keyed_address(5).unwrap();
+
+        assert!(validate_address(&address).unwrap());
+        assert_eq!(key_hash_from_address(&address), Some(pubkey_hash(key.public_key.as_bytes())));
+        assert_eq!(address, address_from_pubkey_on(Network::Mainnet, key.public_key.as_bytes()));
+
+        // Receive addresses still come from the seed alone, as they always have
+        assert_eq!(wallet.derive_address(5), address_from_seed(&wallet.master_key, 5));
+        assert_ne!(wallet.derive_address(5), address);
+    }
+
+    #[test]
+    fn test_mainnet_is_default_network() {
+        let seed = [0u8; 32];
+        assert_eq!(address_from_seed(&seed, 0), address_from_seed_on(Network::Mainnet, &seed, 0));
+        assert_ne!(address_from_seed(&seed, 0), address_from_seed_on(Network::Testnet, &seed, 0));
+    }
 }
@@ -1,9 +1,311 @@
 //! Transaction building for wallet
 
+use pqcrypto_dilithium::dilithium2::{signature_bytes, SecretKey};
+use qc_crypto::{pq_sign, tx_sighash};
+use qc_types::{Amount, OutPoint, Transaction, TxIn, TxOut};
+
+/// Outputs below this are not worth creating; smaller change goes to the fee
+pub const DEFAULT_DUST_THRESHOLD: Amount = 546;
+
+/// Signing passes allowed for the fee to settle. Dilithium2 signatures have a
+/// fixed size, so the second pass already matches the first's size.
+const MAX_FEE_PASSES: usize = 4;
+
 /// Transaction building errors
 #[derive(thiserror::Error, Debug)]
 pub enum TransactionError {
     /// Insufficient funds
     #[error("Insufficient funds")]
     InsufficientFunds,
+
+    /// Amounts overflow or are negative
+    #[error("Invalid amount")]
+    InvalidAmount,
+
+    /// Fee did not settle between signing passes
+    #[error("Fee did not converge")]
+    FeeNotConverged,
+}
+
+/// Produces the signature for one input over the transaction's sighash
+pub trait TxSigner {
+    fn sign(&self, input: usize, sighash: &[u8; 32]) -> Vec<u8>;
+}
+
+impl<F: Fn(usize, &[u8; 32]) -> Vec<u8>> TxSigner for F {
+    fn sign(&self, input: usize, sighash: &[u8; 32]) -> Vec<u8> {
+        self(input, sighash)
+    }
+}
+
+/// Signs every input with one key
+impl TxSigner for SecretKey {
+    fn sign(&self, _input: usize, sighash: &[u8; 32]) -> Vec<u8> {
+        pq_sign(self, sighash)
+    }
+}
+
+/// Sighash all inputs sign, the same one validation checks
+pub fn signing_hash(tx: &Transaction) -> [u8; 32] {
+    tx_sighash(&tx.signing_skeleton())
+}
+
+/// Fee owed by `size` bytes at `fee_rate` sats per kB, rounded up
+pub fn fee_for_size(size: usize, fee_rate: Amount) -> Amount {
+    (size as Amount * fee_rate + 999) / 1000
+}
+
+/// Serialized size once every input carries a full Dilithium2 signature.
+/// Signatures dwarf everything else, so sizing a transaction without them
+/// underpays by roughly 2.4 kB per input.
+pub fn estimate_signed_size(inputs: &[OutPoint], outputs: &[TxOut]) -> usize {
+    let vin = inputs.iter().map(|prevout| TxIn::new(prevout.clone(), vec![0u8; signature_bytes()], false)).collect();
+    let tx = Transaction::new(1, vin, outputs.to_vec(), 0);
+    bincode::serialize(&tx).expect("serialize transaction").len()
+}
+
+/// Assembles a transaction from funded inputs and payments, adding a change
+/// output sized so the signed transaction pays the target fee rate
+#[derive(Debug, Clone)]
+pub struct TransactionBuilder {
+    inputs: Vec<(OutPoint, Amount)>,
+    outputs: Vec<TxOut>,
+    change: Option<Vec<u8>>,
+    fee_rate: Amount,
+    dust_threshold: Amount,
+    lock_time: u32,
+}
+
+impl Default for TransactionBuilder {
+    fn default() -> Self {
+        Self {
+            inputs: Vec::new(),
+            outputs: Vec::new(),
+            change: None,
+            fee_rate: 0,
+            dust_threshold: DEFAULT_DUST_THRESHOLD,
+            lock_time: 0,
+        }
+    }
+}
+
+impl TransactionBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spend `prevout`, which holds `value`
+    pub fn add_input(mut self, prevout: OutPoint, value: Amount) -> Self {
+        self.inputs.push((prevout, value));
+        self
+    }
+
+    pub fn add_output(mut self, output: TxOut) -> Self {
+        self.outputs.push(output);
+        self
+    }
+
+    /// Target fee rate in sats per kB of signed transaction
+    pub fn set_fee_rate(mut self, fee_rate: Amount) -> Self {
+        self.fee_rate = fee_rate;
+        self
+    }
+
+    /// Pay whatever the fee leaves over to `pubkey`
+    pub fn add_change(mut self, pubkey: Vec<u8>) -> Self {
+        self.change = Some(pubkey);
+        self
+    }
+
+    pub fn set_dust_threshold(mut self, dust_threshold: Amount) -> Self {
+        self.dust_threshold = dust_threshold;
+        self
+    }
+
+    pub fn set_lock_time(mut self, lock_time: u32) -> Self {
+        self.lock_time = lock_time;
+        self
+    }
+
+    /// Signed size estimate for the current inputs and outputs, plus a
+    /// change output when `with_change` and a change key are set
+    pub fn estimated_size(&self, with_change: bool) -> usize {
+        let inputs: Vec<OutPoint> = self.inputs.iter().map(|(prevout, _)| prevout.clone()).collect();
+        let mut outputs = self.outputs.clone();
+        if let (true, Some(pubkey)) = (with_change, &self.change) {
+            outputs.push(TxOut::new_p2pq(0, pubkey.clone()));
+        }
+        estimate_signed_size(&inputs, &outputs)
+    }
+
+    /// Add inputs from `candidates`, largest first, until they cover the
+    /// outputs plus the fee for the signed size including change
+    pub fn select_inputs(mut self, candidates: &[(OutPoint, Amount)]) -> Result<Self, TransactionError> {
+        let mut candidates = candidates.to_vec();
+        candidates.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.txid.0.cmp(&b.0.txid.0)).then_with(|| a.0.vout.cmp(&b.0.vout)));
+
+        let total_out = sum(self.outputs.iter().map(|out| out.value))?;
+        let mut total_in = sum(self.inputs.iter().map(|(_, value)| *value))?;
+        let mut candidates = candidates.into_iter();
+        loop {
+            let fee = fee_for_size(self.estimated_size(true), self.fee_rate);
+            if total_in >= total_out + fee && !self.inputs.is_empty() {
+                return Ok(self);
+            }
+            let (prevout, value) = candidates.next().ok_or(TransactionError::InsufficientFunds)?;
+            total_in = total_in.checked_add(value).ok_or(TransactionError::InvalidAmount)?;
+            self.inputs.push((prevout, value));
+        }
+    }
+
+    /// Sign with `signer`, re-signing until the fee matches the signed size.
+    /// Change below the dust threshold is dropped and left to the fee.
+    pub fn build_and_sign(&self, signer: &impl TxSigner) -> Result<Transaction, TransactionError> {
+        let total_in = sum(self.inputs.iter().map(|(_, value)| *value))?;
+        let total_out = sum(self.outputs.iter().map(|out| out.value))?;
+        let available = total_in.checked_sub(total_out).ok_or(TransactionError::InvalidAmount)?;
+
+        // Starting from the estimate, the first signing pass normally settles
+        let mut fee = fee_for_size(self.estimated_size(true), self.fee_rate);
+        for _ in 0..MAX_FEE_PASSES {
+            let change = available - fee;
+            let change = (self.change.is_some() && change >= self.dust_threshold).then_some(change);
+            let tx = self.sign(change, signer);
+            let needed = fee_for_size(bincode::serialize(&tx).expect("serialize transaction").len(), self.fee_rate);
+            match change {
+                Some(_) if needed == fee => return Ok(tx),
+                Some(_) => fee = needed,
+                // Without change the whole leftover is the fee; it only has to cover what's owed
+                None if available >= needed => return Ok(tx),
+                None => return Err(TransactionError::InsufficientFunds),
+            }
+        }
+        Err(TransactionError::FeeNotConverged)
+    }
+
+    fn sign(&self, change: Option<Amount>, signer: &impl TxSigner) -> Transaction {
+        let mut outputs = self.outputs.clone();
+        if let (Some(pubkey), Some(change)) = (&self.change, change) {
+            outputs.push(TxOut::new_p2pq(change, pubkey.clone()));
+        }
+        let inputs = self.inputs.iter().map(|(prevout, _)| TxIn::new(prevout.clone(), Vec::new(), false)).collect();
+        let mut tx = Transaction::new(1, inputs, outputs, self.lock_time);
+
+        let sighash = signing_hash(&tx);
+        for (i, input) in tx.vin.iter_mut().enumerate() {
+            input.pq_signature = signer.sign(i, &sighash);
+        }
+        tx
+    }
+}
+
+fn sum(values: impl Iterator<Item = Amount>) -> Result<Amount, TransactionError> {
+    values.try_fold(0 as Amount, |acc, v| {
+        if v < 0 {
+            return Err(TransactionError::InvalidAmount);
+        }
+        acc.checked_add(v).ok_or(TransactionError::InvalidAmount)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pqcrypto_traits::sign::PublicKey as _;
+    use qc_crypto::{generate_keypair, pq_verify};
+    use qc_types::Hash32;
+
+    fn fee_rate_of(tx: &Transaction, total_in: Amount) -> f64 {
+        let fee = total_in - tx.total_output_value().unwrap();
+        fee as f64 * 1000.0 / bincode::serialize(tx).unwrap().len() as f64
+    }
+
+    #[test]
+    fn test_change_hits_target_fee_rate() {
+        let (pk, sk) = generate_keypair();
+        let pubkey = pk.as_bytes().to_vec();
+        let builder = TransactionBuilder::new()
+            .add_input(OutPoint::new(Hash32([1u8; 32]), 0), 600_000)
+            .add_input(OutPoint::new(Hash32([2u8; 32]), 1), 400_000)
+            .add_output(TxOut::new_p2pq(250_000, vec![7u8; 1312]))
+            .add_change(pubkey.clone());
+
+        for fee_rate in [1_000, 5_000, 20_000] {
+            let tx = builder.clone().set_fee_rate(fee_rate).build_and_sign(&sk).unwrap();
+            assert_eq!(tx.vout.len(), 2);
+            assert_eq!(tx.vout[1].kind, TxOut::new_p2pq(0, pubkey.clone()).kind);
+
+            // Rounding the fee up to whole sats is the only slack
+            let size = bincode::serialize(&tx).unwrap().len() as f64;
+            let actual = fee_rate_of(&tx, 1_000_000);
+            assert!(actual >= fee_rate as f64 && actual < fee_rate as f64 + 1000.0 / size, "rate {} for target {}", actual, fee_rate);
+
+            let sighash = signing_hash(&tx);
+            assert!(tx.vin.iter().all(|input| pq_verify(&pk, &sighash, &input.pq_signature)));
+        }
+
+        // Deterministic up to the signatures
+        let a = builder.clone().set_fee_rate(1_000).build_and_sign(&sk).unwrap();
+        let b = builder.clone().set_fee_rate(1_000).build_and_sign(&sk).unwrap();
+        assert_eq!(a.vout, b.vout);
+    }
+
+    #[test]
+    fn test_estimated_size_matches_signed_size() {
+        let (pk, sk) = generate_keypair();
+        for input_count in 1..=4u8 {
+            let builder = (0..input_count)
+                .fold(TransactionBuilder::new(), |b, i| b.add_input(OutPoint::new(Hash32([i; 32]), i as u32), 100_000))
+                .add_output(TxOut::new_p2pq(50_000, vec![7u8; 1312]))
+                .add_change(pk.as_bytes().to_vec())
+                .set_fee_rate(1_000);
+            let tx = builder.build_and_sign(&sk).unwrap();
+            let actual = bincode::serialize(&tx).unwrap().len();
+            let estimated = builder.estimated_size(true);
+            assert!(estimated.abs_diff(actual) <= 2 * input_count as usize, "{} inputs: {} vs {}", input_count, estimated, actual);
+
+            // Ignoring signatures would miss most of the transaction
+            let unsigned = Transaction { vin: tx.vin.iter().map(|i| TxIn::new(i.prevout.clone(), vec![], false)).collect(), ..tx.clone() };
+            assert!(bincode::serialize(&unsigned).unwrap().len() * 2 < actual);
+        }
+    }
+
+    #[test]
+    fn test_selection_covers_signed_fee() {
+        let (pk, sk) = generate_keypair();
+        let candidates: Vec<(OutPoint, Amount)> =
+            (1..=5u8).map(|i| (OutPoint::new(Hash32([i; 32]), 0), 10_000)).collect();
+        let builder = TransactionBuilder::new()
+            .add_output(TxOut::new_p2pq(25_000, vec![7u8; 1312]))
+            .add_change(pk.as_bytes().to_vec())
+            .set_fee_rate(1_000);
+
+        // Three inputs cover the payment only if signatures are left out of the fee
+        let funded = builder.clone().select_inputs(&candidates).unwrap();
+        let tx = funded.build_and_sign(&sk).unwrap();
+        assert_eq!(tx.vin.len(), 4);
+        let total_in = 10_000 * tx.vin.len() as Amount;
+        assert!(fee_rate_of(&tx, total_in) >= 1_000.0);
+
+        assert!(matches!(builder.select_inputs(&candidates[..2]), Err(TransactionError::InsufficientFunds)));
+    }
+
+    #[test]
+    fn test_dust_change_dropped_and_shortfall_rejected() {
+        let (_, sk) = generate_keypair();
+        let payment = TxOut::new_p2pq(100_000, vec![7u8; 1312]);
+        let funded = |value| {
+            TransactionBuilder::new()
+                .add_input(OutPoint::new(Hash32([1u8; 32]), 0), value)
+                .add_output(payment.clone())
+                .add_change(vec![8u8; 1312])
+                .set_fee_rate(1_000)
+        };
+
+        let tx = funded(100_000 + 5_000).build_and_sign(&sk).unwrap();
+        assert_eq!(tx.vout, vec![payment.clone()]);
+        assert!(fee_rate_of(&tx, 105_000) >= 1_000.0);
+
+        assert!(matches!(funded(100_001).build_and_sign(&sk), Err(TransactionError::InsufficientFunds)));
+    }
 }
@@ -1,13 +1,209 @@
 use anyhow::Result;
+use async_trait::async_trait;
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
-use crate::{BlockData, NetworkMetrics};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use crate::{AttackAnalysis, BlockData, NetworkMetrics};
+
+/// Samples buffered before a write is forced
+pub const DEFAULT_BATCH_SIZE: usize = 60;
+/// Longest a sample waits in the buffer before a write is forced
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Analyses at or above this risk are kept for later review. The alert
+/// threshold is higher; recording near-misses too shows how risk built up.
+pub const MIN_RECORDED_RISK: f64 = 0.5;
+
+/// A past attack-detector result
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AttackRecord {
+    pub detected_at: DateTime<Utc>,
+    pub block_height: u64,
+    pub risk_level: f64,
+    pub attack_probabilities: HashMap<String, f64>,
+}
+
+/// A measurement waiting to be written
+#[derive(Debug, Clone)]
+pub enum AnalyticsSample {
+    Block(BlockData),
+    Network {
+        recorded_at: DateTime<Utc>,
+        metrics: NetworkMetrics,
+    },
+}
+
+/// Samples collected between database writes
+#[derive(Debug)]
+pub struct SampleBuffer {
+    samples: Vec<AnalyticsSample>,
+    max_batch: usize,
+    flush_interval: Duration,
+    last_flush: Instant,
+}
+
+impl SampleBuffer {
+    pub fn new(max_batch: usize, flush_interval: Duration) -> Self {
+        Self {
+            samples: Vec::new(),
+            max_batch: max_batch.max(1),
+            flush_interval,
+            last_flush: Instant::now(),
+        }
+    }
+
+    pub fn push(&mut self, sample: AnalyticsSample) {
+        self.samples.push(sample);
+    }
+
+    /// Whether the buffer is full or has waited long enough
+    pub fn is_due(&self, now: Instant) -> bool {
+        !self.samples.is_empty()
+            && (self.samples.len() >= self.max_batch
+                || now.duration_since(self.last_flush) >= self.flush_interval)
+    }
+
+    /// Everything buffered, as one batch
+    pub fn take(&mut self, now: Instant) -> Vec<AnalyticsSample> {
+        self.last_flush = now;
+        std::mem::take(&mut self.samples)
+    }
+
+    /// Put back a batch that failed to write, ahead of anything newer
+    pub fn restore(&mut self, mut batch: Vec<AnalyticsSample>) {
+        batch.append(&mut self.samples);
+        self.samples = batch;
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+}
+
+impl Default for SampleBuffer {
+    fn default() -> Self {
+        Self::new(DEFAULT_BATCH_SIZE, DEFAULT_FLUSH_INTERVAL)
+    }
+}
+
+/// Where analytics samples are persisted
+#[async_trait]
+pub trait AnalyticsStore: Send + Sync {
+    /// Write a batch atomically: all samples or none
+    async fn write_batch(&self, batch: &[AnalyticsSample]) -> Result<()>;
+    /// Most recent blocks, highest first
+    async fn recent_blocks(&self, limit: u32) -> Result<Vec<BlockData>>;
+    async fn store_attack_record(&self, record: &AttackRecord) -> Result<()>;
+    /// Records detected at or after `since` with at least `min_risk_level`, oldest first
+    async fn attack_history(&self, since: DateTime<Utc>, min_risk_level: f64) -> Result<Vec<AttackRecord>>;
+    async fn close(&self);
+}
 
 pub struct BlockchainAnalytics {
-    db_pool: PgPool,
+    store: Box<dyn AnalyticsStore>,
+    buffer: SampleBuffer,
 }
 
 impl BlockchainAnalytics {
+    pub fn new(store: Box<dyn AnalyticsStore>) -> Self {
+        Self { store, buffer: SampleBuffer::default() }
+    }
+
+    /// Override when buffered samples are written
+    pub fn with_batch_limits(mut self, max_batch: usize, flush_interval: Duration) -> Self {
+        self.buffer = SampleBuffer::new(max_batch, flush_interval);
+        self
+    }
+
+    /// Buffer a block sample, writing the buffer out if it's due
+    pub async fn store_block_data(&mut self, block: &BlockData) -> Result<()> {
+        self.buffer.push(AnalyticsSample::Block(block.clone()));
+        self.flush_if_due().await
+    }
+
+    /// Buffer a network sample, writing the buffer out if it's due
+    pub async fn store_network_metrics(&mut self, metrics: &NetworkMetrics) -> Result<()> {
+        self.buffer.push(AnalyticsSample::Network {
+            recorded_at: Utc::now(),
+            metrics: metrics.clone(),
+        });
+        self.flush_if_due().await
+    }
+
+    pub fn pending_samples(&self) -> usize {
+        self.buffer.len()
+    }
+
+    async fn flush_if_due(&mut self) -> Result<()> {
+        if self.buffer.is_due(Instant::now()) {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Write every buffered sample in one transaction. On failure the
+    /// samples stay buffered for the next attempt.
+    pub async fn flush(&mut self) -> Result<usize> {
+        if self.buffer.is_empty() {
+            return Ok(0);
+        }
+
+        let batch = self.buffer.take(Instant::now());
+        match self.store.write_batch(&batch).await {
+            Ok(()) => Ok(batch.len()),
+            Err(e) => {
+                self.buffer.restore(batch);
+                Err(e)
+            }
+        }
+    }
+
+    pub async fn get_recent_data(&self, limit: u32) -> Result<Vec<BlockData>> {
+        self.store.recent_blocks(limit).await
+    }
+
+    pub async fn get_training_data(&self, limit: u32) -> Result<Vec<BlockData>> {
+        // Get historical data for model training
+        self.get_recent_data(limit).await
+    }
+
+    /// Persist an analysis if it is risky enough to be worth reviewing.
+    /// Written immediately rather than batched, so alerts survive a crash.
+    pub async fn record_attack_analysis(&self, block_height: u64, analysis: &AttackAnalysis) -> Result<bool> {
+        if analysis.risk_level < MIN_RECORDED_RISK {
+            return Ok(false);
+        }
+        let record = AttackRecord {
+            detected_at: Utc::now(),
+            block_height,
+            risk_level: analysis.risk_level,
+            attack_probabilities: analysis.attack_probabilities.clone(),
+        };
+        self.store.store_attack_record(&record).await?;
+        Ok(true)
+    }
+
+    /// Detections since `since` with at least `min_risk_level`, oldest first
+    pub async fn get_attack_history(&self, since: DateTime<Utc>, min_risk_level: f64) -> Result<Vec<AttackRecord>> {
+        self.store.attack_history(since, min_risk_level).await
+    }
+
+    pub async fn close(&self) {
+        self.store.close().await;
+    }
+}
+
+pub struct PgAnalyticsStore {
+    db_pool: PgPool,
+}
+
+impl PgAnalyticsStore {
     pub async fn new(db_pool: &PgPool) -> Result<Self> {
         // Initialize database tables for analytics
         sqlx::query(r#"
@@ -59,12 +255,26 @@ impl BlockchainAnalytics {
             CREATE INDEX IF NOT EXISTS idx_ai_predictions_type_time ON ai_predictions(prediction_type, timestamp);
         "#).execute(db_pool).await?;
 
+        sqlx::query(r#"
+            CREATE TABLE IF NOT EXISTS attack_detections (
+                id BIGSERIAL PRIMARY KEY,
+                detected_at TIMESTAMPTZ NOT NULL,
+                block_height BIGINT NOT NULL,
+                risk_level DOUBLE PRECISION NOT NULL,
+                attack_probabilities JSONB NOT NULL
+            )
+        "#).execute(db_pool).await?;
+
+        sqlx::query(r#"
+            CREATE INDEX IF NOT EXISTS idx_attack_detections_time ON attack_detections(detected_at)
+        "#).execute(db_pool).await?;
+
         Ok(Self {
             db_pool: db_pool.clone(),
         })
     }
 
-    pub async fn store_block_data(&self, block: &BlockData) -> Result<()> {
+    async fn insert_block_data(tx: &mut sqlx::PgConnection, block: &BlockData) -> Result<()> {
         sqlx::query(r#"
             INSERT INTO block_analytics 
             (height, timestamp, hash, difficulty, tx_count, size_bytes, propagation_time_ms)
@@ -80,65 +290,37 @@ impl BlockchainAnalytics {
         .bind(block.tx_count as i32)
         .bind(block.size_bytes as i64)
         .bind(block.propagation_time_ms.map(|ms| ms as i64))
-        .execute(&self.db_pool)
+        .execute(tx)
         .await?;
 
         Ok(())
     }
 
-    pub async fn store_network_metrics(&self, metrics: &NetworkMetrics) -> Result<()> {
+    async fn insert_network_metrics(
+        tx: &mut sqlx::PgConnection,
+        recorded_at: DateTime<Utc>,
+        metrics: &NetworkMetrics
+    ) -> Result<()> {
         let fee_percentiles_json = serde_json::to_value(&metrics.fee_percentiles)?;
 
         sqlx::query(r#"
             INSERT INTO network_analytics 
             (timestamp, peer_count, mempool_size, avg_block_time, hashrate_estimate, orphan_rate, fee_percentiles)
-            VALUES (NOW(), $1, $2, $3, $4, $5, $6)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
         "#)
+        .bind(recorded_at)
         .bind(metrics.peer_count as i32)
         .bind(metrics.mempool_size as i32)
         .bind(metrics.avg_block_time)
         .bind(metrics.hashrate_estimate)
         .bind(metrics.orphan_rate)
         .bind(fee_percentiles_json)
-        .execute(&self.db_pool)
+        .execute(tx)
         .await?;
 
         Ok(())
     }
 
-    pub async fn get_recent_data(&self, limit: u32) -> Result<Vec<BlockData>> {
-        let rows = sqlx::query(r#"
-            SELECT height, timestamp, hash, difficulty, tx_count, size_bytes, propagation_time_ms
-            FROM block_analytics 
-            ORDER BY height DESC 
-            LIMIT $1
-        "#)
-        .bind(limit as i64)
-        .fetch_all(&self.db_pool)
-        .await?;
-
-        let mut blocks = Vec::new();
-        for row in rows {
-            blocks.push(BlockData {
-                height: row.get::<i64, _>("height") as u64,
-                timestamp: row.get("timestamp"),
-                hash: row.get("hash"),
-                difficulty: row.get("difficulty"),
-                tx_count: row.get::<i32, _>("tx_count") as u32,
-                size_bytes: row.get::<i64, _>("size_bytes") as u64,
-                propagation_time_ms: row.get::<Option<i64>, _>("propagation_time_ms")
-                    .map(|ms| ms as u64),
-            });
-        }
-
-        Ok(blocks)
-    }
-
-    pub async fn get_training_data(&self, limit: u32) -> Result<Vec<BlockData>> {
-        // Get historical data for model training
-        self.get_recent_data(limit).await
-    }
-
     pub async fn store_ai_prediction(
         &self,
         prediction_type: &str,
@@ -195,3 +377,244 @@ impl BlockchainAnalytics {
         Ok(row.get::<Option<f64>, _>("avg_accuracy").unwrap_or(0.0))
     }
 }
+
+#[async_trait]
+impl AnalyticsStore for PgAnalyticsStore {
+    async fn write_batch(&self, batch: &[AnalyticsSample]) -> Result<()> {
+        let mut tx = self.db_pool.begin().await?;
+        for sample in batch {
+            match sample {
+                AnalyticsSample::Block(block) => Self::insert_block_data(&mut tx, block).await?,
+                AnalyticsSample::Network { recorded_at, metrics } => {
+                    Self::insert_network_metrics(&mut tx, *recorded_at, metrics).await?
+                }
+            }
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn recent_blocks(&self, limit: u32) -> Result<Vec<BlockData>> {
+        let rows = sqlx::query(r#"
+            SELECT height, timestamp, hash, difficulty, tx_count, size_bytes, propagation_time_ms
+            FROM block_analytics 
+            ORDER BY height DESC 
+            LIMIT $1
+        "#)
+        .bind(limit as i64)
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        let mut blocks = Vec::new();
+        for row in rows {
+            blocks.push(BlockData {
+                height: row.get::<i64, _>("height") as u64,
+                timestamp: row.get("timestamp"),
+                hash: row.get("hash"),
+                difficulty: row.get("difficulty"),
+                tx_count: row.get::<i32, _>("tx_count") as u32,
+                size_bytes: row.get::<i64, _>("size_bytes") as u64,
+                propagation_time_ms: row.get::<Option<i64>, _>("propagation_time_ms")
+                    .map(|ms| ms as u64),
+            });
+        }
+
+        Ok(blocks)
+    }
+
+    async fn store_attack_record(&self, record: &AttackRecord) -> Result<()> {
+        sqlx::query(r#"
+            INSERT INTO attack_detections
+            (detected_at, block_height, risk_level, attack_probabilities)
+            VALUES ($1, $2, $3, $4)
+        "#)
+        .bind(record.detected_at)
+        .bind(record.block_height as i64)
+        .bind(record.risk_level)
+        .bind(serde_json::to_value(&record.attack_probabilities)?)
+        .execute(&self.db_pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn attack_history(&self, since: DateTime<Utc>, min_risk_level: f64) -> Result<Vec<AttackRecord>> {
+        let rows = sqlx::query(r#"
+            SELECT detected_at, block_height, risk_level, attack_probabilities
+            FROM attack_detections
+            WHERE detected_at >= $1 AND risk_level >= $2
+            ORDER BY detected_at ASC
+        "#)
+        .bind(since)
+        .bind(min_risk_level)
+        .fetch_all(&self.db_pool)
+        .await?;
+
+        let mut records = Vec::new();
+        for row in rows {
+            records.push(AttackRecord {
+                detected_at: row.get("detected_at"),
+                block_height: row.get::<i64, _>("block_height") as u64,
+                risk_level: row.get("risk_level"),
+                attack_probabilities: serde_json::from_value(row.get("attack_probabilities"))?,
+            });
+        }
+
+        Ok(records)
+    }
+
+    async fn close(&self) {
+        // Waits for in-flight queries before disconnecting
+        self.db_pool.close().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryStore;
+
+    fn block(height: u64) -> AnalyticsSample {
+        AnalyticsSample::Block(BlockData {
+            height,
+            timestamp: Utc::now(),
+            hash: format!("{:064x}", height),
+            difficulty: 1.0,
+            tx_count: 1,
+            size_bytes: 250,
+            propagation_time_ms: None,
+        })
+    }
+
+    fn heights(batch: &[AnalyticsSample]) -> Vec<u64> {
+        batch.iter().filter_map(|sample| match sample {
+            AnalyticsSample::Block(block) => Some(block.height),
+            AnalyticsSample::Network { .. } => None,
+        }).collect()
+    }
+
+    #[test]
+    fn test_full_buffer_flushes_as_one_batch() {
+        let start = Instant::now();
+        let mut buffer = SampleBuffer::new(4, Duration::from_secs(3600));
+
+        for height in 0..3 {
+            buffer.push(block(height));
+            assert!(!buffer.is_due(start));
+        }
+        buffer.push(block(3));
+        assert!(buffer.is_due(start));
+
+        let batch = buffer.take(start);
+        assert_eq!(heights(&batch), vec![0, 1, 2, 3]);
+        assert!(buffer.is_empty());
+        assert!(!buffer.is_due(start));
+    }
+
+    #[test]
+    fn test_interval_forces_partial_flush() {
+        let mut buffer = SampleBuffer::new(100, Duration::from_secs(10));
+        let start = Instant::now();
+
+        assert!(!buffer.is_due(start + Duration::from_secs(60)), "nothing to write");
+        buffer.push(block(1));
+        assert!(!buffer.is_due(start + Duration::from_secs(5)));
+        assert!(buffer.is_due(start + Duration::from_secs(10)));
+
+        // The interval restarts from the last flush
+        buffer.take(start + Duration::from_secs(10));
+        buffer.push(block(2));
+        assert!(!buffer.is_due(start + Duration::from_secs(15)));
+    }
+
+    #[test]
+    fn test_failed_batch_kept_in_order() {
+        let now = Instant::now();
+        let mut buffer = SampleBuffer::new(2, Duration::from_secs(10));
+        buffer.push(block(1));
+        buffer.push(block(2));
+        let failed = buffer.take(now);
+
+        buffer.push(block(3));
+        buffer.restore(failed);
+        assert_eq!(heights(&buffer.take(now)), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_shutdown_takes_partial_batch() {
+        let now = Instant::now();
+        let mut buffer = SampleBuffer::default();
+        buffer.push(block(7));
+        buffer.push(AnalyticsSample::Network {
+            recorded_at: Utc::now(),
+            metrics: NetworkMetrics {
+                peer_count: 8,
+                mempool_size: 0,
+                avg_block_time: 600.0,
+                hashrate_estimate: 0.0,
+                orphan_rate: 0.0,
+                fee_percentiles: vec![],
+            },
+        });
+
+        // Not due yet, but a shutdown flush writes it anyway
+        assert!(!buffer.is_due(now));
+        assert_eq!(buffer.take(now).len(), 2);
+        assert!(buffer.is_empty());
+    }
+
+    fn attack(detected_at: DateTime<Utc>, block_height: u64, risk_level: f64) -> AttackRecord {
+        AttackRecord {
+            detected_at,
+            block_height,
+            risk_level,
+            attack_probabilities: HashMap::from([("double_spend".to_string(), risk_level)]),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_attack_history_filters_by_time_and_risk() {
+        let store = MemoryStore::default();
+        let now = Utc::now();
+        let hours_ago = |h: i64| now - chrono::Duration::hours(h);
+        for record in [
+            attack(hours_ago(30), 100, 0.95),
+            attack(hours_ago(5), 500, 0.6),
+            attack(hours_ago(3), 700, 0.9),
+            attack(hours_ago(1), 800, 0.75),
+        ] {
+            store.store_attack_record(&record).await.unwrap();
+        }
+        let analytics = BlockchainAnalytics::new(Box::new(store));
+
+        let last_day = analytics.get_attack_history(hours_ago(24), 0.0).await.unwrap();
+        assert_eq!(last_day.iter().map(|r| r.block_height).collect::<Vec<_>>(), vec![500, 700, 800]);
+
+        let severe = analytics.get_attack_history(hours_ago(24), 0.8).await.unwrap();
+        assert_eq!(severe, vec![attack(hours_ago(3), 700, 0.9)]);
+
+        assert_eq!(analytics.get_attack_history(hours_ago(48), 0.9).await.unwrap().len(), 2);
+        assert!(analytics.get_attack_history(now, 0.0).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_only_risky_analyses_recorded() {
+        let store = MemoryStore::default();
+        let analytics = BlockchainAnalytics::new(Box::new(store));
+        let start = Utc::now();
+        let analysis = |risk_level: f64| AttackAnalysis {
+            risk_level,
+            attack_probabilities: HashMap::from([("selfish_mining".to_string(), risk_level)]),
+            recommended_fee_floor: 1.0,
+            confidence: 0.9,
+        };
+
+        assert!(!analytics.record_attack_analysis(10, &analysis(0.2)).await.unwrap());
+        assert!(analytics.record_attack_analysis(11, &analysis(MIN_RECORDED_RISK)).await.unwrap());
+        assert!(analytics.record_attack_analysis(12, &analysis(0.85)).await.unwrap());
+
+        let history = analytics.get_attack_history(start, 0.0).await.unwrap();
+        assert_eq!(history.iter().map(|r| r.block_height).collect::<Vec<_>>(), vec![11, 12]);
+        assert_eq!(history[1].attack_probabilities["selfish_mining"], 0.85);
+    }
+}
@@ -0,0 +1,112 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use crate::{BlockData, NetworkMetrics, SentinelOutput};
+
+/// Where sentinel outputs are published for real-time consumers
+#[async_trait]
+pub trait OutputSink: Send {
+    async fn publish(&mut self, output: &SentinelOutput) -> Result<()>;
+    async fn close(&mut self);
+}
+
+/// The node the sentinel watches and advises
+#[async_trait]
+pub trait NodeSource: Send + Sync {
+    async fn latest_block(&self) -> Result<BlockData>;
+    async fn network_metrics(&self) -> Result<NetworkMetrics>;
+    async fn send_update(&self, output: &SentinelOutput) -> Result<()>;
+}
+
+/// Publishes outputs on the `quantumcoin:sentinel` Redis channel
+pub struct RedisSink {
+    client: redis::Client,
+    /// Reused for every publish; dropped on close
+    conn: Option<redis::Connection>,
+}
+
+impl RedisSink {
+    pub fn new(client: redis::Client) -> Self {
+        Self { client, conn: None }
+    }
+}
+
+#[async_trait]
+impl OutputSink for RedisSink {
+    async fn publish(&mut self, output: &SentinelOutput) -> Result<()> {
+        if self.conn.is_none() {
+            self.conn = Some(self.client.get_connection()?);
+        }
+        let output_json = serde_json::to_string(output)?;
+        if let Some(conn) = self.conn.as_mut() {
+            redis::cmd("PUBLISH")
+                .arg("quantumcoin:sentinel")
+                .arg(&output_json)
+                .execute(conn);
+        }
+        Ok(())
+    }
+
+    async fn close(&mut self) {
+        self.conn = None;
+    }
+}
+
+/// Talks to a node over its HTTP RPC
+pub struct RpcNode {
+    node_rpc_url: String,
+    client: reqwest::Client,
+}
+
+impl RpcNode {
+    pub fn new(node_rpc_url: String) -> Self {
+        Self { node_rpc_url, client: reqwest::Client::new() }
+    }
+}
+
+#[async_trait]
+impl NodeSource for RpcNode {
+    async fn latest_block(&self) -> Result<BlockData> {
+        let response = reqwest::get(&format!("{}/status", self.node_rpc_url)).await?;
+        let status: serde_json::Value = response.json().await?;
+
+        let height = status["height"].as_u64().unwrap_or(0);
+        let latest_block_response = reqwest::get(&format!("{}/block/latest", self.node_rpc_url)).await?;
+        let block: serde_json::Value = latest_block_response.json().await?;
+
+        Ok(BlockData {
+            height,
+            timestamp: Utc::now(), // In real implementation, parse from block
+            hash: block["hash"].as_str().unwrap_or("").to_string(),
+            difficulty: status["difficulty"].as_f64().unwrap_or(0.0),
+            tx_count: block["transactions"].as_array().map(|v| v.len() as u32).unwrap_or(0),
+            size_bytes: block["size"].as_u64().unwrap_or(0),
+            propagation_time_ms: None, // Measured by peer network
+        })
+    }
+
+    async fn network_metrics(&self) -> Result<NetworkMetrics> {
+        let response = reqwest::get(&format!("{}/network/metrics", self.node_rpc_url)).await?;
+        let metrics: serde_json::Value = response.json().await?;
+
+        Ok(NetworkMetrics {
+            peer_count: metrics["peer_count"].as_u64().unwrap_or(0) as u32,
+            mempool_size: metrics["mempool_size"].as_u64().unwrap_or(0) as u32,
+            avg_block_time: metrics["avg_block_time"].as_f64().unwrap_or(15.0),
+            hashrate_estimate: metrics["hashrate"].as_f64().unwrap_or(0.0),
+            orphan_rate: metrics["orphan_rate"].as_f64().unwrap_or(0.0),
+            fee_percentiles: metrics["fee_percentiles"]
+                .as_array()
+                .map(|arr| arr.iter().filter_map(|v| v.as_f64()).collect())
+                .unwrap_or_default(),
+        })
+    }
+
+    async fn send_update(&self, output: &SentinelOutput) -> Result<()> {
+        self.client.post(&format!("{}/sentinel/update", self.node_rpc_url))
+            .json(output)
+            .send()
+            .await?;
+        Ok(())
+    }
+}
@@ -2,17 +2,22 @@ use anyhow::Result;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use tokio::sync::watch;
 use tokio::time::{interval, Duration};
 use tracing::{info, warn, error};
 
 mod analytics;
+mod backend;
 mod attack_detection;
 mod network_optimizer;
 mod performance_tuner;
 mod production_models;
 mod perfect_ai_system;
+#[cfg(any(test, feature = "in-memory"))]
+mod memory;
 
 use analytics::*;
+use backend::*;
 use attack_detection::*;
 use network_optimizer::*;
 use performance_tuner::*;
@@ -39,7 +44,7 @@ pub struct NetworkMetrics {
     pub fee_percentiles: Vec<f64>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SentinelOutput {
     pub timestamp: DateTime<Utc>,
     pub risk_level: f64,           // 0.0 = safe, 1.0 = high risk
@@ -56,9 +61,8 @@ pub struct AISentinel {
     attack_detector: AttackDetector,
     network_optimizer: NetworkOptimizer,
     performance_tuner: PerformanceTuner,
-    node_rpc_url: String,
-    db_pool: sqlx::PgPool,
-    redis_client: redis::Client,
+    node: Box<dyn NodeSource>,
+    sink: Box<dyn OutputSink>,
 }
 
 impl AISentinel {
@@ -69,25 +73,44 @@ impl AISentinel {
     ) -> Result<Self> {
         let db_pool = sqlx::PgPool::connect(&database_url).await?;
         let redis_client = redis::Client::open(redis_url)?;
-        
-        // Initialize AI subsystems
-        let analytics = BlockchainAnalytics::new(&db_pool).await?;
-        let attack_detector = AttackDetector::new();
-        let network_optimizer = NetworkOptimizer::new();
-        let performance_tuner = PerformanceTuner::new();
+        let store = PgAnalyticsStore::new(&db_pool).await?;
 
-        Ok(Self {
+        Ok(Self::with_backends(
+            Box::new(RpcNode::new(node_rpc_url)),
+            BlockchainAnalytics::new(Box::new(store)),
+            Box::new(RedisSink::new(redis_client)),
+        ))
+    }
+
+    pub fn with_backends(
+        node: Box<dyn NodeSource>,
+        analytics: BlockchainAnalytics,
+        sink: Box<dyn OutputSink>
+    ) -> Self {
+        // Initialize AI subsystems
+        Self {
             analytics,
-            attack_detector,
-            network_optimizer,
-            performance_tuner,
-            node_rpc_url,
-            db_pool,
-            redis_client,
-        })
+            attack_detector: AttackDetector::new(),
+            network_optimizer: NetworkOptimizer::new(),
+            performance_tuner: PerformanceTuner::new(),
+            node,
+            sink,
+        }
+    }
+
+    /// A sentinel backed by a simulated node and in-memory storage
+    #[cfg(any(test, feature = "in-memory"))]
+    pub fn in_memory() -> Self {
+        Self::with_backends(
+            Box::new(memory::SimulatedNode::default()),
+            BlockchainAnalytics::new(Box::new(memory::MemoryStore::default())),
+            Box::new(memory::MemorySink::default()),
+        )
     }
 
-    pub async fn run(&mut self) -> Result<()> {
+    /// Run until `shutdown` changes (or its sender is dropped), then flush
+    /// and close the analytics store and output sink
+    pub async fn run(&mut self, mut shutdown: watch::Receiver<bool>) -> Result<()> {
         info!("🤖 AI Sentinel starting - enhancing QuantumCoin blockchain");
         
         let mut block_monitor = interval(Duration::from_secs(1));  // Monitor every second
@@ -96,6 +119,11 @@ impl AISentinel {
         
         loop {
             tokio::select! {
+                biased;
+                _ = shutdown.changed() => {
+                    info!("AI Sentinel received shutdown signal");
+                    break;
+                }
                 _ = block_monitor.tick() => {
                     if let Err(e) = self.monitor_blockchain().await {
                         error!("Block monitoring error: {}", e);
@@ -113,12 +141,27 @@ impl AISentinel {
                 }
             }
         }
+
+        self.shutdown().await
+    }
+
+    async fn shutdown(&mut self) -> Result<()> {
+        match self.analytics.flush().await {
+            Ok(written) => info!("Flushed {} buffered analytics samples", written),
+            Err(e) => error!("Failed to flush {} analytics samples: {}", self.analytics.pending_samples(), e),
+        }
+
+        self.analytics.close().await;
+        self.sink.close().await;
+
+        info!("AI Sentinel stopped");
+        Ok(())
     }
 
     async fn monitor_blockchain(&mut self) -> Result<()> {
         // Fetch latest block data from node
-        let block_data = self.fetch_latest_block_data().await?;
-        let network_metrics = self.fetch_network_metrics().await?;
+        let block_data = self.node.latest_block().await?;
+        let network_metrics = self.node.network_metrics().await?;
 
         // Store in analytics database
         self.analytics.store_block_data(&block_data).await?;
@@ -126,6 +169,9 @@ impl AISentinel {
 
         // Real-time attack detection
         let attack_analysis = self.attack_detector.analyze_block(&block_data, &network_metrics).await?;
+        if let Err(e) = self.analytics.record_attack_analysis(block_data.height, &attack_analysis).await {
+            error!("Failed to record attack analysis: {}", e);
+        }
         
         if attack_analysis.risk_level > 0.7 {
             warn!("🚨 High attack risk detected: {:.2}%", attack_analysis.risk_level * 100.0);
@@ -167,43 +213,7 @@ impl AISentinel {
         Ok(())
     }
 
-    async fn fetch_latest_block_data(&self) -> Result<BlockData> {
-        let response = reqwest::get(&format!("{}/status", self.node_rpc_url)).await?;
-        let status: serde_json::Value = response.json().await?;
-        
-        let height = status["height"].as_u64().unwrap_or(0);
-        let latest_block_response = reqwest::get(&format!("{}/block/latest", self.node_rpc_url)).await?;
-        let block: serde_json::Value = latest_block_response.json().await?;
-
-        Ok(BlockData {
-            height,
-            timestamp: Utc::now(), // In real implementation, parse from block
-            hash: block["hash"].as_str().unwrap_or("").to_string(),
-            difficulty: status["difficulty"].as_f64().unwrap_or(0.0),
-            tx_count: block["transactions"].as_array().map(|v| v.len() as u32).unwrap_or(0),
-            size_bytes: block["size"].as_u64().unwrap_or(0),
-            propagation_time_ms: None, // Measured by peer network
-        })
-    }
-
-    async fn fetch_network_metrics(&self) -> Result<NetworkMetrics> {
-        let response = reqwest::get(&format!("{}/network/metrics", self.node_rpc_url)).await?;
-        let metrics: serde_json::Value = response.json().await?;
-
-        Ok(NetworkMetrics {
-            peer_count: metrics["peer_count"].as_u64().unwrap_or(0) as u32,
-            mempool_size: metrics["mempool_size"].as_u64().unwrap_or(0) as u32,
-            avg_block_time: metrics["avg_block_time"].as_f64().unwrap_or(15.0),
-            hashrate_estimate: metrics["hashrate"].as_f64().unwrap_or(0.0),
-            orphan_rate: metrics["orphan_rate"].as_f64().unwrap_or(0.0),
-            fee_percentiles: metrics["fee_percentiles"]
-                .as_array()
-                .map(|arr| arr.iter().filter_map(|v| v.as_f64()).collect())
-                .unwrap_or_default(),
-        })
-    }
-
-    async fn trigger_defensive_measures(&self, analysis: &AttackAnalysis) -> Result<()> {
+    async fn trigger_defensive_measures(&mut self, analysis: &AttackAnalysis) -> Result<()> {
         let output = SentinelOutput {
             timestamp: Utc::now(),
             risk_level: analysis.risk_level,
@@ -223,7 +233,7 @@ impl AISentinel {
     }
 
     async fn apply_optimizations(
-        &self, 
+        &mut self, 
         network_opts: &NetworkOptimizations,
         performance_opts: &PerformanceTuning
     ) -> Result<()> {
@@ -242,21 +252,12 @@ impl AISentinel {
         Ok(())
     }
 
-    async fn broadcast_sentinel_output(&self, output: &SentinelOutput) -> Result<()> {
-        // Send to Redis for real-time consumption
-        let mut conn = self.redis_client.get_connection()?;
-        let output_json = serde_json::to_string(output)?;
-        redis::cmd("PUBLISH")
-            .arg("quantumcoin:sentinel")
-            .arg(&output_json)
-            .execute(&mut conn);
-
-        // Send directly to node via RPC
-        let client = reqwest::Client::new();
-        client.post(&format!("{}/sentinel/update", self.node_rpc_url))
-            .json(output)
-            .send()
-            .await?;
+    async fn broadcast_sentinel_output(&mut self, output: &SentinelOutput) -> Result<()> {
+        // Publish for real-time consumption
+        self.sink.publish(output).await?;
+
+        // Send directly to node
+        self.node.send_update(output).await?;
 
         info!("📡 AI optimizations broadcasted to network");
         Ok(())
@@ -274,10 +275,104 @@ async fn main() -> Result<()> {
     let redis_url = std::env::var("REDIS_URL")
         .unwrap_or_else(|_| "redis://localhost:6379".to_string());
 
-    let mut sentinel = AISentinel::new(node_rpc_url, database_url, redis_url).await?;
+    // SENTINEL_BACKEND=memory runs without a node, Postgres or Redis
+    let mut sentinel = match std::env::var("SENTINEL_BACKEND").as_deref() {
+        #[cfg(feature = "in-memory")]
+        Ok("memory") => {
+            info!("Using in-memory backends");
+            AISentinel::in_memory()
+        }
+        _ => AISentinel::new(node_rpc_url, database_url, redis_url).await?,
+    };
+
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            let _ = shutdown_tx.send(true);
+        }
+    });
     
     info!("🤖 QuantumCoin AI Sentinel initialized - beginning blockchain enhancement");
-    sentinel.run().await?;
+    sentinel.run(shutdown_rx).await?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memory::{MemorySink, MemoryStore, SimulatedNode};
+
+    fn memory_sentinel(store: &MemoryStore, sink: &MemorySink, node: &SimulatedNode) -> AISentinel {
+        AISentinel::with_backends(
+            Box::new(node.clone()),
+            BlockchainAnalytics::new(Box::new(store.clone())),
+            Box::new(sink.clone()),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_run_returns_on_shutdown() {
+        let (store, sink, node) = (MemoryStore::default(), MemorySink::default(), SimulatedNode::default());
+        let mut sentinel = memory_sentinel(&store, &sink, &node);
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+
+        let stop = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            shutdown_tx.send(true).unwrap();
+        };
+        let (result, ()) = tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::join!(sentinel.run(shutdown_rx), stop)
+        })
+        .await
+        .expect("run did not return after shutdown");
+
+        assert!(result.is_ok());
+        assert!(store.is_closed());
+        assert!(sink.is_closed());
+        // Samples still buffered when the signal arrived were written on the way out
+        assert_eq!(sentinel.analytics.pending_samples(), 0);
+        assert!(!store.blocks().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_flushes_buffered_samples() {
+        let (store, sink, node) = (MemoryStore::default(), MemorySink::default(), SimulatedNode::default());
+        let mut sentinel = memory_sentinel(&store, &sink, &node);
+
+        for _ in 0..3 {
+            sentinel.monitor_blockchain().await.unwrap();
+        }
+        assert_eq!(sentinel.analytics.pending_samples(), 6);
+        assert_eq!(store.batches_written(), 0);
+
+        sentinel.shutdown().await.unwrap();
+        assert_eq!(store.batches_written(), 1);
+        assert_eq!(store.blocks().len(), 3);
+        assert_eq!(store.network_samples(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_cycle_produces_output() {
+        let (store, sink, node) = (MemoryStore::default(), MemorySink::default(), SimulatedNode::default());
+        let mut sentinel = AISentinel::with_backends(
+            Box::new(node.clone()),
+            BlockchainAnalytics::new(Box::new(store.clone())).with_batch_limits(2, Duration::from_secs(10)),
+            Box::new(sink.clone()),
+        );
+
+        for _ in 0..5 {
+            sentinel.monitor_blockchain().await.unwrap();
+        }
+        assert_eq!(store.blocks().len(), 5);
+
+        sentinel.optimize_network().await.unwrap();
+
+        let output = sink.outputs().pop().expect("no sentinel output published");
+        assert_eq!(output.risk_level, 0.0);
+        assert_eq!(output.reorg_protection_depth, 6);
+        assert!(output.min_relay_fee > 0.0);
+        // The node got the same advice
+        assert_eq!(node.updates().last().map(|u| u.timestamp), Some(output.timestamp));
+    }
+}
@@ -0,0 +1,164 @@
+// In-memory backends for running the sentinel without Postgres, Redis or a node
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use parking_lot::Mutex;
+use std::sync::Arc;
+use crate::analytics::{AnalyticsSample, AnalyticsStore, AttackRecord};
+use crate::backend::{NodeSource, OutputSink};
+use crate::{BlockData, NetworkMetrics, SentinelOutput};
+
+#[derive(Default)]
+struct StoreState {
+    blocks: Vec<BlockData>,
+    network: Vec<NetworkMetrics>,
+    attacks: Vec<AttackRecord>,
+    batches: usize,
+    closed: bool,
+}
+
+/// Analytics kept in a `Vec`; clones share the same data
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    state: Arc<Mutex<StoreState>>,
+}
+
+impl MemoryStore {
+    pub fn blocks(&self) -> Vec<BlockData> {
+        self.state.lock().blocks.clone()
+    }
+
+    pub fn network_samples(&self) -> usize {
+        self.state.lock().network.len()
+    }
+
+    pub fn batches_written(&self) -> usize {
+        self.state.lock().batches
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.state.lock().closed
+    }
+}
+
+#[async_trait]
+impl AnalyticsStore for MemoryStore {
+    async fn write_batch(&self, batch: &[AnalyticsSample]) -> Result<()> {
+        let mut state = self.state.lock();
+        for sample in batch {
+            match sample {
+                AnalyticsSample::Block(block) => {
+                    // Same (height, hash) replaces the earlier row, as in Postgres
+                    state.blocks.retain(|b| (b.height, &b.hash) != (block.height, &block.hash));
+                    state.blocks.push(block.clone());
+                }
+                AnalyticsSample::Network { metrics, .. } => state.network.push(metrics.clone()),
+            }
+        }
+        state.batches += 1;
+        Ok(())
+    }
+
+    async fn recent_blocks(&self, limit: u32) -> Result<Vec<BlockData>> {
+        let mut blocks = self.blocks();
+        blocks.sort_by(|a, b| b.height.cmp(&a.height));
+        blocks.truncate(limit as usize);
+        Ok(blocks)
+    }
+
+    async fn store_attack_record(&self, record: &AttackRecord) -> Result<()> {
+        self.state.lock().attacks.push(record.clone());
+        Ok(())
+    }
+
+    async fn attack_history(&self, since: DateTime<Utc>, min_risk_level: f64) -> Result<Vec<AttackRecord>> {
+        let mut records: Vec<AttackRecord> = self.state.lock().attacks
+            .iter()
+            .filter(|r| r.detected_at >= since && r.risk_level >= min_risk_level)
+            .cloned()
+            .collect();
+        records.sort_by_key(|r| r.detected_at);
+        Ok(records)
+    }
+
+    async fn close(&self) {
+        self.state.lock().closed = true;
+    }
+}
+
+/// Collects published outputs; clones share the same list
+#[derive(Clone, Default)]
+pub struct MemorySink {
+    outputs: Arc<Mutex<Vec<SentinelOutput>>>,
+    closed: Arc<Mutex<bool>>,
+}
+
+impl MemorySink {
+    pub fn outputs(&self) -> Vec<SentinelOutput> {
+        self.outputs.lock().clone()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        *self.closed.lock()
+    }
+}
+
+#[async_trait]
+impl OutputSink for MemorySink {
+    async fn publish(&mut self, output: &SentinelOutput) -> Result<()> {
+        self.outputs.lock().push(output.clone());
+        Ok(())
+    }
+
+    async fn close(&mut self) {
+        *self.closed.lock() = true;
+    }
+}
+
+/// A quiet node that mines a block every time it's asked for the latest one
+#[derive(Clone, Default)]
+pub struct SimulatedNode {
+    height: Arc<Mutex<u64>>,
+    updates: Arc<Mutex<Vec<SentinelOutput>>>,
+}
+
+impl SimulatedNode {
+    /// Updates the sentinel sent to the node
+    pub fn updates(&self) -> Vec<SentinelOutput> {
+        self.updates.lock().clone()
+    }
+}
+
+#[async_trait]
+impl NodeSource for SimulatedNode {
+    async fn latest_block(&self) -> Result<BlockData> {
+        let mut height = self.height.lock();
+        *height += 1;
+        Ok(BlockData {
+            height: *height,
+            timestamp: Utc::now() + Duration::seconds(15 * *height as i64),
+            hash: format!("{:064x}", *height),
+            difficulty: 1.0,
+            tx_count: 10,
+            size_bytes: 4_000,
+            propagation_time_ms: Some(200),
+        })
+    }
+
+    async fn network_metrics(&self) -> Result<NetworkMetrics> {
+        Ok(NetworkMetrics {
+            peer_count: 8,
+            mempool_size: 100,
+            avg_block_time: 15.0,
+            hashrate_estimate: 1_000.0,
+            orphan_rate: 0.0,
+            fee_percentiles: vec![1.0, 2.0, 5.0],
+        })
+    }
+
+    async fn send_update(&self, output: &SentinelOutput) -> Result<()> {
+        self.updates.lock().push(output.clone());
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+#!/usr/bin/env python3
+"""
+Dilithium2 known-answer values for qc_crypto::keypair_from_seed
+
+A from-the-spec implementation of CRYSTALS-Dilithium round 3.1 key
+generation (the version PQClean ships), using only hashlib's SHAKE. It
+shares no code with either Rust implementation, so the digests it prints
+pin seeded key generation to the encoding the PQClean verifier expects.
+
+    python3 scripts/dilithium2_kat.py
+"""
+
+import hashlib
+
+Q = 8380417
+N = 256
+K, L, ETA, D = 4, 4, 2, 13
+SEEDBYTES, CRHBYTES = 32, 64
+
+
+def brv8(x):
+    return int(f"{x:08b}"[::-1], 2)
+
+
+ZETAS = [pow(1753, brv8(i), Q) for i in range(N)]
+
+
+def ntt(a):
+    w = list(a)
+    m, length = 0, 128
+    while length >= 1:
+        for start in range(0, N, 2 * length):
+            m += 1
+            z = ZETAS[m]
+            for j in range(start, start + length):
+                t = z * w[j + length] % Q
+                w[j + length] = (w[j] - t) % Q
+                w[j] = (w[j] + t) % Q
+        length //= 2
+    return w
+
+
+def intt(a):
+    w = list(a)
+    m, length = N, 1
+    while length < N:
+        for start in range(0, N, 2 * length):
+            m -= 1
+            z = -ZETAS[m]
+            for j in range(start, start + length):
+                t = w[j]
+                w[j] = (t + w[j + length]) % Q
+                w[j + length] = z * (t - w[j + length]) % Q
+        length *= 2
+    n_inv = pow(N, -1, Q)
+    return [x * n_inv % Q for x in w]
+
+
+def shake128(data, n):
+    return hashlib.shake_128(data).digest(n)
+
+
+def shake256(data, n):
+    return hashlib.shake_256(data).digest(n)
+
+
+def poly_uniform(rho, nonce):
+    # Plenty of stream for the rejection sampler
+    buf = shake128(rho + nonce.to_bytes(2, "little"), 10 * 168)
+    coeffs, pos = [], 0
+    while len(coeffs) < N:
+        t = int.from_bytes(buf[pos:pos + 3], "little") & 0x7FFFFF
+        pos += 3
+        if t < Q:
+            coeffs.append(t)
+    return coeffs
+
+
+def poly_uniform_eta(rhoprime, nonce):
+    buf = shake256(rhoprime + nonce.to_bytes(2, "little"), 4 * 136)
+    coeffs = []
+    for byte in buf:
+        for t in (byte & 0x0F, byte >> 4):
+            if t < 15 and len(coeffs) < N:
+                coeffs.append(ETA - (t % 5))
+    assert len(coeffs) == N
+    return coeffs
+
+
+def power2round(a):
+    a1 = (a + (1 << (D - 1)) - 1) >> D
+    return a1, a - (a1 << D)
+
+
+def pack_bits(values, bits):
+    acc, nbits, out = 0, 0, bytearray()
+    for v in values:
+        acc |= v << nbits
+        nbits += bits
+        while nbits >= 8:
+            out.append(acc & 0xFF)
+            acc >>= 8
+            nbits -= 8
+    assert nbits == 0
+    return bytes(out)
+
+
+def keypair(zeta):
+    seedbuf = shake256(zeta, 2 * SEEDBYTES + CRHBYTES)
+    rho, rhoprime, key = seedbuf[:32], seedbuf[32:96], seedbuf[96:]
+
+    a_hat = [[poly_uniform(rho, (i << 8) + j) for j in range(L)] for i in range(K)]
+    s1 = [poly_uniform_eta(rhoprime, j) for j in range(L)]
+    s2 = [poly_uniform_eta(rhoprime, L + i) for i in range(K)]
+
+    s1_hat = [ntt([c % Q for c in p]) for p in s1]
+    t1, t0 = [], []
+    for i in range(K):
+        acc = [0] * N
+        for j in range(L):
+            acc = [(x + y * z) % Q for x, y, z in zip(acc, a_hat[i][j], s1_hat[j])]
+        t = [(x + y) % Q for x, y in zip(intt(acc), s2[i])]
+        rounded = [power2round(c) for c in t]
+        t1.append([hi for hi, _ in rounded])
+        t0.append([lo for _, lo in rounded])
+
+    pk = rho + b"".join(pack_bits(p, 10) for p in t1)
+    tr = shake256(pk, SEEDBYTES)
+    sk = (rho + key + tr
+          + b"".join(pack_bits([ETA - c for c in p], 3) for p in s1 + s2)
+          + b"".join(pack_bits([(1 << (D - 1)) - c for c in p], 13) for p in t0))
+    assert (len(pk), len(sk)) == (1312, 2528)
+    return pk, sk
+
+
+if __name__ == "__main__":
+    for fill in (0x00, 0x07):
+        pk, sk = keypair(bytes([fill]) * 32)
+        print(f"seed [{fill:#04x}; 32]")
+        print(f"  sha256(pk) = {hashlib.sha256(pk).hexdigest()}")
+        print(f"  sha256(sk) = {hashlib.sha256(sk).hexdigest()}")
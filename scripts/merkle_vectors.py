@@ -0,0 +1,45 @@
+#!/usr/bin/env python3
+"""
+Merkle root vectors for crates/validation/tests/merkle.rs
+
+Builds each transaction's version-1 canonical encoding by hand (see
+crates/types/src/canonical.rs) and hashes it with hashlib, so the pinned
+roots don't come from the code they check.
+
+    python3 scripts/merkle_vectors.py
+"""
+
+import hashlib
+import struct
+
+
+def tx(tag):
+    """Canonical encoding of `tx(tag)` from the test: no inputs, one P2PQ
+    output of 5,000 paying `[tag; 4]`, lock time `tag`"""
+    out = bytes([1])                               # canonical version
+    out += struct.pack("<I", 1)                    # tx version
+    out += struct.pack("<I", 0)                    # no inputs
+    out += struct.pack("<I", 1)                    # one output
+    out += struct.pack("<q", 5_000) + bytes([0])   # value, P2PQ tag
+    out += struct.pack("<I", 4) + bytes([tag]) * 4
+    out += struct.pack("<I", tag)                  # lock time
+    return out
+
+
+def merkle_root(txs):
+    layer = [hashlib.sha256(t).digest() for t in txs]
+    if not layer:
+        return bytes(32)
+    while len(layer) > 1:
+        # An odd last node is paired with itself
+        layer = [
+            hashlib.sha256(layer[i] + layer[min(i + 1, len(layer) - 1)]).digest()
+            for i in range(0, len(layer), 2)
+        ]
+    return layer[0]
+
+
+if __name__ == "__main__":
+    for count in range(4):
+        tags = list(range(1, count + 1))
+        print(f"txs {tags}: {merkle_root([tx(t) for t in tags]).hex()}")
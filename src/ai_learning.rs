@@ -1008,13 +1008,14 @@ impl Default for AIStats {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mempool::MempoolPolicy;
     use crate::transaction::{TransactionInput, TransactionOutput};
     
     #[tokio::test]
     async fn test_ai_learning_system() {
         let mut ai = AILearningSystem::new();
         let blockchain = Blockchain::new();
-        let mempool = Mempool::new(1000);
+        let mempool = Mempool::new(MempoolPolicy { max_count: 1000, ..MempoolPolicy::default() });
         let network_stats = NetworkStats {
             connected_peers: 5,
             known_peers: 10,
@@ -1022,6 +1023,9 @@ mod tests {
             outbound_peers: 3,
             total_bytes_sent: 1000,
             total_bytes_received: 1500,
+            bytes_sent_by_type: HashMap::new(),
+            bytes_received_by_type: HashMap::new(),
+            block_propagation: Default::default(),
         };
         
         let analysis = TransactionAnalysis {
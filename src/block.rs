@@ -4,6 +4,9 @@ use chrono::{DateTime, Utc};
 use crate::transaction::{Transaction, SignedTransaction};
 use anyhow::{Result, anyhow};
 
+/// Consensus cap on a block's serialized size
+pub const MAX_BLOCK_SIZE: usize = 1_000_000;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BlockHeader {
     pub version: u32,
@@ -225,6 +228,6 @@ impl BlockValidator {
 
 impl Default for BlockValidator {
     fn default() -> Self {
-        Self::new(1_000_000, 10_000) // 1MB blocks, 10k transactions max
+        Self::new(MAX_BLOCK_SIZE, 10_000) // 1MB blocks, 10k transactions max
     }
 }
@@ -412,12 +412,12 @@ pub struct PerformanceMetrics {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{blockchain::Blockchain, mempool::Mempool};
+    use crate::{blockchain::Blockchain, mempool::{Mempool, MempoolPolicy}};
     
     #[tokio::test]
     async fn test_block_monitor_creation() {
         let blockchain = Arc::new(RwLock::new(Blockchain::new()));
-        let mempool = Arc::new(RwLock::new(Mempool::new(1000)));
+        let mempool = Arc::new(RwLock::new(Mempool::new(MempoolPolicy { max_count: 1000, ..MempoolPolicy::default() })));
         let p2p_node = Arc::new(P2PNode::new(
             "127.0.0.1:0".parse().unwrap(),
             Arc::clone(&blockchain),
@@ -444,7 +444,7 @@ mod tests {
     #[tokio::test]
     async fn test_health_report_generation() {
         let blockchain = Arc::new(RwLock::new(Blockchain::new()));
-        let mempool = Arc::new(RwLock::new(Mempool::new(1000)));
+        let mempool = Arc::new(RwLock::new(Mempool::new(MempoolPolicy { max_count: 1000, ..MempoolPolicy::default() })));
         let p2p_node = Arc::new(P2PNode::new(
             "127.0.0.1:0".parse().unwrap(),
             Arc::clone(&blockchain),
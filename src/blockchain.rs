@@ -3,6 +3,26 @@ use std::collections::HashMap;
 use chrono::{DateTime, Utc};
 use blake3;
 use anyhow::Result;
+use thiserror::Error;
+
+/// Why a block was refused by `Blockchain::add_block`
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum BlockValidationError {
+    #[error("Block index {got} does not extend tip, expected {expected}")]
+    InvalidIndex { expected: u64, got: u64 },
+    #[error("Previous hash {got} does not match tip {expected}")]
+    PrevHashMismatch { expected: String, got: String },
+    #[error("Block hash does not match its contents")]
+    HashMismatch,
+    #[error("Block difficulty {got} is below required {required}")]
+    DifficultyTooLow { required: usize, got: usize },
+    #[error("Block hash does not meet difficulty {0}")]
+    InsufficientWork(usize),
+    #[error("Merkle root does not match transactions")]
+    MerkleMismatch,
+    #[error("Block reward {claimed} exceeds allowed {allowed}")]
+    ExcessiveReward { claimed: u64, allowed: u64 },
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Transaction {
@@ -84,7 +104,7 @@ impl Blockchain {
             id: format!("reward_{}", Utc::now().timestamp()),
             from: "".to_string(),
             to: mining_reward_address.clone(),
-            amount: self.mining_reward,
+            amount: self.get_current_mining_reward(),
             timestamp: Utc::now(),
             signature: "".to_string(),
             fee: 0,
@@ -104,13 +124,76 @@ impl Blockchain {
         };
 
         self.mine_block(&mut block);
-        self.chain.push(block.clone());
-        self.update_balances(&block);
-        self.pending_transactions.clear();
+        self.add_block(block.clone())?;
 
         Ok(block)
     }
 
+    /// Append a block after running the full consensus checks against the tip
+    pub fn add_block(&mut self, block: Block) -> Result<(), BlockValidationError> {
+        self.validate_block(&block)?;
+
+        self.update_balances(&block);
+        self.pending_transactions
+            .retain(|pending| !block.transactions.iter().any(|tx| tx.id == pending.id));
+        self.chain.push(block);
+        Ok(())
+    }
+
+    /// Check that a block extends the current tip: linkage, PoW, merkle root and reward
+    pub fn validate_block(&self, block: &Block) -> Result<(), BlockValidationError> {
+        let tip = self.get_latest_block();
+
+        let expected_index = tip.index + 1;
+        if block.index != expected_index {
+            return Err(BlockValidationError::InvalidIndex {
+                expected: expected_index,
+                got: block.index,
+            });
+        }
+
+        if block.previous_hash != tip.hash {
+            return Err(BlockValidationError::PrevHashMismatch {
+                expected: tip.hash.clone(),
+                got: block.previous_hash.clone(),
+            });
+        }
+
+        if block.hash != self.calculate_hash(block) {
+            return Err(BlockValidationError::HashMismatch);
+        }
+
+        if block.difficulty < self.difficulty {
+            return Err(BlockValidationError::DifficultyTooLow {
+                required: self.difficulty,
+                got: block.difficulty,
+            });
+        }
+
+        if !block.hash.starts_with(&"0".repeat(block.difficulty)) {
+            return Err(BlockValidationError::InsufficientWork(block.difficulty));
+        }
+
+        if block.merkle_root != self.calculate_merkle_root(&block.transactions) {
+            return Err(BlockValidationError::MerkleMismatch);
+        }
+
+        // Coinbase outputs may claim the subsidy plus the fees of the block's transactions
+        let (claimed, fees) = block.transactions.iter().fold((0u64, 0u64), |(claimed, fees), tx| {
+            if tx.from.is_empty() {
+                (claimed.saturating_add(tx.amount), fees)
+            } else {
+                (claimed, fees.saturating_add(tx.fee))
+            }
+        });
+        let allowed = self.reward_at(block.index).saturating_add(fees);
+        if claimed > allowed {
+            return Err(BlockValidationError::ExcessiveReward { claimed, allowed });
+        }
+
+        Ok(())
+    }
+
     fn mine_block(&self, block: &mut Block) {
         let target = "0".repeat(self.difficulty);
         
@@ -193,9 +276,13 @@ impl Blockchain {
     }
 
     pub fn get_current_mining_reward(&self) -> u64 {
+        self.reward_at(self.chain.len() as u64)
+    }
+
+    fn reward_at(&self, index: u64) -> u64 {
         // Halving every 210,000 blocks like Bitcoin
-        let halvings = self.chain.len() / 210_000;
-        self.mining_reward >> halvings
+        let halvings = index / 210_000;
+        self.mining_reward.checked_shr(halvings as u32).unwrap_or(0)
     }
 
     pub fn is_chain_valid(&self) -> bool {
@@ -222,7 +309,7 @@ impl Blockchain {
             let recent_block = &self.chain[self.chain.len() - 1];
             let old_block = &self.chain[self.chain.len() - DIFFICULTY_ADJUSTMENT_INTERVAL];
             
-            let time_taken = recent_block.timestamp.timestamp() - old_block.timestamp.timestamp();
+            let time_taken = (recent_block.timestamp.timestamp() - old_block.timestamp.timestamp()).max(0) as u64;
             let expected_time = (DIFFICULTY_ADJUSTMENT_INTERVAL as u64) * TARGET_BLOCK_TIME;
             
             if time_taken < expected_time / 2 {
@@ -241,3 +328,118 @@ impl Default for Blockchain {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_chain() -> Blockchain {
+        let mut blockchain = Blockchain::new();
+        blockchain.difficulty = 2;
+        blockchain
+    }
+
+    fn coinbase(to: &str, amount: u64) -> Transaction {
+        Transaction {
+            id: format!("reward_{}", to),
+            from: "".to_string(),
+            to: to.to_string(),
+            amount,
+            timestamp: Utc::now(),
+            signature: "".to_string(),
+            fee: 0,
+        }
+    }
+
+    /// Block on top of the current tip with a consistent hash but no work done
+    fn unmined_block(blockchain: &Blockchain, transactions: Vec<Transaction>) -> Block {
+        let mut block = Block {
+            index: blockchain.get_latest_block().index + 1,
+            timestamp: Utc::now(),
+            merkle_root: blockchain.calculate_merkle_root(&transactions),
+            transactions,
+            previous_hash: blockchain.get_latest_block().hash.clone(),
+            hash: String::new(),
+            nonce: 0,
+            difficulty: blockchain.difficulty,
+        };
+        block.hash = blockchain.calculate_hash(&block);
+        block
+    }
+
+    fn mined_block(blockchain: &Blockchain, transactions: Vec<Transaction>) -> Block {
+        let mut block = unmined_block(blockchain, transactions);
+        blockchain.mine_block(&mut block);
+        block
+    }
+
+    #[test]
+    fn test_add_block_rejects_invalid_pow() {
+        let mut blockchain = test_chain();
+        let mut block = unmined_block(&blockchain, vec![coinbase("miner", blockchain.mining_reward)]);
+        while block.hash.starts_with("00") {
+            block.nonce += 1;
+            block.hash = blockchain.calculate_hash(&block);
+        }
+
+        assert_eq!(
+            blockchain.add_block(block),
+            Err(BlockValidationError::InsufficientWork(2))
+        );
+        assert_eq!(blockchain.chain.len(), 1);
+        assert_eq!(blockchain.get_balance("miner"), 0);
+    }
+
+    #[test]
+    fn test_add_block_extends_tip() {
+        let mut blockchain = test_chain();
+        let block = mined_block(&blockchain, vec![coinbase("miner", blockchain.mining_reward)]);
+        let hash = block.hash.clone();
+
+        blockchain.add_block(block).unwrap();
+
+        assert_eq!(blockchain.chain.len(), 2);
+        assert_eq!(blockchain.get_latest_block().hash, hash);
+        assert_eq!(blockchain.get_balance("miner"), blockchain.mining_reward);
+        assert!(blockchain.is_chain_valid());
+    }
+
+    #[test]
+    fn test_add_block_rejects_bad_linkage_merkle_and_reward() {
+        let mut blockchain = test_chain();
+
+        let mut wrong_parent = unmined_block(&blockchain, vec![]);
+        wrong_parent.previous_hash = "00".repeat(32);
+        wrong_parent.hash.clear();
+        blockchain.mine_block(&mut wrong_parent);
+        assert!(matches!(
+            blockchain.add_block(wrong_parent),
+            Err(BlockValidationError::PrevHashMismatch { .. })
+        ));
+
+        let mut bad_merkle = unmined_block(&blockchain, vec![coinbase("miner", 1)]);
+        bad_merkle.merkle_root = "0".to_string();
+        bad_merkle.hash.clear();
+        blockchain.mine_block(&mut bad_merkle);
+        assert_eq!(blockchain.add_block(bad_merkle), Err(BlockValidationError::MerkleMismatch));
+
+        let greedy = mined_block(&blockchain, vec![coinbase("miner", blockchain.mining_reward + 1)]);
+        assert!(matches!(
+            blockchain.add_block(greedy),
+            Err(BlockValidationError::ExcessiveReward { .. })
+        ));
+
+        assert_eq!(blockchain.chain.len(), 1);
+    }
+
+    #[test]
+    fn test_mined_blocks_pass_validation() {
+        let mut blockchain = test_chain();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+        blockchain.mine_pending_transactions("miner".to_string()).unwrap();
+
+        assert_eq!(blockchain.chain.len(), 3);
+        assert!(blockchain.pending_transactions.is_empty());
+        assert_eq!(blockchain.get_balance("miner"), 2 * blockchain.mining_reward);
+    }
+}
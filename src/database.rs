@@ -1,6 +1,7 @@
 use anyhow::{Result, Context};
 use serde::{Serialize, Deserialize};
 use sqlx::{SqlitePool, Row, sqlite::SqliteConnectOptions};
+use std::collections::HashMap;
 use std::path::Path;
 use tokio::sync::RwLock;
 use std::sync::Arc;
@@ -125,6 +126,10 @@ struct UTXOEntry {
     pub spent_at_height: Option<u64>,
 }
 
+/// Transaction ids per query in `get_transaction_fees`, well under
+/// SQLite's limit on bound parameters
+pub const FEE_LOOKUP_BATCH: usize = 500;
+
 /// Database-backed blockchain storage
 pub struct BlockchainDatabase {
     pool: SqlitePool,
@@ -300,10 +305,17 @@ impl BlockchainDatabase {
         .bind(block_data)
         .execute(&mut *tx).await?;
 
+        // Fees come from the outputs each transaction spends, including ones
+        // created earlier in this block, so walk a copy of the UTXO set
+        let mut utxo_view = self.utxo_cache.read().await.clone();
+
         // Insert transactions
         for (index, transaction) in transactions.iter().enumerate() {
             let tx_data = bincode::serialize(transaction)?;
             let tx_size = tx_data.len() as u32;
+            let is_coinbase = transaction.inputs.len() == 1 && transaction.inputs[0].previous_output.starts_with("coinbase");
+            let fee = if is_coinbase { 0 } else { utxo_view.transaction_fee(transaction)? };
+            utxo_view.apply_transaction(transaction, block.index, is_coinbase)?;
 
             sqlx::query(r#"
                 INSERT INTO transactions (txid, block_hash, block_height, transaction_index, version, lock_time, input_count, output_count, fee, size, timestamp, data)
@@ -317,7 +329,7 @@ impl BlockchainDatabase {
             .bind(transaction.lock_time as i64)
             .bind(transaction.inputs.len() as i64)
             .bind(transaction.outputs.len() as i64)
-            .bind(transaction.calculate_fee(&std::collections::HashMap::new()).unwrap_or(0) as i64)
+            .bind(fee as i64)
             .bind(tx_size as i64)
             .bind(transaction.timestamp.to_rfc3339())
             .bind(tx_data)
@@ -335,14 +347,8 @@ impl BlockchainDatabase {
         tx.commit().await?;
 
         // Update in-memory UTXO cache
-        {
-            let mut utxo_cache = self.utxo_cache.write().await;
-            for transaction in transactions {
-                let is_coinbase = transaction.inputs.len() == 1 && transaction.inputs[0].previous_output.starts_with("coinbase");
-                utxo_cache.apply_transaction(transaction, block.index, is_coinbase)?;
-            }
-            utxo_cache.set_height(block.index);
-        }
+        utxo_view.set_height(block.index);
+        *self.utxo_cache.write().await = utxo_view;
 
         Ok(())
     }
@@ -441,6 +447,43 @@ impl BlockchainDatabase {
         }
     }
 
+    /// Get a confirmed transaction with the fee derived from the outputs it
+    /// spent when its block was stored
+    pub async fn get_transaction_with_fee(&self, txid: &str) -> Result<Option<(SignedTransaction, u64)>> {
+        let row = sqlx::query("SELECT data, fee FROM transactions WHERE txid = ?")
+            .bind(txid)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        if let Some(row) = row {
+            let data: Vec<u8> = row.get("data");
+            let tx: SignedTransaction = bincode::deserialize(&data)?;
+            Ok(Some((tx, row.get::<i64, _>("fee") as u64)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Derived fees of the stored transactions among `txids`, looked up
+    /// `FEE_LOOKUP_BATCH` at a time. Ids the database doesn't hold are left out.
+    pub async fn get_transaction_fees(&self, txids: &[String]) -> Result<HashMap<String, u64>> {
+        let mut fees = HashMap::with_capacity(txids.len());
+        for batch in txids.chunks(FEE_LOOKUP_BATCH) {
+            let sql = format!(
+                "SELECT txid, fee FROM transactions WHERE txid IN ({})",
+                vec!["?"; batch.len()].join(", ")
+            );
+            let mut query = sqlx::query(&sql);
+            for txid in batch {
+                query = query.bind(txid);
+            }
+            for row in query.fetch_all(&self.pool).await? {
+                fees.insert(row.get("txid"), row.get::<i64, _>("fee") as u64);
+            }
+        }
+        Ok(fees)
+    }
+
     /// Get current blockchain height
     pub async fn get_chain_height(&self) -> Result<u64> {
         let row = sqlx::query("SELECT value FROM chain_state WHERE key = 'best_block_height'")
@@ -754,6 +797,45 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_stored_fee_derived_from_spent_outputs() -> Result<()> {
+        let db = create_test_db().await?;
+
+        let output = |value: u64| TransactionOutput { value, script_pubkey: vec![], address: "alice".to_string() };
+        let transaction = |id: &str, previous_output: &str, value: u64| SignedTransaction {
+            id: id.to_string(),
+            version: 1,
+            inputs: vec![TransactionInput { previous_output: previous_output.to_string(), script_sig: vec![], sequence: 0 }],
+            outputs: vec![output(value)],
+            lock_time: 0,
+            timestamp: Utc::now(),
+            signature: "sig".to_string(),
+            public_key: "pub".to_string(),
+        };
+
+        // The spend draws on an output created earlier in the same block
+        let funding = transaction("funding", "coinbase:0", 5_000);
+        let spend = transaction("spend", "funding:0", 4_200);
+        db.store_block(&Block::new(1, "genesis".to_string(), vec![], 4), &[funding, spend]).await?;
+
+        let fee = |txid: &str| sqlx::query("SELECT fee FROM transactions WHERE txid = ?").bind(txid.to_string()).fetch_one(&db.pool);
+        assert_eq!(fee("funding").await?.get::<i64, _>("fee"), 0);
+        assert_eq!(fee("spend").await?.get::<i64, _>("fee"), 800);
+        let (stored, stored_fee) = db.get_transaction_with_fee("spend").await?.unwrap();
+        assert_eq!((stored.id.as_str(), stored_fee), ("spend", 800));
+
+        let txids = ["funding", "spend", "unknown"].map(String::from);
+        let fees = db.get_transaction_fees(&txids).await?;
+        assert_eq!(fees, HashMap::from([("funding".to_string(), 0), ("spend".to_string(), 800)]));
+
+        // Spending an output the set does not hold stores nothing
+        let unknown = transaction("unknown", "missing:0", 1_000);
+        assert!(db.store_block(&Block::new(2, "block_1".to_string(), vec![], 4), &[unknown]).await.is_err());
+        assert_eq!(db.get_chain_height().await?, 1);
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_database_stats() -> Result<()> {
         let db = create_test_db().await?;
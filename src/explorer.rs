@@ -14,15 +14,21 @@ use tokio::sync::RwLock;
 use tower_http::services::ServeDir;
 use tower_http::cors::{Any, CorsLayer};
 use tracing::{info, error};
+use qc_types::target::{compact_to_target, target_to_compact, target_to_difficulty, U256};
 
 use crate::{
-    blockchain::Blockchain,
+    blockchain::{Blockchain, Transaction},
     database::BlockchainDatabase,
-    mempool::Mempool,
+    mempool::{Mempool, PriorityScore},
+    transaction::SignedTransaction,
     p2p::{P2PNode, NetworkStats},
     rpc::AppState,
+    utxo::{UtxoStatus, COINBASE_MATURITY},
 };
 
+/// Confirmations an output needs to be shown as spendable by default
+pub const DEFAULT_MIN_CONFIRMATIONS: u64 = 1;
+
 /// Block Explorer Server
 pub struct ExplorerServer {
     addr: SocketAddr,
@@ -72,12 +78,141 @@ pub struct TransactionSummary {
     pub txid: String,
     pub timestamp: i64,
     pub amount: u64,
-    pub fee: u64,
+    /// Unknown for a confirmed transaction the database didn't derive a fee for
+    pub fee: Option<u64>,
     pub input_count: usize,
     pub output_count: usize,
     pub confirmations: Option<u64>,
 }
 
+impl TransactionSummary {
+    /// Summarize a confirmed transaction, taking its fee and its input and
+    /// output counts from the database's stored form when there is one.
+    /// Without one the fee is unknown, and the account-model transfer is one
+    /// output funded by one input, or by none for a coinbase.
+    pub fn confirmed(tx: &Transaction, stored: Option<&StoredTransaction>, confirmations: u64) -> Self {
+        let (input_count, output_count) = match stored {
+            Some(stored) => (stored.transaction.inputs.len(), stored.transaction.outputs.len()),
+            None => (usize::from(!tx.from.is_empty()), 1),
+        };
+        Self {
+            txid: tx.id.clone(),
+            timestamp: tx.timestamp.timestamp(),
+            amount: tx.amount,
+            fee: stored.map(|stored| stored.fee),
+            input_count,
+            output_count,
+            confirmations: Some(confirmations),
+        }
+    }
+}
+
+/// A confirmed transaction as the database stored it
+pub struct StoredTransaction {
+    pub transaction: SignedTransaction,
+    /// Derived from the outputs the transaction spent
+    pub fee: u64,
+}
+
+/// Stored form of a confirmed transaction, if a database is attached
+async fn stored_transaction(database: Option<&BlockchainDatabase>, txid: &str) -> Option<StoredTransaction> {
+    let (transaction, fee) = database?.get_transaction_with_fee(txid).await.ok().flatten()?;
+    Some(StoredTransaction { transaction, fee })
+}
+
+/// Fees of confirmed transactions as the database derived them from the
+/// outputs they spent, in one lookup. The account-model `fee` field is never
+/// trusted: without a database, or for a transaction it doesn't hold, the
+/// fee is unknown and has no entry.
+async fn confirmed_fees<'a>(
+    database: Option<&BlockchainDatabase>,
+    txs: impl IntoIterator<Item = &'a Transaction>,
+) -> HashMap<String, u64> {
+    let Some(database) = database else {
+        return HashMap::new();
+    };
+    let txids: Vec<String> = txs.into_iter().map(|tx| tx.id.clone()).collect();
+    database.get_transaction_fees(&txids).await.unwrap_or_else(|e| {
+        error!("Failed to look up transaction fees: {}", e);
+        HashMap::new()
+    })
+}
+
+/// A fee for display, or "unknown" when it couldn't be derived
+fn format_fee(fee: Option<u64>) -> String {
+    fee.map_or_else(|| "unknown".to_string(), |fee| format!("{:.8} QTC", fee as f64 / 100_000_000.0))
+}
+
+/// Confirmed transactions to or from `address` with their block heights,
+/// oldest first. The chain lock is released before any fee lookup.
+async fn address_transactions(state: &AppState, address: &str) -> Vec<(Transaction, u64)> {
+    let blockchain = state.blockchain.read().await;
+    blockchain.chain.iter()
+        .flat_map(|block| block.transactions.iter().map(move |tx| (tx, block.index)))
+        .filter(|(tx, _)| tx.from == address || tx.to == address)
+        .map(|(tx, height)| (tx.clone(), height))
+        .collect()
+}
+
+/// Account-model balance of `address` over `transactions`. Spends whose fee
+/// is unknown are charged their amount only.
+fn address_balance(address: &str, transactions: &[(Transaction, u64)], fees: &HashMap<String, u64>) -> u64 {
+    transactions.iter().fold(0u64, |mut balance, (tx, _)| {
+        if tx.to == address {
+            balance += tx.amount;
+        }
+        if tx.from == address {
+            balance = balance.saturating_sub(tx.amount + fees.get(&tx.id).copied().unwrap_or(0));
+        }
+        balance
+    })
+}
+
+/// Pending transaction with its inclusion priority
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MempoolTransaction {
+    pub txid: String,
+    pub size: usize,
+    pub fee: u64,
+    pub received_time: i64,
+    pub priority: PriorityScore,
+}
+
+/// Mempool contents for `/api/mempool`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MempoolSummary {
+    pub transaction_count: usize,
+    pub total_bytes: usize,
+    pub avg_fee_per_byte: f64,
+    /// Highest priority first
+    pub transactions: Vec<MempoolTransaction>,
+}
+
+/// One block's entry in `/api/difficulty-history`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DifficultyPoint {
+    pub height: u64,
+    pub timestamp: i64,
+    /// Compact encoding of the block's PoW target
+    pub bits: u32,
+    /// Difficulty of the target decoded from `bits`, relative to the
+    /// difficulty-1 target
+    pub difficulty: f64,
+}
+
+/// Query parameters for the difficulty history endpoint
+#[derive(Debug, Deserialize)]
+pub struct DifficultyHistoryQuery {
+    /// Number of most recent blocks to return
+    pub blocks: Option<usize>,
+}
+
+/// Blocks returned by `/api/difficulty-history` when `blocks` is not given
+pub const DEFAULT_DIFFICULTY_HISTORY: usize = 144;
+
+/// Most blocks `/api/difficulty-history` returns in one response
+pub const MAX_DIFFICULTY_HISTORY: usize = 2016;
+
 /// Search results
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SearchResults {
@@ -96,6 +231,16 @@ pub struct AddressSummary {
     pub transaction_count: usize,
     pub first_seen: Option<i64>,
     pub last_seen: Option<i64>,
+    /// Sum of the outputs in `utxos` that are spendable now
+    pub spendable_balance: u64,
+    pub utxos: Vec<UtxoStatus>,
+}
+
+/// Query parameters for the address endpoint
+#[derive(Debug, Deserialize)]
+pub struct AddressQuery {
+    /// Confirmations an output needs before it counts as spendable
+    pub min_confirmations: Option<u64>,
 }
 
 /// Query parameters for pagination
@@ -133,6 +278,8 @@ impl ExplorerServer {
             .route("/api/transactions", get(get_transactions_api))
             .route("/api/transactions/:txid", get(get_transaction_api))
             .route("/api/addresses/:address", get(get_address_api))
+            .route("/api/mempool", get(get_mempool_api))
+            .route("/api/difficulty-history", get(get_difficulty_history_api))
             
             // Web interface
             .route("/", get(explorer_home))
@@ -257,7 +404,7 @@ async fn get_recent_activity(State(state): State<AppState>) -> Json<RecentActivi
             txid: entry.transaction.id.clone(),
             timestamp: entry.transaction.timestamp.timestamp(),
             amount: entry.transaction.outputs.iter().map(|o| o.value).sum(),
-            fee: entry.transaction.calculate_fee(&std::collections::HashMap::new()).unwrap_or(0),
+            fee: Some(entry.fee),
             input_count: entry.transaction.inputs.len(),
             output_count: entry.transaction.outputs.len(),
             confirmations: None, // Unconfirmed
@@ -339,16 +486,14 @@ async fn search(
     if results.result_type.is_none() {
         for block in &blockchain.chain {
             if let Some(tx) = block.transactions.iter().find(|t| t.id == search_term) {
+                let database = state.database.read().await;
+                let stored = stored_transaction(database.as_ref(), &tx.id).await;
                 results.result_type = Some("transaction".to_string());
-                results.transaction = Some(TransactionSummary {
-                    txid: tx.id.clone(),
-                    timestamp: tx.timestamp.timestamp(),
-                    amount: tx.amount,
-                    fee: tx.fee,
-                    input_count: 1, // Simplified
-                    output_count: 1, // Simplified
-                    confirmations: Some(blockchain.chain.len() as u64 - block.index),
-                });
+                results.transaction = Some(TransactionSummary::confirmed(
+                    tx,
+                    stored.as_ref(),
+                    blockchain.chain.len() as u64 - block.index,
+                ));
                 break;
             }
         }
@@ -363,6 +508,8 @@ async fn search(
             transaction_count: 0, // TODO: Count transactions
             first_seen: None,
             last_seen: None,
+            spendable_balance: 0,
+            utxos: Vec::new(),
         });
     }
     
@@ -499,7 +646,7 @@ async fn explorer_home(State(state): State<AppState>) -> Html<String> {
                     <div class="hash">{}</div>
                     <div>
                         <span class="amount">{:.8} QTC</span> • 
-                        Fee: {:.8} QTC
+                        Fee: {}
                     </div>
                     <div style="font-size: 12px; color: #888;">
                         {} • {} in → {} out
@@ -507,7 +654,7 @@ async fn explorer_home(State(state): State<AppState>) -> Html<String> {
                 </div>"#,
                 &tx.txid[..16],
                 tx.amount as f64 / 100_000_000.0,
-                tx.fee as f64 / 100_000_000.0,
+                format_fee(tx.fee),
                 if tx.confirmations.is_some() { "Confirmed" } else { "Unconfirmed" },
                 tx.input_count,
                 tx.output_count
@@ -577,21 +724,23 @@ a:hover {{ text-decoration: underline; }}
 
 async fn explorer_block(Path(height): Path<u64>, State(state): State<AppState>) -> Html<String> {
     let blockchain = state.blockchain.read().await;
+    let database = state.database.read().await;
     
     if let Some(block) = blockchain.chain.get(height as usize) {
+        let fees = confirmed_fees(database.as_ref(), &block.transactions).await;
         let transactions_html = block.transactions
             .iter()
             .map(|tx| format!(
                 r#"<tr>
                     <td><a href="/transactions/{}" class="hash">{}</a></td>
                     <td>{:.8} QTC</td>
-                    <td>{:.8} QTC</td>
+                    <td>{}</td>
                     <td><a href="/addresses/{}">{}</a></td>
                     <td>{}</td>
                 </tr>"#,
                 tx.id, &tx.id[..16],
                 tx.amount as f64 / 100_000_000.0,
-                tx.fee as f64 / 100_000_000.0,
+                format_fee(fees.get(&tx.id).copied()),
                 tx.to, &tx.to[..20],
                 format_timestamp(tx.timestamp.timestamp())
             ))
@@ -646,36 +795,38 @@ a:hover {{ text-decoration: underline; }}
 async fn explorer_transactions(State(state): State<AppState>) -> Html<String> {
     let blockchain = state.blockchain.read().await;
     let mempool = state.mempool.read().await;
+    let database = state.database.read().await;
     
     // Get recent transactions from blockchain
+    let recent_blocks: Vec<_> = blockchain.chain.iter().rev().take(10).collect();
+    let fees = confirmed_fees(database.as_ref(), recent_blocks.iter().flat_map(|block| &block.transactions)).await;
     let mut all_transactions = Vec::new();
-    for block in blockchain.chain.iter().rev().take(10) {
+    for block in recent_blocks {
         for tx in &block.transactions {
-            all_transactions.push((tx, Some(block.index)));
+            all_transactions.push((tx.clone(), fees.get(&tx.id).copied(), Some(block.index)));
         }
     }
     
     // Add unconfirmed transactions from mempool
     for tx_entry in mempool.get_transactions_by_fee(20) {
-        let simple_tx = tx_entry.transaction.to_simple_transaction();
-        all_transactions.push((&simple_tx, None));
+        all_transactions.push((tx_entry.transaction.to_simple_transaction(), Some(tx_entry.fee), None));
     }
     
     let transactions_html = all_transactions
         .into_iter()
         .take(50)
-        .map(|(tx, block_height)| format!(
+        .map(|(tx, fee, block_height)| format!(
             r#"<tr>
                 <td><a href="/transactions/{}" class="hash">{}</a></td>
                 <td>{:.8} QTC</td>
-                <td>{:.8} QTC</td>
+                <td>{}</td>
                 <td><a href="/addresses/{}">{}</a></td>
                 <td>{}</td>
                 <td>{}</td>
             </tr>"#,
             tx.id, &tx.id[..16],
             tx.amount as f64 / 100_000_000.0,
-            tx.fee as f64 / 100_000_000.0,
+            format_fee(fee),
             tx.to, &tx.to[..20],
             if let Some(height) = block_height { 
                 format!("Block #{}", height) 
@@ -712,10 +863,12 @@ a:hover {{ text-decoration: underline; }}
 
 async fn explorer_transaction(Path(txid): Path<String>, State(state): State<AppState>) -> Html<String> {
     let blockchain = state.blockchain.read().await;
+    let database = state.database.read().await;
     
     // Find transaction in blockchain
     for block in &blockchain.chain {
         if let Some(tx) = block.transactions.iter().find(|t| t.id == txid) {
+            let fee = stored_transaction(database.as_ref(), &tx.id).await.map(|stored| stored.fee);
             let html = format!(r#"
 <!DOCTYPE html>
 <html><head><title>Transaction {}</title><style>
@@ -731,7 +884,7 @@ a:hover {{ text-decoration: underline; }}
 <p><strong>Transaction ID:</strong> <span class="hash">{}</span></p>
 <p><strong>Block:</strong> <a href="/blocks/{}">#{}</a></p>
 <p><strong>Amount:</strong> {:.8} QTC</p>
-<p><strong>Fee:</strong> {:.8} QTC</p>
+<p><strong>Fee:</strong> {}</p>
 <p><strong>From:</strong> <a href="/addresses/{}">{}</a></p>
 <p><strong>To:</strong> <a href="/addresses/{}">{}</a></p>
 <p><strong>Timestamp:</strong> {}</p>
@@ -741,7 +894,7 @@ a:hover {{ text-decoration: underline; }}
 </body></html>"#,
                 tx.id, block.index, block.index,
                 tx.amount as f64 / 100_000_000.0,
-                tx.fee as f64 / 100_000_000.0,
+                format_fee(fee),
                 tx.from, tx.from,
                 tx.to, tx.to,
                 format_timestamp(tx.timestamp.timestamp()),
@@ -757,45 +910,29 @@ a:hover {{ text-decoration: underline; }}
 }
 
 async fn explorer_address(Path(address): Path<String>, State(state): State<AppState>) -> Html<String> {
-    let blockchain = state.blockchain.read().await;
-    
-    // Calculate balance and find transactions for this address
-    let mut balance = 0u64;
-    let mut address_transactions = Vec::new();
-    
-    for block in &blockchain.chain {
-        for tx in &block.transactions {
-            if tx.from == address || tx.to == address {
-                address_transactions.push((tx, block));
-                
-                if tx.to == address {
-                    balance += tx.amount;
-                }
-                if tx.from == address {
-                    balance = balance.saturating_sub(tx.amount + tx.fee);
-                }
-            }
-        }
-    }
+    let address_transactions = address_transactions(&state, &address).await;
+    let database = state.database.read().await;
+    let fees = confirmed_fees(database.as_ref(), address_transactions.iter().map(|(tx, _)| tx)).await;
+    let balance = address_balance(&address, &address_transactions, &fees);
     
     let transactions_html = address_transactions
         .iter()
         .rev()
         .take(20)
-        .map(|(tx, block)| format!(
+        .map(|(tx, height)| format!(
             r#"<tr>
                 <td><a href="/transactions/{}" class="hash">{}</a></td>
                 <td>{}</td>
                 <td>{:.8} QTC</td>
-                <td>{:.8} QTC</td>
+                <td>{}</td>
                 <td><a href="/blocks/{}">#{}</a></td>
                 <td>{}</td>
             </tr>"#,
             tx.id, &tx.id[..16],
             if tx.to == address { "Received" } else { "Sent" },
             tx.amount as f64 / 100_000_000.0,
-            tx.fee as f64 / 100_000_000.0,
-            block.index, block.index,
+            format_fee(fees.get(&tx.id).copied()),
+            height, height,
             format_timestamp(tx.timestamp.timestamp())
         ))
         .collect::<Vec<_>>()
@@ -866,12 +1003,12 @@ async fn explorer_search(Query(query): Query<SearchQuery>, State(state): State<A
                         <h3>💰 Transaction Found</h3>
                         <p><strong>ID:</strong> <a href="/transactions/{}" class="hash">{}</a></p>
                         <p><strong>Amount:</strong> {:.8} QTC</p>
-                        <p><strong>Fee:</strong> {:.8} QTC</p>
+                        <p><strong>Fee:</strong> {}</p>
                         <p><strong>Confirmations:</strong> {}</p>
                     </div>"#,
                     tx.txid, tx.txid,
                     tx.amount as f64 / 100_000_000.0,
-                    tx.fee as f64 / 100_000_000.0,
+                    format_fee(tx.fee),
                     tx.confirmations.unwrap_or(0)
                     )
                 } else { "".to_string() }
@@ -975,21 +1112,23 @@ async fn explorer_mempool(State(state): State<AppState>) -> Html<String> {
     let mempool = state.mempool.read().await;
     let mempool_stats = mempool.get_mempool_stats();
     
-    let transactions_html = mempool.get_transactions_by_fee(50)
+    let transactions_html = mempool.get_transactions_by_priority(50)
         .iter()
-        .map(|entry| format!(
+        .map(|(entry, priority)| format!(
             r#"<tr>
                 <td><span class="hash">{}</span></td>
                 <td>{:.8} QTC</td>
                 <td>{:.6}</td>
+                <td>{:.6}</td>
                 <td>{}</td>
-                <td>{}</td>
+                <td>{:.6}</td>
             </tr>"#,
             &entry.transaction.id[..16],
             entry.transaction.outputs.iter().map(|o| o.value).sum::<u64>() as f64 / 100_000_000.0,
-            entry.fee_per_byte,
+            priority.fee_rate,
+            priority.ancestor_fee_rate,
             format_timestamp(entry.received_time.timestamp()),
-            entry.priority
+            priority.score
         ))
         .collect::<Vec<_>>()
         .join("");
@@ -1013,8 +1152,9 @@ th {{ background-color: #f5f5f5; }}
 <p><strong>Max Fee:</strong> {:.6} QTC/byte</p>
 </div>
 <h2>Pending Transactions</h2>
+<p>Priority is the lower of the fee rate and the ancestor package fee rate, boosted by up to 2x over the first 24 hours in the pool.</p>
 <table>
-<tr><th>Transaction ID</th><th>Amount</th><th>Fee/Byte</th><th>Received</th><th>Priority</th></tr>
+<tr><th>Transaction ID</th><th>Amount</th><th>Fee/Byte</th><th>Ancestor Fee/Byte</th><th>Received</th><th>Priority</th></tr>
 {}
 </table>
 <script>setTimeout(() => location.reload(), 5000);</script>
@@ -1127,21 +1267,19 @@ async fn get_block_api(Path(height): Path<u64>, State(state): State<AppState>) -
 async fn get_transactions_api(State(state): State<AppState>) -> Json<Vec<TransactionSummary>> {
     let blockchain = state.blockchain.read().await;
     let mempool = state.mempool.read().await;
+    let database = state.database.read().await;
     
     let mut transactions = Vec::new();
     
     // Get confirmed transactions from recent blocks
     for block in blockchain.chain.iter().rev().take(10) {
         for tx in &block.transactions {
-            transactions.push(TransactionSummary {
-                txid: tx.id.clone(),
-                timestamp: tx.timestamp.timestamp(),
-                amount: tx.amount,
-                fee: tx.fee,
-                input_count: 1, // Simplified for basic transactions
-                output_count: 1,
-                confirmations: Some(blockchain.chain.len() as u64 - block.index),
-            });
+            let stored = stored_transaction(database.as_ref(), &tx.id).await;
+            transactions.push(TransactionSummary::confirmed(
+                tx,
+                stored.as_ref(),
+                blockchain.chain.len() as u64 - block.index,
+            ));
         }
     }
     
@@ -1151,7 +1289,7 @@ async fn get_transactions_api(State(state): State<AppState>) -> Json<Vec<Transac
             txid: entry.transaction.id.clone(),
             timestamp: entry.transaction.timestamp.timestamp(),
             amount: entry.transaction.outputs.iter().map(|o| o.value).sum(),
-            fee: entry.transaction.calculate_fee(&std::collections::HashMap::new()).unwrap_or(0),
+            fee: Some(entry.fee),
             input_count: entry.transaction.inputs.len(),
             output_count: entry.transaction.outputs.len(),
             confirmations: None, // Unconfirmed
@@ -1161,22 +1299,71 @@ async fn get_transactions_api(State(state): State<AppState>) -> Json<Vec<Transac
     Json(transactions)
 }
 
+async fn get_mempool_api(State(state): State<AppState>) -> Json<MempoolSummary> {
+    let mempool = state.mempool.read().await;
+    let stats = mempool.get_mempool_stats();
+
+    let transactions = mempool.get_transactions_by_priority(100)
+        .into_iter()
+        .map(|(entry, priority)| MempoolTransaction {
+            txid: entry.transaction.id.clone(),
+            size: entry.size,
+            fee: entry.fee,
+            received_time: entry.received_time.timestamp(),
+            priority,
+        })
+        .collect();
+
+    Json(MempoolSummary {
+        transaction_count: stats.transaction_count,
+        total_bytes: stats.total_bytes,
+        avg_fee_per_byte: stats.avg_fee_per_byte,
+        transactions,
+    })
+}
+
+async fn get_difficulty_history_api(
+    Query(params): Query<DifficultyHistoryQuery>,
+    State(state): State<AppState>,
+) -> Json<Vec<DifficultyPoint>> {
+    let blockchain = state.blockchain.read().await;
+    let blocks = params.blocks.unwrap_or(DEFAULT_DIFFICULTY_HISTORY).min(MAX_DIFFICULTY_HISTORY);
+    Json(difficulty_history(&blockchain, blocks))
+}
+
+/// Difficulty of the last `blocks` blocks, oldest first
+fn difficulty_history(blockchain: &Blockchain, blocks: usize) -> Vec<DifficultyPoint> {
+    let start = blockchain.chain.len().saturating_sub(blocks);
+    blockchain.chain[start..]
+        .iter()
+        .map(|block| {
+            let bits = target_to_compact(leading_zeros_to_target(block.difficulty));
+            DifficultyPoint {
+                height: block.index,
+                timestamp: block.timestamp.timestamp(),
+                bits,
+                difficulty: target_to_difficulty(compact_to_target(bits)),
+            }
+        })
+        .collect()
+}
+
+/// Target for a block whose hash must start with `zeros` hex zeros: that
+/// PoW check accepts exactly the hashes `<=` this value
+fn leading_zeros_to_target(zeros: usize) -> U256 {
+    U256::MAX >> (4 * zeros).min(256) as u32
+}
+
 async fn get_transaction_api(Path(txid): Path<String>, State(state): State<AppState>) -> Json<Option<TransactionSummary>> {
     let blockchain = state.blockchain.read().await;
+    let database = state.database.read().await;
     
     // Search confirmed transactions
     for block in &blockchain.chain {
         if let Some(tx) = block.transactions.iter().find(|t| t.id == txid) {
-            let tx_summary = TransactionSummary {
-                txid: tx.id.clone(),
-                timestamp: tx.timestamp.timestamp(),
-                amount: tx.amount,
-                fee: tx.fee,
-                input_count: 1,
-                output_count: 1,
-                confirmations: Some(blockchain.chain.len() as u64 - block.index),
-            };
-            return Json(Some(tx_summary));
+            let stored = stored_transaction(database.as_ref(), &tx.id).await;
+            let confirmations = blockchain.chain.len() as u64 - block.index;
+            return Json(Some(TransactionSummary::confirmed(tx, stored.as_ref(), confirmations)));
         }
     }
     
@@ -1187,7 +1374,7 @@ async fn get_transaction_api(Path(txid): Path<String>, State(state): State<AppSt
             txid: entry.transaction.id.clone(),
             timestamp: entry.transaction.timestamp.timestamp(),
             amount: entry.transaction.outputs.iter().map(|o| o.value).sum(),
-            fee: entry.transaction.calculate_fee(&std::collections::HashMap::new()).unwrap_or(0),
+            fee: Some(entry.fee),
             input_count: entry.transaction.inputs.len(),
             output_count: entry.transaction.outputs.len(),
             confirmations: None,
@@ -1198,47 +1385,47 @@ async fn get_transaction_api(Path(txid): Path<String>, State(state): State<AppSt
     Json(None)
 }
 
-async fn get_address_api(Path(address): Path<String>, State(state): State<AppState>) -> Json<Option<AddressSummary>> {
-    let blockchain = state.blockchain.read().await;
-    
-    let mut balance = 0u64;
-    let mut transaction_count = 0usize;
-    let mut first_seen: Option<i64> = None;
-    let mut last_seen: Option<i64> = None;
+async fn get_address_api(
+    Path(address): Path<String>,
+    Query(query): Query<AddressQuery>,
+    State(state): State<AppState>,
+) -> Json<Option<AddressSummary>> {
+    let address_transactions = address_transactions(&state, &address).await;
+    let database = state.database.read().await;
+    let fees = confirmed_fees(database.as_ref(), address_transactions.iter().map(|(tx, _)| tx)).await;
+    let balance = address_balance(&address, &address_transactions, &fees);
+    let transaction_count = address_transactions.len();
+    let timestamps = address_transactions.iter().map(|(tx, _)| tx.timestamp.timestamp());
+    let first_seen = timestamps.clone().min();
+    let last_seen = timestamps.max();
     
-    // Calculate address statistics from blockchain
-    for block in &blockchain.chain {
-        for tx in &block.transactions {
-            let timestamp = tx.timestamp.timestamp();
-            
-            if tx.from == address || tx.to == address {
-                transaction_count += 1;
-                
-                if first_seen.is_none() || timestamp < first_seen.unwrap() {
-                    first_seen = Some(timestamp);
-                }
-                
-                if last_seen.is_none() || timestamp > last_seen.unwrap() {
-                    last_seen = Some(timestamp);
-                }
-                
-                if tx.to == address {
-                    balance += tx.amount;
-                }
-                if tx.from == address {
-                    balance = balance.saturating_sub(tx.amount + tx.fee);
-                }
-            }
+    let utxos = match database.as_ref() {
+        Some(database) => {
+            let utxo_set = database.get_utxo_set().await;
+            let revstop = match &state.revstop {
+                Some(revstop) => Some(revstop.read().await),
+                None => None,
+            };
+            utxo_set.get_utxo_statuses(
+                &address,
+                query.min_confirmations.unwrap_or(DEFAULT_MIN_CONFIRMATIONS),
+                COINBASE_MATURITY,
+                |utxo| revstop.as_ref().is_some_and(|r| r.blocks_spend(&utxo.tx_id, &address)),
+            )
         }
-    }
+        None => Vec::new(),
+    };
+    let spendable_balance = utxos.iter().filter(|u| u.spendable).map(|u| u.amount).sum();
     
-    if transaction_count > 0 {
+    if transaction_count > 0 || !utxos.is_empty() {
         let address_summary = AddressSummary {
             address,
             balance,
             transaction_count,
             first_seen,
             last_seen,
+            spendable_balance,
+            utxos,
         };
         Json(Some(address_summary))
     } else {
@@ -1276,4 +1463,115 @@ mod tests {
         assert_eq!(stats.total_blocks, 1000);
         assert_eq!(stats.circulating_supply, 1000000000000);
     }
+
+    #[test]
+    fn test_difficulty_history() {
+        let mut blockchain = Blockchain::new();
+        let genesis = blockchain.chain[0].clone();
+        for (i, difficulty) in [1, 2, 3, 4, 5, 6, 7, 8].into_iter().enumerate() {
+            let mut block = genesis.clone();
+            block.index = i as u64 + 1;
+            block.timestamp = genesis.timestamp + chrono::Duration::minutes(10 * (i as i64 + 1));
+            block.difficulty = difficulty;
+            blockchain.chain.push(block);
+        }
+
+        let history = difficulty_history(&blockchain, 5);
+        assert_eq!(history.len(), 5);
+        assert_eq!(history.first().unwrap().height, 4);
+        assert_eq!(history.last().unwrap().height, 8);
+        for point in &history {
+            let block = &blockchain.chain[point.height as usize];
+            assert_eq!(point.timestamp, block.timestamp.timestamp());
+            assert_eq!(point.bits, target_to_compact(leading_zeros_to_target(block.difficulty)));
+            assert_eq!(point.difficulty, target_to_difficulty(compact_to_target(point.bits)));
+        }
+        // Each leading hex zero makes a block 16 times harder to find
+        for pair in history.windows(2) {
+            assert!((pair[1].difficulty / pair[0].difficulty - 16.0).abs() < 1e-3);
+        }
+
+        assert_eq!(difficulty_history(&blockchain, 100).len(), blockchain.chain.len());
+    }
+
+    #[test]
+    fn test_confirmed_summary_uses_stored_form() {
+        use crate::transaction::{TransactionInput, TransactionOutput};
+
+        let stored = SignedTransaction::new(
+            (0..3)
+                .map(|i| TransactionInput {
+                    previous_output: format!("prev:{}", i),
+                    script_sig: Vec::new(),
+                    sequence: 0xffffffff,
+                })
+                .collect(),
+            (0..2)
+                .map(|i| TransactionOutput {
+                    value: 1000 * (i + 1),
+                    script_pubkey: Vec::new(),
+                    address: format!("qtc1qrecipient{}", i),
+                })
+                .collect(),
+            0,
+        );
+        let tx = Transaction {
+            id: stored.id.clone(),
+            from: "qtc1qsender".to_string(),
+            to: "qtc1qrecipient0".to_string(),
+            amount: 3000,
+            timestamp: stored.timestamp,
+            signature: String::new(),
+            fee: 10,
+        };
+
+        let stored = StoredTransaction { transaction: stored, fee: 250 };
+
+        let summary = TransactionSummary::confirmed(&tx, Some(&stored), 6);
+        assert_eq!(summary.input_count, 3);
+        assert_eq!(summary.output_count, 2);
+        assert_eq!(summary.fee, Some(250));
+        assert_eq!(summary.confirmations, Some(6));
+
+        // Without a stored form the account-model shape is reported, and
+        // the unverifiable fee field is not
+        let summary = TransactionSummary::confirmed(&tx, None, 6);
+        assert_eq!((summary.input_count, summary.output_count), (1, 1));
+        assert_eq!(summary.fee, None);
+        let coinbase = Transaction { from: String::new(), ..tx };
+        let summary = TransactionSummary::confirmed(&coinbase, None, 6);
+        assert_eq!((summary.input_count, summary.output_count), (0, 1));
+    }
+
+    #[test]
+    fn test_address_balance_uses_derived_fees() {
+        let transfer = |id: &str, from: &str, to: &str, amount: u64| Transaction {
+            id: id.to_string(),
+            from: from.to_string(),
+            to: to.to_string(),
+            amount,
+            timestamp: chrono::Utc::now(),
+            signature: String::new(),
+            // Claims a fee nobody derived; never charged
+            fee: 1_000,
+        };
+        let transactions = vec![
+            (transfer("in", "", "alice", 10_000), 1),
+            (transfer("out", "alice", "bob", 4_000), 2),
+            (transfer("unknown", "alice", "bob", 1_000), 3),
+        ];
+        let fees = HashMap::from([("out".to_string(), 250)]);
+
+        assert_eq!(address_balance("alice", &transactions, &fees), 10_000 - 4_250 - 1_000);
+        assert_eq!(address_balance("bob", &transactions, &fees), 5_000);
+    }
+
+    #[test]
+    fn test_leading_zeros_to_target() {
+        // Eight hex zeros is the difficulty-1 target
+        assert_eq!(target_to_compact(leading_zeros_to_target(8)), 0x1d00ffff);
+        assert_eq!(target_to_difficulty(compact_to_target(0x1d00ffff)), 1.0);
+        assert_eq!(leading_zeros_to_target(0), U256::MAX);
+        assert_eq!(leading_zeros_to_target(64), U256::ZERO);
+    }
 }
@@ -19,13 +19,16 @@ mod block_monitor;
 use blockchain::Blockchain;
 use transaction::Transaction;
 use block::Block;
-use mining::Miner;
-use mempool::Mempool;
-use network::NetworkNode;
+use mining::{Miner, MiningConfig};
+use mempool::{Mempool, MempoolPolicy};
+use network::NetworkManager;
+use p2p::P2PNode;
 use revstop::RevStop;
+use rpc::RpcServer;
 
 use clap::{Parser, Subcommand};
 use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use anyhow::Result;
@@ -50,6 +53,9 @@ enum Commands {
         /// Address to bind to
         #[arg(short, long, default_value = "0.0.0.0")]
         bind: String,
+        /// Port for the RPC API, served on loopback
+        #[arg(long, default_value = "8332")]
+        rpc_port: u16,
         /// Enable mining
         #[arg(short, long)]
         mine: bool,
@@ -59,6 +65,13 @@ enum Commands {
         /// Peer addresses to connect to
         #[arg(long)]
         peers: Vec<String>,
+        /// Node config file; its [mempool] and [mining] sections set the
+        /// mempool policy and block assembly
+        #[arg(long)]
+        config: Option<PathBuf>,
+        /// Directory holding the node identity, so the PeerId survives restarts
+        #[arg(long, default_value = "data")]
+        data_dir: PathBuf,
     },
     /// Mining operations
     Mine {
@@ -113,8 +126,12 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
     
     match cli.command {
-        Commands::Node { port, bind, mine, mining_address, peers } => {
-            start_node(port, &bind, mine, mining_address, peers).await?;
+        Commands::Node { port, bind, rpc_port, mine, mining_address, peers, config, data_dir } => {
+            let (policy, mining_config) = match config {
+                Some(path) => (MempoolPolicy::load(&path)?, MiningConfig::load(&path)?),
+                None => (MempoolPolicy::default(), MiningConfig::default()),
+            };
+            start_node(port, &bind, rpc_port, mine, mining_address, peers, policy, mining_config, &data_dir).await?;
         }
         Commands::Mine { address, threads } => {
             start_mining(&address, threads).await?;
@@ -133,55 +150,91 @@ async fn main() -> Result<()> {
 async fn start_node(
     port: u16,
     bind: &str,
+    rpc_port: u16,
     enable_mining: bool,
     mining_address: Option<String>,
     peer_addresses: Vec<String>,
+    mempool_policy: MempoolPolicy,
+    mining_config: MiningConfig,
+    data_dir: &Path,
 ) -> Result<()> {
     info!("Starting QuantumCoin node on {}:{}", bind, port);
     
     // Initialize components
     let blockchain = Arc::new(RwLock::new(Blockchain::new()));
-    let mempool = Arc::new(RwLock::new(Mempool::default()));
+    let mempool = Arc::new(RwLock::new(Mempool::new(mempool_policy)));
     let revstop = Arc::new(RwLock::new(RevStop::new()));
     
     // Start network node
     let listen_addr: SocketAddr = format!("{}:{}", bind, port).parse()?;
-    let mut network_node = NetworkNode::new(listen_addr, {
-        let blockchain_read = blockchain.read().await;
-        blockchain_read.clone()
-    });
+    let network = NetworkManager::with_data_dir(
+        listen_addr,
+        Arc::clone(&blockchain),
+        Arc::clone(&mempool),
+        None,
+        data_dir,
+    ).await?;
+    info!("Node identity {}", network.node_id);
     
-    network_node.start().await?;
+    // Unreachable seeds leave the node running on the peers given below
+    if let Err(e) = network.start().await {
+        error!("Network startup incomplete: {}", e);
+    }
     
     // Connect to peers
     for peer_addr in peer_addresses {
         if let Ok(addr) = peer_addr.parse::<SocketAddr>() {
-            if let Err(e) = network_node.connect_to_peer(addr).await {
+            if let Err(e) = network.peer_manager.connect_to_peer(addr).await {
                 error!("Failed to connect to peer {}: {}", addr, e);
             }
         }
     }
     
     // Start mining if enabled
+    let mut miner = None;
     if enable_mining {
         if let Some(mining_addr) = mining_address {
-            let miner = Miner::new(
+            let local_miner = Arc::new(Miner::new(
                 mining_addr,
                 Arc::clone(&blockchain),
                 Arc::clone(&mempool),
                 Arc::clone(&revstop),
-            );
+            )
+            .with_config(&mining_config));
             
+            let mining = Arc::clone(&local_miner);
             tokio::spawn(async move {
-                if let Err(e) = miner.start_mining().await {
+                if let Err(e) = mining.start_mining().await {
                     error!("Mining error: {}", e);
                 }
             });
+            miner = Some(local_miner);
         } else {
             error!("Mining enabled but no mining address provided");
         }
     }
     
+    // Start RPC server
+    {
+        let rpc_addr = SocketAddr::from(([127, 0, 0, 1], rpc_port));
+        let mut rpc_server = RpcServer::new(
+            rpc_addr,
+            Arc::clone(&blockchain),
+            Arc::new(RwLock::new(None)),
+            Arc::clone(&mempool),
+            Arc::new(P2PNode::new(listen_addr, Arc::clone(&blockchain), Arc::clone(&mempool))),
+        )
+        .with_revstop(Arc::clone(&revstop));
+        if let Some(miner) = &miner {
+            rpc_server = rpc_server.with_miner(Arc::clone(miner));
+        }
+        tokio::spawn(async move {
+            if let Err(e) = rpc_server.start().await {
+                error!("RPC server error: {}", e);
+            }
+        });
+    }
+    
     // Start mempool cleanup task
     {
         let mempool = Arc::clone(&mempool);
@@ -189,8 +242,7 @@ async fn start_node(
             let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(300));
             loop {
                 interval.tick().await;
-                let mempool_read = mempool.read().await;
-                mempool_read.cleanup_expired();
+                mempool.write().await.cleanup_expired();
             }
         });
     }
@@ -201,7 +253,20 @@ async fn start_node(
     loop {
         tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
         
-        let peer_count = network_node.get_peer_count().await;
+        // Re-announce our own transactions that are still unconfirmed
+        let due = mempool.write().await.due_for_rebroadcast(chrono::Utc::now());
+        for tx in due {
+            if let Err(e) = network.gossip_transaction(tx.to_simple_transaction()).await {
+                error!("Failed to re-announce transaction: {}", e);
+            }
+        }
+
+        // Back off mining while too many of the blocks we see are orphaned
+        if let Some(miner) = &miner {
+            miner.update_orphan_rate(network.metrics.orphan_rate().await).await;
+        }
+
+        let peer_count = network.peer_manager.get_peer_count().await;
         let mempool_size = {
             let mempool_read = mempool.read().await;
             mempool_read.size()
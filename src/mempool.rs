@@ -1,109 +1,726 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, RwLock};
 use chrono::{DateTime, Utc, Duration};
 use serde::{Serialize, Deserialize};
+use tokio::sync::broadcast;
 use crate::transaction::{Transaction, SignedTransaction};
+use crate::utxo::UTXOSet;
 use anyhow::{Result, anyhow};
 
+/// Events buffered per subscriber; a subscriber that falls further behind
+/// loses the oldest events
+pub const MEMPOOL_EVENT_BUFFER: usize = 1024;
+
+/// Default cap on the total serialized size of pooled transactions
+pub const DEFAULT_MEMPOOL_MAX_BYTES: usize = 300 * 1024 * 1024;
+
+/// Default time a transaction may wait in the pool before it is evicted
+pub const DEFAULT_MEMPOOL_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// Size a transaction counts for in fee rates
+pub fn serialized_size<T: Serialize>(transaction: &T) -> usize {
+    bincode::serialize(transaction).map(|data| data.len()).unwrap_or(1)
+}
+
+/// Fee rate in satoshis per `serialized_size` byte. The one unit of
+/// `MempoolEntry::fee_per_byte`, `min_relay_fee` and `relay_fee_floor`, so
+/// whatever is checked against them must be rated with it.
+pub fn fee_per_byte(fee: u64, size: usize) -> f64 {
+    if size > 0 { fee as f64 / size as f64 } else { 0.0 }
+}
+
+/// Mempool limits and relay rules, normally read from the `[mempool]`
+/// section of the node config. Missing keys fall back to the defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MempoolPolicy {
+    /// Cap on the total serialized size of pooled transactions
+    pub max_bytes: usize,
+    /// Cap on the number of pooled transactions
+    pub max_count: usize,
+    /// Lowest fee per byte accepted into the pool
+    pub min_relay_fee: f64,
+    /// Fee per byte a replacement must add on top of what it replaces
+    pub incremental_relay_fee: f64,
+    /// Most in-pool ancestors a transaction may have, counting itself
+    pub max_ancestors: usize,
+    /// Most in-pool descendants any pooled transaction may have, counting itself
+    pub max_descendants: usize,
+    /// Whether a higher-fee spend may replace a pooled conflicting one
+    pub rbf_enabled: bool,
+    /// Allow replacing pooled transactions that do not signal
+    /// replaceability through their own or an ancestor's input sequences
+    pub full_rbf: bool,
+    /// Seconds a transaction may stay pooled before it is evicted
+    pub ttl_secs: u64,
+    /// How often to re-announce our own still-unconfirmed transactions;
+    /// unset disables re-broadcast
+    pub rebroadcast_secs: Option<u64>,
+    /// Raise a `DoubleSpend` event, relayed to peers, when a correctly
+    /// signed spend conflicting with a pooled transaction first arrives
+    pub double_spend_alerts: bool,
+}
+
+impl Default for MempoolPolicy {
+    fn default() -> Self {
+        Self {
+            max_bytes: DEFAULT_MEMPOOL_MAX_BYTES,
+            max_count: 10_000,
+            min_relay_fee: 0.0001,
+            incremental_relay_fee: 0.0001,
+            max_ancestors: 25,
+            max_descendants: 25,
+            rbf_enabled: true,
+            full_rbf: false,
+            ttl_secs: DEFAULT_MEMPOOL_TTL_SECS,
+            rebroadcast_secs: None,
+            double_spend_alerts: true,
+        }
+    }
+}
+
+impl MempoolPolicy {
+    /// Read the `[mempool]` section of a TOML config; the section is optional
+    pub fn from_toml(config: &str) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct Config {
+            #[serde(default)]
+            mempool: MempoolPolicy,
+        }
+        Ok(toml::from_str::<Config>(config)?.mempool)
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Self> {
+        let config = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        Self::from_toml(&config)
+    }
+}
+
+/// Time in the pool after which a transaction earns the full age boost
+pub const PRIORITY_AGE_RAMP_SECS: i64 = 24 * 60 * 60;
+
+/// Largest fraction of its fee rate a transaction can gain from waiting
+pub const PRIORITY_MAX_AGE_BOOST: f64 = 1.0;
+
+/// Most double-spend alerts raised against one pooled transaction. The
+/// owner of its inputs can sign any number of distinct conflicts, and
+/// after the first few another alert tells a merchant nothing new.
+pub const MAX_ALERTS_PER_TRANSACTION: usize = 4;
+
+/// Inclusion priority of a pooled transaction.
+///
+/// The score starts from the lower of the transaction's own fee rate and the
+/// fee rate of the package formed with its unconfirmed ancestors, since a
+/// child cannot be mined before its parents. That rate is then boosted
+/// linearly with time spent in the pool, up to `PRIORITY_MAX_AGE_BOOST`
+/// after `PRIORITY_AGE_RAMP_SECS`, so a long-waiting transaction gains
+/// ground on equal-fee newcomers without overtaking much higher fees.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PriorityScore {
+    pub fee_rate: f64,
+    pub ancestor_fee_rate: f64,
+    pub age_secs: i64,
+    pub score: f64,
+}
+
+impl PriorityScore {
+    pub fn compute(fee_rate: f64, ancestor_fee_rate: f64, age: Duration) -> Self {
+        let age_secs = age.num_seconds().max(0);
+        let ramp = (age_secs as f64 / PRIORITY_AGE_RAMP_SECS as f64).min(1.0);
+        let score = fee_rate.min(ancestor_fee_rate) * (1.0 + PRIORITY_MAX_AGE_BOOST * ramp);
+        Self { fee_rate, ancestor_fee_rate, age_secs, score }
+    }
+}
+
+/// Why a transaction left the mempool without being mined
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EvictionReason {
+    /// Pushed out by a higher-fee transaction when the pool was full
+    LowFee,
+    /// Sat in the pool longer than the maximum transaction age
+    Expired,
+    /// Replaced by a higher-fee spend of the same outputs, or descended
+    /// from a replaced transaction
+    Replaced,
+    /// Spent an output of a pooled transaction that was evicted
+    ParentEvicted,
+}
+
+/// Two transactions spending at least one output in common. Lets
+/// merchants accepting zero-conf payments learn of a conflict quickly.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DoubleSpendAlert {
+    /// The pooled transaction
+    pub original: String,
+    /// The later arrival spending the same output
+    pub conflicting: String,
+}
+
+/// Mempool arrivals and departures, for fee estimators and other observers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum MempoolEvent {
+    TxAccepted {
+        txid: String,
+        fee_rate: f64,
+        size: usize,
+        time: DateTime<Utc>,
+    },
+    TxEvicted {
+        txid: String,
+        reason: EvictionReason,
+    },
+    /// Raised once per pair, whether the conflicting transaction replaces
+    /// the original or is rejected
+    DoubleSpend(DoubleSpendAlert),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MempoolEntry {
     pub transaction: SignedTransaction,
     pub received_time: DateTime<Utc>,
     pub fee_per_byte: f64,
-    pub priority: u64,
+    pub fee: u64,
+    pub size: usize,
+    /// Witness-discounted size, see `SignedTransaction::weight`
+    #[serde(default)]
+    pub weight: usize,
+    /// Submitted through this node rather than relayed to us by a peer
+    #[serde(default)]
+    pub local: bool,
+    /// When we last announced a local transaction ourselves
+    #[serde(default)]
+    pub last_broadcast: Option<DateTime<Utc>>,
 }
 
 impl MempoolEntry {
-    pub fn new(transaction: SignedTransaction) -> Self {
-        let fee = transaction.calculate_fee(&HashMap::new()).unwrap_or(0);
-        let size = bincode::serialize(&transaction).map(|data| data.len()).unwrap_or(1);
-        let fee_per_byte = if size > 0 { fee as f64 / size as f64 } else { 0.0 };
+    /// `fee` must be derived from the outputs `transaction` spends, see
+    /// `Mempool::derive_fee`
+    pub fn new(transaction: SignedTransaction, fee: u64) -> Self {
+        let size = serialized_size(&transaction);
+        let weight = transaction.weight();
         
         Self {
             transaction,
             received_time: Utc::now(),
-            fee_per_byte,
-            priority: fee,
+            fee_per_byte: fee_per_byte(fee, size),
+            fee,
+            size,
+            weight,
+            local: false,
+            last_broadcast: None,
         }
     }
 
+    /// Fee per weight unit; entries without a recorded weight fall back to
+    /// their serialized size
+    pub fn fee_per_weight(&self) -> f64 {
+        let weight = if self.weight > 0 { self.weight } else { self.size };
+        if weight > 0 { self.fee as f64 / weight as f64 } else { 0.0 }
+    }
+
     pub fn is_expired(&self, max_age: Duration) -> bool {
-        Utc::now() - self.received_time > max_age
+        self.is_expired_at(max_age, Utc::now())
+    }
+
+    pub fn is_expired_at(&self, max_age: Duration, now: DateTime<Utc>) -> bool {
+        now - self.received_time > max_age
     }
 }
 
 pub struct Mempool {
     transactions: HashMap<String, MempoolEntry>,
-    max_size: usize,
+    /// Previous output -> id of the pooled transaction spending it
+    spent_by: HashMap<String, String>,
+    /// Confirmed unspent output -> its value, for deriving fees
+    utxo_values: HashMap<String, u64>,
+    /// Confirmed unspent output -> address it pays, for checking who
+    /// signed a conflicting spend
+    utxo_owners: HashMap<String, String>,
+    /// Pooled transaction id -> virtual fee from `prioritise_transaction`
+    fee_deltas: HashMap<String, i64>,
+    /// Pooled transaction id -> conflicting ids already alerted on, so
+    /// resending a conflict does not raise the alert again. At most
+    /// `MAX_ALERTS_PER_TRANSACTION` per original.
+    alerted: HashMap<String, HashSet<String>>,
+    policy: MempoolPolicy,
+    total_bytes: usize,
     max_transaction_age: Duration,
-    min_fee_per_byte: f64,
+    events: broadcast::Sender<MempoolEvent>,
 }
 
 impl Mempool {
-    pub fn new(max_size: usize) -> Self {
+    pub fn new(policy: MempoolPolicy) -> Self {
+        let (events, _) = broadcast::channel(MEMPOOL_EVENT_BUFFER);
         Self {
             transactions: HashMap::new(),
-            max_size,
-            max_transaction_age: Duration::hours(24),
-            min_fee_per_byte: 0.0001, // Minimum fee per byte
+            spent_by: HashMap::new(),
+            utxo_values: HashMap::new(),
+            utxo_owners: HashMap::new(),
+            fee_deltas: HashMap::new(),
+            alerted: HashMap::new(),
+            max_transaction_age: Duration::seconds(policy.ttl_secs as i64),
+            policy,
+            total_bytes: 0,
+            events,
+        }
+    }
+
+    /// Limit the pool by total serialized size as well as transaction count
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.policy.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn policy(&self) -> &MempoolPolicy {
+        &self.policy
+    }
+
+    /// Total serialized size of pooled transactions
+    pub fn total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+
+    pub fn max_bytes(&self) -> usize {
+        self.policy.max_bytes
+    }
+
+    /// Receive acceptance and eviction events from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<MempoolEvent> {
+        self.events.subscribe()
+    }
+
+    fn emit(&self, event: MempoolEvent) {
+        // Sending only fails when nobody is subscribed
+        let _ = self.events.send(event);
+    }
+
+    /// Value fees from `utxos` from now on. Call whenever the confirmed
+    /// UTXO set changes.
+    pub fn set_utxo_set(&mut self, utxos: &UTXOSet) {
+        self.utxo_values = utxos.outpoint_values();
+        self.utxo_owners = utxos.outpoint_owners();
+    }
+
+    /// Value of `outpoint`, whether confirmed or created by a pooled transaction
+    fn prevout_value(&self, outpoint: &str) -> Option<u64> {
+        if let Some(value) = self.utxo_values.get(outpoint) {
+            return Some(*value);
         }
+        let (parent_id, index) = outpoint.split_once(':')?;
+        let parent = self.transactions.get(parent_id)?;
+        parent.transaction.outputs.get(index.parse::<usize>().ok()?).map(|output| output.value)
+    }
+
+    /// Address `outpoint` pays, whether confirmed or created by a pooled transaction
+    fn prevout_owner(&self, outpoint: &str) -> Option<&str> {
+        if let Some(owner) = self.utxo_owners.get(outpoint) {
+            return Some(owner);
+        }
+        let (parent_id, index) = outpoint.split_once(':')?;
+        let parent = self.transactions.get(parent_id)?;
+        parent.transaction.outputs.get(index.parse::<usize>().ok()?).map(|output| output.address.as_str())
+    }
+
+    /// Value of `outpoint` if a new transaction could spend it: a confirmed
+    /// output or one created by a pooled transaction, not already spent by
+    /// another pooled transaction. Lets wallets chain spends off unconfirmed
+    /// change.
+    pub fn unspent_value(&self, outpoint: &str) -> Option<u64> {
+        if self.spent_by.contains_key(outpoint) {
+            return None;
+        }
+        self.prevout_value(outpoint)
+    }
+
+    /// Fee paid by `transaction`: the value of the outputs it spends minus
+    /// its own outputs. Fails if any spent output is unknown.
+    pub fn derive_fee(&self, transaction: &SignedTransaction) -> Result<u64> {
+        let input_values = transaction.inputs
+            .iter()
+            .filter_map(|input| {
+                let value = self.prevout_value(&input.previous_output)?;
+                Some((input.previous_output.clone(), value))
+            })
+            .collect();
+        transaction.calculate_fee(&input_values)
     }
 
     pub fn add_transaction(&mut self, transaction: SignedTransaction) -> Result<()> {
+        let fee = self.derive_fee(&transaction)?;
+        self.add_entry(MempoolEntry::new(transaction, fee))
+    }
+
+    /// Add a transaction submitted through this node. The caller announces
+    /// it now; `due_for_rebroadcast` announces it again while it stays pooled.
+    pub fn add_local_transaction(&mut self, transaction: SignedTransaction) -> Result<()> {
+        let fee = self.derive_fee(&transaction)?;
+        let mut entry = MempoolEntry::new(transaction, fee);
+        entry.local = true;
+        entry.last_broadcast = Some(entry.received_time);
+        self.add_entry(entry)
+    }
+
+    fn add_entry(&mut self, entry: MempoolEntry) -> Result<()> {
         // Check if transaction already exists
-        if self.transactions.contains_key(&transaction.id) {
+        if self.transactions.contains_key(&entry.transaction.id) {
             return Err(anyhow!("Transaction already in mempool"));
         }
 
-        let entry = MempoolEntry::new(transaction);
-
         // Check minimum fee
-        if entry.fee_per_byte < self.min_fee_per_byte {
+        if entry.fee_per_byte < self.policy.min_relay_fee {
             return Err(anyhow!(
                 "Transaction fee too low: {} < {}",
                 entry.fee_per_byte,
-                self.min_fee_per_byte
+                self.policy.min_relay_fee
+            ));
+        }
+
+        if entry.size > self.policy.max_bytes {
+            return Err(anyhow!(
+                "Transaction too large for mempool: {} > {} bytes",
+                entry.size,
+                self.policy.max_bytes
             ));
         }
 
-        // If mempool is full, try to evict lowest fee transaction
-        if self.transactions.len() >= self.max_size {
-            self.evict_lowest_fee_transaction()?;
+        let conflicts = self.conflicts_with(&entry.transaction);
+        if self.policy.double_spend_alerts && !conflicts.is_empty() {
+            self.alert_double_spend(&entry.transaction, &conflicts);
+        }
+        if !conflicts.is_empty() {
+            self.check_replacement(&entry, &conflicts)?;
+        }
+        self.check_package_limits(&entry.transaction, &conflicts)?;
+
+        for tx_id in &conflicts {
+            for replaced in self.with_descendants(tx_id) {
+                self.remove_transaction(&replaced);
+                self.emit(MempoolEvent::TxEvicted { txid: replaced, reason: EvictionReason::Replaced });
+            }
+        }
+
+        // Evict lowest fee transactions until both the count and byte limits
+        // fit, but only for a transaction paying more than each of them
+        for tx_id in self.plan_evictions(&entry)? {
+            self.evict(&tx_id, EvictionReason::LowFee);
         }
 
         let tx_id = entry.transaction.id.clone();
+        let event = MempoolEvent::TxAccepted {
+            txid: tx_id.clone(),
+            fee_rate: entry.fee_per_byte,
+            size: entry.size,
+            time: entry.received_time,
+        };
+        self.total_bytes += entry.size;
+        for input in &entry.transaction.inputs {
+            self.spent_by.insert(input.previous_output.clone(), tx_id.clone());
+        }
         self.transactions.insert(tx_id, entry);
+        self.emit(event);
         
         Ok(())
     }
 
+    /// Whether `transaction` carries a valid signature by the address
+    /// every one of its inputs pays. A spend of an output we cannot
+    /// attribute counts as unsigned.
+    fn signed_by_owner(&self, transaction: &SignedTransaction) -> bool {
+        // Verifying first also rejects a public key that is not valid hex
+        if !transaction.verify_signature(&transaction.public_key) {
+            return false;
+        }
+        let signer = crate::quantum_crypto::public_key_to_address(&transaction.public_key);
+        transaction.inputs.iter()
+            .all(|input| self.prevout_owner(&input.previous_output) == Some(signer.as_str()))
+    }
+
+    /// Alert on each pair of `conflicting` and a pooled transaction in
+    /// `conflicts` not alerted on before. Only a conflict signed by the
+    /// owner of the outputs it spends is worth relaying; anyone can sign a
+    /// forgery with a key of their own.
+    fn alert_double_spend(&mut self, conflicting: &SignedTransaction, conflicts: &[String]) {
+        if !self.signed_by_owner(conflicting) {
+            return;
+        }
+        for tx_id in conflicts {
+            let alerted = self.alerted.entry(tx_id.clone()).or_default();
+            if alerted.len() < MAX_ALERTS_PER_TRANSACTION && alerted.insert(conflicting.id.clone()) {
+                self.emit(MempoolEvent::DoubleSpend(DoubleSpendAlert {
+                    original: tx_id.clone(),
+                    conflicting: conflicting.id.clone(),
+                }));
+            }
+        }
+    }
+
     pub fn remove_transaction(&mut self, tx_id: &str) -> Option<MempoolEntry> {
-        self.transactions.remove(tx_id)
+        let entry = self.transactions.remove(tx_id)?;
+        self.total_bytes -= entry.size;
+        self.fee_deltas.remove(tx_id);
+        self.alerted.remove(tx_id);
+        for input in &entry.transaction.inputs {
+            if self.spent_by.get(&input.previous_output).map(String::as_str) == Some(tx_id) {
+                self.spent_by.remove(&input.previous_output);
+            }
+        }
+        Some(entry)
+    }
+
+    /// Id of the pooled transaction spending `previous_output`, if any
+    pub fn spender(&self, previous_output: &str) -> Option<&str> {
+        self.spent_by.get(previous_output).map(String::as_str)
     }
 
     pub fn get_transaction(&self, tx_id: &str) -> Option<&MempoolEntry> {
         self.transactions.get(tx_id)
     }
 
+    /// Add `fee_delta` satoshis of virtual fee to a pooled transaction, as
+    /// `prioritisetransaction` does, and return its accumulated delta. The
+    /// delta only changes where the transaction is ordered and selected for
+    /// mining, never the fee it pays, and is dropped once it leaves the pool.
+    pub fn prioritise_transaction(&mut self, tx_id: &str, fee_delta: i64) -> Result<i64> {
+        if !self.transactions.contains_key(tx_id) {
+            return Err(anyhow!("Transaction {} not in mempool", tx_id));
+        }
+        let delta = self.fee_deltas.entry(tx_id.to_string()).or_insert(0);
+        *delta = delta.saturating_add(fee_delta);
+        let total = *delta;
+        if total == 0 {
+            self.fee_deltas.remove(tx_id);
+        }
+        Ok(total)
+    }
+
+    pub fn fee_delta(&self, tx_id: &str) -> i64 {
+        self.fee_deltas.get(tx_id).copied().unwrap_or(0)
+    }
+
+    /// Fee plus any prioritisation delta, floored at zero
+    pub fn modified_fee(&self, entry: &MempoolEntry) -> u64 {
+        (entry.fee as i64).saturating_add(self.fee_delta(&entry.transaction.id)).max(0) as u64
+    }
+
+    /// Fee per byte ordering and mining selection use, see `modified_fee`
+    pub fn modified_fee_rate(&self, entry: &MempoolEntry) -> f64 {
+        if entry.size > 0 { self.modified_fee(entry) as f64 / entry.size as f64 } else { 0.0 }
+    }
+
     pub fn get_transactions_by_fee(&self, limit: usize) -> Vec<&MempoolEntry> {
         let mut entries: Vec<&MempoolEntry> = self.transactions.values().collect();
-        entries.sort_by(|a, b| b.fee_per_byte.partial_cmp(&a.fee_per_byte).unwrap_or(std::cmp::Ordering::Equal));
+        entries.sort_by(|a, b| {
+            self.modified_fee_rate(b).partial_cmp(&self.modified_fee_rate(a)).unwrap_or(std::cmp::Ordering::Equal)
+        });
+        entries.into_iter().take(limit).collect()
+    }
+
+    /// Pooled transactions spending any output `tx` spends. One index
+    /// lookup per input, however large the pool is.
+    fn conflicts_with(&self, tx: &SignedTransaction) -> Vec<String> {
+        let mut conflicts: Vec<String> = Vec::new();
+        for input in &tx.inputs {
+            if let Some(tx_id) = self.spent_by.get(&input.previous_output) {
+                if !conflicts.contains(tx_id) {
+                    conflicts.push(tx_id.clone());
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// A pooled transaction may be replaced if it or one of its in-pool
+    /// ancestors signals replace-by-fee
+    fn is_replaceable(&self, tx_id: &str) -> bool {
+        let Some(entry) = self.transactions.get(tx_id) else { return false };
+        entry.transaction.signals_rbf()
+            || self.ancestors_of(&entry.transaction)
+                .iter()
+                .any(|ancestor| self.transactions[ancestor].transaction.signals_rbf())
+    }
+
+    /// A replacement must be allowed by policy, only replace transactions
+    /// that signal replaceability, pay a higher fee rate than each conflict,
+    /// and add at least the incremental relay fee for its own
+    /// size on top of their combined fees
+    fn check_replacement(&self, entry: &MempoolEntry, conflicts: &[String]) -> Result<()> {
+        if !self.policy.rbf_enabled {
+            return Err(anyhow!("Transaction conflicts with a pooled transaction"));
+        }
+
+        let mut replaced_fee = 0u64;
+        for tx_id in conflicts {
+            if !self.policy.full_rbf && !self.is_replaceable(tx_id) {
+                return Err(anyhow!("Transaction {} does not signal replaceability", tx_id));
+            }
+            for replaced in self.with_descendants(tx_id) {
+                let pooled = &self.transactions[&replaced];
+                if replaced == *tx_id && entry.fee_per_byte <= pooled.fee_per_byte {
+                    return Err(anyhow!("Replacement fee rate does not exceed {}", replaced));
+                }
+                replaced_fee += pooled.fee;
+            }
+        }
+
+        let required = replaced_fee as f64 + self.policy.incremental_relay_fee * entry.size as f64;
+        if (entry.fee as f64) < required {
+            return Err(anyhow!("Replacement fee too low: {} < {}", entry.fee, required));
+        }
+        Ok(())
+    }
+
+    /// Reject `tx` if it would have too many in-pool ancestors or push any
+    /// of them past the descendant limit. Transactions about to be replaced
+    /// are not counted.
+    fn check_package_limits(&self, tx: &SignedTransaction, replacing: &[String]) -> Result<()> {
+        let ancestors = self.ancestors_of(tx);
+        if ancestors.len() + 1 > self.policy.max_ancestors {
+            return Err(anyhow!(
+                "Too many unconfirmed ancestors: {} > {}",
+                ancestors.len() + 1,
+                self.policy.max_ancestors
+            ));
+        }
+
+        let replaced: HashSet<String> = replacing.iter().flat_map(|id| self.with_descendants(id)).collect();
+        for ancestor in &ancestors {
+            let descendants = self.with_descendants(ancestor).into_iter().filter(|d| !replaced.contains(d)).count();
+            if descendants + 1 > self.policy.max_descendants {
+                return Err(anyhow!(
+                    "Too many unconfirmed descendants of {}: {} > {}",
+                    ancestor,
+                    descendants + 1,
+                    self.policy.max_descendants
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Ids of the in-pool transactions `tx` directly or indirectly spends from
+    fn ancestors_of(&self, tx: &SignedTransaction) -> HashSet<String> {
+        let mut ancestors = HashSet::new();
+        let mut pending = vec![tx];
+
+        while let Some(tx) = pending.pop() {
+            for input in &tx.inputs {
+                let parent_id = input.previous_output.split(':').next().unwrap_or_default();
+                if let Some(parent) = self.transactions.get(parent_id) {
+                    if ancestors.insert(parent_id.to_string()) {
+                        pending.push(&parent.transaction);
+                    }
+                }
+            }
+        }
+        ancestors
+    }
+
+    /// `tx_id` followed by every pooled transaction spending from it,
+    /// directly or indirectly
+    fn with_descendants(&self, tx_id: &str) -> Vec<String> {
+        let mut found = vec![tx_id.to_string()];
+        let mut next = 0;
+
+        while next < found.len() {
+            let parent = found[next].clone();
+            next += 1;
+            for entry in self.transactions.values() {
+                let spends_parent = entry.transaction.inputs
+                    .iter()
+                    .any(|i| i.previous_output.split(':').next() == Some(parent.as_str()));
+                if spends_parent && !found.contains(&entry.transaction.id) {
+                    found.push(entry.transaction.id.clone());
+                }
+            }
+        }
+        found
+    }
+
+    /// Modified fee rate of the package formed by `entry` and all of its
+    /// in-pool ancestors. Equals the entry's own rate when it has no pooled
+    /// parents.
+    pub fn ancestor_fee_rate(&self, entry: &MempoolEntry) -> f64 {
+        let (fee, size) = self.ancestors_of(&entry.transaction)
+            .iter()
+            .filter_map(|id| self.transactions.get(id))
+            .fold((self.modified_fee(entry), entry.size), |(fee, size), parent| {
+                (fee + self.modified_fee(parent), size + parent.size)
+            });
+
+        if size > 0 { fee as f64 / size as f64 } else { 0.0 }
+    }
+
+    pub fn priority_at(&self, entry: &MempoolEntry, now: DateTime<Utc>) -> PriorityScore {
+        PriorityScore::compute(self.modified_fee_rate(entry), self.ancestor_fee_rate(entry), now - entry.received_time)
+    }
+
+    pub fn priority(&self, tx_id: &str) -> Option<PriorityScore> {
+        self.transactions.get(tx_id).map(|entry| self.priority_at(entry, Utc::now()))
+    }
+
+    /// Pooled transactions in descending priority order
+    pub fn get_transactions_by_priority(&self, limit: usize) -> Vec<(&MempoolEntry, PriorityScore)> {
+        let now = Utc::now();
+        let mut entries: Vec<(&MempoolEntry, PriorityScore)> = self.transactions
+            .values()
+            .map(|entry| (entry, self.priority_at(entry, now)))
+            .collect();
+        entries.sort_by(|a, b| b.1.score.partial_cmp(&a.1.score).unwrap_or(std::cmp::Ordering::Equal));
         entries.into_iter().take(limit).collect()
     }
 
+    /// Local transactions not announced for a full `rebroadcast_secs`,
+    /// oldest first. They are marked as announced at `now`, so the caller
+    /// is expected to gossip every one returned.
+    pub fn due_for_rebroadcast(&mut self, now: DateTime<Utc>) -> Vec<SignedTransaction> {
+        let Some(interval) = self.policy.rebroadcast_secs.map(|secs| Duration::seconds(secs as i64)) else {
+            return Vec::new();
+        };
+
+        let mut due: Vec<&mut MempoolEntry> = self.transactions
+            .values_mut()
+            .filter(|entry| entry.local && entry.last_broadcast.is_none_or(|at| now - at >= interval))
+            .collect();
+        due.sort_by_key(|entry| entry.received_time);
+        due.into_iter()
+            .map(|entry| {
+                entry.last_broadcast = Some(now);
+                entry.transaction.clone()
+            })
+            .collect()
+    }
+
+    /// Evict transactions pooled for longer than the policy TTL
     pub fn cleanup_expired(&mut self) -> usize {
+        self.cleanup_expired_at(Utc::now())
+    }
+
+    pub fn cleanup_expired_at(&mut self, now: DateTime<Utc>) -> usize {
         let expired_keys: Vec<String> = self.transactions
             .iter()
-            .filter(|(_, entry)| entry.is_expired(self.max_transaction_age))
+            .filter(|(_, entry)| entry.is_expired_at(self.max_transaction_age, now))
             .map(|(key, _)| key.clone())
             .collect();
 
-        let count = expired_keys.len();
-        for key in expired_keys {
-            self.transactions.remove(&key);
+        expired_keys
+            .into_iter()
+            .map(|key| self.evict(&key, EvictionReason::Expired))
+            .sum()
+    }
+
+    /// Remove `tx_id` and every pooled transaction spending from it, since
+    /// their inputs no longer exist. Returns how many left the pool.
+    fn evict(&mut self, tx_id: &str, reason: EvictionReason) -> usize {
+        let mut evicted = 0;
+        for (i, id) in self.with_descendants(tx_id).into_iter().enumerate() {
+            if self.remove_transaction(&id).is_some() {
+                let reason = if i == 0 { reason } else { EvictionReason::ParentEvicted };
+                self.emit(MempoolEvent::TxEvicted { txid: id, reason });
+                evicted += 1;
+            }
         }
-        
-        count
+        evicted
     }
 
     pub fn size(&self) -> usize {
@@ -116,6 +733,9 @@ impl Mempool {
 
     pub fn clear(&mut self) {
         self.transactions.clear();
+        self.spent_by.clear();
+        self.fee_deltas.clear();
+        self.total_bytes = 0;
     }
 
     pub fn contains(&self, tx_id: &str) -> bool {
@@ -130,11 +750,8 @@ impl Mempool {
         let mut selected = Vec::new();
         let mut total_size = 0;
         
-        // Get transactions sorted by fee per byte (highest first)
-        let mut entries: Vec<&MempoolEntry> = self.transactions.values().collect();
-        entries.sort_by(|a, b| b.fee_per_byte.partial_cmp(&a.fee_per_byte).unwrap_or(std::cmp::Ordering::Equal));
-        
-        for entry in entries {
+        // Highest modified fee per byte first, so prioritised transactions move up
+        for entry in self.get_transactions_by_fee(self.transactions.len()) {
             if selected.len() >= max_count {
                 break;
             }
@@ -151,27 +768,39 @@ impl Mempool {
         selected
     }
 
-    fn evict_lowest_fee_transaction(&mut self) -> Result<()> {
-        if self.transactions.is_empty() {
-            return Err(anyhow!("Cannot evict from empty mempool"));
-        }
-
-        // Find transaction with lowest fee per byte
-        let lowest_fee_tx = self.transactions
-            .iter()
-            .min_by(|a, b| a.1.fee_per_byte.partial_cmp(&b.1.fee_per_byte).unwrap_or(std::cmp::Ordering::Equal))
-            .map(|(key, _)| key.clone());
+    /// Pooled transactions to evict, cheapest first, so `entry` fits both
+    /// the count and byte limits. Fails without evicting anything when
+    /// `entry` does not pay a higher fee rate than every one of them.
+    fn plan_evictions(&self, entry: &MempoolEntry) -> Result<Vec<String>> {
+        // Rank by fee per weight unit, so signature bytes do not count
+        // against a transaction as heavily as payload
+        let mut candidates: Vec<&MempoolEntry> = self.transactions.values().collect();
+        candidates.sort_by(|a, b| a.fee_per_weight().partial_cmp(&b.fee_per_weight()).unwrap_or(std::cmp::Ordering::Equal));
+        let mut candidates = candidates.into_iter();
 
-        if let Some(tx_id) = lowest_fee_tx {
-            self.transactions.remove(&tx_id);
+        let (mut count, mut bytes) = (self.transactions.len(), self.total_bytes);
+        let mut evictions = Vec::new();
+        while count >= self.policy.max_count || bytes + entry.size > self.policy.max_bytes {
+            let Some(candidate) = candidates.next() else {
+                return Err(anyhow!("Cannot evict from empty mempool"));
+            };
+            if entry.fee_per_weight() <= candidate.fee_per_weight() {
+                return Err(anyhow!(
+                    "Mempool full: fee rate {:.3} does not beat {:.3} of the cheapest pooled transaction",
+                    entry.fee_per_weight(),
+                    candidate.fee_per_weight()
+                ));
+            }
+            count -= 1;
+            bytes -= candidate.size;
+            evictions.push(candidate.transaction.id.clone());
         }
-
-        Ok(())
+        Ok(evictions)
     }
 
     pub fn estimate_fee_for_priority(&self, target_confirmations: u32) -> f64 {
         if self.transactions.is_empty() {
-            return self.min_fee_per_byte;
+            return self.policy.min_relay_fee;
         }
 
         let mut fees: Vec<f64> = self.transactions.values().map(|entry| entry.fee_per_byte).collect();
@@ -186,7 +815,29 @@ impl Mempool {
         };
 
         let index = ((fees.len() as f64 * position) as usize).min(fees.len().saturating_sub(1));
-        fees.get(index).copied().unwrap_or(self.min_fee_per_byte)
+        fees.get(index).copied().unwrap_or(self.policy.min_relay_fee)
+    }
+
+    /// Lowest fee per byte worth relaying right now. Once the pool is full a
+    /// new transaction only gets in by evicting the cheapest one, so the
+    /// floor rises to just above that transaction's rate.
+    pub fn relay_fee_floor(&self) -> f64 {
+        let average_size = self.total_bytes / self.transactions.len().max(1);
+        let full = self.transactions.len() >= self.policy.max_count
+            || self.total_bytes + average_size > self.policy.max_bytes;
+        if !full {
+            return self.policy.min_relay_fee;
+        }
+
+        let cheapest = self.transactions
+            .values()
+            .map(|entry| entry.fee_per_byte)
+            .fold(f64::INFINITY, f64::min);
+        if cheapest.is_finite() {
+            self.policy.min_relay_fee.max(cheapest + self.policy.incremental_relay_fee)
+        } else {
+            self.policy.min_relay_fee
+        }
     }
 
     pub fn get_mempool_stats(&self) -> MempoolStats {
@@ -212,6 +863,7 @@ impl Mempool {
 
         MempoolStats {
             transaction_count: self.transactions.len(),
+            total_bytes: self.total_bytes,
             avg_fee_per_byte: avg_fee,
             median_fee_per_byte: median_fee,
             min_fee_per_byte: sorted_fees.first().copied().unwrap_or(0.0),
@@ -223,6 +875,7 @@ impl Mempool {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MempoolStats {
     pub transaction_count: usize,
+    pub total_bytes: usize,
     pub avg_fee_per_byte: f64,
     pub median_fee_per_byte: f64,
     pub min_fee_per_byte: f64,
@@ -233,6 +886,7 @@ impl Default for MempoolStats {
     fn default() -> Self {
         Self {
             transaction_count: 0,
+            total_bytes: 0,
             avg_fee_per_byte: 0.0,
             median_fee_per_byte: 0.0,
             min_fee_per_byte: 0.0,
@@ -243,15 +897,15 @@ impl Default for MempoolStats {
 
 impl Default for Mempool {
     fn default() -> Self {
-        Self::new(10000) // Default to 10k transactions
+        Self::new(MempoolPolicy::default())
     }
 }
 
 // Thread-safe mempool wrapper
 pub type SharedMempool = Arc<RwLock<Mempool>>;
 
-pub fn create_shared_mempool(max_size: usize) -> SharedMempool {
-    Arc::new(RwLock::new(Mempool::new(max_size)))
+pub fn create_shared_mempool(policy: MempoolPolicy) -> SharedMempool {
+    Arc::new(RwLock::new(Mempool::new(policy)))
 }
 
 #[cfg(test)]
@@ -259,6 +913,38 @@ mod tests {
     use super::*;
     use crate::transaction::{TransactionInput, TransactionOutput};
 
+    fn relay_free_policy(max_count: usize) -> MempoolPolicy {
+        MempoolPolicy { max_count, min_relay_fee: 0.0, ..MempoolPolicy::default() }
+    }
+
+    /// Credit every input of `tx` the pool cannot value yet with what its
+    /// outputs pay, so it is added with a zero fee
+    fn fund(mempool: &mut Mempool, tx: &SignedTransaction) {
+        let value = tx.outputs.iter().map(|o| o.value).sum::<u64>() / tx.inputs.len() as u64;
+        for input in &tx.inputs {
+            if mempool.prevout_value(&input.previous_output).is_none() {
+                mempool.utxo_values.insert(input.previous_output.clone(), value);
+            }
+        }
+    }
+
+    fn add(mempool: &mut Mempool, tx: SignedTransaction) -> Result<()> {
+        fund(mempool, &tx);
+        mempool.add_transaction(tx)
+    }
+
+    /// Like `add`, but funding the single input of `tx` so it pays `fee`
+    fn add_paying(mempool: &mut Mempool, tx: SignedTransaction, fee: u64) -> Result<()> {
+        let value = tx.outputs.iter().map(|o| o.value).sum::<u64>() + fee;
+        mempool.utxo_values.insert(tx.inputs[0].previous_output.clone(), value);
+        mempool.add_transaction(tx)
+    }
+
+    fn add_local(mempool: &mut Mempool, tx: SignedTransaction) -> Result<()> {
+        fund(mempool, &tx);
+        mempool.add_local_transaction(tx)
+    }
+
     fn create_test_transaction(id: &str) -> SignedTransaction {
         SignedTransaction::new(
             vec![TransactionInput {
@@ -277,11 +963,11 @@ mod tests {
 
     #[test]
     fn test_mempool_add_remove() {
-        let mut mempool = Mempool::new(100);
+        let mut mempool = Mempool::new(MempoolPolicy { max_count: 100, ..MempoolPolicy::default() });
         let tx = create_test_transaction("test_tx_1");
         let tx_id = tx.id.clone();
 
-        assert!(mempool.add_transaction(tx).is_ok());
+        assert!(add(&mut mempool, tx).is_ok());
         assert_eq!(mempool.size(), 1);
         assert!(mempool.contains(&tx_id));
 
@@ -293,11 +979,11 @@ mod tests {
 
     #[test]
     fn test_mempool_cleanup_expired() {
-        let mut mempool = Mempool::new(100);
+        let mut mempool = Mempool::new(MempoolPolicy { max_count: 100, ..MempoolPolicy::default() });
         mempool.max_transaction_age = Duration::seconds(1);
         
         let tx = create_test_transaction("test_tx_2");
-        mempool.add_transaction(tx).unwrap();
+        add(&mut mempool, tx).unwrap();
         
         // Wait for expiration
         std::thread::sleep(std::time::Duration::from_secs(2));
@@ -306,4 +992,701 @@ mod tests {
         assert_eq!(expired_count, 1);
         assert_eq!(mempool.size(), 0);
     }
+
+    fn spending(previous_output: &str) -> SignedTransaction {
+        SignedTransaction::new(
+            vec![TransactionInput {
+                previous_output: previous_output.to_string(),
+                script_sig: vec![],
+                sequence: 0,
+            }],
+            vec![TransactionOutput {
+                value: 1000,
+                script_pubkey: vec![],
+                address: "test_address".to_string(),
+            }],
+            0,
+        )
+    }
+
+    #[test]
+    fn test_events_for_accept_and_evict() {
+        let mut mempool = Mempool::new(relay_free_policy(1));
+        let mut events = mempool.subscribe();
+
+        let first = spending("utxo_a");
+        let second = spending("utxo_b");
+        let (first_id, second_id) = (first.id.clone(), second.id.clone());
+
+        add(&mut mempool, first).unwrap();
+        match events.try_recv().unwrap() {
+            MempoolEvent::TxAccepted { txid, size, .. } => {
+                assert_eq!(txid, first_id);
+                assert_eq!(size, mempool.get_transaction(&first_id).unwrap().size);
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+
+        // Pool is full, so the better-paying second arrival evicts the first
+        add_paying(&mut mempool, second, 100).unwrap();
+        match events.try_recv().unwrap() {
+            MempoolEvent::TxEvicted { txid, reason } => {
+                assert_eq!(txid, first_id);
+                assert_eq!(reason, EvictionReason::LowFee);
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(matches!(events.try_recv().unwrap(), MempoolEvent::TxAccepted { txid, .. } if txid == second_id));
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_slow_subscriber_loses_oldest_events() {
+        let mut mempool = Mempool::new(relay_free_policy(MEMPOOL_EVENT_BUFFER * 2));
+        let mut events = mempool.subscribe();
+
+        for i in 0..MEMPOOL_EVENT_BUFFER + 10 {
+            add(&mut mempool, spending(&format!("utxo_{}", i))).unwrap();
+        }
+
+        assert!(matches!(events.try_recv(), Err(broadcast::error::TryRecvError::Lagged(10))));
+        let expected = spending("utxo_10").id;
+        assert!(matches!(events.try_recv().unwrap(), MempoolEvent::TxAccepted { txid, .. } if txid == expected));
+    }
+
+    fn signed(tx: SignedTransaction) -> SignedTransaction {
+        let (_, private_key) = crate::quantum_crypto::generate_keypair();
+        signed_by(tx, &private_key)
+    }
+
+    fn signed_by(mut tx: SignedTransaction, private_key: &str) -> SignedTransaction {
+        tx.sign(private_key).unwrap();
+        tx
+    }
+
+    /// Record `outpoint` as paying the address of a fresh key, returning
+    /// that key's private half
+    fn owned(mempool: &mut Mempool, outpoint: &str) -> String {
+        let (public_key, private_key) = crate::quantum_crypto::generate_keypair();
+        let owner = crate::quantum_crypto::public_key_to_address(&public_key);
+        mempool.utxo_owners.insert(outpoint.to_string(), owner);
+        private_key
+    }
+
+    fn spending_with_script(previous_output: &str, script_len: usize) -> SignedTransaction {
+        SignedTransaction::new(
+            vec![TransactionInput {
+                previous_output: previous_output.to_string(),
+                script_sig: vec![0u8; script_len],
+                sequence: 0,
+            }],
+            vec![TransactionOutput {
+                value: 1000,
+                script_pubkey: vec![],
+                address: "test_address".to_string(),
+            }],
+            0,
+        )
+    }
+
+    #[test]
+    fn test_byte_budget_evicts_before_count_limit() {
+        let mut mempool = Mempool::new(relay_free_policy(1000)).with_max_bytes(25_000);
+
+        for i in 0..3 {
+            add_paying(&mut mempool, spending_with_script(&format!("big_{}", i), 10_000), i * 1_000).unwrap();
+            assert!(mempool.total_bytes() <= mempool.max_bytes());
+        }
+
+        // Only two 10 KB transactions fit in 25 KB, far below the count limit
+        assert_eq!(mempool.size(), 2);
+        assert!(mempool.total_bytes() > 20_000);
+        assert_eq!(mempool.get_mempool_stats().total_bytes, mempool.total_bytes());
+    }
+
+    #[test]
+    fn test_full_pool_keeps_better_paying_transactions() {
+        let mut mempool = Mempool::new(relay_free_policy(1000)).with_max_bytes(25_000);
+        add_paying(&mut mempool, spending_with_script("big_0", 10_000), 5_000).unwrap();
+        add_paying(&mut mempool, spending_with_script("big_1", 10_000), 5_000).unwrap();
+        let mut events = mempool.subscribe();
+
+        // Paying no more than the cheapest pooled transaction evicts nothing
+        let err = add_paying(&mut mempool, spending_with_script("big_2", 10_000), 5_000).unwrap_err();
+        assert!(err.to_string().contains("Mempool full"));
+        assert_eq!(mempool.size(), 2);
+        assert!(events.try_recv().is_err());
+
+        add_paying(&mut mempool, spending_with_script("big_3", 10_000), 6_000).unwrap();
+        assert_eq!(mempool.size(), 2);
+        assert!(matches!(events.try_recv().unwrap(), MempoolEvent::TxEvicted { reason: EvictionReason::LowFee, .. }));
+    }
+
+    #[test]
+    fn test_byte_usage_tracks_removals() {
+        let mut mempool = Mempool::new(relay_free_policy(100));
+
+        let tx = spending_with_script("utxo", 500);
+        let id = tx.id.clone();
+        add(&mut mempool, tx).unwrap();
+        let size = mempool.get_transaction(&id).unwrap().size;
+        assert_eq!(mempool.total_bytes(), size);
+
+        mempool.remove_transaction(&id);
+        assert_eq!(mempool.total_bytes(), 0);
+    }
+
+    #[test]
+    fn test_transaction_larger_than_budget_rejected() {
+        let mut mempool = Mempool::new(relay_free_policy(100)).with_max_bytes(1_000);
+
+        assert!(add(&mut mempool, spending_with_script("huge", 5_000)).is_err());
+        assert_eq!(mempool.total_bytes(), 0);
+    }
+
+    #[test]
+    fn test_fee_derived_from_utxo_set() {
+        let mut utxos = UTXOSet::new();
+        let funding = TransactionOutput { value: 5_000, script_pubkey: vec![], address: "test_address".to_string() };
+        utxos.add_utxo(crate::utxo::UTXO::new("funding".to_string(), 0, &funding, 1, false)).unwrap();
+        let mut mempool = Mempool::new(relay_free_policy(100));
+        mempool.set_utxo_set(&utxos);
+
+        let parent = spending("funding:0");
+        let parent_id = parent.id.clone();
+        mempool.add_transaction(parent).unwrap();
+        assert_eq!(mempool.get_transaction(&parent_id).unwrap().fee, 4_000);
+
+        // A child is valued from its pooled parent's outputs
+        let mut child = spending(&format!("{}:0", parent_id));
+        child.outputs[0].value = 600;
+        let child_id = child.id.clone();
+        mempool.add_transaction(child).unwrap();
+        let entry = mempool.get_transaction(&child_id).unwrap();
+        assert_eq!(entry.fee, 400);
+        assert_eq!(entry.fee_per_byte, 400.0 / entry.size as f64);
+    }
+
+    #[test]
+    fn test_chained_spend_accepted_and_dropped_with_parent() {
+        let mut mempool = Mempool::new(relay_free_policy(100));
+        let parent = spending("confirmed:0");
+        let parent_id = parent.id.clone();
+        add(&mut mempool, parent).unwrap();
+        assert_eq!(mempool.unspent_value("confirmed:0"), None);
+
+        // Unconfirmed change is spendable, and only once
+        let change = format!("{}:0", parent_id);
+        assert_eq!(mempool.unspent_value(&change), Some(1000));
+        let child = spending(&change);
+        let child_id = child.id.clone();
+        mempool.add_transaction(child).unwrap();
+        assert_eq!(mempool.unspent_value(&change), None);
+        assert_eq!(mempool.spender(&change), Some(child_id.as_str()));
+
+        let mut events = mempool.subscribe();
+        mempool.transactions.get_mut(&parent_id).unwrap().received_time = Utc::now() - Duration::days(2);
+        assert_eq!(mempool.cleanup_expired(), 2);
+        assert!(!mempool.contains(&child_id));
+        for (txid, reason) in [(parent_id, EvictionReason::Expired), (child_id, EvictionReason::ParentEvicted)] {
+            match events.try_recv().unwrap() {
+                MempoolEvent::TxEvicted { txid: evicted, reason: why } => assert_eq!((evicted, why), (txid, reason)),
+                other => panic!("unexpected event {:?}", other),
+            }
+        }
+
+        // With the parent gone its change can't be valued
+        assert_eq!(mempool.unspent_value(&change), None);
+        assert!(mempool.add_transaction(spending(&change)).is_err());
+    }
+
+    #[test]
+    fn test_unvalued_inputs_rejected() {
+        let mut mempool = Mempool::new(relay_free_policy(100));
+        let err = mempool.add_transaction(spending("unknown:0")).unwrap_err();
+        assert!(err.to_string().contains("UTXO not found"));
+
+        // Outputs worth more than the inputs pay no fee, they are invalid
+        mempool.utxo_values.insert("small:0".to_string(), 999);
+        let err = mempool.add_local_transaction(spending("small:0")).unwrap_err();
+        assert!(err.to_string().contains("Insufficient funds"));
+        assert!(mempool.is_empty());
+    }
+
+    fn set_fee(mempool: &mut Mempool, tx_id: &str, fee: u64, age: Duration) {
+        let entry = mempool.transactions.get_mut(tx_id).unwrap();
+        entry.fee = fee;
+        entry.fee_per_byte = fee as f64 / entry.size as f64;
+        entry.received_time = Utc::now() - age;
+    }
+
+    #[test]
+    fn test_heavier_transaction_evicted_first_at_equal_fee() {
+        let mut mempool = Mempool::new(relay_free_policy(2));
+
+        // More bytes overall, but mostly discounted witness data
+        let witness_heavy = spending_with_script("utxo_a", 400);
+        let mut base_heavy = spending("utxo_b");
+        base_heavy.outputs[0].address = "b".repeat(200);
+        let (witness_id, base_id) = (witness_heavy.id.clone(), base_heavy.id.clone());
+        add(&mut mempool, witness_heavy).unwrap();
+        add(&mut mempool, base_heavy).unwrap();
+        set_fee(&mut mempool, &witness_id, 1_000, Duration::zero());
+        set_fee(&mut mempool, &base_id, 1_000, Duration::zero());
+
+        let witness_entry = mempool.get_transaction(&witness_id).unwrap();
+        let base_entry = mempool.get_transaction(&base_id).unwrap();
+        assert!(witness_entry.size > base_entry.size);
+        assert!(witness_entry.weight < base_entry.weight);
+
+        add_paying(&mut mempool, spending("utxo_c"), 5_000).unwrap();
+        assert!(mempool.get_transaction(&base_id).is_none());
+        assert!(mempool.get_transaction(&witness_id).is_some());
+    }
+
+    #[test]
+    fn test_relay_fee_floor_rises_when_full() {
+        let policy = MempoolPolicy { max_count: 3, min_relay_fee: 0.0, incremental_relay_fee: 0.5, ..MempoolPolicy::default() };
+        let mut mempool = Mempool::new(policy);
+        let ids: Vec<String> = (0..3)
+            .map(|i| {
+                let tx = spending(&format!("utxo_{}", i));
+                let id = tx.id.clone();
+                add(&mut mempool, tx).unwrap();
+                id
+            })
+            .collect();
+
+        set_fee(&mut mempool, &ids[0], 4_000, Duration::zero());
+        set_fee(&mut mempool, &ids[1], 8_000, Duration::zero());
+        set_fee(&mut mempool, &ids[2], 12_000, Duration::zero());
+        let cheapest = mempool.get_transaction(&ids[0]).unwrap().fee_per_byte;
+        assert!(cheapest > 0.0);
+
+        // Full: relaying needs to beat the cheapest pooled transaction
+        assert!((mempool.relay_fee_floor() - (cheapest + 0.5)).abs() < 1e-9);
+
+        mempool.remove_transaction(&ids[2]);
+        assert_eq!(mempool.relay_fee_floor(), 0.0);
+    }
+
+    #[test]
+    fn test_high_fee_young_beats_low_fee_old() {
+        let mut mempool = Mempool::new(relay_free_policy(100));
+
+        let young = spending("utxo_young");
+        let old = spending("utxo_old");
+        let (young_id, old_id) = (young.id.clone(), old.id.clone());
+        add(&mut mempool, young).unwrap();
+        add(&mut mempool, old).unwrap();
+        set_fee(&mut mempool, &young_id, 10_000, Duration::seconds(5));
+        set_fee(&mut mempool, &old_id, 1_000, Duration::hours(48));
+
+        let young_score = mempool.priority(&young_id).unwrap();
+        let old_score = mempool.priority(&old_id).unwrap();
+        assert!(young_score.score > old_score.score);
+        // Waiting the full ramp doubles the old transaction's rate, no more
+        assert!((old_score.score - 2.0 * old_score.fee_rate).abs() < 1e-9);
+
+        let order: Vec<&str> = mempool.get_transactions_by_priority(10)
+            .iter()
+            .map(|(entry, _)| entry.transaction.id.as_str())
+            .collect();
+        assert_eq!(order, vec![young_id.as_str(), old_id.as_str()]);
+    }
+
+    #[test]
+    fn test_age_breaks_ties_between_similar_fees() {
+        let mut mempool = Mempool::new(relay_free_policy(100));
+
+        let fresh = spending("utxo_fresh");
+        let waiting = spending("utxo_waiting");
+        let (fresh_id, waiting_id) = (fresh.id.clone(), waiting.id.clone());
+        add(&mut mempool, fresh).unwrap();
+        add(&mut mempool, waiting).unwrap();
+        set_fee(&mut mempool, &fresh_id, 1_100, Duration::zero());
+        set_fee(&mut mempool, &waiting_id, 1_000, Duration::hours(6));
+
+        assert!(mempool.priority(&waiting_id).unwrap().score > mempool.priority(&fresh_id).unwrap().score);
+    }
+
+    #[test]
+    fn test_low_fee_parent_caps_child_priority() {
+        let mut mempool = Mempool::new(relay_free_policy(100));
+
+        let parent = spending("utxo_parent");
+        let parent_id = parent.id.clone();
+        let child = spending(&format!("{}:0", parent_id));
+        let child_id = child.id.clone();
+        add(&mut mempool, parent).unwrap();
+        add(&mut mempool, child).unwrap();
+        set_fee(&mut mempool, &parent_id, 0, Duration::zero());
+        set_fee(&mut mempool, &child_id, 10_000, Duration::zero());
+
+        let child_score = mempool.priority(&child_id).unwrap();
+        assert!(child_score.ancestor_fee_rate < child_score.fee_rate);
+        assert!((child_score.score - child_score.ancestor_fee_rate).abs() < 1e-9);
+
+        // Without pooled parents the ancestor rate is the transaction's own
+        let parent_score = mempool.priority(&parent_id).unwrap();
+        assert_eq!(parent_score.ancestor_fee_rate, parent_score.fee_rate);
+    }
+
+    #[test]
+    fn test_prioritised_transaction_mined_first() {
+        let mut mempool = Mempool::new(relay_free_policy(100));
+
+        let cheap = spending("utxo_cheap");
+        let rich = spending("utxo_rich");
+        let (cheap_id, rich_id) = (cheap.id.clone(), rich.id.clone());
+        add(&mut mempool, cheap).unwrap();
+        add(&mut mempool, rich).unwrap();
+        set_fee(&mut mempool, &cheap_id, 1_000, Duration::zero());
+        set_fee(&mut mempool, &rich_id, 5_000, Duration::zero());
+
+        let mined = |mempool: &Mempool| -> Vec<String> {
+            mempool.get_transactions_for_mining(10, usize::MAX).into_iter().map(|tx| tx.id).collect()
+        };
+        assert_eq!(mined(&mempool), vec![rich_id.clone(), cheap_id.clone()]);
+
+        assert_eq!(mempool.prioritise_transaction(&cheap_id, 3_000).unwrap(), 3_000);
+        assert_eq!(mempool.prioritise_transaction(&cheap_id, 2_000).unwrap(), 5_000);
+        assert_eq!(mined(&mempool), vec![cheap_id.clone(), rich_id.clone()]);
+        assert_eq!(mempool.get_transactions_by_priority(1)[0].0.transaction.id, cheap_id);
+        // Only the ordering changes, not the fee actually paid
+        assert_eq!(mempool.get_transaction(&cheap_id).unwrap().fee, 1_000);
+
+        // A negative delta pushes a transaction down instead
+        mempool.prioritise_transaction(&rich_id, -5_000).unwrap();
+        assert_eq!(mined(&mempool).last(), Some(&rich_id));
+
+        assert!(mempool.prioritise_transaction("unknown", 1_000).is_err());
+        mempool.remove_transaction(&cheap_id);
+        assert_eq!(mempool.fee_delta(&cheap_id), 0);
+    }
+
+    /// parent <- child <- grandchild, each spending output 0 of the previous
+    fn chain(len: usize) -> Vec<SignedTransaction> {
+        let mut txs = vec![spending("confirmed_utxo")];
+        while txs.len() < len {
+            let prev = format!("{}:0", txs.last().unwrap().id);
+            txs.push(spending(&prev));
+        }
+        txs
+    }
+
+    #[test]
+    fn test_strict_ancestor_limit_rejects_package() {
+        let mut mempool = Mempool::new(MempoolPolicy { max_ancestors: 2, ..relay_free_policy(100) });
+        let txs = chain(3);
+
+        add(&mut mempool, txs[0].clone()).unwrap();
+        add(&mut mempool, txs[1].clone()).unwrap();
+        let err = add(&mut mempool, txs[2].clone()).unwrap_err();
+        assert!(err.to_string().contains("ancestors"));
+        assert_eq!(mempool.size(), 2);
+    }
+
+    #[test]
+    fn test_permissive_policy_accepts_package() {
+        let mut mempool = Mempool::new(relay_free_policy(100));
+        for tx in chain(3) {
+            add(&mut mempool, tx).unwrap();
+        }
+        assert_eq!(mempool.size(), 3);
+    }
+
+    #[test]
+    fn test_descendant_limit_counts_siblings() {
+        let mut mempool = Mempool::new(MempoolPolicy { max_descendants: 2, ..relay_free_policy(100) });
+        let parent = spending("confirmed_utxo");
+        let parent_id = parent.id.clone();
+        add(&mut mempool, parent).unwrap();
+
+        add(&mut mempool, spending(&format!("{}:0", parent_id))).unwrap();
+        assert!(add(&mut mempool, spending(&format!("{}:1", parent_id))).is_err());
+    }
+
+    #[test]
+    fn test_conflict_rejected_without_rbf() {
+        let mut mempool = Mempool::new(MempoolPolicy { rbf_enabled: false, ..relay_free_policy(100) });
+        add(&mut mempool, spending("utxo")).unwrap();
+
+        let double_spend = spending_with_script("utxo", 10);
+        let err = add(&mut mempool, double_spend).unwrap_err();
+        assert!(err.to_string().contains("conflicts"));
+    }
+
+    #[test]
+    fn test_conflict_raises_double_spend_alert() {
+        let mut mempool = Mempool::new(MempoolPolicy { rbf_enabled: false, ..relay_free_policy(100) });
+        let owner_key = owned(&mut mempool, "utxo");
+        let original = spending("utxo");
+        let original_id = original.id.clone();
+        add(&mut mempool, original).unwrap();
+        let mut events = mempool.subscribe();
+
+        // An unsigned conflict could be forged by anyone, so raises nothing
+        assert!(add(&mut mempool, spending_with_script("utxo", 20)).is_err());
+        assert!(events.try_recv().is_err());
+
+        // Neither does one validly signed by a key that does not own the output
+        assert!(add(&mut mempool, signed(spending_with_script("utxo", 30))).is_err());
+        assert!(events.try_recv().is_err());
+
+        // The rejected conflict is reported, not silently dropped
+        let double_spend = signed_by(spending_with_script("utxo", 10), &owner_key);
+        let double_spend_id = double_spend.id.clone();
+        assert!(add(&mut mempool, double_spend.clone()).is_err());
+        match events.try_recv().unwrap() {
+            MempoolEvent::DoubleSpend(alert) => {
+                assert_eq!(alert, DoubleSpendAlert { original: original_id.clone(), conflicting: double_spend_id });
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(events.try_recv().is_err());
+
+        // Resending the same conflict is not alerted on again
+        assert!(add(&mut mempool, double_spend).is_err());
+        assert!(events.try_recv().is_err());
+
+        // Unrelated spends raise nothing
+        add(&mut mempool, spending("other_utxo")).unwrap();
+        assert!(matches!(events.try_recv().unwrap(), MempoolEvent::TxAccepted { .. }));
+
+        let mut quiet = Mempool::new(MempoolPolicy { double_spend_alerts: false, rbf_enabled: false, ..relay_free_policy(100) });
+        let owner_key = owned(&mut quiet, "utxo");
+        add(&mut quiet, spending("utxo")).unwrap();
+        let mut events = quiet.subscribe();
+        assert!(add(&mut quiet, signed_by(spending_with_script("utxo", 10), &owner_key)).is_err());
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_double_spend_alerts_are_bounded_per_transaction() {
+        let mut mempool = Mempool::new(MempoolPolicy { rbf_enabled: false, ..relay_free_policy(100) });
+        let owner_key = owned(&mut mempool, "utxo");
+        let original_id = spending("utxo").id;
+        add(&mut mempool, spending("utxo")).unwrap();
+        let mut events = mempool.subscribe();
+
+        for script_len in 1..=MAX_ALERTS_PER_TRANSACTION + 2 {
+            assert!(add(&mut mempool, signed_by(spending_with_script("utxo", script_len), &owner_key)).is_err());
+        }
+        for _ in 0..MAX_ALERTS_PER_TRANSACTION {
+            assert!(matches!(events.try_recv().unwrap(), MempoolEvent::DoubleSpend(_)));
+        }
+        assert!(events.try_recv().is_err());
+        assert_eq!(mempool.alerted[&original_id].len(), MAX_ALERTS_PER_TRANSACTION);
+
+        // The record goes with the original
+        mempool.remove_transaction(&original_id);
+        assert!(mempool.alerted.is_empty());
+    }
+
+    #[test]
+    fn test_replacement_must_outbid_incremental_fee() {
+        let mut mempool = Mempool::new(MempoolPolicy { incremental_relay_fee: 10.0, ..relay_free_policy(100) });
+        let original = spending("utxo");
+        let original_id = original.id.clone();
+        add(&mut mempool, original).unwrap();
+        set_fee(&mut mempool, &original_id, 1_000, Duration::zero());
+
+        let mut replacement = MempoolEntry::new(spending_with_script("utxo", 10), 0);
+        let conflicts = mempool.conflicts_with(&replacement.transaction);
+        assert_eq!(conflicts, vec![original_id.clone()]);
+
+        // Higher rate but not enough extra to cover the incremental relay fee
+        let increment = (mempool.policy().incremental_relay_fee * replacement.size as f64).ceil() as u64;
+        replacement.fee = 1_000 + increment - 1;
+        replacement.fee_per_byte = replacement.fee as f64 / replacement.size as f64;
+        assert!(mempool.check_replacement(&replacement, &conflicts).is_err());
+
+        replacement.fee = 1_000 + increment;
+        replacement.fee_per_byte = replacement.fee as f64 / replacement.size as f64;
+        assert!(mempool.check_replacement(&replacement, &conflicts).is_ok());
+    }
+
+    #[test]
+    fn test_only_signaling_transactions_replaceable() {
+        let mut mempool = Mempool::new(relay_free_policy(100));
+        let final_spend = |previous_output: &str| {
+            let mut tx = spending(previous_output);
+            tx.inputs[0].sequence = u32::MAX;
+            SignedTransaction::new(tx.inputs, tx.outputs, 0)
+        };
+        let outbid = |mempool: &Mempool, previous_output: &str| {
+            let mut replacement = MempoolEntry::new(spending_with_script(previous_output, 10), 0);
+            replacement.fee = 10_000;
+            replacement.fee_per_byte = replacement.fee as f64 / replacement.size as f64;
+            let conflicts = mempool.conflicts_with(&replacement.transaction);
+            assert_eq!(conflicts.len(), 1);
+            mempool.check_replacement(&replacement, &conflicts)
+        };
+
+        let signaling = spending("signaling");
+        assert!(signaling.signals_rbf());
+        add(&mut mempool, signaling).unwrap();
+        assert!(outbid(&mempool, "signaling").is_ok());
+
+        let non_signaling = final_spend("final");
+        assert!(!non_signaling.signals_rbf());
+        add(&mut mempool, non_signaling).unwrap();
+        let err = outbid(&mempool, "final").unwrap_err();
+        assert!(err.to_string().contains("does not signal"));
+
+        // A non-signaling child inherits replaceability from a signaling parent
+        let parent = spending("parent_utxo");
+        let parent_id = parent.id.clone();
+        add(&mut mempool, parent).unwrap();
+        add(&mut mempool, final_spend(&format!("{}:0", parent_id))).unwrap();
+        assert!(outbid(&mempool, &format!("{}:0", parent_id)).is_ok());
+
+        mempool.policy.full_rbf = true;
+        assert!(outbid(&mempool, "final").is_ok());
+    }
+
+    /// Every pooled input maps to its transaction and nothing else is indexed
+    fn assert_spent_index_consistent(mempool: &Mempool) {
+        let mut inputs = 0;
+        for entry in mempool.transactions.values() {
+            for input in &entry.transaction.inputs {
+                assert_eq!(mempool.spender(&input.previous_output), Some(entry.transaction.id.as_str()));
+                inputs += 1;
+            }
+        }
+        assert_eq!(mempool.spent_by.len(), inputs);
+    }
+
+    #[test]
+    fn test_conflict_found_through_spent_index() {
+        let mut mempool = Mempool::new(relay_free_policy(1_000));
+        for i in 0..200 {
+            add(&mut mempool, spending(&format!("unrelated_{}", i))).unwrap();
+        }
+        let original = spending("utxo");
+        let original_id = original.id.clone();
+        add(&mut mempool, original).unwrap();
+
+        let double_spend = spending_with_script("utxo", 10);
+        assert_eq!(mempool.conflicts_with(&double_spend), vec![original_id.clone()]);
+        assert_eq!(mempool.spender("utxo"), Some(original_id.as_str()));
+        assert_eq!(mempool.spender("unspent"), None);
+        assert!(mempool.conflicts_with(&spending("unspent")).is_empty());
+        assert_spent_index_consistent(&mempool);
+    }
+
+    #[test]
+    fn test_spent_index_follows_eviction_and_replacement() {
+        let mut mempool = Mempool::new(relay_free_policy(3));
+        let original = spending("utxo");
+        let original_id = original.id.clone();
+        let child = spending(&format!("{}:0", original_id));
+        let child_id = child.id.clone();
+        add(&mut mempool, original).unwrap();
+        add(&mut mempool, child).unwrap();
+        set_fee(&mut mempool, &original_id, 1_000, Duration::zero());
+        set_fee(&mut mempool, &child_id, 1_000, Duration::zero());
+
+        // The pool is full, so the zero-fee filler is evicted and unindexed
+        add(&mut mempool, spending("filler")).unwrap();
+        add_paying(&mut mempool, spending("filler_2"), 100).unwrap();
+        assert_eq!(mempool.size(), 3);
+        assert_eq!(mempool.spender("filler"), None);
+        assert_spent_index_consistent(&mempool);
+
+        // Replacing the parent evicts its child along with it
+        let mut replacement = MempoolEntry::new(spending_with_script("utxo", 10), 0);
+        replacement.fee = 10_000;
+        replacement.fee_per_byte = replacement.fee as f64 / replacement.size as f64;
+        let replacement_id = replacement.transaction.id.clone();
+        mempool.add_entry(replacement).unwrap();
+        assert!(!mempool.contains(&original_id) && !mempool.contains(&child_id));
+        assert_eq!(mempool.spender("utxo"), Some(replacement_id.as_str()));
+        assert_eq!(mempool.spender(&format!("{}:0", original_id)), None);
+        assert_spent_index_consistent(&mempool);
+
+        mempool.remove_transaction(&replacement_id);
+        assert_eq!(mempool.spender("utxo"), None);
+        assert_spent_index_consistent(&mempool);
+
+        mempool.clear();
+        assert!(mempool.spent_by.is_empty());
+    }
+
+    #[test]
+    fn test_stale_transaction_evicted_after_ttl() {
+        let mut mempool = Mempool::new(MempoolPolicy { ttl_secs: 600, ..relay_free_policy(100) });
+        let mut events = mempool.subscribe();
+        let stale = spending("ttl:stale");
+        let fresh = spending("ttl:fresh");
+        let (stale_id, fresh_id) = (stale.id.clone(), fresh.id.clone());
+        add(&mut mempool, stale).unwrap();
+        add(&mut mempool, fresh).unwrap();
+        mempool.transactions.get_mut(&stale_id).unwrap().received_time = Utc::now() - Duration::seconds(900);
+        events.try_recv().unwrap();
+        events.try_recv().unwrap();
+
+        assert_eq!(mempool.cleanup_expired_at(Utc::now()), 1);
+        assert!(!mempool.contains(&stale_id));
+        assert!(mempool.contains(&fresh_id));
+        assert_eq!(mempool.spender("ttl:stale"), None);
+        match events.try_recv().unwrap() {
+            MempoolEvent::TxEvicted { txid, reason } => {
+                assert_eq!(txid, stale_id);
+                assert_eq!(reason, EvictionReason::Expired);
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+
+        // The fresh one goes once it too outlives the TTL
+        assert_eq!(mempool.cleanup_expired_at(Utc::now() + Duration::seconds(601)), 1);
+        assert!(mempool.is_empty());
+    }
+
+    #[test]
+    fn test_local_transaction_rebroadcast() {
+        let mut mempool = Mempool::new(MempoolPolicy { rebroadcast_secs: Some(60), ..relay_free_policy(100) });
+        let local = spending("rebroadcast:local");
+        let local_id = local.id.clone();
+        add_local(&mut mempool, local).unwrap();
+        add(&mut mempool, spending("rebroadcast:relayed")).unwrap();
+        let start = mempool.get_transaction(&local_id).unwrap().received_time;
+
+        // Just announced on submission
+        assert!(mempool.due_for_rebroadcast(start + Duration::seconds(30)).is_empty());
+
+        // Only the local one is re-gossiped, once per interval
+        let due = mempool.due_for_rebroadcast(start + Duration::seconds(60));
+        assert_eq!(due.iter().map(|tx| tx.id.as_str()).collect::<Vec<_>>(), vec![local_id.as_str()]);
+        assert!(mempool.due_for_rebroadcast(start + Duration::seconds(90)).is_empty());
+        assert_eq!(mempool.due_for_rebroadcast(start + Duration::seconds(120)).len(), 1);
+
+        // Confirmed (removed) transactions are no longer announced
+        mempool.remove_transaction(&local_id);
+        assert!(mempool.due_for_rebroadcast(start + Duration::seconds(600)).is_empty());
+    }
+
+    #[test]
+    fn test_rebroadcast_disabled_by_default() {
+        let mut mempool = Mempool::new(relay_free_policy(100));
+        add_local(&mut mempool, spending("rebroadcast:off")).unwrap();
+        assert!(mempool.due_for_rebroadcast(Utc::now() + Duration::days(1)).is_empty());
+    }
+
+    #[test]
+    fn test_policy_from_config() {
+        let policy = MempoolPolicy::from_toml(
+            "[mempool]\nmax_ancestors = 5\nrbf_enabled = false\nttl_secs = 3600\nrebroadcast_secs = 900\n",
+        ).unwrap();
+        assert_eq!(policy.max_ancestors, 5);
+        assert!(!policy.rbf_enabled);
+        assert_eq!(policy.ttl_secs, 3600);
+        assert_eq!(policy.rebroadcast_secs, Some(900));
+        assert_eq!(Mempool::new(policy.clone()).max_transaction_age, Duration::hours(1));
+        assert_eq!(policy.max_descendants, MempoolPolicy::default().max_descendants);
+
+        // Configs without the section get the defaults
+        assert_eq!(MempoolPolicy::from_toml("[p2p]\nmax_peers = 8\n").unwrap(), MempoolPolicy::default());
+    }
 }
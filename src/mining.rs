@@ -2,13 +2,120 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use chrono::Utc;
 use anyhow::{Result, anyhow};
+use serde::{Serialize, Deserialize};
 use tracing::{info, warn, error};
 
 use crate::blockchain::{Blockchain, Block};
 use crate::mempool::Mempool;
 use crate::revstop::RevStop;
 use crate::transaction::{Transaction, SignedTransaction, TransactionOutput, TransactionInput};
-use crate::block::DetailedBlock;
+use crate::block::{DetailedBlock, MAX_BLOCK_SIZE};
+
+/// Orphan rate above which mining pauses
+pub const DEFAULT_MAX_ORPHAN_RATE: f64 = 0.10;
+/// Orphan rate the network must fall back to before mining resumes
+pub const DEFAULT_RESUME_ORPHAN_RATE: f64 = 0.05;
+
+/// Default bytes of transactions a mined block is filled up to
+pub const DEFAULT_BLOCK_SIZE_TARGET: usize = 500_000;
+
+/// Block assembly settings, normally read from the `[mining]` section of the
+/// node config. They only shape the blocks this node mines; what other
+/// nodes accept is still decided by the consensus limits.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MiningConfig {
+    /// Stop adding transactions once they would take a block past this many
+    /// bytes, e.g. to keep blocks small for faster propagation. Never
+    /// exceeds the consensus maximum.
+    pub block_size_target: usize,
+}
+
+impl Default for MiningConfig {
+    fn default() -> Self {
+        Self { block_size_target: DEFAULT_BLOCK_SIZE_TARGET }
+    }
+}
+
+impl MiningConfig {
+    /// Read the `[mining]` section of a TOML config; the section is optional
+    pub fn from_toml(config: &str) -> Result<Self> {
+        #[derive(Deserialize)]
+        struct Config {
+            #[serde(default)]
+            mining: MiningConfig,
+        }
+        Ok(toml::from_str::<Config>(config)?.mining)
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Self> {
+        let config = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        Self::from_toml(&config)
+    }
+}
+
+/// Pauses mining while the network's orphan rate suggests a split.
+///
+/// Separate trip and resume thresholds keep a rate hovering around the limit
+/// from toggling the miner on every update.
+#[derive(Debug, Clone)]
+pub struct OrphanRateBreaker {
+    pub max_orphan_rate: f64,
+    pub resume_orphan_rate: f64,
+    orphan_rate: f64,
+    tripped: bool,
+}
+
+impl OrphanRateBreaker {
+    pub fn new(max_orphan_rate: f64, resume_orphan_rate: f64) -> Self {
+        Self {
+            max_orphan_rate,
+            resume_orphan_rate: resume_orphan_rate.min(max_orphan_rate),
+            orphan_rate: 0.0,
+            tripped: false,
+        }
+    }
+
+    /// Record a new orphan rate sample; returns true if the breaker changed state
+    pub fn update(&mut self, orphan_rate: f64) -> bool {
+        self.orphan_rate = orphan_rate;
+        let was_tripped = self.tripped;
+        if orphan_rate > self.max_orphan_rate {
+            self.tripped = true;
+        } else if orphan_rate <= self.resume_orphan_rate {
+            self.tripped = false;
+        }
+        self.tripped != was_tripped
+    }
+
+    pub fn is_tripped(&self) -> bool {
+        self.tripped
+    }
+}
+
+impl Default for OrphanRateBreaker {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_ORPHAN_RATE, DEFAULT_RESUME_ORPHAN_RATE)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MiningState {
+    Stopped,
+    Mining,
+    /// Miner is running but holding off because the orphan rate is too high
+    PausedHighOrphanRate,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MiningStatus {
+    pub state: MiningState,
+    pub orphan_rate: f64,
+    pub max_orphan_rate: f64,
+    pub resume_orphan_rate: f64,
+}
 
 pub struct Miner {
     mining_address: String,
@@ -18,6 +125,8 @@ pub struct Miner {
     is_mining: Arc<RwLock<bool>>,
     max_transactions_per_block: usize,
     max_block_size: usize,
+    block_size_target: usize,
+    orphan_breaker: Arc<RwLock<OrphanRateBreaker>>,
 }
 
 impl Miner {
@@ -34,7 +143,56 @@ impl Miner {
             revstop,
             is_mining: Arc::new(RwLock::new(false)),
             max_transactions_per_block: 1000,
-            max_block_size: 1_000_000, // 1MB
+            max_block_size: MAX_BLOCK_SIZE,
+            block_size_target: DEFAULT_BLOCK_SIZE_TARGET,
+            orphan_breaker: Arc::new(RwLock::new(OrphanRateBreaker::default())),
+        }
+    }
+
+    /// Override the orphan rate thresholds that pause and resume mining
+    pub fn with_orphan_rate_limits(self, max_orphan_rate: f64, resume_orphan_rate: f64) -> Self {
+        Self {
+            orphan_breaker: Arc::new(RwLock::new(OrphanRateBreaker::new(max_orphan_rate, resume_orphan_rate))),
+            ..self
+        }
+    }
+
+    /// Apply the block assembly settings from the node config
+    pub fn with_config(self, config: &MiningConfig) -> Self {
+        Self { block_size_target: config.block_size_target, ..self }
+    }
+
+    /// Feed the latest orphan rate, e.g. from `NetworkMetrics::orphan_rate`
+    pub async fn update_orphan_rate(&self, orphan_rate: f64) {
+        let mut breaker = self.orphan_breaker.write().await;
+        if breaker.update(orphan_rate) {
+            if breaker.is_tripped() {
+                warn!(
+                    "Orphan rate {:.1}% exceeds {:.1}%, pausing mining",
+                    orphan_rate * 100.0,
+                    breaker.max_orphan_rate * 100.0
+                );
+            } else {
+                info!("Orphan rate back to {:.1}%, resuming mining", orphan_rate * 100.0);
+            }
+        }
+    }
+
+    pub async fn mining_status(&self) -> MiningStatus {
+        let breaker = self.orphan_breaker.read().await;
+        let state = if !self.is_mining().await {
+            MiningState::Stopped
+        } else if breaker.is_tripped() {
+            MiningState::PausedHighOrphanRate
+        } else {
+            MiningState::Mining
+        };
+
+        MiningStatus {
+            state,
+            orphan_rate: breaker.orphan_rate,
+            max_orphan_rate: breaker.max_orphan_rate,
+            resume_orphan_rate: breaker.resume_orphan_rate,
         }
     }
 
@@ -58,6 +216,11 @@ impl Miner {
                 break;
             }
 
+            if self.orphan_breaker.read().await.is_tripped() {
+                tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+                continue;
+            }
+
             match self.mine_next_block().await {
                 Ok(block) => {
                     info!(
@@ -132,10 +295,11 @@ impl Miner {
     async fn select_transactions_for_block(&self) -> Result<Vec<SignedTransaction>> {
         let mempool = self.mempool.read().await;
         
-        // Get transactions sorted by fee (highest first)
+        // Get transactions sorted by fee (highest first), filling up to the
+        // soft size target rather than the consensus maximum
         let selected_transactions = mempool.get_transactions_for_mining(
             self.max_transactions_per_block - 1, // Reserve space for coinbase
-            self.max_block_size / 2, // Reserve space for other data
+            self.block_size_target.min(self.max_block_size),
         );
 
         info!(
@@ -432,7 +596,9 @@ pub struct MiningStats {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::block::BlockValidator;
     use crate::revstop::RevStop;
+    use crate::utxo::{UTXOSet, UTXO};
 
     #[tokio::test]
     async fn test_miner_creation() {
@@ -451,6 +617,137 @@ mod tests {
         assert!(!miner.is_mining().await);
     }
 
+    fn test_miner() -> Miner {
+        Miner::new(
+            "miner_address".to_string(),
+            Arc::new(RwLock::new(Blockchain::new())),
+            Arc::new(RwLock::new(Mempool::default())),
+            Arc::new(RwLock::new(RevStop::new())),
+        )
+        .with_orphan_rate_limits(0.10, 0.05)
+    }
+
+    #[tokio::test]
+    async fn test_high_orphan_rate_pauses_mining() {
+        let miner = test_miner();
+        *miner.is_mining.write().await = true;
+        assert_eq!(miner.mining_status().await.state, MiningState::Mining);
+
+        miner.update_orphan_rate(0.25).await;
+        let status = miner.mining_status().await;
+        assert_eq!(status.state, MiningState::PausedHighOrphanRate);
+        assert_eq!(status.orphan_rate, 0.25);
+    }
+
+    #[tokio::test]
+    async fn test_normal_orphan_rate_resumes_mining() {
+        let miner = test_miner();
+        *miner.is_mining.write().await = true;
+        miner.update_orphan_rate(0.25).await;
+
+        // Still above the resume threshold, so stay paused
+        miner.update_orphan_rate(0.08).await;
+        assert_eq!(miner.mining_status().await.state, MiningState::PausedHighOrphanRate);
+
+        miner.update_orphan_rate(0.02).await;
+        assert_eq!(miner.mining_status().await.state, MiningState::Mining);
+
+        miner.stop_mining().await;
+        assert_eq!(miner.mining_status().await.state, MiningState::Stopped);
+    }
+
+    #[test]
+    fn test_breaker_reports_transitions() {
+        let mut breaker = OrphanRateBreaker::new(0.10, 0.05);
+        assert!(!breaker.update(0.10));
+        assert!(breaker.update(0.11));
+        assert!(!breaker.update(0.30));
+        assert!(breaker.update(0.05));
+        assert!(!breaker.is_tripped());
+    }
+
+    /// `count` pooled transactions, each paying a fee and padded with a
+    /// `script_len` byte signature script
+    fn fee_paying_mempool(count: usize, script_len: usize) -> Mempool {
+        let mut utxos = UTXOSet::new();
+        let output = |value: u64| TransactionOutput { value, script_pubkey: vec![], address: "payee".to_string() };
+        let txs: Vec<SignedTransaction> = (0..count)
+            .map(|i| {
+                utxos.add_utxo(UTXO::new(format!("funding_{}", i), 0, &output(100_000), 1, false)).unwrap();
+                SignedTransaction::new(
+                    vec![TransactionInput {
+                        previous_output: format!("funding_{}:0", i),
+                        script_sig: vec![0u8; script_len],
+                        sequence: 0,
+                    }],
+                    vec![output(50_000)],
+                    0,
+                )
+            })
+            .collect();
+
+        let mut mempool = Mempool::default();
+        mempool.set_utxo_set(&utxos);
+        for tx in txs {
+            mempool.add_transaction(tx).unwrap();
+        }
+        mempool
+    }
+
+    fn miner_with_pool(mempool: Mempool, config: &MiningConfig) -> Miner {
+        Miner::new(
+            "miner_address".to_string(),
+            Arc::new(RwLock::new(Blockchain::new())),
+            Arc::new(RwLock::new(mempool)),
+            Arc::new(RwLock::new(RevStop::new())),
+        )
+        .with_config(config)
+    }
+
+    #[tokio::test]
+    async fn test_assembly_stops_at_size_target() {
+        let config = MiningConfig { block_size_target: 5_000 };
+        let selected = miner_with_pool(fee_paying_mempool(10, 1_000), &config)
+            .select_transactions_for_block()
+            .await
+            .unwrap();
+
+        // Every pooled transaction pays a fee, but only the target's worth is taken
+        let bytes: usize = selected.iter().map(|tx| bincode::serialize(tx).unwrap().len()).sum();
+        assert!(!selected.is_empty() && selected.len() < 10);
+        assert!(bytes <= config.block_size_target);
+
+        let all = miner_with_pool(fee_paying_mempool(10, 1_000), &MiningConfig::default())
+            .select_transactions_for_block()
+            .await
+            .unwrap();
+        assert_eq!(all.len(), 10);
+    }
+
+    #[test]
+    fn test_validation_ignores_size_target() {
+        let config = MiningConfig { block_size_target: 5_000 };
+        let mempool = fee_paying_mempool(10, 1_000);
+        let txs = mempool.get_all_transactions().into_iter().cloned().collect();
+        let block = DetailedBlock::new("0".to_string(), txs, 1, 1);
+        assert!(block.size > config.block_size_target);
+        assert!(BlockValidator::default().validate_block(&block).is_ok());
+
+        // The consensus maximum still applies
+        let huge = fee_paying_mempool(1, MAX_BLOCK_SIZE);
+        let oversized = DetailedBlock::new("0".to_string(), huge.get_all_transactions().into_iter().cloned().collect(), 1, 1);
+        assert!(BlockValidator::default().validate_block(&oversized).is_err());
+    }
+
+    #[test]
+    fn test_mining_config_from_toml() {
+        let config = MiningConfig::from_toml("[mining]\nenabled = true\nblock_size_target = 250000\n").unwrap();
+        assert_eq!(config.block_size_target, 250_000);
+
+        // Configs without the section get the defaults
+        assert_eq!(MiningConfig::from_toml("[p2p]\nmax_peers = 8\n").unwrap(), MiningConfig::default());
+    }
+
     #[tokio::test]
     async fn test_coinbase_transaction() {
         let blockchain = Arc::new(RwLock::new(Blockchain::new()));
@@ -1,6 +1,7 @@
 use serde::{Serialize, Deserialize};
 use std::sync::Arc;
 use parking_lot::RwLock;
+use qc_validation::sync_status::SyncStatus;
 use std::collections::HashMap;
 use chrono::{DateTime, Utc, Duration};
 use tokio::time::{interval, Duration as TokioDuration};
@@ -67,6 +68,8 @@ pub struct MetricsCollector {
     request_times: Arc<RwLock<Vec<f64>>>,
     error_count: Arc<RwLock<u64>>,
     request_count: Arc<RwLock<u64>>,
+    /// Latest sync state; syncing until the node reports otherwise
+    sync_status: Arc<RwLock<SyncStatus>>,
 }
 
 impl Default for SystemMetrics {
@@ -144,6 +147,7 @@ impl MetricsCollector {
             request_times: Arc::new(RwLock::new(Vec::new())),
             error_count: Arc::new(RwLock::new(0)),
             request_count: Arc::new(RwLock::new(0)),
+            sync_status: Arc::new(RwLock::new(SyncStatus::Syncing)),
         }
     }
 
@@ -246,6 +250,12 @@ impl MetricsCollector {
         }
     }
 
+    /// Record the node's sync state, from `qc_validation::sync_status::sync_status`
+    /// over the heights its peers report
+    pub fn update_sync_status(&self, status: SyncStatus) {
+        *self.sync_status.write() = status;
+    }
+
     pub fn update_environmental_metrics(&self, carbon_offset: f64, renewable_energy: f64) {
         let mut metrics = self.environmental_metrics.write();
         metrics.total_carbon_offset_kg += carbon_offset;
@@ -381,13 +391,17 @@ impl MetricsCollector {
         serde_json::to_string_pretty(&self.get_all_metrics()).unwrap_or_default()
     }
 
+    /// Load-based health, and never "healthy" while the node is behind its
+    /// peers or too few of them agree on its tip
     pub fn get_health_status(&self) -> &'static str {
         let system = self.system_metrics.read();
-        let performance = self.performance_metrics.read();
+        let sync = *self.sync_status.read();
         
         if system.cpu_usage > 90.0 || system.memory_usage > 90.0 || system.error_rate > 10.0 {
             "unhealthy"
-        } else if system.cpu_usage > 70.0 || system.memory_usage > 80.0 || system.error_rate > 5.0 {
+        } else if sync == SyncStatus::Syncing {
+            "syncing"
+        } else if system.cpu_usage > 70.0 || system.memory_usage > 80.0 || system.error_rate > 5.0 || sync == SyncStatus::Degraded {
             "degraded"
         } else {
             "healthy"
@@ -0,0 +1,85 @@
+// zstd compression for large gossip payloads
+use anyhow::{anyhow, Result};
+use std::io::Read;
+
+/// Payloads smaller than this are always sent raw
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+/// Compressed output must be at most this fraction of the input to be worth sending
+pub const MAX_COMPRESSION_RATIO: f64 = 0.9;
+/// Upper bound on decompressed size, so a tiny stream can't expand without limit
+pub const MAX_DECOMPRESSED_SIZE: usize = 32 * 1024 * 1024;
+/// zstd level; favours speed since blocks are compressed on the relay path
+const ZSTD_LEVEL: i32 = 3;
+
+/// Compress a payload, or return `None` if it should go out uncompressed
+/// because it is below the threshold or doesn't shrink enough.
+pub fn compress_payload(data: &[u8]) -> Result<Option<Vec<u8>>> {
+    if data.len() < COMPRESSION_THRESHOLD {
+        return Ok(None);
+    }
+
+    let compressed = zstd::bulk::compress(data, ZSTD_LEVEL)?;
+    if compressed.len() as f64 > data.len() as f64 * MAX_COMPRESSION_RATIO {
+        return Ok(None);
+    }
+    Ok(Some(compressed))
+}
+
+/// Decompress a payload, failing if it would produce more than `limit` bytes.
+///
+/// The frame header's declared content size is not trusted; output is
+/// streamed and cut off one byte past the limit.
+pub fn decompress_payload(data: &[u8], limit: usize) -> Result<Vec<u8>> {
+    let decoder = zstd::stream::read::Decoder::new(data)?;
+    let mut out = Vec::new();
+    decoder.take(limit as u64 + 1).read_to_end(&mut out)?;
+
+    if out.len() > limit {
+        return Err(anyhow!("Decompressed payload exceeds {} bytes", limit));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let data: Vec<u8> = b"quantumcoin block payload ".iter().cycle().take(64 * 1024).copied().collect();
+
+        let compressed = compress_payload(&data).unwrap().expect("repetitive data should compress");
+        assert!(compressed.len() < data.len() / 10);
+        assert_eq!(decompress_payload(&compressed, MAX_DECOMPRESSED_SIZE).unwrap(), data);
+    }
+
+    #[test]
+    fn test_small_payload_skipped() {
+        let data = vec![0u8; COMPRESSION_THRESHOLD - 1];
+        assert!(compress_payload(&data).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_incompressible_payload_skipped() {
+        use rand::RngCore;
+        let mut data = vec![0u8; 16 * 1024];
+        rand::thread_rng().fill_bytes(&mut data);
+
+        assert!(compress_payload(&data).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_decompression_bomb_rejected() {
+        // 64 MiB of zeros compresses to a few KiB
+        let bomb = zstd::bulk::compress(&vec![0u8; 64 * 1024 * 1024], ZSTD_LEVEL).unwrap();
+        assert!(bomb.len() < 64 * 1024);
+
+        assert!(decompress_payload(&bomb, MAX_DECOMPRESSED_SIZE).is_err());
+        assert!(decompress_payload(&bomb, 1024).is_err());
+    }
+
+    #[test]
+    fn test_garbage_rejected() {
+        assert!(decompress_payload(b"definitely not zstd", MAX_DECOMPRESSED_SIZE).is_err());
+    }
+}
@@ -3,18 +3,24 @@
 
 use crate::block::Block;
 use crate::transaction::Transaction;
+use crate::mempool::{fee_per_byte, serialized_size};
 use crate::network::protocol::{NetworkMessage, InventoryItem, InventoryType};
 use crate::network::{ChainSpec, NetworkMetrics, SecurityManager};
+use crate::network::seen_blocks::{SeenBlockFilter, DEFAULT_SEEN_BLOCK_CAPACITY, SEEN_BLOCKS_FILE};
 use anyhow::{Result, anyhow};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet, VecDeque, BTreeMap};
 use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::{RwLock, mpsc, Mutex};
 use tokio::time::{sleep, timeout, interval};
 use uuid::Uuid;
 use blake3::Hasher;
+use async_trait::async_trait;
+use qc_types::codec;
+use rand::Rng;
 
 /// Maximum number of items in a single gossip message
 const MAX_GOSSIP_ITEMS: usize = 1000;
@@ -22,26 +28,70 @@ const MAX_GOSSIP_ITEMS: usize = 1000;
 const MAX_GOSSIP_AGE: Duration = Duration::from_secs(300); // 5 minutes
 /// Gossip retry interval for failed propagation
 const GOSSIP_RETRY_INTERVAL: Duration = Duration::from_secs(30);
-/// Maximum number of peers to gossip to per round
-const MAX_GOSSIP_PEERS: usize = 8;
+/// Fewest peers to gossip to per round, however small the network
+const MIN_GOSSIP_FANOUT: usize = 2;
+/// Most peers to gossip to per round, however large the network
+const MAX_GOSSIP_FANOUT: usize = 16;
 /// Backpressure threshold - stop gossiping when queue exceeds this
 const BACKPRESSURE_THRESHOLD: usize = 10000;
 /// DoS score threshold for banning peers
 const DOS_BAN_THRESHOLD: i32 = 100;
 /// Maximum concurrent gossip operations per peer
 const MAX_CONCURRENT_GOSSIP: usize = 3;
+/// Selection weight floor so even poor-quality peers are occasionally picked
+const MIN_SELECTION_WEIGHT: f64 = 0.05;
+/// Number of recent block hashes considered "near the tip" for prioritisation
+const RECENT_BLOCK_WINDOW: usize = 100;
+/// Most hops a transaction we originate spends in the Dandelion stem phase
+const MAX_STEM_HOPS: u8 = 4;
+/// Fluff a stem transaction ourselves if no fluffed copy has come back by then
+const STEM_FLUFF_TIMEOUT: Duration = Duration::from_secs(30);
+/// Mean delay before a peer's queued transaction announcements go out
+const TX_INV_INTERVAL: Duration = Duration::from_secs(5);
 
-/// Gossip message types with priority levels
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+/// Gossip message types with priority levels. Encoded as their `u32`
+/// discriminant; new types take the next unused number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
 pub enum GossipType {
-    Block,
-    Transaction,
-    BlockHeader,
-    CompactBlock,
-    Emergency, // For critical network messages
+    Block = 0,
+    Transaction = 1,
+    BlockHeader = 2,
+    CompactBlock = 3,
+    Emergency = 4, // For critical network messages
+    /// A type newer than this node, dropped on receipt without penalty
+    Unknown = u32::MAX,
+}
+
+impl Serialize for GossipType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_u32(*self as u32)
+    }
+}
+
+impl<'de> Deserialize<'de> for GossipType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        u32::deserialize(deserializer).map(GossipType::from_code)
+    }
 }
 
 impl GossipType {
+    const KNOWN: [GossipType; 5] = [
+        GossipType::Block,
+        GossipType::Transaction,
+        GossipType::BlockHeader,
+        GossipType::CompactBlock,
+        GossipType::Emergency,
+    ];
+
+    /// The type with wire discriminant `code`, or `Unknown`
+    pub fn from_code(code: u32) -> Self {
+        Self::KNOWN
+            .into_iter()
+            .find(|kind| *kind as u32 == code)
+            .unwrap_or(GossipType::Unknown)
+    }
+
     /// Get priority for message processing (0 = highest priority)
     pub fn priority(&self) -> u8 {
         match self {
@@ -50,6 +100,7 @@ impl GossipType {
             GossipType::BlockHeader => 2,
             GossipType::CompactBlock => 3,
             GossipType::Transaction => 4,
+            GossipType::Unknown => u8::MAX,
         }
     }
     
@@ -61,6 +112,7 @@ impl GossipType {
             GossipType::BlockHeader => 5,
             GossipType::CompactBlock => 5,
             GossipType::Transaction => 3,
+            GossipType::Unknown => 0,
         }
     }
     
@@ -72,10 +124,84 @@ impl GossipType {
             GossipType::BlockHeader => 20.0,
             GossipType::CompactBlock => 20.0,
             GossipType::Transaction => 100.0,
+            GossipType::Unknown => 0.0,
+        }
+    }
+}
+
+/// How a gossiped block relates to our current view of the chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainRelevance {
+    /// Parent is our current tip
+    ExtendsTip,
+    /// Parent is a recent block, i.e. a short fork
+    Recent,
+    /// Parent is unknown or too old to matter right now
+    Unknown,
+}
+
+impl ChainRelevance {
+    /// Queue priority for a block with this relevance (0 = highest priority)
+    pub fn priority(&self) -> u8 {
+        match self {
+            ChainRelevance::ExtendsTip => 0,
+            ChainRelevance::Recent => GossipType::Block.priority(),
+            // Behind transactions: orphans can't be connected until their parent arrives
+            ChainRelevance::Unknown => GossipType::Transaction.priority() + 1,
+        }
+    }
+}
+
+/// Our current tip and the hashes just below it
+#[derive(Debug, Default)]
+pub struct ChainView {
+    tip: Option<String>,
+    recent: VecDeque<String>,
+}
+
+impl ChainView {
+    pub fn relevance(&self, parent_hash: &str) -> ChainRelevance {
+        if self.tip.as_deref() == Some(parent_hash) {
+            ChainRelevance::ExtendsTip
+        } else if self.recent.iter().any(|h| h == parent_hash) {
+            ChainRelevance::Recent
+        } else {
+            ChainRelevance::Unknown
+        }
+    }
+    
+    pub fn set_tip(&mut self, hash: String) {
+        if let Some(old_tip) = self.tip.replace(hash) {
+            self.recent.push_back(old_tip);
+            if self.recent.len() > RECENT_BLOCK_WINDOW {
+                self.recent.pop_front();
+            }
         }
     }
 }
 
+/// Bounds on how many peers each gossip round targets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FanoutConfig {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl Default for FanoutConfig {
+    fn default() -> Self {
+        Self { min: MIN_GOSSIP_FANOUT, max: MAX_GOSSIP_FANOUT }
+    }
+}
+
+impl FanoutConfig {
+    /// Peers to gossip to when `known_peers` are connected: `ceil(sqrt(n))`,
+    /// clamped to the configured bounds
+    pub fn fanout(&self, known_peers: usize) -> usize {
+        let scaled = (known_peers as f64).sqrt().ceil() as usize;
+        scaled.max(self.min).min(self.max)
+    }
+}
+
 /// Gossip item with metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GossipItem {
@@ -85,12 +211,15 @@ pub struct GossipItem {
     pub timestamp: u64,
     pub hop_count: u8,
     pub priority: u8,
-    pub origin_peer: Option<String>,
+    pub origin_peer: Option<SocketAddr>,
     pub checksum: u32,
+    /// Remaining Dandelion stem hops; 0 once the item is being broadcast (fluff)
+    #[serde(default)]
+    pub stem_hops: u8,
 }
 
 impl GossipItem {
-    pub fn new(gossip_type: GossipType, data: Vec<u8>, origin_peer: Option<String>) -> Self {
+    pub fn new(gossip_type: GossipType, data: Vec<u8>, origin_peer: Option<SocketAddr>) -> Self {
         let id = Self::generate_id(&data);
         let checksum = Self::calculate_checksum(&data);
         let timestamp = SystemTime::now()
@@ -100,13 +229,14 @@ impl GossipItem {
         
         Self {
             id,
-            gossip_type: gossip_type.clone(),
+            gossip_type,
             data,
             timestamp,
             hop_count: 0,
             priority: gossip_type.priority(),
             origin_peer,
             checksum,
+            stem_hops: 0,
         }
     }
     
@@ -146,12 +276,72 @@ impl GossipItem {
     pub fn increment_hop(&mut self) {
         self.hop_count += 1;
     }
+    
+    /// Whether the item is still in the Dandelion stem phase
+    pub fn is_stem(&self) -> bool {
+        self.stem_hops > 0
+    }
+}
+
+/// Random delay from a Poisson process with the given mean interval
+fn poisson_delay<R: Rng>(mean: Duration, rng: &mut R) -> Duration {
+    let u: f64 = rng.gen();
+    mean.mul_f64(-(1.0 - u).ln())
+}
+
+/// Fluffed transactions waiting to be announced, per peer. Each peer's
+/// queue is flushed as one inv on its own Poisson timer, so announcements
+/// batch up and their timing doesn't reveal which peer heard first.
+#[derive(Debug, Clone)]
+pub struct TxInvBatcher {
+    interval: Duration,
+    /// Queued txids and when they are announced
+    pending: HashMap<SocketAddr, (Vec<String>, Instant)>,
+}
+
+impl TxInvBatcher {
+    pub fn new(interval: Duration) -> Self {
+        Self { interval, pending: HashMap::new() }
+    }
+    
+    /// Queue `txid` for `peer`. The first id queued starts the peer's timer.
+    pub fn queue<R: Rng>(&mut self, peer: SocketAddr, txid: String, now: Instant, rng: &mut R) {
+        let interval = self.interval;
+        let (txids, _) = self.pending
+            .entry(peer)
+            .or_insert_with(|| (Vec::new(), now + poisson_delay(interval, rng)));
+        if !txids.contains(&txid) {
+            txids.push(txid);
+        }
+    }
+    
+    /// Txids waiting for `peer`'s next inv
+    pub fn pending_for(&self, peer: SocketAddr) -> usize {
+        self.pending.get(&peer).map_or(0, |(txids, _)| txids.len())
+    }
+    
+    /// One inv for each peer whose timer has run out by `now`
+    pub fn due(&mut self, now: Instant) -> Vec<(SocketAddr, NetworkMessage)> {
+        let ready: Vec<SocketAddr> = self.pending.iter()
+            .filter(|(_, (_, send_at))| *send_at <= now)
+            .map(|(peer, _)| *peer)
+            .collect();
+        ready.into_iter()
+            .filter_map(|peer| self.pending.remove(&peer).map(|(txids, _)| (peer, txids)))
+            .map(|(peer, txids)| {
+                let inventory = txids.into_iter()
+                    .map(|hash| InventoryItem { inv_type: InventoryType::MsgTx, hash })
+                    .collect();
+                (peer, NetworkMessage::Inv { inventory })
+            })
+            .collect()
+    }
 }
 
 /// Peer gossip state for tracking what each peer knows
 #[derive(Debug, Clone)]
 pub struct PeerGossipState {
-    pub peer_id: String,
+    pub peer_id: SocketAddr,
     pub known_items: HashSet<String>,
     pub last_gossip: Instant,
     pub gossip_count: u32,
@@ -162,9 +352,9 @@ pub struct PeerGossipState {
 }
 
 impl PeerGossipState {
-    pub fn new(peer_id: String) -> Self {
+    pub fn new(peer_id: SocketAddr) -> Self {
         Self {
-            peer_id: peer_id.clone(),
+            peer_id,
             known_items: HashSet::new(),
             last_gossip: Instant::now(),
             gossip_count: 0,
@@ -218,14 +408,8 @@ impl RateLimiter {
     pub fn new() -> Self {
         let mut limits = HashMap::new();
         
-        for gossip_type in [
-            GossipType::Block,
-            GossipType::Transaction,
-            GossipType::BlockHeader,
-            GossipType::CompactBlock,
-            GossipType::Emergency,
-        ] {
-            limits.insert(gossip_type.clone(), TokenBucket::new(gossip_type.rate_limit()));
+        for gossip_type in GossipType::KNOWN {
+            limits.insert(gossip_type, TokenBucket::new(gossip_type.rate_limit()));
         }
         
         Self { limits }
@@ -370,9 +554,25 @@ pub struct GossipProtocol {
     security_manager: Arc<SecurityManager>,
     
     /// Peer gossip states
-    peers: Arc<RwLock<HashMap<String, PeerGossipState>>>,
+    peers: Arc<RwLock<HashMap<SocketAddr, PeerGossipState>>>,
     /// Items we've seen and processed
     seen_items: Arc<RwLock<HashMap<String, Instant>>>,
+    /// Blocks already validated, surviving restarts when persisted
+    seen_blocks: Arc<RwLock<SeenBlockFilter>>,
+    /// Where `seen_blocks` is saved, if anywhere
+    seen_blocks_path: Option<PathBuf>,
+    /// Items this node originated, used to detect gossip loops
+    originated_items: Arc<RwLock<HashMap<String, Instant>>>,
+    /// Current tip, used to prioritise blocks that extend it
+    chain_view: Arc<RwLock<ChainView>>,
+    /// Bounds on peers targeted per gossip round
+    fanout: FanoutConfig,
+    /// Stem-phase items we relayed, with the time we fluff them ourselves
+    stem_pending: Arc<RwLock<HashMap<String, (GossipItem, Instant)>>>,
+    /// How long a stem may stall before we fluff it
+    stem_timeout: Duration,
+    /// Fluffed transactions waiting for each peer's next batched inv
+    tx_invs: Arc<Mutex<TxInvBatcher>>,
     /// Outgoing gossip queue
     outgoing_queue: Arc<Mutex<GossipQueue>>,
     /// Incoming gossip queue  
@@ -384,7 +584,7 @@ pub struct GossipProtocol {
     
     /// Communication channels
     gossip_tx: mpsc::UnboundedSender<GossipCommand>,
-    peer_tx: HashMap<String, mpsc::UnboundedSender<NetworkMessage>>,
+    peer_tx: HashMap<SocketAddr, mpsc::UnboundedSender<NetworkMessage>>,
     
     /// Health monitoring
     health_monitor: Arc<Mutex<HealthMonitor>>,
@@ -396,14 +596,43 @@ pub struct GossipProtocol {
     running: Arc<RwLock<bool>>,
 }
 
+/// Kinds of peer misbehaviour, each carrying a fixed DoS penalty
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Misbehavior {
+    InvalidBlock,
+    InvalidTx,
+    BadChecksum,
+    RateLimitExceeded,
+    InvalidMessage,
+    /// Relayed a transaction paying less than our relay fee floor
+    BelowFeeFloor,
+    Spam,
+}
+
+impl Misbehavior {
+    /// DoS points added per offence; a peer is banned at `DOS_BAN_THRESHOLD`
+    pub const fn penalty(self) -> i32 {
+        match self {
+            Misbehavior::InvalidBlock => 20,
+            Misbehavior::InvalidTx => 10,
+            Misbehavior::BadChecksum => 10,
+            Misbehavior::RateLimitExceeded => 5,
+            Misbehavior::InvalidMessage => 10,
+            // Floors differ between nodes, so only persistent offenders get banned
+            Misbehavior::BelowFeeFloor => 2,
+            Misbehavior::Spam => DOS_BAN_THRESHOLD,
+        }
+    }
+}
+
 /// Commands for gossip protocol control
 #[derive(Debug)]
 pub enum GossipCommand {
-    AddPeer(String, mpsc::UnboundedSender<NetworkMessage>),
-    RemovePeer(String),
+    AddPeer(SocketAddr, mpsc::UnboundedSender<NetworkMessage>),
+    RemovePeer(SocketAddr),
     GossipItem(GossipItem),
-    ProcessIncoming(String, GossipItem),
-    UpdatePeerScore(String, i32),
+    ProcessIncoming(SocketAddr, GossipItem),
+    Punish(SocketAddr, Misbehavior),
     ForceSync,
     Shutdown,
 }
@@ -416,6 +645,7 @@ pub struct HealthMonitor {
     backpressure_events: u64,
     partition_events: u64,
     banned_peers: u64,
+    loop_events: u64,
     last_health_check: Instant,
 }
 
@@ -427,6 +657,7 @@ impl HealthMonitor {
             backpressure_events: 0,
             partition_events: 0,
             banned_peers: 0,
+            loop_events: 0,
             last_health_check: Instant::now(),
         }
     }
@@ -455,6 +686,10 @@ impl HealthMonitor {
         self.banned_peers += 1;
     }
     
+    pub fn record_loop(&mut self) {
+        self.loop_events += 1;
+    }
+    
     pub fn is_healthy(&self) -> bool {
         self.error_rate < 10.0 && // Less than 10 errors per second
         self.gossip_rate > 0.1 && // At least some gossip activity
@@ -465,7 +700,7 @@ impl HealthMonitor {
 /// Network partition detection
 #[derive(Debug)]
 pub struct PartitionDetector {
-    peer_connectivity: HashMap<String, Instant>,
+    peer_connectivity: HashMap<SocketAddr, Instant>,
     partition_threshold: Duration,
     min_peers_for_health: usize,
 }
@@ -479,8 +714,8 @@ impl PartitionDetector {
         }
     }
     
-    pub fn update_peer_activity(&mut self, peer_id: &str) {
-        self.peer_connectivity.insert(peer_id.to_string(), Instant::now());
+    pub fn update_peer_activity(&mut self, peer_id: SocketAddr) {
+        self.peer_connectivity.insert(peer_id, Instant::now());
     }
     
     pub fn detect_partition(&mut self) -> bool {
@@ -504,15 +739,33 @@ impl PartitionDetector {
 }
 
 /// Block handler trait
+#[async_trait]
 pub trait BlockHandler {
     async fn handle_block(&self, block: Block) -> Result<()>;
     async fn validate_block(&self, block: &Block) -> Result<bool>;
+    
+    /// Whether block storage already holds `hash`
+    async fn has_block(&self, _hash: &str) -> Result<bool> {
+        Ok(false)
+    }
+    
+    /// Hashes of the newest `count` stored blocks, oldest first, tip last
+    async fn recent_block_hashes(&self, _count: usize) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
 }
 
 /// Transaction handler trait
+#[async_trait]
 pub trait TransactionHandler {
     async fn handle_transaction(&self, transaction: Transaction) -> Result<()>;
     async fn validate_transaction(&self, transaction: &Transaction) -> Result<bool>;
+    
+    /// Lowest fee rate worth relaying right now, in `mempool::fee_per_byte`
+    /// units
+    async fn relay_fee_floor(&self) -> f64 {
+        0.0
+    }
 }
 
 impl GossipProtocol {
@@ -526,6 +779,12 @@ impl GossipProtocol {
     ) -> Result<Self> {
         let (gossip_tx, _) = mpsc::unbounded_channel();
         
+        // Start from the stored tip so the first blocks are prioritised against it
+        let mut chain_view = ChainView::default();
+        for hash in block_handler.recent_block_hashes(RECENT_BLOCK_WINDOW + 1).await? {
+            chain_view.set_tip(hash);
+        }
+        
         Ok(Self {
             node_id,
             chain_spec,
@@ -533,6 +792,14 @@ impl GossipProtocol {
             security_manager,
             peers: Arc::new(RwLock::new(HashMap::new())),
             seen_items: Arc::new(RwLock::new(HashMap::new())),
+            seen_blocks: Arc::new(RwLock::new(SeenBlockFilter::new(DEFAULT_SEEN_BLOCK_CAPACITY))),
+            seen_blocks_path: None,
+            originated_items: Arc::new(RwLock::new(HashMap::new())),
+            chain_view: Arc::new(RwLock::new(chain_view)),
+            fanout: FanoutConfig::default(),
+            stem_pending: Arc::new(RwLock::new(HashMap::new())),
+            stem_timeout: STEM_FLUFF_TIMEOUT,
+            tx_invs: Arc::new(Mutex::new(TxInvBatcher::new(TX_INV_INTERVAL))),
             outgoing_queue: Arc::new(Mutex::new(GossipQueue::new())),
             incoming_queue: Arc::new(Mutex::new(GossipQueue::new())),
             block_handler,
@@ -545,6 +812,18 @@ impl GossipProtocol {
         })
     }
     
+    /// Override the default gossip fanout bounds
+    pub fn with_fanout(mut self, fanout: FanoutConfig) -> Self {
+        self.fanout = fanout;
+        self
+    }
+    
+    /// Override the mean delay before transaction announcements go out
+    pub fn with_tx_relay_interval(mut self, interval: Duration) -> Self {
+        self.tx_invs = Arc::new(Mutex::new(TxInvBatcher::new(interval)));
+        self
+    }
+    
     /// Start the gossip protocol
     pub async fn start(&mut self) -> Result<()> {
         *self.running.write().await = true;
@@ -586,16 +865,16 @@ impl GossipProtocol {
                 self.add_peer(peer_id, sender).await;
             }
             GossipCommand::RemovePeer(peer_id) => {
-                self.remove_peer(&peer_id).await;
+                self.remove_peer(peer_id).await;
             }
             GossipCommand::GossipItem(item) => {
                 self.queue_for_gossip(item).await?;
             }
             GossipCommand::ProcessIncoming(peer_id, item) => {
-                self.process_incoming_item(&peer_id, item).await?;
+                self.process_incoming_item(peer_id, item).await?;
             }
-            GossipCommand::UpdatePeerScore(peer_id, delta) => {
-                self.update_peer_score(&peer_id, delta).await;
+            GossipCommand::Punish(peer_id, misbehavior) => {
+                self.punish(peer_id, misbehavior).await;
             }
             GossipCommand::ForceSync => {
                 self.force_sync().await?;
@@ -609,17 +888,17 @@ impl GossipProtocol {
     }
     
     /// Add a peer to gossip to
-    async fn add_peer(&self, peer_id: String, sender: mpsc::UnboundedSender<NetworkMessage>) {
+    async fn add_peer(&self, peer_id: SocketAddr, sender: mpsc::UnboundedSender<NetworkMessage>) {
         let mut peers = self.peers.write().await;
-        peers.insert(peer_id.clone(), PeerGossipState::new(peer_id.clone()));
+        peers.insert(peer_id, PeerGossipState::new(peer_id));
         
         log::debug!("Added peer {} to gossip protocol", peer_id);
     }
     
     /// Remove a peer
-    async fn remove_peer(&self, peer_id: &str) {
+    async fn remove_peer(&self, peer_id: SocketAddr) {
         let mut peers = self.peers.write().await;
-        peers.remove(peer_id);
+        peers.remove(&peer_id);
         
         log::debug!("Removed peer {} from gossip protocol", peer_id);
     }
@@ -627,7 +906,7 @@ impl GossipProtocol {
     /// Queue an item for gossip
     pub async fn gossip_block(&self, block: Block) -> Result<()> {
         let data = bincode::serialize(&block)?;
-        let item = GossipItem::new(GossipType::Block, data, Some(self.node_id.clone()));
+        let item = GossipItem::new(GossipType::Block, data, None);
         
         self.gossip_tx.send(GossipCommand::GossipItem(item))
             .map_err(|_| anyhow!("Failed to queue block for gossip"))?;
@@ -635,10 +914,22 @@ impl GossipProtocol {
         Ok(())
     }
     
-    /// Queue a transaction for gossip
+    /// Queue a transaction for gossip.
+    ///
+    /// The transaction starts in the Dandelion stem phase, relayed to one peer
+    /// at a time for a few hops so the broadcast doesn't start from us.
+    /// Remember validated blocks in `data_dir`, loading what an earlier run saved
+    pub fn with_seen_block_store(mut self, data_dir: &Path) -> Self {
+        let path = data_dir.join(SEEN_BLOCKS_FILE);
+        self.seen_blocks = Arc::new(RwLock::new(SeenBlockFilter::load(&path, DEFAULT_SEEN_BLOCK_CAPACITY)));
+        self.seen_blocks_path = Some(path);
+        self
+    }
+    
     pub async fn gossip_transaction(&self, transaction: Transaction) -> Result<()> {
         let data = bincode::serialize(&transaction)?;
-        let item = GossipItem::new(GossipType::Transaction, data, Some(self.node_id.clone()));
+        let mut item = GossipItem::new(GossipType::Transaction, data, None);
+        item.stem_hops = rand::thread_rng().gen_range(1..=MAX_STEM_HOPS);
         
         self.gossip_tx.send(GossipCommand::GossipItem(item))
             .map_err(|_| anyhow!("Failed to queue transaction for gossip"))?;
@@ -656,8 +947,19 @@ impl GossipProtocol {
         
         // Mark as seen
         seen.insert(item.id.clone(), Instant::now());
+        drop(seen);
+        
+        // Items without a relaying peer were created by this node
+        if item.origin_peer.is_none() {
+            self.originated_items.write().await.insert(item.id.clone(), Instant::now());
+        }
         
-        // Queue for outgoing gossip
+        self.relay_item(item).await
+    }
+    
+    /// Queue an accepted incoming item for onward gossip. It was marked
+    /// seen on arrival, so it skips the dedup in `queue_for_gossip`.
+    async fn relay_item(&self, item: GossipItem) -> Result<()> {
         let mut queue = self.outgoing_queue.lock().await;
         if !queue.push(item) {
             // Backpressure - queue is full
@@ -669,22 +971,33 @@ impl GossipProtocol {
     }
     
     /// Process incoming gossip item from peer
-    pub async fn process_incoming_gossip(&self, peer_id: &str, item: GossipItem) -> Result<()> {
-        self.gossip_tx.send(GossipCommand::ProcessIncoming(peer_id.to_string(), item))
+    pub async fn process_incoming_gossip(&self, peer_id: SocketAddr, item: GossipItem) -> Result<()> {
+        self.gossip_tx.send(GossipCommand::ProcessIncoming(peer_id, item))
             .map_err(|_| anyhow!("Failed to queue incoming gossip"))?;
         
         Ok(())
     }
     
     /// Process incoming item
-    async fn process_incoming_item(&self, peer_id: &str, mut item: GossipItem) -> Result<()> {
+    async fn process_incoming_item(&self, peer_id: SocketAddr, mut item: GossipItem) -> Result<()> {
+        // Banned peers are ignored regardless of which subsystem banned them
+        if self.is_peer_banned(peer_id).await {
+            return Err(anyhow!("Peer {} is banned", peer_id));
+        }
+        
         // Update partition detector
         self.partition_detector.lock().await.update_peer_activity(peer_id);
         
+        // Types from newer peers are skipped before any check that could penalise
+        if item.gossip_type == GossipType::Unknown {
+            log::debug!("Ignoring gossip of unknown type from peer {}", peer_id);
+            return Ok(());
+        }
+        
         // Verify checksum
         if !item.verify_checksum() {
             log::warn!("Invalid checksum from peer {}", peer_id);
-            self.update_peer_score(peer_id, 10).await;
+            self.punish(peer_id, Misbehavior::BadChecksum).await;
             return Err(anyhow!("Invalid checksum"));
         }
         
@@ -693,12 +1006,28 @@ impl GossipProtocol {
             return Ok(()); // Silently drop stale items
         }
         
+        // A fluffed copy of something we stemmed means the stem worked
+        if !item.is_stem() {
+            self.stem_pending.write().await.remove(&item.id);
+        }
+        
+        // Our own item came back through a cycle of peers - drop it
+        if self.originated_items.read().await.contains_key(&item.id) {
+            log::debug!("Gossip loop detected: {} returned via peer {}", item.id, peer_id);
+            self.health_monitor.lock().await.record_loop();
+            if let Some(peer_state) = self.peers.write().await.get_mut(&peer_id) {
+                peer_state.mark_known(item.id.clone());
+            }
+            return Ok(());
+        }
+        
         // Check rate limiting
         let mut peers = self.peers.write().await;
-        if let Some(peer_state) = peers.get_mut(peer_id) {
+        if let Some(peer_state) = peers.get_mut(&peer_id) {
             if !peer_state.can_accept_gossip(&item.gossip_type) {
                 log::debug!("Rate limiting gossip from peer {}", peer_id);
-                self.update_peer_score(peer_id, 5).await;
+                drop(peers);
+                self.punish(peer_id, Misbehavior::RateLimitExceeded).await;
                 return Err(anyhow!("Rate limit exceeded"));
             }
             
@@ -712,6 +1041,14 @@ impl GossipProtocol {
         }
         drop(peers);
         
+        // Remember who relayed this item so penalties land on the right peer
+        item.origin_peer = Some(peer_id);
+        
+        // A stem item used up a hop reaching us
+        if item.is_stem() {
+            item.stem_hops -= 1;
+        }
+        
         // Check if we've already processed this item
         let mut seen = self.seen_items.write().await;
         if seen.contains_key(&item.id) {
@@ -722,6 +1059,9 @@ impl GossipProtocol {
         seen.insert(item.id.clone(), Instant::now());
         drop(seen);
         
+        // Blocks building on our tip jump the queue; stale branches wait
+        item.priority = self.incoming_priority(&item).await;
+        
         // Queue for incoming processing
         let mut queue = self.incoming_queue.lock().await;
         if !queue.push(item) {
@@ -733,10 +1073,33 @@ impl GossipProtocol {
         Ok(())
     }
     
+    /// Whether a block was validated before. Filter hits are confirmed
+    /// against storage, so a false positive costs a lookup, not the block.
+    async fn already_validated(&self, block_hash: &str) -> Result<bool> {
+        if !self.seen_blocks.read().await.might_contain(block_hash) {
+            return Ok(false);
+        }
+        self.block_handler.has_block(block_hash).await
+    }
+    
+    /// Save the seen-block filter, if it has a store
+    pub async fn save_seen_blocks(&self) -> Result<()> {
+        match &self.seen_blocks_path {
+            Some(path) => self.seen_blocks.read().await.save(path),
+            None => Ok(()),
+        }
+    }
+    
+    /// Penalise a peer for misbehaviour, banning it once its score reaches the threshold
+    pub async fn punish(&self, peer_id: SocketAddr, misbehavior: Misbehavior) {
+        log::debug!("Peer {} misbehaved: {:?} (+{})", peer_id, misbehavior, misbehavior.penalty());
+        self.update_peer_score(peer_id, misbehavior.penalty()).await;
+    }
+    
     /// Update peer DoS score
-    async fn update_peer_score(&self, peer_id: &str, delta: i32) {
+    async fn update_peer_score(&self, peer_id: SocketAddr, delta: i32) {
         let mut peers = self.peers.write().await;
-        if let Some(peer_state) = peers.get_mut(peer_id) {
+        if let Some(peer_state) = peers.get_mut(&peer_id) {
             if delta > 0 {
                 peer_state.increase_dos_score(delta);
             } else {
@@ -751,6 +1114,30 @@ impl GossipProtocol {
         }
     }
     
+    /// Record a new chain tip so incoming blocks can be prioritised against it
+    pub async fn update_chain_tip(&self, hash: String) {
+        self.chain_view.write().await.set_tip(hash);
+    }
+    
+    /// Priority for an incoming item, taking chain relevance of blocks into account
+    async fn incoming_priority(&self, item: &GossipItem) -> u8 {
+        if item.gossip_type != GossipType::Block {
+            return item.gossip_type.priority();
+        }
+        match codec::decode::<Block>(&item.data) {
+            Ok(block) => self.chain_view.read().await.relevance(&block.previous_hash).priority(),
+            Err(_) => item.gossip_type.priority(),
+        }
+    }
+    
+    /// Check whether a peer is currently banned
+    pub async fn is_peer_banned(&self, peer_id: SocketAddr) -> bool {
+        self.peers.read().await
+            .get(&peer_id)
+            .map(|state| state.is_banned())
+            .unwrap_or(false)
+    }
+    
     /// Force synchronization with peers
     async fn force_sync(&self) -> Result<()> {
         log::info!("Forcing gossip synchronization");
@@ -779,12 +1166,15 @@ impl GossipProtocol {
                     log::error!("Outgoing gossip processing error: {}", e);
                     protocol.health_monitor.lock().await.record_error();
                 }
+                protocol.flush_tx_announcements(Instant::now()).await;
             }
         });
     }
     
     /// Process outgoing gossip queue
     async fn process_outgoing_queue(&self) -> Result<()> {
+        self.fluff_stalled_stems().await;
+        
         let mut queue = self.outgoing_queue.lock().await;
         let mut processed = 0;
         
@@ -794,6 +1184,9 @@ impl GossipProtocol {
                 
                 // Select peers to gossip to
                 let target_peers = self.select_gossip_peers(&item).await;
+                if item.is_stem() && target_peers.is_empty() {
+                    item.stem_hops = 0; // Nobody to stem through, broadcast instead
+                }
                 
                 for peer_id in target_peers {
                     // Check if peer already knows about this item
@@ -804,11 +1197,18 @@ impl GossipProtocol {
                     drop(peers);
                     
                     if should_send {
-                        // Create network message
-                        let msg = self.create_gossip_message(&item)?;
-                        
-                        // TODO: Send to peer via network layer
-                        log::trace!("Gossiping {} to peer {}", item.id, peer_id);
+                        if item.gossip_type == GossipType::Transaction && !item.is_stem() {
+                            // Fluffed transactions are announced in batches
+                            let transaction: Transaction = codec::decode(&item.data)?;
+                            self.tx_invs.lock().await
+                                .queue(peer_id, transaction.id, Instant::now(), &mut rand::thread_rng());
+                        } else {
+                            // Create network message
+                            let msg = self.create_gossip_message(&item)?;
+                            
+                            // TODO: Send to peer via network layer
+                            log::trace!("Gossiping {} to peer {}", item.id, peer_id);
+                        }
                         
                         // Mark as known by this peer
                         let mut peers = self.peers.write().await;
@@ -818,6 +1218,15 @@ impl GossipProtocol {
                     }
                 }
                 
+                // Stem items go to a single peer; the fluff timer takes over from here
+                if item.is_stem() {
+                    let fluff_at = Instant::now() + self.stem_timeout;
+                    self.stem_pending.write().await.insert(item.id.clone(), (item, fluff_at));
+                    queue = self.outgoing_queue.lock().await;
+                    processed += 1;
+                    continue;
+                }
+                
                 // Increment hop count for next round
                 item.increment_hop();
                 
@@ -838,33 +1247,67 @@ impl GossipProtocol {
         Ok(())
     }
     
+    /// Batched transaction invs for every peer whose timer has run out
+    async fn flush_tx_announcements(&self, now: Instant) -> Vec<(SocketAddr, NetworkMessage)> {
+        let due = self.tx_invs.lock().await.due(now);
+        for (peer_id, _msg) in &due {
+            // TODO: Send to peer via network layer
+            log::trace!("Announcing batched transactions to peer {}", peer_id);
+        }
+        due
+    }
+    
+    /// Broadcast stem items whose fluff timer has run out
+    async fn fluff_stalled_stems(&self) {
+        let now = Instant::now();
+        let mut pending = self.stem_pending.write().await;
+        let stalled: Vec<String> = pending.iter()
+            .filter(|(_, (_, fluff_at))| *fluff_at <= now)
+            .map(|(id, _)| id.clone())
+            .collect();
+        if stalled.is_empty() {
+            return;
+        }
+        
+        let mut queue = self.outgoing_queue.lock().await;
+        for id in stalled {
+            if let Some((mut item, _)) = pending.remove(&id) {
+                log::debug!("Stem for {} stalled, fluffing", id);
+                item.stem_hops = 0;
+                if !queue.push(item) {
+                    self.health_monitor.lock().await.record_backpressure();
+                }
+            }
+        }
+    }
+    
     /// Select peers for gossip propagation
-    async fn select_gossip_peers(&self, item: &GossipItem) -> Vec<String> {
+    async fn select_gossip_peers(&self, item: &GossipItem) -> Vec<SocketAddr> {
         let peers = self.peers.read().await;
-        let mut candidates: Vec<_> = peers.iter()
-            .filter(|(_, state)| !state.is_banned() && !state.knows_item(&item.id))
-            .map(|(id, state)| (id.clone(), state.connection_quality))
+        let fanout = if item.is_stem() { 1 } else { self.fanout.fanout(peers.len()) };
+        let candidates: Vec<_> = peers.iter()
+            .filter(|(id, state)| {
+                // Never echo an item back to the peer we received it from
+                Some(**id) != item.origin_peer &&
+                !state.is_banned() &&
+                !state.knows_item(&item.id)
+            })
+            .map(|(id, state)| (*id, state.connection_quality))
             .collect();
         
-        // Sort by connection quality (best first)
-        candidates.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-        
-        // Take up to MAX_GOSSIP_PEERS
-        candidates.into_iter()
-            .take(MAX_GOSSIP_PEERS)
-            .map(|(id, _)| id)
-            .collect()
+        // Weighted random choice keeps propagation paths diverse and hard to map
+        weighted_peer_sample(candidates, fanout, &mut rand::thread_rng())
     }
     
     /// Create network message from gossip item
     fn create_gossip_message(&self, item: &GossipItem) -> Result<NetworkMessage> {
         match item.gossip_type {
             GossipType::Block => {
-                let block: Block = bincode::deserialize(&item.data)?;
+                let block: Block = codec::decode(&item.data)?;
                 Ok(NetworkMessage::Block { block })
             }
             GossipType::Transaction => {
-                let transaction: Transaction = bincode::deserialize(&item.data)?;
+                let transaction: Transaction = codec::decode(&item.data)?;
                 Ok(NetworkMessage::Tx { transaction })
             }
             GossipType::BlockHeader => {
@@ -879,6 +1322,7 @@ impl GossipProtocol {
                 // TODO: Implement emergency message
                 Err(anyhow!("Emergency gossip not yet implemented"))
             }
+            GossipType::Unknown => Err(anyhow!("Cannot relay gossip of unknown type")),
         }
     }
     
@@ -912,38 +1356,70 @@ impl GossipProtocol {
                 // Process based on type
                 match item.gossip_type {
                     GossipType::Block => {
-                        let block: Block = bincode::deserialize(&item.data)?;
+                        let block: Block = match codec::decode(&item.data) {
+                            Ok(block) => block,
+                            Err(e) => {
+                                if let Some(origin) = item.origin_peer {
+                                    self.punish(origin, Misbehavior::InvalidMessage).await;
+                                }
+                                return Err(e.into());
+                            }
+                        };
                         
                         // Validate block
-                        if self.block_handler.validate_block(&block).await? {
+                        if self.already_validated(&block.hash).await? {
+                            log::debug!("Skipping validation of known block {}", block.hash);
+                        } else if self.block_handler.validate_block(&block).await? {
+                            let extends_tip = item.priority == ChainRelevance::ExtendsTip.priority();
+                            let hash = block.hash.clone();
                             self.block_handler.handle_block(block).await?;
+                            self.seen_blocks.write().await.insert(&hash);
+                            if extends_tip {
+                                self.update_chain_tip(hash).await;
+                            }
                             
                             // Re-gossip if still can propagate
                             if item.can_propagate() {
-                                self.queue_for_gossip(item).await?;
+                                self.relay_item(item).await?;
                             }
                         } else {
                             log::warn!("Invalid block received via gossip: {}", item.id);
-                            if let Some(origin) = &item.origin_peer {
-                                self.update_peer_score(origin, 20).await;
+                            if let Some(origin) = item.origin_peer {
+                                self.punish(origin, Misbehavior::InvalidBlock).await;
                             }
                         }
                     }
                     GossipType::Transaction => {
-                        let transaction: Transaction = bincode::deserialize(&item.data)?;
+                        let transaction: Transaction = match codec::decode(&item.data) {
+                            Ok(transaction) => transaction,
+                            Err(e) => {
+                                if let Some(origin) = item.origin_peer {
+                                    self.punish(origin, Misbehavior::InvalidMessage).await;
+                                }
+                                return Err(e.into());
+                            }
+                        };
                         
-                        // Validate transaction
-                        if self.transaction_handler.validate_transaction(&transaction).await? {
+                        // Drop transactions too cheap to relay before doing any real
+                        // validation, rating them in the mempool's units
+                        let fee_rate = fee_per_byte(transaction.fee, serialized_size(&transaction));
+                        let floor = self.transaction_handler.relay_fee_floor().await;
+                        if fee_rate < floor {
+                            log::debug!("Transaction {} below relay fee floor: {:.4} < {:.4}", item.id, fee_rate, floor);
+                            if let Some(origin) = item.origin_peer {
+                                self.punish(origin, Misbehavior::BelowFeeFloor).await;
+                            }
+                        } else if self.transaction_handler.validate_transaction(&transaction).await? {
                             self.transaction_handler.handle_transaction(transaction).await?;
                             
                             // Re-gossip if still can propagate
                             if item.can_propagate() {
-                                self.queue_for_gossip(item).await?;
+                                self.relay_item(item).await?;
                             }
                         } else {
                             log::warn!("Invalid transaction received via gossip: {}", item.id);
-                            if let Some(origin) = &item.origin_peer {
-                                self.update_peer_score(origin, 10).await;
+                            if let Some(origin) = item.origin_peer {
+                                self.punish(origin, Misbehavior::InvalidTx).await;
                             }
                         }
                     }
@@ -1012,6 +1488,10 @@ impl GossipProtocol {
                 });
                 drop(seen);
                 
+                protocol.originated_items.write().await.retain(|_, timestamp| {
+                    now.duration_since(*timestamp) < MAX_GOSSIP_AGE
+                });
+                
                 // Clean up stale queue items
                 protocol.outgoing_queue.lock().await.cleanup_stale();
                 protocol.incoming_queue.lock().await.cleanup_stale();
@@ -1021,6 +1501,11 @@ impl GossipProtocol {
                 for peer_state in peers.values_mut() {
                     peer_state.decrease_dos_score(1); // Slowly decrease scores
                 }
+                drop(peers);
+                
+                if let Err(e) = protocol.save_seen_blocks().await {
+                    log::warn!("{}", e);
+                }
                 
                 log::trace!("Gossip protocol cleanup completed");
             }
@@ -1044,6 +1529,7 @@ impl GossipProtocol {
             error_rate: health.error_rate,
             backpressure_events: health.backpressure_events,
             partition_events: health.partition_events,
+            loop_events: health.loop_events,
             active_peers: partition.get_active_peer_count(),
             has_backpressure: outgoing_queue.has_backpressure() || incoming_queue.has_backpressure(),
             is_healthy: health.is_healthy(),
@@ -1053,6 +1539,7 @@ impl GossipProtocol {
     /// Shutdown the gossip protocol
     pub async fn shutdown(&self) -> Result<()> {
         *self.running.write().await = false;
+        self.save_seen_blocks().await?;
         
         self.gossip_tx.send(GossipCommand::Shutdown)
             .map_err(|_| anyhow!("Failed to send shutdown command"))?;
@@ -1062,6 +1549,31 @@ impl GossipProtocol {
     }
 }
 
+/// Pick up to `count` peers without replacement, favouring higher connection quality.
+///
+/// Uses the Efraimidis-Spirakis method: each peer draws `u^(1/w)` and the
+/// highest keys win. Weights are floored at `MIN_SELECTION_WEIGHT` so every
+/// candidate keeps a nonzero chance of being selected.
+pub fn weighted_peer_sample<R: Rng>(
+    candidates: Vec<(SocketAddr, f64)>,
+    count: usize,
+    rng: &mut R,
+) -> Vec<SocketAddr> {
+    let mut keyed: Vec<(f64, SocketAddr)> = candidates.into_iter()
+        .map(|(peer, quality)| {
+            let weight = if quality.is_finite() { quality.max(MIN_SELECTION_WEIGHT) } else { MIN_SELECTION_WEIGHT };
+            let u: f64 = rng.gen_range(f64::EPSILON..1.0);
+            (u.powf(1.0 / weight), peer)
+        })
+        .collect();
+    
+    keyed.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+    keyed.into_iter()
+        .take(count)
+        .map(|(_, peer)| peer)
+        .collect()
+}
+
 /// Gossip protocol statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GossipStats {
@@ -1073,6 +1585,7 @@ pub struct GossipStats {
     pub error_rate: f64,
     pub backpressure_events: u64,
     pub partition_events: u64,
+    pub loop_events: u64,
     pub active_peers: usize,
     pub has_backpressure: bool,
     pub is_healthy: bool,
@@ -1088,6 +1601,14 @@ impl Clone for GossipProtocol {
             security_manager: self.security_manager.clone(),
             peers: self.peers.clone(),
             seen_items: self.seen_items.clone(),
+            seen_blocks: self.seen_blocks.clone(),
+            seen_blocks_path: self.seen_blocks_path.clone(),
+            originated_items: self.originated_items.clone(),
+            chain_view: self.chain_view.clone(),
+            fanout: self.fanout,
+            stem_pending: self.stem_pending.clone(),
+            stem_timeout: self.stem_timeout,
+            tx_invs: self.tx_invs.clone(),
             outgoing_queue: self.outgoing_queue.clone(),
             incoming_queue: self.incoming_queue.clone(),
             block_handler: self.block_handler.clone(),
@@ -1106,6 +1627,40 @@ mod tests {
     use super::*;
     use tokio::test;
     
+    struct NoopHandler;
+    
+    #[async_trait]
+    impl BlockHandler for NoopHandler {
+        async fn handle_block(&self, _block: Block) -> Result<()> { Ok(()) }
+        async fn validate_block(&self, _block: &Block) -> Result<bool> { Ok(true) }
+    }
+    
+    #[async_trait]
+    impl TransactionHandler for NoopHandler {
+        async fn handle_transaction(&self, _transaction: Transaction) -> Result<()> { Ok(()) }
+        async fn validate_transaction(&self, _transaction: &Transaction) -> Result<bool> { Ok(true) }
+    }
+    
+    async fn test_protocol() -> GossipProtocol {
+        let chain_spec = Arc::new(ChainSpec::default());
+        let metrics = Arc::new(NetworkMetrics::new());
+        let security_manager = Arc::new(SecurityManager::new(chain_spec.clone(), metrics.clone()));
+        let handler = Arc::new(NoopHandler);
+        
+        GossipProtocol::new(
+            "test-node".to_string(),
+            chain_spec,
+            metrics,
+            security_manager,
+            handler.clone(),
+            handler,
+        ).await.unwrap()
+    }
+    
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+    
     #[test]
     async fn test_gossip_item_creation() {
         let data = vec![1, 2, 3, 4, 5];
@@ -1149,9 +1704,29 @@ mod tests {
         assert_eq!(popped.gossip_type, GossipType::Emergency);
     }
     
+    #[test]
+    async fn test_unknown_gossip_type_ignored_without_penalty() {
+        let decoded: GossipType = bincode::deserialize(&99u32.to_le_bytes()).unwrap();
+        assert_eq!(decoded, GossipType::Unknown);
+        let block: GossipType = bincode::deserialize(&bincode::serialize(&GossipType::Block).unwrap()).unwrap();
+        assert_eq!(block, GossipType::Block);
+        
+        let protocol = test_protocol().await;
+        let sender = addr("10.0.0.1:8333");
+        let (tx, _rx) = mpsc::unbounded_channel();
+        protocol.add_peer(sender, tx).await;
+        
+        let mut item = GossipItem::new(GossipType::Transaction, vec![1, 2, 3], None);
+        item.gossip_type = decoded;
+        protocol.process_incoming_item(sender, item).await.unwrap();
+        
+        assert_eq!(protocol.peers.read().await[&sender].dos_score, 0);
+        assert!(protocol.incoming_queue.lock().await.is_empty());
+    }
+    
     #[test]
     async fn test_peer_dos_scoring() {
-        let mut peer = PeerGossipState::new("test_peer".to_string());
+        let mut peer = PeerGossipState::new("127.0.0.1:8333".parse().unwrap());
         
         assert!(!peer.is_banned());
         
@@ -1167,4 +1742,498 @@ mod tests {
         peer.decrease_dos_score(50);
         assert!(!peer.is_banned()); // Now unbanned
     }
+    
+    #[test]
+    async fn test_each_misbehavior_applies_its_penalty() {
+        let protocol = test_protocol().await;
+        let categories = [
+            Misbehavior::InvalidBlock,
+            Misbehavior::InvalidTx,
+            Misbehavior::BadChecksum,
+            Misbehavior::RateLimitExceeded,
+            Misbehavior::InvalidMessage,
+            Misbehavior::BelowFeeFloor,
+            Misbehavior::Spam,
+        ];
+        
+        for (i, misbehavior) in categories.into_iter().enumerate() {
+            let peer = addr(&format!("10.0.1.{}:8333", i + 1));
+            let (tx, _rx) = mpsc::unbounded_channel();
+            protocol.add_peer(peer, tx).await;
+            
+            protocol.punish(peer, misbehavior).await;
+            let score = protocol.peers.read().await[&peer].dos_score;
+            assert_eq!(score, misbehavior.penalty(), "{:?}", misbehavior);
+        }
+        assert!(protocol.is_peer_banned(addr("10.0.1.7:8333")).await, "spam bans immediately");
+    }
+    
+    #[test]
+    async fn test_misbehavior_accumulates_to_ban() {
+        let protocol = test_protocol().await;
+        let peer = addr("10.0.0.1:8333");
+        let (tx, _rx) = mpsc::unbounded_channel();
+        protocol.add_peer(peer, tx).await;
+        
+        let offences = DOS_BAN_THRESHOLD / Misbehavior::InvalidBlock.penalty();
+        for _ in 1..offences {
+            protocol.punish(peer, Misbehavior::InvalidBlock).await;
+        }
+        assert!(!protocol.is_peer_banned(peer).await);
+        
+        protocol.punish(peer, Misbehavior::InvalidBlock).await;
+        assert!(protocol.is_peer_banned(peer).await);
+        assert_eq!(protocol.get_stats().await.banned_peers, 1);
+    }
+    
+    #[test]
+    async fn test_ban_visible_across_subsystems() {
+        let protocol = test_protocol().await;
+        let (tx, _rx) = mpsc::unbounded_channel();
+        protocol.add_peer(addr("10.0.0.1:8333"), tx.clone()).await;
+        protocol.add_peer(addr("10.0.0.2:8333"), tx).await;
+        
+        // DoS scoring bans the peer...
+        protocol.update_peer_score(addr("10.0.0.1:8333"), DOS_BAN_THRESHOLD).await;
+        assert!(protocol.is_peer_banned(addr("10.0.0.1:8333")).await);
+        assert!(!protocol.is_peer_banned(addr("10.0.0.2:8333")).await);
+        
+        // ...propagation no longer selects it...
+        let item = GossipItem::new(GossipType::Transaction, vec![1, 2, 3], None);
+        let targets = protocol.select_gossip_peers(&item).await;
+        assert_eq!(targets, vec![addr("10.0.0.2:8333")]);
+        
+        // ...incoming gossip from it is refused, and stats count it once
+        assert!(protocol.process_incoming_item(addr("10.0.0.1:8333"), item).await.is_err());
+        assert_eq!(protocol.get_stats().await.banned_peers, 1);
+    }
+    
+    #[test]
+    async fn test_weighted_peer_selection() {
+        use rand::{rngs::StdRng, SeedableRng};
+        
+        let strong = addr("10.0.0.1:8333");
+        let weak: Vec<SocketAddr> = (2..=10).map(|i| addr(&format!("10.0.0.{}:8333", i))).collect();
+        let mut candidates = vec![(strong, 1.0)];
+        candidates.extend(weak.iter().map(|p| (*p, 0.1)));
+        
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut counts: HashMap<SocketAddr, usize> = HashMap::new();
+        for _ in 0..2000 {
+            let picked = weighted_peer_sample(candidates.clone(), 3, &mut rng);
+            assert_eq!(picked.len(), 3);
+            for peer in picked {
+                *counts.entry(peer).or_insert(0) += 1;
+            }
+        }
+        
+        // High-quality peer dominates, but every low-quality peer still gets picked
+        let strong_count = counts[&strong];
+        for peer in &weak {
+            let weak_count = counts.get(peer).copied().unwrap_or(0);
+            assert!(weak_count > 0, "peer {} never selected", peer);
+            assert!(strong_count > weak_count * 2);
+        }
+    }
+    
+    #[test]
+    async fn test_fanout_scales_with_network_size() {
+        let fanout = FanoutConfig::default();
+        
+        assert_eq!(fanout.fanout(0), MIN_GOSSIP_FANOUT);
+        assert_eq!(fanout.fanout(3), 2);
+        assert_eq!(fanout.fanout(10), 4);
+        assert_eq!(fanout.fanout(100), 10);
+        assert_eq!(fanout.fanout(10_000), MAX_GOSSIP_FANOUT);
+        
+        // Grows with the peer set, but much more slowly than it
+        let mut previous = 0;
+        for n in [16, 64, 256, 1024] {
+            let current = fanout.fanout(n);
+            assert!(current >= previous);
+            assert!(current * 4 <= n);
+            previous = current;
+        }
+        
+        let narrow = FanoutConfig { min: 3, max: 5 };
+        assert_eq!(narrow.fanout(1), 3);
+        assert_eq!(narrow.fanout(1000), 5);
+    }
+    
+    #[test]
+    async fn test_gossip_loop_dropped_and_counted() {
+        let protocol = test_protocol().await;
+        let (tx, _rx) = mpsc::unbounded_channel();
+        protocol.add_peer(addr("10.0.0.1:8333"), tx).await;
+        
+        // Originate an item locally, then receive it back from a peer
+        let item = GossipItem::new(GossipType::Transaction, vec![9, 9, 9], None);
+        protocol.queue_for_gossip(item.clone()).await.unwrap();
+        protocol.process_incoming_item(addr("10.0.0.1:8333"), item).await.unwrap();
+        
+        let stats = protocol.get_stats().await;
+        assert_eq!(stats.loop_events, 1);
+        assert_eq!(stats.incoming_queue_size, 0);
+    }
+    
+    #[test]
+    async fn test_no_echo_to_sender() {
+        let protocol = test_protocol().await;
+        let (tx, _rx) = mpsc::unbounded_channel();
+        protocol.add_peer(addr("10.0.0.1:8333"), tx.clone()).await;
+        protocol.add_peer(addr("10.0.0.2:8333"), tx).await;
+        
+        let item = GossipItem::new(GossipType::Transaction, vec![4, 5, 6], Some(addr("10.0.0.1:8333")));
+        let targets = protocol.select_gossip_peers(&item).await;
+        assert_eq!(targets, vec![addr("10.0.0.2:8333")]);
+    }
+    
+    fn tx_item(id: &str) -> GossipItem {
+        tx_item_with_fee(id, 10)
+    }
+    
+    fn tx_item_with_fee(id: &str, fee: u64) -> GossipItem {
+        let transaction = Transaction {
+            id: id.to_string(),
+            from: "alice".to_string(),
+            to: "bob".to_string(),
+            amount: 1000,
+            timestamp: chrono::Utc::now(),
+            signature: String::new(),
+            fee,
+        };
+        GossipItem::new(GossipType::Transaction, bincode::serialize(&transaction).unwrap(), None)
+    }
+    
+    #[test]
+    async fn test_tx_invs_wait_for_peer_timer() {
+        use rand::{rngs::StdRng, SeedableRng};
+        
+        let mut batcher = TxInvBatcher::new(Duration::from_secs(5));
+        let mut rng = StdRng::seed_from_u64(7);
+        let (peer, start) = (addr("10.0.0.1:8333"), Instant::now());
+        batcher.queue(peer, "a".to_string(), start, &mut rng);
+        let send_at = batcher.pending[&peer].1;
+        batcher.queue(peer, "b".to_string(), start + Duration::from_millis(10), &mut rng);
+        batcher.queue(peer, "a".to_string(), start + Duration::from_millis(20), &mut rng);
+        
+        // Later arrivals join the pending batch without resetting its timer
+        assert_eq!(batcher.pending[&peer].1, send_at);
+        assert_eq!(batcher.pending_for(peer), 2);
+        if send_at > start {
+            assert!(batcher.due(start).is_empty());
+        }
+        assert_eq!(batcher.due(send_at).len(), 1);
+        assert_eq!(batcher.pending_for(peer), 0);
+    }
+    
+    #[test]
+    async fn test_transactions_within_interval_share_one_inv() {
+        let protocol = test_protocol().await.with_tx_relay_interval(Duration::from_secs(1));
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let peers = [addr("10.0.0.1:8333"), addr("10.0.0.2:8333")];
+        for peer in peers {
+            protocol.add_peer(peer, tx.clone()).await;
+        }
+        
+        let txids = ["tx-1", "tx-2", "tx-3"];
+        {
+            let mut queue = protocol.outgoing_queue.lock().await;
+            for id in txids {
+                assert!(queue.push(tx_item(id)));
+            }
+        }
+        protocol.process_outgoing_queue().await.unwrap();
+        
+        let mut invs = protocol.flush_tx_announcements(Instant::now() + Duration::from_secs(3600)).await;
+        invs.sort_by_key(|(peer, _)| *peer);
+        assert_eq!(invs.iter().map(|(peer, _)| *peer).collect::<Vec<_>>(), peers);
+        for (_, msg) in invs {
+            let NetworkMessage::Inv { inventory } = msg else { panic!("expected inv, got {:?}", msg) };
+            let mut announced: Vec<_> = inventory.iter().map(|item| item.hash.as_str()).collect();
+            announced.sort();
+            assert_eq!(announced, txids);
+            assert!(inventory.iter().all(|item| item.inv_type == InventoryType::MsgTx));
+        }
+        
+        // Nothing left to announce until new transactions arrive
+        assert!(protocol.flush_tx_announcements(Instant::now() + Duration::from_secs(3600)).await.is_empty());
+    }
+    
+    async fn peers_knowing(protocol: &GossipProtocol, item_id: &str) -> usize {
+        protocol.peers.read().await.values().filter(|p| p.knows_item(item_id)).count()
+    }
+    
+    /// Accepts every transaction but reports a fixed relay fee floor
+    struct FeeFloorHandler(f64);
+    
+    #[async_trait]
+    impl TransactionHandler for FeeFloorHandler {
+        async fn handle_transaction(&self, _transaction: Transaction) -> Result<()> { Ok(()) }
+        async fn validate_transaction(&self, _transaction: &Transaction) -> Result<bool> { Ok(true) }
+        async fn relay_fee_floor(&self) -> f64 { self.0 }
+    }
+    
+    async fn fee_floor_protocol(floor: f64) -> GossipProtocol {
+        let chain_spec = Arc::new(ChainSpec::default());
+        let metrics = Arc::new(NetworkMetrics::new());
+        let security_manager = Arc::new(SecurityManager::new(chain_spec.clone(), metrics.clone()));
+        
+        GossipProtocol::new(
+            "test-node".to_string(),
+            chain_spec,
+            metrics,
+            security_manager,
+            Arc::new(NoopHandler),
+            Arc::new(FeeFloorHandler(floor)),
+        ).await.unwrap()
+    }
+    
+    #[test]
+    async fn test_below_floor_transaction_not_relayed() {
+        let protocol = fee_floor_protocol(1.0).await;
+        let sender = addr("10.0.0.1:8333");
+        let (tx, _rx) = mpsc::unbounded_channel();
+        protocol.add_peer(sender, tx.clone()).await;
+        protocol.add_peer(addr("10.0.0.2:8333"), tx).await;
+        
+        protocol.process_incoming_item(sender, tx_item_with_fee("cheap", 0)).await.unwrap();
+        protocol.process_incoming_queue().await.unwrap();
+        
+        assert!(protocol.outgoing_queue.lock().await.is_empty());
+        assert_eq!(protocol.peers.read().await[&sender].dos_score, Misbehavior::BelowFeeFloor.penalty());
+        
+        // A peer that keeps sending them is eventually banned
+        for i in 1..DOS_BAN_THRESHOLD / Misbehavior::BelowFeeFloor.penalty() {
+            protocol.process_incoming_item(sender, tx_item_with_fee(&format!("cheap-{}", i), 0)).await.unwrap();
+            protocol.process_incoming_queue().await.unwrap();
+        }
+        assert!(protocol.is_peer_banned(sender).await);
+        assert!(protocol.outgoing_queue.lock().await.is_empty());
+    }
+    
+    #[test]
+    async fn test_above_floor_transaction_relayed() {
+        let protocol = fee_floor_protocol(1.0).await;
+        let sender = addr("10.0.0.1:8333");
+        let (tx, _rx) = mpsc::unbounded_channel();
+        protocol.add_peer(sender, tx.clone()).await;
+        protocol.add_peer(addr("10.0.0.2:8333"), tx).await;
+        
+        let item = tx_item_with_fee("generous", 10_000);
+        let id = item.id.clone();
+        protocol.process_incoming_item(sender, item).await.unwrap();
+        protocol.process_incoming_queue().await.unwrap();
+        
+        let relayed = protocol.outgoing_queue.lock().await.pop().unwrap();
+        assert_eq!(relayed.id, id);
+        assert_eq!(relayed.origin_peer, Some(sender));
+        assert_eq!(protocol.peers.read().await[&sender].dos_score, 0);
+    }
+    
+    #[test]
+    async fn test_fee_floor_uses_mempool_fee_rate() {
+        let item = tx_item_with_fee("exact", 10_000);
+        let transaction: Transaction = codec::decode(&item.data).unwrap();
+        let protocol = fee_floor_protocol(fee_per_byte(10_000, serialized_size(&transaction))).await;
+        let sender = addr("10.0.0.1:8333");
+        let (tx, _rx) = mpsc::unbounded_channel();
+        protocol.add_peer(sender, tx.clone()).await;
+        protocol.add_peer(addr("10.0.0.2:8333"), tx).await;
+        
+        // Paying exactly the floor as the mempool rates it is enough
+        protocol.process_incoming_item(sender, item).await.unwrap();
+        protocol.process_incoming_queue().await.unwrap();
+        assert_eq!(protocol.outgoing_queue.lock().await.pop().unwrap().id, transaction.id);
+        
+        protocol.process_incoming_item(sender, tx_item_with_fee("short", 5_000)).await.unwrap();
+        protocol.process_incoming_queue().await.unwrap();
+        assert!(protocol.outgoing_queue.lock().await.is_empty());
+        assert_eq!(protocol.peers.read().await[&sender].dos_score, Misbehavior::BelowFeeFloor.penalty());
+    }
+    
+    #[test]
+    async fn test_stem_then_fluff_after_timer() {
+        let mut protocol = test_protocol().await;
+        protocol.stem_timeout = Duration::from_millis(50);
+        let (tx, _rx) = mpsc::unbounded_channel();
+        for i in 1..=9 {
+            protocol.add_peer(addr(&format!("10.0.0.{}:8333", i)), tx.clone()).await;
+        }
+        
+        let mut item = tx_item("stem-tx");
+        item.stem_hops = 2;
+        let id = item.id.clone();
+        protocol.queue_for_gossip(item).await.unwrap();
+        
+        // Stem phase: exactly one peer, and nothing left queued to broadcast
+        protocol.process_outgoing_queue().await.unwrap();
+        protocol.process_outgoing_queue().await.unwrap();
+        assert_eq!(peers_knowing(&protocol, &id).await, 1);
+        assert!(protocol.outgoing_queue.lock().await.is_empty());
+        
+        // Nobody fluffed it back to us, so we broadcast it ourselves
+        sleep(Duration::from_millis(80)).await;
+        protocol.process_outgoing_queue().await.unwrap();
+        assert!(peers_knowing(&protocol, &id).await >= 1 + FanoutConfig::default().fanout(9));
+        assert!(protocol.stem_pending.read().await.is_empty());
+    }
+    
+    #[test]
+    async fn test_fluffed_copy_cancels_stem_timer() {
+        let mut protocol = test_protocol().await;
+        protocol.stem_timeout = Duration::from_millis(50);
+        let (tx, _rx) = mpsc::unbounded_channel();
+        protocol.add_peer(addr("10.0.0.1:8333"), tx.clone()).await;
+        protocol.add_peer(addr("10.0.0.2:8333"), tx).await;
+        
+        let mut item = tx_item("echoed-tx");
+        item.stem_hops = 1;
+        protocol.queue_for_gossip(item.clone()).await.unwrap();
+        protocol.process_outgoing_queue().await.unwrap();
+        assert_eq!(protocol.stem_pending.read().await.len(), 1);
+        
+        item.stem_hops = 0;
+        protocol.process_incoming_item(addr("10.0.0.2:8333"), item).await.unwrap();
+        assert!(protocol.stem_pending.read().await.is_empty());
+    }
+    
+    fn block_item(previous_hash: &str, hash: &str) -> GossipItem {
+        let block = Block {
+            index: 1,
+            timestamp: chrono::Utc::now(),
+            transactions: vec![],
+            previous_hash: previous_hash.to_string(),
+            hash: hash.to_string(),
+            nonce: 0,
+            merkle_root: "0".to_string(),
+            difficulty: 1,
+        };
+        GossipItem::new(GossipType::Block, bincode::serialize(&block).unwrap(), None)
+    }
+    
+    #[test]
+    async fn test_tip_extending_block_processed_first() {
+        let protocol = test_protocol().await;
+        protocol.update_chain_tip("old".to_string()).await;
+        protocol.update_chain_tip("tip".to_string()).await;
+        
+        let peer = addr("10.0.0.1:8333");
+        let tx = GossipItem::new(GossipType::Transaction, vec![7, 7, 7], None);
+        let stale = block_item("old", "fork");
+        let orphan = block_item("nowhere", "orphan");
+        let extending = block_item("tip", "next");
+        
+        // Arrival order is the worst case for the tip-extending block
+        for item in [tx, orphan, stale, extending] {
+            protocol.process_incoming_item(peer, item).await.unwrap();
+        }
+        
+        let mut queue = protocol.incoming_queue.lock().await;
+        let order: Vec<u8> = std::iter::from_fn(|| queue.pop()).map(|i| i.priority).collect();
+        assert_eq!(order, vec![
+            ChainRelevance::ExtendsTip.priority(),
+            ChainRelevance::Recent.priority(),
+            GossipType::Transaction.priority(),
+            ChainRelevance::Unknown.priority(),
+        ]);
+    }
+    
+    /// Storage already holding the given chain, oldest first
+    struct SeededHandler(Vec<String>);
+    
+    #[async_trait]
+    impl BlockHandler for SeededHandler {
+        async fn handle_block(&self, _block: Block) -> Result<()> { Ok(()) }
+        async fn validate_block(&self, _block: &Block) -> Result<bool> { Ok(true) }
+        async fn recent_block_hashes(&self, count: usize) -> Result<Vec<String>> {
+            Ok(self.0[self.0.len().saturating_sub(count)..].to_vec())
+        }
+    }
+    
+    #[test]
+    async fn test_chain_view_seeded_from_stored_tip() {
+        let chain_spec = Arc::new(ChainSpec::default());
+        let metrics = Arc::new(NetworkMetrics::new());
+        let security_manager = Arc::new(SecurityManager::new(chain_spec.clone(), metrics.clone()));
+        let protocol = GossipProtocol::new(
+            "test-node".to_string(),
+            chain_spec,
+            metrics,
+            security_manager,
+            Arc::new(SeededHandler(vec!["old".to_string(), "tip".to_string()])),
+            Arc::new(NoopHandler),
+        ).await.unwrap();
+        
+        // No block has arrived yet, but the stored tip is already known
+        let view = protocol.chain_view.read().await;
+        assert_eq!(view.relevance("tip"), ChainRelevance::ExtendsTip);
+        assert_eq!(view.relevance("old"), ChainRelevance::Recent);
+        assert_eq!(view.relevance("nowhere"), ChainRelevance::Unknown);
+    }
+    
+    /// Accepts every block, counting validations, with storage holding
+    /// exactly the blocks it has handled
+    #[derive(Default)]
+    struct StoringHandler {
+        validations: std::sync::atomic::AtomicUsize,
+        stored: std::sync::Mutex<HashSet<String>>,
+    }
+    
+    #[async_trait]
+    impl BlockHandler for StoringHandler {
+        async fn handle_block(&self, block: Block) -> Result<()> {
+            self.stored.lock().unwrap().insert(block.hash);
+            Ok(())
+        }
+        async fn validate_block(&self, _block: &Block) -> Result<bool> {
+            self.validations.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(true)
+        }
+        async fn has_block(&self, hash: &str) -> Result<bool> {
+            Ok(self.stored.lock().unwrap().contains(hash))
+        }
+    }
+    
+    async fn storing_protocol(handler: Arc<StoringHandler>, data_dir: &Path) -> GossipProtocol {
+        let chain_spec = Arc::new(ChainSpec::default());
+        let metrics = Arc::new(NetworkMetrics::new());
+        let security_manager = Arc::new(SecurityManager::new(chain_spec.clone(), metrics.clone()));
+        
+        GossipProtocol::new(
+            "test-node".to_string(),
+            chain_spec,
+            metrics,
+            security_manager,
+            handler,
+            Arc::new(NoopHandler),
+        ).await.unwrap().with_seen_block_store(data_dir)
+    }
+    
+    #[test]
+    async fn test_known_block_not_revalidated_after_restart() {
+        let data_dir = tempfile::tempdir().unwrap();
+        let peer = addr("10.0.0.1:8333");
+        let handler = Arc::new(StoringHandler::default());
+        
+        let protocol = storing_protocol(handler.clone(), data_dir.path()).await;
+        protocol.process_incoming_item(peer, block_item("tip", "known")).await.unwrap();
+        protocol.process_incoming_queue().await.unwrap();
+        assert_eq!(handler.validations.load(std::sync::atomic::Ordering::SeqCst), 1);
+        protocol.save_seen_blocks().await.unwrap();
+        
+        // After a restart the in-memory dedup is empty but the filter is not
+        let restarted = storing_protocol(handler.clone(), data_dir.path()).await;
+        assert!(restarted.seen_items.read().await.is_empty());
+        restarted.process_incoming_item(peer, block_item("tip", "known")).await.unwrap();
+        restarted.process_incoming_queue().await.unwrap();
+        assert_eq!(handler.validations.load(std::sync::atomic::Ordering::SeqCst), 1);
+        
+        // A filter hit storage cannot confirm is validated as usual
+        handler.stored.lock().unwrap().clear();
+        let restarted = storing_protocol(handler.clone(), data_dir.path()).await;
+        restarted.process_incoming_item(peer, block_item("tip", "known")).await.unwrap();
+        restarted.process_incoming_queue().await.unwrap();
+        assert_eq!(handler.validations.load(std::sync::atomic::Ordering::SeqCst), 2);
+    }
 }
@@ -3,14 +3,17 @@
 
 use crate::block::Block;
 use crate::transaction::Transaction;
-use crate::blockchain::Blockchain;
+use crate::blockchain::{BlockValidationError, Blockchain};
 use crate::mempool::Mempool;
 use crate::network::gossip::*;
 use crate::network::{NetworkManager, ChainSpec, NetworkMetrics, SecurityManager};
 use anyhow::{Result, anyhow};
+use std::net::SocketAddr;
+use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use async_trait::async_trait;
+use qc_types::target::{compact_to_target, U256};
 
 /// Production block handler for gossip protocol
 pub struct ProductionBlockHandler {
@@ -40,11 +43,15 @@ impl BlockHandler for ProductionBlockHandler {
         
         let start = std::time::Instant::now();
         
-        // Add block to blockchain
-        {
-            let mut blockchain = self.blockchain.write().await;
-            blockchain.add_block(block.clone())?;
-        }
+        // Add block to blockchain; one that doesn't build on our tip counts
+        // towards the orphan rate the miner backs off on
+        let added = self.blockchain.write().await.add_block(block.clone());
+        let orphaned = matches!(
+            added,
+            Err(BlockValidationError::PrevHashMismatch { .. } | BlockValidationError::InvalidIndex { .. })
+        );
+        self.metrics.record_block_outcome(orphaned).await;
+        added?;
         
         // Update metrics
         let processing_time = start.elapsed().as_millis();
@@ -54,6 +61,16 @@ impl BlockHandler for ProductionBlockHandler {
         Ok(())
     }
     
+    async fn has_block(&self, hash: &str) -> Result<bool> {
+        Ok(self.blockchain.read().await.chain.iter().any(|block| block.hash == hash))
+    }
+    
+    async fn recent_block_hashes(&self, count: usize) -> Result<Vec<String>> {
+        let blockchain = self.blockchain.read().await;
+        let skip = blockchain.chain.len().saturating_sub(count);
+        Ok(blockchain.chain[skip..].iter().map(|block| block.hash.clone()).collect())
+    }
+    
     async fn validate_block(&self, block: &Block) -> Result<bool> {
         // Comprehensive block validation
         
@@ -118,13 +135,12 @@ impl BlockHandler for ProductionBlockHandler {
 impl ProductionBlockHandler {
     async fn validate_proof_of_work(&self, block: &Block) -> Result<bool> {
         // Validate that block hash meets difficulty target
-        let hash_bytes = hex::decode(&block.hash)
-            .map_err(|_| anyhow!("Invalid block hash format"))?;
+        let hash_bytes: [u8; 32] = hex::decode(&block.hash)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| anyhow!("Invalid block hash format"))?;
         
-        let target = self.calculate_target(block.bits);
-        let hash_value = bytes_to_u256(&hash_bytes);
-        
-        Ok(hash_value <= target)
+        Ok(U256::from_be_bytes(hash_bytes) <= compact_to_target(block.bits))
     }
     
     async fn validate_transaction_in_block(&self, tx: &Transaction, is_coinbase: bool) -> Result<bool> {
@@ -164,18 +180,6 @@ impl ProductionBlockHandler {
         Ok(block.bits == expected_bits)
     }
     
-    fn calculate_target(&self, bits: u32) -> u256 {
-        // Simplified difficulty target calculation
-        let exponent = ((bits >> 24) & 0xff) as usize;
-        let mantissa = bits & 0xffffff;
-        
-        if exponent <= 3 {
-            u256::from(mantissa >> (8 * (3 - exponent)))
-        } else {
-            u256::from(mantissa) << (8 * (exponent - 3))
-        }
-    }
-    
     fn calculate_merkle_root(&self, transactions: &[Transaction]) -> Result<String> {
         if transactions.is_empty() {
             return Ok("0".repeat(64));
@@ -206,65 +210,6 @@ impl ProductionBlockHandler {
     }
 }
 
-/// Simple U256 implementation for difficulty calculations
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-struct u256([u64; 4]);
-
-impl u256 {
-    fn from(val: u32) -> Self {
-        u256([val as u64, 0, 0, 0])
-    }
-}
-
-impl std::ops::Shl<usize> for u256 {
-    type Output = u256;
-    
-    fn shl(mut self, rhs: usize) -> Self::Output {
-        let word_shift = rhs / 64;
-        let bit_shift = rhs % 64;
-        
-        if word_shift >= 4 {
-            return u256([0, 0, 0, 0]);
-        }
-        
-        // Shift words
-        for i in (word_shift..4).rev() {
-            self.0[i] = if i >= word_shift { self.0[i - word_shift] } else { 0 };
-        }
-        for i in 0..word_shift {
-            self.0[i] = 0;
-        }
-        
-        // Shift bits
-        if bit_shift > 0 {
-            let mut carry = 0;
-            for i in 0..4 {
-                let new_carry = self.0[i] >> (64 - bit_shift);
-                self.0[i] = (self.0[i] << bit_shift) | carry;
-                carry = new_carry;
-            }
-        }
-        
-        self
-    }
-}
-
-fn bytes_to_u256(bytes: &[u8]) -> u256 {
-    let mut result = [0u64; 4];
-    
-    for (i, chunk) in bytes.chunks(8).enumerate() {
-        if i >= 4 { break; }
-        
-        let mut val = 0u64;
-        for (j, &byte) in chunk.iter().enumerate() {
-            val |= (byte as u64) << (8 * j);
-        }
-        result[i] = val;
-    }
-    
-    u256(result)
-}
-
 /// Production transaction handler for gossip protocol
 pub struct ProductionTransactionHandler {
     mempool: Arc<RwLock<Mempool>>,
@@ -357,16 +302,15 @@ impl TransactionHandler for ProductionTransactionHandler {
             return Ok(false);
         }
         
-        // Validate fee
-        if !self.validate_fee(transaction).await? {
-            log::debug!("Transaction {} rejected: insufficient fee", transaction.hash());
-            return Ok(false);
-        }
-        
-        // All validations passed
+        // All validations passed; the fee was checked against the relay
+        // floor before validation
         log::trace!("Transaction {} validation passed", transaction.hash());
         Ok(true)
     }
+    
+    async fn relay_fee_floor(&self) -> f64 {
+        self.mempool.read().await.relay_fee_floor()
+    }
 }
 
 impl ProductionTransactionHandler {
@@ -408,28 +352,6 @@ impl ProductionTransactionHandler {
         
         Ok(false) // No double spending
     }
-    
-    async fn validate_fee(&self, transaction: &Transaction) -> Result<bool> {
-        let total_input: u64 = transaction.inputs.iter()
-            .map(|input| input.amount)
-            .sum();
-        
-        let total_output: u64 = transaction.outputs.iter()
-            .map(|output| output.amount)
-            .sum();
-        
-        let fee = total_input.saturating_sub(total_output);
-        let min_fee = self.calculate_minimum_fee(transaction).await?;
-        
-        Ok(fee >= min_fee)
-    }
-    
-    async fn calculate_minimum_fee(&self, transaction: &Transaction) -> Result<u64> {
-        // Calculate fee based on transaction size
-        let tx_size = bincode::serialize(transaction)?.len() as u64;
-        let fee_rate = 10; // satoshis per byte
-        Ok(tx_size * fee_rate)
-    }
 }
 
 /// Gossip protocol manager for NetworkManager integration
@@ -447,6 +369,7 @@ impl GossipManager {
         security_manager: Arc<SecurityManager>,
         blockchain: Arc<RwLock<Blockchain>>,
         mempool: Arc<RwLock<Mempool>>,
+        data_dir: Option<&Path>,
     ) -> Result<Self> {
         // Create handlers
         let block_handler = Arc::new(ProductionBlockHandler::new(
@@ -471,6 +394,9 @@ impl GossipManager {
             block_handler.clone(),
             transaction_handler.clone(),
         ).await?;
+        if let Some(data_dir) = data_dir {
+            gossip_protocol = gossip_protocol.with_seen_block_store(data_dir);
+        }
         
         // Start the protocol
         gossip_protocol.start().await?;
@@ -483,15 +409,15 @@ impl GossipManager {
     }
     
     /// Add a peer to gossip with
-    pub async fn add_peer(&self, peer_id: String, sender: tokio::sync::mpsc::UnboundedSender<crate::network::protocol::NetworkMessage>) -> Result<()> {
+    pub async fn add_peer(&self, peer_id: SocketAddr, sender: tokio::sync::mpsc::UnboundedSender<crate::network::protocol::NetworkMessage>) -> Result<()> {
         self.gossip_protocol.gossip_tx.send(GossipCommand::AddPeer(peer_id, sender))
             .map_err(|_| anyhow!("Failed to add peer to gossip"))?;
         Ok(())
     }
     
     /// Remove a peer from gossip
-    pub async fn remove_peer(&self, peer_id: &str) -> Result<()> {
-        self.gossip_protocol.gossip_tx.send(GossipCommand::RemovePeer(peer_id.to_string()))
+    pub async fn remove_peer(&self, peer_id: SocketAddr) -> Result<()> {
+        self.gossip_protocol.gossip_tx.send(GossipCommand::RemovePeer(peer_id))
             .map_err(|_| anyhow!("Failed to remove peer from gossip"))?;
         Ok(())
     }
@@ -507,7 +433,7 @@ impl GossipManager {
     }
     
     /// Process incoming gossip from peer
-    pub async fn process_incoming_gossip(&self, peer_id: &str, item: GossipItem) -> Result<()> {
+    pub async fn process_incoming_gossip(&self, peer_id: SocketAddr, item: GossipItem) -> Result<()> {
         self.gossip_protocol.process_incoming_gossip(peer_id, item).await
     }
     
@@ -516,9 +442,9 @@ impl GossipManager {
         self.gossip_protocol.get_stats().await
     }
     
-    /// Update peer score for DoS protection
-    pub async fn update_peer_score(&self, peer_id: &str, delta: i32) -> Result<()> {
-        self.gossip_protocol.gossip_tx.send(GossipCommand::UpdatePeerScore(peer_id.to_string(), delta))
+    /// Penalise a peer for misbehaviour (DoS protection)
+    pub async fn punish(&self, peer_id: SocketAddr, misbehavior: Misbehavior) -> Result<()> {
+        self.gossip_protocol.gossip_tx.send(GossipCommand::Punish(peer_id, misbehavior))
             .map_err(|_| anyhow!("Failed to update peer score"))?;
         Ok(())
     }
@@ -536,11 +462,11 @@ impl GossipManager {
     }
     
     /// Handle flood attack by implementing emergency backpressure
-    pub async fn handle_flood_attack(&self, peer_id: &str) -> Result<()> {
+    pub async fn handle_flood_attack(&self, peer_id: SocketAddr) -> Result<()> {
         log::warn!("Flood attack detected from peer: {}", peer_id);
         
         // Immediately ban the peer
-        self.update_peer_score(peer_id, DOS_BAN_THRESHOLD).await?;
+        self.punish(peer_id, Misbehavior::Spam).await?;
         
         // Enable emergency backpressure
         let stats = self.get_stats().await;
@@ -589,13 +515,6 @@ mod tests {
     use super::*;
     use tokio::test;
     
-    #[test]
-    async fn test_u256_operations() {
-        let val = u256::from(1);
-        let shifted = val << 8;
-        assert!(shifted > val);
-    }
-    
     #[test] 
     async fn test_flood_resistance() {
         let resistance = FloodTestResistance::default();
@@ -607,4 +526,18 @@ mod tests {
         assert!(resistance.should_activate_backpressure(2000.0, 0.5));
         assert!(resistance.should_activate_backpressure(100.0, 0.9));
     }
+
+    #[test]
+    async fn test_unlinked_block_counts_as_orphan() {
+        let blockchain = Arc::new(RwLock::new(Blockchain::new()));
+        let metrics = Arc::new(NetworkMetrics::new());
+        let handler = ProductionBlockHandler::new(blockchain.clone(), metrics.clone(), "node".to_string());
+
+        let mut block = blockchain.read().await.get_latest_block().clone();
+        block.index += 1;
+        block.previous_hash = "ff".repeat(32);
+        assert!(handler.handle_block(block).await.is_err());
+
+        assert_eq!(metrics.orphan_rate().await, 1.0);
+    }
 }
@@ -0,0 +1,104 @@
+// Node identity keypair, persisted so the PeerId survives restarts
+use crate::quantum_crypto::{generate_keypair, sign_message, QuantumSignature};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// File in the data directory holding the node identity
+pub const IDENTITY_FILE: &str = "node_identity.json";
+
+/// Long-lived Dilithium2 keypair identifying this node to its peers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeIdentity {
+    pub public_key: String,
+    private_key: String,
+}
+
+impl NodeIdentity {
+    /// A fresh identity that is not persisted anywhere
+    pub fn generate() -> Self {
+        let (public_key, private_key) = generate_keypair();
+        Self { public_key, private_key }
+    }
+
+    /// Load the identity stored in `data_dir`, generating and saving one on
+    /// first run. A corrupt identity file is an error rather than silently
+    /// replaced, since that would change the PeerId.
+    pub fn load_or_generate(data_dir: &Path) -> Result<Self> {
+        let path = data_dir.join(IDENTITY_FILE);
+        if path.exists() {
+            let json = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read node identity {}", path.display()))?;
+            return serde_json::from_str(&json)
+                .with_context(|| format!("Invalid node identity {}", path.display()));
+        }
+
+        let identity = Self::generate();
+        fs::create_dir_all(data_dir)?;
+        // Owner-only from creation, so the private key is never readable by
+        // others, not even between writing it and tightening permissions
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        options
+            .open(&path)
+            .and_then(|mut file| file.write_all(serde_json::to_string_pretty(&identity)?.as_bytes()))
+            .with_context(|| format!("Failed to write node identity {}", path.display()))?;
+        log::info!("Generated node identity {}", identity.peer_id());
+        Ok(identity)
+    }
+
+    /// Stable peer identifier: the first 16 bytes of the public key's blake3 hash
+    pub fn peer_id(&self) -> String {
+        let public_key = hex::decode(&self.public_key).unwrap_or_default();
+        hex::encode(&blake3::hash(&public_key).as_bytes()[..16])
+    }
+
+    pub fn sign(&self, message: &[u8]) -> Result<QuantumSignature> {
+        sign_message(&self.private_key, message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_identity_survives_restart() -> Result<()> {
+        let data_dir = tempdir()?;
+        let first = NodeIdentity::load_or_generate(data_dir.path())?;
+        assert!(data_dir.path().join(IDENTITY_FILE).exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(data_dir.path().join(IDENTITY_FILE))?.permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let restarted = NodeIdentity::load_or_generate(data_dir.path())?;
+        assert_eq!(restarted.peer_id(), first.peer_id());
+        assert_eq!(restarted.public_key, first.public_key);
+
+        // Another data directory is another node
+        let other_dir = tempdir()?;
+        let other = NodeIdentity::load_or_generate(other_dir.path())?;
+        assert_ne!(other.peer_id(), first.peer_id());
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_identity_not_replaced() -> Result<()> {
+        let data_dir = tempdir()?;
+        fs::write(data_dir.path().join(IDENTITY_FILE), "not json")?;
+        assert!(NodeIdentity::load_or_generate(data_dir.path()).is_err());
+        assert_eq!(fs::read_to_string(data_dir.path().join(IDENTITY_FILE))?, "not json");
+        Ok(())
+    }
+}
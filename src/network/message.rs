@@ -1,4 +1,5 @@
 use crate::{Block, Transaction};
+use qc_types::codec;
 use serde::{Serialize, Deserialize};
 use std::net::SocketAddr;
 
@@ -59,7 +60,7 @@ impl NetworkMessage {
     }
     
     pub fn deserialize(data: &[u8]) -> Result<Self, bincode::Error> {
-        bincode::deserialize(data)
+        codec::decode(data)
     }
 }
 
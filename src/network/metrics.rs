@@ -1,6 +1,6 @@
 // Network metrics and monitoring
 use anyhow::Result;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::{mpsc, RwLock};
@@ -78,8 +78,13 @@ pub struct SyncMetrics {
     pub sync_errors: u64,
     pub peer_sync_scores: HashMap<String, f32>,
     pub reorg_count: u64,
+    /// Whether each recent gossiped block was orphaned, oldest first
+    pub recent_orphans: VecDeque<bool>,
 }
 
+/// Gossiped blocks the orphan rate is measured over
+pub const ORPHAN_RATE_WINDOW: usize = 100;
+
 #[derive(Debug, Default)]
 pub struct SystemMetrics {
     pub uptime: Duration,
@@ -236,6 +241,25 @@ impl NetworkMetrics {
         let _ = self.event_sender.send(MetricEvent::SyncProgress(progress)).await;
     }
 
+    /// Record whether a gossiped block connected or failed to build on our tip
+    pub async fn record_block_outcome(&self, orphaned: bool) {
+        let mut sync = self.sync.write().await;
+        sync.recent_orphans.push_back(orphaned);
+        if sync.recent_orphans.len() > ORPHAN_RATE_WINDOW {
+            sync.recent_orphans.pop_front();
+        }
+    }
+
+    /// Share of the last `ORPHAN_RATE_WINDOW` gossiped blocks that were orphaned
+    pub async fn orphan_rate(&self) -> f64 {
+        let sync = self.sync.read().await;
+        if sync.recent_orphans.is_empty() {
+            return 0.0;
+        }
+        let orphaned = sync.recent_orphans.iter().filter(|&&orphaned| orphaned).count();
+        orphaned as f64 / sync.recent_orphans.len() as f64
+    }
+
     pub async fn record_dns_discovery(&self, address_count: usize, duration: Duration) {
         let mut performance = self.performance.write().await;
         performance.dns_resolution_time = duration;
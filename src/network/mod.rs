@@ -5,21 +5,24 @@ pub mod discovery;
 pub mod transport;
 pub mod peer_manager;
 pub mod protocol;
+pub mod compression;
 pub mod security;
 pub mod metrics;
 pub mod nat;
 pub mod gossip;
 pub mod gossip_integration;
+pub mod identity;
+pub mod seen_blocks;
 
 use crate::blockchain::Blockchain;
 use crate::mempool::Mempool;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use anyhow::Result;
-use uuid::Uuid;
 
 pub use discovery::*;
 pub use transport::*;
@@ -30,11 +33,15 @@ pub use metrics::*;
 pub use nat::*;
 pub use gossip::*;
 pub use gossip_integration::*;
+pub use identity::*;
+pub use seen_blocks::*;
 
 /// Production network manager for QuantumCoin
 #[derive(Clone)]
 pub struct NetworkManager {
+    /// Peer id derived from `identity`
     pub node_id: String,
+    pub identity: Arc<NodeIdentity>,
     pub chain_spec: Arc<ChainSpec>,
     pub blockchain: Arc<RwLock<Blockchain>>,
     pub mempool: Arc<RwLock<Mempool>>,
@@ -80,14 +87,40 @@ impl Default for ChainSpec {
 }
 
 impl NetworkManager {
+    /// A network node with a throwaway identity; see `with_data_dir` for
+    /// one whose PeerId survives restarts
     pub async fn new(
         listen_addr: SocketAddr,
         blockchain: Arc<RwLock<Blockchain>>,
         mempool: Arc<RwLock<Mempool>>,
         chain_spec: Option<ChainSpec>,
+    ) -> Result<Self> {
+        Self::with_identity(listen_addr, blockchain, mempool, chain_spec, NodeIdentity::generate(), None).await
+    }
+
+    /// A network node using the identity persisted in `data_dir`, created
+    /// there on first run, and remembering validated blocks across restarts
+    pub async fn with_data_dir(
+        listen_addr: SocketAddr,
+        blockchain: Arc<RwLock<Blockchain>>,
+        mempool: Arc<RwLock<Mempool>>,
+        chain_spec: Option<ChainSpec>,
+        data_dir: &Path,
+    ) -> Result<Self> {
+        let identity = NodeIdentity::load_or_generate(data_dir)?;
+        Self::with_identity(listen_addr, blockchain, mempool, chain_spec, identity, Some(data_dir)).await
+    }
+
+    async fn with_identity(
+        listen_addr: SocketAddr,
+        blockchain: Arc<RwLock<Blockchain>>,
+        mempool: Arc<RwLock<Mempool>>,
+        chain_spec: Option<ChainSpec>,
+        identity: NodeIdentity,
+        data_dir: Option<&Path>,
     ) -> Result<Self> {
         let chain_spec = Arc::new(chain_spec.unwrap_or_default());
-        let node_id = Uuid::new_v4().to_string();
+        let node_id = identity.peer_id();
         
         let metrics = Arc::new(NetworkMetrics::new());
         let security_manager = Arc::new(SecurityManager::new(chain_spec.clone(), metrics.clone()));
@@ -113,10 +146,12 @@ impl NetworkManager {
             security_manager.clone(),
             blockchain.clone(),
             mempool.clone(),
+            data_dir,
         ).await?);
 
         Ok(Self {
             node_id,
+            identity: Arc::new(identity),
             chain_spec,
             blockchain,
             mempool,
@@ -235,17 +270,17 @@ impl NetworkManager {
     }
     
     /// Process incoming gossip from a peer
-    pub async fn process_incoming_gossip(&self, peer_id: &str, item: GossipItem) -> Result<()> {
+    pub async fn process_incoming_gossip(&self, peer_id: SocketAddr, item: GossipItem) -> Result<()> {
         self.gossip_manager.process_incoming_gossip(peer_id, item).await
     }
     
     /// Handle flood attack detection
-    pub async fn handle_flood_attack(&self, peer_id: &str) -> Result<()> {
+    pub async fn handle_flood_attack(&self, peer_id: SocketAddr) -> Result<()> {
         log::warn!("Flood attack detected from peer: {}", peer_id);
         self.gossip_manager.handle_flood_attack(peer_id).await?;
         
-        // Also ban at security manager level
-        self.security_manager.ban_peer(peer_id).await?;
+        // Also ban at the connection level, keyed on the same address
+        self.peer_manager.ban_peer(peer_id, "flood attack".to_string(), std::time::Duration::from_secs(3600)).await;
         
         Ok(())
     }
@@ -96,7 +96,7 @@ impl Peer {
                 return Err(anyhow!("Checksum mismatch"));
             }
             
-            let message = NetworkMessage::deserialize(&payload)?;
+            let message = NetworkMessage::deserialize(&payload)?.decompress()?;
             self.info.last_seen = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
             
             Ok(message)
@@ -1,7 +1,8 @@
 // Comprehensive peer management with scoring and DoS protection
 use crate::network::{ChainSpec, SecurityManager, SecureTransport, NetworkMetrics, SecureConnection};
-use crate::network::protocol::{NetworkMessage, ProtocolVersion};
+use crate::network::protocol::{NetworkMessage, ProtocolVersion, NODE_COMPRESSION, NODE_NETWORK};
 use anyhow::Result;
+use rand::Rng;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::net::SocketAddr;
 use std::sync::Arc;
@@ -22,6 +23,7 @@ pub struct PeerManager {
     connection_pool: Arc<RwLock<ConnectionPool>>,
     message_queue: Arc<RwLock<VecDeque<PendingMessage>>>,
     sync_state: Arc<RwLock<SyncState>>,
+    reconnect_backoff: Arc<RwLock<ReconnectBackoff>>,
     shutdown_signal: mpsc::Sender<()>,
 }
 
@@ -121,6 +123,125 @@ pub struct SyncState {
     pub sync_progress: f32,
 }
 
+/// First retry delay after an outbound peer drops or a dial fails
+pub const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(5);
+/// Retry delays stop doubling here
+pub const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30 * 60);
+/// Consecutive dial failures before a peer is retired
+pub const MAX_RECONNECT_FAILURES: u32 = 8;
+
+/// Per-address reconnection schedule for outbound peers.
+/// Each consecutive failure doubles the delay up to `max_delay`; the delay
+/// actually used is drawn from its upper half so peers that dropped together
+/// don't all redial together. Peers that exceed `max_failures` move to the
+/// tried-but-failed bucket and are not redialed.
+#[derive(Debug)]
+pub struct ReconnectBackoff {
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    pub max_failures: u32,
+    pending: HashMap<SocketAddr, ReconnectState>,
+    failed: HashSet<SocketAddr>,
+}
+
+#[derive(Debug, Clone)]
+struct ReconnectState {
+    failures: u32,
+    next_attempt: Instant,
+}
+
+impl ReconnectBackoff {
+    pub fn new(base_delay: Duration, max_delay: Duration, max_failures: u32) -> Self {
+        Self {
+            base_delay,
+            max_delay,
+            max_failures,
+            pending: HashMap::new(),
+            failed: HashSet::new(),
+        }
+    }
+
+    /// Un-jittered delay after `failures` consecutive failures
+    fn delay_for(&self, failures: u32) -> Duration {
+        let factor = 1u32.checked_shl(failures.min(31)).unwrap_or(u32::MAX);
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+
+    fn jittered<R: Rng>(&self, failures: u32, rng: &mut R) -> Duration {
+        let delay = self.delay_for(failures);
+        let half = delay / 2;
+        half + rng.gen_range(Duration::ZERO..=delay - half)
+    }
+
+    /// Schedule a redial of a peer that just disconnected, keeping its
+    /// failure count
+    pub fn schedule<R: Rng>(&mut self, addr: SocketAddr, now: Instant, rng: &mut R) -> Option<Duration> {
+        if self.failed.contains(&addr) {
+            return None;
+        }
+        let failures = self.pending.get(&addr).map_or(0, |state| state.failures);
+        let delay = self.jittered(failures, rng);
+        self.pending.insert(addr, ReconnectState { failures, next_attempt: now + delay });
+        Some(delay)
+    }
+
+    /// Record a failed dial. Returns the delay before the next attempt, or
+    /// `None` once the peer has been retired.
+    pub fn record_failure<R: Rng>(&mut self, addr: SocketAddr, now: Instant, rng: &mut R) -> Option<Duration> {
+        if self.failed.contains(&addr) {
+            return None;
+        }
+        let failures = self.pending.get(&addr).map_or(0, |state| state.failures) + 1;
+        if failures > self.max_failures {
+            self.pending.remove(&addr);
+            self.failed.insert(addr);
+            return None;
+        }
+        let delay = self.jittered(failures, rng);
+        self.pending.insert(addr, ReconnectState { failures, next_attempt: now + delay });
+        Some(delay)
+    }
+
+    /// Forget the schedule once a connection succeeds
+    pub fn record_success(&mut self, addr: SocketAddr) {
+        self.pending.remove(&addr);
+    }
+
+    /// Whether `addr` may be dialed at `now`
+    pub fn can_attempt(&self, addr: SocketAddr, now: Instant) -> bool {
+        !self.failed.contains(&addr)
+            && self.pending.get(&addr).is_none_or(|state| now >= state.next_attempt)
+    }
+
+    /// Scheduled peers whose delay has elapsed
+    pub fn due(&self, now: Instant) -> Vec<SocketAddr> {
+        self.pending
+            .iter()
+            .filter(|(_, state)| now >= state.next_attempt)
+            .map(|(addr, _)| *addr)
+            .collect()
+    }
+
+    pub fn failures(&self, addr: SocketAddr) -> u32 {
+        self.pending.get(&addr).map_or(0, |state| state.failures)
+    }
+
+    pub fn is_retired(&self, addr: SocketAddr) -> bool {
+        self.failed.contains(&addr)
+    }
+
+    /// The tried-but-failed bucket
+    pub fn retired(&self) -> impl Iterator<Item = &SocketAddr> {
+        self.failed.iter()
+    }
+}
+
+impl Default for ReconnectBackoff {
+    fn default() -> Self {
+        Self::new(RECONNECT_BASE_DELAY, RECONNECT_MAX_DELAY, MAX_RECONNECT_FAILURES)
+    }
+}
+
 impl PeerManager {
     pub fn new(
         chain_spec: Arc<ChainSpec>,
@@ -154,6 +275,7 @@ impl PeerManager {
                 blocks_downloaded: 0,
                 sync_progress: 0.0,
             })),
+            reconnect_backoff: Arc::new(RwLock::new(ReconnectBackoff::default())),
             shutdown_signal: tx,
         }
     }
@@ -197,6 +319,17 @@ impl PeerManager {
             return Err(anyhow::anyhow!("Peer {} is banned", addr));
         }
         
+        // Respect the reconnection schedule
+        {
+            let backoff = self.reconnect_backoff.read().await;
+            if backoff.is_retired(addr) {
+                return Err(anyhow::anyhow!("Peer {} exceeded {} reconnection attempts", addr, backoff.max_failures));
+            }
+            if !backoff.can_attempt(addr, Instant::now()) {
+                return Err(anyhow::anyhow!("Peer {} is backing off", addr));
+            }
+        }
+        
         // Check connection limits
         if !self.can_connect_outbound().await {
             return Err(anyhow::anyhow!("Maximum outbound connections reached"));
@@ -210,6 +343,7 @@ impl PeerManager {
         match connection_result {
             Ok(connection) => {
                 self.on_connection_established(addr, ConnectionType::Outbound, connection).await?;
+                self.reconnect_backoff.write().await.record_success(addr);
                 Ok(())
             }
             Err(e) => {
@@ -304,7 +438,7 @@ impl PeerManager {
     async fn initiate_peer_handshake(&self, addr: SocketAddr) -> Result<()> {
         let version_message = NetworkMessage::Version {
             version: self.chain_spec.protocol_version,
-            services: 1, // NODE_NETWORK
+            services: NODE_NETWORK | NODE_COMPRESSION,
             timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
             user_agent: "QuantumCoin/2.0.0".to_string(),
             start_height: 0, // Will be filled by blockchain
@@ -314,8 +448,55 @@ impl PeerManager {
         self.send_message_to_peer(addr, version_message).await
     }
 
+    /// Record what a peer advertised in its version message
+    pub async fn on_peer_version(
+        &self,
+        addr: SocketAddr,
+        version: u32,
+        services: u64,
+        user_agent: String,
+        start_height: u64,
+    ) {
+        if let Some(peer) = self.peers.write().await.get_mut(&addr) {
+            peer.protocol_version = version;
+            peer.services = services;
+            peer.user_agent = user_agent;
+            peer.height = start_height;
+        }
+    }
+
+    /// Decode a frame read from `addr`, unwrapping compression, and apply
+    /// the handshake messages the peer manager owns. The decoded message is
+    /// returned for the caller to dispatch further.
+    pub async fn receive_message(&self, addr: SocketAddr, data: &[u8]) -> Result<NetworkMessage> {
+        let message = NetworkMessage::deserialize(data)?.decompress()?;
+        if let Some(peer) = self.peers.write().await.get_mut(&addr) {
+            peer.last_seen = Instant::now();
+        }
+        
+        match &message {
+            NetworkMessage::Version { version, services, user_agent, start_height, .. } => {
+                self.on_peer_version(addr, *version, *services, user_agent.clone(), *start_height).await;
+                self.send_message_to_peer(addr, NetworkMessage::VerAck).await?;
+            }
+            NetworkMessage::VerAck => {
+                if let Some(peer) = self.peers.write().await.get_mut(&addr) {
+                    if peer.state == PeerState::Handshaking {
+                        peer.state = PeerState::Ready;
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(message)
+    }
+
     /// Send message to specific peer
     pub async fn send_message_to_peer(&self, addr: SocketAddr, message: NetworkMessage) -> Result<()> {
+        // Compress large payloads for peers that negotiated it
+        let services = self.peers.read().await.get(&addr).map(|p| p.services).unwrap_or(0);
+        let message = message.compress_for(services)?;
+        
         // Serialize message
         let data = message.serialize()?;
         
@@ -463,10 +644,26 @@ impl PeerManager {
         
         // Update connection pool
         let mut pool = self.connection_pool.write().await;
-        if let Some(slot) = pool.connection_slots.remove(&addr) {
-            match slot.connection_type {
-                ConnectionType::Inbound => pool.current_inbound -= 1,
-                ConnectionType::Outbound => pool.current_outbound -= 1,
+        let was_outbound = match pool.connection_slots.remove(&addr) {
+            Some(slot) => match slot.connection_type {
+                ConnectionType::Inbound => {
+                    pool.current_inbound -= 1;
+                    false
+                }
+                ConnectionType::Outbound => {
+                    pool.current_outbound -= 1;
+                    true
+                }
+            },
+            None => false,
+        };
+        drop(pool);
+        
+        // Redial dropped outbound peers later rather than immediately
+        if was_outbound && !self.is_peer_banned(addr).await {
+            let delay = self.reconnect_backoff.write().await.schedule(addr, Instant::now(), &mut rand::thread_rng());
+            if let Some(delay) = delay {
+                log::debug!("Will reconnect to {} in {:?}", addr, delay);
             }
         }
         
@@ -499,6 +696,12 @@ impl PeerManager {
             score.reliability_score *= 0.9; // Reduce reliability
             score.overall_score = ((score.reliability_score * 100.0) as i32).max(0);
         }
+        
+        let retry = self.reconnect_backoff.write().await.record_failure(addr, Instant::now(), &mut rand::thread_rng());
+        match retry {
+            Some(delay) => log::debug!("Will retry {} in {:?}", addr, delay),
+            None => log::info!("Giving up on peer {} after repeated connection failures", addr),
+        }
     }
 
     /// Maintenance loops
@@ -553,6 +756,12 @@ impl PeerManager {
             
             if current_outbound < target_outbound {
                 log::debug!("Need more outbound connections: {} < {}", current_outbound, target_outbound);
+                
+                // Redial dropped peers whose backoff has elapsed
+                let due = self.reconnect_backoff.read().await.due(Instant::now());
+                for addr in due.into_iter().take(target_outbound - current_outbound) {
+                    let _ = self.try_connect_to_peer(addr).await;
+                }
                 // Trigger peer discovery
             }
         }
@@ -648,7 +857,139 @@ impl Clone for PeerManager {
             connection_pool: self.connection_pool.clone(),
             message_queue: self.message_queue.clone(),
             sync_state: self.sync_state.clone(),
+            reconnect_backoff: self.reconnect_backoff.clone(),
             shutdown_signal: self.shutdown_signal.clone(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_compressed_version_recorded_on_receive() {
+        let chain_spec = Arc::new(ChainSpec::default());
+        let metrics = Arc::new(NetworkMetrics::new());
+        let security_manager = Arc::new(SecurityManager::new(chain_spec.clone(), metrics.clone()));
+        let transport = Arc::new(SecureTransport::new(chain_spec.clone(), metrics.clone()).await.unwrap());
+        let manager = PeerManager::new(chain_spec, security_manager, transport, metrics);
+        
+        let peer = addr("10.0.0.1:8333");
+        manager.peers.write().await.insert(peer, Peer {
+            address: peer,
+            node_id: String::new(),
+            protocol_version: 0,
+            services: 0,
+            user_agent: String::new(),
+            height: 0,
+            connected_at: Instant::now(),
+            last_seen: Instant::now(),
+            connection_type: ConnectionType::Outbound,
+            state: PeerState::Handshaking,
+        });
+        
+        // Long enough that the sender compresses it
+        let version = NetworkMessage::Version {
+            version: 70015,
+            services: NODE_NETWORK | NODE_COMPRESSION,
+            timestamp: 0,
+            user_agent: "QuantumCoin/2.0.0 ".repeat(200),
+            start_height: 1_234,
+            relay: true,
+        }.compress_for(NODE_COMPRESSION).unwrap();
+        assert!(matches!(version, NetworkMessage::Compressed { .. }));
+        
+        let received = manager.receive_message(peer, &version.serialize().unwrap()).await.unwrap();
+        assert!(matches!(received, NetworkMessage::Version { start_height: 1_234, .. }));
+        {
+            let peers = manager.peers.read().await;
+            assert_eq!(peers[&peer].height, 1_234);
+            assert_eq!(peers[&peer].services, NODE_NETWORK | NODE_COMPRESSION);
+        }
+        assert!(matches!(manager.message_queue.read().await[0].message, NetworkMessage::VerAck));
+        
+        manager.receive_message(peer, &NetworkMessage::VerAck.serialize().unwrap()).await.unwrap();
+        assert_eq!(manager.peers.read().await[&peer].state, PeerState::Ready);
+    }
+
+    #[test]
+    fn test_reconnect_delay_grows_with_jitter() {
+        let mut backoff = ReconnectBackoff::new(Duration::from_secs(1), Duration::from_secs(60), 20);
+        let mut rng = StdRng::seed_from_u64(7);
+        let peer = addr("10.0.0.1:8333");
+        let start = Instant::now();
+
+        let delays: Vec<Duration> = (0..10)
+            .map(|_| backoff.record_failure(peer, start, &mut rng).unwrap())
+            .collect();
+
+        for (i, delay) in delays.iter().enumerate() {
+            let ceiling = Duration::from_secs(1 << (i + 1)).min(Duration::from_secs(60));
+            assert!(*delay >= ceiling / 2 && *delay <= ceiling, "attempt {} delay {:?}", i, delay);
+        }
+        // Ranges of successive uncapped attempts only touch at their edges
+        assert!(delays[..5].windows(2).all(|w| w[1] >= w[0]));
+        assert!(delays[4] > delays[0] * 4);
+        // Capped at the max interval
+        assert!(delays[9] <= Duration::from_secs(60));
+
+        // Jitter: two peers failing in lockstep don't get identical schedules
+        let other = addr("10.0.0.2:8333");
+        let other_delays: Vec<Duration> = (0..10)
+            .map(|_| backoff.record_failure(other, start, &mut rng).unwrap())
+            .collect();
+        assert_ne!(delays, other_delays);
+
+        // Not dialable until the delay has elapsed
+        assert!(!backoff.can_attempt(peer, start));
+        assert!(backoff.can_attempt(peer, start + delays[9]));
+        assert_eq!(backoff.due(start + Duration::from_secs(60)).len(), 2);
+    }
+
+    #[test]
+    fn test_peer_retired_after_failure_cap() {
+        let mut backoff = ReconnectBackoff::new(Duration::from_secs(1), Duration::from_secs(60), 3);
+        let mut rng = StdRng::seed_from_u64(1);
+        let peer = addr("10.0.0.1:8333");
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(backoff.record_failure(peer, now, &mut rng).is_some());
+        }
+        assert!(!backoff.is_retired(peer));
+
+        assert_eq!(backoff.record_failure(peer, now, &mut rng), None);
+        assert!(backoff.is_retired(peer));
+        assert_eq!(backoff.retired().collect::<Vec<_>>(), vec![&peer]);
+        assert!(!backoff.can_attempt(peer, now + Duration::from_secs(3600)));
+        assert!(backoff.due(now + Duration::from_secs(3600)).is_empty());
+        assert_eq!(backoff.schedule(peer, now, &mut rng), None);
+    }
+
+    #[test]
+    fn test_success_resets_backoff() {
+        let mut backoff = ReconnectBackoff::new(Duration::from_secs(1), Duration::from_secs(60), 3);
+        let mut rng = StdRng::seed_from_u64(3);
+        let peer = addr("10.0.0.1:8333");
+        let now = Instant::now();
+
+        backoff.record_failure(peer, now, &mut rng);
+        backoff.record_failure(peer, now, &mut rng);
+        assert_eq!(backoff.failures(peer), 2);
+
+        backoff.record_success(peer);
+        assert_eq!(backoff.failures(peer), 0);
+        assert!(backoff.can_attempt(peer, now));
+
+        // A clean disconnect waits the base delay before redialing
+        let delay = backoff.schedule(peer, now, &mut rng).unwrap();
+        assert!(delay >= Duration::from_millis(500) && delay <= Duration::from_secs(1));
+        assert_eq!(backoff.failures(peer), 0);
+    }
+}
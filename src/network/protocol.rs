@@ -1,7 +1,9 @@
 // Network protocol messages and versioning
 use crate::block::Block;
+use crate::network::compression::{compress_payload, decompress_payload, MAX_DECOMPRESSED_SIZE};
 use crate::transaction::Transaction;
 use anyhow::Result;
+use qc_types::codec;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::SocketAddr;
@@ -10,6 +12,11 @@ use std::net::SocketAddr;
 pub const PROTOCOL_VERSION: u32 = 70015;
 pub const MIN_PROTOCOL_VERSION: u32 = 70010;
 
+/// Service bits advertised in the version handshake
+pub const NODE_NETWORK: u64 = 1;
+/// Peer accepts zstd-compressed messages
+pub const NODE_COMPRESSION: u64 = 1 << 3;
+
 /// Network protocol messages for QuantumCoin P2P
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum NetworkMessage {
@@ -108,6 +115,12 @@ pub enum NetworkMessage {
         command: String,
         payload: Vec<u8>,
     },
+    
+    // zstd-compressed bincode of another message; only sent to peers
+    // advertising NODE_COMPRESSION. Appended to keep existing variant tags stable.
+    Compressed {
+        payload: Vec<u8>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -294,7 +307,7 @@ impl NetworkMessage {
         }
         
         // Deserialize payload
-        let message: NetworkMessage = bincode::deserialize(payload)?;
+        let message: NetworkMessage = codec::decode(payload)?;
         Ok(message)
     }
     
@@ -325,6 +338,7 @@ impl NetworkMessage {
             NetworkMessage::QuantumProof { .. } => "qproof",
             NetworkMessage::Alert { .. } => "alert",
             NetworkMessage::Unknown { command, .. } => command,
+            NetworkMessage::Compressed { .. } => "compressed",
         };
         
         let mut cmd = [0u8; 12];
@@ -334,6 +348,34 @@ impl NetworkMessage {
         cmd
     }
     
+    /// Wrap the message in `Compressed` if the peer supports it and it's worth it
+    pub fn compress_for(self, peer_services: u64) -> Result<Self> {
+        if peer_services & NODE_COMPRESSION == 0 || matches!(self, NetworkMessage::Compressed { .. }) {
+            return Ok(self);
+        }
+        
+        let raw = bincode::serialize(&self)?;
+        Ok(match compress_payload(&raw)? {
+            Some(payload) => NetworkMessage::Compressed { payload },
+            None => self,
+        })
+    }
+    
+    /// Unwrap a `Compressed` message; other messages are returned unchanged
+    pub fn decompress(self) -> Result<Self> {
+        match self {
+            NetworkMessage::Compressed { payload } => {
+                let raw = decompress_payload(&payload, MAX_DECOMPRESSED_SIZE)?;
+                let inner: NetworkMessage = codec::decode(&raw)?;
+                if matches!(inner, NetworkMessage::Compressed { .. }) {
+                    return Err(anyhow::anyhow!("Nested compressed message"));
+                }
+                Ok(inner)
+            }
+            other => Ok(other),
+        }
+    }
+    
     /// Check if message is critical for network operation
     pub fn is_critical(&self) -> bool {
         matches!(self,
@@ -504,4 +546,31 @@ mod tests {
         assert_eq!(protocol.get_state(), ProtocolState::Ready);
         assert!(protocol.is_ready());
     }
+    
+    #[test]
+    fn test_compression_negotiated_by_service_bits() {
+        let msg = NetworkMessage::QuantumProof { proof_data: vec![7u8; 16 * 1024] };
+        
+        let plain = msg.clone().compress_for(NODE_NETWORK).unwrap();
+        assert!(matches!(plain, NetworkMessage::QuantumProof { .. }));
+        
+        let compressed = msg.compress_for(NODE_NETWORK | NODE_COMPRESSION).unwrap();
+        let NetworkMessage::Compressed { payload } = &compressed else {
+            panic!("large payload should be compressed");
+        };
+        assert!(payload.len() < 1024);
+        
+        let framed = compressed.serialize().unwrap();
+        let restored = NetworkMessage::deserialize(&framed).unwrap().decompress().unwrap();
+        match restored {
+            NetworkMessage::QuantumProof { proof_data } => assert_eq!(proof_data, vec![7u8; 16 * 1024]),
+            other => panic!("Wrong message type: {:?}", other),
+        }
+    }
+    
+    #[test]
+    fn test_small_message_not_compressed() {
+        let msg = NetworkMessage::Ping { nonce: 1 }.compress_for(NODE_COMPRESSION).unwrap();
+        assert!(matches!(msg, NetworkMessage::Ping { nonce: 1 }));
+    }
 }
@@ -0,0 +1,187 @@
+// Compact persistent record of recently validated blocks, so a restarted
+// node can skip re-validating blocks it is offered again
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// File in the data directory holding the seen-block filter
+pub const SEEN_BLOCKS_FILE: &str = "seen_blocks.bin";
+
+/// Blocks each filter generation holds before it is rotated out
+pub const DEFAULT_SEEN_BLOCK_CAPACITY: usize = 10_000;
+
+/// Filter bits per block; with `SEEN_BLOCK_PROBES` this gives roughly a 1%
+/// false positive rate at capacity
+const BITS_PER_BLOCK: usize = 10;
+const SEEN_BLOCK_PROBES: u64 = 7;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Bloom {
+    bits: Vec<u64>,
+    count: usize,
+}
+
+impl Bloom {
+    fn new(capacity: usize) -> Self {
+        Self { bits: vec![0; (capacity.max(1) * BITS_PER_BLOCK).div_ceil(64)], count: 0 }
+    }
+
+    /// Bit positions for `block_hash`, by double hashing its blake3 digest.
+    /// None for a filter without bits, which only a bad file can produce.
+    fn positions(&self, block_hash: &str) -> Vec<usize> {
+        if self.bits.is_empty() {
+            return Vec::new();
+        }
+        let digest = blake3::hash(block_hash.as_bytes());
+        let bytes = digest.as_bytes();
+        let h1 = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+        let h2 = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+        let len = (self.bits.len() * 64) as u64;
+        (0..SEEN_BLOCK_PROBES)
+            .map(|i| (h1.wrapping_add(i.wrapping_mul(h2)) % len) as usize)
+            .collect()
+    }
+
+    fn insert(&mut self, block_hash: &str) {
+        for bit in self.positions(block_hash) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+        self.count += 1;
+    }
+
+    fn contains(&self, block_hash: &str) -> bool {
+        let positions = self.positions(block_hash);
+        !positions.is_empty() && positions.into_iter().all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+}
+
+/// Bloom filter over validated block hashes, kept in two generations so the
+/// most recent `capacity` to `2 * capacity` blocks are remembered without the
+/// false positive rate growing. A hit only means "probably validated" and
+/// must be confirmed against block storage; a miss is definite.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SeenBlockFilter {
+    capacity: usize,
+    current: Bloom,
+    previous: Bloom,
+}
+
+impl SeenBlockFilter {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, current: Bloom::new(capacity), previous: Bloom::new(capacity) }
+    }
+
+    pub fn insert(&mut self, block_hash: &str) {
+        if self.current.count >= self.capacity {
+            self.previous = std::mem::replace(&mut self.current, Bloom::new(self.capacity));
+        }
+        self.current.insert(block_hash);
+    }
+
+    pub fn might_contain(&self, block_hash: &str) -> bool {
+        self.current.contains(block_hash) || self.previous.contains(block_hash)
+    }
+
+    /// Load the filter saved at `path`. A missing or unreadable file only
+    /// costs re-validation, so it yields an empty filter instead of an error.
+    pub fn load(path: &Path, capacity: usize) -> Self {
+        let Ok(data) = fs::read(path) else {
+            return Self::new(capacity);
+        };
+        match bincode::deserialize::<Self>(&data) {
+            Ok(filter) if filter.capacity == capacity && filter.sized_for_capacity() => filter,
+            Ok(filter) if filter.capacity == capacity => {
+                log::warn!("Ignoring malformed seen-block filter {}", path.display());
+                Self::new(capacity)
+            }
+            Ok(_) => {
+                log::info!("Seen-block filter {} has a different capacity, starting afresh", path.display());
+                Self::new(capacity)
+            }
+            Err(e) => {
+                log::warn!("Ignoring corrupt seen-block filter {}: {}", path.display(), e);
+                Self::new(capacity)
+            }
+        }
+    }
+
+    /// Whether both generations have the bits `capacity` calls for
+    fn sized_for_capacity(&self) -> bool {
+        let bits = Bloom::new(self.capacity).bits.len();
+        self.current.bits.len() == bits && self.previous.bits.len() == bits
+    }
+
+    /// Write the filter to `path`, replacing any earlier copy atomically
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, bincode::serialize(self)?)
+            .with_context(|| format!("Failed to write seen-block filter {}", tmp.display()))?;
+        fs::rename(&tmp, path)
+            .with_context(|| format!("Failed to replace seen-block filter {}", path.display()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn block_hash(i: usize) -> String {
+        hex::encode(blake3::hash(&i.to_le_bytes()).as_bytes())
+    }
+
+    #[test]
+    fn test_restart_loaded_filter_has_no_false_negatives() -> Result<()> {
+        let data_dir = tempdir()?;
+        let path = data_dir.path().join(SEEN_BLOCKS_FILE);
+        let mut filter = SeenBlockFilter::new(1_000);
+        for i in 0..1_500 {
+            filter.insert(&block_hash(i));
+        }
+        filter.save(&path)?;
+
+        let restarted = SeenBlockFilter::load(&path, 1_000);
+        assert!((0..1_500).all(|i| restarted.might_contain(&block_hash(i))));
+
+        // Unseen blocks mostly miss, so storage is rarely consulted for them
+        let false_positives = (10_000..20_000).filter(|&i| restarted.might_contain(&block_hash(i))).count();
+        assert!(false_positives < 500, "{} false positives", false_positives);
+        Ok(())
+    }
+
+    #[test]
+    fn test_old_generation_rotated_out() {
+        let mut filter = SeenBlockFilter::new(100);
+        for i in 0..300 {
+            filter.insert(&block_hash(i));
+        }
+        // The newest full generation is always remembered
+        assert!((200..300).all(|i| filter.might_contain(&block_hash(i))));
+        let forgotten = (0..100).filter(|&i| !filter.might_contain(&block_hash(i))).count();
+        assert!(forgotten > 90);
+    }
+
+    #[test]
+    fn test_missing_or_corrupt_file_starts_empty() -> Result<()> {
+        let data_dir = tempdir()?;
+        let path = data_dir.path().join(SEEN_BLOCKS_FILE);
+        assert!(!SeenBlockFilter::load(&path, 100).might_contain(&block_hash(0)));
+
+        fs::write(&path, b"garbage")?;
+        assert!(!SeenBlockFilter::load(&path, 100).might_contain(&block_hash(0)));
+
+        // Well-formed bincode with no filter bits must not divide by zero
+        let mut empty = SeenBlockFilter::new(100);
+        empty.current.bits.clear();
+        empty.previous.bits.clear();
+        assert!(!empty.might_contain(&block_hash(0)));
+        empty.insert(&block_hash(0));
+        empty.save(&path)?;
+        let mut loaded = SeenBlockFilter::load(&path, 100);
+        loaded.insert(&block_hash(1));
+        assert!(loaded.might_contain(&block_hash(1)));
+        Ok(())
+    }
+}
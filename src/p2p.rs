@@ -1,11 +1,14 @@
 use anyhow::{Result, Context};
+use qc_types::codec;
+use quantumcoin_p2p::{MessageId, PropagationManager, PropagationStats};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, AsyncReadExt};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{broadcast, mpsc, RwLock};
 use tokio::time::{interval, timeout};
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
@@ -14,7 +17,7 @@ use crate::{
     block::Block,
     blockchain::Blockchain,
     database::BlockchainDatabase,
-    mempool::Mempool,
+    mempool::{DoubleSpendAlert, Mempool, MempoolEvent},
     transaction::SignedTransaction,
 };
 
@@ -33,36 +36,99 @@ pub const CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
 /// Ping interval
 pub const PING_INTERVAL: Duration = Duration::from_secs(30);
 
+/// Largest framed message accepted from a peer
+pub const MAX_MESSAGE_SIZE: usize = 32 * 1024 * 1024;
+
 /// Peer timeout (no activity)
 pub const PEER_TIMEOUT: Duration = Duration::from_secs(120);
 
-/// P2P message types
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Window over which peers' bandwidth use is compared
+pub const BANDWIDTH_WINDOW: Duration = Duration::from_secs(60);
+
+/// Bytes per window any peer may use before share limits apply
+pub const MIN_BANDWIDTH_BUDGET: u64 = 4 * 1024 * 1024;
+
+/// Largest fraction of the window's total traffic a single peer may take
+pub const MAX_BANDWIDTH_SHARE: f64 = 0.5;
+
+/// P2P message types. On the wire each is its `u32` discriminant, the same
+/// width bincode gives an enum tag, so existing types keep their encoding.
+/// New types take the next unused number; never renumber or reuse one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
 pub enum MessageType {
     /// Version handshake
-    Version,
-    VerAck,
+    Version = 0,
+    VerAck = 1,
     
     /// Ping/Pong for keepalive
-    Ping,
-    Pong,
+    Ping = 2,
+    Pong = 3,
     
     /// Block and transaction propagation
-    NewBlock,
-    NewTransaction,
-    GetBlocks,
-    BlockResponse,
-    GetMempool,
-    MempoolResponse,
+    NewBlock = 4,
+    NewTransaction = 5,
+    GetBlocks = 6,
+    BlockResponse = 7,
+    GetMempool = 8,
+    MempoolResponse = 9,
     
     /// Peer discovery
-    GetPeers,
-    PeersResponse,
+    GetPeers = 10,
+    PeersResponse = 11,
     
     /// Blockchain synchronization
-    GetHeaders,
-    HeadersResponse,
-    GetBlock,
+    GetHeaders = 12,
+    HeadersResponse = 13,
+    GetBlock = 14,
+
+    /// Two conflicting transaction ids, for zero-conf awareness
+    DoubleSpendAlert = 15,
+
+    /// A type introduced after this node was built. Decoded rather than
+    /// rejected so the message can be ignored; never sent.
+    Unknown = u32::MAX,
+}
+
+impl MessageType {
+    const KNOWN: [MessageType; 16] = [
+        MessageType::Version,
+        MessageType::VerAck,
+        MessageType::Ping,
+        MessageType::Pong,
+        MessageType::NewBlock,
+        MessageType::NewTransaction,
+        MessageType::GetBlocks,
+        MessageType::BlockResponse,
+        MessageType::GetMempool,
+        MessageType::MempoolResponse,
+        MessageType::GetPeers,
+        MessageType::PeersResponse,
+        MessageType::GetHeaders,
+        MessageType::HeadersResponse,
+        MessageType::GetBlock,
+        MessageType::DoubleSpendAlert,
+    ];
+
+    /// The type with wire discriminant `code`, or `Unknown`
+    pub fn from_code(code: u32) -> Self {
+        Self::KNOWN
+            .into_iter()
+            .find(|kind| *kind as u32 == code)
+            .unwrap_or(MessageType::Unknown)
+    }
+}
+
+impl Serialize for MessageType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_u32(*self as u32)
+    }
+}
+
+impl<'de> Deserialize<'de> for MessageType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        u32::deserialize(deserializer).map(MessageType::from_code)
+    }
 }
 
 /// P2P network message
@@ -102,7 +168,12 @@ impl P2PMessage {
     }
     
     pub fn deserialize(data: &[u8]) -> Result<Self> {
-        bincode::deserialize(data).context("Failed to deserialize P2P message")
+        codec::decode(data).context("Failed to deserialize P2P message")
+    }
+
+    /// Size of the message as framed on the wire
+    pub fn wire_size(&self) -> u64 {
+        bincode::serialized_size(self).unwrap_or(self.payload.len() as u64)
     }
 }
 
@@ -117,6 +188,32 @@ pub struct VersionMessage {
     pub relay: bool,
 }
 
+/// Bytes exchanged with a peer, split by message type
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BandwidthUsage {
+    pub sent: HashMap<MessageType, u64>,
+    pub received: HashMap<MessageType, u64>,
+}
+
+impl BandwidthUsage {
+    pub fn sent_for(&self, message_type: MessageType) -> u64 {
+        self.sent.get(&message_type).copied().unwrap_or(0)
+    }
+
+    pub fn received_for(&self, message_type: MessageType) -> u64 {
+        self.received.get(&message_type).copied().unwrap_or(0)
+    }
+
+    fn merge_into(&self, sent: &mut HashMap<MessageType, u64>, received: &mut HashMap<MessageType, u64>) {
+        for (&message_type, &bytes) in &self.sent {
+            *sent.entry(message_type).or_insert(0) += bytes;
+        }
+        for (&message_type, &bytes) in &self.received {
+            *received.entry(message_type).or_insert(0) += bytes;
+        }
+    }
+}
+
 /// Peer information
 #[derive(Debug, Clone)]
 pub struct PeerInfo {
@@ -126,7 +223,13 @@ pub struct PeerInfo {
     pub connected_at: SystemTime,
     pub bytes_sent: u64,
     pub bytes_received: u64,
+    pub bandwidth: BandwidthUsage,
     pub is_outbound: bool,
+    /// Chain length the peer has claimed, from its version handshake and
+    /// raised by the blocks it relays
+    pub best_height: Option<u64>,
+    window_start: SystemTime,
+    window_bytes: u64,
 }
 
 impl PeerInfo {
@@ -139,13 +242,50 @@ impl PeerInfo {
             connected_at: now,
             bytes_sent: 0,
             bytes_received: 0,
+            bandwidth: BandwidthUsage::default(),
             is_outbound,
+            best_height: None,
+            window_start: now,
+            window_bytes: 0,
         }
     }
     
     pub fn is_timeout(&self) -> bool {
         SystemTime::now().duration_since(self.last_seen).unwrap_or_default() > PEER_TIMEOUT
     }
+
+    pub fn record_sent(&mut self, message: &P2PMessage) {
+        let bytes = message.wire_size();
+        self.bytes_sent += bytes;
+        *self.bandwidth.sent.entry(message.message_type).or_insert(0) += bytes;
+        self.charge_window(bytes);
+    }
+
+    pub fn record_received(&mut self, message: &P2PMessage) {
+        let bytes = message.wire_size();
+        self.bytes_received += bytes;
+        *self.bandwidth.received.entry(message.message_type).or_insert(0) += bytes;
+        self.last_seen = SystemTime::now();
+        self.charge_window(bytes);
+    }
+
+    /// Bytes exchanged in the current bandwidth window, both directions
+    pub fn window_bytes(&self) -> u64 {
+        if SystemTime::now().duration_since(self.window_start).unwrap_or_default() > BANDWIDTH_WINDOW {
+            0
+        } else {
+            self.window_bytes
+        }
+    }
+
+    fn charge_window(&mut self, bytes: u64) {
+        let now = SystemTime::now();
+        if now.duration_since(self.window_start).unwrap_or_default() > BANDWIDTH_WINDOW {
+            self.window_start = now;
+            self.window_bytes = 0;
+        }
+        self.window_bytes += bytes;
+    }
 }
 
 /// P2P Network Node
@@ -177,6 +317,9 @@ pub struct P2PNode {
     
     /// Running state
     is_running: Arc<RwLock<bool>>,
+
+    /// Block propagation latency tracking
+    propagation: Arc<PropagationManager>,
 }
 
 impl P2PNode {
@@ -198,10 +341,12 @@ impl P2PNode {
             mempool,
             node_id: Uuid::new_v4(),
             is_running: Arc::new(RwLock::new(false)),
+            propagation: Arc::new(PropagationManager::new()),
         }
     }
     
     pub async fn set_database(&self, database: BlockchainDatabase) {
+        self.mempool.write().await.set_utxo_set(&database.get_utxo_set().await);
         let mut db_guard = self.database.write().await;
         *db_guard = Some(database);
     }
@@ -222,6 +367,7 @@ impl P2PNode {
         // Start background tasks
         self.start_message_handler().await;
         self.start_peer_maintenance().await;
+        self.start_double_spend_relay().await;
         
         // Accept incoming connections
         loop {
@@ -315,15 +461,94 @@ impl P2PNode {
         debug!("Broadcasting {:?} to {} peers", message.message_type, peers.len());
         
         for peer_addr in peers {
-            if let Err(e) = self.message_tx.send((peer_addr, message.clone())) {
+            if let Err(e) = self.send_to_peer(peer_addr, message.clone()).await {
                 error!("Failed to send message to {}: {}", peer_addr, e);
             }
         }
     }
+
+    /// Queue a message for a peer, charging it to the peer's bandwidth
+    pub async fn send_to_peer(&self, addr: SocketAddr, message: P2PMessage) -> Result<()> {
+        if let Some(peer) = self.peers.write().await.get_mut(&addr) {
+            peer.record_sent(&message);
+        }
+        self.message_tx.send((addr, message))?;
+        Ok(())
+    }
+
+    /// Account for a message read from a peer.
+    ///
+    /// Returns false when the peer is using a disproportionate share of our
+    /// bandwidth, in which case the caller should drop the message.
+    pub async fn record_received(&self, addr: SocketAddr, message: &P2PMessage) -> bool {
+        Self::account_received(&self.peers, &self.propagation, addr, message).await
+    }
+
+    async fn account_received(
+        peers: &Arc<RwLock<HashMap<SocketAddr, PeerInfo>>>,
+        propagation: &PropagationManager,
+        addr: SocketAddr,
+        message: &P2PMessage,
+    ) -> bool {
+        if message.message_type == MessageType::NewBlock {
+            Self::track_block_relay(propagation, addr, &message.payload).await;
+        }
+
+        let mut peers = peers.write().await;
+        match peers.get_mut(&addr) {
+            Some(peer) => peer.record_received(message),
+            None => return true,
+        }
+
+        if Self::exceeds_bandwidth_share(&peers, addr) {
+            warn!("Peer {} exceeds its bandwidth share, dropping {:?}", addr, message.message_type);
+            return false;
+        }
+        true
+    }
+
+    /// Whether a peer is taking more than its share of the current window
+    fn exceeds_bandwidth_share(peers: &HashMap<SocketAddr, PeerInfo>, addr: SocketAddr) -> bool {
+        let Some(peer) = peers.get(&addr) else { return false };
+        let used = peer.window_bytes();
+        if used <= MIN_BANDWIDTH_BUDGET || peers.len() < 2 {
+            return false;
+        }
+        let total: u64 = peers.values().map(|p| p.window_bytes()).sum();
+        used as f64 > total as f64 * MAX_BANDWIDTH_SHARE
+    }
+
+    /// A peer relaying a block we already know has acknowledged it; a block
+    /// we haven't seen starts its propagation clock instead
+    async fn track_block_relay(propagation: &PropagationManager, addr: SocketAddr, payload: &[u8]) {
+        let id = MessageId::new(payload);
+        let now = SystemTime::now();
+        if !propagation.record_block_ack(&id, addr, now).await {
+            propagation.record_block_seen(id, payload.len(), now).await;
+        }
+    }
+
+    /// Block propagation latency histograms in Prometheus text format
+    pub async fn propagation_metrics(&self) -> String {
+        self.propagation.export_prometheus().await
+    }
+
+    /// Per-peer byte counters, split by message type
+    pub async fn peer_bandwidth(&self) -> HashMap<SocketAddr, BandwidthUsage> {
+        self.peers
+            .read()
+            .await
+            .iter()
+            .map(|(&addr, peer)| (addr, peer.bandwidth.clone()))
+            .collect()
+    }
     
     /// Broadcast new block
     pub async fn broadcast_block(&self, block: &Block) -> Result<()> {
         let payload = bincode::serialize(block)?;
+        self.propagation
+            .record_block_seen(MessageId::new(&payload), payload.len(), SystemTime::now())
+            .await;
         self.broadcast(MessageType::NewBlock, payload).await;
         Ok(())
     }
@@ -344,8 +569,7 @@ impl P2PNode {
             peers.insert(addr, peer_info);
         }
         
-        // TODO: Handle connection protocol
-        // For now, just add to peers list
+        self.spawn_read_loop(stream, addr);
         debug!("Added incoming peer {}", addr);
     }
     
@@ -358,6 +582,8 @@ impl P2PNode {
             peers.insert(addr, peer_info);
         }
         
+        self.spawn_read_loop(stream, addr);
+        
         // Send version handshake
         if let Err(e) = self.send_version_handshake(addr).await {
             error!("Failed to send version to {}: {}", addr, e);
@@ -366,6 +592,59 @@ impl P2PNode {
         debug!("Added outgoing peer {}", addr);
     }
     
+    /// Handle messages from a peer's connection until it closes
+    fn spawn_read_loop(&self, stream: TcpStream, addr: SocketAddr) {
+        let peers = Arc::clone(&self.peers);
+        let propagation = Arc::clone(&self.propagation);
+        let blockchain = Arc::clone(&self.blockchain);
+        let mempool = Arc::clone(&self.mempool);
+        let database = Arc::clone(&self.database);
+        
+        tokio::spawn(async move {
+            Self::read_loop(stream, addr, &peers, &propagation, &blockchain, &mempool, &database).await;
+            peers.write().await.remove(&addr);
+            info!("Peer {} disconnected", addr);
+        });
+    }
+    
+    /// Read length-prefixed messages, charging each to the peer's bandwidth
+    /// before handling it
+    async fn read_loop<R: AsyncRead + Unpin>(
+        mut reader: R,
+        addr: SocketAddr,
+        peers: &Arc<RwLock<HashMap<SocketAddr, PeerInfo>>>,
+        propagation: &PropagationManager,
+        blockchain: &Arc<RwLock<Blockchain>>,
+        mempool: &Arc<RwLock<Mempool>>,
+        database: &Arc<RwLock<Option<BlockchainDatabase>>>,
+    ) {
+        loop {
+            let message = match Self::read_message(&mut reader).await {
+                Ok(message) => message,
+                Err(e) => {
+                    debug!("Stopped reading from {}: {}", addr, e);
+                    return;
+                }
+            };
+            if !Self::account_received(peers, propagation, addr, &message).await {
+                continue;
+            }
+            if let Err(e) = Self::handle_message(addr, message, peers, blockchain, mempool, database).await {
+                error!("Error handling message from {}: {}", addr, e);
+            }
+        }
+    }
+    
+    async fn read_message<R: AsyncRead + Unpin>(reader: &mut R) -> Result<P2PMessage> {
+        let len = reader.read_u32_le().await? as usize;
+        if len > MAX_MESSAGE_SIZE {
+            anyhow::bail!("Message of {} bytes exceeds {}", len, MAX_MESSAGE_SIZE);
+        }
+        let mut frame = vec![0u8; len];
+        reader.read_exact(&mut frame).await?;
+        P2PMessage::deserialize(&frame)
+    }
+    
     /// Send version handshake
     async fn send_version_handshake(&self, addr: SocketAddr) -> Result<()> {
         let blockchain_height = {
@@ -388,8 +667,7 @@ impl P2PNode {
         let payload = bincode::serialize(&version_msg)?;
         let message = P2PMessage::new(MessageType::Version, payload);
         
-        self.message_tx.send((addr, message))?;
-        Ok(())
+        self.send_to_peer(addr, message).await
     }
     
     /// Start message handler task
@@ -400,13 +678,14 @@ impl P2PNode {
         };
         
         if let Some(mut message_rx) = message_rx {
+            let peers = Arc::clone(&self.peers);
             let blockchain = Arc::clone(&self.blockchain);
             let mempool = Arc::clone(&self.mempool);
             let database = Arc::clone(&self.database);
             
             tokio::spawn(async move {
                 while let Some((addr, message)) = message_rx.recv().await {
-                    if let Err(e) = Self::handle_message(addr, message, &blockchain, &mempool, &database).await {
+                    if let Err(e) = Self::handle_message(addr, message, &peers, &blockchain, &mempool, &database).await {
                         error!("Error handling message from {}: {}", addr, e);
                     }
                 }
@@ -418,6 +697,7 @@ impl P2PNode {
     async fn handle_message(
         addr: SocketAddr,
         message: P2PMessage,
+        peers: &Arc<RwLock<HashMap<SocketAddr, PeerInfo>>>,
         blockchain: &Arc<RwLock<Blockchain>>,
         mempool: &Arc<RwLock<Mempool>>,
         database: &Arc<RwLock<Option<BlockchainDatabase>>>,
@@ -431,14 +711,22 @@ impl P2PNode {
         
         match message.message_type {
             MessageType::Version => {
-                let version_msg: VersionMessage = bincode::deserialize(&message.payload)?;
+                let version_msg: VersionMessage = codec::decode(&message.payload)?;
                 info!("Peer {} version: {}", addr, version_msg.user_agent);
+                if let Some(peer) = peers.write().await.get_mut(&addr) {
+                    peer.best_height = Some(version_msg.start_height);
+                    peer.version = Some(version_msg);
+                }
                 // TODO: Send VerAck
             }
             
             MessageType::NewBlock => {
-                let block: Block = bincode::deserialize(&message.payload)?;
+                let block: Block = codec::decode(&message.payload)?;
                 info!("Received new block {} from {}", block.hash, addr);
+                // Heights count blocks, like the handshake's start_height
+                if let Some(peer) = peers.write().await.get_mut(&addr) {
+                    peer.best_height = Some(peer.best_height.unwrap_or(0).max(block.index + 1));
+                }
                 
                 // Validate and add block
                 let mut blockchain_guard = blockchain.write().await;
@@ -452,12 +740,14 @@ impl P2PNode {
                         if let Err(e) = db.store_block(&block, &[]).await {
                             error!("Failed to store block in database: {}", e);
                         }
+                        // Pooled fees are valued against the confirmed outputs
+                        mempool.write().await.set_utxo_set(&db.get_utxo_set().await);
                     }
                 }
             }
             
             MessageType::NewTransaction => {
-                let transaction: SignedTransaction = bincode::deserialize(&message.payload)?;
+                let transaction: SignedTransaction = codec::decode(&message.payload)?;
                 info!("Received new transaction {} from {}", transaction.id, addr);
                 
                 // Add to mempool
@@ -467,6 +757,14 @@ impl P2PNode {
                 }
             }
             
+            MessageType::DoubleSpendAlert => {
+                let alert: DoubleSpendAlert = codec::decode(&message.payload)?;
+                warn!(
+                    "Peer {} reports double spend: {} conflicts with {}",
+                    addr, alert.conflicting, alert.original
+                );
+            }
+            
             MessageType::GetBlocks => {
                 // TODO: Send blocks to peer
                 debug!("Peer {} requested blocks", addr);
@@ -477,6 +775,11 @@ impl P2PNode {
                 debug!("Ping from {}", addr);
             }
             
+            MessageType::Unknown => {
+                // Newer peers may speak types we don't; that is not misbehaviour
+                debug!("Ignoring message of unknown type from {}", addr);
+            }
+            
             _ => {
                 debug!("Unhandled message type {:?} from {}", message.message_type, addr);
             }
@@ -485,6 +788,32 @@ impl P2PNode {
         Ok(())
     }
     
+    /// Relay every double-spend the mempool detects to all peers
+    async fn start_double_spend_relay(&self) {
+        let mut events = self.mempool.read().await.subscribe();
+        let peers = Arc::clone(&self.peers);
+        let message_tx = self.message_tx.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let alert = match events.recv().await {
+                    Ok(MempoolEvent::DoubleSpend(alert)) => alert,
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                warn!("Double spend: {} conflicts with {}", alert.conflicting, alert.original);
+                let Ok(payload) = bincode::serialize(&alert) else { continue };
+                let message = P2PMessage::new(MessageType::DoubleSpendAlert, payload);
+                for (addr, peer) in peers.write().await.iter_mut() {
+                    peer.record_sent(&message);
+                    if let Err(e) = message_tx.send((*addr, message.clone())) {
+                        error!("Failed to relay double-spend alert to {}: {}", addr, e);
+                    }
+                }
+            }
+        });
+    }
+
     /// Start peer maintenance task
     async fn start_peer_maintenance(&self) {
         let peers = Arc::clone(&self.peers);
@@ -542,10 +871,21 @@ impl P2PNode {
         });
     }
     
+    /// Chain lengths connected peers have claimed, for sync status
+    pub async fn peer_heights(&self) -> Vec<u64> {
+        self.peers.read().await.values().filter_map(|peer| peer.best_height).collect()
+    }
+
     /// Get network statistics
     pub async fn get_stats(&self) -> NetworkStats {
         let peers_guard = self.peers.read().await;
         let known_peers_guard = self.known_peers.read().await;
+
+        let mut bytes_sent_by_type = HashMap::new();
+        let mut bytes_received_by_type = HashMap::new();
+        for peer in peers_guard.values() {
+            peer.bandwidth.merge_into(&mut bytes_sent_by_type, &mut bytes_received_by_type);
+        }
         
         NetworkStats {
             connected_peers: peers_guard.len(),
@@ -554,6 +894,9 @@ impl P2PNode {
             outbound_peers: peers_guard.values().filter(|p| p.is_outbound).count(),
             total_bytes_sent: peers_guard.values().map(|p| p.bytes_sent).sum(),
             total_bytes_received: peers_guard.values().map(|p| p.bytes_received).sum(),
+            bytes_sent_by_type,
+            bytes_received_by_type,
+            block_propagation: self.propagation.stats().await,
         }
     }
 }
@@ -567,11 +910,16 @@ pub struct NetworkStats {
     pub outbound_peers: usize,
     pub total_bytes_sent: u64,
     pub total_bytes_received: u64,
+    pub bytes_sent_by_type: HashMap<MessageType, u64>,
+    pub bytes_received_by_type: HashMap<MessageType, u64>,
+    /// Peer acknowledgement latencies of relayed blocks, by size class
+    pub block_propagation: PropagationStats,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mempool::MempoolPolicy;
     use tokio::time::sleep;
     
     #[tokio::test]
@@ -588,6 +936,60 @@ mod tests {
         assert!(deserialized.verify_checksum());
     }
     
+    #[tokio::test]
+    async fn test_unknown_message_type_decoded_and_ignored() {
+        let message = P2PMessage::new(MessageType::DoubleSpendAlert, vec![9; 16]);
+        let mut bytes = message.serialize().unwrap();
+        // The type tag follows the four magic bytes
+        assert_eq!(bytes[4..8], 15u32.to_le_bytes());
+        bytes[4..8].copy_from_slice(&4_000u32.to_le_bytes());
+
+        let decoded = P2PMessage::deserialize(&bytes).unwrap();
+        assert_eq!(decoded.message_type, MessageType::Unknown);
+        assert_eq!(decoded.payload, message.payload);
+        assert_eq!(MessageType::from_code(MessageType::NewBlock as u32), MessageType::NewBlock);
+
+        // Handling it is not an error, so the sender keeps its connection
+        let node = test_node();
+        let addr = "127.0.0.1:8334".parse().unwrap();
+        add_test_peer(&node, addr).await;
+        P2PNode::handle_message(addr, decoded, &node.peers, &node.blockchain, &node.mempool, &node.database).await.unwrap();
+        assert!(node.peers.read().await.contains_key(&addr));
+    }
+
+    #[tokio::test]
+    async fn test_sync_needs_peers_agreeing_on_tip() {
+        use qc_validation::sync_status::{sync_status, SyncStatus};
+
+        let node = test_node();
+        let ours = node.blockchain.read().await.chain.len() as u64;
+        let announce = |height: u64| {
+            let version = VersionMessage {
+                protocol_version: PROTOCOL_VERSION,
+                services: 1,
+                timestamp: 12345,
+                user_agent: "Test/1.0".to_string(),
+                start_height: height,
+                relay: true,
+            };
+            P2PMessage::new(MessageType::Version, bincode::serialize(&version).unwrap())
+        };
+
+        // A single peer agreeing on our tip is not enough
+        let first: SocketAddr = "127.0.0.1:8340".parse().unwrap();
+        add_test_peer(&node, first).await;
+        P2PNode::handle_message(first, announce(ours), &node.peers, &node.blockchain, &node.mempool, &node.database).await.unwrap();
+        assert_eq!(node.peer_heights().await, vec![ours]);
+        assert_eq!(sync_status(ours, &node.peer_heights().await, 3), SyncStatus::Degraded);
+
+        for port in [8341, 8342] {
+            let addr: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+            add_test_peer(&node, addr).await;
+            P2PNode::handle_message(addr, announce(ours), &node.peers, &node.blockchain, &node.mempool, &node.database).await.unwrap();
+        }
+        assert_eq!(sync_status(ours, &node.peer_heights().await, 3), SyncStatus::Healthy);
+    }
+
     #[tokio::test]
     async fn test_version_message() {
         let version = VersionMessage {
@@ -614,4 +1016,141 @@ mod tests {
         assert!(peer.is_outbound);
         assert!(!peer.is_timeout());
     }
+
+    fn test_node() -> P2PNode {
+        P2PNode::new(
+            "127.0.0.1:0".parse().unwrap(),
+            Arc::new(RwLock::new(Blockchain::new())),
+            Arc::new(RwLock::new(Mempool::new(MempoolPolicy { max_count: 1000, ..MempoolPolicy::default() }))),
+        )
+    }
+
+    async fn add_test_peer(node: &P2PNode, addr: SocketAddr) {
+        node.peers.write().await.insert(addr, PeerInfo::new(addr, true));
+    }
+
+    #[tokio::test]
+    async fn test_sent_bytes_counted_per_type() {
+        let node = test_node();
+        let addr: SocketAddr = "127.0.0.1:8333".parse().unwrap();
+        add_test_peer(&node, addr).await;
+
+        let block = P2PMessage::new(MessageType::NewBlock, vec![0; 500]);
+        let ping = P2PMessage::new(MessageType::Ping, vec![]);
+        let (block_size, ping_size) = (block.wire_size(), ping.wire_size());
+
+        node.send_to_peer(addr, block).await.unwrap();
+        node.send_to_peer(addr, ping.clone()).await.unwrap();
+        node.send_to_peer(addr, ping).await.unwrap();
+
+        let usage = &node.peer_bandwidth().await[&addr];
+        assert_eq!(usage.sent_for(MessageType::NewBlock), block_size);
+        assert_eq!(usage.sent_for(MessageType::Ping), 2 * ping_size);
+        assert_eq!(usage.sent_for(MessageType::NewTransaction), 0);
+        assert_eq!(usage.received_for(MessageType::NewBlock), 0);
+
+        let stats = node.get_stats().await;
+        assert_eq!(stats.total_bytes_sent, block_size + 2 * ping_size);
+        assert_eq!(stats.bytes_sent_by_type[&MessageType::NewBlock], block_size);
+    }
+
+    #[tokio::test]
+    async fn test_broadcast_charges_every_peer() {
+        let node = test_node();
+        let a: SocketAddr = "127.0.0.1:8333".parse().unwrap();
+        let b: SocketAddr = "127.0.0.2:8333".parse().unwrap();
+        add_test_peer(&node, a).await;
+        add_test_peer(&node, b).await;
+
+        node.broadcast(MessageType::NewTransaction, vec![7; 200]).await;
+
+        let bandwidth = node.peer_bandwidth().await;
+        assert!(bandwidth[&a].sent_for(MessageType::NewTransaction) > 200);
+        assert_eq!(
+            bandwidth[&a].sent_for(MessageType::NewTransaction),
+            bandwidth[&b].sent_for(MessageType::NewTransaction)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bandwidth_hog_is_limited() {
+        let node = test_node();
+        let hog: SocketAddr = "127.0.0.1:8333".parse().unwrap();
+        let quiet: SocketAddr = "127.0.0.2:8333".parse().unwrap();
+        add_test_peer(&node, hog).await;
+        add_test_peer(&node, quiet).await;
+
+        let small = P2PMessage::new(MessageType::NewTransaction, vec![1; 1000]);
+        assert!(node.record_received(quiet, &small).await);
+
+        let large = P2PMessage::new(MessageType::BlockResponse, vec![0; 1_000_000]);
+        let mut accepted = 0;
+        while node.record_received(hog, &large).await {
+            accepted += 1;
+            assert!(accepted < 10, "hog was never limited");
+        }
+
+        // Below the floor budget, so still accepted
+        assert_eq!(accepted, 4);
+        assert!(node.record_received(quiet, &small).await);
+        assert!(node.peer_bandwidth().await[&hog].received_for(MessageType::BlockResponse) > MIN_BANDWIDTH_BUDGET);
+    }
+
+    #[tokio::test]
+    async fn test_read_loop_charges_received_bandwidth() {
+        let node = test_node();
+        let addr: SocketAddr = "127.0.0.1:8333".parse().unwrap();
+        add_test_peer(&node, addr).await;
+
+        let ping = P2PMessage::new(MessageType::Ping, vec![1; 8]);
+        let frame = ping.serialize().unwrap();
+        let mut wire = (frame.len() as u32).to_le_bytes().to_vec();
+        wire.extend_from_slice(&frame);
+
+        P2PNode::read_loop(
+            &wire[..],
+            addr,
+            &node.peers,
+            &node.propagation,
+            &node.blockchain,
+            &node.mempool,
+            &node.database,
+        )
+        .await;
+
+        let usage = &node.peer_bandwidth().await[&addr];
+        assert_eq!(usage.received_for(MessageType::Ping), ping.wire_size());
+    }
+
+    #[tokio::test]
+    async fn test_relayed_block_recorded_from_read_loop() {
+        let node = test_node();
+        let first: SocketAddr = "127.0.0.1:8333".parse().unwrap();
+        let second: SocketAddr = "127.0.0.2:8333".parse().unwrap();
+        add_test_peer(&node, first).await;
+        add_test_peer(&node, second).await;
+
+        let block = P2PMessage::new(MessageType::NewBlock, vec![5; 64]);
+        let frame = block.serialize().unwrap();
+        let mut wire = (frame.len() as u32).to_le_bytes().to_vec();
+        wire.extend_from_slice(&frame);
+
+        // The first relay starts the clock, the second is an acknowledgement
+        for addr in [first, second] {
+            P2PNode::read_loop(
+                &wire[..],
+                addr,
+                &node.peers,
+                &node.propagation,
+                &node.blockchain,
+                &node.mempool,
+                &node.database,
+            )
+            .await;
+        }
+
+        let stats = node.get_stats().await;
+        let samples: u64 = stats.block_propagation.block_latency.iter().map(|h| h.count).sum();
+        assert_eq!(samples, 1);
+    }
 }
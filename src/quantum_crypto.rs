@@ -27,6 +27,17 @@ pub fn generate_keypair() -> (String, String) {
     (public_key, private_key)
 }
 
+/// Dilithium2 keypair generated from `seed` rather than the OS RNG, so crypto
+/// tests are reproducible and failures can be replayed. Only in test builds
+/// and under the `test-keys` feature; production keys always come from
+/// `generate_keypair`.
+#[cfg(any(test, feature = "test-keys"))]
+pub fn generate_keypair_seeded(seed: u64) -> (String, String) {
+    let expanded = blake3::hash(&seed.to_le_bytes());
+    let (pk, sk) = qc_crypto::keypair_from_seed(expanded.as_bytes()).expect("seeded Dilithium2 keypair");
+    (hex::encode(pk.as_bytes()), hex::encode(sk.as_bytes()))
+}
+
 /// Generate a QuantumCoin address from a public key
 pub fn public_key_to_address(public_key: &str) -> String {
     let pk_bytes = hex::decode(public_key).expect("Invalid public key hex");
@@ -254,9 +265,21 @@ mod tests {
         assert_ne!(pub_key, priv_key);
     }
 
+    #[test]
+    fn test_seeded_keypairs_reproducible() {
+        let (pub_key, priv_key) = generate_keypair_seeded(42);
+        assert_eq!(generate_keypair_seeded(42), (pub_key.clone(), priv_key.clone()));
+        assert_ne!(generate_keypair_seeded(43).0, pub_key);
+
+        // Seeded keys are ordinary Dilithium2 keys
+        let signature = sign_message(&priv_key, b"reproducible").unwrap();
+        assert_eq!(signature.public_key, pub_key);
+        assert!(verify_signature(&signature, b"reproducible"));
+    }
+
     #[test]
     fn test_address_generation() {
-        let (pub_key, _) = generate_keypair();
+        let (pub_key, _) = generate_keypair_seeded(1);
         let address = public_key_to_address(&pub_key);
         assert!(!address.is_empty());
         assert!(address.starts_with('Q')); // QuantumCoin addresses start with Q
@@ -264,7 +287,7 @@ mod tests {
 
     #[test]
     fn test_sign_and_verify() {
-        let (pub_key, priv_key) = generate_keypair();
+        let (pub_key, priv_key) = generate_keypair_seeded(2);
         let message = b"Hello, Quantum World!";
         
         let signature = sign_message(&priv_key, message).unwrap();
@@ -48,7 +48,7 @@ pub enum ReversalReason {
     QuantumThreat,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ReversalStatus {
     Pending,
     Approved,
@@ -186,7 +186,7 @@ impl RevStop {
     }
 
     async fn calculate_risk_score(&self, transaction: &SignedTransaction) -> f64 {
-        let mut risk_score = 0.0;
+        let mut risk_score: f64 = 0.0;
 
         // Check against fraud patterns
         for pattern in &self.fraud_patterns {
@@ -256,7 +256,7 @@ impl RevStop {
     }
 
     async fn evaluate_fraud_pattern(&self, transaction: &SignedTransaction, pattern: &FraudPattern) -> f64 {
-        let mut pattern_score = 0.0;
+        let mut pattern_score: f64 = 0.0;
 
         for rule in &pattern.detection_rules {
             match rule {
@@ -528,6 +528,19 @@ impl RevStop {
         self.active_reversals.values().collect()
     }
 
+    /// Whether an open reversal of `transaction_id` still holds the funds it
+    /// paid to `owner`. Such outputs may be clawed back, so the owner cannot
+    /// spend them until the reversal is rejected, executed or expires.
+    pub fn blocks_spend(&self, transaction_id: &str, owner: &str) -> bool {
+        let now = Utc::now();
+        self.active_reversals.values().any(|order| {
+            order.original_transaction_id == transaction_id
+                && order.to_address == owner
+                && matches!(order.status, ReversalStatus::Pending | ReversalStatus::Approved)
+                && now <= order.expires_at
+        })
+    }
+
     pub fn get_stats(&self) -> &RevStopStats {
         &self.stats
     }
@@ -649,4 +662,25 @@ mod tests {
         assert!(!reversal_id.is_empty());
         assert!(revstop.get_reversal_status(&reversal_id).is_some());
     }
+
+    #[tokio::test]
+    async fn test_open_reversal_blocks_recipient_spend() {
+        let mut revstop = RevStop::new();
+        let tx = create_test_transaction();
+        revstop.analyze_transaction(&tx).await.unwrap();
+        assert!(!revstop.blocks_spend(&tx.id, "test_address"));
+
+        let reversal_id = revstop.request_reversal(
+            tx.id.clone(),
+            ReversalReason::UserRequested,
+            "test_user".to_string(),
+        ).await.unwrap();
+        let recipient = revstop.get_reversal_status(&reversal_id).unwrap().to_address.clone();
+
+        assert!(revstop.blocks_spend(&tx.id, &recipient));
+        assert!(!revstop.blocks_spend("other_tx", &recipient));
+
+        revstop.reject_reversal(&reversal_id).unwrap();
+        assert!(!revstop.blocks_spend(&tx.id, &recipient));
+    }
 }
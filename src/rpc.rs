@@ -1,11 +1,14 @@
 use anyhow::{Result, Context};
 use axum::{
-    extract::{Path, Query, State},
+    extract::{ConnectInfo, Path, Query, Request, State},
     http::StatusCode,
-    response::Json,
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
     routing::{get, post},
     Router,
 };
+use qc_validation::sync_status::{sync_status, SyncStatus};
+use qc_validation::DEFAULT_MIN_SYNC_PEERS;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::net::SocketAddr;
@@ -20,8 +23,10 @@ use crate::{
     blockchain::Blockchain,
     database::BlockchainDatabase,
     mempool::Mempool,
-    p2p::{P2PNode, NetworkStats},
+    mining::{Miner, MiningStatus},
+    p2p::{BandwidthUsage, P2PNode, NetworkStats},
     quantum_crypto::{generate_keypair, public_key_to_address},
+    revstop::RevStop,
     transaction::SignedTransaction,
     utxo::UTXOSet,
 };
@@ -42,6 +47,15 @@ pub struct RpcServer {
     
     /// P2P node
     p2p_node: Arc<P2PNode>,
+    
+    /// Local miner, if this node mines
+    miner: Option<Arc<Miner>>,
+    
+    /// RevStop reversals, used to flag outputs the owner cannot spend yet
+    revstop: Option<Arc<RwLock<RevStop>>>,
+    
+    /// Peers that must report our tip before `/status` calls sync complete
+    min_sync_peers: usize,
 }
 
 /// Shared application state
@@ -51,6 +65,9 @@ pub struct AppState {
     pub database: Arc<RwLock<Option<BlockchainDatabase>>>,
     pub mempool: Arc<RwLock<Mempool>>,
     pub p2p_node: Arc<P2PNode>,
+    pub miner: Option<Arc<Miner>>,
+    pub revstop: Option<Arc<RwLock<RevStop>>>,
+    pub min_sync_peers: usize,
 }
 
 /// API Response wrapper
@@ -197,6 +214,14 @@ pub struct SendTransactionRequest {
     pub raw_transaction: String, // Hex encoded
 }
 
+/// `prioritisetransaction` request: virtual fee, in satoshis, to add to a
+/// pooled transaction for ordering and mining selection. May be negative.
+#[derive(Debug, Deserialize)]
+pub struct PrioritiseTransactionRequest {
+    pub txid: String,
+    pub fee_delta: i64,
+}
+
 impl RpcServer {
     pub fn new(
         addr: SocketAddr,
@@ -211,9 +236,31 @@ impl RpcServer {
             database,
             mempool,
             p2p_node,
+            miner: None,
+            revstop: None,
+            min_sync_peers: DEFAULT_MIN_SYNC_PEERS,
         }
     }
     
+    /// Expose a local miner's state over RPC
+    pub fn with_miner(mut self, miner: Arc<Miner>) -> Self {
+        self.miner = Some(miner);
+        self
+    }
+    
+    /// Report RevStop holds on address outputs
+    pub fn with_revstop(mut self, revstop: Arc<RwLock<RevStop>>) -> Self {
+        self.revstop = Some(revstop);
+        self
+    }
+    
+    /// Peers that must agree on our tip before sync counts as complete,
+    /// normally the spec's `network.min_sync_peers`
+    pub fn with_min_sync_peers(mut self, min_sync_peers: usize) -> Self {
+        self.min_sync_peers = min_sync_peers;
+        self
+    }
+    
     /// Start the RPC server
     pub async fn start(&self) -> Result<()> {
         info!("Starting RPC server on {}", self.addr);
@@ -223,8 +270,17 @@ impl RpcServer {
             database: Arc::clone(&self.database),
             mempool: Arc::clone(&self.mempool),
             p2p_node: Arc::clone(&self.p2p_node),
+            miner: self.miner.clone(),
+            revstop: self.revstop.clone(),
+            min_sync_peers: self.min_sync_peers,
         };
         
+        // Operator-only: served to loopback clients and left out of the CORS
+        // layer, so a web page in the operator's browser can't reach it either
+        let operator = Router::new()
+            .route("/mempool/prioritise", post(prioritise_transaction))
+            .route_layer(middleware::from_fn(require_loopback));
+
         let app = Router::new()
             // Node information
             .route("/", get(get_node_info))
@@ -254,10 +310,13 @@ impl RpcServer {
             
             // Network endpoints
             .route("/network", get(get_network_info))
+            .route("/network/metrics", get(get_network_metrics))
             .route("/peers", get(get_peers))
+            .route("/peers/bandwidth", get(get_peer_bandwidth))
             
             // Mining endpoints
             .route("/mining", get(get_mining_info))
+            .route("/mining/status", get(get_mining_status))
             
             // Utility endpoints
             .route("/utils/address/generate", post(generate_address))
@@ -275,6 +334,7 @@ impl RpcServer {
                         .allow_headers(Any)
                     ),
             )
+            .merge(operator)
             .with_state(state);
         
         let listener = tokio::net::TcpListener::bind(self.addr).await
@@ -282,7 +342,7 @@ impl RpcServer {
             
         info!("RPC server listening on {}", self.addr);
         
-        axum::serve(listener, app)
+        axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
             .await
             .context("RPC server error")?;
             
@@ -290,6 +350,16 @@ impl RpcServer {
     }
 }
 
+/// Turn away operator requests that don't come from this machine
+async fn require_loopback(ConnectInfo(peer): ConnectInfo<SocketAddr>, request: Request, next: Next) -> Response {
+    if peer.ip().is_loopback() {
+        next.run(request).await
+    } else {
+        let body = ApiResponse::<()>::error("operator endpoints are only served to localhost".to_string());
+        (StatusCode::FORBIDDEN, Json(body)).into_response()
+    }
+}
+
 // RPC endpoint handlers
 
 /// Get node information
@@ -324,7 +394,10 @@ async fn get_node_status(State(state): State<AppState>) -> Json<ApiResponse<Hash
     status.insert("chain_height".to_string(), serde_json::json!(blockchain.chain.len()));
     status.insert("mempool_size".to_string(), serde_json::json!(mempool.size()));
     status.insert("connected_peers".to_string(), serde_json::json!(network_stats.connected_peers));
-    status.insert("is_syncing".to_string(), serde_json::json!(false)); // TODO: Implement sync status
+    // Caught up only counts once enough peers report the same tip
+    let sync = sync_status(blockchain.chain.len() as u64, &state.p2p_node.peer_heights().await, state.min_sync_peers);
+    status.insert("is_syncing".to_string(), serde_json::json!(sync == SyncStatus::Syncing));
+    status.insert("sync_status".to_string(), serde_json::json!(sync));
     
     Json(ApiResponse::success(status))
 }
@@ -442,9 +515,9 @@ async fn get_mempool_info(State(state): State<AppState>) -> Json<ApiResponse<Mem
     
     let mempool_info = MempoolInfo {
         size: stats.transaction_count,
-        bytes: 0, // TODO: Calculate total bytes
-        usage: stats.transaction_count,
-        max_mempool: 10000, // TODO: Get from config
+        bytes: stats.total_bytes,
+        usage: stats.total_bytes,
+        max_mempool: mempool.max_bytes(),
         mempoolmin_fee: stats.min_fee_per_byte,
         unbroadcast_count: 0, // TODO: Track unbroadcast transactions
     };
@@ -475,6 +548,16 @@ async fn get_network_info(State(state): State<AppState>) -> Json<ApiResponse<Net
     Json(ApiResponse::success(stats))
 }
 
+/// Block propagation latency histograms for Prometheus scraping
+async fn get_network_metrics(State(state): State<AppState>) -> String {
+    state.p2p_node.propagation_metrics().await
+}
+
+/// Get per-peer byte counters, split by message type
+async fn get_peer_bandwidth(State(state): State<AppState>) -> Json<ApiResponse<HashMap<SocketAddr, BandwidthUsage>>> {
+    Json(ApiResponse::success(state.p2p_node.peer_bandwidth().await))
+}
+
 /// Generate new address
 async fn generate_address() -> Json<ApiResponse<HashMap<String, String>>> {
     let (public_key, private_key) = generate_keypair();
@@ -497,6 +580,22 @@ async fn health_check() -> Json<ApiResponse<HashMap<String, String>>> {
     Json(ApiResponse::success(health))
 }
 
+/// Bump or lower a pooled transaction's mining priority without changing its
+/// fee; returns the transaction's accumulated fee delta
+async fn prioritise_transaction(
+    State(state): State<AppState>,
+    Json(request): Json<PrioritiseTransactionRequest>,
+) -> Json<ApiResponse<i64>> {
+    let mut mempool = state.mempool.write().await;
+    match mempool.prioritise_transaction(&request.txid, request.fee_delta) {
+        Ok(total) => {
+            info!("Prioritised {} by {} sats (total {})", request.txid, request.fee_delta, total);
+            Json(ApiResponse::success(total))
+        }
+        Err(e) => Json(ApiResponse::error(e.to_string())),
+    }
+}
+
 // TODO: Implement remaining endpoints
 async fn get_transactions(
     State(_state): State<AppState>,
@@ -552,6 +651,13 @@ async fn get_mining_info(State(_state): State<AppState>) -> Json<ApiResponse<Min
     Json(ApiResponse::error("Not implemented yet".to_string()))
 }
 
+async fn get_mining_status(State(state): State<AppState>) -> Json<ApiResponse<MiningStatus>> {
+    match &state.miner {
+        Some(miner) => Json(ApiResponse::success(miner.mining_status().await)),
+        None => Json(ApiResponse::error("Mining not enabled on this node".to_string())),
+    }
+}
+
 async fn estimate_fee(State(_state): State<AppState>) -> Json<ApiResponse<f64>> {
     Json(ApiResponse::error("Not implemented yet".to_string()))
 }
@@ -576,6 +682,23 @@ mod tests {
         assert!(body.data.is_some());
     }
     
+    #[tokio::test]
+    async fn test_operator_routes_refuse_remote_clients() {
+        use axum::extract::connect_info::MockConnectInfo;
+
+        let app = |peer: &str| {
+            Router::new()
+                .route("/health", get(health_check))
+                .route_layer(middleware::from_fn(require_loopback))
+                .layer(MockConnectInfo(peer.parse::<SocketAddr>().unwrap()))
+        };
+
+        let server = TestServer::new(app("203.0.113.7:5000")).unwrap();
+        assert_eq!(server.get("/health").await.status_code(), 403);
+        let server = TestServer::new(app("127.0.0.1:5000")).unwrap();
+        assert_eq!(server.get("/health").await.status_code(), 200);
+    }
+    
     #[tokio::test]
     async fn test_generate_address() {
         let app = Router::new()
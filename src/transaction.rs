@@ -5,6 +5,12 @@ use anyhow::{Result, anyhow};
 
 pub use crate::blockchain::Transaction;
 
+/// How much more a byte of base data weighs than a byte of witness data
+pub const WITNESS_SCALE_FACTOR: usize = 4;
+
+/// Highest input sequence number that opts a transaction in to replacement
+pub const MAX_RBF_SEQUENCE: u32 = 0xffff_fffd;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionInput {
     pub previous_output: String,
@@ -97,6 +103,34 @@ impl SignedTransaction {
         Ok(input_value - output_value)
     }
 
+    /// Whether any input opts the transaction in to replace-by-fee
+    pub fn signals_rbf(&self) -> bool {
+        self.inputs.iter().any(|input| input.sequence <= MAX_RBF_SEQUENCE)
+    }
+
+    /// Bytes of signature data: input scripts, signature and public key
+    pub fn witness_size(&self) -> usize {
+        let scripts: usize = self.inputs.iter().map(|input| input.script_sig.len()).sum();
+        scripts + self.signature.len() + self.public_key.len()
+    }
+
+    /// Serialized size with the witness data stripped
+    pub fn base_size(&self) -> usize {
+        let mut stripped = self.clone();
+        for input in &mut stripped.inputs {
+            input.script_sig.clear();
+        }
+        stripped.signature.clear();
+        stripped.public_key.clear();
+        bincode::serialize(&stripped).map(|data| data.len()).unwrap_or(0)
+    }
+
+    /// Base bytes count `WITNESS_SCALE_FACTOR` times, witness bytes once, so
+    /// large post-quantum signatures are not charged like payload
+    pub fn weight(&self) -> usize {
+        self.base_size() * WITNESS_SCALE_FACTOR + self.witness_size()
+    }
+
     pub fn sign(&mut self, private_key: &str) -> Result<()> {
         let message = self.get_signing_message();
         
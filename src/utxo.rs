@@ -3,6 +3,9 @@ use serde::{Deserialize, Serialize};
 use anyhow::{Result, anyhow};
 use crate::transaction::{SignedTransaction, TransactionOutput};
 
+/// Blocks a coinbase output must be buried under before it can be spent
+pub const COINBASE_MATURITY: u64 = 100;
+
 /// Unspent Transaction Output
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UTXO {
@@ -54,6 +57,21 @@ impl UTXO {
     }
 }
 
+/// An address's unspent output as shown to wallets, with whether it can be
+/// spent right now and, if not, why
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UtxoStatus {
+    pub outpoint: String,
+    pub amount: u64,
+    pub confirmations: u64,
+    pub is_coinbase: bool,
+    /// False for coinbase outputs younger than the maturity depth
+    pub mature: bool,
+    /// Held by an open RevStop reversal against the owner
+    pub revstop_locked: bool,
+    pub spendable: bool,
+}
+
 /// UTXO Set manager for tracking unspent outputs
 #[derive(Debug, Clone, Default)]
 pub struct UTXOSet {
@@ -107,6 +125,29 @@ impl UTXOSet {
         self.utxos.get(outpoint)
     }
 
+    /// Fee paid by `tx`: its inputs, valued from this set, minus its outputs.
+    /// Fails if an input is not in the set.
+    pub fn transaction_fee(&self, tx: &SignedTransaction) -> Result<u64> {
+        let input_values = tx.inputs
+            .iter()
+            .filter_map(|input| {
+                let utxo = self.get_utxo(&input.previous_output)?;
+                Some((input.previous_output.clone(), utxo.amount))
+            })
+            .collect();
+        tx.calculate_fee(&input_values)
+    }
+
+    /// Value of every unspent output, keyed by outpoint
+    pub fn outpoint_values(&self) -> HashMap<String, u64> {
+        self.utxos.iter().map(|(outpoint, utxo)| (outpoint.clone(), utxo.amount)).collect()
+    }
+
+    /// Address paid by every unspent output, keyed by outpoint
+    pub fn outpoint_owners(&self) -> HashMap<String, String> {
+        self.utxos.iter().map(|(outpoint, utxo)| (outpoint.clone(), utxo.address.clone())).collect()
+    }
+
     /// Check if a UTXO exists
     pub fn contains_utxo(&self, outpoint: &str) -> bool {
         self.utxos.contains_key(outpoint)
@@ -127,6 +168,44 @@ impl UTXOSet {
             .collect()
     }
 
+    /// Describe every unspent output of `address`, oldest first. An output
+    /// is spendable once it is mature, has at least `min_confirmations` and
+    /// `is_locked` does not report it as held by RevStop.
+    pub fn get_utxo_statuses<F>(
+        &self,
+        address: &str,
+        min_confirmations: u64,
+        coinbase_maturity: u64,
+        is_locked: F,
+    ) -> Vec<UtxoStatus>
+    where
+        F: Fn(&UTXO) -> bool,
+    {
+        let mut utxos = self.get_utxos_for_address(address);
+        utxos.sort_by(|a, b| {
+            a.block_height.cmp(&b.block_height)
+                .then_with(|| a.tx_id.cmp(&b.tx_id))
+                .then_with(|| a.output_index.cmp(&b.output_index))
+        });
+
+        utxos.into_iter().map(|utxo| {
+            let mut current = utxo.clone();
+            current.update_confirmations(self.current_height);
+            let mature = current.is_mature(coinbase_maturity);
+            let revstop_locked = is_locked(utxo);
+
+            UtxoStatus {
+                outpoint: utxo.get_outpoint(),
+                amount: utxo.amount,
+                confirmations: current.confirmations,
+                is_coinbase: utxo.is_coinbase,
+                mature,
+                revstop_locked,
+                spendable: mature && !revstop_locked && current.confirmations >= min_confirmations,
+            }
+        }).collect()
+    }
+
     /// Calculate balance for an address
     pub fn get_balance(&self, address: &str) -> u64 {
         self.utxos.values()
@@ -470,4 +549,84 @@ mod tests {
         assert_eq!(stats.unique_addresses, 10);
         assert_eq!(stats.coinbase_utxos, 0);
     }
+
+    #[test]
+    fn test_utxo_statuses_over_synthetic_chain() {
+        let owner = "qtc1qowner0000000000000000000000000";
+        let mut utxo_set = UTXOSet::new();
+
+        let coinbase = |height: u64| SignedTransaction {
+            id: format!("coinbase_{}", height),
+            version: 1,
+            inputs: vec![],
+            outputs: vec![TransactionOutput {
+                value: 5000000000,
+                script_pubkey: vec![],
+                address: owner.to_string(),
+            }],
+            lock_time: 0,
+            timestamp: Utc::now(),
+            signature: String::new(),
+            public_key: String::new(),
+        };
+
+        // Block 1 pays the owner a coinbase, block 50 another; block 60 spends
+        // the first coinbase into a payment and change back to the owner
+        utxo_set.apply_transaction(&coinbase(1), 1, true).unwrap();
+        utxo_set.apply_transaction(&coinbase(50), 50, true).unwrap();
+        utxo_set.set_height(101);
+        let spend = SignedTransaction {
+            id: "spend".to_string(),
+            version: 1,
+            inputs: vec![TransactionInput {
+                previous_output: "coinbase_1:0".to_string(),
+                script_sig: vec![],
+                sequence: 0xffffffff,
+            }],
+            outputs: vec![
+                TransactionOutput {
+                    value: 1000000000,
+                    script_pubkey: vec![],
+                    address: "qtc1qother0000000000000000000000000".to_string(),
+                },
+                TransactionOutput {
+                    value: 3999000000,
+                    script_pubkey: vec![],
+                    address: owner.to_string(),
+                },
+            ],
+            lock_time: 0,
+            timestamp: Utc::now(),
+            signature: String::new(),
+            public_key: String::new(),
+        };
+        utxo_set.apply_transaction(&spend, 101, false).unwrap();
+        utxo_set.apply_transaction(&coinbase(102), 102, true).unwrap();
+        utxo_set.set_height(102);
+
+        let statuses = utxo_set.get_utxo_statuses(owner, 1, COINBASE_MATURITY, |_| false);
+        let outpoints: Vec<&str> = statuses.iter().map(|s| s.outpoint.as_str()).collect();
+        assert_eq!(outpoints, vec!["coinbase_50:0", "spend:1", "coinbase_102:0"]);
+
+        // 53 confirmations is short of coinbase maturity
+        assert_eq!(statuses[0].confirmations, 53);
+        assert!(!statuses[0].mature && !statuses[0].spendable);
+
+        // Change is not coinbase, so spendable as soon as it confirms
+        assert_eq!(statuses[1].amount, 3999000000);
+        assert_eq!(statuses[1].confirmations, 2);
+        assert!(statuses[1].mature && statuses[1].spendable);
+
+        assert_eq!(statuses[2].confirmations, 1);
+        assert!(!statuses[2].spendable);
+
+        // An open reversal of the spend holds its change
+        let locked = utxo_set.get_utxo_statuses(owner, 1, COINBASE_MATURITY, |utxo| utxo.tx_id == "spend");
+        assert!(locked[1].revstop_locked && !locked[1].spendable);
+        assert!(!locked[0].revstop_locked);
+
+        // A higher confirmation target holds back the young change output
+        let strict = utxo_set.get_utxo_statuses(owner, 6, COINBASE_MATURITY, |_| false);
+        assert!(strict.iter().all(|s| !s.spendable));
+    }
 }
@@ -1,19 +1,21 @@
 use anyhow::Result;
-use std::sync::{Arc, atomic::{AtomicU64, Ordering}};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 use tokio::time::timeout;
 use rayon::prelude::*;
 use proptest::prelude::*;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::HashMap;
 
 use quantumcoin::{
     blockchain::Blockchain,
     database::{BlockchainDatabase, DatabaseConfig},
-    mempool::Mempool,
+    mempool::{Mempool, MempoolPolicy},
     p2p::P2PNode,
     transaction::{SignedTransaction, TransactionInput, TransactionOutput},
     utxo::{UTXOSet, UTXO},
-    quantum_crypto::{generate_keypair, sign_message, verify_signature},
+    quantum_crypto::{generate_keypair_seeded, sign_message, verify_signature},
     economics::EconomicsEngine,
     genesis::create_mainnet_genesis,
 };
@@ -78,77 +80,132 @@ mod extreme_stress_tests {
         Ok(())
     }
     
-    /// Test 2: Concurrent Transaction Flood
+    /// What a generated flood transaction should do to the mempool
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    enum FloodKind {
+        Valid,
+        /// Spends an outpoint that was never funded
+        UnknownInput,
+        /// Pays out more than its input holds
+        Overspend,
+        /// Conflicts with a pooled transaction at a lower fee rate
+        DoubleSpend,
+        /// Resubmits a pooled transaction
+        Duplicate,
+    }
+
+    /// Seed for the flood mix; set `QC_FLOOD_SEED` to replay a failing run
+    const DEFAULT_FLOOD_SEED: u64 = 0x5eed_f100d;
+    const FLOOD_FUNDING: u64 = 100_000_000;
+    const FLOOD_FEE: u64 = 10_000;
+
+    fn flood_seed() -> u64 {
+        std::env::var("QC_FLOOD_SEED").ok().and_then(|seed| seed.parse().ok()).unwrap_or(DEFAULT_FLOOD_SEED)
+    }
+
+    fn flood_output(value: u64, i: usize) -> TransactionOutput {
+        TransactionOutput {
+            value,
+            script_pubkey: vec![0x76, 0xa9, 0x14],
+            address: format!("qtc1qflood{:035}", i % 100),
+        }
+    }
+
+    fn flood_tx(outpoint: &str, value: u64, i: usize) -> SignedTransaction {
+        SignedTransaction::new(
+            vec![TransactionInput {
+                previous_output: outpoint.to_string(),
+                script_sig: vec![],
+                sequence: 0xffffffff,
+            }],
+            vec![flood_output(value, i)],
+            0,
+        )
+    }
+
+    /// Test 2: Transaction Flood
+    ///
+    /// Floods the mempool with a seeded mix of valid and invalid transactions
+    /// and checks that exactly the valid ones get in.
     #[tokio::test]
     async fn test_transaction_flood_attack() -> Result<()> {
         println!("🔥 STRESS TEST: Transaction Flood Attack");
         
-        let mut mempool = Mempool::new(50_000); // Large mempool
-        let tx_count = Arc::new(AtomicU64::new(0));
-        let error_count = Arc::new(AtomicU64::new(0));
+        let seed = flood_seed();
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut utxos = UTXOSet::new();
+        let mut pooled: Vec<SignedTransaction> = Vec::new();
+        let mut transactions = Vec::new();
         
         let start_time = Instant::now();
         
-        // Create 10,000 transactions concurrently
-        let handles: Vec<_> = (0..10_000).map(|i| {
-            let tx_count = Arc::clone(&tx_count);
-            let error_count = Arc::clone(&error_count);
+        // Create 10,000 transactions, about 60% of them valid
+        for i in 0..10_000 {
+            let funding = format!("flood_funding_{}", i);
+            let outpoint = format!("{}:0", funding);
+            let mut kind = match rng.gen_range(0..10) {
+                0..=5 => FloodKind::Valid,
+                6 => FloodKind::UnknownInput,
+                7 => FloodKind::Overspend,
+                8 => FloodKind::DoubleSpend,
+                _ => FloodKind::Duplicate,
+            };
+            if pooled.is_empty() && matches!(kind, FloodKind::DoubleSpend | FloodKind::Duplicate) {
+                kind = FloodKind::Valid;
+            }
             
-            tokio::spawn(async move {
-                let tx = SignedTransaction {
-                    id: format!("flood_tx_{}", i),
-                    version: 1,
-                    inputs: vec![
-                        TransactionInput {
-                            previous_output: format!("input_{}:0", i),
-                            script_sig: vec![0x47, 0x30, 0x44], // Dummy signature
-                            sequence: 0xffffffff,
-                        }
-                    ],
-                    outputs: vec![
-                        TransactionOutput {
-                            value: 100_000_000 + (i % 1000) as u64,
-                            script_pubkey: vec![0x76, 0xa9, 0x14],
-                            address: format!("qtc1qflood{:035}", i % 100),
-                        }
-                    ],
-                    lock_time: 0,
-                    timestamp: chrono::Utc::now(),
-                    signature: format!("flood_sig_{}", i),
-                    public_key: format!("flood_pub_{}", i),
-                };
-                
-                (i, tx)
-            })
-        }).collect();
-        
-        // Collect all transactions
-        let mut transactions = Vec::new();
-        for handle in handles {
-            let (i, tx) = handle.await.unwrap();
-            transactions.push(tx);
+            let tx = match kind {
+                FloodKind::Valid => {
+                    utxos.add_utxo(UTXO::new(funding, 0, &flood_output(FLOOD_FUNDING, i), 1, false))?;
+                    let tx = flood_tx(&outpoint, FLOOD_FUNDING - FLOOD_FEE, i);
+                    pooled.push(tx.clone());
+                    tx
+                }
+                FloodKind::UnknownInput => flood_tx(&outpoint, FLOOD_FUNDING - FLOOD_FEE, i),
+                FloodKind::Overspend => {
+                    utxos.add_utxo(UTXO::new(funding, 0, &flood_output(FLOOD_FUNDING, i), 1, false))?;
+                    flood_tx(&outpoint, FLOOD_FUNDING + 1, i)
+                }
+                FloodKind::DoubleSpend => {
+                    let original = &pooled[rng.gen_range(0..pooled.len())];
+                    flood_tx(&original.inputs[0].previous_output, FLOOD_FUNDING - FLOOD_FEE / 2, i)
+                }
+                FloodKind::Duplicate => pooled[rng.gen_range(0..pooled.len())].clone(),
+            };
+            transactions.push((kind, tx));
         }
         
+        let mut mempool = Mempool::new(MempoolPolicy { max_count: 50_000, ..MempoolPolicy::default() });
+        mempool.set_utxo_set(&utxos);
+        
         // Add transactions to mempool as fast as possible
+        let mut breakdown: HashMap<FloodKind, (usize, usize)> = HashMap::new();
         let add_start = Instant::now();
-        for tx in transactions {
-            match mempool.add_transaction(tx) {
-                Ok(_) => { tx_count.fetch_add(1, Ordering::Relaxed); }
-                Err(_) => { error_count.fetch_add(1, Ordering::Relaxed); }
-            }
+        for (kind, tx) in transactions {
+            let tx_id = tx.id.clone();
+            let result = mempool.add_transaction(tx);
+            assert_eq!(
+                result.is_ok(),
+                kind == FloodKind::Valid,
+                "seed {}: {:?} transaction {} {}",
+                seed,
+                kind,
+                tx_id,
+                result.err().map(|e| e.to_string()).unwrap_or_else(|| "was accepted".to_string())
+            );
+            let (accepted, rejected) = breakdown.entry(kind).or_default();
+            if kind == FloodKind::Valid { *accepted += 1 } else { *rejected += 1 }
         }
         
         let total_time = start_time.elapsed();
         let add_time = add_start.elapsed();
         
-        let final_tx_count = tx_count.load(Ordering::Relaxed);
-        let final_error_count = error_count.load(Ordering::Relaxed);
-        
-        println!("⚡ Transaction flood results:");
+        println!("⚡ Transaction flood results (seed {}):", seed);
         println!("   Total time: {:?}", total_time);
         println!("   Add time: {:?}", add_time);
-        println!("   Successful: {}", final_tx_count);
-        println!("   Rejected: {}", final_error_count);
+        for (kind, (accepted, rejected)) in &breakdown {
+            println!("   {:?}: {} accepted, {} rejected", kind, accepted, rejected);
+        }
         println!("   Final mempool size: {}", mempool.size());
         
         // Verify mempool integrity
@@ -156,10 +213,17 @@ mod extreme_stress_tests {
         println!("📊 Mempool stats: avg_fee={:.6}, min_fee={:.6}, max_fee={:.6}", 
                  stats.avg_fee_per_byte, stats.min_fee_per_byte, stats.max_fee_per_byte);
         
+        // The mix covers every kind, and only the valid ones got in
+        for kind in [FloodKind::Valid, FloodKind::UnknownInput, FloodKind::Overspend, FloodKind::DoubleSpend, FloodKind::Duplicate] {
+            assert!(breakdown.contains_key(&kind), "seed {} generated no {:?} transactions", seed, kind);
+        }
+        assert_eq!(breakdown[&FloodKind::Valid], (pooled.len(), 0));
+        assert!(breakdown.iter().filter(|(kind, _)| **kind != FloodKind::Valid).all(|(_, (accepted, _))| *accepted == 0));
+        assert_eq!(mempool.size(), pooled.len());
+        assert!(pooled.iter().all(|tx| mempool.contains(&tx.id)));
+        
         // Performance assertions
         assert!(add_time < Duration::from_secs(5), "Transaction addition too slow");
-        assert!(final_tx_count > 0, "No transactions were added");
-        assert!(mempool.size() <= 50_000, "Mempool exceeded maximum size");
         
         println!("✅ TRANSACTION FLOOD STRESS TEST PASSED");
         Ok(())
@@ -175,8 +239,8 @@ mod extreme_stress_tests {
         
         // Parallel cryptographic operations
         let results: Vec<Result<bool>> = (0..iterations).into_par_iter().map(|i| {
-            // Generate keypair
-            let (public_key, private_key) = generate_keypair();
+            // Seeded keypair, so a failing iteration can be replayed
+            let (public_key, private_key) = generate_keypair_seeded(i as u64);
             
             // Create message
             let message = format!("stress_test_message_{}", i);
@@ -766,7 +830,7 @@ mod performance_benchmarks {
     async fn benchmark_transaction_validation() -> Result<()> {
         println!("📊 BENCHMARK: Transaction Validation");
         
-        let (public_key, private_key) = generate_keypair();
+        let (public_key, private_key) = generate_keypair_seeded(0);
         let message = b"benchmark_transaction_data";
         
         let iterations = 1000;